@@ -0,0 +1,84 @@
+//! Settings that make the viewer easier to use for more people: the language, the scale of
+//! the interface for high DPI displays, and a high contrast theme.
+use egui::{Color32, Stroke, Theme, Visuals};
+
+use crate::{
+    i18n::{self, tr, Language},
+    session::InterfaceSettings,
+};
+
+const UI_SCALES: [f32; 8] = [0.75, 1.0, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0];
+
+// White on black, with yellow to show what's hovered, selected or focused.
+fn high_contrast_visuals() -> Visuals {
+    let mut visuals = Visuals::dark();
+    visuals.override_text_color = Some(Color32::WHITE);
+    visuals.panel_fill = Color32::BLACK;
+    visuals.window_fill = Color32::BLACK;
+    visuals.extreme_bg_color = Color32::BLACK;
+    visuals.faint_bg_color = Color32::from_gray(32);
+    visuals.window_stroke = Stroke::new(1.0, Color32::WHITE);
+    visuals.hyperlink_color = Color32::YELLOW;
+    visuals.selection.bg_fill = Color32::from_rgb(0, 0, 160);
+    visuals.selection.stroke = Stroke::new(2.0, Color32::YELLOW);
+
+    let widgets = &mut visuals.widgets;
+    for state in [
+        &mut widgets.noninteractive,
+        &mut widgets.inactive,
+        &mut widgets.hovered,
+        &mut widgets.active,
+        &mut widgets.open,
+    ] {
+        state.bg_fill = Color32::BLACK;
+        state.weak_bg_fill = Color32::BLACK;
+        state.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+        state.fg_stroke = Stroke::new(1.5, Color32::WHITE);
+    }
+    widgets.hovered.bg_stroke = Stroke::new(2.0, Color32::YELLOW);
+    widgets.active.bg_stroke = Stroke::new(2.0, Color32::YELLOW);
+    widgets.open.bg_stroke = Stroke::new(2.0, Color32::YELLOW);
+    visuals
+}
+
+/// Use the interface settings for the next frames.
+pub(crate) fn apply_interface(ctx: &egui::Context, settings: &InterfaceSettings) {
+    ctx.set_zoom_factor(settings.ui_scale);
+    if settings.high_contrast {
+        ctx.set_visuals_of(Theme::Dark, high_contrast_visuals());
+        ctx.set_visuals_of(Theme::Light, high_contrast_visuals());
+    } else {
+        ctx.set_visuals_of(Theme::Dark, Visuals::dark());
+        ctx.set_visuals_of(Theme::Light, Visuals::light());
+    }
+}
+
+pub(crate) fn interface_settings_ui(ui: &mut egui::Ui, settings: &mut InterfaceSettings) {
+    let mut language = i18n::language();
+    egui::ComboBox::from_label(tr("Language"))
+        .selected_text(language.name())
+        .show_ui(ui, |ui| {
+            for option in Language::ALL {
+                ui.selectable_value(&mut language, option, option.name());
+            }
+        });
+    i18n::set_language(language);
+
+    // Fixed steps, as a slider would scale away from under the pointer while dragging it.
+    let percent = |scale: f32| format!("{:.0}%", scale * 100.0);
+    egui::ComboBox::from_label(tr("Interface scale"))
+        .selected_text(percent(settings.ui_scale))
+        .show_ui(ui, |ui| {
+            for scale in UI_SCALES {
+                ui.selectable_value(&mut settings.ui_scale, scale, percent(scale));
+            }
+        })
+        .response
+        .on_hover_text(tr("Also ctrl + plus and ctrl + minus"));
+
+    ui.checkbox(&mut settings.high_contrast, tr("High contrast"));
+    ui.label(tr(
+        "Tab moves between controls, and F6 between panels. Once the scene is focused, the \
+         arrow keys orbit, with shift they pan, and page up and down zoom.",
+    ));
+}
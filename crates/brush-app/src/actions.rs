@@ -0,0 +1,492 @@
+//! Named viewer actions, run from the command palette (Ctrl+P) and from scripts.
+//!
+//! An action is a name followed by its arguments as words, eg. `crop -1 -1 -1 1 1 1` or
+//! `export "my scene.ply"`, see [`COMMANDS`] for all of them. Scripts run actions with
+//! `run(name)` or `run(name, [args])`, so a workflow can be automated:
+//!
+//! ```text
+//! run("open", ["scan.ply"]);
+//! run("crop", [-2, -1, -2, 2, 3, 2]);
+//! run("export", ["cropped.ply"]);
+//! ```
+//!
+//! The viewer runs a script given with `--script`, or with the `script` action. Training
+//! scripts can run actions from their callbacks too, see `process_loop::script`.
+//!
+//! Actions run one after the other. Loading and exporting hold up the actions after them until
+//! they're done, so every step sees the result of the previous one.
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::Context;
+use brush_render::{bounding_box::BoundingBox, ColorSpace, RenderMode};
+
+use crate::data_source::DataSource;
+
+/// A viewer action as listed in the command palette.
+pub struct Command {
+    pub name: &'static str,
+    /// The arguments, optional ones in brackets.
+    pub args: &'static str,
+    pub description: &'static str,
+}
+
+impl Command {
+    /// Whether the action can't run without arguments.
+    pub fn needs_args(&self) -> bool {
+        self.args.starts_with('<')
+    }
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "open",
+        args: "[path or url]",
+        description: "Load a model or dataset, picked from disk when no path is given",
+    },
+    Command {
+        name: "open_folder",
+        args: "",
+        description: "Load a dataset folder picked from disk",
+    },
+    Command {
+        name: "cancel",
+        args: "",
+        description: "Stop loading and training, and free its memory",
+    },
+    Command {
+        name: "pause",
+        args: "[on|off]",
+        description: "Pause or resume training, and the animation of animated splats",
+    },
+    Command {
+        name: "live_update",
+        args: "[on|off]",
+        description: "Show the splats as they train",
+    },
+    Command {
+        name: "crop",
+        args: "<x0 y0 z0 x1 y1 z1>",
+        description: "Keep only the splats with their center in a box, given by two corners, \
+                      as an edit layer",
+    },
+    Command {
+        name: "save_edits",
+        args: "[path]",
+        description: "Save the edit layers, next to the model when no path is given",
+    },
+    Command {
+        name: "load_edits",
+        args: "[path]",
+        description: "Load edit layers, from a file picked on disk when no path is given",
+    },
+    Command {
+        name: "clear_edits",
+        args: "",
+        description: "Remove all edit layers",
+    },
+    Command {
+        name: "export",
+        args: "[path]",
+        description: "Export a ply, to a file picked on disk when no path is given",
+    },
+    Command {
+        name: "export_progressive",
+        args: "[path]",
+        description: "Export a ply ordered from coarse to fine detail",
+    },
+    Command {
+        name: "export_chunks",
+        args: "[path]",
+        description: "Export a zip of spatial chunks with collision boxes",
+    },
+    Command {
+        name: "export_octree",
+        args: "[path]",
+        description: "Export a zip of octree chunks with levels of detail",
+    },
+    Command {
+        name: "export_binary",
+        args: "[path]",
+        description: "Export a .splats model",
+    },
+    Command {
+        name: "render_mode",
+        args: "<color|shaded|points|ellipsoids>",
+        description: "Change how splats are drawn",
+    },
+    Command {
+        name: "color_space",
+        args: "<srgb|display_p3|hdr10>",
+        description: "Change the color space the splats are shown in",
+    },
+    Command {
+        name: "ground",
+        args: "[on|off]",
+        description: "Show the shadow catcher trained on the ground",
+    },
+    Command {
+        name: "uncertainty",
+        args: "[on|off]",
+        description: "Color splats by how many training views saw them",
+    },
+    Command {
+        name: "contribution",
+        args: "[on|off]",
+        description: "Color splats by how much they contribute to the current view",
+    },
+    Command {
+        name: "sort_by_distance",
+        args: "[on|off]",
+        description: "Sort splats by distance, to reduce popping in large scenes",
+    },
+    Command {
+        name: "order_independent",
+        args: "[on|off]",
+        description: "Blend splats without sorting them",
+    },
+    Command {
+        name: "dynamic_resolution",
+        args: "[on|off]",
+        description: "Lower the resolution while moving to keep up the frame rate",
+    },
+    Command {
+        name: "temporal_aa",
+        args: "[on|off]",
+        description: "Blend jittered frames while moving to reduce shimmering",
+    },
+    Command {
+        name: "foveated",
+        args: "[on|off]",
+        description: "Preview foveated rendering around the pointer",
+    },
+    Command {
+        name: "script",
+        args: "<path>",
+        description: "Run a script of viewer actions",
+    },
+];
+
+/// View settings that are switched on and off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewSetting {
+    LiveUpdate,
+    Ground,
+    Uncertainty,
+    Contribution,
+    SortByDistance,
+    OrderIndependent,
+    DynamicResolution,
+    TemporalAa,
+    Foveated,
+}
+
+const VIEW_SETTINGS: [(&str, ViewSetting); 9] = [
+    ("live_update", ViewSetting::LiveUpdate),
+    ("ground", ViewSetting::Ground),
+    ("uncertainty", ViewSetting::Uncertainty),
+    ("contribution", ViewSetting::Contribution),
+    ("sort_by_distance", ViewSetting::SortByDistance),
+    ("order_independent", ViewSetting::OrderIndependent),
+    ("dynamic_resolution", ViewSetting::DynamicResolution),
+    ("temporal_aa", ViewSetting::TemporalAa),
+    ("foveated", ViewSetting::Foveated),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Ply,
+    Progressive,
+    Chunks,
+    Octree,
+    Binary,
+}
+
+impl ExportFormat {
+    /// Name suggested when picking the file to export to.
+    pub fn default_name(self) -> &'static str {
+        match self {
+            Self::Ply => "export.ply",
+            Self::Progressive => "export_progressive.ply",
+            Self::Chunks => "export_chunks.zip",
+            Self::Octree => "export_octree.zip",
+            Self::Binary => "export.splats",
+        }
+    }
+
+    /// What's exported, as shown in the progress.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ply | Self::Progressive => "ply",
+            Self::Chunks => "chunks",
+            Self::Octree => "octree",
+            Self::Binary => "binary model",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViewerAction {
+    Open(DataSource),
+    Cancel,
+    /// Pause or resume, or toggle when not given.
+    Pause(Option<bool>),
+    /// Add a layer that deletes the splats with their mean outside the box.
+    Crop(BoundingBox),
+    /// Save the edit layers to a path, or next to the model.
+    SaveEdits(Option<PathBuf>),
+    /// Load edit layers from a path, or from a file picked by the user.
+    LoadEdits(Option<PathBuf>),
+    ClearEdits,
+    /// Export to a path, or to a file picked by the user.
+    Export(ExportFormat, Option<PathBuf>),
+    RenderMode(RenderMode),
+    ColorSpace(ColorSpace),
+    /// Switch a setting on or off, or toggle it when not given.
+    Set(ViewSetting, Option<bool>),
+    /// The source of a script to run, read from the path given to the `script` action.
+    Script(String),
+}
+
+fn on_off(arg: &str) -> anyhow::Result<bool> {
+    match arg.to_lowercase().as_str() {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => anyhow::bail!("Expected on or off, got {arg}"),
+    }
+}
+
+impl ViewerAction {
+    /// Parse an action from its name and arguments, see [`COMMANDS`].
+    pub fn parse(name: &str, args: &[String]) -> anyhow::Result<Self> {
+        let optional = || -> anyhow::Result<Option<String>> {
+            match args {
+                [] => Ok(None),
+                [arg] => Ok(Some(arg.clone())),
+                _ => Err(anyhow::anyhow!("{name} takes at most one argument")),
+            }
+        };
+        let required = || -> anyhow::Result<String> {
+            optional()?.with_context(|| {
+                let args = COMMANDS
+                    .iter()
+                    .find(|c| c.name == name)
+                    .map_or("", |c| c.args);
+                format!("{name} expects {args}")
+            })
+        };
+        let export = |format| -> anyhow::Result<Self> {
+            Ok(Self::Export(format, optional()?.map(PathBuf::from)))
+        };
+
+        match name {
+            "open" => Ok(Self::Open(match optional()? {
+                None => DataSource::PickFile,
+                Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                    DataSource::Url(url)
+                }
+                Some(path) => DataSource::Path(PathBuf::from(path)),
+            })),
+            "open_folder" => Ok(Self::Open(DataSource::PickDirectory)),
+            "cancel" => Ok(Self::Cancel),
+            "pause" => Ok(Self::Pause(
+                optional()?.map(|arg| on_off(&arg)).transpose()?,
+            )),
+            "crop" => {
+                let corners: Vec<f32> = args
+                    .iter()
+                    .map(|v| v.parse())
+                    .collect::<Result<_, _>>()
+                    .context("crop expects numbers")?;
+                let [x0, y0, z0, x1, y1, z1] = corners[..] else {
+                    anyhow::bail!("crop expects 6 numbers, the corners of the box");
+                };
+                let (a, b) = (glam::vec3(x0, y0, z0), glam::vec3(x1, y1, z1));
+                Ok(Self::Crop(BoundingBox::from_min_max(a.min(b), a.max(b))))
+            }
+            "save_edits" => Ok(Self::SaveEdits(optional()?.map(PathBuf::from))),
+            "load_edits" => Ok(Self::LoadEdits(optional()?.map(PathBuf::from))),
+            "clear_edits" => Ok(Self::ClearEdits),
+            "export" => export(ExportFormat::Ply),
+            "export_progressive" => export(ExportFormat::Progressive),
+            "export_chunks" => export(ExportFormat::Chunks),
+            "export_octree" => export(ExportFormat::Octree),
+            "export_binary" => export(ExportFormat::Binary),
+            "render_mode" => {
+                let mode = match required()?.to_lowercase().as_str() {
+                    "color" => RenderMode::Color,
+                    "shaded" => RenderMode::Shaded,
+                    "points" => RenderMode::Points,
+                    "ellipsoids" => RenderMode::Ellipsoids,
+                    other => anyhow::bail!("Unknown render mode {other}"),
+                };
+                Ok(Self::RenderMode(mode))
+            }
+            "color_space" => {
+                let space = match required()?.to_lowercase().as_str() {
+                    "srgb" => ColorSpace::Srgb,
+                    "display_p3" | "p3" => ColorSpace::DisplayP3,
+                    "hdr10" => ColorSpace::Hdr10,
+                    other => anyhow::bail!("Unknown color space {other}"),
+                };
+                Ok(Self::ColorSpace(space))
+            }
+            "script" => {
+                let path = required()?;
+                let source = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read script {path}"))?;
+                Ok(Self::Script(source))
+            }
+            _ => {
+                let (_, setting) = VIEW_SETTINGS
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .with_context(|| format!("Unknown action {name}"))?;
+                Ok(Self::Set(
+                    *setting,
+                    optional()?.map(|arg| on_off(&arg)).transpose()?,
+                ))
+            }
+        }
+    }
+
+    /// Parse an action as typed in the command palette. Arguments are separated by spaces,
+    /// and can be quoted to include spaces, eg. `open "my scans/room.ply"`.
+    pub fn parse_line(line: &str) -> anyhow::Result<Self> {
+        let mut words = vec![];
+        let mut word: Option<String> = None;
+        let mut quoted = false;
+        for c in line.chars() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    word.get_or_insert_with(String::new);
+                }
+                c if c.is_whitespace() && !quoted => words.extend(word.take()),
+                c => word.get_or_insert_with(String::new).push(c),
+            }
+        }
+        words.extend(word);
+
+        let (name, args) = words.split_first().context("No action given")?;
+        Self::parse(&name.to_lowercase(), args)
+    }
+}
+
+/// Keeps the actions after it waiting while it's alive, see [`ActionQueue::hold`].
+pub struct QueueHold {
+    holds: Arc<AtomicUsize>,
+    ctx: egui::Context,
+}
+
+impl Drop for QueueHold {
+    fn drop(&mut self) {
+        self.holds.fetch_sub(1, Ordering::SeqCst);
+        // Run the next action right away.
+        self.ctx.request_repaint();
+    }
+}
+
+/// Actions waiting to run, in order.
+#[derive(Clone)]
+pub struct ActionQueue {
+    pending: Arc<Mutex<VecDeque<ViewerAction>>>,
+    holds: Arc<AtomicUsize>,
+    ctx: egui::Context,
+}
+
+impl ActionQueue {
+    pub fn new(ctx: egui::Context) -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(VecDeque::new())),
+            holds: Arc::new(AtomicUsize::new(0)),
+            ctx,
+        }
+    }
+
+    pub fn push(&self, action: ViewerAction) {
+        self.pending
+            .lock()
+            .expect("Lock poisoned")
+            .push_back(action);
+        self.ctx.request_repaint();
+    }
+
+    /// Hold up the actions after the current one until the returned hold is dropped, eg. by
+    /// the background task running the current action.
+    pub fn hold(&self) -> QueueHold {
+        self.holds.fetch_add(1, Ordering::SeqCst);
+        QueueHold {
+            holds: self.holds.clone(),
+            ctx: self.ctx.clone(),
+        }
+    }
+
+    /// The next action to run, unless the queue is held up.
+    pub fn next(&self) -> Option<ViewerAction> {
+        if self.holds.load(Ordering::SeqCst) > 0 {
+            return None;
+        }
+        self.pending.lock().expect("Lock poisoned").pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use brush_render::{bounding_box::BoundingBox, RenderMode};
+
+    use super::{ExportFormat, ViewSetting, ViewerAction, COMMANDS};
+    use crate::data_source::DataSource;
+
+    #[test]
+    fn parse_actions() {
+        assert_eq!(
+            ViewerAction::parse_line(r#"open "my scans/room.ply""#).expect("Valid action"),
+            ViewerAction::Open(DataSource::Path(PathBuf::from("my scans/room.ply")))
+        );
+        assert_eq!(
+            ViewerAction::parse_line("crop 1 1 1 -1 -1 -1").expect("Valid action"),
+            ViewerAction::Crop(BoundingBox::from_min_max(
+                glam::Vec3::NEG_ONE,
+                glam::Vec3::ONE
+            ))
+        );
+        assert_eq!(
+            ViewerAction::parse_line("export_binary").expect("Valid action"),
+            ViewerAction::Export(ExportFormat::Binary, None)
+        );
+        assert_eq!(
+            ViewerAction::parse_line("Render_Mode points").expect("Valid action"),
+            ViewerAction::RenderMode(RenderMode::Points)
+        );
+        assert_eq!(
+            ViewerAction::parse_line("foveated off").expect("Valid action"),
+            ViewerAction::Set(ViewSetting::Foveated, Some(false))
+        );
+
+        assert!(ViewerAction::parse_line("crop 1 2 3").is_err());
+        assert!(ViewerAction::parse_line("render_mode").is_err());
+        assert!(ViewerAction::parse_line("uncertainty maybe").is_err());
+        assert!(ViewerAction::parse_line("nope").is_err());
+        assert!(ViewerAction::parse_line("  ").is_err());
+    }
+
+    #[test]
+    fn every_command_parses() {
+        for command in COMMANDS.iter().filter(|c| !c.needs_args()) {
+            assert!(
+                ViewerAction::parse(command.name, &[]).is_ok(),
+                "{} should run without arguments",
+                command.name
+            );
+        }
+    }
+}
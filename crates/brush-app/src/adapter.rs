@@ -0,0 +1,285 @@
+//! Pick the GPU to run on.
+//!
+//! Systems with several adapters, eg. an integrated and a dedicated GPU, or one GPU on
+//! several backends, are benchmarked on the first launch. The fastest adapter is saved to the
+//! config folder and used on the next launches, unless `--adapter` picks another one.
+
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use brush_render::{
+    bounding_box::BoundingBox,
+    camera::Camera,
+    gaussian_splats::{RandomSplatsConfig, Splats},
+    RenderOptions,
+};
+use burn::tensor::{Int, Tensor};
+use burn_wgpu::{JitBackend, Wgpu, WgpuDevice, WgpuRuntime};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+use wgpu::{Adapter, Device, Instance, Queue};
+
+use crate::session;
+
+type SortBackend = JitBackend<WgpuRuntime, f32, i32, u32>;
+
+const BENCH_SORT_COUNT: usize = 1 << 21;
+const BENCH_SPLAT_COUNT: usize = 200_000;
+const BENCH_RESOLUTION: glam::UVec2 = glam::uvec2(1920, 1080);
+const BENCH_ITERS: u32 = 5;
+
+/// The adapter to use on the next launches.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdapterConfig {
+    pub name: String,
+    pub backend: String,
+}
+
+impl AdapterConfig {
+    fn path() -> Option<PathBuf> {
+        session::config_dir().map(|dir| dir.join("adapter.json"))
+    }
+
+    fn load() -> Option<Self> {
+        let data = std::fs::read(Self::path()?).ok()?;
+        serde_json::from_slice(&data)
+            .inspect_err(|e| log::warn!("Invalid adapter config: {e}"))
+            .ok()
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path().context("No config folder")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(self)?;
+        rrfd::write_atomic(&path, &data, false)
+            .with_context(|| format!("Failed to write adapter config {path:?}"))
+    }
+
+    fn of(adapter: &Adapter) -> Self {
+        let info = adapter.get_info();
+        Self {
+            name: info.name,
+            backend: format!("{:?}", info.backend),
+        }
+    }
+}
+
+impl std::fmt::Display for AdapterConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.backend)
+    }
+}
+
+/// A device created on the chosen adapter.
+pub struct SelectedAdapter {
+    pub instance: Arc<Instance>,
+    pub adapter: Arc<Adapter>,
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+}
+
+/// How fast an adapter ran the benchmark, in milliseconds per iteration.
+#[derive(Clone, Debug)]
+pub struct AdapterBenchmark {
+    pub adapter: AdapterConfig,
+    pub sort_ms: f64,
+    pub rasterize_ms: f64,
+}
+
+impl AdapterBenchmark {
+    pub fn total_ms(&self) -> f64 {
+        self.sort_ms + self.rasterize_ms
+    }
+}
+
+fn create_instance() -> Instance {
+    Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
+        ..Default::default()
+    })
+}
+
+// Every word of the query has to be in the name or backend of the adapter, so eg. "nvidia
+// vulkan" picks the Vulkan adapter of an NVIDIA GPU.
+fn matches(adapter: &Adapter, query: &str) -> bool {
+    let desc = AdapterConfig::of(adapter).to_string().to_lowercase();
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .all(|word| desc.contains(word))
+}
+
+// Adapters worth benchmarking. The GL backend and software rasterizers are only fallbacks,
+// and can crash on the benchmark.
+fn is_candidate(adapter: &Adapter) -> bool {
+    let info = adapter.get_info();
+    info.backend != wgpu::Backend::Gl && info.device_type != wgpu::DeviceType::Cpu
+}
+
+async fn open(instance: Arc<Instance>, adapter: Adapter) -> anyhow::Result<SelectedAdapter> {
+    let (device, queue) = adapter
+        .request_device(&brush_ui::device_descriptor(&adapter), None)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to create a device on {}",
+                AdapterConfig::of(&adapter)
+            )
+        })?;
+    Ok(SelectedAdapter {
+        instance,
+        adapter: Arc::new(adapter),
+        device: Arc::new(device),
+        queue: Arc::new(queue),
+    })
+}
+
+// Run `f` a few times after a warmup run, returning the milliseconds per run.
+async fn time_runs<F: std::future::Future<Output = ()>>(mut f: impl FnMut() -> F) -> f64 {
+    f().await;
+    let start = Instant::now();
+    for _ in 0..BENCH_ITERS {
+        f().await;
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / BENCH_ITERS as f64
+}
+
+async fn benchmark_device(device: &WgpuDevice) -> (f64, f64) {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let keys: Vec<i32> = (0..BENCH_SORT_COUNT)
+        .map(|_| rng.gen_range(0..i32::MAX))
+        .collect();
+    let keys = Tensor::<SortBackend, 1, Int>::from_ints(keys.as_slice(), device);
+    let values = Tensor::<SortBackend, 1, Int>::arange(0..BENCH_SORT_COUNT as i64, device);
+    let count = Tensor::<SortBackend, 1, Int>::from_ints([BENCH_SORT_COUNT as i32], device);
+    let sort_ms = time_runs(|| {
+        let (_, sorted) = brush_sort::radix_argsort(
+            keys.clone().into_primitive(),
+            values.clone().into_primitive(),
+            &count.clone().into_primitive(),
+            32,
+        );
+        async move {
+            Tensor::<SortBackend, 1, Int>::from_primitive(sorted)
+                .slice([0..1])
+                .into_data_async()
+                .await;
+        }
+    })
+    .await;
+
+    let config = RandomSplatsConfig::new().with_init_count(BENCH_SPLAT_COUNT);
+    let bounds = BoundingBox::from_min_max(-glam::Vec3::ONE, glam::Vec3::ONE);
+    let splats = Splats::<Wgpu>::from_random_config(&config, bounds, &mut rng, device);
+    let camera = Camera::new(
+        glam::vec3(0.0, 0.0, -3.0),
+        glam::Quat::IDENTITY,
+        0.8,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    );
+    let rasterize_ms = time_runs(|| {
+        let img =
+            splats.render_inference(&camera, BENCH_RESOLUTION, false, RenderOptions::default());
+        async move {
+            img.slice([0..1, 0..1, 0..1]).into_data_async().await;
+        }
+    })
+    .await;
+
+    (sort_ms, rasterize_ms)
+}
+
+/// Benchmark sorting and rasterizing on all adapters, and save the fastest for the next
+/// launches. Returns the results, fastest first.
+pub async fn benchmark_adapters() -> anyhow::Result<Vec<AdapterBenchmark>> {
+    let instance = Arc::new(create_instance());
+    let mut results = vec![];
+    for adapter in instance.enumerate_adapters(wgpu::Backends::all()) {
+        if !is_candidate(&adapter) {
+            continue;
+        }
+        let config = AdapterConfig::of(&adapter);
+        log::info!("Benchmarking {config}");
+        let selected = open(instance.clone(), adapter).await?;
+        let device = brush_ui::create_wgpu_device(
+            selected.adapter.clone(),
+            selected.device.clone(),
+            selected.queue.clone(),
+        );
+        let (sort_ms, rasterize_ms) = benchmark_device(&device).await;
+        results.push(AdapterBenchmark {
+            adapter: config,
+            sort_ms,
+            rasterize_ms,
+        });
+    }
+    results.sort_by(|a, b| a.total_ms().total_cmp(&b.total_ms()));
+
+    if let Some(best) = results.first() {
+        best.adapter.save()?;
+    }
+    Ok(results)
+}
+
+/// Create a device on the adapter to run on.
+///
+/// With a query the first adapter matching it is used, see `--adapter`. Otherwise the adapter
+/// saved by [`benchmark_adapters`] is used, running the benchmark first when there are several
+/// adapters to choose from. Returns `None` to leave the choice to wgpu, when there's only one
+/// adapter.
+pub async fn select_adapter(query: Option<&str>) -> anyhow::Result<Option<SelectedAdapter>> {
+    let instance = Arc::new(create_instance());
+    let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+
+    if let Some(query) = query {
+        let names: Vec<_> = adapters
+            .iter()
+            .map(|a| AdapterConfig::of(a).to_string())
+            .collect();
+        let adapter = adapters
+            .into_iter()
+            .find(|a| matches(a, query))
+            .with_context(|| {
+                format!(
+                    "No adapter matches {query:?}, available adapters:\n  {}",
+                    names.join("\n  ")
+                )
+            })?;
+        return open(instance, adapter).await.map(Some);
+    }
+
+    let saved = match AdapterConfig::load() {
+        Some(saved) => Some(saved),
+        None if adapters.iter().filter(|a| is_candidate(a)).count() > 1 => {
+            let results = benchmark_adapters().await?;
+            for result in &results {
+                log::info!(
+                    "{}: sort {:.1}ms, rasterize {:.1}ms",
+                    result.adapter,
+                    result.sort_ms,
+                    result.rasterize_ms
+                );
+            }
+            results.into_iter().next().map(|r| r.adapter)
+        }
+        None => None,
+    };
+
+    let Some(saved) = saved else {
+        return Ok(None);
+    };
+    let Some(adapter) = adapters.into_iter().find(|a| {
+        let info = a.get_info();
+        info.name == saved.name && format!("{:?}", info.backend) == saved.backend
+    }) else {
+        log::warn!("Saved adapter {saved} is gone, picking the default adapter");
+        return Ok(None);
+    };
+    log::info!("Running on {saved}");
+    open(instance, adapter).await.map(Some)
+}
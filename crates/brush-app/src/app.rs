@@ -2,14 +2,28 @@ use core::f32;
 use std::ops::Range;
 use std::sync::{Arc, RwLock};
 
+use crate::accessibility::apply_interface;
+use crate::actions::{ActionQueue, QueueHold, ViewerAction};
+use crate::command_palette::CommandPalette;
 use crate::data_source::DataSource;
-use crate::process_loop::{start_process, ProcessArgs, ProcessMessage, RunningProcess};
+use crate::drop_import::DropImport;
+use crate::i18n;
+use crate::process_loop::{
+    run_viewer_script, start_process, ProcessArgs, ProcessMessage, RunningProcess, SaveArgs,
+};
+use crate::recent::RecentFiles;
+use crate::session::{InterfaceSettings, Session, SessionCamera};
 use crate::{
     orbit_controls::OrbitControls,
-    panels::{DatasetPanel, LoadDataPanel, PresetsPanel, ScenePanel, StatsPanel, TracingPanel},
+    panels::{
+        DatasetPanel, LoadDataPanel, PresetsPanel, ScenePanel, SplatStatsPanel, StatsPanel,
+        TracingPanel,
+    },
 };
-use brush_dataset::{self, Dataset};
+use brush_dataset::{self, Dataset, LoadDatasetArgs, LoadInitArgs};
 use brush_render::camera::Camera;
+use brush_tasks::{CancellationToken, ProgressSender};
+use brush_train::train::TrainConfig;
 use brush_ui::channel::reactive_receiver;
 use burn_wgpu::WgpuDevice;
 use eframe::egui;
@@ -25,11 +39,31 @@ pub(crate) trait AppPanel {
         let _ = message;
         let _ = context;
     }
+    /// Store any state of the panel that should be restored with the session.
+    fn save_session(&self, session: &mut Session) {
+        let _ = session;
+    }
+    /// Restore the state stored with [`AppPanel::save_session`].
+    fn restore_session(&mut self, session: &Session) {
+        let _ = session;
+    }
+    /// Called before the current data is replaced by a new source, and when the app exits.
+    fn on_close(&mut self, context: &AppContext) {
+        let _ = context;
+    }
+    /// Run a viewer action, see [`crate::actions`]. Every panel sees every action, and
+    /// handles the ones that concern it.
+    fn on_action(&mut self, action: &ViewerAction, context: &mut AppContext) {
+        let _ = action;
+        let _ = context;
+    }
 }
 
 struct AppTree {
     zen: bool,
     context: Arc<RwLock<AppContext>>,
+    // Pane to move the keyboard focus into when it's next drawn.
+    focus_pane: Option<TileId>,
 }
 
 type PaneType = Box<dyn AppPanel>;
@@ -42,9 +76,20 @@ impl egui_tiles::Behavior<PaneType> for AppTree {
     fn pane_ui(
         &mut self,
         ui: &mut egui::Ui,
-        _tile_id: egui_tiles::TileId,
+        tile_id: egui_tiles::TileId,
         pane: &mut PaneType,
     ) -> egui_tiles::UiResponse {
+        if self.focus_pane == Some(tile_id) {
+            self.focus_pane = None;
+            // With nothing focused, focus moves to the next widget that can take it, the first
+            // one of this pane.
+            ui.memory_mut(|m| {
+                if let Some(id) = m.focused() {
+                    m.surrender_focus(id);
+                }
+                m.move_focus(egui::FocusDirection::Next);
+            });
+        }
         pane.ui(ui, &mut self.context.write().expect("Lock poisoned"));
         egui_tiles::UiResponse::None
     }
@@ -76,8 +121,16 @@ fn parse_search(search: &str) -> HashMap<String, String> {
 
 pub struct App {
     tree: egui_tiles::Tree<PaneType>,
+    // All panes, in the order F6 moves through them.
+    panes: Vec<TileId>,
     datasets: Option<TileId>,
     tree_ctx: AppTree,
+    drop_import: DropImport,
+    palette: CommandPalette,
+    // The pane F6 last moved to.
+    focused_pane: Option<TileId>,
+    // The interface settings currently in use by egui.
+    applied_interface: Option<InterfaceSettings>,
 }
 
 // TODO: Bit too much random shared state here.
@@ -89,6 +142,19 @@ pub struct AppContext {
     pub device: WgpuDevice,
     ctx: egui::Context,
     running_process: Option<RunningProcess>,
+    source: Option<DataSource>,
+    // Camera of a restored session, applied again once its data is loaded.
+    pending_camera: Option<SessionCamera>,
+    /// Settings to load data with, edited in the load data panel.
+    pub(crate) process_args: ProcessArgs,
+    /// Recently opened sources, shown on the start screen.
+    pub(crate) recent: RecentFiles,
+    /// Scale and theme of the interface, edited in the load data panel.
+    pub(crate) interface: InterfaceSettings,
+    /// Viewer actions waiting to run, from the command palette and scripts.
+    pub(crate) actions: ActionQueue,
+    // Holds up the actions while data loads.
+    loading: Option<QueueHold>,
 }
 
 struct CameraSettings {
@@ -118,6 +184,7 @@ impl AppContext {
             cam_settings.focal,
             glam::vec2(0.5, 0.5),
         );
+        let actions = ActionQueue::new(ctx.clone());
 
         Self {
             camera,
@@ -127,9 +194,47 @@ impl AppContext {
             ctx,
             dataset: Dataset::empty(),
             running_process: None,
+            source: None,
+            pending_camera: None,
+            recent: RecentFiles::load(),
+            interface: InterfaceSettings::default(),
+            actions,
+            loading: None,
+            process_args: ProcessArgs {
+                // Super high resolutions are a bit sketchy. Limit to at least
+                // some size.
+                load_args: LoadDatasetArgs {
+                    max_resolution: Some(1920),
+                    ..Default::default()
+                },
+                train_config: TrainConfig::default(),
+                init_args: LoadInitArgs::default(),
+                save_args: SaveArgs::default(),
+                source: DataSource::PickFile,
+                captures: vec![],
+                script: None,
+                heal: None,
+            },
+        }
+    }
+
+    fn session_camera(&self) -> SessionCamera {
+        SessionCamera {
+            position: self.controls.position.into(),
+            rotation: self.controls.rotation,
+            focus: self.controls.focus.into(),
+            fov_y: self.camera.fov_y,
         }
     }
 
+    fn restore_camera(&mut self, cam: &SessionCamera) {
+        self.controls.position = cam.position.into();
+        self.controls.rotation = cam.rotation;
+        self.controls.focus = cam.focus.into();
+        self.controls.dirty = true;
+        self.camera.fov_y = cam.fov_y;
+    }
+
     pub fn set_up_axis(&mut self, up_axis: Vec3) {
         let rotation = Quat::from_rotation_arc(Vec3::Y, up_axis);
         let model_transform = Affine3A::from_rotation_translation(rotation, Vec3::ZERO).inverse();
@@ -152,6 +257,9 @@ impl AppContext {
     }
 
     pub fn connect_to(&mut self, process: RunningProcess) {
+        // Stop whatever the previous process was doing, instead of leaving it running until
+        // it notices nobody listens anymore.
+        self.cancel_process();
         self.dataset = Dataset::empty();
         // Conver the receiver to a "reactive" receiver that wakes up the UI.
         let process = RunningProcess {
@@ -161,11 +269,53 @@ impl AppContext {
         self.running_process = Some(process);
     }
 
+    /// Stop the running process, if any.
+    pub fn cancel_process(&mut self) {
+        if let Some(process) = self.running_process.take() {
+            process.cancel();
+        }
+        self.loading = None;
+    }
+
+    /// A token that's cancelled together with the running process, for background work on
+    /// its splats like exports.
+    pub(crate) fn cancel_token(&self) -> CancellationToken {
+        self.running_process
+            .as_ref()
+            .map_or_else(CancellationToken::new, |p| p.cancel.child_token())
+    }
+
+    /// Reports the progress of background work on the splats of the running process, like
+    /// exports, in the process messages.
+    pub(crate) fn progress(&self) -> ProgressSender {
+        self.running_process
+            .as_ref()
+            .map_or_else(ProgressSender::default, |p| p.progress.clone())
+    }
+
     pub(crate) fn control_message(&self, msg: crate::process_loop::ControlMessage) {
         if let Some(process) = self.running_process.as_ref() {
             let _ = process.control.send(msg);
         }
     }
+
+    /// Where the current data was loaded from.
+    pub(crate) fn source(&self) -> Option<&DataSource> {
+        self.source.as_ref()
+    }
+
+    /// Run the top level of a script, and queue the viewer actions it asks for. When the
+    /// script has training callbacks, datasets loaded from then on train with it.
+    pub(crate) fn run_script(&mut self, source: &str) -> anyhow::Result<()> {
+        let (actions, trains) = run_viewer_script(source)?;
+        if trains {
+            self.process_args.script = Some(source.to_owned());
+        }
+        for action in actions {
+            self.actions.push(action);
+        }
+        Ok(())
+    }
 }
 
 pub struct AppCreateCb {
@@ -176,6 +326,7 @@ impl App {
     pub fn new(
         cc: &eframe::CreationContext,
         create_callback: tokio::sync::oneshot::Sender<AppCreateCb>,
+        session: Option<Session>,
     ) -> Self {
         // For now just assume we're running on the default
         let state = cc
@@ -279,38 +430,49 @@ impl App {
             pitch_range: min_pitch..max_pitch,
         };
 
-        let context = AppContext::new(device.clone(), cc.egui_ctx.clone(), settings);
+        let mut context = AppContext::new(device.clone(), cc.egui_ctx.clone(), settings);
 
         let mut tiles: Tiles<PaneType> = Tiles::default();
         let scene_pane = ScenePanel::new(
             state.queue.clone(),
             state.device.clone(),
             state.renderer.clone(),
+            state.target_format,
             zen,
         );
 
         let scene_pane_id = tiles.insert_pane(Box::new(scene_pane));
+        let mut panes = vec![];
 
         let root_container = if !zen {
             let loading_subs = vec![
                 tiles.insert_pane(Box::new(LoadDataPanel::new())),
                 tiles.insert_pane(Box::new(PresetsPanel::new())),
             ];
+            panes.extend(&loading_subs);
             let loading_pane = tiles.insert_tab_tile(loading_subs);
 
-            #[allow(unused_mut)]
-            let mut sides = vec![
-                loading_pane,
+            let stats_subs = vec![
                 tiles.insert_pane(Box::new(StatsPanel::new(device.clone(), &state.adapter))),
+                tiles.insert_pane(Box::new(SplatStatsPanel::new())),
             ];
+            panes.extend(&stats_subs);
+            let stats_pane = tiles.insert_tab_tile(stats_subs);
+
+            #[allow(unused_mut)]
+            let mut sides = vec![loading_pane, stats_pane];
 
             #[cfg(all(not(target_family = "wasm"), not(target_os = "android")))]
             {
-                sides.push(tiles.insert_pane(Box::new(crate::panels::RerunPanel::new())));
+                let rerun = tiles.insert_pane(Box::new(crate::panels::RerunPanel::new()));
+                panes.push(rerun);
+                sides.push(rerun);
             }
 
             if cfg!(feature = "tracing") {
-                sides.push(tiles.insert_pane(Box::new(TracingPanel::default())));
+                let tracing = tiles.insert_pane(Box::new(TracingPanel::default()));
+                panes.push(tracing);
+                sides.push(tracing);
             }
 
             let side_panel = tiles.insert_vertical_tile(sides);
@@ -325,22 +487,53 @@ impl App {
             scene_pane_id
         };
 
-        let tree = egui_tiles::Tree::new("brush_tree", root_container, tiles);
+        let mut tree = egui_tiles::Tree::new("brush_tree", root_container, tiles);
+
+        if let Some(session) = &session {
+            i18n::set_language(session.language);
+            context.interface = session.interface;
+        }
+
+        // Restore a session, unless a url to load is given.
+        let url = search_params.get("url");
+        let session = session.filter(|_| url.is_none());
+        let mut session_source = None;
+        if let Some(session) = session {
+            for (_, tile) in tree.tiles.iter_mut() {
+                if let Tile::Pane(pane) = tile {
+                    pane.restore_session(&session);
+                }
+            }
+            if let Some(cam) = session.camera {
+                context.restore_camera(&cam);
+                context.pending_camera = Some(cam);
+            }
+            session_source = session.source.filter(DataSource::is_restorable);
+        }
 
         let context = Arc::new(RwLock::new(context));
         let _ = create_callback.send(AppCreateCb {
             context: context.clone(),
         });
 
-        let tree_ctx = AppTree { zen, context };
+        let tree_ctx = AppTree {
+            zen,
+            context,
+            focus_pane: None,
+        };
+        panes.push(scene_pane_id);
 
-        let url = search_params.get("url");
-        if let Some(url) = url {
+        let source = url.map(|url| DataSource::Url(url.to_owned()));
+        if let Some(source) = source.or(session_source) {
             let args = ProcessArgs {
-                source: DataSource::Url(url.to_owned()),
+                source,
+                captures: vec![],
                 load_args: Default::default(),
                 init_args: Default::default(),
                 train_config: Default::default(),
+                save_args: Default::default(),
+                script: None,
+                heal: None,
             };
             let running = start_process(args, device);
             tree_ctx
@@ -353,13 +546,73 @@ impl App {
 
         Self {
             tree,
+            panes,
             tree_ctx,
             datasets: None,
+            drop_import: DropImport::default(),
+            palette: CommandPalette::default(),
+            focused_pane: None,
+            applied_interface: None,
         }
     }
 }
 
 impl App {
+    /// Run a script of viewer actions, see [`crate::actions`].
+    pub fn run_script(&mut self, source: &str) -> anyhow::Result<()> {
+        self.tree_ctx
+            .context
+            .write()
+            .expect("Lock poisoned")
+            .run_script(source)
+    }
+
+    // Run the queued viewer actions, until one holds up the queue.
+    fn run_actions(&mut self) {
+        let mut context = self.tree_ctx.context.write().expect("Lock poisoned");
+        while let Some(action) = context.actions.next() {
+            match action {
+                ViewerAction::Open(source) => {
+                    let args = ProcessArgs {
+                        source,
+                        ..context.process_args.clone()
+                    };
+                    let process = start_process(args, context.device.clone());
+                    context.connect_to(process);
+                    context.loading = Some(context.actions.hold());
+                }
+                ViewerAction::Script(source) => {
+                    if let Err(e) = context.run_script(&source) {
+                        log::error!("{e:?}");
+                    }
+                }
+                action => {
+                    for (_, tile) in self.tree.tiles.iter_mut() {
+                        if let Tile::Pane(pane) = tile {
+                            pane.on_action(&action, &mut context);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Activate the next or previous pane, and move the keyboard focus into it.
+    fn cycle_pane(&mut self, step: isize) {
+        let current = self
+            .focused_pane
+            .and_then(|id| self.panes.iter().position(|p| *p == id));
+        let next = current.map_or(0, |i| {
+            (i as isize + step).rem_euclid(self.panes.len() as isize) as usize
+        });
+        let Some(&pane) = self.panes.get(next) else {
+            return;
+        };
+        self.tree.make_active(|id, _| id == pane);
+        self.focused_pane = Some(pane);
+        self.tree_ctx.focus_pane = Some(pane);
+    }
+
     fn receive_messages(&mut self) {
         let mut context = self.tree_ctx.context.write().expect("Lock poisoned");
 
@@ -376,6 +629,7 @@ impl App {
                     if self.datasets.is_none() {
                         let pane_id = self.tree.tiles.insert_pane(Box::new(DatasetPanel::new()));
                         self.datasets = Some(pane_id);
+                        self.panes.push(pane_id);
                         if let Some(Tile::Container(Container::Linear(lin))) = self
                             .tree
                             .tiles
@@ -386,6 +640,15 @@ impl App {
                     }
                 }
 
+                if matches!(message, ProcessMessage::NewSource { .. }) {
+                    // Let panes wrap up the data that's being replaced, eg. take a thumbnail.
+                    for (_, tile) in self.tree.tiles.iter_mut() {
+                        if let Tile::Pane(pane) = tile {
+                            pane.on_close(&context);
+                        }
+                    }
+                }
+
                 for (_, pane) in self.tree.tiles.iter_mut() {
                     match pane {
                         Tile::Pane(pane) => {
@@ -394,6 +657,23 @@ impl App {
                         Tile::Container(_) => {}
                     }
                 }
+
+                match message {
+                    ProcessMessage::NewSource { source } => {
+                        context.recent.add(&source);
+                        context.source = Some(source);
+                    }
+                    ProcessMessage::DoneLoading { .. } => {
+                        // Loading data moves the camera, put it back where the session left it.
+                        if let Some(cam) = context.pending_camera.take() {
+                            context.restore_camera(&cam);
+                        }
+                        context.loading = None;
+                    }
+                    ProcessMessage::Error(_) => context.loading = None,
+                    ProcessMessage::ViewerAction(action) => context.actions.push(action),
+                    _ => {}
+                }
             }
         }
     }
@@ -402,6 +682,42 @@ impl App {
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
         self.receive_messages();
+        self.run_actions();
+
+        {
+            let mut context = self.tree_ctx.context.write().expect("Lock poisoned");
+            // Ctrl + plus and minus scale the interface as well.
+            if let Some(applied) = self.applied_interface.as_mut() {
+                if applied.ui_scale != ctx.zoom_factor() {
+                    applied.ui_scale = ctx.zoom_factor();
+                    context.interface.ui_scale = applied.ui_scale;
+                }
+            }
+            if self.applied_interface != Some(context.interface) {
+                apply_interface(ctx, &context.interface);
+                self.applied_interface = Some(context.interface);
+            }
+        }
+
+        // F6 moves between the panels, to reach all of them with just the keyboard.
+        let step = ctx.input_mut(|r| {
+            if r.consume_key(egui::Modifiers::SHIFT, egui::Key::F6) {
+                -1
+            } else if r.consume_key(egui::Modifiers::NONE, egui::Key::F6) {
+                1
+            } else {
+                0
+            }
+        });
+        if step != 0 {
+            self.cycle_pane(step);
+        }
+
+        // Before the panels, so escape closes the palette rather than the viewer.
+        self.palette.ui(
+            ctx,
+            &self.tree_ctx.context.read().expect("Lock poisoned").actions,
+        );
 
         egui::CentralPanel::default().show(ctx, |ui| {
             // Close when pressing escape (in a native viewer anyway).
@@ -411,5 +727,41 @@ impl eframe::App for App {
             }
             self.tree.ui(&mut self.tree_ctx, ui);
         });
+
+        // Files can be dropped anywhere, not just on the load data panel.
+        self.drop_import.ui(
+            ctx,
+            &mut self.tree_ctx.context.write().expect("Lock poisoned"),
+        );
+    }
+
+    fn on_exit(&mut self) {
+        let context = self.tree_ctx.context.read().expect("Lock poisoned");
+        for (_, tile) in self.tree.tiles.iter_mut() {
+            if let Tile::Pane(pane) = tile {
+                pane.on_close(&context);
+            }
+        }
+
+        // Save the session, so the next launch can pick up where this one left off.
+        let Some(path) = Session::default_path() else {
+            return;
+        };
+        let mut session = Session {
+            source: context.source.clone().filter(DataSource::is_restorable),
+            camera: Some(context.session_camera()),
+            language: i18n::language(),
+            interface: context.interface,
+            ..Default::default()
+        };
+        for (_, tile) in self.tree.tiles.iter() {
+            if let Tile::Pane(pane) = tile {
+                pane.save_session(&mut session);
+            }
+        }
+
+        if let Err(e) = session.save(&path) {
+            log::warn!("Failed to save session: {e:?}");
+        }
     }
 }
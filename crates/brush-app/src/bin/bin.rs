@@ -1,10 +1,14 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+#[cfg(not(target_family = "wasm"))]
+use brush_app::session::Session;
 use brush_app::App;
 
 #[cfg(target_family = "wasm")]
 use wasm_bindgen::JsCast;
 
+// CLI errors are reported on stderr, as the command exits before any UI is shown.
+#[allow(clippy::print_stderr)]
 fn main() {
     let wgpu_options = brush_ui::create_egui_options();
 
@@ -20,6 +24,64 @@ fn main() {
         runtime.block_on(async {
             env_logger::init();
 
+            let (session_path, adapter, script_path) =
+                match brush_app::cli::parse_args(std::env::args().skip(1)) {
+                    Ok(Some(brush_app::cli::Command::View {
+                        session,
+                        adapter,
+                        script,
+                    })) => (session, adapter, script),
+                    Ok(Some(command)) => {
+                        if let Err(e) = brush_app::cli::run(command).await {
+                            log::error!("{e:?}");
+                            eprintln!("{e:?}");
+                            std::process::exit(1);
+                        }
+                        return;
+                    }
+                    Ok(None) => (None, None, None),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(1);
+                    }
+                };
+
+            let wgpu_options = match brush_app::adapter::select_adapter(adapter.as_deref()).await {
+                Ok(Some(selected)) => brush_ui::create_egui_options_with(
+                    selected.instance,
+                    selected.adapter,
+                    selected.device,
+                    selected.queue,
+                ),
+                Ok(None) => wgpu_options,
+                Err(e) => {
+                    eprintln!("{e:?}");
+                    std::process::exit(1);
+                }
+            };
+
+            // Restore the session passed on the command line, or otherwise the last one.
+            let session = match session_path {
+                Some(path) => match Session::load(&path) {
+                    Ok(session) => Some(session),
+                    Err(e) => {
+                        eprintln!("{e:?}");
+                        std::process::exit(1);
+                    }
+                },
+                None => Session::default_path()
+                    .filter(|p| p.exists())
+                    .and_then(|p| Session::load(&p).inspect_err(|e| log::warn!("{e:?}")).ok()),
+            };
+
+            let script = match script_path.map(std::fs::read_to_string).transpose() {
+                Ok(script) => script,
+                Err(e) => {
+                    eprintln!("Failed to read script: {e}");
+                    std::process::exit(1);
+                }
+            };
+
             // NB: Load carrying icon. egui at head fails when no icon is included
             // as the built-in one is git-lfs which cargo doesn't clone properly.
             let icon =
@@ -39,7 +101,15 @@ fn main() {
             eframe::run_native(
                 "Brush",
                 native_options,
-                Box::new(move |cc| Ok(Box::new(App::new(cc, send)))),
+                Box::new(move |cc| {
+                    let mut app = App::new(cc, send, session);
+                    if let Some(script) = script {
+                        if let Err(e) = app.run_script(&script) {
+                            log::error!("{e:?}");
+                        }
+                    }
+                    Ok(Box::new(app))
+                }),
             )
             .expect("Failed to run egui app");
         });
@@ -73,7 +143,7 @@ fn main() {
                     .start(
                         canvas,
                         web_options,
-                        Box::new(|cc| Ok(Box::new(App::new(cc, send)))),
+                        Box::new(|cc| Ok(Box::new(App::new(cc, send, None)))),
                     )
                     .await
                     .expect("failed to start eframe");
@@ -127,7 +197,7 @@ mod embedded {
                             wgpu_options,
                             ..Default::default()
                         },
-                        Box::new(|cc| Ok(Box::new(App::new(cc, send)))),
+                        Box::new(|cc| Ok(Box::new(App::new(cc, send, None)))),
                     )
                     .await
                     .expect("failed to start eframe");
@@ -145,9 +215,13 @@ mod embedded {
             // Load initial url.
             let _ = cmd_send.send(ProcessArgs {
                 source: DataSource::Url(url.to_owned()),
+                captures: vec![],
                 load_args: Default::default(),
                 init_args: Default::default(),
                 train_config: Default::default(),
+                save_args: Default::default(),
+                script: None,
+                heal: None,
             });
             Self {
                 command_channel: cmd_send,
@@ -158,9 +232,13 @@ mod embedded {
         pub fn load_url(&self, url: &str) {
             let args = ProcessArgs {
                 source: DataSource::Url(url.to_owned()),
+                captures: vec![],
                 load_args: Default::default(),
                 init_args: Default::default(),
                 train_config: Default::default(),
+                save_args: Default::default(),
+                script: None,
+                heal: None,
             };
             self.command_channel.send(args).expect("Viewer was closed?");
         }
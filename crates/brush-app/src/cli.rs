@@ -0,0 +1,1413 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use brush_dataset::{
+    brush_vfs::BrushVfs,
+    checkpoint, chunk_export,
+    coordinates::CoordinateConvention,
+    dataset_export,
+    distill::{self, DistillViews},
+    model_info::ModelInfo,
+    novel_views::{self, NovelViewConfig},
+    octree_export,
+    presets::Preset,
+    progressive_export,
+    render_views::{self, RenderViewsOptions},
+    scene_loader::SceneLoader,
+    splat_export, splat_import,
+    survey_diff::{self, ReferenceGeometry, SurveyDiffOptions},
+    Dataset, LoadDatasetArgs, LoadInitArgs,
+};
+use brush_render::{
+    bounding_box::BoundingBox, device::default_device, gaussian_splats::Splats, Backend,
+};
+use brush_tasks::{Progress, ProgressSender};
+use brush_train::{
+    eval::{eval_stats, EvalStats, EvalView},
+    freeze::FreezeRule,
+    scene::Scene,
+    simplify::simplify_splats,
+    train::{SplatTrainer, TrainConfig},
+};
+use burn::{backend::Autodiff, module::AutodiffModule, tensor::ElementConversion};
+use burn_wgpu::{Wgpu, WgpuDevice};
+use rand::{rngs::StdRng, SeedableRng};
+use tokio_stream::StreamExt;
+
+use crate::{
+    data_source::DataSource,
+    process_loop::{start_process, ProcessArgs, ProcessMessage, SaveArgs},
+    remote::{self, SnapshotState},
+};
+
+const USAGE: &str = "Usage:
+  brush_app                                   Start the viewer, restoring the last session
+  brush_app --session <session.json>          Start the viewer with a saved session
+  brush_app --adapter <name>                  Start the viewer on the GPU matching this name,
+                                              eg. \"nvidia vulkan\"
+  brush_app --script <file.rhai>              Start the viewer and run a script of viewer
+                                              actions, eg. to load, crop and export a model
+  brush_app adapters                          Benchmark the GPUs and remember the fastest,
+                                              which the viewer then starts on
+  brush_app convert <input> <output> [options]  Convert a dataset to the nerfstudio format
+  brush_app simplify <input.ply> <output.ply> --target-count <N> [--dataset <path>]
+                                              Merge splats to at most N splats
+  brush_app distill <teacher.ply> <output.ply> --target-count <N> [options]
+                                              Train a model of at most N splats on renders
+                                              of a large model, eg. for mobile or the web
+  brush_app chunks <input.ply> <output> [options]
+                                              Export spatial chunks and collision boxes for
+                                              game engines
+  brush_app progressive <input.ply> <output.ply> [--coordinates <name>]
+                                              Reorder splats from coarse to fine detail, for
+                                              web viewers that show the model while loading
+  brush_app train <dataset>... [options]      Train without a window. Several datasets are
+                                              trained as captures of the same scene
+  brush_app info <model>                      Show how a ply or .brush checkpoint was trained
+  brush_app render <model.ply> <dataset> <output> [options]
+                                              Render the training and eval views of a dataset
+  brush_app diff <a.ply> <b.ply> --dataset <path>
+                                              Compare the PSNR and SSIM of two models on the
+                                              eval views of a dataset
+  brush_app augment <model.ply> <dataset> <output> [options]
+                                              Render new views around the training views
+                                              into a dataset with depth maps
+  brush_app survey <model.ply> <dataset> <reference> <output> [options]
+                                              Compare the surfaces of a model to a reference
+                                              point cloud (.ply) or mesh (.obj), eg. a lidar
+                                              survey, with statistics and deviation renders
+
+Train options:
+  --serve <address>        Serve snapshots to viewers, eg. 7878 for only this machine or
+                           0.0.0.0:7878 for all interfaces. Anyone who can connect can
+                           download the model and pause training, there's no authentication
+  --output <dir>           Save checkpoints and the best model to this folder
+  --resume <file.brush>    Continue training from a checkpoint saved in an output folder.
+                           The optimizer state isn't saved and starts over
+  --fsync                  Flush saved files to the disk, so they survive a power loss
+  --image-memory <MB>      Keep at most this many megabytes of images decoded, decoding
+                           the others again when they're needed
+  --gpu-image-memory <MB>  Keep at most this many megabytes of images on the GPU
+  --script <file.rhai>     Run a script while training, to change settings, export or
+                           stop at chosen steps
+  --preset <name>          Use the settings of a benchmark: mipnerf360, tanks-temples,
+                           deep-blending or nerf-synthetic
+  --region <x0,y0,z0,x1,y1,z1>
+                           Only train the splats in this box, on the views that see it, eg.
+                           to fix one area of a model given with --resume or an init.ply
+  --heal <N>               Seed N new splats in the --region first, to fill a hole or
+                           repair an artifact of the model with a short run
+  --freeze <group>[:<from>[:<until>]]
+                           Keep means, rotations, scales, opacity or sh fixed, from and until
+                           the given steps. Can be given several times, eg. --freeze means
+                           --freeze scales to only train the colors of a model
+  --perceptual-weights <file.safetensors>
+                           VGG16 weights for the perceptual loss of a preset or script,
+                           instead of downloading them
+
+Convert options:
+  --max-frames <N>         Only convert the first N frames
+  --max-resolution <N>     Downscale images to at most N pixels
+  --eval-split-every <N>   Split off every Nth view as an eval view
+  --random-eval-split      Split off as many eval views at random instead
+  --vignette               Correct the vignetting of the images
+  --white-balance          Normalize the white balance of the images
+
+Simplify options:
+  --target-count <N>       Number of splats to keep at most
+  --dataset <path>         Report the PSNR on these training views before and after
+  --coordinates <name>     Write the output for brush, blender, unity or unreal
+
+Distill options:
+  --target-count <N>       Number of splats of the output at most
+  --steps <N>              Training steps, 5000 by default
+  --views <N>              Views rendered around the teacher, 256 by default
+  --resolution <N>         Width and height of the views, 512 by default
+  --coordinates <name>     Write the output for brush, blender, unity or unreal
+
+Render options:
+  --ground-truth           Also write the ground truth images
+  --diff                   Also write the difference between the renders and ground truth
+
+Augment options:
+  --views-per-pair <N>     New views between every two training views, 2 by default
+  --jitter <F>             Move views off the trajectory by at most this fraction of the
+                           scene size, 0.05 by default
+  --max-angle <DEGREES>    Turn views off the trajectory by at most this angle, 10 by default
+
+Survey options:
+  --range <F>              Distance that is fully red (behind) or blue (in front of the
+                           reference) in the renders, 0.1 by default
+  --tolerance <F>          Distances counted as matching the reference, 0.02 by default
+  --max-distance <F>       Ignore points further from the reference, eg. outside the survey
+
+Chunks options:
+  --max-splats <N>         Number of splats per chunk at most, 65536 by default
+  --octree                 Split into an octree of chunks with levels of detail, for
+                           viewers that stream large scenes
+  --lod-levels <N>         Levels of detail per octree chunk including the full one, 3 by
+                           default
+  --coordinates <name>     Write the output for brush, blender, unity or unreal";
+
+pub enum Command {
+    Help,
+    /// Start the viewer, with the session saved at this path and on the adapter matching this
+    /// name, see [`crate::adapter::select_adapter`].
+    View {
+        session: Option<PathBuf>,
+        adapter: Option<String>,
+        /// A script of viewer actions to run once the viewer started, see [`crate::actions`].
+        script: Option<PathBuf>,
+    },
+    /// Benchmark all adapters and remember the fastest.
+    Adapters,
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        load_args: LoadDatasetArgs,
+    },
+    Simplify {
+        input: PathBuf,
+        output: PathBuf,
+        target_count: usize,
+        dataset: Option<PathBuf>,
+        coordinates: CoordinateConvention,
+    },
+    /// Write a ply from coarse to fine detail, see [`brush_dataset::progressive_export`].
+    Progressive {
+        input: PathBuf,
+        output: PathBuf,
+        coordinates: CoordinateConvention,
+    },
+    /// Train a small model on renders of a large one, see [`brush_dataset::distill`].
+    Distill {
+        input: PathBuf,
+        output: PathBuf,
+        target_count: usize,
+        steps: u32,
+        views: DistillViews,
+        coordinates: CoordinateConvention,
+    },
+    /// Train headless, optionally serving snapshots to remote viewers.
+    Train {
+        inputs: Vec<PathBuf>,
+        serve: Option<String>,
+        output: Option<PathBuf>,
+        resume: Option<PathBuf>,
+        fsync: bool,
+        /// Memory budgets for the images, in megabytes, see
+        /// [`LoadDatasetArgs::host_image_budget_mb`] and [`TrainConfig::gpu_image_budget_mb`].
+        image_memory: Option<u32>,
+        gpu_image_memory: Option<u32>,
+        /// A Rhai script with callbacks while training.
+        script: Option<PathBuf>,
+        /// Settings of a benchmark, see [`brush_dataset::presets`].
+        preset: Option<Preset>,
+        /// Only train this region, see [`TrainConfig::refine_region`].
+        region: Option<BoundingBox>,
+        /// Splats to seed in the region, see [`TrainConfig::heal_seeds`].
+        heal: usize,
+        /// Parameters to keep fixed, see [`TrainConfig::freeze`].
+        freeze: Vec<FreezeRule>,
+        /// See [`TrainConfig::perceptual_weights`].
+        perceptual_weights: Option<PathBuf>,
+    },
+    Chunks {
+        input: PathBuf,
+        output: PathBuf,
+        max_splats: usize,
+        coordinates: CoordinateConvention,
+        /// Levels of detail of an octree export, `None` for flat chunks.
+        octree_levels: Option<usize>,
+    },
+    /// Print the metadata of a model, see [`brush_dataset::model_info`].
+    Info {
+        input: PathBuf,
+    },
+    /// Render the views of a dataset from a model.
+    Render {
+        model: PathBuf,
+        dataset: PathBuf,
+        output: PathBuf,
+        options: RenderViewsOptions,
+    },
+    /// Compare two models on the views of a dataset.
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+        dataset: PathBuf,
+    },
+    /// Render new views of a model into a synthetic dataset.
+    Augment {
+        model: PathBuf,
+        dataset: PathBuf,
+        output: PathBuf,
+        config: NovelViewConfig,
+    },
+    /// Compare the geometry of a model to a reference survey.
+    Survey {
+        model: PathBuf,
+        dataset: PathBuf,
+        reference: PathBuf,
+        output: PathBuf,
+        options: SurveyDiffOptions,
+    },
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> anyhow::Result<T> {
+    value
+        .and_then(|v| v.parse().ok())
+        .with_context(|| format!("{flag} expects a number.\n\n{USAGE}"))
+}
+
+/// Parse the command line arguments, without the executable name. Returns `None` when
+/// no command is given and the viewer should start.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> anyhow::Result<Option<Command>> {
+    let mut args = args.into_iter();
+
+    let Some(command) = args.next() else {
+        return Ok(None);
+    };
+
+    match command.as_str() {
+        "convert" => {
+            let mut positional = vec![];
+            let mut load_args = LoadDatasetArgs::default();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--max-frames" => {
+                        load_args.max_frames = Some(parse_value(&arg, args.next())?);
+                    }
+                    "--max-resolution" => {
+                        load_args.max_resolution = Some(parse_value(&arg, args.next())?);
+                    }
+                    "--eval-split-every" => {
+                        load_args.eval_split_every = Some(parse_value(&arg, args.next())?);
+                    }
+                    "--random-eval-split" => load_args.random_eval_split = true,
+                    "--vignette" => load_args.photometric.vignette = true,
+                    "--white-balance" => load_args.photometric.white_balance = true,
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [input, output] = <[PathBuf; 2]>::try_from(positional)
+                .map_err(|_e| anyhow::anyhow!("convert expects an input and output.\n\n{USAGE}"))?;
+
+            Ok(Some(Command::Convert {
+                input,
+                output,
+                load_args,
+            }))
+        }
+        "simplify" => {
+            let mut positional = vec![];
+            let mut target_count = None;
+            let mut dataset = None;
+            let mut coordinates = CoordinateConvention::BRUSH;
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--target-count" => target_count = Some(parse_value(&arg, args.next())?),
+                    "--dataset" => {
+                        dataset =
+                            Some(PathBuf::from(args.next().with_context(|| {
+                                format!("--dataset expects a path.\n\n{USAGE}")
+                            })?));
+                    }
+                    "--coordinates" => {
+                        let name = args.next().unwrap_or_default();
+                        coordinates = name
+                            .parse()
+                            .with_context(|| format!("Invalid --coordinates.\n\n{USAGE}"))?;
+                    }
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [input, output] = <[PathBuf; 2]>::try_from(positional).map_err(|_e| {
+                anyhow::anyhow!("simplify expects an input and output.\n\n{USAGE}")
+            })?;
+            let target_count = target_count
+                .with_context(|| format!("simplify expects a --target-count.\n\n{USAGE}"))?;
+
+            Ok(Some(Command::Simplify {
+                input,
+                output,
+                target_count,
+                dataset,
+                coordinates,
+            }))
+        }
+        "progressive" => {
+            let mut positional = vec![];
+            let mut coordinates = CoordinateConvention::BRUSH;
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--coordinates" => {
+                        let name = args.next().unwrap_or_default();
+                        coordinates = name
+                            .parse()
+                            .with_context(|| format!("Invalid --coordinates.\n\n{USAGE}"))?;
+                    }
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [input, output] = <[PathBuf; 2]>::try_from(positional).map_err(|_e| {
+                anyhow::anyhow!("progressive expects an input and output.\n\n{USAGE}")
+            })?;
+
+            Ok(Some(Command::Progressive {
+                input,
+                output,
+                coordinates,
+            }))
+        }
+        "distill" => {
+            let mut positional = vec![];
+            let mut target_count = None;
+            let mut steps = 5000;
+            let mut views = DistillViews::default();
+            let mut coordinates = CoordinateConvention::BRUSH;
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--target-count" => target_count = Some(parse_value(&arg, args.next())?),
+                    "--steps" => steps = parse_value(&arg, args.next())?,
+                    "--views" => views.num_views = parse_value(&arg, args.next())?,
+                    "--resolution" => views.resolution = parse_value(&arg, args.next())?,
+                    "--coordinates" => {
+                        let name = args.next().unwrap_or_default();
+                        coordinates = name
+                            .parse()
+                            .with_context(|| format!("Invalid --coordinates.\n\n{USAGE}"))?;
+                    }
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [input, output] = <[PathBuf; 2]>::try_from(positional).map_err(|_e| {
+                anyhow::anyhow!("distill expects a teacher and output.\n\n{USAGE}")
+            })?;
+            let target_count = target_count
+                .with_context(|| format!("distill expects a --target-count.\n\n{USAGE}"))?;
+
+            Ok(Some(Command::Distill {
+                input,
+                output,
+                target_count,
+                steps,
+                views,
+                coordinates,
+            }))
+        }
+        "chunks" => {
+            let mut positional = vec![];
+            let mut max_splats = chunk_export::DEFAULT_SPLATS_PER_CHUNK;
+            let mut coordinates = CoordinateConvention::BRUSH;
+            let mut octree = false;
+            let mut lod_levels = octree_export::DEFAULT_LOD_LEVELS;
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--max-splats" => max_splats = parse_value(&arg, args.next())?,
+                    "--octree" => octree = true,
+                    "--lod-levels" => lod_levels = parse_value(&arg, args.next())?,
+                    "--coordinates" => {
+                        let name = args.next().unwrap_or_default();
+                        coordinates = name
+                            .parse()
+                            .with_context(|| format!("Invalid --coordinates.\n\n{USAGE}"))?;
+                    }
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [input, output] = <[PathBuf; 2]>::try_from(positional)
+                .map_err(|_e| anyhow::anyhow!("chunks expects an input and output.\n\n{USAGE}"))?;
+
+            Ok(Some(Command::Chunks {
+                input,
+                output,
+                max_splats,
+                coordinates,
+                octree_levels: octree.then_some(lod_levels),
+            }))
+        }
+        "train" => {
+            let mut inputs = vec![];
+            let mut serve = None;
+            let mut output = None;
+            let mut resume = None;
+            let mut fsync = false;
+            let mut image_memory = None;
+            let mut gpu_image_memory = None;
+            let mut script = None;
+            let mut preset = None;
+            let mut region = None;
+            let mut heal = 0;
+            let mut freeze = vec![];
+            let mut perceptual_weights = None;
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--serve" => {
+                        serve =
+                            Some(args.next().with_context(|| {
+                                format!("--serve expects an address.\n\n{USAGE}")
+                            })?);
+                    }
+                    "--output" => {
+                        output =
+                            Some(PathBuf::from(args.next().with_context(|| {
+                                format!("--output expects a path.\n\n{USAGE}")
+                            })?));
+                    }
+                    "--resume" => {
+                        resume = Some(PathBuf::from(args.next().with_context(|| {
+                            format!("--resume expects a checkpoint.\n\n{USAGE}")
+                        })?));
+                    }
+                    "--fsync" => fsync = true,
+                    "--script" => {
+                        script =
+                            Some(PathBuf::from(args.next().with_context(|| {
+                                format!("--script expects a file.\n\n{USAGE}")
+                            })?));
+                    }
+                    "--preset" => {
+                        let name = args.next().unwrap_or_default();
+                        preset = Some(
+                            name.parse()
+                                .with_context(|| format!("Invalid --preset.\n\n{USAGE}"))?,
+                        );
+                    }
+                    "--region" => {
+                        let corners: Vec<f32> = args
+                            .next()
+                            .unwrap_or_default()
+                            .split(',')
+                            .map(|v| v.trim().parse())
+                            .collect::<Result<_, _>>()
+                            .with_context(|| format!("Invalid --region.\n\n{USAGE}"))?;
+                        let [x0, y0, z0, x1, y1, z1] = corners[..] else {
+                            anyhow::bail!("--region expects 6 numbers.\n\n{USAGE}");
+                        };
+                        let (a, b) = (glam::vec3(x0, y0, z0), glam::vec3(x1, y1, z1));
+                        region = Some(BoundingBox::from_min_max(a.min(b), a.max(b)));
+                    }
+                    "--heal" => heal = parse_value(&arg, args.next())?,
+                    "--freeze" => {
+                        let rule = args.next().unwrap_or_default();
+                        freeze.push(
+                            rule.parse()
+                                .with_context(|| format!("Invalid --freeze.\n\n{USAGE}"))?,
+                        );
+                    }
+                    "--image-memory" => image_memory = Some(parse_value(&arg, args.next())?),
+                    "--gpu-image-memory" => {
+                        gpu_image_memory = Some(parse_value(&arg, args.next())?);
+                    }
+                    "--perceptual-weights" => {
+                        perceptual_weights =
+                            Some(PathBuf::from(args.next().with_context(|| {
+                                format!("--perceptual-weights expects a file.\n\n{USAGE}")
+                            })?));
+                    }
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => inputs.push(PathBuf::from(arg)),
+                }
+            }
+
+            if inputs.is_empty() {
+                anyhow::bail!("train expects a dataset.\n\n{USAGE}");
+            }
+            Ok(Some(Command::Train {
+                inputs,
+                serve,
+                output,
+                resume,
+                fsync,
+                image_memory,
+                gpu_image_memory,
+                script,
+                preset,
+                region,
+                heal,
+                freeze,
+                perceptual_weights,
+            }))
+        }
+        "render" => {
+            let mut positional = vec![];
+            let mut options = RenderViewsOptions::default();
+
+            for arg in args {
+                match arg.as_str() {
+                    "--ground-truth" => options.ground_truth = true,
+                    "--diff" => options.diff = true,
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [model, dataset, output] = <[PathBuf; 3]>::try_from(positional).map_err(|_e| {
+                anyhow::anyhow!("render expects a model, dataset and output.\n\n{USAGE}")
+            })?;
+
+            Ok(Some(Command::Render {
+                model,
+                dataset,
+                output,
+                options,
+            }))
+        }
+        "augment" => {
+            let mut positional = vec![];
+            let mut config = NovelViewConfig::default();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--views-per-pair" => config.views_per_pair = parse_value(&arg, args.next())?,
+                    "--jitter" => config.jitter = parse_value(&arg, args.next())?,
+                    "--max-angle" => config.max_angle = parse_value(&arg, args.next())?,
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [model, dataset, output] = <[PathBuf; 3]>::try_from(positional).map_err(|_e| {
+                anyhow::anyhow!("augment expects a model, dataset and output.\n\n{USAGE}")
+            })?;
+
+            Ok(Some(Command::Augment {
+                model,
+                dataset,
+                output,
+                config,
+            }))
+        }
+        "survey" => {
+            let mut positional = vec![];
+            let mut options = SurveyDiffOptions::default();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--range" => options.range = parse_value(&arg, args.next())?,
+                    "--tolerance" => options.tolerance = parse_value(&arg, args.next())?,
+                    "--max-distance" => {
+                        options.max_distance = Some(parse_value(&arg, args.next())?);
+                    }
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [model, dataset, reference, output] = <[PathBuf; 4]>::try_from(positional)
+                .map_err(|_e| {
+                    anyhow::anyhow!(
+                        "survey expects a model, dataset, reference and output.\n\n{USAGE}"
+                    )
+                })?;
+
+            Ok(Some(Command::Survey {
+                model,
+                dataset,
+                reference,
+                output,
+                options,
+            }))
+        }
+        "diff" => {
+            let mut positional = vec![];
+            let mut dataset = None;
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--dataset" => {
+                        dataset =
+                            Some(PathBuf::from(args.next().with_context(|| {
+                                format!("--dataset expects a path.\n\n{USAGE}")
+                            })?));
+                    }
+                    _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}\n\n{USAGE}"),
+                    _ => positional.push(PathBuf::from(arg)),
+                }
+            }
+
+            let [a, b] = <[PathBuf; 2]>::try_from(positional)
+                .map_err(|_e| anyhow::anyhow!("diff expects two models.\n\n{USAGE}"))?;
+            let dataset =
+                dataset.with_context(|| format!("diff expects a --dataset.\n\n{USAGE}"))?;
+
+            Ok(Some(Command::Diff { a, b, dataset }))
+        }
+        "info" => {
+            let input = args
+                .next()
+                .with_context(|| format!("info expects a model.\n\n{USAGE}"))?;
+            Ok(Some(Command::Info {
+                input: PathBuf::from(input),
+            }))
+        }
+        "--session" | "--adapter" | "--script" => {
+            let mut session = None;
+            let mut adapter = None;
+            let mut script = None;
+            let mut arg = Some(command);
+            while let Some(flag) = arg {
+                match flag.as_str() {
+                    "--session" => {
+                        let path = args
+                            .next()
+                            .with_context(|| format!("--session expects a path.\n\n{USAGE}"))?;
+                        session = Some(PathBuf::from(path));
+                    }
+                    "--adapter" => {
+                        adapter =
+                            Some(args.next().with_context(|| {
+                                format!("--adapter expects a name.\n\n{USAGE}")
+                            })?);
+                    }
+                    "--script" => {
+                        let path = args
+                            .next()
+                            .with_context(|| format!("--script expects a file.\n\n{USAGE}"))?;
+                        script = Some(PathBuf::from(path));
+                    }
+                    _ => anyhow::bail!("Unknown option {flag}\n\n{USAGE}"),
+                }
+                arg = args.next();
+            }
+            Ok(Some(Command::View {
+                session,
+                adapter,
+                script,
+            }))
+        }
+        "adapters" => Ok(Some(Command::Adapters)),
+        "--help" | "-h" | "help" => Ok(Some(Command::Help)),
+        _ => anyhow::bail!("Unknown command {command}\n\n{USAGE}"),
+    }
+}
+
+// Redraw the progress bar in place, and move on to a new line once a stage is done.
+// Drawn on stderr so progress doesn't mix with results printed to stdout.
+#[allow(clippy::print_stderr)]
+fn draw_progress(progress: &Progress) {
+    eprint!("\r\x1b[2K{}", brush_tasks::progress_bar(progress, 30));
+    if progress.fraction >= 1.0 {
+        eprintln!();
+    }
+}
+
+// Draw the progress of commands that don't run a process.
+fn print_progress() -> ProgressSender {
+    let (progress, mut reports) = ProgressSender::channel();
+    tokio::spawn(async move {
+        while let Some(report) = reports.recv().await {
+            draw_progress(&report);
+        }
+    });
+    progress
+}
+
+async fn open_vfs(path: &Path) -> anyhow::Result<BrushVfs> {
+    if path.is_dir() {
+        BrushVfs::from_directory(path).await
+    } else {
+        let data = std::fs::read(path).with_context(|| format!("Failed to read {path:?}"))?;
+        BrushVfs::from_zip_reader(std::io::Cursor::new(data))
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+async fn convert(input: &Path, output: &Path, load_args: &LoadDatasetArgs) -> anyhow::Result<()> {
+    let device = default_device();
+    let vfs = open_vfs(input).await?;
+    let load_args = LoadDatasetArgs {
+        progress: print_progress(),
+        ..load_args.clone()
+    };
+
+    let (mut splat_stream, mut data_stream) =
+        brush_dataset::load_dataset::<Wgpu>(vfs, &load_args, &device).await?;
+
+    let mut init_splats = None;
+    while let Some(message) = splat_stream.next().await {
+        init_splats = Some(message?.splats);
+    }
+
+    let mut dataset = Dataset::empty();
+    while let Some(d) = data_stream.next().await {
+        dataset = d?;
+    }
+
+    log::info!(
+        "Converting {} train and {} eval views",
+        dataset.train.views.len(),
+        dataset.eval.as_ref().map_or(0, |e| e.views.len())
+    );
+
+    let mut files = dataset_export::dataset_to_nerfstudio(&dataset)?;
+
+    // Keep the initial point cloud, the nerfstudio loader picks up an init.ply.
+    if let Some(splats) = init_splats {
+        files.push((
+            PathBuf::from("init.ply"),
+            splat_export::splat_to_ply(splats).await?,
+        ));
+    }
+
+    write_files(output, files)
+}
+
+fn write_files(output: &Path, files: Vec<(PathBuf, Vec<u8>)>) -> anyhow::Result<()> {
+    for (path, data) in files {
+        let path = output.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        rrfd::write_atomic(&path, &data, false)
+            .with_context(|| format!("Failed to write {path:?}"))?;
+    }
+    Ok(())
+}
+
+async fn read_ply(input: &Path, device: &WgpuDevice) -> anyhow::Result<Splats<Wgpu>> {
+    read_ply_on::<Wgpu>(input, device).await
+}
+
+async fn read_ply_on<B: Backend>(input: &Path, device: &B::Device) -> anyhow::Result<Splats<B>> {
+    let data = std::fs::read(input).with_context(|| format!("Failed to read {input:?}"))?;
+    let mut splat_stream =
+        splat_import::load_splat_from_ply::<_, B>(Cursor::new(data), None, device.clone());
+    let mut splats = None;
+    while let Some(message) = splat_stream.next().await {
+        splats = Some(message?.splats);
+    }
+    splats.context("No splats found in input")
+}
+
+async fn read_dataset(path: &Path, device: &WgpuDevice) -> anyhow::Result<Dataset> {
+    let vfs = open_vfs(path).await?;
+    let load_args = LoadDatasetArgs {
+        progress: print_progress(),
+        ..Default::default()
+    };
+    let (_, mut data_stream) = brush_dataset::load_dataset::<Wgpu>(vfs, &load_args, device).await?;
+    let mut dataset = Dataset::empty();
+    while let Some(d) = data_stream.next().await {
+        dataset = d?;
+    }
+    Ok(dataset)
+}
+
+async fn train_psnr(splats: &Splats<Wgpu>, scene: &Scene, device: &WgpuDevice) -> f32 {
+    // A fixed subset of views, so the before and after numbers are comparable.
+    let mut rng = StdRng::seed_from_u64(0);
+    let progress = print_progress();
+    let eval = eval_stats(
+        splats.clone(),
+        scene,
+        Some(16),
+        false,
+        None,
+        &mut rng,
+        device,
+        &progress,
+    )
+    .await;
+    eval.samples.iter().map(|s| s.psnr).sum::<f32>() / eval.samples.len().max(1) as f32
+}
+
+async fn simplify(
+    input: &Path,
+    output: &Path,
+    target_count: usize,
+    dataset: Option<&Path>,
+    coordinates: CoordinateConvention,
+) -> anyhow::Result<()> {
+    let device = default_device();
+    let splats = read_ply(input, &device).await?;
+
+    let train_scene = if let Some(path) = dataset {
+        Some(read_dataset(path, &device).await?.train)
+    } else {
+        None
+    };
+
+    if let Some(scene) = &train_scene {
+        let psnr = train_psnr(&splats, scene, &device).await;
+        println!("{} splats, PSNR {psnr:.2}", splats.num_splats());
+    }
+
+    let simplified = simplify_splats(splats, target_count).await?;
+
+    if let Some(scene) = &train_scene {
+        let psnr = train_psnr(&simplified, scene, &device).await;
+        println!("{} splats, PSNR {psnr:.2}", simplified.num_splats());
+    }
+
+    let data = splat_export::splat_to_ply_in_convention(simplified, &[], coordinates).await?;
+    rrfd::write_atomic(output, &data, false)
+        .with_context(|| format!("Failed to write {output:?}"))?;
+    Ok(())
+}
+
+async fn progressive(
+    input: &Path,
+    output: &Path,
+    coordinates: CoordinateConvention,
+) -> anyhow::Result<()> {
+    let splats = read_ply(input, &default_device()).await?;
+    let data =
+        progressive_export::splat_to_progressive_ply(splats, coordinates, &ModelInfo::default())
+            .await?;
+    rrfd::write_atomic(output, &data, false)
+        .with_context(|| format!("Failed to write {output:?}"))?;
+    Ok(())
+}
+
+async fn distill(
+    input: &Path,
+    output: &Path,
+    target_count: usize,
+    steps: u32,
+    views: DistillViews,
+    coordinates: CoordinateConvention,
+) -> anyhow::Result<()> {
+    let device = default_device();
+    let teacher = read_ply_on::<Autodiff<Wgpu>>(input, &device).await?;
+    let points: Vec<_> = teacher
+        .means
+        .val()
+        .into_data_async()
+        .await
+        .to_vec::<f32>()
+        .map_err(|e| anyhow::anyhow!("Failed to read the teacher {e:?}"))?
+        .chunks_exact(3)
+        .map(|p| glam::vec3(p[0], p[1], p[2]))
+        .collect();
+
+    let progress = print_progress();
+    let cameras = distill::orbit_cameras(&points, &views);
+    let scene =
+        distill::render_teacher_views(&teacher.valid(), cameras, views.resolution, &progress).await;
+
+    // The student starts as a simplified teacher. It isn't refined, so it keeps at most the
+    // target count of splats.
+    let mut student = simplify_splats(teacher, target_count).await?;
+    println!(
+        "Distilling {} splats into {}",
+        points.len(),
+        student.num_splats()
+    );
+
+    let config = TrainConfig::default();
+    let mut loader = SceneLoader::new(
+        &scene,
+        scene.extent(&points),
+        1,
+        config.seed,
+        config.view_sampling.clone(),
+        config.gpu_image_budget_mb,
+        &device,
+    );
+    let mut trainer = SplatTrainer::new(&student, &config, &device);
+    trainer.prepare_scene(&scene);
+    for iter in 0..steps {
+        let batch = loader.next_batch(config.resolution_level(iter)).await;
+        let (splats, stats) = trainer.step(iter, batch, student).await;
+        loader.report_loss(&stats.gt_views, stats.loss);
+        student = splats;
+        progress.report("Distilling", iter as usize + 1, steps as usize);
+    }
+
+    let student = student.valid();
+    let psnr = train_psnr(&student, &scene, &device).await;
+    println!(
+        "{} splats, PSNR {psnr:.2} to the teacher",
+        student.num_splats()
+    );
+
+    let data = splat_export::splat_to_ply_in_convention(student, &[], coordinates).await?;
+    rrfd::write_atomic(output, &data, false)
+        .with_context(|| format!("Failed to write {output:?}"))?;
+    Ok(())
+}
+
+async fn chunks(
+    input: &Path,
+    output: &Path,
+    max_splats: usize,
+    coordinates: CoordinateConvention,
+    octree_levels: Option<usize>,
+) -> anyhow::Result<()> {
+    let splats = read_ply(input, &default_device()).await?;
+    let files = match octree_levels {
+        Some(levels) => {
+            octree_export::splats_to_octree(splats, max_splats, levels, coordinates).await?
+        }
+        None => chunk_export::splats_to_chunks(splats, max_splats, coordinates).await?,
+    };
+    // Besides the chunks there's the index and collision boxes.
+    println!("Wrote {} chunk files", files.len() - 2);
+    write_files(output, files)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn train(
+    inputs: Vec<PathBuf>,
+    serve: Option<String>,
+    output: Option<PathBuf>,
+    resume: Option<PathBuf>,
+    fsync: bool,
+    load_args: LoadDatasetArgs,
+    train_config: TrainConfig,
+    script: Option<String>,
+) -> anyhow::Result<()> {
+    let mut sources = inputs.into_iter().map(DataSource::Path);
+    let args = ProcessArgs {
+        source: sources.next().context("No dataset given")?,
+        captures: sources.collect(),
+        load_args,
+        init_args: LoadInitArgs {
+            resume,
+            ..Default::default()
+        },
+        train_config,
+        save_args: SaveArgs {
+            output_dir: output,
+            sync_writes: fsync,
+            ..Default::default()
+        },
+        script,
+        heal: None,
+    };
+    let mut process = start_process(args, default_device());
+
+    let snapshots = SnapshotState::default();
+    if let Some(addr) = serve {
+        let snapshots = snapshots.clone();
+        let control = process.control.clone();
+        tokio::spawn(async move {
+            if let Err(e) = remote::serve(&remote::serve_address(&addr), snapshots, control).await {
+                log::error!("{e:?}");
+            }
+        });
+    }
+
+    while let Some(message) = process.messages.recv().await {
+        match message {
+            ProcessMessage::TrainStep { splats, iter, .. } => {
+                if iter % 1000 == 0 {
+                    println!("Step {iter}, {} splats", splats.num_splats());
+                }
+                snapshots.update(iter, *splats).await;
+            }
+            ProcessMessage::EvalResult { iter, eval } => {
+                let psnr = eval.samples.iter().map(|s| s.psnr).sum::<f32>()
+                    / eval.samples.len().max(1) as f32;
+                println!("Step {iter}, eval PSNR {psnr:.2}");
+            }
+            ProcessMessage::Progress(progress) => draw_progress(&progress),
+            ProcessMessage::Error(e) => return Err(e),
+            ProcessMessage::ViewerAction(action) => {
+                log::warn!("Skipping viewer action {action:?}, there's no viewer while training");
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Mean of the largest axis of the splats, and their mean opacity.
+async fn splat_sizes(splats: &Splats<Wgpu>) -> (f32, f32) {
+    let size = splats.scales().max_dim(1).mean();
+    let opacity = splats.opacity().mean();
+    (
+        size.into_scalar_async().await.elem::<f32>(),
+        opacity.into_scalar_async().await.elem::<f32>(),
+    )
+}
+
+async fn render(
+    model: &Path,
+    dataset: &Path,
+    output: &Path,
+    options: RenderViewsOptions,
+) -> anyhow::Result<()> {
+    let device = default_device();
+    let splats = read_ply(model, &device).await?;
+    let dataset = read_dataset(dataset, &device).await?;
+
+    let files = render_views::render_views(splats, &dataset, options, print_progress());
+    let mut files = std::pin::pin!(files);
+    while let Some(file) = files.next().await {
+        write_files(output, vec![file?])?;
+    }
+    Ok(())
+}
+
+async fn augment(
+    model: &Path,
+    dataset: &Path,
+    output: &Path,
+    config: NovelViewConfig,
+) -> anyhow::Result<()> {
+    let device = default_device();
+    let splats = read_ply(model, &device).await?;
+    let dataset = read_dataset(dataset, &device).await?;
+
+    let files = novel_views::render_novel_views(splats, &dataset.train, &config, print_progress());
+    let mut files = std::pin::pin!(files);
+    while let Some(file) = files.next().await {
+        write_files(output, vec![file?])?;
+    }
+    Ok(())
+}
+
+async fn read_reference(path: &Path, device: &WgpuDevice) -> anyhow::Result<ReferenceGeometry> {
+    let is_obj = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("obj"));
+    if is_obj {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+        return ReferenceGeometry::from_obj(&text);
+    }
+
+    // Point clouds are read like splats, only the positions are used.
+    let points = read_ply(path, device).await?;
+    let means = points
+        .means
+        .val()
+        .into_data_async()
+        .await
+        .to_vec::<f32>()
+        .map_err(|e| anyhow::anyhow!("Failed to read points {e:?}"))?;
+    let points = means
+        .chunks_exact(3)
+        .map(|p| glam::vec3(p[0], p[1], p[2]))
+        .collect();
+    Ok(ReferenceGeometry::from_points(points))
+}
+
+async fn survey(
+    model: &Path,
+    dataset: &Path,
+    reference: &Path,
+    output: &Path,
+    options: SurveyDiffOptions,
+) -> anyhow::Result<()> {
+    let device = default_device();
+    let splats = read_ply(model, &device).await?;
+    let dataset = read_dataset(dataset, &device).await?;
+    let reference = read_reference(reference, &device).await?;
+    log::info!(
+        "Comparing to a reference {} of {} vertices",
+        if reference.is_mesh() {
+            "mesh"
+        } else {
+            "point cloud"
+        },
+        reference.vertices().len()
+    );
+
+    let files = survey_diff::survey_diff(
+        splats,
+        &dataset.train,
+        Arc::new(reference),
+        options,
+        print_progress(),
+    );
+    let mut files = std::pin::pin!(files);
+    let mut report = None;
+    while let Some(file) = files.next().await {
+        let (path, data) = file?;
+        if path.as_os_str() == "deviation.json" {
+            report = Some(serde_json::from_slice::<serde_json::Value>(&data)?);
+        }
+        write_files(output, vec![(path, data)])?;
+    }
+
+    if let Some(all) = report.as_ref().map(|r| &r["all"]) {
+        println!("{:<24} {:>12}", "Samples", all["samples"]);
+        for (name, key) in [
+            ("Mean", "mean"),
+            ("Std", "std"),
+            ("Mean absolute", "mean_abs"),
+            ("RMS", "rms"),
+            ("Median", "median"),
+            ("95% absolute", "p95_abs"),
+            ("Max absolute", "max_abs"),
+        ] {
+            println!(
+                "{name:<24} {:>+12.4}",
+                all[key].as_f64().unwrap_or_default()
+            );
+        }
+        println!(
+            "{:<24} {:>11.1}%",
+            format!("Within {}", options.tolerance),
+            all["within_tolerance"].as_f64().unwrap_or_default() * 100.0
+        );
+    }
+    Ok(())
+}
+
+async fn diff(a: &Path, b: &Path, dataset: &Path) -> anyhow::Result<()> {
+    let device = default_device();
+    let splats_a = read_ply(a, &device).await?;
+    let splats_b = read_ply(b, &device).await?;
+
+    // Compare on the eval views, or the training views when the dataset has none.
+    let dataset = read_dataset(dataset, &device).await?;
+    let scene = dataset.eval.unwrap_or(dataset.train);
+    anyhow::ensure!(!scene.views.is_empty(), "The dataset has no views");
+
+    // All views are used, so the random number generator isn't used.
+    let mut rng = StdRng::seed_from_u64(0);
+    let progress = print_progress();
+    let eval_a = eval_stats(
+        splats_a.clone(),
+        &scene,
+        None,
+        false,
+        None,
+        &mut rng,
+        &device,
+        &progress,
+    )
+    .await;
+    let eval_b = eval_stats(
+        splats_b.clone(),
+        &scene,
+        None,
+        false,
+        None,
+        &mut rng,
+        &device,
+        &progress,
+    )
+    .await;
+
+    println!(
+        "{:<32} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+        "View", "PSNR A", "PSNR B", "Delta", "SSIM A", "SSIM B", "Delta"
+    );
+    for (sa, sb) in eval_a.samples.iter().zip(&eval_b.samples) {
+        println!(
+            "{:<32} {:>8.2} {:>8.2} {:>+8.2} {:>8.4} {:>8.4} {:>+8.4}",
+            sa.view.name,
+            sa.psnr,
+            sb.psnr,
+            sb.psnr - sa.psnr,
+            sa.ssim,
+            sb.ssim,
+            sb.ssim - sa.ssim
+        );
+    }
+
+    let n = eval_a.samples.len() as f32;
+    let mean = |eval: &EvalStats<Wgpu>, f: fn(&EvalView<Wgpu>) -> f32| {
+        eval.samples.iter().map(f).sum::<f32>() / n
+    };
+    let (psnr_a, psnr_b) = (mean(&eval_a, |s| s.psnr), mean(&eval_b, |s| s.psnr));
+    let (ssim_a, ssim_b) = (mean(&eval_a, |s| s.ssim), mean(&eval_b, |s| s.ssim));
+    println!(
+        "{:<32} {psnr_a:>8.2} {psnr_b:>8.2} {:>+8.2} {ssim_a:>8.4} {ssim_b:>8.4} {:>+8.4}",
+        "Mean",
+        psnr_b - psnr_a,
+        ssim_b - ssim_a
+    );
+
+    let (size_a, opacity_a) = splat_sizes(&splats_a).await;
+    let (size_b, opacity_b) = splat_sizes(&splats_b).await;
+    println!();
+    println!("{:<32} {:>12} {:>12}", "", "A", "B");
+    println!(
+        "{:<32} {:>12} {:>12}",
+        "Splats",
+        splats_a.num_splats(),
+        splats_b.num_splats()
+    );
+    println!("{:<32} {size_a:>12.5} {size_b:>12.5}", "Mean size");
+    println!("{:<32} {opacity_a:>12.3} {opacity_b:>12.3}", "Mean opacity");
+    Ok(())
+}
+
+async fn adapters() -> anyhow::Result<()> {
+    let results = crate::adapter::benchmark_adapters().await?;
+    if results.is_empty() {
+        anyhow::bail!("No adapters to benchmark");
+    }
+    for result in &results {
+        println!(
+            "{}: sort {:.1}ms, rasterize {:.1}ms",
+            result.adapter, result.sort_ms, result.rasterize_ms
+        );
+    }
+    println!("The viewer will start on {}", results[0].adapter);
+    Ok(())
+}
+
+async fn info(input: &Path) -> anyhow::Result<()> {
+    let data = std::fs::read(input).with_context(|| format!("Failed to read {input:?}"))?;
+    let (num_splats, info) = if checkpoint::is_checkpoint(&data) {
+        checkpoint::read_checkpoint_info(&data)?
+    } else {
+        splat_import::read_ply_info(Cursor::new(data)).await?
+    };
+
+    println!("Splats:              {num_splats}");
+    match info {
+        Some(info) => println!("{info}"),
+        None => println!("No Brush metadata in this model"),
+    }
+    Ok(())
+}
+
+pub async fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Command::View { .. } => anyhow::bail!("The viewer has to be started from the main thread"),
+        Command::Convert {
+            input,
+            output,
+            load_args,
+        } => convert(&input, &output, &load_args).await,
+        Command::Simplify {
+            input,
+            output,
+            target_count,
+            dataset,
+            coordinates,
+        } => {
+            simplify(
+                &input,
+                &output,
+                target_count,
+                dataset.as_deref(),
+                coordinates,
+            )
+            .await
+        }
+        Command::Progressive {
+            input,
+            output,
+            coordinates,
+        } => progressive(&input, &output, coordinates).await,
+        Command::Distill {
+            input,
+            output,
+            target_count,
+            steps,
+            views,
+            coordinates,
+        } => distill(&input, &output, target_count, steps, views, coordinates).await,
+        Command::Chunks {
+            input,
+            output,
+            max_splats,
+            coordinates,
+            octree_levels,
+        } => chunks(&input, &output, max_splats, coordinates, octree_levels).await,
+        Command::Train {
+            inputs,
+            serve,
+            output,
+            resume,
+            fsync,
+            image_memory,
+            gpu_image_memory,
+            script,
+            preset,
+            region,
+            heal,
+            freeze,
+            perceptual_weights,
+        } => {
+            let mut load_args = LoadDatasetArgs {
+                host_image_budget_mb: image_memory,
+                ..Default::default()
+            };
+            let mut train_config = TrainConfig::default();
+            if let Some(mb) = gpu_image_memory {
+                train_config.gpu_image_budget_mb = mb;
+            }
+            if let Some(preset) = preset {
+                let scene = inputs[0]
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                log::info!("Using the {preset} preset for scene {scene}");
+                preset.apply(&scene, &mut load_args, &mut train_config);
+            }
+            anyhow::ensure!(
+                heal == 0 || region.is_some(),
+                "--heal needs a --region to seed the splats in.\n\n{USAGE}"
+            );
+            train_config.refine_region = region;
+            train_config.heal_seeds = heal;
+            train_config.freeze = freeze;
+            train_config.perceptual_weights = perceptual_weights;
+            let script = script
+                .map(|path| {
+                    std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read script {path:?}"))
+                })
+                .transpose()?;
+            train(
+                inputs,
+                serve,
+                output,
+                resume,
+                fsync,
+                load_args,
+                train_config,
+                script,
+            )
+            .await
+        }
+        Command::Info { input } => info(&input).await,
+        Command::Adapters => adapters().await,
+        Command::Diff { a, b, dataset } => diff(&a, &b, &dataset).await,
+        Command::Render {
+            model,
+            dataset,
+            output,
+            options,
+        } => render(&model, &dataset, &output, options).await,
+        Command::Augment {
+            model,
+            dataset,
+            output,
+            config,
+        } => augment(&model, &dataset, &output, config).await,
+        Command::Survey {
+            model,
+            dataset,
+            reference,
+            output,
+            options,
+        } => survey(&model, &dataset, &reference, &output, options).await,
+    }
+}
@@ -0,0 +1,149 @@
+//! The command palette, opened with Ctrl+P to run any viewer action by name, see
+//! [`crate::actions`].
+//!
+//! Typing filters the actions by their name and description. Enter runs the selected one, or
+//! what's typed when it has arguments, eg. `crop -1 -1 -1 1 1 1`.
+use egui::{Align2, Key, Modifiers};
+
+use crate::{
+    actions::{ActionQueue, Command, ViewerAction, COMMANDS},
+    i18n::tr,
+};
+
+#[derive(Default)]
+pub(crate) struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+    error: Option<String>,
+}
+
+impl CommandPalette {
+    // The commands matching the query, or the one being given arguments.
+    fn matches(&self) -> Vec<&'static Command> {
+        let query = self.query.trim_start().to_lowercase();
+        match query.split_once(char::is_whitespace) {
+            Some((name, _)) => COMMANDS.iter().filter(|c| c.name == name).collect(),
+            None => COMMANDS
+                .iter()
+                .filter(|c| {
+                    c.name.contains(&query) || tr(c.description).to_lowercase().contains(&query)
+                })
+                .collect(),
+        }
+    }
+
+    // Run a command, or start typing its arguments when it needs them.
+    fn choose(&mut self, command: &Command, actions: &ActionQueue) {
+        let has_args = self.query.trim().contains(char::is_whitespace);
+        if command.needs_args() && !has_args {
+            self.query = format!("{} ", command.name);
+            return;
+        }
+        let line = if has_args {
+            self.query.clone()
+        } else {
+            command.name.to_owned()
+        };
+        match ViewerAction::parse_line(&line) {
+            Ok(action) => {
+                actions.push(action);
+                self.open = false;
+            }
+            Err(e) => self.error = Some(format!("{e:#}")),
+        }
+    }
+
+    pub(crate) fn ui(&mut self, ctx: &egui::Context, actions: &ActionQueue) {
+        if ctx.input_mut(|r| r.consume_key(Modifiers::COMMAND, Key::P)) {
+            *self = Self {
+                open: !self.open,
+                ..Default::default()
+            };
+        }
+        if !self.open {
+            return;
+        }
+
+        // Take the keys the text field would otherwise use.
+        let (close, enter, step) = ctx.input_mut(|r| {
+            let step = if r.consume_key(Modifiers::NONE, Key::ArrowDown) {
+                1
+            } else if r.consume_key(Modifiers::NONE, Key::ArrowUp) {
+                -1
+            } else {
+                0
+            };
+            (
+                r.consume_key(Modifiers::NONE, Key::Escape),
+                r.consume_key(Modifiers::NONE, Key::Enter),
+                step,
+            )
+        });
+        if close {
+            self.open = false;
+            return;
+        }
+
+        let matches = self.matches();
+        self.selected = (self.selected as isize + step)
+            .clamp(0, matches.len().saturating_sub(1) as isize) as usize;
+        if enter {
+            if let Some(command) = matches.get(self.selected) {
+                self.choose(command, actions);
+            }
+        }
+
+        let mut clicked = None;
+        egui::Window::new(tr("Commands"))
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_TOP, egui::vec2(0.0, 40.0))
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text(tr("Type an action, eg. crop -1 -1 -1 1 1 1"))
+                        .desired_width(f32::INFINITY),
+                );
+                response.request_focus();
+                if response.changed() {
+                    self.selected = 0;
+                    self.error = None;
+                }
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        for (i, command) in matches.iter().enumerate() {
+                            let text = format!("{} {}", command.name, command.args);
+                            let row = ui
+                                .selectable_label(
+                                    i == self.selected,
+                                    egui::RichText::new(text).monospace(),
+                                )
+                                .on_hover_text(tr(command.description));
+                            if i == self.selected && step != 0 {
+                                row.scroll_to_me(None);
+                            }
+                            if row.clicked() {
+                                clicked = Some(*command);
+                            }
+                            ui.label(egui::RichText::new(tr(command.description)).weak());
+                        }
+                        if matches.is_empty() {
+                            ui.label(tr("No matching actions"));
+                        }
+                    });
+            });
+
+        if let Some(command) = clicked {
+            self.choose(command, actions);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::{path::PathBuf, sync::Arc};
+
 use anyhow::Context;
 use async_fn_stream::try_fn_stream;
 
@@ -6,14 +8,88 @@ use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 use tokio_util::{bytes::Bytes, io::StreamReader};
 use tokio_with_wasm::alias as tokio_wasm;
 
-#[derive(Clone, Debug)]
+/// What a json file holds, told apart by its keys as they all share the extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum JsonKind {
+    /// A WebDataset index, see [`brush_dataset::WebDatasetIndex`].
+    WebDataset,
+    /// The `transforms.json` of a nerfstudio dataset, next to its images.
+    Transforms,
+    /// A viewer session, see [`crate::session::Session`].
+    Session,
+    Unknown,
+}
+
+impl JsonKind {
+    pub(crate) fn detect(data: &[u8]) -> Self {
+        let Ok(serde_json::Value::Object(map)) = serde_json::from_slice(data) else {
+            return Self::Unknown;
+        };
+        if map.contains_key("shards") {
+            Self::WebDataset
+        } else if map.contains_key("frames") {
+            Self::Transforms
+        } else if ["source", "camera", "display"]
+            .iter()
+            .any(|key| map.contains_key(*key))
+        {
+            Self::Session
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum DataSource {
     PickFile,
     PickDirectory,
     Url(String),
+    /// A file or directory on disk.
+    Path(PathBuf),
+    /// A file dropped on the viewer without a path, eg. on the web.
+    #[serde(skip)]
+    Dropped {
+        name: String,
+        data: Arc<[u8]>,
+    },
 }
 
 impl DataSource {
+    /// Show the file pickers up front on native, so the picked path is known and can
+    /// be saved in a session. Elsewhere picked files don't have a path.
+    pub async fn resolve(self) -> anyhow::Result<Self> {
+        if cfg!(any(target_family = "wasm", target_os = "android")) {
+            return Ok(self);
+        }
+
+        match self {
+            Self::PickFile => {
+                let picked = rrfd::pick_file().await?;
+                Ok(Self::Path(
+                    picked.path().context("Picked file has no path")?,
+                ))
+            }
+            Self::PickDirectory => Ok(Self::Path(rrfd::pick_directory().await?)),
+            source => Ok(source),
+        }
+    }
+
+    /// The file name or url of this source, if it has one.
+    pub fn name(&self) -> Option<String> {
+        match self {
+            Self::Url(url) => Some(url.clone()),
+            Self::Path(path) => Some(path.to_string_lossy().into_owned()),
+            Self::Dropped { name, .. } => Some(name.clone()),
+            Self::PickFile | Self::PickDirectory => None,
+        }
+    }
+
+    /// Whether this source can be loaded again without asking the user.
+    pub fn is_restorable(&self) -> bool {
+        matches!(self, Self::Url(_) | Self::Path(_))
+    }
+
     pub fn into_reader(self) -> impl AsyncRead + Send {
         let (send, rec) = tokio::sync::mpsc::channel(16);
 
@@ -42,6 +118,24 @@ impl DataSource {
                         bytes.extend(path_bytes);
                         emitter.emit(Bytes::from_owner(bytes)).await;
                     }
+                    Self::Path(path) => {
+                        if path.is_dir() {
+                            let mut bytes = b"BRUSH_PATH".to_vec();
+                            let path_bytes = path
+                                .to_str()
+                                .context("invalid path")
+                                .map_err(|_e| std::io::ErrorKind::InvalidData)?
+                                .as_bytes();
+                            bytes.extend(path_bytes);
+                            emitter.emit(Bytes::from_owner(bytes)).await;
+                        } else {
+                            let data = std::fs::read(&path)?;
+                            emitter.emit(Bytes::from_owner(data)).await;
+                        }
+                    }
+                    Self::Dropped { data, .. } => {
+                        emitter.emit(Bytes::from_owner(data)).await;
+                    }
                     Self::Url(url) => {
                         let mut url = url.clone();
                         if !url.starts_with("http://") && !url.starts_with("https://") {
@@ -74,3 +168,24 @@ impl DataSource {
         StreamReader::new(ReceiverStream::new(rec))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::JsonKind;
+
+    #[test]
+    fn detects_json_by_contents() {
+        let detect = |json: &str| JsonKind::detect(json.as_bytes());
+        assert_eq!(detect(r#"{"shards": ["a.tar"]}"#), JsonKind::WebDataset);
+        assert_eq!(
+            detect(r#"{"fl_x": 500, "frames": []}"#),
+            JsonKind::Transforms
+        );
+        assert_eq!(
+            detect(r#"{"source": null, "display": {}}"#),
+            JsonKind::Session
+        );
+        assert_eq!(detect(r#"{"other": 1}"#), JsonKind::Unknown);
+        assert_eq!(detect("not json"), JsonKind::Unknown);
+    }
+}
@@ -0,0 +1,292 @@
+//! Load files dropped anywhere on the viewer, and paths or urls pasted into it.
+//!
+//! The format is detected from the name of the file, or from its first bytes when there's
+//! no telling from the name. Before loading, a dialog shows the detected format with the
+//! import options that apply to it, eg. the coordinates and scale of a ply model.
+use std::{io::Read, path::Path};
+
+use brush_dataset::binary_model;
+use egui::{Align2, Color32};
+
+use crate::{
+    app::AppContext,
+    data_source::{DataSource, JsonKind},
+    i18n::tr,
+    panels::model_import_options,
+    process_loop::{start_process, ProcessArgs},
+    session::Session,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ply,
+    Splat,
+    BinaryModel,
+    Checkpoint,
+    Zip,
+    Folder,
+    WebDataset,
+    Transforms,
+    Session,
+    Unknown,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        let extension = Path::new(name).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "ply" => Some(Self::Ply),
+            "splat" => Some(Self::Splat),
+            "splats" => Some(Self::BinaryModel),
+            "brush" => Some(Self::Checkpoint),
+            "zip" => Some(Self::Zip),
+            "json" => Some(Self::WebDataset),
+            _ => None,
+        }
+    }
+
+    fn from_bytes(data: &[u8]) -> Self {
+        #[cfg(not(target_family = "wasm"))]
+        if brush_dataset::checkpoint::is_checkpoint(data) {
+            return Self::Checkpoint;
+        }
+        if data.starts_with(b"ply") {
+            Self::Ply
+        } else if data.starts_with(b"PK") {
+            Self::Zip
+        } else if binary_model::is_binary_model(data) {
+            Self::BinaryModel
+        } else {
+            Self::Unknown
+        }
+    }
+
+    fn from_json(data: &[u8]) -> Self {
+        match JsonKind::detect(data) {
+            JsonKind::WebDataset => Self::WebDataset,
+            JsonKind::Transforms => Self::Transforms,
+            JsonKind::Session => Self::Session,
+            JsonKind::Unknown => Self::Unknown,
+        }
+    }
+
+    fn detect(source: &DataSource) -> Self {
+        // Json files share their extension, so look at what's in them.
+        if Self::from_name(&source.name().unwrap_or_default()) == Some(Self::WebDataset) {
+            if let Some(data) = local_bytes(source) {
+                return match (Self::from_json(&data), source) {
+                    // The shards of an index are next to it, which a dropped file lacks.
+                    (Self::WebDataset, DataSource::Dropped { .. }) => Self::Unknown,
+                    (format, _) => format,
+                };
+            }
+        }
+
+        match source {
+            DataSource::Path(path) if path.is_dir() => Self::Folder,
+            DataSource::Path(path) => {
+                Self::from_name(&path.to_string_lossy()).unwrap_or_else(|| {
+                    // Read the start of the file to detect it.
+                    let mut peek = [0; 64];
+                    let read = std::fs::File::open(path)
+                        .and_then(|mut file| file.read(&mut peek))
+                        .unwrap_or(0);
+                    Self::from_bytes(&peek[..read])
+                })
+            }
+            DataSource::Url(url) => Self::from_name(url).unwrap_or(Self::Unknown),
+            DataSource::Dropped { name, data } => {
+                Self::from_name(name).unwrap_or_else(|| Self::from_bytes(data))
+            }
+            DataSource::PickFile | DataSource::PickDirectory => Self::Unknown,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Ply => "Splat model (.ply)",
+            Self::Splat => "Splat model (.splat)",
+            Self::BinaryModel => "Binary splat model (.splats)",
+            Self::Checkpoint => "Training checkpoint (.brush)",
+            Self::Zip => "Zip archive, of a dataset or .ply files",
+            Self::Folder => "Folder, of a dataset or .ply files",
+            Self::WebDataset => "WebDataset index (.json)",
+            Self::Transforms => "Nerfstudio dataset (transforms.json)",
+            Self::Session => "Viewer session (.json)",
+            Self::Unknown => "Unknown format, detected while loading",
+        }
+    }
+
+    fn is_model(self) -> bool {
+        matches!(
+            self,
+            Self::Ply | Self::Splat | Self::BinaryModel | Self::Checkpoint
+        )
+    }
+}
+
+// The contents of a file that can be read without downloading it.
+fn local_bytes(source: &DataSource) -> Option<Vec<u8>> {
+    match source {
+        DataSource::Path(path) => std::fs::read(path).ok(),
+        DataSource::Dropped { data, .. } => Some(data.to_vec()),
+        DataSource::Url(_) | DataSource::PickFile | DataSource::PickDirectory => None,
+    }
+}
+
+// What to load for the given format: the folder of a transforms.json, or the model or
+// dataset a session was saved with.
+fn load_source(source: DataSource, format: Format) -> anyhow::Result<DataSource> {
+    match (format, &source) {
+        (Format::Transforms, DataSource::Path(path)) => Ok(DataSource::Path(
+            path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        )),
+        (Format::Transforms, _) => {
+            anyhow::bail!("A transforms.json needs its images, load its folder or a zip instead.")
+        }
+        (Format::Session, _) => {
+            let data = local_bytes(&source).unwrap_or_default();
+            let session: Session = serde_json::from_slice(&data)?;
+            session
+                .source
+                .filter(DataSource::is_restorable)
+                .ok_or_else(|| anyhow::anyhow!("The session has no model or dataset to load."))
+        }
+        _ => Ok(source),
+    }
+}
+
+// A path or url in pasted text, if it looks like one.
+fn source_from_text(text: &str) -> Option<DataSource> {
+    let text = text.trim().trim_matches(|c| c == '"' || c == '\'');
+    if text.starts_with("http://") || text.starts_with("https://") {
+        return Some(DataSource::Url(text.to_owned()));
+    }
+    if cfg!(target_family = "wasm") {
+        return None;
+    }
+    let path = Path::new(text.strip_prefix("file://").unwrap_or(text));
+    path.exists().then(|| DataSource::Path(path.to_path_buf()))
+}
+
+/// Picks up dropped and pasted files, and asks how to import them.
+#[derive(Default)]
+pub(crate) struct DropImport {
+    pending: Option<(DataSource, Format)>,
+}
+
+impl DropImport {
+    pub(crate) fn ui(&mut self, ctx: &egui::Context, context: &mut AppContext) {
+        let dropped = ctx.input(|r| r.raw.dropped_files.first().cloned());
+        let dropped = dropped.and_then(|file| match (file.path, file.bytes) {
+            (Some(path), _) => Some(DataSource::Path(path)),
+            (None, Some(data)) => Some(DataSource::Dropped {
+                name: file.name,
+                data,
+            }),
+            (None, None) => None,
+        });
+
+        // Text fields handle their own pastes.
+        let pasted = if ctx.wants_keyboard_input() {
+            None
+        } else {
+            ctx.input(|r| {
+                r.events.iter().find_map(|event| match event {
+                    egui::Event::Paste(text) => source_from_text(text),
+                    _ => None,
+                })
+            })
+        };
+
+        if let Some(source) = dropped.or(pasted) {
+            let format = Format::detect(&source);
+            self.pending = Some((source, format));
+        }
+
+        if ctx.input(|r| !r.raw.hovered_files.is_empty()) {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("drop_overlay"),
+            ));
+            let rect = ctx.screen_rect();
+            painter.rect_filled(rect, 0.0, Color32::from_black_alpha(160));
+            painter.text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                tr("Drop to load"),
+                egui::FontId::proportional(32.0),
+                Color32::WHITE,
+            );
+        }
+
+        let Some((source, format)) = &self.pending else {
+            return;
+        };
+        let (mut load, mut cancel) = (false, false);
+
+        egui::Window::new(tr("Import"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                let name = match source {
+                    DataSource::Path(path) => path.to_string_lossy().into_owned(),
+                    DataSource::Url(url) => url.clone(),
+                    DataSource::Dropped { name, .. } => name.clone(),
+                    DataSource::PickFile | DataSource::PickDirectory => String::new(),
+                };
+                ui.label(egui::RichText::new(name).strong());
+                ui.label(tr(format.description()));
+                ui.add_space(6.0);
+
+                let load_args = &mut context.process_args.load_args;
+                match format {
+                    Format::Ply | Format::Splat => model_import_options(ui, load_args, true),
+                    Format::BinaryModel | Format::Checkpoint => {
+                        model_import_options(ui, load_args, false);
+                    }
+                    Format::Zip | Format::Folder | Format::Unknown => {
+                        model_import_options(ui, load_args, true);
+                        ui.label(tr(
+                            "Datasets train with the settings of the Load data panel.",
+                        ));
+                    }
+                    Format::WebDataset | Format::Transforms => {
+                        ui.label(tr("Trains with the settings of the Load data panel."));
+                    }
+                    Format::Session => {
+                        ui.label(tr("Loads the model or dataset of the session."));
+                    }
+                }
+
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    let verb = if format.is_model() {
+                        tr("View")
+                    } else {
+                        tr("Load")
+                    };
+                    load = ui.button(verb).clicked();
+                    cancel = ui.button(tr("Cancel")).clicked();
+                });
+            });
+
+        if load {
+            if let Some((source, format)) = self.pending.take() {
+                match load_source(source, format) {
+                    Ok(source) => {
+                        let args = ProcessArgs {
+                            source,
+                            ..context.process_args.clone()
+                        };
+                        context.connect_to(start_process(args, context.device.clone()));
+                    }
+                    Err(e) => log::error!("Failed to import: {e}"),
+                }
+            }
+        } else if cancel {
+            self.pending = None;
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Frame rate the viewer aims for while the view is changing.
+const TARGET_FPS: f32 = 60.0;
+
+// Lowest fraction of the window resolution the viewer renders at.
+const MIN_SCALE: f32 = 0.25;
+
+// Changes in scale smaller than this are ignored, so the resolution doesn't flicker.
+const MIN_SCALE_CHANGE: f32 = 0.05;
+
+/// Scales the render resolution of the viewer to keep up a target frame rate.
+///
+/// The render is upsampled to the window size when drawn. Frame times are only meaningful
+/// while frames are rendered back to back, so the viewer only uses this while the view
+/// is moving, and renders a still view at full resolution.
+pub(crate) struct DynamicResolution {
+    pub(crate) enabled: bool,
+    /// Trades frame rate for sharpness. -1 doubles the target frame rate, 1 halves it.
+    pub(crate) quality_bias: f32,
+    scale: f32,
+    frame_time: Option<f32>,
+}
+
+impl DynamicResolution {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: false,
+            quality_bias: 0.0,
+            scale: 1.0,
+            frame_time: None,
+        }
+    }
+
+    pub(crate) fn scale(&self) -> f32 {
+        if self.enabled {
+            self.scale
+        } else {
+            1.0
+        }
+    }
+
+    /// Report how long the last frame took in seconds.
+    pub(crate) fn update(&mut self, frame_time: f32) {
+        // Ignore hitches, eg. from a window losing focus.
+        let frame_time = frame_time.min(0.25);
+        let smoothed = match self.frame_time {
+            Some(prev) => prev * 0.8 + frame_time * 0.2,
+            None => frame_time,
+        };
+        self.frame_time = Some(smoothed);
+
+        let target = 2.0f32.powf(self.quality_bias) / TARGET_FPS;
+
+        // Render time scales with the number of pixels, so with the square of the scale.
+        // Only move part of the way there to avoid oscillating.
+        let ideal = self.scale * (target / smoothed).sqrt();
+        let next = (self.scale + (ideal - self.scale) * 0.5).clamp(MIN_SCALE, 1.0);
+
+        if (next - self.scale).abs() > MIN_SCALE_CHANGE || next == 1.0 || next == MIN_SCALE {
+            self.scale = next;
+        }
+    }
+
+    /// Forget the measured frame times, eg. when the view stops moving.
+    pub(crate) fn reset(&mut self) {
+        self.frame_time = None;
+    }
+
+    pub(crate) fn render_size(&self, size: glam::UVec2) -> glam::UVec2 {
+        let scaled = (size.as_vec2() * self.scale()).round().as_uvec2();
+        scaled.max(glam::UVec2::splat(8)).min(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynamicResolution;
+
+    #[test]
+    fn scale_follows_frame_time() {
+        let mut res = DynamicResolution::new();
+        res.enabled = true;
+
+        for _ in 0..50 {
+            res.update(1.0 / 15.0);
+        }
+        let slow_scale = res.scale();
+        assert!(slow_scale < 0.75);
+
+        for _ in 0..50 {
+            res.update(1.0 / 240.0);
+        }
+        assert!(res.scale() > slow_scale);
+        assert_eq!(res.render_size(glam::uvec2(100, 50)).x, 100);
+    }
+}
@@ -0,0 +1,77 @@
+use gilrs::{Axis, Button, Gilrs};
+use glam::Vec2;
+
+// Stick values below this are treated as zero, sticks rarely rest at exactly zero.
+const DEADZONE: f32 = 0.15;
+
+// How fast a fully pushed stick or trigger moves the camera. Orbit and pan are in pixels
+// per second, matching mouse drags, zoom is in scroll units per second.
+const ORBIT_SPEED: f32 = 2000.0;
+const PAN_SPEED: f32 = 2000.0;
+const ZOOM_SPEED: f32 = 200.0;
+
+/// Camera movement requested by the gamepads, or the keyboard, this frame.
+#[derive(Default)]
+pub(crate) struct GamepadMotion {
+    pub(crate) pan: Vec2,
+    pub(crate) rotate: Vec2,
+    pub(crate) zoom: f32,
+}
+
+impl GamepadMotion {
+    pub(crate) fn is_zero(&self) -> bool {
+        self.pan == Vec2::ZERO && self.rotate == Vec2::ZERO && self.zoom == 0.0
+    }
+}
+
+/// Reads the connected gamepads to navigate the viewer. The left stick orbits, the right
+/// stick pans, and the triggers zoom in and out.
+pub(crate) struct GamepadInput {
+    gilrs: Option<Gilrs>,
+}
+
+fn deadzone(v: f32) -> f32 {
+    if v.abs() < DEADZONE {
+        0.0
+    } else {
+        // Rescale so the output still starts at zero at the edge of the deadzone.
+        v.signum() * (v.abs() - DEADZONE) / (1.0 - DEADZONE)
+    }
+}
+
+impl GamepadInput {
+    pub(crate) fn new() -> Self {
+        let gilrs = Gilrs::new()
+            .inspect_err(|e| log::warn!("Gamepad input unavailable: {e}"))
+            .ok();
+        Self { gilrs }
+    }
+
+    pub(crate) fn has_gamepad(&self) -> bool {
+        self.gilrs
+            .as_ref()
+            .is_some_and(|g| g.gamepads().next().is_some())
+    }
+
+    pub(crate) fn poll(&mut self, delta_time: f32) -> GamepadMotion {
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return GamepadMotion::default();
+        };
+
+        // Events have to be drained to update the gamepad state.
+        while gilrs.next_event().is_some() {}
+
+        let mut motion = GamepadMotion::default();
+        for (_, pad) in gilrs.gamepads() {
+            let stick = |x, y| Vec2::new(deadzone(pad.value(x)), -deadzone(pad.value(y)));
+            let trigger = |b| pad.button_data(b).map_or(0.0, |d| d.value());
+
+            motion.rotate += stick(Axis::LeftStickX, Axis::LeftStickY) * ORBIT_SPEED * delta_time;
+            motion.pan += stick(Axis::RightStickX, Axis::RightStickY) * PAN_SPEED * delta_time;
+            motion.zoom += (trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2))
+                * ZOOM_SPEED
+                * delta_time;
+        }
+        motion
+    }
+}
@@ -0,0 +1,296 @@
+//! German translations.
+
+pub(super) const STRINGS: &[(&str, &str)] = &[
+    // Panels.
+    ("Scene", "Szene"),
+    ("Load data", "Daten laden"),
+    ("Dataset", "Datensatz"),
+    ("Presets", "Vorlagen"),
+    ("Rerun", "Rerun"),
+    ("Splat stats", "Splat-Statistik"),
+    ("Stats", "Statistik"),
+    // Start screen.
+    ("Load a ply file or dataset to get started.", "Lade eine ply-Datei oder einen Datensatz, um zu beginnen."),
+    (
+        "\nLoad a pretrained .ply file to view it\n\nOr load a dataset to train on. These are zip files with:\n    - a transforms.json and images, like the nerfstudio dataset format.\n    - COLMAP data, containing the `images` & `sparse` folder.\n\nOr the URL of a WebDataset index.json, to stream its tar shards while training.",
+        "\nLade eine trainierte .ply-Datei, um sie anzusehen\n\nOder lade einen Datensatz zum Trainieren. Das sind zip-Dateien mit:\n    - einer transforms.json und Bildern, wie im nerfstudio-Format.\n    - COLMAP-Daten, mit den Ordnern `images` & `sparse`.\n\nOder die URL einer WebDataset index.json, deren tar-Teile beim Trainieren gestreamt werden.",
+    ),
+    ("Note: Running in browser is still experimental", "Hinweis: Der Browser wird noch experimentell unterstützt"),
+    (
+        "\nIn browser training is slower, and lower quality than the native app.\n\nFor bigger training runs consider using the native app.",
+        "\nIm Browser ist das Training langsamer und von geringerer Qualität als in der App.\n\nFür größere Trainings empfiehlt sich die App.",
+    ),
+    ("Recent", "Zuletzt geöffnet"),
+    ("No preview", "Keine Vorschau"),
+    ("Remove from list", "Aus der Liste entfernen"),
+    // Load data.
+    ("Ply coordinates", "Ply-Koordinaten"),
+    ("Convention of the ply files to view, converted to Brush on load", "Konvention der anzuzeigenden ply-Dateien, wird beim Laden nach Brush umgerechnet"),
+    ("Model scale", "Modellmaßstab"),
+    ("Scale of the models to view, eg. 0.01 for a model in centimeters", "Maßstab der anzuzeigenden Modelle, z. B. 0.01 für ein Modell in Zentimetern"),
+    ("Select or drop a .ply to visualize, or a .zip with training data.", "Wähle eine .ply zum Ansehen oder eine .zip mit Trainingsdaten, oder ziehe sie hierher."),
+    ("Load file", "Datei laden"),
+    ("Load directory", "Ordner laden"),
+    ("Load URL", "URL laden"),
+    ("Watch a trainer started with brush_app train --serve.", "Ein mit brush_app train --serve gestartetes Training verfolgen."),
+    ("Connect to trainer", "Mit Training verbinden"),
+    ("Train live on a webcam or RTSP stream (experimental, needs ffmpeg).", "Live auf einer Webcam oder einem RTSP-Stream trainieren (experimentell, benötigt ffmpeg)."),
+    ("Start live capture", "Live-Aufnahme starten"),
+    ("Train on frames and poses streamed from a phone AR app.", "Auf Bildern und Posen aus einer AR-App auf dem Handy trainieren."),
+    ("Receive from phone", "Vom Handy empfangen"),
+    ("Train settings", "Trainingseinstellungen"),
+    ("Spherical Harmonics Degree:", "Grad der Kugelflächenfunktionen:"),
+    ("Photometric loss", "Photometrischer Verlust"),
+    ("View sampling", "Auswahl der Ansichten"),
+    ("Shuffle", "Zufällig"),
+    ("Priority", "Priorität"),
+    ("Coarse to fine", "Grob zu fein"),
+    ("Train on downscaled images first, which is faster at the start", "Zuerst auf verkleinerten Bildern trainieren, was zu Beginn schneller ist"),
+    ("double every ", "verdoppeln alle "),
+    (" steps", " Schritte"),
+    ("Limit training resolution", "Trainingsauflösung begrenzen"),
+    ("Limit max frames", "Anzahl der Bilder begrenzen"),
+    ("Split dataset for evaluation", "Datensatz zur Auswertung aufteilen"),
+    ("1 out of ", "1 von "),
+    (" frames", " Bildern"),
+    ("Evaluate", "Auswerten"),
+    ("every ", "alle "),
+    ("Refine camera intrinsics", "Kameraparameter verfeinern"),
+    ("Rolling shutter cameras", "Kameras mit Rolling Shutter"),
+    ("Learn background", "Hintergrund lernen"),
+    ("Shadow catcher", "Schattenfänger"),
+    ("Relightable (experimental)", "Neu beleuchtbar (experimentell)"),
+    ("Feature splatting", "Feature-Splatting"),
+    ("Perceptual loss", "Wahrnehmungsbasierter Verlust"),
+    ("Compare VGG features for sharper textures. Downloads the VGG16 weights (~500MB)", "VGG-Merkmale vergleichen, für schärfere Texturen. Lädt die VGG16-Gewichte herunter (~500MB)"),
+    ("weight ", "Gewicht "),
+    ("Model blurry views", "Unscharfe Ansichten modellieren"),
+    ("Weight views by sharpness", "Ansichten nach Schärfe gewichten"),
+    ("drop blurriest ", "unschärfste verwerfen "),
+    ("Subsample frames", "Bilder ausdünnen"),
+    ("Keep 1 out of ", "Behalte 1 von "),
+    ("Subsample points", "Punkte ausdünnen"),
+    (" points", " Punkten"),
+    ("Filter noisy points", "Verrauschte Punkte filtern"),
+    ("Downscaled images", "Verkleinerte Bilder"),
+    ("Load COLMAP images from eg. images_4 instead of images", "COLMAP-Bilder z. B. aus images_4 statt images laden"),
+    ("Correct vignetting", "Vignettierung korrigieren"),
+    ("Brighten the darkened corners of each image", "Die abgedunkelten Ecken jedes Bildes aufhellen"),
+    ("Normalize white balance", "Weißabgleich angleichen"),
+    ("Remove color casts that differ between images, eg. from phone cameras", "Farbstiche entfernen, die sich zwischen Bildern unterscheiden, z. B. von Handykameras"),
+    ("Limit image memory", "Bildspeicher begrenzen"),
+    ("Keep images compressed and decode them when they're needed", "Bilder komprimiert halten und erst bei Bedarf dekodieren"),
+    ("Save checkpoints", "Zwischenstände speichern"),
+    ("keep last ", "behalte letzte "),
+    ("Save best model by eval PSNR", "Bestes Modell nach PSNR der Auswertung speichern"),
+    ("Freeze", "Einfrieren"),
+    ("Refine region", "Bereich verfeinern"),
+    ("Fixed seed", "Fester Seed"),
+    ("Use a fixed seed for the view order and initialization, so runs are reproducible", "Einen festen Seed für die Reihenfolge und Initialisierung verwenden, damit Durchläufe reproduzierbar sind"),
+    ("Random eval views", "Zufällige Auswertungsansichten"),
+    ("Pick the eval views at random with the seed, instead of every nth view", "Die Auswertungsansichten zufällig mit dem Seed wählen, statt jede n-te Ansicht"),
+    ("Select COLMAP model", "COLMAP-Modell wählen"),
+    ("By default the COLMAP model with the most images is used", "Standardmäßig wird das COLMAP-Modell mit den meisten Bildern verwendet"),
+    // Presets.
+    ("Mipnerf scenes", "Mipnerf-Szenen"),
+    ("Synthetic blender scenes", "Synthetische Blender-Szenen"),
+    // Scene.
+    ("Error: ", "Fehler: "),
+    ("Render mode", "Darstellung"),
+    ("Color", "Farbe"),
+    ("The regular colors of the splats", "Die normalen Farben der Splats"),
+    ("Shaded", "Schattiert"),
+    ("Shade splats using their estimated normals", "Splats anhand ihrer geschätzten Normalen schattieren"),
+    ("Points", "Punkte"),
+    ("Ellipsoids", "Ellipsoide"),
+    ("Ground", "Boden"),
+    ("Uncertainty", "Unsicherheit"),
+    ("Contribution", "Beitrag"),
+    ("Update", "Aktualisieren"),
+    ("Recompute the contributions for the current view", "Die Beiträge für die aktuelle Ansicht neu berechnen"),
+    ("Suggest views", "Ansichten vorschlagen"),
+    ("Find camera poses that would best constrain the uncertain regions", "Kameraposen finden, die die unsicheren Bereiche am besten festlegen"),
+    ("Go to suggested view, {}% uncertain", "Zur vorgeschlagenen Ansicht, {}% unsicher"),
+    ("Sort by", "Sortieren nach"),
+    ("Depth", "Tiefe"),
+    ("The depth along the view direction", "Die Tiefe entlang der Blickrichtung"),
+    ("Distance", "Entfernung"),
+    ("Reduces popping when looking around in large scenes", "Verringert Springen beim Umsehen in großen Szenen"),
+    ("Log distance", "Log. Entfernung"),
+    ("Sorts fewer bits, faster on scenes with many visible splats", "Sortiert weniger Bits, schneller bei Szenen mit vielen sichtbaren Splats"),
+    ("Order independent blending", "Reihenfolgeunabhängiges Mischen"),
+    ("Faster on huge scenes as splats aren't sorted, but less accurate", "Schneller bei riesigen Szenen, da Splats nicht sortiert werden, aber ungenauer"),
+    ("Dynamic resolution", "Dynamische Auflösung"),
+    ("Lower the resolution while moving to keep up the frame rate", "Die Auflösung bei Bewegung senken, um die Bildrate zu halten"),
+    ("Quality bias", "Qualitätsvorzug"),
+    ("Higher values favour sharpness over frame rate", "Höhere Werte bevorzugen Schärfe vor Bildrate"),
+    ("Temporal anti-aliasing", "Temporales Anti-Aliasing"),
+    ("Foveated", "Foveated"),
+    ("Loading...", "Lädt..."),
+    ("Loading... Please wait.", "Lädt... Bitte warten."),
+    ("⏹ Cancel", "⏹ Abbrechen"),
+    ("Stop loading and training, and free its memory", "Laden und Training beenden und den Speicher freigeben"),
+    ("🔴 Live update splats", "🔴 Splats live aktualisieren"),
+    ("Coordinate convention of the exported ply", "Koordinatenkonvention der exportierten ply"),
+    ("Level", "Ausrichten"),
+    ("Rotate the exported ply so the ground is level", "Die exportierte ply drehen, sodass der Boden waagerecht ist"),
+    ("⬆ Export", "⬆ Exportieren"),
+    ("⬆ Export progressive", "⬆ Progressiv exportieren"),
+    ("⬆ Export chunks", "⬆ Blöcke exportieren"),
+    ("⬆ Export octree", "⬆ Octree exportieren"),
+    ("⬆ Export binary", "⬆ Binär exportieren"),
+    ("Export a .splats model, which opens much faster than a ply in Brush", "Ein .splats-Modell exportieren, das sich in Brush viel schneller öffnet als eine ply"),
+    ("🔍 Pick feature", "🔍 Merkmal wählen"),
+    ("Click the view to select everything with a similar feature", "In die Ansicht klicken, um alles mit einem ähnlichen Merkmal auszuwählen"),
+    ("Load query", "Abfrage laden"),
+    ("Similarity", "Ähnlichkeit"),
+    ("Keep selection", "Auswahl behalten"),
+    ("Remove all splats that aren't selected", "Alle nicht ausgewählten Splats entfernen"),
+    ("Remove selection", "Auswahl entfernen"),
+    ("Clear", "Leeren"),
+    ("Color space", "Farbraum"),
+    ("For regular displays", "Für normale Bildschirme"),
+    (
+        "For wide gamut displays, eg. of most laptops and phones, where sRGB colors look oversaturated",
+        "Für Bildschirme mit großem Farbraum, z. B. der meisten Laptops und Handys, auf denen sRGB-Farben übersättigt aussehen",
+    ),
+    (
+        "For HDR displays, with white at the brightness of SDR content",
+        "Für HDR-Bildschirme, mit Weiß in der Helligkeit von SDR-Inhalten",
+    ),
+    ("The display doesn't support HDR output", "Der Bildschirm unterstützt keine HDR-Ausgabe"),
+    ("Edit layers", "Bearbeitungsebenen"),
+    (
+        "The splats of this layer were picked on a model with a different number of splats, it doesn't apply to these",
+        "Die Splats dieser Ebene wurden in einem Modell mit einer anderen Anzahl Splats ausgewählt, sie gilt nicht für diese",
+    ),
+    ("Remove layer", "Ebene entfernen"),
+    ("➕ Add layer", "➕ Ebene hinzufügen"),
+    ("Heal", "Heilen"),
+    ("Fill the box with new splats and train only them for a short run, on the views that see it, to repair a hole or artifact. Loads the dataset again", "Die Box mit neuen Splats füllen und nur diese kurz trainieren, auf den Ansichten, die sie sehen, um ein Loch oder Artefakt zu reparieren. Lädt den Datensatz erneut"),
+    ("Healing needs the dataset the model was trained on", "Zum Heilen wird der Datensatz benötigt, mit dem das Modell trainiert wurde"),
+    (" splats", " Splats"),
+    ("Crop to box", "Auf Box zuschneiden"),
+    ("Transform", "Transformieren"),
+    ("Recolor", "Umfärben"),
+    ("Save edits", "Bearbeitungen speichern"),
+    (
+        "Save the layers next to the model, or to a file picked on disk. They're loaded again with the model",
+        "Die Ebenen neben dem Modell oder in einer gewählten Datei speichern. Sie werden mit dem Modell wieder geladen",
+    ),
+    ("Load edits", "Bearbeitungen laden"),
+    ("Splats", "Splats"),
+    ("{} picked", "{} ausgewählt"),
+    ("All", "Alle"),
+    ("In box", "In Box"),
+    ("Invert", "Umkehren"),
+    ("Edit the splats that aren't selected instead", "Stattdessen die nicht ausgewählten Splats bearbeiten"),
+    ("Min", "Min"),
+    ("Max", "Max"),
+    ("Move", "Verschieben"),
+    ("Rotate", "Drehen"),
+    ("Scale", "Skalieren"),
+    ("Pivot", "Drehpunkt"),
+    ("Tint", "Tönung"),
+    ("Exposure", "Belichtung"),
+    ("Feather", "Weiche Kante"),
+    (
+        "Fade out the copy over this distance from the sides of the box, to blend it in",
+        "Die Kopie über diese Entfernung von den Seiten der Box ausblenden, damit sie sich einfügt",
+    ),
+    ("Duplicate region", "Bereich duplizieren"),
+    (
+        "Copy the splats in a box to somewhere else, eg. to patch a hole with similar content from nearby",
+        "Die Splats in einer Box an eine andere Stelle kopieren, z. B. um ein Loch mit ähnlichem Inhalt aus der Nähe zu flicken",
+    ),
+    ("Saturation", "Sättigung"),
+    ("Recolor selection", "Auswahl umfärben"),
+    (
+        "Add a layer to change the color, exposure and saturation of the selected splats",
+        "Eine Ebene hinzufügen, die Farbe, Belichtung und Sättigung der ausgewählten Splats ändert",
+    ),
+    // Stats.
+    ("Statistics are shown while training.", "Die Statistik wird beim Training angezeigt."),
+    ("{} splats at step {}", "{} Splats bei Schritt {}"),
+    ("Opacity", "Deckkraft"),
+    ("Anisotropy (log10)", "Anisotropie (log10)"),
+    ("SH energy", "SH-Energie"),
+    ("Screen radius (log10 px)", "Bildschirmradius (log10 px)"),
+    ("Refinement", "Verfeinerung"),
+    ("SH Degree", "SH-Grad"),
+    ("Frames", "Bilder"),
+    ("Train step", "Trainingsschritt"),
+    ("Steps/s", "Schritte/s"),
+    ("Last eval PSNR", "Letzter PSNR der Auswertung"),
+    ("Training time", "Trainingszeit"),
+    ("GPU memory", "GPU-Speicher"),
+    ("Bytes in use", "Belegte Bytes"),
+    ("Bytes reserved", "Reservierte Bytes"),
+    ("Active allocations", "Aktive Allokationen"),
+    ("Name", "Name"),
+    ("Type", "Typ"),
+    ("Driver", "Treiber"),
+    // Dataset.
+    ("Reconstruction", "Rekonstruktion"),
+    ("Mean track length", "Mittlere Spurlänge"),
+    ("Mean reprojection error", "Mittlerer Reprojektionsfehler"),
+    ("Images with features", "Bilder mit Merkmalen"),
+    ("Verified image pairs", "Verifizierte Bildpaare"),
+    ("Mean keypoints", "Mittlere Anzahl Schlüsselpunkte"),
+    ("Mean verified matches", "Mittlere verifizierte Treffer"),
+    // Import.
+    ("Drop to load", "Zum Laden loslassen"),
+    ("Import", "Importieren"),
+    ("View", "Ansehen"),
+    ("Load", "Laden"),
+    ("Cancel", "Abbrechen"),
+    ("Splat model (.ply)", "Splat-Modell (.ply)"),
+    ("Binary splat model (.splats)", "Binäres Splat-Modell (.splats)"),
+    ("Training checkpoint (.brush)", "Trainingsstand (.brush)"),
+    ("Zip archive, of a dataset or .ply files", "Zip-Archiv, mit einem Datensatz oder .ply-Dateien"),
+    ("Folder, of a dataset or .ply files", "Ordner, mit einem Datensatz oder .ply-Dateien"),
+    ("WebDataset index (.json)", "WebDataset-Index (.json)"),
+    ("Unknown format, detected while loading", "Unbekanntes Format, wird beim Laden erkannt"),
+    ("Datasets train with the settings of the Load data panel.", "Datensätze werden mit den Einstellungen von Daten laden trainiert."),
+    ("Trains with the settings of the Load data panel.", "Trainiert mit den Einstellungen von Daten laden."),
+    // Settings.
+    ("Interface", "Oberfläche"),
+    ("Language", "Sprache"),
+    ("Interface scale", "Skalierung der Oberfläche"),
+    ("Also ctrl + plus and ctrl + minus", "Auch mit Strg + Plus und Strg + Minus"),
+    ("High contrast", "Hoher Kontrast"),
+    (
+        "Tab moves between controls, and F6 between panels. Once the scene is focused, the arrow keys orbit, with shift they pan, and page up and down zoom.",
+        "Tab wechselt zwischen Bedienelementen und F6 zwischen Bereichen. Hat die Szene den Fokus, drehen die Pfeiltasten die Ansicht, mit Umschalt verschieben sie sie, und Bild auf und ab zoomen.",
+    ),
+    // Command palette.
+    ("Commands", "Befehle"),
+    ("Type an action, eg. crop -1 -1 -1 1 1 1", "Aktion eingeben, z. B. crop -1 -1 -1 1 1 1"),
+    ("No matching actions", "Keine passenden Aktionen"),
+    ("Load a model or dataset, picked from disk when no path is given", "Ein Modell oder einen Datensatz laden, ohne Pfad aus einer Dateiauswahl"),
+    ("Load a dataset folder picked from disk", "Einen ausgewählten Datensatz-Ordner laden"),
+    ("Pause or resume training, and the animation of animated splats", "Training und die Animation animierter Splats anhalten oder fortsetzen"),
+    ("Show the splats as they train", "Die Splats während des Trainings anzeigen"),
+    (
+        "Keep only the splats with their center in a box, given by two corners, as an edit layer",
+        "Nur die Splats mit ihrem Mittelpunkt in einer Box behalten, gegeben durch zwei Ecken, als Bearbeitungsebene",
+    ),
+    ("Save the edit layers, next to the model when no path is given", "Die Bearbeitungsebenen speichern, ohne Pfad neben dem Modell"),
+    ("Load edit layers, from a file picked on disk when no path is given", "Bearbeitungsebenen laden, ohne Pfad aus einer gewählten Datei"),
+    ("Remove all edit layers", "Alle Bearbeitungsebenen entfernen"),
+    ("Export a ply, to a file picked on disk when no path is given", "Eine ply exportieren, ohne Pfad in eine ausgewählte Datei"),
+    ("Export a ply ordered from coarse to fine detail", "Eine ply von groben zu feinen Details sortiert exportieren"),
+    ("Export a zip of spatial chunks with collision boxes", "Ein Zip mit räumlichen Blöcken und Kollisionsboxen exportieren"),
+    ("Export a zip of octree chunks with levels of detail", "Ein Zip mit Octree-Blöcken und Detailstufen exportieren"),
+    ("Export a .splats model", "Ein .splats-Modell exportieren"),
+    ("Change how splats are drawn", "Ändern, wie Splats gezeichnet werden"),
+    ("Change the color space the splats are shown in", "Den Farbraum der Splats ändern"),
+    ("Show the shadow catcher trained on the ground", "Den auf dem Boden trainierten Schattenfänger anzeigen"),
+    ("Color splats by how many training views saw them", "Splats danach einfärben, wie viele Trainingsansichten sie sahen"),
+    ("Color splats by how much they contribute to the current view", "Splats nach ihrem Beitrag zur aktuellen Ansicht einfärben"),
+    ("Sort splats by distance, to reduce popping in large scenes", "Splats nach Entfernung sortieren, gegen Aufpoppen in großen Szenen"),
+    ("Blend splats without sorting them", "Splats ohne Sortierung überblenden"),
+    ("Blend jittered frames while moving to reduce shimmering", "Beim Bewegen versetzte Bilder überblenden, gegen Flimmern"),
+    ("Preview foveated rendering around the pointer", "Foveated Rendering um den Mauszeiger als Vorschau zeigen"),
+    ("Run a script of viewer actions", "Ein Skript mit Viewer-Aktionen ausführen"),
+];
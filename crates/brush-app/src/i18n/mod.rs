@@ -0,0 +1,103 @@
+//! Translations of the text of the viewer.
+//!
+//! Text is looked up by its English version with [`tr`], so the English text stays readable
+//! in the code, and anything that isn't translated yet shows up in English. Each language
+//! has a catalog of translations in its own module.
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        OnceLock,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+mod de;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Self; 2] = [Self::English, Self::German];
+
+    /// Name of the language, in that language.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::German => "Deutsch",
+        }
+    }
+
+    fn catalog(self) -> Option<&'static HashMap<&'static str, &'static str>> {
+        static GERMAN: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+        match self {
+            Self::English => None,
+            Self::German => Some(GERMAN.get_or_init(|| de::STRINGS.iter().copied().collect())),
+        }
+    }
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+/// The language the viewer is shown in.
+pub fn language() -> Language {
+    Language::ALL
+        .get(LANGUAGE.load(Ordering::Relaxed) as usize)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Switch the language of the viewer, which applies from the next frame.
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+/// The text in the current language, or the English text without a translation.
+pub fn tr(text: &'static str) -> &'static str {
+    language()
+        .catalog()
+        .and_then(|catalog| catalog.get(text).copied())
+        .unwrap_or(text)
+}
+
+/// Like [`tr`], with the `{}` in the text replaced by the arguments in order. The arguments
+/// can be in another order in a translation, by numbering them like `{1}`.
+pub fn tr_args(text: &'static str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut out = tr(text).to_owned();
+    for (i, arg) in args.iter().enumerate() {
+        let arg = arg.to_string();
+        let numbered = format!("{{{i}}}");
+        out = if out.contains(&numbered) {
+            out.replace(&numbered, &arg)
+        } else {
+            out.replacen("{}", &arg, 1)
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalogs_have_no_duplicates() {
+        let mut seen = std::collections::HashSet::new();
+        for (english, _) in de::STRINGS {
+            assert!(seen.insert(english), "Duplicate translation of {english:?}");
+        }
+    }
+
+    #[test]
+    fn translations_keep_arguments() {
+        for (english, translated) in de::STRINGS {
+            let count = |s: &str| s.matches('{').count();
+            assert_eq!(count(english), count(translated), "{english:?}");
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use egui::{InputState, Key};
+use glam::Vec2;
+
+use crate::gamepad::GamepadMotion;
+
+// How fast a held key moves the camera, in the same units as the gamepad sticks.
+const ORBIT_SPEED: f32 = 1000.0;
+const PAN_SPEED: f32 = 1000.0;
+const ZOOM_SPEED: f32 = 150.0;
+
+fn axis(input: &InputState, negative: &[Key], positive: &[Key]) -> f32 {
+    let held = |keys: &[Key]| keys.iter().any(|k| input.key_down(*k));
+    match (held(negative), held(positive)) {
+        (true, false) => -1.0,
+        (false, true) => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Camera movement of the held keys, to navigate the viewer without a mouse. The arrow keys
+/// orbit, or pan while shift is held, and page up and down or plus and minus zoom.
+pub(crate) fn keyboard_motion(input: &InputState, delta_time: f32) -> GamepadMotion {
+    // Leave shortcuts like ctrl + plus to egui.
+    if input.modifiers.command || input.modifiers.alt {
+        return GamepadMotion::default();
+    }
+
+    let arrows = Vec2::new(
+        axis(input, &[Key::ArrowLeft], &[Key::ArrowRight]),
+        axis(input, &[Key::ArrowUp], &[Key::ArrowDown]),
+    );
+    let zoom = axis(
+        input,
+        &[Key::PageDown, Key::Minus],
+        &[Key::PageUp, Key::Plus, Key::Equals],
+    );
+
+    let mut motion = GamepadMotion::default();
+    if input.modifiers.shift {
+        motion.pan = arrows * PAN_SPEED * delta_time;
+    } else {
+        motion.rotate = arrows * ORBIT_SPEED * delta_time;
+    }
+    motion.zoom = zoom * ZOOM_SPEED * delta_time;
+    motion
+}
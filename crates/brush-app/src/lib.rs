@@ -1,11 +1,28 @@
+mod accessibility;
+pub mod actions;
+#[cfg(not(target_family = "wasm"))]
+pub mod adapter;
+#[cfg(not(target_family = "wasm"))]
+pub mod cli;
+mod command_palette;
 pub mod data_source;
+mod drop_import;
+mod dynamic_resolution;
+mod gamepad;
+pub mod i18n;
+mod keyboard;
 mod orbit_controls;
 mod panels;
 pub mod process_loop;
+pub mod recent;
+pub mod session;
 
 #[cfg(not(target_family = "wasm"))]
 mod rerun_tools;
 
+#[cfg(not(target_family = "wasm"))]
+pub mod remote;
+
 mod app;
 
 pub use app::*;
@@ -1,5 +1,6 @@
 use crate::{
     app::{AppContext, AppPanel},
+    i18n::tr,
     process_loop::ProcessMessage,
 };
 use brush_train::scene::{Scene, ViewType};
@@ -36,12 +37,12 @@ impl DatasetPanel {
 
 impl AppPanel for DatasetPanel {
     fn title(&self) -> String {
-        "Dataset".to_owned()
+        tr("Dataset").to_owned()
     }
 
     fn on_message(&mut self, message: &ProcessMessage, context: &mut AppContext) {
         match message {
-            ProcessMessage::NewSource | ProcessMessage::DoneLoading { .. } => {
+            ProcessMessage::NewSource { .. } | ProcessMessage::DoneLoading { .. } => {
                 self.loading = false;
             }
             ProcessMessage::StartLoading { training } => {
@@ -75,7 +76,7 @@ impl AppPanel for DatasetPanel {
             }
 
             if dirty {
-                let image = &self.selected_scene(context).views[*nearest].image;
+                let image = self.selected_scene(context).views[*nearest].image.load();
                 let img_size = [image.width() as usize, image.height() as usize];
                 let color_img = if image.color().has_alpha() {
                     egui::ColorImage::from_rgba_unmultiplied(img_size, &image.to_rgba8().into_vec())
@@ -170,8 +171,48 @@ impl AppPanel for DatasetPanel {
             });
         }
 
+        if let Some(stats) = &context.dataset.reconstruction {
+            ui.collapsing(tr("Reconstruction"), |ui| {
+                egui::Grid::new("reconstruction_grid")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label(tr("Points"));
+                        ui.label(format!("{}", stats.num_points));
+                        ui.end_row();
+
+                        ui.label(tr("Mean track length"));
+                        ui.label(format!("{:.2}", stats.mean_track_length));
+                        ui.end_row();
+
+                        ui.label(tr("Mean reprojection error"));
+                        ui.label(format!("{:.3} px", stats.mean_reprojection_error));
+                        ui.end_row();
+
+                        if stats.num_database_images > 0 {
+                            ui.label(tr("Images with features"));
+                            ui.label(format!("{}", stats.num_database_images));
+                            ui.end_row();
+
+                            ui.label(tr("Verified image pairs"));
+                            ui.label(format!("{}", stats.num_verified_pairs));
+                            ui.end_row();
+
+                            ui.label(tr("Mean keypoints"));
+                            ui.label(format!("{:.0}", stats.mean_keypoints));
+                            ui.end_row();
+
+                            ui.label(tr("Mean verified matches"));
+                            ui.label(format!("{:.0}", stats.mean_matches));
+                            ui.end_row();
+                        }
+                    });
+            });
+        }
+
         if self.loading {
-            ui.label("Loading...");
+            ui.label(tr("Loading..."));
         }
     }
 }
@@ -1,158 +1,501 @@
 use crate::{
+    accessibility::interface_settings_ui,
     app::{AppContext, AppPanel},
     data_source::DataSource,
-    process_loop::{start_process, ProcessArgs},
+    i18n::tr,
+    process_loop::start_process,
+};
+use brush_dataset::{
+    coordinates::CoordinateConvention, point_cloud::PointCloudFilter, LoadDatasetArgs,
+};
+use brush_render::bounding_box::BoundingBox;
+use brush_train::{
+    freeze::{FreezeRule, ParamGroup},
+    loss::PhotometricLoss,
+    sampler::ViewSampling,
 };
-use brush_dataset::{LoadDatasetArgs, LoadInitArgs};
-use brush_train::train::TrainConfig;
 use egui::Slider;
 
+#[cfg(not(target_family = "wasm"))]
+use crate::process_loop::{LiveCaptureArgs, LiveSource};
+
 pub(crate) struct LoadDataPanel {
-    args: ProcessArgs,
     url: String,
+    #[cfg(not(target_family = "wasm"))]
+    remote_addr: String,
+    #[cfg(not(target_family = "wasm"))]
+    live_source: String,
+    #[cfg(not(target_family = "wasm"))]
+    pose_stream_addr: String,
 }
 
 impl LoadDataPanel {
     pub(crate) fn new() -> Self {
         Self {
-            args: ProcessArgs {
-                // Super high resolutions are a bit sketchy. Limit to at least
-                // some size.
-                load_args: LoadDatasetArgs {
-                    max_resolution: Some(1920),
-                    ..Default::default()
-                },
-                train_config: TrainConfig::default(),
-                init_args: LoadInitArgs::default(),
-                source: DataSource::PickFile,
-            },
             url: "splat.com/example.ply".to_owned(),
+            #[cfg(not(target_family = "wasm"))]
+            remote_addr: "localhost:7878".to_owned(),
+            #[cfg(not(target_family = "wasm"))]
+            live_source: "/dev/video0".to_owned(),
+            #[cfg(not(target_family = "wasm"))]
+            pose_stream_addr: "0.0.0.0:7879".to_owned(),
         }
     }
 }
 
+/// Options for splat models that are viewed. Only ply files can be in another coordinate
+/// convention, the other formats are always in Brush coordinates.
+pub(crate) fn model_import_options(ui: &mut egui::Ui, load_args: &mut LoadDatasetArgs, ply: bool) {
+    if ply {
+        let coordinates = &mut load_args.coordinates;
+        egui::ComboBox::from_label(tr("Ply coordinates"))
+            .selected_text(coordinates.name())
+            .show_ui(ui, |ui| {
+                for (name, convention) in CoordinateConvention::ALL {
+                    ui.selectable_value(coordinates, convention, name);
+                }
+            })
+            .response
+            .on_hover_text(tr(
+                "Convention of the ply files to view, converted to Brush on load",
+            ));
+    }
+
+    ui.horizontal(|ui| {
+        let mut scale = load_args.scale.unwrap_or(1.0);
+        let changed = ui
+            .add(
+                egui::DragValue::new(&mut scale)
+                    .speed(0.01)
+                    .range(0.001..=1000.0),
+            )
+            .changed();
+        ui.label(tr("Model scale")).on_hover_text(tr(
+            "Scale of the models to view, eg. 0.01 for a model in centimeters",
+        ));
+        if changed {
+            load_args.scale = (scale != 1.0).then_some(scale);
+        }
+    });
+}
+
 impl AppPanel for LoadDataPanel {
     fn title(&self) -> String {
-        "Load data".to_owned()
+        tr("Load data").to_owned()
     }
 
     fn ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
+        // Started processes replace the current one once the settings aren't borrowed anymore.
+        let mut process = None;
         egui::ScrollArea::vertical().show(ui, |ui| {
-            ui.label("Select a .ply to visualize, or a .zip with training data.");
+            let args = &mut context.process_args;
+            ui.label(tr("Select or drop a .ply to visualize, or a .zip with training data."));
 
-            let file = ui.button("Load file").clicked();
+            let file = ui.button(tr("Load file")).clicked();
 
             let can_pick_dir = !cfg!(target_family = "wasm") && !cfg!(target_os = "android");
-            let dir = can_pick_dir && ui.button("Load directory").clicked();
+            let dir = can_pick_dir && ui.button(tr("Load directory")).clicked();
 
             ui.add_space(10.0);
             ui.text_edit_singleline(&mut self.url);
 
-            let url = ui.button("Load URL").clicked();
+            let url = ui.button(tr("Load URL")).clicked();
 
             ui.add_space(10.0);
 
+            model_import_options(ui, &mut args.load_args, true);
+
             if file || dir || url {
-                self.args.source = if file {
+                args.source = if file {
                     DataSource::PickFile
                 } else if dir {
                     DataSource::PickDirectory
                 } else {
                     DataSource::Url(self.url.clone())
                 };
-                context.connect_to(start_process(self.args.clone(), context.device.clone()));
+                process = Some(start_process(args.clone(), context.device.clone()));
+            }
+
+            #[cfg(not(target_family = "wasm"))]
+            {
+                ui.add_space(10.0);
+                ui.label(tr("Watch a trainer started with brush_app train --serve."));
+                ui.text_edit_singleline(&mut self.remote_addr);
+                if ui.button(tr("Connect to trainer")).clicked() {
+                    process = Some(crate::remote::start_remote_process(
+                        self.remote_addr.clone(),
+                        context.device.clone(),
+                    ));
+                }
+
+                ui.add_space(10.0);
+                ui.label(tr("Train live on a webcam or RTSP stream (experimental, needs ffmpeg)."));
+                ui.text_edit_singleline(&mut self.live_source);
+                let video = ui.button(tr("Start live capture")).clicked();
+
+                ui.label(tr("Train on frames and poses streamed from a phone AR app."));
+                ui.text_edit_singleline(&mut self.pose_stream_addr);
+                let phone = ui.button(tr("Receive from phone")).clicked();
+
+                if video || phone {
+                    let source = if video {
+                        LiveSource::Video(self.live_source.clone())
+                    } else {
+                        LiveSource::PoseStream(self.pose_stream_addr.clone())
+                    };
+                    let live_args = LiveCaptureArgs {
+                        train_config: args.train_config.clone(),
+                        ..LiveCaptureArgs::new(source)
+                    };
+                    process = Some(crate::process_loop::start_live_process(
+                        live_args,
+                        context.device.clone(),
+                    ));
+                }
             }
 
             ui.add_space(10.0);
-            ui.heading("Train settings");
+            ui.heading(tr("Train settings"));
 
-            ui.label("Spherical Harmonics Degree:");
-            ui.add(Slider::new(&mut self.args.init_args.sh_degree, 0..=4));
+            ui.label(tr("Spherical Harmonics Degree:"));
+            ui.add(Slider::new(&mut args.init_args.sh_degree, 0..=4));
 
-            let mut limit_res = self.args.load_args.max_resolution.is_some();
+            let loss = &mut args.train_config.photometric_loss;
+            egui::ComboBox::from_label(tr("Photometric loss"))
+                .selected_text(format!("{loss:?}"))
+                .show_ui(ui, |ui| {
+                    for option in [
+                        PhotometricLoss::L1,
+                        PhotometricLoss::L2,
+                        PhotometricLoss::Huber,
+                        PhotometricLoss::Charbonnier,
+                    ] {
+                        let text = format!("{option:?}");
+                        ui.selectable_value(loss, option, text);
+                    }
+                });
+
+            let sampling = &mut args.train_config.view_sampling;
+            egui::ComboBox::from_label(tr("View sampling"))
+                .selected_text(format!("{sampling:?}"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(sampling, ViewSampling::Shuffle, tr("Shuffle"));
+                    ui.selectable_value(sampling, ViewSampling::Priority, tr("Priority"));
+                });
+
+            let config = &mut args.train_config;
+            let mut coarse_to_fine = config.coarse_to_fine_levels > 0;
             if ui
-                .checkbox(&mut limit_res, "Limit training resolution")
+                .checkbox(&mut coarse_to_fine, tr("Coarse to fine"))
+                .on_hover_text(tr("Train on downscaled images first, which is faster at the start"))
                 .clicked()
             {
-                self.args.load_args.max_resolution = if limit_res { Some(800) } else { None };
+                config.coarse_to_fine_levels = if coarse_to_fine { 2 } else { 0 };
+            }
+            if coarse_to_fine {
+                ui.add(Slider::new(&mut config.coarse_to_fine_levels, 1..=4).prefix("1/2^"));
+                ui.add(
+                    Slider::new(&mut config.coarse_to_fine_every, 250..=5000)
+                        .prefix(tr("double every "))
+                        .suffix(tr(" steps")),
+                );
             }
 
-            if let Some(target_res) = self.args.load_args.max_resolution.as_mut() {
+            let mut limit_res = args.load_args.max_resolution.is_some();
+            if ui
+                .checkbox(&mut limit_res, tr("Limit training resolution"))
+                .clicked()
+            {
+                args.load_args.max_resolution = if limit_res { Some(800) } else { None };
+            }
+
+            if let Some(target_res) = args.load_args.max_resolution.as_mut() {
                 ui.add(Slider::new(target_res, 32..=2048));
             }
 
-            let mut limit_frames = self.args.load_args.max_frames.is_some();
-            if ui.checkbox(&mut limit_frames, "Limit max frames").clicked() {
-                self.args.load_args.max_frames = if limit_frames { Some(32) } else { None };
+            let mut limit_frames = args.load_args.max_frames.is_some();
+            if ui.checkbox(&mut limit_frames, tr("Limit max frames")).clicked() {
+                args.load_args.max_frames = if limit_frames { Some(32) } else { None };
             }
 
-            if let Some(max_frames) = self.args.load_args.max_frames.as_mut() {
+            if let Some(max_frames) = args.load_args.max_frames.as_mut() {
                 ui.add(Slider::new(max_frames, 1..=256));
             }
 
-            let mut use_eval_split = self.args.load_args.eval_split_every.is_some();
+            let mut use_eval_split = args.load_args.eval_split_every.is_some();
             if ui
-                .checkbox(&mut use_eval_split, "Split dataset for evaluation")
+                .checkbox(&mut use_eval_split, tr("Split dataset for evaluation"))
                 .clicked()
             {
-                self.args.load_args.eval_split_every = if use_eval_split { Some(8) } else { None };
+                args.load_args.eval_split_every = if use_eval_split { Some(8) } else { None };
             }
 
-            if let Some(eval_split) = self.args.load_args.eval_split_every.as_mut() {
+            if let Some(eval_split) = args.load_args.eval_split_every.as_mut() {
                 ui.add(
                     Slider::new(eval_split, 2..=32)
-                        .prefix("1 out of ")
-                        .suffix(" frames"),
+                        .prefix(tr("1 out of "))
+                        .suffix(tr(" frames")),
                 );
+                ui.checkbox(&mut args.load_args.random_eval_split, tr("Random eval views"))
+                    .on_hover_text(tr("Pick the eval views at random with the seed, instead of every nth view"));
             }
 
             ui.horizontal(|ui| {
-                ui.label("Evaluate");
+                ui.label(tr("Evaluate"));
                 ui.add(
-                    egui::Slider::new(&mut self.args.train_config.eval_every, 1..=5000)
-                        .prefix("every ")
-                        .suffix(" steps"),
+                    egui::Slider::new(&mut args.train_config.eval_every, 1..=5000)
+                        .prefix(tr("every "))
+                        .suffix(tr(" steps")),
                 );
             });
 
-            let mut use_frame_subsample = self.args.load_args.subsample_frames.is_some();
+            ui.checkbox(
+                &mut args.train_config.optimize_intrinsics,
+                tr("Refine camera intrinsics"),
+            );
+            ui.checkbox(
+                &mut args.train_config.rolling_shutter,
+                tr("Rolling shutter cameras"),
+            );
+            ui.checkbox(
+                &mut args.train_config.background_model,
+                tr("Learn background"),
+            );
+            ui.checkbox(&mut args.train_config.shadow_catcher, tr("Shadow catcher"))
+                .on_hover_text(tr("Learn the ground separately from the scene, so it can be left out of exports of object captures. Saves best_shadow_catcher.ply next to best.ply"));
+            ui.checkbox(&mut args.train_config.relighting, tr("Relightable (experimental)"))
+                .on_hover_text(tr("Learn the albedo of the splats and the lighting of the scene separately. Saves best_albedo.ply and best_lighting.json next to best.ply"));
+            ui.checkbox(&mut args.train_config.feature_splatting, tr("Feature splatting"))
+                .on_hover_text(tr("Learn a feature per splat from the feature maps of the views (feature_path of each frame in transforms.json, a float .npy of [height, width, dim]). Saves best_features.npy next to best.ply"));
+
+            let mut perceptual = args.train_config.perceptual_weight > 0.0;
+            if ui
+                .checkbox(&mut perceptual, tr("Perceptual loss"))
+                .on_hover_text(tr("Compare VGG features for sharper textures. Downloads the VGG16 weights (~500MB)"))
+                .clicked()
+            {
+                args.train_config.perceptual_weight = if perceptual { 0.05 } else { 0.0 };
+            }
+
+            if perceptual {
+                ui.add(
+                    Slider::new(&mut args.train_config.perceptual_weight, 0.001..=0.5)
+                        .logarithmic(true)
+                        .prefix(tr("weight ")),
+                );
+                ui.add(
+                    Slider::new(&mut args.train_config.perceptual_every, 1..=100)
+                        .prefix(tr("every "))
+                        .suffix(tr(" steps")),
+                );
+            }
+
+            let mut model_blur = args.train_config.blur_samples > 1;
+            if ui.checkbox(&mut model_blur, tr("Model blurry views")).clicked() {
+                args.train_config.blur_samples = if model_blur { 4 } else { 0 };
+            }
+
+            ui.checkbox(
+                &mut args.train_config.sharpness_weighting,
+                tr("Weight views by sharpness"),
+            );
+            ui.add(
+                Slider::new(&mut args.train_config.drop_blurry_fraction, 0.0..=0.5)
+                    .prefix(tr("drop blurriest "))
+                    .custom_formatter(|x, _| format!("{:.0}%", x * 100.0)),
+            );
+
+            let mut use_frame_subsample = args.load_args.subsample_frames.is_some();
             if ui
-                .checkbox(&mut use_frame_subsample, "Subsample frames")
+                .checkbox(&mut use_frame_subsample, tr("Subsample frames"))
                 .clicked()
             {
-                self.args.load_args.subsample_frames =
+                args.load_args.subsample_frames =
                     if use_frame_subsample { Some(2) } else { None };
             }
 
-            if let Some(subsample_frames) = self.args.load_args.subsample_frames.as_mut() {
+            if let Some(subsample_frames) = args.load_args.subsample_frames.as_mut() {
                 ui.add(
                     Slider::new(subsample_frames, 2..=32)
-                        .prefix("Keep 1 out of ")
-                        .suffix(" frames"),
+                        .prefix(tr("Keep 1 out of "))
+                        .suffix(tr(" frames")),
                 );
             }
 
-            let mut use_point_subsample = self.args.load_args.subsample_points.is_some();
+            let mut use_point_subsample = args.load_args.subsample_points.is_some();
             if ui
-                .checkbox(&mut use_point_subsample, "Subsample points")
+                .checkbox(&mut use_point_subsample, tr("Subsample points"))
                 .clicked()
             {
-                self.args.load_args.subsample_points =
+                args.load_args.subsample_points =
                     if use_point_subsample { Some(2) } else { None };
             }
 
-            if let Some(subsample_points) = self.args.load_args.subsample_points.as_mut() {
+            if let Some(subsample_points) = args.load_args.subsample_points.as_mut() {
                 ui.add(
                     Slider::new(subsample_points, 2..=32)
-                        .prefix("Keep 1 out of ")
-                        .suffix(" points"),
+                        .prefix(tr("Keep 1 out of "))
+                        .suffix(tr(" points")),
                 );
             }
 
+            let mut filter_points = args.load_args.point_filter.is_some();
+            if ui
+                .checkbox(&mut filter_points, tr("Filter noisy points"))
+                .on_hover_text(
+                    tr("Remove stray points from lidar or depth sensor point clouds, and orient \
+                     the initial splats along the surface"),
+                )
+                .clicked()
+            {
+                args.load_args.point_filter = filter_points.then(PointCloudFilter::default);
+            }
+
+            let mut use_downscale = args.load_args.image_downscale.is_some();
+            if ui
+                .checkbox(&mut use_downscale, tr("Downscaled images"))
+                .on_hover_text(tr("Load COLMAP images from eg. images_4 instead of images"))
+                .clicked()
+            {
+                args.load_args.image_downscale = if use_downscale { Some(2) } else { None };
+            }
+
+            if let Some(downscale) = args.load_args.image_downscale.as_mut() {
+                ui.add(Slider::new(downscale, 2..=8).prefix("images_"));
+            }
+
+            let photometric = &mut args.load_args.photometric;
+            ui.checkbox(&mut photometric.vignette, tr("Correct vignetting"))
+                .on_hover_text(tr("Brighten the darkened corners of each image"));
+            ui.checkbox(&mut photometric.white_balance, tr("Normalize white balance"))
+                .on_hover_text(
+                    tr("Remove color casts that differ between images, eg. from phone cameras"),
+                );
+
+            let budget = &mut args.load_args.host_image_budget_mb;
+            let mut limit_memory = budget.is_some();
+            if ui
+                .checkbox(&mut limit_memory, tr("Limit image memory"))
+                .on_hover_text(tr("Keep images compressed and decode them when they're needed"))
+                .clicked()
+            {
+                *budget = limit_memory.then_some(4096);
+            }
+            if let Some(budget) = budget.as_mut() {
+                ui.add(Slider::new(budget, 512..=32768).logarithmic(true).suffix(" MB"));
+            }
+
+            if !cfg!(target_family = "wasm") {
+                let save_args = &mut args.save_args;
+                let mut autosave = save_args.output_dir.is_some();
+                if ui.checkbox(&mut autosave, tr("Save checkpoints")).clicked() {
+                    save_args.output_dir = autosave.then(|| "brush_output".into());
+                }
+
+                if let Some(output_dir) = save_args.output_dir.as_mut() {
+                    let mut dir = output_dir.to_string_lossy().into_owned();
+                    if ui.text_edit_singleline(&mut dir).changed() {
+                        *output_dir = dir.into();
+                    }
+
+                    if let Some(save_every) = save_args.save_every.as_mut() {
+                        ui.add(
+                            Slider::new(save_every, 500..=30000)
+                                .prefix(tr("every "))
+                                .suffix(tr(" steps")),
+                        );
+                    }
+                    ui.add(Slider::new(&mut save_args.keep_last, 1..=10).prefix(tr("keep last ")));
+                    ui.checkbox(&mut save_args.save_best, tr("Save best model by eval PSNR"));
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.label(tr("Freeze")).on_hover_text(
+                    tr("Keep these parameters fixed while training, eg. freeze all but sh to only \
+                     touch up the colors of an imported model"),
+                );
+                let freeze = &mut args.train_config.freeze;
+                for group in ParamGroup::ALL {
+                    let mut frozen = freeze.iter().any(|r| r.group == group);
+                    if ui.checkbox(&mut frozen, group.name()).clicked() {
+                        freeze.retain(|r| r.group != group);
+                        if frozen {
+                            freeze.push(FreezeRule::new(group));
+                        }
+                    }
+                }
+            });
+
+            let mut refine_region = args.train_config.refine_region.is_some();
+            if ui
+                .checkbox(&mut refine_region, tr("Refine region"))
+                .on_hover_text(
+                    tr("Only train the splats in a box, on the views that see it, eg. to fix one \
+                     area of a trained model loaded with an init.ply or checkpoint"),
+                )
+                .clicked()
+            {
+                args.train_config.refine_region = refine_region
+                    .then(|| BoundingBox::from_min_max(-glam::Vec3::ONE, glam::Vec3::ONE));
+            }
+
+            if let Some(region) = args.train_config.refine_region.as_mut() {
+                let (mut min, mut max) = (region.min(), region.max());
+                let mut changed = false;
+                for (label, corner) in [("min", &mut min), ("max", &mut max)] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        for axis in 0..3 {
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut corner[axis]).speed(0.05))
+                                .changed();
+                        }
+                    });
+                }
+                if changed {
+                    *region = BoundingBox::from_min_max(min.min(max), max.max(min));
+                }
+            }
+
+            let mut use_seed = args.load_args.seed.is_some();
+            if ui
+                .checkbox(&mut use_seed, tr("Fixed seed"))
+                .on_hover_text(tr("Use a fixed seed for the view order and initialization, so runs are reproducible"))
+                .clicked()
+            {
+                args.load_args.seed = if use_seed { Some(42) } else { None };
+            }
+
+            if let Some(seed) = args.load_args.seed.as_mut() {
+                ui.add(egui::DragValue::new(seed).prefix(tr("seed ")));
+            }
+
+            let mut pick_model = args.load_args.model_index.is_some();
+            if ui
+                .checkbox(&mut pick_model, tr("Select COLMAP model"))
+                .on_hover_text(tr("By default the COLMAP model with the most images is used"))
+                .clicked()
+            {
+                args.load_args.model_index = if pick_model { Some(0) } else { None };
+            }
+
+            if let Some(model_index) = args.load_args.model_index.as_mut() {
+                ui.add(Slider::new(model_index, 0..=9).prefix("sparse/"));
+            }
+
+            ui.add_space(10.0);
+            ui.heading(tr("Interface"));
+            interface_settings_ui(ui, &mut context.interface);
+
             #[cfg(not(target_family = "wasm"))]
             if ui.input(|r| r.key_pressed(egui::Key::Escape)) {
                 ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
             }
         });
+
+        if let Some(process) = process {
+            context.connect_to(process);
+        }
     }
 }
@@ -3,6 +3,7 @@ mod load_data;
 
 mod presets;
 mod scene;
+mod splat_stats;
 mod stats;
 mod tracing_debug;
 
@@ -10,6 +11,7 @@ pub(crate) use datasets::*;
 pub(crate) use load_data::*;
 pub(crate) use presets::*;
 pub(crate) use scene::*;
+pub(crate) use splat_stats::*;
 pub(crate) use stats::*;
 #[allow(unused)]
 pub(crate) use tracing_debug::*;
@@ -1,5 +1,6 @@
 use crate::{
     app::{AppContext, AppPanel},
+    i18n::tr,
     process_loop::ProcessMessage,
 };
 use egui::Hyperlink;
@@ -18,13 +19,13 @@ fn url_button(label: &str, url: &str, ui: &mut egui::Ui) {
 
 impl AppPanel for PresetsPanel {
     fn title(&self) -> String {
-        "Presets".to_owned()
+        tr("Presets").to_owned()
     }
 
     fn on_message(&mut self, _: &ProcessMessage, _: &mut AppContext) {}
 
     fn ui(&mut self, ui: &mut egui::Ui, _: &mut AppContext) {
-        ui.heading("Mipnerf scenes");
+        ui.heading(tr("Mipnerf scenes"));
 
         egui::Grid::new("mip_grid")
             .num_columns(3)
@@ -45,7 +46,7 @@ impl AppPanel for PresetsPanel {
                 ui.end_row();
             });
 
-        ui.heading("Synthetic blender scenes");
+        ui.heading(tr("Synthetic blender scenes"));
         egui::Grid::new("blend_grid")
             .num_columns(4)
             .spacing([40.0, 4.0])
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use crate::{
     app::{AppContext, AppPanel},
+    i18n::tr,
     process_loop::ProcessMessage,
     rerun_tools::VisualizeTools,
 };
@@ -30,7 +31,7 @@ impl RerunPanel {}
 
 impl AppPanel for RerunPanel {
     fn title(&self) -> String {
-        "Rerun".to_owned()
+        tr("Rerun").to_owned()
     }
 
     fn on_message(&mut self, message: &ProcessMessage, _context: &mut AppContext) {
@@ -53,7 +54,7 @@ impl AppPanel for RerunPanel {
                 splats,
                 stats,
                 iter,
-                timestamp: _,
+                ..
             } => {
                 let Some(visualize) = self.visualize.clone() else {
                     return;
@@ -91,20 +92,22 @@ impl AppPanel for RerunPanel {
 
     fn ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
         let Some(visualize) = self.visualize.clone() else {
-            if ui.button("Enable rerun").clicked() {
+            if ui.button(tr("Enable rerun")).clicked() {
                 self.visualize = Some(Arc::new(VisualizeTools::new()));
             }
 
             ui.horizontal(|ui| {
                 ui.spacing_mut().item_spacing.x = 0.0;
-                ui.label("Stream data to ");
+                ui.label(tr("Stream data to "));
                 ui.hyperlink_to("Rerun.io", "https://rerun.io");
-                ui.label(" for visualization");
+                ui.label(tr(" for visualization"));
             });
 
-            ui.label("Install the viewer to get started.");
-            ui.label("Will open the viewer if it isn't open yet. Open the viewer before enabling rerun to keep data.");
-            ui.label("Open the brush_blueprint.rbl in the rerun viewer for a good default layout.");
+            ui.label(tr("Install the viewer to get started."));
+            ui.label(tr("Will open the viewer if it isn't open yet. Open the viewer before enabling rerun to keep data."));
+            ui.label(tr(
+                "Open the brush_blueprint.rbl in the rerun viewer for a good default layout.",
+            ));
             return;
         };
 
@@ -114,32 +117,32 @@ impl AppPanel for RerunPanel {
         }
 
         ui.horizontal(|ui| {
-            ui.label("Log train stats");
+            ui.label(tr("Log train stats"));
             ui.add(
                 egui::Slider::new(&mut self.log_train_stats_every, 1..=1000)
-                    .prefix("every ")
-                    .suffix(" steps"),
+                    .prefix(tr("every "))
+                    .suffix(tr(" steps")),
             );
         });
 
         let mut limit_eval_views = self.eval_view_count.is_some();
-        ui.checkbox(&mut limit_eval_views, "Limit eval views");
+        ui.checkbox(&mut limit_eval_views, tr("Limit eval views"));
         if limit_eval_views != self.eval_view_count.is_some() {
             self.eval_view_count = if limit_eval_views { Some(4) } else { None };
         }
 
         if let Some(count) = self.eval_view_count.as_mut() {
-            ui.add(egui::Slider::new(count, 1..=100).text("Eval view count"));
+            ui.add(egui::Slider::new(count, 1..=100).text(tr("Eval view count")));
         }
 
         let mut visualize_splats = self.visualize_splats_every.is_some();
-        ui.checkbox(&mut visualize_splats, "Visualize splats");
+        ui.checkbox(&mut visualize_splats, tr("Visualize splats"));
         if visualize_splats != self.visualize_splats_every.is_some() {
             self.visualize_splats_every = if visualize_splats { Some(500) } else { None };
         }
 
         if let Some(every) = self.visualize_splats_every.as_mut() {
-            ui.add(egui::Slider::new(every, 1..=5000).text("Visualize splats every"));
+            ui.add(egui::Slider::new(every, 1..=5000).text(tr("Visualize splats every")));
         }
     }
 }
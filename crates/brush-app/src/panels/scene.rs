@@ -1,24 +1,55 @@
-use brush_dataset::splat_export;
+use brush_dataset::{
+    binary_model, chunk_export,
+    coordinates::CoordinateConvention,
+    edit_layers::{sidecar_path, Edit, EditLayer, EditStack, Selection, SplatTransform},
+    model_info::{dataset_fingerprint, ModelInfo},
+    npy, octree_export, progressive_export, splat_export,
+};
+use brush_train::{
+    environment::{EnvironmentMap, EnvironmentRays},
+    features::{highlight_selection, FeatureField},
+    ground::GroundPlane,
+    shadow_catcher::{with_shadow_catcher, ShadowCatcher},
+};
 use brush_ui::burn_texture::BurnTexture;
+use burn::tensor::{Bool, Tensor};
 use burn_wgpu::Wgpu;
 use core::f32;
 use egui::epaint::mutex::RwLock as EguiRwLock;
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use brush_render::{
-    camera::{focal_to_fov, fov_to_focal},
+    bounding_box::BoundingBox,
+    camera::{focal_to_fov, fov_to_focal, Camera},
+    contribution,
     gaussian_splats::Splats,
+    motion, raycast,
+    spatial_index::SpatialIndex,
+    temporal::{jittered_camera, TemporalAccumulator},
+    uncertainty, ColorSpace, DepthKey, Foveation, RenderMode, RenderOptions,
 };
 use eframe::egui_wgpu::Renderer;
 use egui::{Color32, Rect};
 use glam::{Quat, Vec2};
-use tokio_with_wasm::alias as tokio_wasm;
 use tracing::trace_span;
 use web_time::Instant;
 
 use crate::{
+    actions::{ExportFormat, ViewSetting, ViewerAction},
     app::{AppContext, AppPanel},
-    process_loop::{ControlMessage, ProcessMessage},
+    data_source::DataSource,
+    dynamic_resolution::DynamicResolution,
+    gamepad::{GamepadInput, GamepadMotion},
+    i18n::{tr, tr_args},
+    keyboard::keyboard_motion,
+    process_loop::{start_process, ControlMessage, HealArgs, ProcessArgs, ProcessMessage},
+    recent::{thumbnail_path, RecentFiles, THUMBNAIL_WIDTH},
+    session::{config_dir, DisplaySettings, Session},
 };
 
 pub(crate) struct ScenePanel {
@@ -36,18 +67,157 @@ pub(crate) struct ScenePanel {
     is_training: bool,
     live_update: bool,
     paused: bool,
+    render_options: RenderOptions,
+    last_interacting: bool,
+    resolution: DynamicResolution,
+    // Blends jittered frames while the view moves, when enabled.
+    temporal: TemporalAccumulator<Wgpu>,
+    temporal_aa: bool,
+    // The splats of the last accumulated frame, for the motion of animated splats.
+    temporal_splats: Option<Splats<Wgpu>>,
+    // Preview of foveated rendering, with the fovea under the pointer.
+    foveated: bool,
+    // Whether egui draws to a surface that can show HDR colors.
+    hdr_output: bool,
+    gamepad: GamepadInput,
 
     last_size: glam::UVec2,
     dirty: bool,
     renderer: Arc<EguiRwLock<Renderer>>,
     zen: bool,
+    export_coordinates: CoordinateConvention,
+    // The ground of the dataset, the viewer is aligned to it and exports can be leveled to it.
+    ground: Option<GroundPlane>,
+    level_export: bool,
+    // Where the current splats came from, written in exported plys.
+    export_info: ModelInfo,
+
+    // The layer trained on the ground, shown and exported with the splats when enabled.
+    shadow_catcher: Option<ShadowCatcher<Wgpu>>,
+    show_shadow_catcher: bool,
+
+    // The learned background, drawn behind the splats of opaque scenes.
+    environment: Option<EnvironmentMap<Wgpu>>,
+    environment_rays: EnvironmentRays<Wgpu>,
+
+    // The learned features of the splats, to select everything similar to a query feature,
+    // picked from the view or loaded from a file.
+    features: Option<FeatureField<Wgpu>>,
+    feature_query: Arc<Mutex<Option<Vec<f32>>>>,
+    // Point under a double click to orbit around, found by a raycast.
+    focus_target: Arc<Mutex<Option<glam::Vec3>>>,
+    // Spatial index of the splats of a frame, built on the first raycast.
+    spatial_index: Arc<Mutex<Option<(usize, Arc<SpatialIndex>)>>>,
+    // The query the last frame was rendered with, to redraw when a new query comes in.
+    shown_query: Option<Vec<f32>>,
+    query_threshold: f32,
+    picking: bool,
+    // Edits of the splats, shown and exported on top of them, see `brush_dataset::edit_layers`.
+    edits: EditStack,
+    // The edited splats of a frame, until the splats or the edits change.
+    edited: Option<(usize, Splats<Wgpu>)>,
+    // The layer being changed in the list of edits.
+    editing_layer: Option<usize>,
+    // Layers made in the background, eg. from a feature selection, and edits loaded from a file.
+    new_layers: Arc<Mutex<Vec<EditLayer>>>,
+    loaded_edits: Arc<Mutex<Option<EditStack>>>,
+    // Splats to seed in the box of a layer when healing it, and the steps to train them.
+    heal_seeds: usize,
+    heal_steps: u32,
+
+    show_uncertainty: bool,
+    // View counts of the splats of the given frame, see `uncertainty::view_counts`. Computed
+    // when first shown, and while training again every `UNCERTAINTY_EVERY` steps or when the
+    // number of splats changes.
+    uncertainty: Option<(usize, Tensor<Wgpu, 1>)>,
+    // Suggested camera poses to take more photos from, see `uncertainty::suggest_views`.
+    suggested_views: Arc<Mutex<Vec<(Camera, f32)>>>,
+
+    show_contribution: bool,
+    // Contribution colored splats of the given frame, for the camera at the time they were
+    // computed. Kept while looking around, to see the splats of that view from elsewhere.
+    contribution: Option<(usize, Splats<Wgpu>)>,
+
+    // Thumbnails of the recent files on the start screen, loaded when first shown.
+    thumbnails: HashMap<PathBuf, Option<egui::TextureHandle>>,
 }
 
+// The view counts are computed from a subset of the training views at a low resolution,
+// which is plenty to find under-constrained regions.
+const MAX_UNCERTAINTY_VIEWS: usize = 64;
+const UNCERTAINTY_VIEW_SIZE: u32 = 256;
+// Steps between updates of the view counts while training.
+const UNCERTAINTY_EVERY: u32 = 100;
+const NUM_SUGGESTED_VIEWS: usize = 5;
+// The color spaces to show the splats in, with a description of each.
+const COLOR_SPACES: [(&str, &str, ColorSpace); 3] = [
+    ("sRGB", "For regular displays", ColorSpace::Srgb),
+    (
+        "Display P3",
+        "For wide gamut displays, eg. of most laptops and phones, where sRGB colors look \
+         oversaturated",
+        ColorSpace::DisplayP3,
+    ),
+    (
+        "HDR10",
+        "For HDR displays, with white at the brightness of SDR content",
+        ColorSpace::Hdr10,
+    ),
+];
+// Radius of the fovea of the foveated preview, as a fraction of the view.
+const FOVEA_RADIUS: f32 = 0.15;
+// The ways to draw splats, with a description of each.
+const RENDER_MODES: [(&str, &str, RenderMode); 4] = [
+    (
+        "Color",
+        "The regular colors of the splats",
+        RenderMode::Color,
+    ),
+    (
+        "Shaded",
+        "Shade splats using their estimated normals",
+        RenderMode::Shaded,
+    ),
+    (
+        "Points",
+        "Draw every splat as a point, whatever its size and opacity, to inspect the density",
+        RenderMode::Points,
+    ),
+    (
+        "Ellipsoids",
+        "Draw the outline of every splat, whatever its opacity, to inspect their shapes",
+        RenderMode::Ellipsoids,
+    ),
+];
+// The keys to sort splats on, with a description of each.
+const DEPTH_KEYS: [(&str, &str, DepthKey); 3] = [
+    (
+        "Depth",
+        "The depth along the view direction",
+        DepthKey::Depth,
+    ),
+    (
+        "Distance",
+        "Reduces popping when looking around in large scenes",
+        DepthKey::Distance,
+    ),
+    (
+        "Log distance",
+        "Sorts fewer bits, faster on scenes with many visible splats",
+        DepthKey::LogDistance,
+    ),
+];
+// Features are smooth, a small render is plenty to pick one from.
+const PICK_VIEW_SIZE: u32 = 256;
+// Frame rate of animated splats.
+const FPS: f32 = 24.0;
+
 impl ScenePanel {
     pub(crate) fn new(
         queue: Arc<wgpu::Queue>,
         device: Arc<wgpu::Device>,
         renderer: Arc<EguiRwLock<Renderer>>,
+        target_format: wgpu::TextureFormat,
         zen: bool,
     ) -> Self {
         Self {
@@ -58,6 +228,18 @@ impl ScenePanel {
             view_splats: vec![],
             live_update: true,
             paused: false,
+            render_options: RenderOptions::default(),
+            last_interacting: false,
+            resolution: DynamicResolution::new(),
+            temporal: TemporalAccumulator::new(),
+            temporal_aa: false,
+            temporal_splats: None,
+            foveated: false,
+            hdr_output: matches!(
+                target_format,
+                wgpu::TextureFormat::Rgb10a2Unorm | wgpu::TextureFormat::Rgba16Float
+            ),
+            gamepad: GamepadInput::new(),
             dirty: true,
             last_size: glam::UVec2::ZERO,
             is_loading: false,
@@ -65,7 +247,886 @@ impl ScenePanel {
             renderer,
             zen,
             frame_count: 0,
+            export_coordinates: CoordinateConvention::BRUSH,
+            ground: None,
+            level_export: true,
+            export_info: ModelInfo::default(),
+            shadow_catcher: None,
+            show_shadow_catcher: true,
+            environment: None,
+            environment_rays: EnvironmentRays::default(),
+            features: None,
+            feature_query: Arc::new(Mutex::new(None)),
+            focus_target: Arc::new(Mutex::new(None)),
+            spatial_index: Arc::new(Mutex::new(None)),
+            shown_query: None,
+            query_threshold: 0.8,
+            picking: false,
+            edits: EditStack::default(),
+            edited: None,
+            editing_layer: None,
+            new_layers: Arc::new(Mutex::new(vec![])),
+            loaded_edits: Arc::new(Mutex::new(None)),
+            heal_seeds: 5000,
+            heal_steps: 2000,
+            show_uncertainty: false,
+            uncertainty: None,
+            suggested_views: Arc::new(Mutex::new(vec![])),
+            show_contribution: false,
+            contribution: None,
+            thumbnails: HashMap::new(),
+        }
+    }
+
+    // The list of recent files on the start screen. Clicking one loads it again.
+    fn recent_files_ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext) {
+        if context.recent.entries.is_empty() {
+            return;
+        }
+
+        ui.add_space(10.0);
+        ui.heading(tr("Recent"));
+        ui.add_space(5.0);
+
+        let mut open = None;
+        let mut forget = None;
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for (i, entry) in context.recent.entries.iter().enumerate() {
+                    let thumbnail = entry.thumbnail_path().and_then(|path| {
+                        self.thumbnails
+                            .entry(path.clone())
+                            .or_insert_with(|| {
+                                let image = image::open(&path).ok()?.to_rgb8();
+                                let size = [image.width() as usize, image.height() as usize];
+                                let image = egui::ColorImage::from_rgb(size, &image.into_vec());
+                                Some(ui.ctx().load_texture(
+                                    path.to_string_lossy(),
+                                    image,
+                                    egui::TextureOptions::default(),
+                                ))
+                            })
+                            .clone()
+                    });
+
+                    let width = THUMBNAIL_WIDTH as f32 * 0.75;
+                    ui.vertical(|ui| {
+                        ui.set_width(width);
+                        let response = if let Some(texture) = thumbnail {
+                            let size = texture.size_vec2() * (width / texture.size_vec2().x);
+                            ui.add(
+                                egui::ImageButton::new(egui::load::SizedTexture::new(
+                                    texture.id(),
+                                    size,
+                                ))
+                                .frame(false),
+                            )
+                        } else {
+                            ui.add_sized(
+                                egui::vec2(width, width * 0.75),
+                                egui::Button::new(tr("No preview")),
+                            )
+                        };
+                        let name = entry.name();
+                        let response = response.on_hover_text(match &entry.source {
+                            DataSource::Path(path) => path.to_string_lossy().into_owned(),
+                            _ => name.clone(),
+                        });
+                        if response.clicked() {
+                            open = Some(entry.source.clone());
+                        }
+                        response.context_menu(|ui| {
+                            if ui.button(tr("Remove from list")).clicked() {
+                                forget = Some(i);
+                                ui.close_menu();
+                            }
+                        });
+                        ui.add(egui::Label::new(name).truncate());
+                    });
+                }
+            });
+        });
+
+        if let Some(i) = forget {
+            if let Some(path) = context.recent.entries[i].thumbnail_path() {
+                self.thumbnails.remove(&path);
+            }
+            context.recent.remove(i);
+        }
+        if let Some(source) = open {
+            let args = ProcessArgs {
+                source,
+                ..context.process_args.clone()
+            };
+            context.connect_to(start_process(args, context.device.clone()));
+        }
+    }
+
+    // A small render of the current view, shown with the source on the start screen.
+    fn save_thumbnail(&mut self, context: &AppContext) {
+        // Thumbnails are kept in the config folder, and rendered with a blocking read.
+        if config_dir().is_none() {
+            return;
+        }
+        let Some(source) = context.source().filter(|s| s.is_restorable()) else {
+            return;
+        };
+        let Some(splats) = self.view_splats.last() else {
+            return;
+        };
+
+        let camera = &context.camera;
+        let aspect = (camera.fov_y / 2.0).tan() / (camera.fov_x / 2.0).tan();
+        let height = (THUMBNAIL_WIDTH as f64 * aspect).round() as u32;
+        let size = glam::uvec2(THUMBNAIL_WIDTH, height.clamp(1, THUMBNAIL_WIDTH * 2));
+        // Thumbnails are sRGB pngs.
+        let options = RenderOptions {
+            foveation: None,
+            color_space: ColorSpace::Srgb,
+            ..self.render_options
+        };
+        let img = splats.render_inference(camera, size, false, options);
+        let Ok(pixels) = img.into_data().to_vec::<f32>() else {
+            return;
+        };
+
+        // The colors are premultiplied, which composites them over black.
+        let rgb = pixels
+            .chunks_exact(4)
+            .flat_map(|p| {
+                p[..3]
+                    .iter()
+                    .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect();
+        let Some(image) = image::RgbImage::from_raw(size.x, size.y, rgb) else {
+            return;
+        };
+        if let Err(e) = RecentFiles::save_thumbnail(source, image) {
+            log::warn!("Failed to save thumbnail: {e:?}");
+        }
+        if let Some(path) = thumbnail_path(source) {
+            self.thumbnails.remove(&path);
+        }
+    }
+
+    fn uncertainty_views(context: &AppContext) -> Vec<(Camera, glam::UVec2)> {
+        let views = &context.dataset.train.views;
+        let step = views.len().div_ceil(MAX_UNCERTAINTY_VIEWS).max(1);
+        views
+            .iter()
+            .step_by(step)
+            .map(|view| {
+                let (w, h) = (view.image.width() as f32, view.image.height() as f32);
+                let scale = (UNCERTAINTY_VIEW_SIZE as f32 / w.max(h)).min(1.0);
+                let size = glam::uvec2(
+                    ((w * scale).round() as u32).max(1),
+                    ((h * scale).round() as u32).max(1),
+                );
+                (view.camera.clone(), size)
+            })
+            .collect()
+    }
+
+    // Mark the suggested views in the scene, numbered by their order of preference.
+    fn draw_suggested_views(&self, ui: &egui::Ui, rect: Rect, context: &AppContext) {
+        let suggested = self.suggested_views.lock().expect("Lock poisoned");
+        if suggested.is_empty() {
+            return;
+        }
+
+        let size = glam::uvec2(rect.width() as u32, rect.height() as u32);
+        let world_to_local = context.camera.world_to_local();
+        let focal = context.camera.focal(size);
+        let center = context.camera.center(size);
+        let arrow_len = context.dataset.train.bounds().extent.length() * 0.05;
+
+        let to_screen = |world: glam::Vec3| {
+            let local = world_to_local.transform_point3(world);
+            (local.z > 0.0).then(|| {
+                let pixel = focal * local.truncate() / local.z + center;
+                rect.min + egui::vec2(pixel.x, pixel.y)
+            })
+        };
+
+        let painter = ui.painter_at(rect);
+        let color = Color32::from_rgb(255, 200, 0);
+        for (i, (camera, _)) in suggested.iter().enumerate() {
+            let Some(pos) = to_screen(camera.position) else {
+                continue;
+            };
+            let forward = camera.position + camera.rotation * glam::Vec3::Z * arrow_len;
+            if let Some(tip) = to_screen(forward) {
+                painter.arrow(pos, tip - pos, egui::Stroke::new(2.0, color));
+            }
+            painter.circle_filled(pos, 9.0, color);
+            painter.text(
+                pos,
+                egui::Align2::CENTER_CENTER,
+                (i + 1).to_string(),
+                egui::FontId::proportional(12.0),
+                Color32::BLACK,
+            );
+        }
+    }
+
+    // The splats selected by the feature query, if there are features for these splats.
+    fn feature_selection(&self, splats: &Splats<Wgpu>) -> Option<Tensor<Wgpu, 1, Bool>> {
+        let features = self
+            .features
+            .as_ref()
+            .filter(|f| f.num_splats() == splats.num_splats())?;
+        let query = self.feature_query.lock().expect("Lock poisoned").clone()?;
+        (query.len() == features.dim()).then(|| features.select(&query, self.query_threshold))
+    }
+
+    // Query for the feature at a point of the view, given as a fraction of its size.
+    fn pick_feature(
+        &self,
+        splats: &Splats<Wgpu>,
+        uv: Vec2,
+        context: &AppContext,
+        ctx: &egui::Context,
+    ) {
+        let Some(features) = self.features.clone() else {
+            return;
+        };
+        let splats = splats.clone();
+        let camera = context.camera.clone();
+        let scale = (PICK_VIEW_SIZE as f32 / self.last_size.max_element().max(1) as f32).min(1.0);
+        let size = (self.last_size.as_vec2() * scale)
+            .as_uvec2()
+            .max(glam::UVec2::ONE);
+        let pixel = (uv.clamp(Vec2::ZERO, Vec2::ONE) * size.as_vec2()).as_uvec2();
+        let query = self.feature_query.clone();
+        let ctx = ctx.clone();
+
+        brush_tasks::spawn(async move {
+            let feature = features.pick(&splats, &camera, size, pixel).await;
+            *query.lock().expect("Lock poisoned") = Some(feature);
+            ctx.request_repaint();
+        });
+    }
+
+    // Find the surface under a point of the view, and orbit around it once found.
+    fn raycast_focus(
+        &self,
+        frame: usize,
+        splats: &Splats<Wgpu>,
+        uv: Vec2,
+        context: &AppContext,
+        ctx: &egui::Context,
+    ) {
+        let splats = splats.clone();
+        let (origin, dir) = context.camera.ray(uv);
+        let target = self.focus_target.clone();
+        let cached = self.spatial_index.clone();
+        let ctx = ctx.clone();
+
+        brush_tasks::spawn(async move {
+            let index = cached
+                .lock()
+                .expect("Lock poisoned")
+                .as_ref()
+                .filter(|(f, _)| *f == frame)
+                .map(|(_, index)| index.clone());
+            let index = match index {
+                Some(index) => index,
+                None => {
+                    let index = Arc::new(SpatialIndex::from_splats(&splats).await);
+                    *cached.lock().expect("Lock poisoned") = Some((frame, index.clone()));
+                    index
+                }
+            };
+            let hits = index.raycast(origin, dir);
+            if let Some(t) = raycast::surface_depth(&hits, 0.5) {
+                *target.lock().expect("Lock poisoned") = Some(origin + dir * t);
+                ctx.request_repaint();
+            }
+        });
+    }
+
+    // Orbit around a point in world space, keeping the view direction and distance to it.
+    fn focus_on(context: &mut AppContext, target: glam::Vec3) {
+        let target = context
+            .model_transform
+            .inverse()
+            .transform_point3a(target.into());
+        let controls = &mut context.controls;
+        let distance = (controls.position - target).length();
+        controls.focus = target;
+        controls.position = target - controls.rotation * glam::Vec3A::Z * distance;
+        controls.dirty = true;
+    }
+
+    // Add a layer that edits the selected splats, or the others when inverted.
+    fn edit_selection(
+        &self,
+        splats: &Splats<Wgpu>,
+        name: &str,
+        invert: bool,
+        edit: Edit,
+        ctx: &egui::Context,
+    ) {
+        let Some(mask) = self.feature_selection(splats) else {
+            return;
+        };
+        let name = name.to_owned();
+        let new_layers = self.new_layers.clone();
+        let ctx = ctx.clone();
+
+        brush_tasks::spawn(async move {
+            let layer = EditLayer {
+                name,
+                enabled: true,
+                selection: Selection::from_mask(mask).await,
+                invert,
+                edit,
+            };
+            new_layers.lock().expect("Lock poisoned").push(layer);
+            ctx.request_repaint();
+        });
+    }
+
+    // Show the edits as they are now.
+    fn edits_changed(&mut self) {
+        self.edited = None;
+        self.uncertainty = None;
+        self.contribution = None;
+        *self.spatial_index.lock().expect("Lock poisoned") = None;
+        self.dirty = true;
+    }
+
+    // Add the layers made in the background, or replace them with loaded ones.
+    fn take_new_edits(&mut self) {
+        let new_layers: Vec<_> = self
+            .new_layers
+            .lock()
+            .expect("Lock poisoned")
+            .drain(..)
+            .collect();
+        let loaded = self.loaded_edits.lock().expect("Lock poisoned").take();
+        if new_layers.is_empty() && loaded.is_none() {
+            return;
+        }
+        if let Some(loaded) = loaded {
+            self.edits = loaded;
+            self.editing_layer = None;
+        }
+        if !new_layers.is_empty() {
+            self.edits.layers.extend(new_layers);
+            self.editing_layer = Some(self.edits.layers.len() - 1);
+        }
+        self.edits_changed();
+    }
+
+    // The splats of a frame with the edits applied, deleted splats are hidden.
+    fn edited_splats(&mut self, frame: usize) -> Splats<Wgpu> {
+        if let Some((cached_frame, cached)) = &self.edited {
+            if *cached_frame == frame {
+                return cached.clone();
+            }
         }
+        let edited = self.edits.preview(&self.view_splats[frame]);
+        self.edited = Some((frame, edited.clone()));
+        edited
+    }
+
+    // Save the edits to a path, next to the model when it was opened from disk, or to a file
+    // picked by the user.
+    fn save_edits(&self, path: Option<PathBuf>, context: &AppContext) {
+        let json = self.edits.to_json();
+        let path = path.or_else(|| match context.source() {
+            Some(DataSource::Path(model)) if model.is_file() => Some(sidecar_path(model)),
+            _ => None,
+        });
+
+        if let Some(path) = path {
+            match rrfd::write_atomic(&path, json.as_bytes(), false) {
+                Ok(()) => log::info!("Saved edits to {}", path.display()),
+                Err(e) => log::error!("Failed to save edits: {e}"),
+            }
+            return;
+        }
+
+        let hold = context.actions.hold();
+        brush_tasks::spawn(async move {
+            match rrfd::save_file("edits.json").await {
+                Ok(file) => {
+                    if let Err(e) = file.write(json.as_bytes()).await {
+                        log::error!("Failed to save edits: {e}");
+                    }
+                }
+                Err(e) => log::error!("Failed to save file: {e}"),
+            }
+            drop(hold);
+        });
+    }
+
+    // Load edits from a path, or from a file picked by the user.
+    fn load_edits(&mut self, path: Option<PathBuf>, context: &AppContext) {
+        if let Some(path) = path {
+            let loaded = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| EditStack::from_json(&json));
+            match loaded {
+                Ok(edits) => {
+                    self.edits = edits;
+                    self.editing_layer = None;
+                    self.edits_changed();
+                }
+                Err(e) => log::error!("Failed to load edits from {}: {e:#}", path.display()),
+            }
+            return;
+        }
+
+        let loaded_edits = self.loaded_edits.clone();
+        let hold = context.actions.hold();
+        brush_tasks::spawn(async move {
+            match rrfd::pick_file().await {
+                Ok(file) => {
+                    let json = String::from_utf8_lossy(&file.read().await).into_owned();
+                    match EditStack::from_json(&json) {
+                        Ok(edits) => *loaded_edits.lock().expect("Lock poisoned") = Some(edits),
+                        Err(e) => log::error!("Failed to load edits: {e:#}"),
+                    }
+                }
+                Err(e) => log::error!("Failed to pick file: {e}"),
+            }
+            drop(hold);
+        });
+    }
+
+    fn frame_index(&self) -> usize {
+        let frame = (self.frame * FPS)
+            .rem_euclid(self.frame_count as f32)
+            .floor() as usize;
+        frame.min(self.view_splats.len().saturating_sub(1))
+    }
+
+    // Export the edited splats of the current frame to a path, or to a file picked by the user.
+    // Runs in the background, and holds up the viewer actions after it until it's done.
+    fn export(&self, format: ExportFormat, path: Option<PathBuf>, context: &AppContext) {
+        let Some(splats) = self.view_splats.get(self.frame_index()).cloned() else {
+            log::warn!("There are no splats to export");
+            return;
+        };
+        let edits = self.edits.clone();
+        let catcher = self
+            .shadow_catcher
+            .clone()
+            .filter(|_| self.show_shadow_catcher);
+        let convention = self.export_coordinates;
+        let level_up = self.ground.filter(|_| self.level_export).map(|g| g.normal);
+        let info = self.export_info.clone();
+        let view_positions: Vec<_> = context
+            .dataset
+            .train
+            .views
+            .iter()
+            .map(|v| v.camera.position)
+            .collect();
+        let progress = context.progress();
+        let hold = context.actions.hold();
+
+        let fut = async move {
+            // Pick the file before the work of serializing.
+            let file = match &path {
+                Some(_) => None,
+                None => match rrfd::save_file(format.default_name()).await {
+                    Ok(file) => Some(file),
+                    Err(e) => {
+                        log::error!("Failed to save file: {e}");
+                        return;
+                    }
+                },
+            };
+            let stage = format!("Exporting {}", format.label());
+            progress.report(&stage, 0, 2);
+
+            // The edits are made for the splats without the ground.
+            let mut splats = edits.apply(&splats).await;
+            if let Some(catcher) = catcher {
+                splats = with_shadow_catcher(&splats, &catcher);
+            }
+
+            let data = match format {
+                ExportFormat::Ply => match level_up {
+                    Some(up) => {
+                        splat_export::splat_to_ply_leveled(
+                            splats,
+                            &view_positions,
+                            convention,
+                            up,
+                            &info,
+                        )
+                        .await
+                    }
+                    None => {
+                        splat_export::splat_to_ply_with_info(
+                            splats,
+                            &view_positions,
+                            convention,
+                            &info,
+                        )
+                        .await
+                    }
+                },
+                ExportFormat::Progressive => {
+                    progressive_export::splat_to_progressive_ply(splats, convention, &info).await
+                }
+                ExportFormat::Chunks => chunk_export::splats_to_chunks(
+                    splats,
+                    chunk_export::DEFAULT_SPLATS_PER_CHUNK,
+                    convention,
+                )
+                .await
+                .and_then(|files| chunk_export::files_to_zip(&files)),
+                ExportFormat::Octree => octree_export::splats_to_octree(
+                    splats,
+                    chunk_export::DEFAULT_SPLATS_PER_CHUNK,
+                    octree_export::DEFAULT_LOD_LEVELS,
+                    convention,
+                )
+                .await
+                .and_then(|files| chunk_export::files_to_zip(&files)),
+                ExportFormat::Binary => binary_model::splat_to_binary(splats).await,
+            };
+            progress.report(&stage, 1, 2);
+
+            let data = match data {
+                Ok(data) => data,
+                Err(e) => {
+                    log::error!("Failed to serialize {}: {e}", format.label());
+                    progress.report(&stage, 2, 2);
+                    return;
+                }
+            };
+
+            let written = match (&file, &path) {
+                (Some(file), _) => file.write(&data).await,
+                (None, Some(path)) => rrfd::write_atomic(path, &data, false),
+                (None, None) => unreachable!("A file is picked when there's no path"),
+            };
+            if let Err(e) = written {
+                log::error!("Failed to write file: {e}");
+            }
+            progress.report(&stage, 2, 2);
+            drop(hold);
+        };
+
+        let token = context.cancel_token();
+        brush_tasks::spawn(async move { brush_tasks::until_cancelled(&token, fut).await });
+    }
+
+    fn cancel(&mut self, context: &mut AppContext) {
+        context.cancel_process();
+        self.is_loading = false;
+        self.is_training = false;
+        self.paused = false;
+    }
+
+    // Tools to select splats by their features, and keep or remove the selection.
+    fn feature_query_ui(&mut self, ui: &mut egui::Ui, splats: &Splats<Wgpu>) {
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.picking, tr("🔍 Pick feature"))
+                .on_hover_text(tr(
+                    "Click the view to select everything with a similar feature",
+                ));
+
+            if ui
+                .button(tr("Load query"))
+                .on_hover_text(tr(
+                    "Load a query feature from a .npy file, eg. a CLIP text embedding reduced \
+                     to the dimensions of the feature maps",
+                ))
+                .clicked()
+            {
+                let query = self.feature_query.clone();
+                let dim = self.features.as_ref().map_or(0, |f| f.dim());
+                let ctx = ui.ctx().clone();
+                brush_tasks::spawn(async move {
+                    let file = match rrfd::pick_file().await {
+                        Ok(file) => file,
+                        Err(e) => {
+                            log::error!("Failed to pick file: {e}");
+                            return;
+                        }
+                    };
+                    match npy::read_npy_f32(&file.read().await) {
+                        Ok((_, values)) if values.len() == dim => {
+                            *query.lock().expect("Lock poisoned") = Some(values);
+                            ctx.request_repaint();
+                        }
+                        Ok((shape, _)) => log::error!(
+                            "Query of shape {shape:?} doesn't match the {dim} dimensional features"
+                        ),
+                        Err(e) => log::error!("Failed to read query: {e}"),
+                    }
+                });
+            }
+
+            if self.shown_query.is_some() {
+                if ui
+                    .add(
+                        egui::Slider::new(&mut self.query_threshold, 0.0..=1.0)
+                            .text(tr("Similarity")),
+                    )
+                    .changed()
+                {
+                    self.dirty = true;
+                }
+                if ui
+                    .button(tr("Keep selection"))
+                    .on_hover_text(tr("Remove all splats that aren't selected"))
+                    .clicked()
+                {
+                    self.edit_selection(splats, "Keep selection", true, Edit::Delete, ui.ctx());
+                }
+                if ui.button(tr("Remove selection")).clicked() {
+                    self.edit_selection(splats, "Remove selection", false, Edit::Delete, ui.ctx());
+                }
+                if ui
+                    .button(tr("Recolor selection"))
+                    .on_hover_text(tr(
+                        "Add a layer to change the color, exposure and saturation of the \
+                         selected splats",
+                    ))
+                    .clicked()
+                {
+                    self.edit_selection(
+                        splats,
+                        "Recolor selection",
+                        false,
+                        Edit::IDENTITY_RECOLOR,
+                        ui.ctx(),
+                    );
+                }
+                if ui.button(tr("Clear")).clicked() {
+                    *self.feature_query.lock().expect("Lock poisoned") = None;
+                }
+            }
+        });
+    }
+
+    // The list of edit layers, to add, change and remove them.
+    fn edit_layers_ui(&mut self, ui: &mut egui::Ui, context: &mut AppContext, num_splats: usize) {
+        let mut changed = false;
+        let mut remove = None;
+        let mut heal = None;
+        // Healing trains on the dataset again, it's loaded from the source.
+        let can_heal = !context.dataset.train.views.is_empty()
+            && context.source().is_some_and(|s| s.is_restorable());
+
+        for (i, layer) in self.edits.layers.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .checkbox(&mut layer.enabled, layer.name.clone())
+                    .changed();
+                if !layer.selection.applies_to(num_splats) {
+                    ui.label("⚠").on_hover_text(tr(
+                        "The splats of this layer were picked on a model with a different \
+                         number of splats, it doesn't apply to these",
+                    ));
+                }
+                let mut editing = self.editing_layer == Some(i);
+                if ui.toggle_value(&mut editing, "✏").changed() {
+                    self.editing_layer = editing.then_some(i);
+                }
+                if ui.button("🗑").on_hover_text(tr("Remove layer")).clicked() {
+                    remove = Some(i);
+                }
+            });
+
+            if self.editing_layer == Some(i) {
+                ui.indent(i, |ui| {
+                    changed |= edit_layer_ui(ui, layer);
+                    if let Selection::Box(region) = layer.selection {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(can_heal, egui::Button::new(tr("Heal")))
+                                .on_hover_text(tr(
+                                    "Fill the box with new splats and train only them for a \
+                                     short run, on the views that see it, to repair a hole or \
+                                     artifact. Loads the dataset again",
+                                ))
+                                .on_disabled_hover_text(tr(
+                                    "Healing needs the dataset the model was trained on",
+                                ))
+                                .clicked()
+                            {
+                                heal = Some(region);
+                            }
+                            ui.add(
+                                egui::DragValue::new(&mut self.heal_seeds)
+                                    .range(1..=100_000)
+                                    .speed(100.0)
+                                    .suffix(tr(" splats")),
+                            );
+                            ui.add(
+                                egui::DragValue::new(&mut self.heal_steps)
+                                    .range(100..=30_000)
+                                    .speed(10.0)
+                                    .suffix(tr(" steps")),
+                            );
+                        });
+                    }
+                });
+            }
+        }
+
+        if let Some(i) = remove {
+            self.edits.layers.remove(i);
+            self.editing_layer = None;
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.menu_button(tr("➕ Add layer"), |ui| {
+                let focus = glam::Vec3::from(context.controls.focus);
+                let layer = if ui.button(tr("Crop to box")).clicked() {
+                    Some(EditLayer::crop(BoundingBox {
+                        center: focus,
+                        extent: glam::Vec3::ONE,
+                    }))
+                } else if ui.button(tr("Transform")).clicked() {
+                    Some(EditLayer {
+                        name: "Transform".to_owned(),
+                        enabled: true,
+                        selection: Selection::All,
+                        invert: false,
+                        edit: Edit::Transform(SplatTransform::around(focus)),
+                    })
+                } else if ui
+                    .button(tr("Duplicate region"))
+                    .on_hover_text(tr(
+                        "Copy the splats in a box to somewhere else, eg. to patch a hole with \
+                         similar content from nearby",
+                    ))
+                    .clicked()
+                {
+                    // Start with the copy next to the box.
+                    Some(EditLayer {
+                        name: "Duplicate".to_owned(),
+                        enabled: true,
+                        selection: Selection::Box(BoundingBox {
+                            center: focus,
+                            extent: glam::Vec3::splat(0.5),
+                        }),
+                        invert: false,
+                        edit: Edit::Duplicate {
+                            transform: SplatTransform {
+                                translation: glam::Vec3::X,
+                                ..SplatTransform::around(focus)
+                            },
+                            feather: 0.1,
+                        },
+                    })
+                } else if ui.button(tr("Recolor")).clicked() {
+                    Some(EditLayer {
+                        name: "Recolor".to_owned(),
+                        enabled: true,
+                        selection: Selection::All,
+                        invert: false,
+                        edit: Edit::IDENTITY_RECOLOR,
+                    })
+                } else {
+                    None
+                };
+                if let Some(layer) = layer {
+                    self.edits.layers.push(layer);
+                    self.editing_layer = Some(self.edits.layers.len() - 1);
+                    changed = true;
+                    ui.close_menu();
+                }
+            });
+
+            if ui
+                .button(tr("Save edits"))
+                .on_hover_text(tr(
+                    "Save the layers next to the model, or to a file picked on disk. They're \
+                     loaded again with the model",
+                ))
+                .clicked()
+            {
+                self.save_edits(None, context);
+            }
+            if ui.button(tr("Load edits")).clicked() {
+                self.load_edits(None, context);
+            }
+        });
+
+        if changed {
+            self.edits_changed();
+        }
+        if let Some(region) = heal {
+            self.heal(region, context);
+        }
+    }
+
+    // Seed new splats in a box of the shown model and train them for a short run, with the
+    // rest of the model and its edits kept as they are. Starts a new process on the dataset.
+    fn heal(&mut self, region: BoundingBox, context: &mut AppContext) {
+        let Some(source) = context.source().filter(|s| s.is_restorable()).cloned() else {
+            return;
+        };
+        let Some(splats) = self.view_splats.get(self.frame_index()).cloned() else {
+            return;
+        };
+        let mut args = ProcessArgs {
+            source,
+            heal: Some(HealArgs {
+                splats,
+                edits: self.edits.clone(),
+                steps: self.heal_steps,
+            }),
+            ..context.process_args.clone()
+        };
+        args.train_config.refine_region = Some(region);
+        args.train_config.heal_seeds = self.heal_seeds;
+        args.init_args.resume = None;
+        context.connect_to(start_process(args, context.device.clone()));
+    }
+
+    fn uncertainty_splats(
+        &mut self,
+        frame: usize,
+        splats: &Splats<Wgpu>,
+        context: &AppContext,
+    ) -> Splats<Wgpu> {
+        let counts = match &self.uncertainty {
+            Some((cached_frame, counts))
+                if *cached_frame == frame && counts.dims()[0] == splats.num_splats() =>
+            {
+                counts.clone()
+            }
+            _ => {
+                let views = Self::uncertainty_views(context);
+                let counts = uncertainty::view_counts(splats, &views);
+                self.uncertainty = Some((frame, counts.clone()));
+                counts
+            }
+        };
+        uncertainty::uncertainty_splats(splats, counts)
+    }
+
+    fn contribution_splats(
+        &mut self,
+        frame: usize,
+        splats: &Splats<Wgpu>,
+        context: &AppContext,
+    ) -> Splats<Wgpu> {
+        if let Some((cached_frame, cached)) = &self.contribution {
+            if *cached_frame == frame {
+                return cached.clone();
+            }
+        }
+
+        let size = self.last_size.max(glam::UVec2::ONE);
+        let contributions = contribution::view_contributions(splats, &context.camera, size);
+        let colored = contribution::contribution_splats(splats, contributions);
+        self.contribution = Some((frame, colored.clone()));
+        colored
     }
 
     pub(crate) fn draw_splats(
@@ -74,11 +1135,11 @@ impl ScenePanel {
         context: &mut AppContext,
         splats: &Splats<Wgpu>,
         delta_time: web_time::Duration,
-    ) {
+    ) -> Option<(Vec2, bool)> {
         let mut size = brush_ui::size_for_splat_view(ui);
 
         if size.x < 8.0 || size.y < 8.0 {
-            return;
+            return None;
         }
 
         if self.is_training {
@@ -98,12 +1159,24 @@ impl ScenePanel {
 
         let (rect, response) = ui.allocate_exact_size(
             egui::Vec2::new(size.x as f32, size.y as f32),
-            egui::Sense::drag(),
+            egui::Sense::click_and_drag(),
         );
 
         let mouse_delta = glam::vec2(response.drag_delta().x, response.drag_delta().y);
 
-        let (pan, rotate) = if response.dragged_by(egui::PointerButton::Primary) {
+        // Two finger touches pan, and twisting them orbits around the focus. A single finger
+        // drags like the primary mouse button.
+        let multi_touch = ui
+            .input(|r| r.multi_touch())
+            .filter(|_| response.contains_pointer());
+
+        let (pan, rotate) = if let Some(touch) = multi_touch {
+            let twist = touch.rotation_delta * rect.width() / std::f32::consts::TAU;
+            (
+                glam::vec2(touch.translation_delta.x, touch.translation_delta.y),
+                glam::vec2(-twist, 0.0),
+            )
+        } else if response.dragged_by(egui::PointerButton::Primary) {
             (Vec2::ZERO, mouse_delta)
         } else if response.dragged_by(egui::PointerButton::Secondary)
             || response.dragged_by(egui::PointerButton::Middle)
@@ -115,15 +1188,50 @@ impl ScenePanel {
 
         let scrolled = ui.input(|r| {
             r.smooth_scroll_delta.y
-                + r.multi_touch().map_or(0.0, |t| {
+                + multi_touch.map_or(0.0, |t| {
                     (t.zoom_delta - 1.0) * context.controls.radius() * 5.0
                 })
         });
 
+        let gamepad = self.gamepad.poll(delta_time.as_secs_f32());
+        if !gamepad.is_zero() {
+            ui.ctx().request_repaint();
+        } else if self.gamepad.has_gamepad() {
+            // Gamepads don't wake up egui, so keep polling while one is connected.
+            ui.ctx().request_repaint_after(Duration::from_millis(50));
+        }
+
+        // Once the view is tabbed to, the arrow keys move the camera instead of moving the
+        // keyboard focus to the next widget.
+        let keys = if response.has_focus() {
+            let filter = egui::EventFilter {
+                horizontal_arrows: true,
+                vertical_arrows: true,
+                ..Default::default()
+            };
+            ui.memory_mut(|m| m.set_focus_lock_filter(response.id, filter));
+            ui.input(|r| keyboard_motion(r, delta_time.as_secs_f32()))
+        } else {
+            GamepadMotion::default()
+        };
+        if !keys.is_zero() {
+            ui.ctx().request_repaint();
+        }
+
+        // Render a cheaper view independent color while the camera is being moved, and
+        // render the full quality image once the interaction stops.
+        let interacting = response.dragged()
+            || multi_touch.is_some()
+            || scrolled != 0.0
+            || !gamepad.is_zero()
+            || !keys.is_zero();
+        self.dirty |= self.last_interacting != interacting;
+        self.last_interacting = interacting;
+
         self.dirty |= context.controls.pan_orbit_camera(
-            pan * 5.0,
-            rotate * 5.0,
-            scrolled * 0.01,
+            pan * 5.0 + gamepad.pan + keys.pan,
+            rotate * 5.0 + gamepad.rotate + keys.rotate,
+            (scrolled + gamepad.zoom + keys.zoom) * 0.01,
             glam::vec2(rect.size().x, rect.size().y),
             delta_time.as_secs_f32(),
         );
@@ -134,26 +1242,110 @@ impl ScenePanel {
 
         context.controls.dirty = false;
 
-        self.dirty |= self.last_size != size;
+        // Lower the resolution to keep up the frame rate while the view keeps changing.
+        // Once it stops, the final frame is rendered at full resolution.
+        let animating =
+            !self.paused && (self.view_splats.len() > 1 || (self.is_training && self.live_update));
+        let render_size = if interacting || animating {
+            self.resolution.update(delta_time.as_secs_f32());
+            self.resolution.render_size(size)
+        } else {
+            self.resolution.reset();
+            size
+        };
+
+        self.dirty |= self.last_size != render_size;
+
+        let foveation = self.foveated.then(|| {
+            let gaze = response.hover_pos().map_or(glam::Vec2::splat(0.5), |pos| {
+                let uv = (pos - rect.min) / rect.size();
+                glam::vec2(uv.x, uv.y)
+            });
+            Foveation::from_gaze(gaze, render_size, FOVEA_RADIUS)
+        });
+        self.dirty |= self.render_options.foveation != foveation;
+        self.render_options.foveation = foveation;
+
+        // The learned background is only drawn behind opaque scenes, like in training.
+        let has_alpha = context
+            .dataset
+            .train
+            .views
+            .first()
+            .is_some_and(|view| view.image.has_alpha());
+        let environment = self
+            .environment
+            .as_ref()
+            .filter(|_| !has_alpha && !self.show_uncertainty);
 
         // If this viewport is re-rendering.
         if ui.ctx().has_requested_repaint() && size.x > 0 && size.y > 0 && self.dirty {
             let _span = trace_span!("Render splats").entered();
-            let (img, _) = splats.render(&context.camera, size, true);
-            self.backbuffer.update_texture(img, &self.renderer);
+            let options = RenderOptions {
+                view_independent: interacting,
+                ..self.render_options
+            };
+            if self.temporal_aa && (interacting || animating) {
+                let offset = self.temporal.next_offset();
+                let camera = jittered_camera(&context.camera, render_size, offset);
+                let frame = splats.render_inference(&camera, render_size, false, options);
+                let frame = match environment {
+                    Some(env) => env.composite(&mut self.environment_rays, frame, &camera),
+                    None => frame,
+                };
+                // Motion is smooth, half the resolution is plenty to reproject.
+                let motion_size = (render_size / 2).max(glam::UVec2::splat(8));
+                let prev_camera = self
+                    .temporal
+                    .previous_camera()
+                    .cloned()
+                    .unwrap_or_else(|| context.camera.clone());
+                let motion = motion::render_motion_vectors(
+                    splats,
+                    self.temporal_splats.as_ref(),
+                    &context.camera,
+                    &prev_camera,
+                    motion_size,
+                );
+                let img = self.temporal.accumulate_with_motion(
+                    frame,
+                    motion,
+                    &context.camera,
+                    offset,
+                    size,
+                );
+                self.backbuffer.update_texture_rgba(img, &self.renderer);
+                self.temporal_splats = Some(splats.clone());
+            } else {
+                // A still view is rendered at full resolution, nothing to accumulate.
+                self.temporal.reset();
+                self.temporal_splats = None;
+                if let Some(env) = environment {
+                    let img = splats.render_inference(&context.camera, render_size, false, options);
+                    let img = env.composite(&mut self.environment_rays, img, &context.camera);
+                    self.backbuffer.update_texture_rgba(img, &self.renderer);
+                } else {
+                    self.backbuffer.render_splats(
+                        splats,
+                        &context.camera,
+                        render_size,
+                        options,
+                        &self.renderer,
+                    );
+                }
+            }
             self.dirty = false;
-            self.last_size = size;
+            self.last_size = render_size;
         }
 
         if let Some(id) = self.backbuffer.id() {
             ui.scope(|ui| {
-                if context
-                    .dataset
-                    .train
-                    .views
-                    .first()
-                    .is_some_and(|view| view.image.color().has_alpha())
-                {
+                if self.show_uncertainty {
+                    // Pixels that the splats don't fully cover are unconstrained.
+                    let [r, g, b] = uncertainty::UNCERTAIN_COLOR.map(|c| (c * 255.0) as u8);
+                    ui.painter()
+                        .rect_filled(rect, 0.0, Color32::from_rgb(r, g, b));
+                } else if has_alpha {
                     // if training views have alpha, show a background checker.
                     brush_ui::draw_checkerboard(ui, rect);
                 } else {
@@ -171,27 +1363,330 @@ impl ScenePanel {
                     Color32::WHITE,
                 );
             });
+
+            if self.show_uncertainty {
+                self.draw_suggested_views(ui, rect, context);
+            }
+        }
+
+        // Show which widget has keyboard focus, when it's the view.
+        if response.has_focus() {
+            let stroke = ui.visuals().selection.stroke;
+            ui.painter()
+                .rect_stroke(rect.shrink(stroke.width), 0.0, stroke);
+        }
+
+        // Where the view was clicked, as a fraction of its size, and whether it was a double
+        // click.
+        response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked() || response.double_clicked())
+            .map(|pos| {
+                let local = pos - rect.min;
+                (
+                    glam::vec2(local.x / rect.width(), local.y / rect.height()),
+                    response.double_clicked(),
+                )
+            })
+    }
+}
+
+// Drag values for the components of a vector, returns whether any changed.
+fn vec3_ui(ui: &mut egui::Ui, label: &str, value: &mut glam::Vec3, speed: f32) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let mut changed = false;
+        for v in [&mut value.x, &mut value.y, &mut value.z] {
+            changed |= ui.add(egui::DragValue::new(v).speed(speed)).changed();
+        }
+        changed
+    })
+    .inner
+}
+
+// Drag values for a transform, returns whether any changed.
+fn transform_ui(ui: &mut egui::Ui, transform: &mut SplatTransform) -> bool {
+    let mut changed = vec3_ui(ui, tr("Move"), &mut transform.translation, 0.01);
+    let (x, y, z) = transform.rotation.to_euler(glam::EulerRot::XYZ);
+    let mut degrees = glam::vec3(x.to_degrees(), y.to_degrees(), z.to_degrees());
+    if vec3_ui(ui, tr("Rotate"), &mut degrees, 0.5) {
+        transform.rotation = Quat::from_euler(
+            glam::EulerRot::XYZ,
+            degrees.x.to_radians(),
+            degrees.y.to_radians(),
+            degrees.z.to_radians(),
+        );
+        changed = true;
+    }
+    ui.horizontal(|ui| {
+        ui.label(tr("Scale"));
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut transform.scale)
+                    .speed(0.01)
+                    .range(0.01..=100.0),
+            )
+            .changed();
+    });
+    changed | vec3_ui(ui, tr("Pivot"), &mut transform.pivot, 0.01)
+}
+
+// The settings of an edit layer, returns whether any changed.
+fn edit_layer_ui(ui: &mut egui::Ui, layer: &mut EditLayer) -> bool {
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        ui.label(tr("Splats"));
+        let is_box = matches!(layer.selection, Selection::Box(_));
+        let picked = match &layer.selection {
+            Selection::Indices { indices, .. } => Some(indices.len()),
+            Selection::All | Selection::Box(_) => None,
+        };
+        match picked {
+            Some(picked) => {
+                ui.label(tr_args("{} picked", &[&picked]));
+            }
+            None => {
+                if ui.selectable_label(!is_box, tr("All")).clicked() && is_box {
+                    layer.selection = Selection::All;
+                    changed = true;
+                }
+                if ui.selectable_label(is_box, tr("In box")).clicked() && !is_box {
+                    layer.selection = Selection::Box(BoundingBox {
+                        center: glam::Vec3::ZERO,
+                        extent: glam::Vec3::ONE,
+                    });
+                    changed = true;
+                }
+            }
+        }
+        changed |= ui
+            .checkbox(&mut layer.invert, tr("Invert"))
+            .on_hover_text(tr("Edit the splats that aren't selected instead"))
+            .changed();
+    });
+
+    if let Selection::Box(region) = &mut layer.selection {
+        let (mut min, mut max) = (region.min(), region.max());
+        let box_changed =
+            vec3_ui(ui, tr("Min"), &mut min, 0.01) | vec3_ui(ui, tr("Max"), &mut max, 0.01);
+        if box_changed {
+            *region = BoundingBox::from_min_max(min.min(max), min.max(max));
+            changed = true;
+        }
+    }
+
+    match &mut layer.edit {
+        Edit::Delete => {}
+        Edit::Transform(transform) => changed |= transform_ui(ui, transform),
+        Edit::Duplicate { transform, feather } => {
+            changed |= transform_ui(ui, transform);
+            ui.horizontal(|ui| {
+                ui.label(tr("Feather"));
+                changed |= ui
+                    .add(egui::DragValue::new(feather).speed(0.01).range(0.0..=10.0))
+                    .on_hover_text(tr(
+                        "Fade out the copy over this distance from the sides of the box, to blend \
+                         it in",
+                    ))
+                    .changed();
+            });
+        }
+        Edit::Recolor {
+            tint,
+            exposure,
+            saturation,
+        } => {
+            if vec3_ui(ui, tr("Tint"), tint, 0.01) {
+                *tint = tint.max(glam::Vec3::ZERO);
+                changed = true;
+            }
+            ui.horizontal(|ui| {
+                ui.label(tr("Exposure"));
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(exposure)
+                            .speed(0.02)
+                            .range(-5.0..=5.0)
+                            .suffix(" EV"),
+                    )
+                    .changed();
+                ui.label(tr("Saturation"));
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(saturation)
+                            .speed(0.01)
+                            .range(0.0..=3.0),
+                    )
+                    .changed();
+            });
         }
     }
+    changed
 }
 
 impl AppPanel for ScenePanel {
     fn title(&self) -> String {
-        "Scene".to_owned()
+        tr("Scene").to_owned()
+    }
+
+    fn save_session(&self, session: &mut Session) {
+        session.display = DisplaySettings {
+            shaded: self.render_options.mode == RenderMode::Shaded,
+            points: self.render_options.mode == RenderMode::Points,
+            ellipsoids: self.render_options.mode == RenderMode::Ellipsoids,
+            sort_by_distance: self.render_options.depth_key == DepthKey::Distance,
+            sort_by_log_distance: self.render_options.depth_key == DepthKey::LogDistance,
+            order_independent: self.render_options.order_independent,
+            dynamic_resolution: self.resolution.enabled,
+            quality_bias: self.resolution.quality_bias,
+            temporal_aa: self.temporal_aa,
+            uncertainty: self.show_uncertainty,
+            color_space: self.render_options.color_space,
+        };
+    }
+
+    fn restore_session(&mut self, session: &Session) {
+        let display = &session.display;
+        self.render_options.mode = if display.points {
+            RenderMode::Points
+        } else if display.ellipsoids {
+            RenderMode::Ellipsoids
+        } else if display.shaded {
+            RenderMode::Shaded
+        } else {
+            RenderMode::Color
+        };
+        self.render_options.depth_key = if display.sort_by_log_distance {
+            DepthKey::LogDistance
+        } else if display.sort_by_distance {
+            DepthKey::Distance
+        } else {
+            DepthKey::Depth
+        };
+        self.render_options.order_independent = display.order_independent;
+        self.resolution.enabled = display.dynamic_resolution;
+        self.resolution.quality_bias = display.quality_bias;
+        self.temporal_aa = display.temporal_aa;
+        self.render_options.color_space = match display.color_space {
+            ColorSpace::Hdr10 if !self.hdr_output => ColorSpace::Srgb,
+            space => space,
+        };
+        self.show_uncertainty = display.uncertainty;
+        self.dirty = true;
+    }
+
+    fn on_action(&mut self, action: &ViewerAction, context: &mut AppContext) {
+        self.take_new_edits();
+        let toggle = |flag: &mut bool, value: &Option<bool>| *flag = value.unwrap_or(!*flag);
+
+        match action {
+            ViewerAction::Cancel => self.cancel(context),
+            ViewerAction::Pause(paused) => {
+                toggle(&mut self.paused, paused);
+                if self.is_training {
+                    context.control_message(ControlMessage::Paused(self.paused));
+                }
+            }
+            ViewerAction::Crop(region) => {
+                self.edits.layers.push(EditLayer::crop(*region));
+                self.edits_changed();
+            }
+            ViewerAction::SaveEdits(path) => self.save_edits(path.clone(), context),
+            ViewerAction::LoadEdits(path) => self.load_edits(path.clone(), context),
+            ViewerAction::ClearEdits => {
+                self.edits = EditStack::default();
+                self.editing_layer = None;
+                self.edits_changed();
+            }
+            ViewerAction::Export(format, path) => self.export(*format, path.clone(), context),
+            ViewerAction::RenderMode(mode) => self.render_options.mode = *mode,
+            ViewerAction::ColorSpace(space) => {
+                if *space == ColorSpace::Hdr10 && !self.hdr_output {
+                    log::warn!("The display doesn't support HDR output");
+                    return;
+                }
+                self.render_options.color_space = *space;
+                self.temporal.reset();
+            }
+            ViewerAction::Set(setting, value) => match setting {
+                ViewSetting::LiveUpdate => toggle(&mut self.live_update, value),
+                ViewSetting::Ground => toggle(&mut self.show_shadow_catcher, value),
+                ViewSetting::Uncertainty => {
+                    toggle(&mut self.show_uncertainty, value);
+                    self.show_contribution &= !self.show_uncertainty;
+                }
+                ViewSetting::Contribution => {
+                    toggle(&mut self.show_contribution, value);
+                    self.show_uncertainty &= !self.show_contribution;
+                    self.contribution = None;
+                }
+                ViewSetting::SortByDistance => {
+                    let mut distance = self.render_options.depth_key == DepthKey::Distance;
+                    toggle(&mut distance, value);
+                    self.render_options.depth_key = if distance {
+                        DepthKey::Distance
+                    } else {
+                        DepthKey::Depth
+                    };
+                }
+                ViewSetting::OrderIndependent => {
+                    toggle(&mut self.render_options.order_independent, value);
+                }
+                ViewSetting::DynamicResolution => toggle(&mut self.resolution.enabled, value),
+                ViewSetting::TemporalAa => {
+                    toggle(&mut self.temporal_aa, value);
+                    self.temporal.reset();
+                }
+                ViewSetting::Foveated => toggle(&mut self.foveated, value),
+            },
+            ViewerAction::Open(_) | ViewerAction::Script(_) => {}
+        }
+        self.dirty = true;
+    }
+
+    fn on_close(&mut self, context: &AppContext) {
+        self.save_thumbnail(context);
     }
 
     fn on_message(&mut self, message: &ProcessMessage, context: &mut AppContext) {
         if self.live_update {
             self.dirty = true;
+            self.edited = None;
+            self.contribution = None;
+            *self.spatial_index.lock().expect("Lock poisoned") = None;
         }
 
         match message {
-            ProcessMessage::NewSource => {
+            ProcessMessage::NewSource { source } => {
+                // Edits saved next to the model come back with it.
+                self.edits = match source {
+                    DataSource::Path(model) if model.is_file() => EditStack::load_sidecar(model)
+                        .unwrap_or_else(|e| {
+                            log::error!("Failed to load edits: {e:#}");
+                            None
+                        }),
+                    _ => None,
+                }
+                .unwrap_or_default();
+                self.editing_layer = None;
+                self.edited = None;
                 self.view_splats = vec![];
+                self.shadow_catcher = None;
+                self.environment = None;
+                self.features = None;
+                *self.feature_query.lock().expect("Lock poisoned") = None;
+                self.picking = false;
+                self.ground = None;
+                self.uncertainty = None;
+                self.contribution = None;
+                *self.spatial_index.lock().expect("Lock poisoned") = None;
+                self.suggested_views.lock().expect("Lock poisoned").clear();
                 self.paused = false;
                 self.is_loading = false;
                 self.is_training = false;
                 self.err = None;
+                self.export_info = ModelInfo::default();
             }
             ProcessMessage::DoneLoading { training: _ } => {
                 self.is_loading = false;
@@ -206,26 +1701,50 @@ impl AppPanel for ScenePanel {
                 frame,
                 total_frames,
             } => {
-                context.set_up_axis(*up_axis);
+                // The ground of a dataset is a better guess than the up axis of the initial splats.
+                context.set_up_axis(self.ground.map_or(*up_axis, |g| g.normal));
 
                 if self.live_update {
                     self.view_splats.truncate(*frame);
                     self.view_splats.push(*splats.clone());
+                    self.uncertainty = None;
                 }
                 self.frame_count = *total_frames;
             }
             ProcessMessage::TrainStep {
                 splats,
-                stats: _,
-                iter: _,
-                timestamp: _,
+                iter,
+                shadow_catcher,
+                features,
+                environment,
+                ..
             } => {
                 let splats = *splats.clone();
+                self.export_info.iter = Some(*iter);
 
                 if self.live_update {
                     self.view_splats = vec![splats];
+                    if iter % UNCERTAINTY_EVERY == 0 {
+                        self.uncertainty = None;
+                    }
+                    self.shadow_catcher = shadow_catcher.as_deref().cloned();
+                    self.environment = environment.as_deref().cloned();
+                    self.features = features.as_deref().cloned();
+                }
+            }
+            ProcessMessage::Dataset { data } => {
+                self.export_info.dataset_fingerprint = Some(dataset_fingerprint(data));
+
+                if data.ground != self.ground {
+                    self.ground = data.ground;
+                    if let Some(ground) = self.ground {
+                        context.set_up_axis(ground.normal);
+                    }
                 }
             }
+            ProcessMessage::EvalResult { iter: _, eval } => {
+                self.export_info.set_eval(eval);
+            }
             ProcessMessage::Error(e) => {
                 self.err = Some(e.to_string());
             }
@@ -243,40 +1762,37 @@ impl AppPanel for ScenePanel {
 
         // Empty scene, nothing to show.
         if !self.is_loading && self.view_splats.is_empty() && self.err.is_none() && !self.zen {
-            ui.heading("Load a ply file or dataset to get started.");
+            ui.heading(tr("Load a ply file or dataset to get started."));
             ui.add_space(5.0);
-            ui.label(
-                r#"
+            ui.label(tr(r#"
 Load a pretrained .ply file to view it
 
 Or load a dataset to train on. These are zip files with:
     - a transforms.json and images, like the nerfstudio dataset format.
-    - COLMAP data, containing the `images` & `sparse` folder."#,
-            );
+    - COLMAP data, containing the `images` & `sparse` folder.
+
+Or the URL of a WebDataset index.json, to stream its tar shards while training."#));
 
             ui.add_space(10.0);
 
             #[cfg(target_family = "wasm")]
             ui.scope(|ui| {
                 ui.visuals_mut().override_text_color = Some(Color32::YELLOW);
-                ui.heading("Note: Running in browser is still experimental");
+                ui.heading(tr("Note: Running in browser is still experimental"));
 
-                ui.label(
-                    r#"
+                ui.label(tr(r#"
 In browser training is slower, and lower quality than the native app.
 
-For bigger training runs consider using the native app."#,
-                );
+For bigger training runs consider using the native app."#));
             });
 
+            self.recent_files_ui(ui, context);
             return;
         }
 
         if let Some(err) = self.err.as_ref() {
-            ui.label("Error: ".to_owned() + &err.to_string());
+            ui.label(tr("Error: ").to_owned() + &err.to_string());
         } else if !self.view_splats.is_empty() {
-            const FPS: f32 = 24.0;
-
             if !self.paused {
                 self.frame += delta_time.as_secs_f32();
             }
@@ -286,17 +1802,312 @@ For bigger training runs consider using the native app."#,
                 self.frame = self.frame.min(max_t);
             }
 
-            let frame = (self.frame * FPS)
-                .rem_euclid(self.frame_count as f32)
-                .floor() as usize;
-            let splats = self.view_splats[frame].clone();
+            self.take_new_edits();
+
+            let frame = self.frame_index();
+            // Features and picked splats belong to the splats of the model. The edited splats
+            // start with those, as deleted splats are hidden rather than removed, followed by
+            // any copies.
+            let model_splats = self.view_splats[frame].clone();
+            let scene_splats = self.edited_splats(frame);
+
+            let query = self.feature_query.lock().expect("Lock poisoned").clone();
+            self.dirty |= query != self.shown_query;
+            self.shown_query = query;
+
+            let mut splats = scene_splats.clone();
+            let mut shown = match self.feature_selection(&model_splats) {
+                Some(mask) => {
+                    let copies = splats.num_splats() - model_splats.num_splats();
+                    let mask = if copies > 0 {
+                        let none = Tensor::<Wgpu, 1>::zeros([copies], &mask.device());
+                        Tensor::cat(vec![mask, none.greater_elem(0.5)], 0)
+                    } else {
+                        mask
+                    };
+                    highlight_selection(&splats, mask)
+                }
+                None => splats.clone(),
+            };
+            if let Some(catcher) = self
+                .shadow_catcher
+                .as_ref()
+                .filter(|_| self.show_shadow_catcher)
+            {
+                splats = with_shadow_catcher(&splats, catcher);
+                shown = with_shadow_catcher(&shown, catcher);
+            }
+            if self.show_uncertainty {
+                splats = self.uncertainty_splats(frame, &splats, context);
+                shown = splats.clone();
+            } else if self.show_contribution {
+                splats = self.contribution_splats(frame, &splats, context);
+                shown = splats.clone();
+            }
+
+            if let Some(target) = self.focus_target.lock().expect("Lock poisoned").take() {
+                Self::focus_on(context, target);
+                self.dirty = true;
+            }
+
+            match self.draw_splats(ui, context, &shown, delta_time) {
+                Some((uv, false)) if self.picking => {
+                    self.pick_feature(&model_splats, uv, context, ui.ctx());
+                    self.picking = false;
+                }
+                Some((uv, true)) => {
+                    self.raycast_focus(frame, &scene_splats, uv, context, ui.ctx());
+                }
+                _ => {}
+            }
+
+            let mode_name = |mode| {
+                RENDER_MODES
+                    .iter()
+                    .find(|(_, _, m)| *m == mode)
+                    .map_or("", |(name, _, _)| tr(*name))
+            };
+            egui::ComboBox::from_label(tr("Render mode"))
+                .selected_text(mode_name(self.render_options.mode))
+                .show_ui(ui, |ui| {
+                    for (name, hover, mode) in RENDER_MODES {
+                        if ui
+                            .selectable_value(&mut self.render_options.mode, mode, tr(name))
+                            .on_hover_text(tr(hover))
+                            .changed()
+                        {
+                            self.dirty = true;
+                        }
+                    }
+                });
+
+            if self.shadow_catcher.is_some()
+                && ui
+                    .checkbox(&mut self.show_shadow_catcher, tr("Ground"))
+                    .on_hover_text(tr(
+                        "Show the shadow catcher trained on the ground. Exports include it when \
+                         shown",
+                    ))
+                    .changed()
+            {
+                self.dirty = true;
+            }
 
-            self.draw_splats(ui, context, &splats, delta_time);
+            if self
+                .features
+                .as_ref()
+                .is_some_and(|f| f.num_splats() == model_splats.num_splats())
+            {
+                self.feature_query_ui(ui, &model_splats);
+            }
 
-            if self.is_loading {
+            ui.collapsing(tr("Edit layers"), |ui| {
+                self.edit_layers_ui(ui, context, model_splats.num_splats());
+            });
+
+            let has_views = !context.dataset.train.views.is_empty();
+            if ui
+                .add_enabled(
+                    has_views,
+                    egui::Checkbox::new(&mut self.show_uncertainty, tr("Uncertainty")),
+                )
+                .on_hover_text(tr(
+                    "Color splats by how many training views saw them, from green for many to \
+                     red for none. Red regions are under-constrained and need more photos",
+                ))
+                .changed()
+            {
+                self.dirty = true;
+            }
+            self.show_uncertainty &= has_views;
+            if self.show_uncertainty {
+                self.show_contribution = false;
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .checkbox(&mut self.show_contribution, tr("Contribution"))
+                    .on_hover_text(tr(
+                        "Color splats by how much they contribute to the current view, from blue \
+                         for barely visible to yellow for the splats that make up most of it. \
+                         Useful to find the floaters that ruin a view",
+                    ))
+                    .changed()
+                {
+                    self.show_uncertainty &= !self.show_contribution;
+                    self.contribution = None;
+                    self.dirty = true;
+                }
+                if self.show_contribution
+                    && ui
+                        .button(tr("Update"))
+                        .on_hover_text(tr("Recompute the contributions for the current view"))
+                        .clicked()
+                {
+                    self.contribution = None;
+                    self.dirty = true;
+                }
+            });
+
+            if self.show_uncertainty {
                 ui.horizontal(|ui| {
-                    ui.label("Loading... Please wait.");
-                    ui.spinner();
+                    if ui
+                        .button(tr("Suggest views"))
+                        .on_hover_text(tr(
+                            "Find camera poses that would best constrain the uncertain regions",
+                        ))
+                        .clicked()
+                    {
+                        let splats = scene_splats.clone();
+                        let views = Self::uncertainty_views(context);
+                        let suggested = self.suggested_views.clone();
+                        let ctx = ui.ctx().clone();
+                        brush_tasks::spawn(async move {
+                            let views =
+                                uncertainty::suggest_views(&splats, &views, NUM_SUGGESTED_VIEWS)
+                                    .await;
+                            *suggested.lock().expect("Lock poisoned") = views;
+                            ctx.request_repaint();
+                        });
+                    }
+
+                    let suggested = self.suggested_views.lock().expect("Lock poisoned").clone();
+                    for (i, (camera, score)) in suggested.iter().enumerate() {
+                        if ui
+                            .button(format!("{}", i + 1))
+                            .on_hover_text(tr_args(
+                                "Go to suggested view, {}% uncertain",
+                                &[&format!("{:.0}", score * 100.0)],
+                            ))
+                            .clicked()
+                        {
+                            context.focus_view(camera);
+                            self.dirty = true;
+                        }
+                    }
+                });
+            }
+
+            let key_name = |key| {
+                DEPTH_KEYS
+                    .iter()
+                    .find(|(_, _, k)| *k == key)
+                    .map_or("", |(name, _, _)| tr(*name))
+            };
+            egui::ComboBox::from_label(tr("Sort by"))
+                .selected_text(key_name(self.render_options.depth_key))
+                .show_ui(ui, |ui| {
+                    for (name, hover, key) in DEPTH_KEYS {
+                        if ui
+                            .selectable_value(&mut self.render_options.depth_key, key, tr(name))
+                            .on_hover_text(tr(hover))
+                            .changed()
+                        {
+                            self.dirty = true;
+                        }
+                    }
+                });
+
+            if ui
+                .checkbox(
+                    &mut self.render_options.order_independent,
+                    tr("Order independent blending"),
+                )
+                .on_hover_text(tr(
+                    "Faster on huge scenes as splats aren't sorted, but less accurate",
+                ))
+                .changed()
+            {
+                self.dirty = true;
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .checkbox(&mut self.resolution.enabled, tr("Dynamic resolution"))
+                    .on_hover_text(tr(
+                        "Lower the resolution while moving to keep up the frame rate",
+                    ))
+                    .changed()
+                {
+                    self.dirty = true;
+                }
+
+                if self.resolution.enabled {
+                    ui.add(
+                        egui::Slider::new(&mut self.resolution.quality_bias, -1.0..=1.0)
+                            .text(tr("Quality bias")),
+                    )
+                    .on_hover_text(tr("Higher values favour sharpness over frame rate"));
+                }
+            });
+
+            if ui
+                .checkbox(&mut self.temporal_aa, tr("Temporal anti-aliasing"))
+                .on_hover_text(tr(
+                    "Blend jittered frames while moving to reduce shimmering, especially at a \
+                     lowered resolution. Renders motion vectors every frame to line them up",
+                ))
+                .changed()
+            {
+                self.temporal.reset();
+                self.dirty = true;
+            }
+
+            let color_space_name = |space| {
+                COLOR_SPACES
+                    .iter()
+                    .find(|(_, _, s)| *s == space)
+                    .map_or("", |(name, _, _)| tr(*name))
+            };
+            egui::ComboBox::from_label(tr("Color space"))
+                .selected_text(color_space_name(self.render_options.color_space))
+                .show_ui(ui, |ui| {
+                    for (name, hover, space) in COLOR_SPACES {
+                        // HDR needs a display surface with more than 8 bits per channel.
+                        let enabled = space != ColorSpace::Hdr10 || self.hdr_output;
+                        let response = ui
+                            .add_enabled_ui(enabled, |ui| {
+                                ui.selectable_value(
+                                    &mut self.render_options.color_space,
+                                    space,
+                                    tr(name),
+                                )
+                            })
+                            .inner
+                            .on_hover_text(tr(hover))
+                            .on_disabled_hover_text(tr("The display doesn't support HDR output"));
+                        if response.changed() {
+                            self.temporal.reset();
+                            self.dirty = true;
+                        }
+                    }
+                });
+
+            if ui
+                .checkbox(&mut self.foveated, tr("Foveated"))
+                .on_hover_text(tr(
+                    "Preview foveated rendering as used for headsets: full resolution only \
+                     around the pointer, and coarser blocks of pixels further away",
+                ))
+                .changed()
+            {
+                self.dirty = true;
+            }
+
+            if self.is_loading || self.is_training {
+                ui.horizontal(|ui| {
+                    if self.is_loading {
+                        ui.label(tr("Loading... Please wait."));
+                        ui.spinner();
+                    }
+
+                    if ui
+                        .button(tr("⏹ Cancel"))
+                        .on_hover_text(tr("Stop loading and training, and free its memory"))
+                        .clicked()
+                    {
+                        self.cancel(context);
+                    }
                 });
             }
 
@@ -340,7 +2151,7 @@ For bigger training runs consider using the native app."#,
                     ui.scope(|ui| {
                         ui.style_mut().visuals.selection.bg_fill = Color32::DARK_RED;
                         if ui
-                            .selectable_label(self.live_update, "🔴 Live update splats")
+                            .selectable_label(self.live_update, tr("🔴 Live update splats"))
                             .clicked()
                         {
                             self.live_update = !self.live_update;
@@ -349,36 +2160,63 @@ For bigger training runs consider using the native app."#,
 
                     ui.add_space(15.0);
 
-                    if ui.button("⬆ Export").clicked() {
-                        let splats = splats.clone();
-
-                        let fut = async move {
-                            let file = rrfd::save_file("export.ply").await;
-
-                            // Not sure where/how to show this error if any.
-                            match file {
-                                Err(e) => {
-                                    log::error!("Failed to save file: {e}");
-                                }
-                                Ok(file) => {
-                                    let data = splat_export::splat_to_ply(splats).await;
-
-                                    let data = match data {
-                                        Ok(data) => data,
-                                        Err(e) => {
-                                            log::error!("Failed to serialize file: {e}");
-                                            return;
-                                        }
-                                    };
-
-                                    if let Err(e) = file.write(&data).await {
-                                        log::error!("Failed to write file: {e}");
-                                    }
-                                }
+                    egui::ComboBox::from_id_salt("export_coordinates")
+                        .selected_text(self.export_coordinates.name())
+                        .show_ui(ui, |ui| {
+                            for (name, convention) in CoordinateConvention::ALL {
+                                ui.selectable_value(&mut self.export_coordinates, convention, name);
                             }
-                        };
+                        })
+                        .response
+                        .on_hover_text(tr("Coordinate convention of the exported ply"));
+
+                    if self.ground.is_some() {
+                        ui.checkbox(&mut self.level_export, tr("Level"))
+                            .on_hover_text(tr("Rotate the exported ply so the ground is level"));
+                    }
+
+                    if ui.button(tr("⬆ Export")).clicked() {
+                        self.export(ExportFormat::Ply, None, context);
+                    }
+
+                    if ui
+                        .button(tr("⬆ Export progressive"))
+                        .on_hover_text(
+                            tr("Export a ply ordered from coarse to fine detail, for web viewers that show the model while it downloads"),
+                        )
+                        .clicked()
+                    {
+                        self.export(ExportFormat::Progressive, None, context);
+                    }
+
+                    if ui
+                        .button(tr("⬆ Export chunks"))
+                        .on_hover_text(
+                            tr("Export a zip of spatial chunks for streaming in game engines, with collision boxes"),
+                        )
+                        .clicked()
+                    {
+                        self.export(ExportFormat::Chunks, None, context);
+                    }
+
+                    if ui
+                        .button(tr("⬆ Export octree"))
+                        .on_hover_text(
+                            tr("Export a zip of octree chunks with levels of detail, for viewers that stream large scenes"),
+                        )
+                        .clicked()
+                    {
+                        self.export(ExportFormat::Octree, None, context);
+                    }
 
-                        tokio_wasm::task::spawn(fut);
+                    if ui
+                        .button(tr("⬆ Export binary"))
+                        .on_hover_text(
+                            tr("Export a .splats model, which opens much faster than a ply in Brush"),
+                        )
+                        .clicked()
+                    {
+                        self.export(ExportFormat::Binary, None, context);
                     }
                 });
             }
@@ -0,0 +1,151 @@
+use brush_render::statistics::{Histogram, SplatStatistics};
+use egui::{pos2, vec2, Color32, Rect, Sense, Stroke};
+
+use crate::{
+    app::{AppContext, AppPanel},
+    i18n::{tr, tr_args},
+    process_loop::ProcessMessage,
+};
+
+struct RefineCounts {
+    iter: u32,
+    split: usize,
+    cloned: usize,
+    pruned: usize,
+}
+
+pub(crate) struct SplatStatsPanel {
+    statistics: Option<(u32, SplatStatistics)>,
+    refines: Vec<RefineCounts>,
+}
+
+impl SplatStatsPanel {
+    pub(crate) fn new() -> Self {
+        Self {
+            statistics: None,
+            refines: vec![],
+        }
+    }
+}
+
+fn draw_histogram(ui: &mut egui::Ui, label: &str, hist: &Histogram) {
+    ui.label(format!(
+        "{label}: {:.3} to {:.3}, mean {:.3}",
+        hist.min, hist.max, hist.mean
+    ));
+
+    let (response, painter) = ui.allocate_painter(vec2(ui.available_width(), 60.0), Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let max_count = hist.counts.iter().copied().max().unwrap_or(0).max(1);
+    let bar_width = rect.width() / hist.counts.len().max(1) as f32;
+    let color = ui.visuals().selection.bg_fill;
+
+    for (i, &count) in hist.counts.iter().enumerate() {
+        let height = rect.height() * count as f32 / max_count as f32;
+        let x = rect.left() + i as f32 * bar_width;
+        let bar = Rect::from_min_max(
+            pos2(x, rect.bottom() - height),
+            pos2(x + bar_width - 1.0, rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, color);
+    }
+
+    if let Some(hover) = response.hover_pos() {
+        let bin = ((hover.x - rect.left()) / bar_width) as usize;
+        if let Some(count) = hist.counts.get(bin) {
+            response.on_hover_text(format!("{:.3}: {count}", hist.bin_center(bin)));
+        }
+    }
+}
+
+fn draw_refine_history(ui: &mut egui::Ui, refines: &[RefineCounts]) {
+    let (response, painter) = ui.allocate_painter(vec2(ui.available_width(), 80.0), Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let (Some(first), Some(last)) = (refines.first(), refines.last()) else {
+        return;
+    };
+
+    let max_count = refines
+        .iter()
+        .map(|r| r.split.max(r.cloned).max(r.pruned))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let iter_range = (last.iter - first.iter).max(1) as f32;
+
+    let series: [(&str, Color32, fn(&RefineCounts) -> usize); 3] = [
+        ("split", Color32::LIGHT_BLUE, |r| r.split),
+        ("cloned", Color32::LIGHT_GREEN, |r| r.cloned),
+        ("pruned", Color32::LIGHT_RED, |r| r.pruned),
+    ];
+
+    for (_, color, value) in series {
+        let points = refines
+            .iter()
+            .map(|r| {
+                let x = rect.left() + rect.width() * (r.iter - first.iter) as f32 / iter_range;
+                let y = rect.bottom() - rect.height() * value(r) as f32 / max_count as f32;
+                pos2(x, y)
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, Stroke::new(1.5, color)));
+    }
+
+    ui.horizontal(|ui| {
+        for (name, color, value) in series {
+            ui.colored_label(color, format!("{name} {}", value(last)));
+        }
+    });
+}
+
+impl AppPanel for SplatStatsPanel {
+    fn title(&self) -> String {
+        tr("Splat stats").to_owned()
+    }
+
+    fn on_message(&mut self, message: &ProcessMessage, _: &mut AppContext) {
+        match message {
+            ProcessMessage::StartLoading { .. } => {
+                self.statistics = None;
+                self.refines.clear();
+            }
+            ProcessMessage::RefineStep { stats, iter } => {
+                self.refines.push(RefineCounts {
+                    iter: *iter,
+                    split: stats.num_split,
+                    cloned: stats.num_cloned,
+                    pruned: stats.num_transparent_pruned + stats.num_scale_pruned,
+                });
+            }
+            ProcessMessage::SplatStatistics { stats, iter } => {
+                self.statistics = Some((*iter, *stats.clone()));
+            }
+            _ => {}
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _: &mut AppContext) {
+        let Some((iter, stats)) = &self.statistics else {
+            ui.label(tr("Statistics are shown while training."));
+            return;
+        };
+
+        ui.label(tr_args("{} splats at step {}", &[&stats.num_splats, iter]));
+        ui.add_space(4.0);
+
+        draw_histogram(ui, tr("Opacity"), &stats.opacity);
+        draw_histogram(ui, tr("Anisotropy (log10)"), &stats.anisotropy);
+        draw_histogram(ui, tr("SH energy"), &stats.sh_energy);
+        if let Some(radius) = &stats.screen_radius {
+            draw_histogram(ui, tr("Screen radius (log10 px)"), radius);
+        }
+
+        ui.add_space(4.0);
+        ui.label(tr("Refinement"));
+        draw_refine_history(ui, &self.refines);
+    }
+}
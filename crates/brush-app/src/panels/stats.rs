@@ -1,7 +1,9 @@
 use crate::{
     app::{AppContext, AppPanel},
+    i18n::tr,
     process_loop::ProcessMessage,
 };
+use brush_tasks::Progress;
 use burn_jit::cubecl::Runtime;
 use burn_wgpu::{WgpuDevice, WgpuRuntime};
 use std::time::Duration;
@@ -19,6 +21,8 @@ pub(crate) struct StatsPanel {
     training_started: bool,
     num_splats: usize,
     frames: usize,
+    // Latest progress of loading, eval or an export, until it's done.
+    progress: Option<Progress>,
 
     start_load_time: Instant,
     adapter_info: AdapterInfo,
@@ -35,6 +39,7 @@ impl StatsPanel {
             training_started: false,
             num_splats: 0,
             frames: 0,
+            progress: None,
             cur_sh_degree: 0,
             start_load_time: Instant::now(),
             adapter_info,
@@ -64,7 +69,7 @@ fn bytes_format(bytes: u64) -> String {
 
 impl AppPanel for StatsPanel {
     fn title(&self) -> String {
-        "Stats".to_owned()
+        tr("Stats").to_owned()
     }
 
     fn on_message(&mut self, message: &ProcessMessage, _: &mut AppContext) {
@@ -76,8 +81,12 @@ impl AppPanel for StatsPanel {
                 self.num_splats = 0;
                 self.cur_sh_degree = 0;
                 self.last_eval_psnr = None;
+                self.progress = None;
                 self.training_started = *training;
             }
+            ProcessMessage::Progress(progress) => {
+                self.progress = (progress.fraction < 1.0).then(|| progress.clone());
+            }
             ProcessMessage::ViewSplats {
                 up_axis: _,
                 splats,
@@ -90,9 +99,9 @@ impl AppPanel for StatsPanel {
             }
             ProcessMessage::TrainStep {
                 splats,
-                stats: _,
                 iter,
                 timestamp,
+                ..
             } => {
                 self.cur_sh_degree = splats.sh_degree();
                 self.num_splats = splats.num_splats();
@@ -116,30 +125,40 @@ impl AppPanel for StatsPanel {
             .spacing([40.0, 4.0])
             .striped(true)
             .show(ui, |ui| {
-                ui.label("Splats");
+                if let Some(progress) = &self.progress {
+                    ui.label(&progress.stage);
+                    ui.add(
+                        egui::ProgressBar::new(progress.fraction)
+                            .show_percentage()
+                            .animate(true),
+                    );
+                    ui.end_row();
+                }
+
+                ui.label(tr("Splats"));
                 ui.label(format!("{}", self.num_splats));
                 ui.end_row();
 
-                ui.label("SH Degree");
+                ui.label(tr("SH Degree"));
                 ui.label(format!("{}", self.cur_sh_degree));
                 ui.end_row();
 
                 if self.frames > 0 {
-                    ui.label("Frames");
+                    ui.label(tr("Frames"));
                     ui.label(format!("{}", self.frames));
                     ui.end_row();
                 }
 
                 if self.training_started {
-                    ui.label("Train step");
+                    ui.label(tr("Train step"));
                     ui.label(format!("{}", self.last_train_step.1));
                     ui.end_row();
 
-                    ui.label("Steps/s");
+                    ui.label(tr("Steps/s"));
                     ui.label(format!("{:.1}", self.train_iter_per_s));
                     ui.end_row();
 
-                    ui.label("Last eval PSNR");
+                    ui.label(tr("Last eval PSNR"));
                     ui.label(if let Some(psnr) = self.last_eval_psnr {
                         format!("{psnr:.}")
                     } else {
@@ -147,7 +166,7 @@ impl AppPanel for StatsPanel {
                     });
                     ui.end_row();
 
-                    ui.label("Training time");
+                    ui.label(tr("Training time"));
                     // Round duration to seconds.
                     let elapsed = Duration::from_secs(self.start_load_time.elapsed().as_secs());
                     ui.label(format!("{}", humantime::Duration::from(elapsed)));
@@ -157,18 +176,18 @@ impl AppPanel for StatsPanel {
                 let client = WgpuRuntime::client(&self.device);
                 let memory = client.memory_usage();
 
-                ui.label("GPU memory");
+                ui.label(tr("GPU memory"));
                 ui.end_row();
 
-                ui.label("Bytes in use");
+                ui.label(tr("Bytes in use"));
                 ui.label(bytes_format(memory.bytes_in_use));
                 ui.end_row();
 
-                ui.label("Bytes reserved");
+                ui.label(tr("Bytes reserved"));
                 ui.label(bytes_format(memory.bytes_reserved));
                 ui.end_row();
 
-                ui.label("Active allocations");
+                ui.label(tr("Active allocations"));
                 ui.label(format!("{}", memory.number_allocs));
                 ui.end_row();
             });
@@ -183,15 +202,15 @@ impl AppPanel for StatsPanel {
                     ui.label("GPU");
                     ui.end_row();
 
-                    ui.label("Name");
+                    ui.label(tr("Name"));
                     ui.label(&self.adapter_info.name);
                     ui.end_row();
 
-                    ui.label("Type");
+                    ui.label(tr("Type"));
                     ui.label(format!("{:?}", self.adapter_info.device_type));
                     ui.end_row();
 
-                    ui.label("Driver");
+                    ui.label(tr("Driver"));
                     ui.label(format!(
                         "{}, {}",
                         self.adapter_info.driver, self.adapter_info.driver_info
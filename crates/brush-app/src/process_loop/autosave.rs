@@ -0,0 +1,238 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use brush_dataset::{
+    coordinates::CoordinateConvention,
+    model_info::{dataset_fingerprint, ModelInfo},
+    npy, splat_export, Dataset,
+};
+use brush_render::gaussian_splats::Splats;
+use brush_train::{
+    eval::EvalStats, features::FeatureField, lighting::Decomposition,
+    shadow_catcher::ShadowCatcher, train::TrainConfig,
+};
+use burn_wgpu::Wgpu;
+use glam::Vec3;
+
+use super::SaveArgs;
+
+fn write_file(path: &PathBuf, data: &[u8], sync: bool) -> anyhow::Result<()> {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Killing training while saving shouldn't leave a checkpoint that fails to load.
+        rrfd::write_atomic(path, data, sync)?;
+        Ok(())
+    }
+
+    #[cfg(target_family = "wasm")]
+    {
+        let _ = (path, data, sync);
+        anyhow::bail!("Saving to disk isn't supported on the web.")
+    }
+}
+
+async fn encode_checkpoint(
+    splats: Splats<Wgpu>,
+    iter: u32,
+    config: &TrainConfig,
+    info: &ModelInfo,
+) -> anyhow::Result<Vec<u8>> {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        brush_dataset::checkpoint::save_checkpoint(splats, iter, config, info).await
+    }
+
+    #[cfg(target_family = "wasm")]
+    {
+        let _ = (splats, iter, config, info);
+        anyhow::bail!("Saving to disk isn't supported on the web.")
+    }
+}
+
+fn remove_file(path: &PathBuf) {
+    #[cfg(not(target_family = "wasm"))]
+    if let Err(e) = std::fs::remove_file(path) {
+        log::warn!("Failed to remove old checkpoint {path:?}: {e}");
+    }
+
+    #[cfg(target_family = "wasm")]
+    let _ = path;
+}
+
+/// Saves the model while training, every so often and whenever the eval PSNR improves.
+pub(crate) struct Autosaver {
+    args: SaveArgs,
+    config: TrainConfig,
+    saved: VecDeque<PathBuf>,
+    best_psnr: f32,
+    // Stored with every saved model, see `brush_dataset::model_info`.
+    info: ModelInfo,
+}
+
+impl Autosaver {
+    pub(crate) fn new(args: SaveArgs, config: TrainConfig, dataset: &Dataset) -> Self {
+        let info = ModelInfo::trained(&config, dataset);
+        Self {
+            args,
+            config,
+            saved: VecDeque::new(),
+            best_psnr: f32::NEG_INFINITY,
+            info,
+        }
+    }
+
+    /// Update the dataset fingerprint when more views are loaded.
+    pub(crate) fn set_dataset(&mut self, dataset: &Dataset) {
+        self.info.dataset_fingerprint = Some(dataset_fingerprint(dataset));
+    }
+
+    pub(crate) fn should_save(&self, iter: u32) -> bool {
+        self.args.output_dir.is_some() && self.args.save_every.is_some_and(|n| iter % n == 0)
+    }
+
+    /// Save a periodic `.brush` checkpoint to resume training from, removing the oldest ones
+    /// past the retention limit. See [`brush_dataset::checkpoint`].
+    pub(crate) async fn save_step(
+        &mut self,
+        iter: u32,
+        splats: Splats<Wgpu>,
+    ) -> anyhow::Result<()> {
+        let Some(dir) = self.args.output_dir.as_ref() else {
+            return Ok(());
+        };
+
+        let path = dir.join(format!("splat_{iter}.brush"));
+        self.info.iter = Some(iter);
+        let data = encode_checkpoint(splats, iter, &self.config, &self.info).await?;
+        write_file(&path, &data, self.args.sync_writes)?;
+        log::info!("Saved checkpoint {path:?}");
+
+        self.saved.push_back(path);
+        while self.saved.len() > self.args.keep_last.max(1) {
+            if let Some(old) = self.saved.pop_front() {
+                remove_file(&old);
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the model as `best.ply` if its eval PSNR is the best so far. When training with
+    /// relighting, the albedo and lighting are saved as `best_albedo.ply` and
+    /// `best_lighting.json` too. The shadow catcher, if any, isn't part of `best.ply` but
+    /// saved on its own as `best_shadow_catcher.ply`. Learned features are saved as
+    /// `best_features.npy`, one row per splat of `best.ply`.
+    pub(crate) async fn save_if_best(
+        &mut self,
+        iter: u32,
+        eval: &EvalStats<Wgpu>,
+        splats: Splats<Wgpu>,
+        decomposition: Option<&Decomposition<Wgpu>>,
+        shadow_catcher: Option<&ShadowCatcher<Wgpu>>,
+        features: Option<&FeatureField<Wgpu>>,
+        view_positions: &[Vec3],
+    ) -> anyhow::Result<()> {
+        self.info.set_eval(eval);
+
+        let Some(dir) = self.args.output_dir.as_ref() else {
+            return Ok(());
+        };
+
+        if !self.args.save_best || eval.samples.is_empty() {
+            return Ok(());
+        }
+
+        let psnr = eval.samples.iter().map(|s| s.psnr).sum::<f32>() / eval.samples.len() as f32;
+        if psnr <= self.best_psnr {
+            return Ok(());
+        }
+        self.best_psnr = psnr;
+
+        let path = dir.join("best.ply");
+        self.info.iter = Some(iter);
+        let data = splat_export::splat_to_ply_with_info(
+            splats,
+            view_positions,
+            CoordinateConvention::BRUSH,
+            &self.info,
+        )
+        .await?;
+        write_file(&path, &data, self.args.sync_writes)?;
+
+        if let Some(decomposition) = decomposition {
+            let data = splat_export::splat_to_ply_with_info(
+                decomposition.albedo.clone(),
+                view_positions,
+                CoordinateConvention::BRUSH,
+                &self.info,
+            )
+            .await?;
+            write_file(&dir.join("best_albedo.ply"), &data, self.args.sync_writes)?;
+
+            // The shading of a splat is this degree 2 spherical harmonic of its normal, see
+            // `brush_train::lighting`.
+            let lighting = serde_json::json!({
+                "shading": "sh_degree_2",
+                "coefficients": decomposition.lighting.coefficients().await,
+            });
+            write_file(
+                &dir.join("best_lighting.json"),
+                serde_json::to_string_pretty(&lighting)?.as_bytes(),
+                self.args.sync_writes,
+            )?;
+        }
+
+        if let Some(catcher) = shadow_catcher {
+            let data = splat_export::splat_to_ply_with_info(
+                catcher.splats.clone(),
+                view_positions,
+                CoordinateConvention::BRUSH,
+                &self.info,
+            )
+            .await?;
+            write_file(
+                &dir.join("best_shadow_catcher.ply"),
+                &data,
+                self.args.sync_writes,
+            )?;
+        }
+
+        if let Some(features) = features {
+            let data = npy::write_npy_f32(
+                &[features.num_splats(), features.dim()],
+                &features.read().await,
+            );
+            write_file(&dir.join("best_features.npy"), &data, self.args.sync_writes)?;
+        }
+
+        log::info!("Saved best model so far at step {iter} with PSNR {psnr:.2}");
+        Ok(())
+    }
+
+    /// Save the model as a ply at `path`, relative to the output folder if there is one, eg.
+    /// when a training script asks for it.
+    pub(crate) async fn export(
+        &mut self,
+        path: &std::path::Path,
+        iter: u32,
+        splats: Splats<Wgpu>,
+        view_positions: &[Vec3],
+    ) -> anyhow::Result<()> {
+        let path = match &self.args.output_dir {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        };
+        self.info.iter = Some(iter);
+        let data = splat_export::splat_to_ply_with_info(
+            splats,
+            view_positions,
+            CoordinateConvention::BRUSH,
+            &self.info,
+        )
+        .await?;
+        write_file(&path, &data, self.args.sync_writes)?;
+        log::info!("Exported {path:?} at step {iter}");
+        Ok(())
+    }
+}
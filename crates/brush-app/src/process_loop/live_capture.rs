@@ -0,0 +1,404 @@
+//! Experimental on-line reconstruction from a webcam, video stream or phone.
+//!
+//! Video frames are decoded by an `ffmpeg` process, so anything ffmpeg can read works as a
+//! source, eg. `/dev/video0` or `rtsp://camera.local/stream`. The first frame is placed at the
+//! origin, and every later frame is tracked against the model trained so far, see
+//! [`brush_train::tracking::track_frame`]. Phones can instead stream frames with the poses
+//! from their AR framework, see [`super::pose_stream`].
+//!
+//! Frames that moved far enough from the views so far are added as new training views while
+//! training keeps running.
+use std::{process::Stdio, sync::Arc};
+
+use anyhow::Context;
+use brush_dataset::Dataset;
+use brush_render::{
+    bounding_box::BoundingBox,
+    camera::Camera,
+    gaussian_splats::{RandomSplatsConfig, Splats},
+};
+use brush_tasks::CancellationToken;
+use brush_train::{
+    image::image_sharpness,
+    scene::{Scene, SceneView},
+    tracking::{track_frame, TrackingConfig},
+    train::TrainConfig,
+};
+use burn::{backend::Autodiff, prelude::Backend};
+use burn_wgpu::{Wgpu, WgpuDevice};
+use glam::{Quat, Vec3};
+use image::{DynamicImage, RgbImage};
+use rand::SeedableRng;
+use tokio::{
+    io::AsyncReadExt,
+    process::Command,
+    sync::{
+        mpsc::{channel, unbounded_channel, Sender, UnboundedReceiver},
+        watch,
+    },
+};
+use tokio_stream::StreamExt;
+
+use super::{
+    forward_progress,
+    pose_stream::serve_pose_stream,
+    train_stream::{train_stream, TrainMessage},
+    ControlMessage, ProcessMessage, RunningProcess,
+};
+
+/// Where the frames of a live capture come from.
+#[derive(Clone, Debug)]
+pub enum LiveSource {
+    /// Anything ffmpeg can read, eg. a webcam device or an RTSP url. Poses are tracked.
+    Video(String),
+    /// Address to listen on for phones streaming posed frames, see [`super::pose_stream`].
+    PoseStream(String),
+}
+
+/// Settings for a live capture.
+#[derive(Clone, Debug)]
+pub struct LiveCaptureArgs {
+    pub source: LiveSource,
+    /// Video frames are scaled to this width.
+    pub width: u32,
+    /// Assumed horizontal field of view of the video camera in degrees.
+    pub fov_x: f64,
+    pub train_config: TrainConfig,
+}
+
+/// A frame of a live capture, with its pose if the source knows it.
+#[derive(Clone)]
+pub(crate) struct LiveFrame {
+    pub(crate) name: String,
+    pub(crate) image: Arc<DynamicImage>,
+    pub(crate) camera: Option<Camera>,
+}
+
+impl LiveCaptureArgs {
+    pub fn new(source: LiveSource) -> Self {
+        Self {
+            source,
+            width: 640,
+            fov_x: 60.0,
+            train_config: TrainConfig::default(),
+        }
+    }
+}
+
+// A new view is added when a frame moved this far from every view so far, as a fraction of
+// the scene extent, or turned this many degrees.
+const KEYFRAME_DISTANCE: f32 = 0.1;
+const KEYFRAME_ANGLE: f32 = 15.0;
+
+// Frames that track worse than this mean color error are considered lost, and skipped.
+const MAX_TRACKING_ERROR: f32 = 0.15;
+
+// Number of training steps between tracking frames.
+const TRACK_EVERY: u32 = 10;
+
+// How frequently to update the UI after a training step.
+const UPDATE_EVERY: u32 = 5;
+
+// Read the size of the video, so frames can be scaled to keep their aspect ratio.
+async fn probe_size(source: &str) -> anyhow::Result<(u32, u32)> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=width,height",
+            "-of",
+            "csv=p=0:s=x",
+            source,
+        ])
+        .output()
+        .await
+        .context("Failed to run ffprobe, is ffmpeg installed?")?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (w, h) = text
+        .trim()
+        .split_once('x')
+        .with_context(|| format!("Failed to read the video size of {source}"))?;
+    Ok((w.parse()?, h.parse()?))
+}
+
+// Decode frames with ffmpeg, and keep only the latest one. Training is slower than the frame
+// rate, so older frames are dropped.
+async fn read_frames(
+    source: String,
+    width: u32,
+    frames: watch::Sender<Option<LiveFrame>>,
+) -> anyhow::Result<()> {
+    let (src_w, src_h) = probe_size(&source).await?;
+    let height = ((width as f32 * src_h as f32 / src_w as f32).round() as u32).max(2) & !1;
+
+    let mut args = vec![];
+    if source.starts_with("rtsp://") {
+        args.extend(["-rtsp_transport", "tcp"]);
+    }
+    let scale = format!("scale={width}:{height}");
+    args.extend([
+        "-i", &source, "-vf", &scale, "-f", "rawvideo", "-pix_fmt", "rgb24", "-",
+    ]);
+
+    let mut child = Command::new("ffmpeg")
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to run ffmpeg, is it installed?")?;
+    let mut stdout = child.stdout.take().context("No ffmpeg output")?;
+
+    let mut buf = vec![0; (width * height * 3) as usize];
+    for index in 0.. {
+        stdout.read_exact(&mut buf).await?;
+        let image = RgbImage::from_raw(width, height, buf.clone()).context("Invalid frame")?;
+        let frame = LiveFrame {
+            name: format!("frame_{index:06}"),
+            image: Arc::new(DynamicImage::ImageRgb8(image)),
+            camera: None,
+        };
+        if frames.send(Some(frame)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn is_keyframe(camera: &Camera, views: &[SceneView], extent: f32) -> bool {
+    views.iter().all(|view| {
+        let distance = (view.camera.position - camera.position).length();
+        let angle = view
+            .camera
+            .rotation
+            .angle_between(camera.rotation)
+            .to_degrees();
+        distance > KEYFRAME_DISTANCE * extent || angle > KEYFRAME_ANGLE
+    })
+}
+
+fn new_view(frame: LiveFrame, camera: Camera) -> SceneView {
+    SceneView {
+        name: frame.name,
+        camera,
+        sharpness: image_sharpness(&frame.image),
+        image: frame.image.into(),
+        mask: None,
+        camera_id: 0,
+        capture_id: 0,
+        features: None,
+        depth: None,
+    }
+}
+
+async fn live_process_loop(
+    output: Sender<ProcessMessage>,
+    args: LiveCaptureArgs,
+    device: WgpuDevice,
+    mut control: UnboundedReceiver<ControlMessage>,
+) -> anyhow::Result<()> {
+    let _ = output
+        .send(ProcessMessage::StartLoading { training: true })
+        .await;
+
+    let (frame_sender, mut frames) = watch::channel(None);
+    let (source, width) = (args.source.clone(), args.width);
+    tokio::spawn(async move {
+        let result = match source {
+            LiveSource::Video(source) => read_frames(source, width, frame_sender).await,
+            LiveSource::PoseStream(addr) => serve_pose_stream(addr, frame_sender).await,
+        };
+        if let Err(e) = result {
+            log::error!("Live capture stopped: {e}");
+        }
+    });
+
+    frames
+        .changed()
+        .await
+        .context("No frames from live source")?;
+    let first = frames
+        .borrow_and_update()
+        .clone()
+        .context("No frames from live source")?;
+
+    let first_camera = first.camera.clone().unwrap_or_else(|| {
+        let aspect = first.image.height() as f64 / first.image.width() as f64;
+        let fov_x = args.fov_x.to_radians();
+        let fov_y = 2.0 * ((fov_x * 0.5).tan() * aspect).atan();
+        Camera::new(
+            Vec3::ZERO,
+            Quat::IDENTITY,
+            fov_x,
+            fov_y,
+            glam::vec2(0.5, 0.5),
+        )
+    });
+
+    let mut views = vec![new_view(first, first_camera.clone())];
+    let dataset = Dataset::from_views(views.clone(), vec![]);
+    let _ = output
+        .send(ProcessMessage::Dataset {
+            data: dataset.clone(),
+        })
+        .await;
+    let _ = output
+        .send(ProcessMessage::DoneLoading { training: true })
+        .await;
+
+    // Without any structure from motion, start from random splats in front of the camera.
+    let config = args.train_config;
+    <Autodiff<Wgpu> as Backend>::seed(config.seed);
+    let mut rng = rand::rngs::StdRng::from_seed([config.seed as u8; 32]);
+    let local_to_world = first_camera.local_to_world();
+    let (min, max) = [-1.5, 1.5]
+        .into_iter()
+        .flat_map(|x| [-1.0, 1.0].map(|y| (x, y)))
+        .flat_map(|(x, y)| [1.0, 4.0].map(|z| Vec3::new(x, y, z)))
+        .map(|corner| local_to_world.transform_point3(corner))
+        .fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        );
+    let bounds = BoundingBox::from_min_max(min, max);
+    let splats = Splats::from_random_config(&RandomSplatsConfig::new(), bounds, &mut rng, &device);
+
+    let (dataset_sender, dataset_receiver) = unbounded_channel();
+    let stream = train_stream(
+        dataset,
+        dataset_receiver,
+        splats,
+        0,
+        None,
+        config,
+        device.clone(),
+    );
+    let mut stream = std::pin::pin!(stream);
+
+    let tracking = TrackingConfig::default();
+    let mut camera = first_camera;
+    let mut paused = false;
+
+    loop {
+        let message = if paused {
+            control.recv().await
+        } else {
+            control.try_recv().ok()
+        };
+        if let Some(ControlMessage::Paused(p)) = message {
+            paused = p;
+            continue;
+        }
+
+        let Some(msg) = stream.next().await else {
+            break;
+        };
+
+        match msg? {
+            TrainMessage::TrainStep {
+                splats,
+                stats,
+                iter,
+                timestamp,
+                environment,
+                ..
+            } => {
+                if iter % TRACK_EVERY == 0 && frames.has_changed().unwrap_or(false) {
+                    let frame = frames.borrow_and_update().clone();
+                    if let Some(frame) = frame {
+                        let extent = Scene::new(views.clone()).extent(&[]);
+                        // Frames with a known pose don't need to be tracked.
+                        let (tracked, error) = match frame.camera.clone() {
+                            Some(known) => (known, 0.0),
+                            None => {
+                                track_frame(&*splats, &frame.image, &camera, extent, &tracking)
+                                    .await
+                            }
+                        };
+
+                        if error > MAX_TRACKING_ERROR {
+                            log::warn!("Lost tracking (error {error:.3}), skipping frame");
+                        } else {
+                            camera = tracked;
+                            if is_keyframe(&camera, &views, extent) {
+                                views.push(new_view(frame, camera.clone()));
+                                let dataset = Dataset::from_views(views.clone(), vec![]);
+                                let _ = dataset_sender.send(dataset.clone());
+                                if output
+                                    .send(ProcessMessage::Dataset { data: dataset })
+                                    .await
+                                    .is_err()
+                                {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if iter % UPDATE_EVERY == 0
+                    && output
+                        .send(ProcessMessage::TrainStep {
+                            splats,
+                            stats,
+                            iter,
+                            timestamp,
+                            shadow_catcher: None,
+                            features: None,
+                            environment,
+                        })
+                        .await
+                        .is_err()
+                {
+                    break;
+                }
+            }
+            TrainMessage::RefineStep { stats, iter } => {
+                if output
+                    .send(ProcessMessage::RefineStep { stats, iter })
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            TrainMessage::Statistics { stats, iter } => {
+                if output
+                    .send(ProcessMessage::SplatStatistics { stats, iter })
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Start training on a live video source, see the [module docs](self).
+pub fn start_live_process(args: LiveCaptureArgs, device: WgpuDevice) -> RunningProcess {
+    let (sender, receiver) = channel(1);
+    let (control_sender, control_receiver) = unbounded_channel();
+    let cancel = CancellationToken::new();
+    let progress = forward_progress(&sender);
+
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        let process = live_process_loop(sender.clone(), args, device, control_receiver);
+        if let Some(Err(e)) = brush_tasks::until_cancelled(&token, process).await {
+            let _ = sender.send(ProcessMessage::Error(e)).await;
+        }
+    });
+
+    RunningProcess {
+        messages: receiver,
+        control: control_sender,
+        cancel,
+        progress,
+    }
+}
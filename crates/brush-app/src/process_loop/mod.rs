@@ -1,7 +1,16 @@
+mod autosave;
+#[cfg(not(target_family = "wasm"))]
+mod live_capture;
+#[cfg(not(target_family = "wasm"))]
+mod pose_stream;
 mod process;
 mod process_args;
+mod script;
 
 mod train_stream;
 
+#[cfg(not(target_family = "wasm"))]
+pub use live_capture::*;
 pub use process::*;
 pub use process_args::*;
+pub(crate) use script::run_viewer_script;
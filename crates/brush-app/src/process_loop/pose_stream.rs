@@ -0,0 +1,130 @@
+//! Receive posed frames from a phone, eg. a companion app using ARKit or ARCore.
+//!
+//! The app connects over TCP and sends every frame as a json line, followed by the encoded
+//! image (JPEG or PNG) of `image_size` bytes:
+//!
+//! ```json
+//! {"timestamp": 12.5, "width": 1920, "height": 1440, "fx": 1450.0, "fy": 1450.0,
+//!  "cx": 960.0, "cy": 720.0, "transform": [16 floats], "image_size": 123456}
+//! ```
+//!
+//! The intrinsics are in pixels of the image, and `transform` is the camera to world matrix in
+//! column major order, with the camera looking down -Z and +Y up, like an ARKit `ARCamera`. On
+//! ARCore use the pose of `Camera.getDisplayOrientedPose`.
+use std::sync::Arc;
+
+use anyhow::Context;
+use brush_render::camera::Camera;
+use glam::Mat4;
+use image::DynamicImage;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+
+use super::live_capture::LiveFrame;
+use crate::remote::read_line;
+
+// Refuse frames bigger than this, to not allocate whatever a bad header asks for.
+const MAX_IMAGE_SIZE: usize = 64 * 1024 * 1024;
+// Largest width or height of a frame, frames are resized to the size in their header.
+const MAX_FRAME_DIMENSION: u32 = 8192;
+
+#[derive(Debug, Deserialize)]
+struct FrameHeader {
+    timestamp: f64,
+    width: u32,
+    height: u32,
+    fx: f64,
+    fy: f64,
+    cx: f32,
+    cy: f32,
+    transform: [f32; 16],
+    image_size: usize,
+}
+
+impl FrameHeader {
+    fn camera(&self) -> Camera {
+        Camera::from_focal(
+            glam::vec2(self.fx as f32, self.fy as f32),
+            glam::vec2(self.cx, self.cy),
+            glam::uvec2(self.width, self.height),
+        )
+        .with_opengl_world_from_camera(Mat4::from_cols_array(&self.transform))
+    }
+}
+
+async fn receive_frames(
+    stream: TcpStream,
+    frames: &watch::Sender<Option<LiveFrame>>,
+) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let Some(line) = read_line(&mut reader).await? else {
+            return Ok(());
+        };
+        let header: FrameHeader = serde_json::from_str(&line).context("Invalid frame header")?;
+        anyhow::ensure!(
+            header.image_size <= MAX_IMAGE_SIZE,
+            "Frame of {} bytes is too big",
+            header.image_size
+        );
+        anyhow::ensure!(
+            (1..=MAX_FRAME_DIMENSION).contains(&header.width)
+                && (1..=MAX_FRAME_DIMENSION).contains(&header.height),
+            "Invalid frame size {}x{}",
+            header.width,
+            header.height
+        );
+
+        let mut data = vec![0; header.image_size];
+        reader.read_exact(&mut data).await?;
+        let (width, height) = (header.width, header.height);
+        let image = brush_tasks::run_blocking(move || {
+            let image = image::load_from_memory(&data).context("Failed to decode frame")?;
+            if image.width() == width && image.height() == height {
+                return anyhow::Ok(image);
+            }
+            // Keep the intrinsics valid if the app sends frames at another resolution.
+            Ok(DynamicImage::ImageRgb8(
+                image
+                    .resize_exact(width, height, image::imageops::FilterType::Triangle)
+                    .to_rgb8(),
+            ))
+        })
+        .await?;
+
+        let frame = LiveFrame {
+            name: format!("frame_{:.3}", header.timestamp),
+            image: Arc::new(image),
+            camera: Some(header.camera()),
+        };
+        if frames.send(Some(frame)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Listen for phones streaming posed frames, see the [module docs](self). Phones are served
+/// one at a time, until training stops.
+pub(crate) async fn serve_pose_stream(
+    addr: String,
+    frames: watch::Sender<Option<LiveFrame>>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(&addr)
+        .await
+        .with_context(|| format!("Failed to listen on {addr}"))?;
+    log::info!("Waiting for posed frames on {}", listener.local_addr()?);
+
+    while !frames.is_closed() {
+        let (stream, peer) = listener.accept().await?;
+        log::info!("Phone connected from {peer}");
+        if let Err(e) = receive_frames(stream, &frames).await {
+            log::warn!("Phone {peer} disconnected: {e}");
+        }
+    }
+    Ok(())
+}
@@ -1,20 +1,40 @@
 use std::path::Path;
 
-use crate::data_source::DataSource;
+use crate::{
+    actions::ViewerAction,
+    data_source::{DataSource, JsonKind},
+};
+use anyhow::Context;
 use brush_dataset::{
+    binary_model::{self, BinaryModel},
     brush_vfs::{BrushVfs, PathReader},
-    splat_import, Dataset, LoadDatasetArgs, LoadInitArgs,
+    coordinates::CoordinateConvention,
+    splat_import::{self, SplatMessage},
+    DataStream, Dataset, DatasetError, LoadDatasetArgs, LoadInitArgs, WebDatasetIndex,
+};
+use brush_render::{
+    gaussian_splats::{RandomSplatsConfig, Splats},
+    statistics::SplatStatistics,
 };
-use brush_render::gaussian_splats::{RandomSplatsConfig, Splats};
+use brush_tasks::{CancellationToken, Progress, ProgressSender};
 use brush_train::{
+    environment::{EnvironmentMap, EnvironmentRays},
     eval::EvalStats,
-    train::{RefineStats, TrainConfig, TrainStepStats},
+    features::FeatureField,
+    perceptual::PerceptualLoss,
+    shadow_catcher::ShadowCatcher,
+    train::{seed_region, RefineStats, TrainConfig, TrainStepStats},
+};
+use burn::{
+    backend::Autodiff,
+    module::AutodiffModule,
+    prelude::Backend,
+    tensor::{ElementConversion, Tensor},
 };
-use burn::{backend::Autodiff, module::AutodiffModule, prelude::Backend};
 use burn_wgpu::{Wgpu, WgpuDevice};
 use glam::Vec3;
 use rand::SeedableRng;
-use tokio::sync::mpsc::{channel, UnboundedSender};
+use tokio::sync::mpsc::{channel, error::TryRecvError, UnboundedSender};
 use tokio::sync::mpsc::{unbounded_channel, Receiver};
 use tokio::{
     io::{AsyncRead, AsyncReadExt, BufReader},
@@ -24,12 +44,18 @@ use tokio_stream::StreamExt;
 use web_time::Instant;
 
 use super::{
+    autosave::Autosaver,
+    script::{ScriptAction, TrainScript},
     train_stream::{self, train_stream},
-    ProcessArgs,
+    HealArgs, ProcessArgs, SaveArgs,
 };
 
 pub enum ProcessMessage {
-    NewSource,
+    /// Started loading from a new source. Picked files are resolved to their path
+    /// where possible, see [`DataSource::resolve`].
+    NewSource {
+        source: DataSource,
+    },
     StartLoading {
         training: bool,
     },
@@ -46,6 +72,8 @@ pub enum ProcessMessage {
         frame: usize,
         total_frames: usize,
     },
+    /// How far along loading, eval or an export is.
+    Progress(Progress),
     /// Loaded a bunch of viewpoints to train on.
     Dataset {
         data: Dataset,
@@ -62,6 +90,12 @@ pub enum ProcessMessage {
         stats: Box<TrainStepStats<Autodiff<Wgpu>>>,
         iter: u32,
         timestamp: Instant,
+        /// The layer on the ground, see [`brush_train::shadow_catcher`].
+        shadow_catcher: Option<Box<ShadowCatcher<Wgpu>>>,
+        /// The learned features of the splats, see [`brush_train::features`].
+        features: Option<Box<FeatureField<Wgpu>>>,
+        /// The learned background, see [`brush_train::environment`].
+        environment: Option<Box<EnvironmentMap<Wgpu>>>,
     },
     /// Some number of training steps are done.
     #[allow(unused)]
@@ -69,12 +103,19 @@ pub enum ProcessMessage {
         stats: Box<RefineStats>,
         iter: u32,
     },
+    /// Distributions of the splat properties, updated every so often while training.
+    SplatStatistics {
+        stats: Box<SplatStatistics>,
+        iter: u32,
+    },
     /// Eval was run successfully with these results.
     #[allow(unused)]
     EvalResult {
         iter: u32,
         eval: EvalStats<Wgpu>,
     },
+    /// A training script asked the viewer to run an action, see [`crate::actions`].
+    ViewerAction(ViewerAction),
 }
 
 #[derive(Debug, Clone)]
@@ -95,11 +136,32 @@ async fn read_at_most<R: AsyncRead + Unpin>(
 async fn load_vfs(source: DataSource) -> anyhow::Result<BrushVfs> {
     // Small hack to peek some bytes: Read them
     // and add them at the start again.
+    // .splat files are headerless, so they can only be told apart by their name.
+    let is_splat = source.name().is_some_and(|name| name.ends_with(".splat"));
     let data = source.into_reader();
     let mut data = BufReader::new(data);
     let peek = read_at_most(&mut data, 64).await?;
     let reader = std::io::Cursor::new(peek.clone()).chain(data);
 
+    if is_splat {
+        let mut path_reader = PathReader::default();
+        path_reader.add(Path::new("input.splat"), reader);
+        return Ok(BrushVfs::from_paths(path_reader));
+    }
+
+    if binary_model::is_binary_model(&peek) {
+        let mut path_reader = PathReader::default();
+        path_reader.add(Path::new("input.splats"), reader);
+        return Ok(BrushVfs::from_paths(path_reader));
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    if brush_dataset::checkpoint::is_checkpoint(&peek) {
+        let mut path_reader = PathReader::default();
+        path_reader.add(Path::new("input.brush"), reader);
+        return Ok(BrushVfs::from_paths(path_reader));
+    }
+
     if peek.as_slice().starts_with(b"ply") {
         let mut path_reader = PathReader::default();
         path_reader.add(Path::new("input.ply"), reader);
@@ -125,11 +187,112 @@ async fn process_loop(
     device: WgpuDevice,
     control_receiver: UnboundedReceiver<ControlMessage>,
 ) {
-    if output.send(ProcessMessage::NewSource).await.is_err() {
+    let source = match args.source.resolve().await {
+        Ok(source) => source,
+        Err(e) => {
+            let _ = output.send(ProcessMessage::Error(e)).await;
+            return;
+        }
+    };
+
+    if output
+        .send(ProcessMessage::NewSource {
+            source: source.clone(),
+        })
+        .await
+        .is_err()
+    {
         return;
     }
 
-    let vfs = load_vfs(args.source).await;
+    // Binary models on disk are memory-mapped instead of read through the VFS.
+    #[cfg(not(target_family = "wasm"))]
+    if let DataSource::Path(path) = &source {
+        if path.extension().is_some_and(|e| e == "splats") {
+            let _ = output
+                .send(ProcessMessage::StartLoading { training: false })
+                .await;
+            let result = match BinaryModel::open(path) {
+                Ok(model) => {
+                    let progress = &args.load_args.progress;
+                    let scale = args.load_args.scale;
+                    view_binary_model(model, &output, progress, &device, scale, 0, 1).await
+                }
+                Err(e) => Err(e),
+            };
+            let _ = output
+                .send(match result {
+                    Ok(()) => ProcessMessage::DoneLoading { training: false },
+                    Err(e) => ProcessMessage::Error(e),
+                })
+                .await;
+            return;
+        }
+    }
+
+    let mut source = source;
+    if let Some(name) = json_name(&source) {
+        let mut json = String::new();
+        if let Err(e) = source.clone().into_reader().read_to_string(&mut json).await {
+            let _ = output.send(ProcessMessage::Error(e.into())).await;
+            return;
+        }
+        match (JsonKind::detect(json.as_bytes()), &source) {
+            (JsonKind::WebDataset, _) => {}
+            // Load the dataset the transforms are part of.
+            (JsonKind::Transforms, DataSource::Path(path)) => {
+                source = DataSource::Path(path.parent().unwrap_or(Path::new(".")).to_path_buf());
+            }
+            (JsonKind::Transforms, _) => {
+                let error = anyhow::anyhow!(
+                    "A transforms.json needs its images, load its folder or a zip instead."
+                );
+                let _ = output.send(ProcessMessage::Error(error)).await;
+                return;
+            }
+            (JsonKind::Session, _) => {
+                let error = anyhow::anyhow!(
+                    "{name} is a viewer session, open it with --session or drop it on the viewer."
+                );
+                let _ = output.send(ProcessMessage::Error(error)).await;
+                return;
+            }
+            (JsonKind::Unknown, _) => {
+                let error = anyhow::anyhow!(
+                    "{name} isn't a WebDataset index or a nerfstudio transforms.json."
+                );
+                let _ = output.send(ProcessMessage::Error(error)).await;
+                return;
+            }
+        }
+        if !matches!(source, DataSource::Path(ref path) if path.is_dir()) {
+            let is_url = matches!(source, DataSource::Url(_));
+            let result = match load_webdataset(is_url, name, &json, &args.load_args).await {
+                Ok(streams) => {
+                    train_process_loop(
+                        output.clone(),
+                        streams,
+                        device,
+                        control_receiver,
+                        args.load_args,
+                        args.init_args,
+                        args.train_config,
+                        args.save_args,
+                        args.script,
+                        args.heal,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                let _ = output.send(ProcessMessage::Error(e)).await;
+            }
+            return;
+        }
+    }
+
+    let vfs = load_vfs(source).await;
 
     let vfs = match vfs {
         Ok(vfs) => vfs,
@@ -142,20 +305,55 @@ async fn process_loop(
     let paths: Vec<_> = vfs.file_names().map(|x| x.to_path_buf()).collect();
     log::info!("Mounted VFS with {} files", paths.len());
 
-    let result = if paths
-        .iter()
-        .all(|p| p.extension().is_some_and(|p| p == "ply"))
-    {
-        view_process_loop(paths, output.clone(), vfs, device).await
+    let result = if paths.iter().all(|p| {
+        p.extension()
+            .is_some_and(|p| p == "ply" || p == "brush" || p == "splats" || p == "splat")
+    }) {
+        view_process_loop(
+            paths,
+            output.clone(),
+            vfs,
+            args.load_args.coordinates,
+            args.load_args.scale,
+            args.load_args.progress.clone(),
+            device,
+        )
+        .await
     } else {
+        let mut captures = vec![vfs];
+        for capture in args.captures {
+            let capture = match capture.resolve().await {
+                Ok(capture) => load_vfs(capture).await,
+                Err(e) => Err(e),
+            };
+            match capture {
+                Ok(vfs) => captures.push(vfs),
+                Err(e) => {
+                    let _ = output.send(ProcessMessage::Error(e)).await;
+                    return;
+                }
+            }
+        }
+
+        let streams = match brush_dataset::load_captures(captures, &args.load_args, &device).await {
+            Ok(streams) => streams,
+            Err(e) => {
+                let _ = output.send(ProcessMessage::Error(e.into())).await;
+                return;
+            }
+        };
+
         train_process_loop(
             output.clone(),
-            vfs,
+            streams,
             device,
             control_receiver,
             args.load_args,
             args.init_args,
             args.train_config,
+            args.save_args,
+            args.script,
+            args.heal,
         )
         .await
     };
@@ -165,10 +363,115 @@ async fn process_loop(
     }
 }
 
+// The index of a WebDataset, when the source is a json file, see
+// [`brush_dataset::WebDatasetIndex`].
+fn json_name(source: &DataSource) -> Option<String> {
+    let name = match source {
+        DataSource::Url(url) => url.clone(),
+        DataSource::Path(path) => path.to_string_lossy().into_owned(),
+        DataSource::PickFile | DataSource::PickDirectory | DataSource::Dropped { .. } => {
+            return None
+        }
+    };
+    name.ends_with(".json").then_some(name)
+}
+
+async fn load_webdataset(
+    is_url: bool,
+    index_name: String,
+    index: &str,
+    load_args: &LoadDatasetArgs,
+) -> anyhow::Result<(
+    DataStream<SplatMessage<Autodiff<Wgpu>>, DatasetError>,
+    DataStream<Dataset, DatasetError>,
+)> {
+    let index: WebDatasetIndex = serde_json::from_str(index).context("Invalid WebDataset index")?;
+    log::info!(
+        "Streaming WebDataset with {} shards, {} samples",
+        index.shards.len(),
+        index
+            .num_samples
+            .map_or("unknown".to_owned(), |n| n.to_string())
+    );
+
+    // Shards are only fetched once the previous shard is read.
+    let shards = tokio_stream::iter(index.shard_urls(&index_name)).map(move |shard| {
+        let source = if is_url {
+            DataSource::Url(shard)
+        } else {
+            DataSource::Path(shard.into())
+        };
+        Ok::<_, anyhow::Error>(Box::pin(source.into_reader()))
+    });
+
+    // WebDatasets don't have initial splats.
+    let splats = Box::pin(tokio_stream::empty::<
+        Result<SplatMessage<Autodiff<Wgpu>>, DatasetError>,
+    >());
+    let num_samples = index.num_samples;
+    Ok((
+        splats,
+        brush_dataset::load_webdataset(shards, num_samples, load_args),
+    ))
+}
+
+// Scale viewed splats uniformly, see `LoadDatasetArgs::scale`.
+fn scale_splats(splats: Splats<Wgpu>, scale: Option<f32>) -> Splats<Wgpu> {
+    let Some(scale) = scale else {
+        return splats;
+    };
+    Splats::from_tensor_data(
+        splats.means.val() * scale,
+        splats.rotation.val(),
+        splats.log_scales.val() + scale.ln(),
+        splats.sh_coeffs.val(),
+        splats.raw_opacity.val(),
+    )
+}
+
+// Show the splats of a binary model as they upload, see [`brush_dataset::binary_model`].
+async fn view_binary_model(
+    model: BinaryModel,
+    output: &Sender<ProcessMessage>,
+    progress: &ProgressSender,
+    device: &WgpuDevice,
+    scale: Option<f32>,
+    frame: usize,
+    total_frames: usize,
+) -> anyhow::Result<()> {
+    log::info!("Loading binary model with {} splats", model.num_splats());
+    let stream = binary_model::load_binary_model(model, device.clone());
+    let mut stream = std::pin::pin!(stream);
+    while let Some(message) = stream.next().await {
+        let message = message?;
+        progress.report(
+            "Loading splats",
+            message.splats.num_splats(),
+            message.meta.total_splats,
+        );
+        if output
+            .send(ProcessMessage::ViewSplats {
+                up_axis: message.meta.up_axis,
+                splats: Box::new(scale_splats(message.splats, scale)),
+                frame,
+                total_frames,
+            })
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
 async fn view_process_loop(
     paths: Vec<std::path::PathBuf>,
     output: Sender<ProcessMessage>,
     vfs: BrushVfs,
+    coordinates: CoordinateConvention,
+    scale: Option<f32>,
+    progress: ProgressSender,
     device: WgpuDevice,
 ) -> Result<(), anyhow::Error> {
     let mut vfs = vfs;
@@ -184,10 +487,58 @@ async fn view_process_loop(
             return Ok(());
         }
 
+        if path.extension().is_some_and(|e| e == "splats") {
+            let mut data = vec![];
+            vfs.open_path(path).await?.read_to_end(&mut data).await?;
+            let model = BinaryModel::from_bytes(data)?;
+            view_binary_model(model, &output, &progress, &device, scale, i, paths.len()).await?;
+            continue;
+        }
+
+        if path.extension().is_some_and(|e| e == "splat") {
+            let mut data = vec![];
+            vfs.open_path(path).await?.read_to_end(&mut data).await?;
+            let splats = splat_import::load_splat_file(&data, coordinates, &device)?;
+            if output
+                .send(ProcessMessage::ViewSplats {
+                    up_axis: Vec3::Y,
+                    splats: Box::new(scale_splats(splats, scale)),
+                    frame: i,
+                    total_frames: paths.len(),
+                })
+                .await
+                .is_err()
+            {
+                return Ok(());
+            }
+            continue;
+        }
+
+        #[cfg(not(target_family = "wasm"))]
+        if path.extension().is_some_and(|e| e == "brush") {
+            let mut data = vec![];
+            vfs.open_path(path).await?.read_to_end(&mut data).await?;
+            let checkpoint = brush_dataset::checkpoint::load_checkpoint(&data, &device)?;
+            if output
+                .send(ProcessMessage::ViewSplats {
+                    up_axis: Vec3::Y,
+                    splats: Box::new(scale_splats(checkpoint.splats, scale)),
+                    frame: i,
+                    total_frames: paths.len(),
+                })
+                .await
+                .is_err()
+            {
+                return Ok(());
+            }
+            continue;
+        }
+
         let sub_sample = None; // Subsampling a trained ply doesn't really make sense.
-        let splat_stream = splat_import::load_splat_from_ply(
+        let splat_stream = splat_import::load_splat_from_ply_with_convention(
             vfs.open_path(path).await?,
             sub_sample,
+            coordinates,
             device.clone(),
         );
 
@@ -195,6 +546,11 @@ async fn view_process_loop(
 
         while let Some(message) = splat_stream.next().await {
             let message = message?;
+            progress.report(
+                "Loading splats",
+                message.splats.num_splats(),
+                message.meta.total_splats,
+            );
 
             // If there's multiple ply files in a zip, don't support animated plys, that would
             // get rather mind bending.
@@ -207,7 +563,7 @@ async fn view_process_loop(
             if output
                 .send(ProcessMessage::ViewSplats {
                     up_axis: message.meta.up_axis,
-                    splats: Box::new(message.splats),
+                    splats: Box::new(scale_splats(message.splats, scale)),
                     frame,
                     total_frames,
                 })
@@ -225,28 +581,167 @@ async fn view_process_loop(
     Ok(())
 }
 
+// VGG16 weights in the torchvision layout, for the perceptual loss.
+const PERCEPTUAL_WEIGHTS_URL: &str =
+    "https://huggingface.co/timm/vgg16.tv_in1k/resolve/main/model.safetensors";
+
+// The perceptual loss with the weights at `path`, or the downloaded weights, which are kept
+// in the config folder so they're only downloaded once.
+async fn load_perceptual_loss(
+    path: Option<&Path>,
+    device: &WgpuDevice,
+) -> anyhow::Result<PerceptualLoss<Autodiff<Wgpu>>> {
+    if let Some(path) = path {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read perceptual loss weights {path:?}"))?;
+        return PerceptualLoss::from_safetensors(&data, device);
+    }
+
+    let cached = crate::session::config_dir().map(|dir| dir.join("vgg16.safetensors"));
+    if let Some(data) = cached.as_ref().and_then(|path| std::fs::read(path).ok()) {
+        match PerceptualLoss::from_safetensors(&data, device) {
+            Ok(loss) => return Ok(loss),
+            Err(e) => log::warn!("Downloading the perceptual loss weights again: {e:#}"),
+        }
+    }
+
+    log::info!("Downloading perceptual loss weights");
+    let data = reqwest::get(PERCEPTUAL_WEIGHTS_URL)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let loss = PerceptualLoss::from_safetensors(&data, device)?;
+
+    if let Some(path) = cached {
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| rrfd::write_atomic(&path, &data, false));
+        if let Err(e) = written {
+            log::warn!("Failed to cache the perceptual loss weights in {path:?}: {e}");
+        }
+    }
+    Ok(loss)
+}
+
+type Resumed = (Splats<Autodiff<Wgpu>>, u32, Option<TrainConfig>);
+
+// The splats, step and config of the checkpoint to resume training from, if any.
+#[cfg(not(target_family = "wasm"))]
+fn read_resume_checkpoint(
+    init_args: &LoadInitArgs,
+    device: &WgpuDevice,
+) -> anyhow::Result<Option<Resumed>> {
+    let Some(path) = &init_args.resume else {
+        return Ok(None);
+    };
+    let data =
+        std::fs::read(path).with_context(|| format!("Failed to read checkpoint {path:?}"))?;
+    let checkpoint = brush_dataset::checkpoint::load_checkpoint(&data, device)?;
+    log::info!(
+        "Resuming from step {} with {} splats",
+        checkpoint.iter,
+        checkpoint.splats.num_splats()
+    );
+    Ok(Some((
+        checkpoint.splats,
+        checkpoint.iter,
+        checkpoint.config,
+    )))
+}
+
+#[cfg(target_family = "wasm")]
+fn read_resume_checkpoint(
+    init_args: &LoadInitArgs,
+    _device: &WgpuDevice,
+) -> anyhow::Result<Option<Resumed>> {
+    anyhow::ensure!(
+        init_args.resume.is_none(),
+        "Resuming from a checkpoint isn't supported on the web."
+    );
+    Ok(None)
+}
+
+// The splats of the viewer, as parameters to train.
+fn trainable(splats: Splats<Wgpu>) -> Splats<Autodiff<Wgpu>> {
+    Splats::from_tensor_data(
+        Tensor::from_inner(splats.means.val()),
+        Tensor::from_inner(splats.rotation.val()),
+        Tensor::from_inner(splats.log_scales.val()),
+        Tensor::from_inner(splats.sh_coeffs.val()),
+        Tensor::from_inner(splats.raw_opacity.val()),
+    )
+}
+
+// Number of training views to load before training starts, the rest of the views
+// keep loading in the background.
+const MIN_TRAIN_VIEWS: usize = 8;
+
+#[allow(clippy::too_many_arguments)]
 async fn train_process_loop(
     output: Sender<ProcessMessage>,
-    vfs: BrushVfs,
+    (mut splat_stream, mut data_stream): (
+        DataStream<SplatMessage<Autodiff<Wgpu>>, DatasetError>,
+        DataStream<Dataset, DatasetError>,
+    ),
     device: WgpuDevice,
     control_receiver: UnboundedReceiver<ControlMessage>,
     load_data_args: LoadDatasetArgs,
     load_init_args: LoadInitArgs,
-    train_config: TrainConfig,
+    mut train_config: TrainConfig,
+    save_args: SaveArgs,
+    script: Option<String>,
+    heal: Option<HealArgs>,
 ) -> Result<(), anyhow::Error> {
     let _ = output
         .send(ProcessMessage::StartLoading { training: true })
         .await;
 
+    // Resume from a checkpoint, with the config and step it was saved at.
+    let (resumed, start_iter) = match read_resume_checkpoint(&load_init_args, &device)? {
+        Some((splats, iter, config)) => {
+            // A region to refine is chosen for this run, not saved with the checkpoint. Frozen
+            // parameters chosen for this run replace those of the checkpoint.
+            if let Some(config) = config {
+                let region = train_config.refine_region;
+                let freeze = std::mem::take(&mut train_config.freeze);
+                train_config = config;
+                train_config.refine_region = region;
+                if !freeze.is_empty() {
+                    train_config.freeze = freeze;
+                }
+            }
+            (Some(splats), iter)
+        }
+        None => (None, 0),
+    };
+
+    // The top level of a training script can change the settings before training starts.
+    let script = script
+        .map(|source| TrainScript::new(&source, &train_config))
+        .transpose()?;
+    if let Some(config) = script
+        .as_ref()
+        .map(|s| s.take_config())
+        .transpose()?
+        .flatten()
+    {
+        train_config = config;
+    }
+
+    if let Some(seed) = load_data_args.seed {
+        train_config.seed = seed;
+    }
+
     <Autodiff<Wgpu> as Backend>::seed(train_config.seed);
     let mut rng = rand::rngs::StdRng::from_seed([train_config.seed as u8; 32]);
+    let mut environment_rays = EnvironmentRays::default();
 
     // Load initial splats if included
     let mut initial_splats = None;
 
     let mut dataset = Dataset::empty();
-    let (mut splat_stream, mut data_stream) =
-        brush_dataset::load_dataset(vfs.clone(), &load_data_args, &device).await?;
 
     // Read initial splats if any.
     while let Some(message) = splat_stream.next().await {
@@ -263,22 +758,50 @@ async fn train_process_loop(
         initial_splats = Some(message.splats);
     }
 
-    // Read dataset stream.
-    while let Some(d) = data_stream.next().await {
+    // Read the dataset stream in the background, so training can start before all views are loaded.
+    let (dataset_sender, mut dataset_receiver) = unbounded_channel();
+    brush_tasks::spawn(async move {
+        while let Some(d) = data_stream.next().await {
+            if dataset_sender.send(d).is_err() {
+                break;
+            }
+        }
+    });
+
+    // Wait for the first few views.
+    let mut loading = true;
+    loop {
+        let Some(d) = dataset_receiver.recv().await else {
+            loading = false;
+            break;
+        };
         dataset = d?;
         let _ = output
             .send(ProcessMessage::Dataset {
                 data: dataset.clone(),
             })
             .await;
+
+        // With a fixed seed, wait for all views, as the data order otherwise
+        // depends on how fast views load.
+        if load_data_args.seed.is_none() && dataset.train.views.len() >= MIN_TRAIN_VIEWS {
+            break;
+        }
     }
 
-    let _ = output
-        .send(ProcessMessage::DoneLoading { training: true })
-        .await;
+    if !loading {
+        let _ = output
+            .send(ProcessMessage::DoneLoading { training: true })
+            .await;
+    }
 
-    let splats = if let Some(splats) = initial_splats {
+    let splats = if let Some(splats) = resumed {
         splats
+    } else if let Some(heal) = &heal {
+        // Heal the model of the viewer as it's shown, with its edits.
+        trainable(heal.edits.apply(&heal.splats).await)
+    } else if let Some(splats) = initial_splats {
+        splats.with_sh_degree(load_init_args.sh_degree)
     } else {
         // By default, spawn the splats in bounds.
         let bounds = dataset.train.bounds();
@@ -291,17 +814,57 @@ async fn train_process_loop(
 
         let config = RandomSplatsConfig::new();
         Splats::from_random_config(&config, adjusted_bounds, &mut rng, &device)
+            .with_sh_degree(load_init_args.sh_degree)
     };
 
-    let splats = splats.with_sh_degree(load_init_args.sh_degree);
+    // Fill the refined region with new splats to heal, unless resuming a run that already did.
+    let splats = match train_config.refine_region {
+        Some(region) if start_iter == 0 => {
+            seed_region(splats, region, train_config.heal_seeds, &mut rng)
+        }
+        _ => splats,
+    };
+
+    // Training goes on without the perceptual loss when its weights aren't available, eg.
+    // when offline.
+    let perceptual = if train_config.perceptual_weight > 0.0 {
+        load_perceptual_loss(train_config.perceptual_weights.as_deref(), &device)
+            .await
+            .inspect_err(|e| log::warn!("Training without the perceptual loss: {e:#}"))
+            .ok()
+    } else {
+        None
+    };
 
     let mut control_receiver = control_receiver;
 
-    let eval_scene = dataset.eval.clone();
-    let stream = train_stream(dataset, splats, train_config.clone(), device.clone());
+    let mut eval_scene = dataset.eval.clone();
+    let mut autosaver = Autosaver::new(save_args, train_config.clone(), &dataset);
+    let mut view_positions: Vec<_> = dataset
+        .train
+        .views
+        .iter()
+        .map(|v| v.camera.position)
+        .collect();
+
+    let (train_dataset_sender, train_dataset_receiver) = unbounded_channel();
+    let (train_config_sender, train_config_receiver) = unbounded_channel();
+    let stream = train_stream(
+        dataset,
+        train_dataset_receiver,
+        train_config_receiver,
+        splats,
+        start_iter,
+        perceptual,
+        train_config.clone(),
+        device.clone(),
+    );
     let mut stream = std::pin::pin!(stream);
 
     let mut train_paused = false;
+    let mut script = script;
+    // The latest splats and their step, for the exports of a training script.
+    let mut latest: Option<(u32, Splats<Wgpu>)> = None;
 
     loop {
         let control = if train_paused {
@@ -318,6 +881,38 @@ async fn train_process_loop(
             }
         }
 
+        // Pass on any views that finished loading.
+        while loading {
+            match dataset_receiver.try_recv() {
+                Ok(d) => {
+                    let dataset = d?;
+                    eval_scene = dataset.eval.clone();
+                    autosaver.set_dataset(&dataset);
+                    view_positions = dataset
+                        .train
+                        .views
+                        .iter()
+                        .map(|v| v.camera.position)
+                        .collect();
+                    let _ = train_dataset_sender.send(dataset.clone());
+                    if output
+                        .send(ProcessMessage::Dataset { data: dataset })
+                        .await
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    loading = false;
+                    let _ = output
+                        .send(ProcessMessage::DoneLoading { training: true })
+                        .await;
+                }
+            }
+        }
+
         let msg = stream.next().await;
 
         let Some(msg) = msg else {
@@ -333,18 +928,57 @@ async fn train_process_loop(
                 stats,
                 iter,
                 timestamp,
+                decomposition,
+                shadow_catcher,
+                features,
+                environment,
             } => {
+                if let Some(script) = script.as_mut().filter(|s| s.wants_steps()) {
+                    let loss = stats.loss.clone().into_scalar_async().await.elem::<f32>();
+                    script.on_step(iter, loss)?;
+                }
+                if script.is_some() {
+                    latest = Some((iter, *splats.clone()));
+                }
+
                 if iter % train_config.eval_every == 0 {
                     if let Some(eval_scene) = eval_scene.as_ref() {
                         let eval = brush_train::eval::eval_stats(
                             *splats.clone(),
                             eval_scene,
                             None,
+                            train_config.white_background,
+                            environment
+                                .as_deref()
+                                .map(|env| (env, &mut environment_rays)),
                             &mut rng,
                             &device,
+                            &load_data_args.progress,
                         )
                         .await;
 
+                        if let Err(e) = autosaver
+                            .save_if_best(
+                                iter,
+                                &eval,
+                                *splats.clone(),
+                                decomposition.as_deref(),
+                                shadow_catcher.as_deref(),
+                                features.as_deref(),
+                                &view_positions,
+                            )
+                            .await
+                        {
+                            log::error!("Failed to save best model: {e}");
+                        }
+
+                        if let Some(script) = script.as_mut() {
+                            let n = eval.samples.len().max(1) as f32;
+                            let psnr = eval.samples.iter().map(|s| s.psnr).sum::<f32>() / n;
+                            let ssim = eval.samples.iter().map(|s| s.ssim).sum::<f32>() / n;
+                            script.on_eval(iter, psnr, ssim)?;
+                        }
+
                         if output
                             .send(ProcessMessage::EvalResult { iter, eval })
                             .await
@@ -355,24 +989,66 @@ async fn train_process_loop(
                     }
                 }
 
+                if autosaver.should_save(iter) {
+                    if let Err(e) = autosaver.save_step(iter, *splats.clone()).await {
+                        log::error!("Failed to save checkpoint: {e}");
+                    }
+                }
+
                 // How frequently to update the UI after a training step.
                 const UPDATE_EVERY: u32 = 5;
 
-                if iter % UPDATE_EVERY == 0
+                // Splats are only added and removed until refinement stops, report how far
+                // along that is. A heal run reports how far along it is instead.
+                let refine_until = heal.as_ref().map_or(train_config.refine_stop_iter, |h| {
+                    h.steps.min(train_config.refine_stop_iter)
+                });
+                if iter % UPDATE_EVERY == 0 && iter <= refine_until {
+                    let stage = if heal.is_some() {
+                        "Healing"
+                    } else {
+                        "Refining splats"
+                    };
+                    load_data_args
+                        .progress
+                        .report(stage, iter as usize, refine_until as usize);
+                }
+
+                // A heal run stops after its steps, with the last splats sent to the viewer.
+                let healed = heal.as_ref().is_some_and(|heal| iter >= heal.steps);
+
+                if (iter % UPDATE_EVERY == 0 || healed)
                     && output
                         .send(ProcessMessage::TrainStep {
                             splats,
                             stats,
                             iter,
                             timestamp,
+                            shadow_catcher,
+                            features,
+                            environment,
                         })
                         .await
                         .is_err()
                 {
                     break;
                 }
+
+                if healed {
+                    log::info!("Healed the region in {iter} steps");
+                    break;
+                }
             }
             train_stream::TrainMessage::RefineStep { stats, iter } => {
+                if let Some(script) = script.as_mut() {
+                    let num_splats = latest.as_ref().map_or(0, |(_, s)| s.num_splats());
+                    script.on_refine(
+                        iter,
+                        num_splats,
+                        stats.num_split + stats.num_cloned,
+                        stats.num_transparent_pruned + stats.num_scale_pruned,
+                    )?;
+                }
                 if output
                     .send(ProcessMessage::RefineStep { stats, iter })
                     .await
@@ -381,15 +1057,110 @@ async fn train_process_loop(
                     break;
                 }
             }
+            train_stream::TrainMessage::Statistics { stats, iter } => {
+                if output
+                    .send(ProcessMessage::SplatStatistics { stats, iter })
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+
+        if let Some(script) = &script {
+            let stop = apply_script(
+                script,
+                &output,
+                &mut train_config,
+                &train_config_sender,
+                &mut autosaver,
+                latest.as_ref(),
+                &view_positions,
+            )
+            .await?;
+            if stop {
+                log::info!("Training stopped by the training script");
+                break;
+            }
         }
     }
 
     Ok(())
 }
 
+// Apply what a training script asked for in its callbacks. Returns whether to stop training.
+async fn apply_script(
+    script: &TrainScript,
+    output: &Sender<ProcessMessage>,
+    train_config: &mut TrainConfig,
+    config_sender: &UnboundedSender<TrainConfig>,
+    autosaver: &mut Autosaver,
+    latest: Option<&(u32, Splats<Wgpu>)>,
+    view_positions: &[Vec3],
+) -> anyhow::Result<bool> {
+    if let Some(config) = script.take_config()? {
+        *train_config = config.clone();
+        let _ = config_sender.send(config);
+    }
+
+    let mut stop = false;
+    for action in script.take_actions() {
+        match action {
+            ScriptAction::Export(path) => {
+                let Some((iter, splats)) = latest else {
+                    log::warn!("Can't export {path:?} before the first training step");
+                    continue;
+                };
+                if let Err(e) = autosaver
+                    .export(&path, *iter, splats.clone(), view_positions)
+                    .await
+                {
+                    log::error!("Failed to export {path:?}: {e}");
+                }
+            }
+            ScriptAction::Stop => stop = true,
+            ScriptAction::Viewer(action) => {
+                let _ = output.send(ProcessMessage::ViewerAction(action)).await;
+            }
+        }
+    }
+    Ok(stop)
+}
+
 pub struct RunningProcess {
     pub messages: Receiver<ProcessMessage>,
     pub control: UnboundedSender<ControlMessage>,
+    /// Stops the process, see [`RunningProcess::cancel`].
+    pub cancel: CancellationToken,
+    /// Reports progress as [`ProcessMessage::Progress`], eg. for exports of its splats.
+    pub progress: ProgressSender,
+}
+
+impl RunningProcess {
+    /// Stop loading or training. The process stops at its next await point, releasing its
+    /// data and GPU memory, and sends no more messages.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+}
+
+/// Send progress reports to the process messages as [`ProcessMessage::Progress`]. Progress
+/// doesn't keep the messages open, they still end when the process is done.
+pub(crate) fn forward_progress(output: &Sender<ProcessMessage>) -> ProgressSender {
+    let (progress, mut reports) = ProgressSender::channel();
+    let output = output.downgrade();
+    brush_tasks::spawn(async move {
+        while let Some(report) = reports.recv().await {
+            let Some(output) = output.upgrade() else {
+                break;
+            };
+            if output.send(ProcessMessage::Progress(report)).await.is_err() {
+                break;
+            }
+        }
+    });
+    progress
 }
 
 pub fn start_process(args: ProcessArgs, device: WgpuDevice) -> RunningProcess {
@@ -400,13 +1171,27 @@ pub fn start_process(args: ProcessArgs, device: WgpuDevice) -> RunningProcess {
     // create a channel for the train loop.
     let (sender, receiver) = channel(1);
     let (train_sender, train_receiver) = unbounded_channel();
+    let cancel = CancellationToken::new();
+    let progress = forward_progress(&sender);
+
+    let mut args = args;
+    args.load_args.progress = progress.clone();
 
-    tokio_with_wasm::alias::task::spawn(async move {
-        process_loop(sender, args, device, train_receiver).await;
+    let token = cancel.clone();
+    brush_tasks::spawn(async move {
+        let process = process_loop(sender, args, device, train_receiver);
+        if brush_tasks::until_cancelled(&token, process)
+            .await
+            .is_none()
+        {
+            log::info!("Process cancelled");
+        }
     });
 
     RunningProcess {
         messages: receiver,
         control: train_sender,
+        cancel,
+        progress,
     }
 }
@@ -1,11 +1,64 @@
+use std::path::PathBuf;
+
 use crate::data_source::DataSource;
-use brush_dataset::{LoadDatasetArgs, LoadInitArgs};
+use brush_dataset::{edit_layers::EditStack, LoadDatasetArgs, LoadInitArgs};
+use brush_render::gaussian_splats::Splats;
 use brush_train::train::TrainConfig;
+use burn_wgpu::Wgpu;
 
 #[derive(Clone)]
 pub struct ProcessArgs {
     pub source: DataSource,
+    /// Other captures of the same scene to train on together with `source`, see
+    /// [`brush_dataset::load_captures`].
+    pub captures: Vec<DataSource>,
     pub load_args: LoadDatasetArgs,
     pub init_args: LoadInitArgs,
     pub train_config: TrainConfig,
+    pub save_args: SaveArgs,
+    /// Source of a Rhai training script. Its `on_step`, `on_eval` and `on_refine` callbacks
+    /// can change settings, export the splats, stop training or run viewer actions.
+    pub script: Option<String>,
+    /// Heal a region of a model from the viewer, instead of training the initial splats of
+    /// the dataset.
+    pub heal: Option<HealArgs>,
+}
+
+/// A short run that seeds new splats in the refine region of the train config and trains
+/// only them, to fill a hole or repair an artifact of a trained model.
+#[derive(Clone)]
+pub struct HealArgs {
+    /// The model to heal, the edits are applied to it first.
+    pub splats: Splats<Wgpu>,
+    pub edits: EditStack,
+    /// Training stops after this many steps.
+    pub steps: u32,
+}
+
+/// Settings to save the model to disk while training.
+#[derive(Clone, Debug)]
+pub struct SaveArgs {
+    /// Folder to save to. Nothing is saved when this isn't set.
+    pub output_dir: Option<PathBuf>,
+    /// Save a checkpoint every n steps.
+    pub save_every: Option<u32>,
+    /// Number of checkpoints to keep, older checkpoints are removed.
+    pub keep_last: usize,
+    /// Save the model as `best.ply` whenever the eval PSNR improves.
+    pub save_best: bool,
+    /// Flush saved files to the disk before moving on, so they survive a power loss. Files
+    /// are always replaced atomically, see [`rrfd::write_atomic`].
+    pub sync_writes: bool,
+}
+
+impl Default for SaveArgs {
+    fn default() -> Self {
+        Self {
+            output_dir: None,
+            save_every: Some(5000),
+            keep_last: 3,
+            save_best: true,
+            sync_writes: false,
+        }
+    }
 }
@@ -0,0 +1,386 @@
+//! Training scripts, to customize training without changing Brush.
+//!
+//! A script is written in [Rhai](https://rhai.rs) and defines any of these callbacks:
+//!
+//! ```text
+//! fn on_step(iter, loss) { }                      // After every training step.
+//! fn on_eval(iter, psnr, ssim) { }                // After every eval.
+//! fn on_refine(iter, num_splats, added, pruned) { } // After splats were added and pruned.
+//! ```
+//!
+//! The script and its callbacks can call:
+//! - `get(name)` and `set(name, value)` to read and change a [`TrainConfig`] setting by its
+//!   name, eg. `set("ssim_weight", 0.4)`. Changes apply from the next step. Settings that are
+//!   only read when training starts, like whether to train an environment map, don't change
+//!   anything.
+//! - `export(path)` to save the splats as a ply, relative to the output folder if any.
+//! - `stop()` to stop training.
+//! - `run(name)` and `run(name, [args])` to run a viewer action, see [`crate::actions`], eg.
+//!   `run("uncertainty", ["on"])`. These do nothing when training without the viewer.
+//!
+//! The top level of the script runs once before training, and `print` writes to the log.
+//! Viewer actions of the top level are skipped, as the viewer runs the top level of a script
+//! itself, see [`run_viewer_script`].
+//! For example, to lower the SSIM weight halfway and stop after 20k steps:
+//!
+//! ```text
+//! fn on_step(iter, loss) {
+//!     if iter == 15000 { set("ssim_weight", 0.1); }
+//!     if iter == 20000 { export("final.ply"); stop(); }
+//! }
+//! ```
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use brush_train::train::TrainConfig;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FuncArgs, Scope, AST};
+use serde_json::{Map, Value};
+
+use crate::actions::ViewerAction;
+
+// The callbacks of a training script.
+const TRAIN_CALLBACKS: [&str; 3] = ["on_step", "on_eval", "on_refine"];
+
+/// Something a script asked for, besides changing settings.
+pub(crate) enum ScriptAction {
+    Export(PathBuf),
+    Stop,
+    Viewer(ViewerAction),
+}
+
+// Register `run`, passing the viewer actions it parses to `push`.
+fn register_run(engine: &mut Engine, push: impl Fn(ViewerAction) + Clone + Send + Sync + 'static) {
+    let p = push.clone();
+    engine.register_fn("run", move |name: &str| -> Result<(), Box<EvalAltResult>> {
+        p(ViewerAction::parse(name, &[]).map_err(|e| format!("{e:#}"))?);
+        Ok(())
+    });
+    engine.register_fn(
+        "run",
+        move |name: &str, args: Array| -> Result<(), Box<EvalAltResult>> {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            push(ViewerAction::parse(name, &args).map_err(|e| format!("{e:#}"))?);
+            Ok(())
+        },
+    );
+}
+
+/// Run the top level of a script in the viewer. Returns the viewer actions it asked for,
+/// and whether it has callbacks to run while training.
+///
+/// The training functions can be called too, so a training script can be passed as is, but
+/// do nothing: the top level runs again when training starts, see [`TrainScript::new`].
+pub(crate) fn run_viewer_script(source: &str) -> anyhow::Result<(Vec<ViewerAction>, bool)> {
+    let actions = Arc::new(Mutex::new(vec![]));
+    let mut engine = Engine::new();
+    engine.on_print(|text| log::info!("Script: {text}"));
+    let ignored = ScriptState::new(&TrainConfig::new())?;
+    register_train_fns(&mut engine, &Arc::new(Mutex::new(ignored)));
+    let a = actions.clone();
+    register_run(&mut engine, move |action| {
+        a.lock().expect("Script state poisoned").push(action);
+    });
+
+    let ast = engine
+        .compile(source)
+        .map_err(|e| anyhow::anyhow!("Failed to compile script: {e}"))?;
+    engine
+        .run_ast(&ast)
+        .map_err(|e| anyhow::anyhow!("Script failed: {e}"))?;
+
+    let trains = ast
+        .iter_functions()
+        .any(|f| TRAIN_CALLBACKS.contains(&f.name));
+    let actions = std::mem::take(&mut *actions.lock().expect("Script state poisoned"));
+    Ok((actions, trains))
+}
+
+struct ScriptState {
+    config: Map<String, Value>,
+    config_changed: bool,
+    actions: Vec<ScriptAction>,
+}
+
+impl ScriptState {
+    fn new(config: &TrainConfig) -> anyhow::Result<Self> {
+        let config = match serde_json::to_value(config)? {
+            Value::Object(map) => map,
+            _ => anyhow::bail!("Train config isn't an object"),
+        };
+        Ok(Self {
+            config,
+            config_changed: false,
+            actions: vec![],
+        })
+    }
+
+    fn set(&mut self, name: &str, value: &Dynamic) -> Result<(), Box<EvalAltResult>> {
+        let current = self
+            .config
+            .get(name)
+            .ok_or_else(|| format!("Unknown setting {name}"))?;
+        let value = match current {
+            Value::Bool(_) => value.as_bool().ok().map(Value::Bool),
+            Value::Number(n) if n.is_f64() => value
+                .as_float()
+                .ok()
+                .or_else(|| value.as_int().ok().map(|i| i as f64))
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number),
+            Value::Number(_) => value
+                .as_int()
+                .ok()
+                .filter(|&i| i >= 0)
+                .map(|i| Value::Number(i.into())),
+            _ => None,
+        };
+        let value = value.ok_or_else(|| format!("Can't set {name} to {value}"))?;
+        self.config.insert(name.to_owned(), value);
+        self.config_changed = true;
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Result<Dynamic, Box<EvalAltResult>> {
+        let value = match self.config.get(name) {
+            Some(Value::Bool(b)) => Dynamic::from(*b),
+            Some(Value::Number(n)) => match n.as_i64() {
+                Some(i) => Dynamic::from(i),
+                None => Dynamic::from(n.as_f64().unwrap_or_default()),
+            },
+            Some(Value::String(s)) => Dynamic::from(s.clone()),
+            Some(_) => Dynamic::UNIT,
+            None => return Err(format!("Unknown setting {name}").into()),
+        };
+        Ok(value)
+    }
+}
+
+// Register `get`, `set`, `export` and `stop`, acting on `state`.
+fn register_train_fns(engine: &mut Engine, state: &Arc<Mutex<ScriptState>>) {
+    let s = state.clone();
+    engine.register_fn("get", move |name: &str| {
+        s.lock().expect("Script state poisoned").get(name)
+    });
+    let s = state.clone();
+    engine.register_fn("set", move |name: &str, value: Dynamic| {
+        s.lock().expect("Script state poisoned").set(name, &value)
+    });
+    let s = state.clone();
+    engine.register_fn("export", move |path: &str| {
+        s.lock()
+            .expect("Script state poisoned")
+            .actions
+            .push(ScriptAction::Export(PathBuf::from(path)));
+    });
+    let s = state.clone();
+    engine.register_fn("stop", move || {
+        s.lock()
+            .expect("Script state poisoned")
+            .actions
+            .push(ScriptAction::Stop);
+    });
+}
+
+/// A compiled training script, see the [module docs](self).
+pub(crate) struct TrainScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Arc<Mutex<ScriptState>>,
+}
+
+impl TrainScript {
+    /// Compile a script and run its top level, starting from the given settings.
+    pub(crate) fn new(source: &str, config: &TrainConfig) -> anyhow::Result<Self> {
+        let state = Arc::new(Mutex::new(ScriptState::new(config)?));
+
+        let mut engine = Engine::new();
+        engine.on_print(|text| log::info!("Script: {text}"));
+        register_train_fns(&mut engine, &state);
+        let s = state.clone();
+        register_run(&mut engine, move |action| {
+            s.lock()
+                .expect("Script state poisoned")
+                .actions
+                .push(ScriptAction::Viewer(action));
+        });
+
+        let ast = engine
+            .compile(source)
+            .map_err(|e| anyhow::anyhow!("Failed to compile script: {e}"))?;
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| anyhow::anyhow!("Script failed: {e}"))?;
+        state
+            .lock()
+            .expect("Script state poisoned")
+            .actions
+            .retain(|action| !matches!(action, ScriptAction::Viewer(_)));
+
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            state,
+        })
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name)
+    }
+
+    fn call(&mut self, name: &str, args: impl FuncArgs) -> anyhow::Result<()> {
+        if !self.defines(name) {
+            return Ok(());
+        }
+        self.engine
+            .call_fn::<Dynamic>(&mut self.scope, &self.ast, name, args)
+            .map_err(|e| anyhow::anyhow!("Script {name} failed: {e}"))?;
+        Ok(())
+    }
+
+    /// Whether the script has an `on_step` callback. The loss of every step is only read back
+    /// when it does, as that waits for the GPU.
+    pub(crate) fn wants_steps(&self) -> bool {
+        self.defines("on_step")
+    }
+
+    pub(crate) fn on_step(&mut self, iter: u32, loss: f32) -> anyhow::Result<()> {
+        self.call("on_step", (iter as i64, loss as f64))
+    }
+
+    pub(crate) fn on_eval(&mut self, iter: u32, psnr: f32, ssim: f32) -> anyhow::Result<()> {
+        self.call("on_eval", (iter as i64, psnr as f64, ssim as f64))
+    }
+
+    pub(crate) fn on_refine(
+        &mut self,
+        iter: u32,
+        num_splats: usize,
+        added: usize,
+        pruned: usize,
+    ) -> anyhow::Result<()> {
+        self.call(
+            "on_refine",
+            (iter as i64, num_splats as i64, added as i64, pruned as i64),
+        )
+    }
+
+    /// The settings as changed by the script, if it changed any since the last call.
+    pub(crate) fn take_config(&self) -> anyhow::Result<Option<TrainConfig>> {
+        let mut state = self.state.lock().expect("Script state poisoned");
+        if !std::mem::take(&mut state.config_changed) {
+            return Ok(None);
+        }
+        let config = serde_json::from_value(Value::Object(state.config.clone()))
+            .context("Script made an invalid train config")?;
+        Ok(Some(config))
+    }
+
+    pub(crate) fn take_actions(&self) -> Vec<ScriptAction> {
+        std::mem::take(&mut self.state.lock().expect("Script state poisoned").actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_train::train::TrainConfig;
+
+    use super::{run_viewer_script, ScriptAction, TrainScript};
+    use crate::actions::{ExportFormat, ViewSetting, ViewerAction};
+
+    #[test]
+    fn script_changes_settings_and_stops() {
+        let source = r#"
+            set("perceptual_weight", 0.5);
+            fn on_step(iter, loss) {
+                if iter == 10 { set("eval_every", get("eval_every") * 2); stop(); }
+            }
+        "#;
+        let config = TrainConfig::new();
+        let mut script = TrainScript::new(source, &config).expect("Script should compile");
+        let changed = script
+            .take_config()
+            .expect("Valid config")
+            .expect("Top level changed a setting");
+        assert_eq!(changed.perceptual_weight, 0.5);
+
+        script.on_step(9, 0.1).expect("Callback failed");
+        assert!(script.take_actions().is_empty());
+
+        script.on_step(10, 0.1).expect("Callback failed");
+        let changed = script
+            .take_config()
+            .expect("Valid config")
+            .expect("Callback changed a setting");
+        assert_eq!(changed.eval_every, config.eval_every * 2);
+        assert!(matches!(
+            script.take_actions().as_slice(),
+            [ScriptAction::Stop]
+        ));
+
+        // Unknown settings are an error of the callback.
+        let mut script = TrainScript::new(r#"fn on_step(i, l) { set("nope", 1); }"#, &config)
+            .expect("Script should compile");
+        assert!(script.on_step(0, 0.0).is_err());
+    }
+
+    #[test]
+    fn scripts_run_viewer_actions() {
+        let source = r#"
+            run("crop", [-1, -1, -1, 1, 1, 1.5]);
+            run("export_binary");
+            fn on_eval(iter, psnr, ssim) { run("uncertainty", ["on"]); }
+        "#;
+        let (actions, trains) = run_viewer_script(source).expect("Script should run");
+        assert!(trains);
+        assert!(matches!(
+            actions.as_slice(),
+            [
+                ViewerAction::Crop(_),
+                ViewerAction::Export(ExportFormat::Binary, None)
+            ]
+        ));
+
+        // Training runs the top level again, without its viewer actions.
+        let mut script =
+            TrainScript::new(source, &TrainConfig::new()).expect("Script should compile");
+        assert!(script.take_actions().is_empty());
+        script.on_eval(100, 30.0, 0.9).expect("Callback failed");
+        assert!(matches!(
+            script.take_actions().as_slice(),
+            [ScriptAction::Viewer(ViewerAction::Set(
+                ViewSetting::Uncertainty,
+                Some(true)
+            ))]
+        ));
+
+        assert!(run_viewer_script(r#"run("nope");"#).is_err());
+    }
+
+    #[test]
+    fn viewer_runs_training_scripts() {
+        // The example of the module docs, with a setting changed before training.
+        let source = r#"
+            set("ssim_weight", 0.4);
+            print(get("ssim_weight"));
+            fn on_step(iter, loss) {
+                if iter == 15000 { set("ssim_weight", 0.1); }
+                if iter == 20000 { export("final.ply"); stop(); }
+            }
+        "#;
+        let (actions, trains) = run_viewer_script(source).expect("Script should run");
+        assert!(actions.is_empty());
+        assert!(trains);
+
+        // Training functions at the top level don't do anything in the viewer.
+        let (actions, trains) =
+            run_viewer_script(r#"export("a.ply"); stop();"#).expect("Script should run");
+        assert!(actions.is_empty());
+        assert!(!trains);
+    }
+}
@@ -2,10 +2,19 @@
 use async_fn_stream::try_fn_stream;
 
 use brush_dataset::{scene_loader::SceneLoader, Dataset};
-use brush_render::gaussian_splats::Splats;
-use brush_train::train::{RefineStats, SplatTrainer, TrainConfig, TrainStepStats};
+use brush_render::{gaussian_splats::Splats, statistics::SplatStatistics};
+use brush_train::{
+    environment::EnvironmentMap,
+    features::FeatureField,
+    lighting::Decomposition,
+    perceptual::PerceptualLoss,
+    scene::Scene,
+    shadow_catcher::ShadowCatcher,
+    train::{RefineStats, SplatTrainer, TrainConfig, TrainStepStats},
+};
 use burn::{backend::Autodiff, module::AutodiffModule};
 use burn_wgpu::{Wgpu, WgpuDevice};
+use tokio::sync::mpsc::UnboundedReceiver;
 use tokio_stream::Stream;
 use tracing::Instrument;
 use web_time::Instant;
@@ -16,53 +25,163 @@ pub enum TrainMessage {
         stats: Box<TrainStepStats<Autodiff<Wgpu>>>,
         iter: u32,
         timestamp: Instant,
+        /// The albedo and lighting, when training with [`TrainConfig::relighting`]. The
+        /// `splats` are then lit by this lighting.
+        decomposition: Option<Box<Decomposition<Wgpu>>>,
+        /// The layer on the ground, when training with [`TrainConfig::shadow_catcher`]. It
+        /// isn't part of the `splats`.
+        shadow_catcher: Option<Box<ShadowCatcher<Wgpu>>>,
+        /// The features of the splats, when training with [`TrainConfig::feature_splatting`],
+        /// in the order of the `splats`.
+        features: Option<Box<FeatureField<Wgpu>>>,
+        /// The background, when training with [`TrainConfig::background_model`]. Opaque views
+        /// are the `splats` composited over it.
+        environment: Option<Box<EnvironmentMap<Wgpu>>>,
     },
     RefineStep {
         stats: Box<RefineStats>,
         iter: u32,
     },
+    Statistics {
+        stats: Box<SplatStatistics>,
+        iter: u32,
+    },
+}
+
+// The views to sample from, only those that see the refined region if any.
+fn sampled_views(scene: &Scene, config: &TrainConfig) -> Scene {
+    match &config.refine_region {
+        Some(region) => scene.seeing(region),
+        None => scene.clone(),
+    }
 }
 
 // False positive: need to pass in TrainConfig by value to keep lifetimes sane.
 #[allow(clippy::needless_pass_by_value)]
 pub(crate) fn train_stream(
     dataset: Dataset,
+    mut dataset_updates: UnboundedReceiver<Dataset>,
+    mut config_updates: UnboundedReceiver<TrainConfig>,
     initial_splats: Splats<Autodiff<Wgpu>>,
+    start_iter: u32,
+    perceptual: Option<PerceptualLoss<Autodiff<Wgpu>>>,
     config: TrainConfig,
     device: WgpuDevice,
 ) -> impl Stream<Item = anyhow::Result<TrainMessage>> {
     try_fn_stream(|emitter| async move {
         let mut splats = initial_splats;
+        let mut config = config;
 
-        let train_scene = dataset.train.clone();
+        let mut train_scene = dataset.train.without_blurriest(config.drop_blurry_fraction);
 
         // TODO: Not really supported atm.
         let batch_size = 1;
 
-        let mut dataloader = SceneLoader::new(&train_scene, batch_size, config.seed, &device);
+        // The initial points, to find the extent of scenes where the cameras are close
+        // together.
+        let points: Vec<_> = splats
+            .means
+            .val()
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+            .unwrap_or_default()
+            .chunks_exact(3)
+            .map(|p| glam::vec3(p[0], p[1], p[2]))
+            .collect();
+
+        // The extent of the whole scene is kept when training a region, so the learning rates
+        // match the original training.
+        let mut dataloader = SceneLoader::new(
+            &sampled_views(&train_scene, &config),
+            train_scene.extent(&points),
+            batch_size,
+            config.seed,
+            config.view_sampling.clone(),
+            config.gpu_image_budget_mb,
+            &device,
+        );
         let mut trainer = SplatTrainer::new(&splats, &config, &device);
+        trainer.resume_at(start_iter);
+        trainer.prepare_scene(&train_scene);
+        trainer.fit_shadow_catcher(&train_scene, &points, dataset.ground);
+        if let Some(perceptual) = perceptual {
+            trainer.set_perceptual_loss(perceptual);
+        }
 
-        let mut iter = 0;
+        let mut iter = start_iter;
+        let mut restart_loader = false;
 
         #[allow(clippy::infinite_loop)]
         loop {
-            let batch = dataloader.next_batch().await;
+            // Views can still be loading while training, pick up the latest dataset if any.
+            let mut new_scene = None;
+            while let Ok(dataset) = dataset_updates.try_recv() {
+                new_scene = Some((
+                    dataset.train.without_blurriest(config.drop_blurry_fraction),
+                    dataset.ground,
+                ));
+            }
+            if let Some((scene, ground)) = new_scene {
+                trainer.prepare_scene(&scene);
+                trainer.fit_shadow_catcher(&scene, &points, ground);
+                train_scene = scene;
+                restart_loader = true;
+            }
+
+            // Settings changed while training, eg. by a training script.
+            while let Ok(new_config) = config_updates.try_recv() {
+                restart_loader |= new_config.refine_region != config.refine_region;
+                trainer.set_config(new_config.clone());
+                config = new_config;
+            }
+
+            if std::mem::take(&mut restart_loader) {
+                // Restart the dataloader so the new views and scene extent are used.
+                let seed = config.seed.wrapping_add(iter as u64);
+                dataloader = SceneLoader::new(
+                    &sampled_views(&train_scene, &config),
+                    train_scene.extent(&points),
+                    batch_size,
+                    seed,
+                    config.view_sampling.clone(),
+                    config.gpu_image_budget_mb,
+                    &device,
+                );
+            }
+
+            let batch = dataloader.next_batch(config.resolution_level(iter)).await;
             let extent = batch.scene_extent;
 
             let (new_splats, stats) = trainer
                 .step(iter, batch, splats)
                 .instrument(tracing::info_span!("Train step"))
                 .await;
+            dataloader.report_loss(&stats.gt_views, stats.loss.clone());
+            let screen_radii = stats.auxes[0].radii.clone();
             let (new_splats, refine) = trainer.refine_if_needed(iter, new_splats, extent).await;
             iter += 1;
             splats = new_splats;
 
+            let decomposition = trainer.lighting().map(|lighting| Decomposition {
+                albedo: splats.valid(),
+                lighting: lighting.valid(),
+            });
+            let lit_splats = match &decomposition {
+                Some(d) => d.lighting.shade(&d.albedo),
+                None => splats.valid(),
+            };
+
             emitter
                 .emit(TrainMessage::TrainStep {
-                    splats: Box::new(splats.valid()),
+                    splats: Box::new(lit_splats),
                     stats: Box::new(stats),
                     iter,
                     timestamp: Instant::now(),
+                    decomposition: decomposition.map(Box::new),
+                    shadow_catcher: trainer.shadow_catcher().map(|c| Box::new(c.valid())),
+                    features: trainer.features().map(|f| Box::new(f.valid())),
+                    environment: trainer.environment().map(|e| Box::new(e.valid())),
                 })
                 .await;
 
@@ -73,6 +192,20 @@ pub(crate) fn train_stream(
                         iter,
                     })
                     .await;
+
+                // Update the splat statistics whenever the splats were refined, as that
+                // is when they change the most.
+                let statistics = splats
+                    .statistics()
+                    .await
+                    .with_screen_radii(screen_radii)
+                    .await;
+                emitter
+                    .emit(TrainMessage::Statistics {
+                        stats: Box::new(statistics),
+                        iter,
+                    })
+                    .await;
             }
         }
     })
@@ -0,0 +1,151 @@
+//! Recently opened models and datasets, listed on the start screen of the viewer.
+//!
+//! The list is kept in the config folder, see [`crate::session::config_dir`], together with a
+//! thumbnail of each entry: a quick render of the splats, taken when they're closed.
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Cursor,
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{data_source::DataSource, session::config_dir};
+
+// Older entries are forgotten.
+const MAX_RECENT: usize = 12;
+
+/// Width of the thumbnails, the height follows the aspect ratio of the view.
+pub const THUMBNAIL_WIDTH: u32 = 192;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecentEntry {
+    pub source: DataSource,
+    /// When it was last opened, in seconds since the unix epoch.
+    pub opened: u64,
+}
+
+impl RecentEntry {
+    /// The file name, or the url.
+    pub fn name(&self) -> String {
+        match &self.source {
+            DataSource::Path(path) => path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            DataSource::Url(url) => url.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Where the thumbnail of this entry is cached, if there is a config folder.
+    pub fn thumbnail_path(&self) -> Option<PathBuf> {
+        thumbnail_path(&self.source)
+    }
+}
+
+/// Where the thumbnail of a source is cached, if there is a config folder.
+pub fn thumbnail_path(source: &DataSource) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    match source {
+        DataSource::Path(path) => path.hash(&mut hasher),
+        DataSource::Url(url) => url.hash(&mut hasher),
+        _ => return None,
+    }
+    config_dir().map(|dir| dir.join(format!("thumbnails/{:016x}.png", hasher.finish())))
+}
+
+/// The recently opened sources, most recent first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentFiles {
+    pub entries: Vec<RecentEntry>,
+}
+
+impl RecentFiles {
+    fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("recent.json"))
+    }
+
+    /// The list saved by earlier launches, or an empty list.
+    pub fn load() -> Self {
+        Self::default_path()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                let data = std::fs::read(&path).ok()?;
+                serde_json::from_slice(&data)
+                    .inspect_err(|e| log::warn!("Invalid recent files {path:?}: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = Self::default_path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(self)?;
+        rrfd::write_atomic(&path, &data, false)
+            .with_context(|| format!("Failed to write recent files {path:?}"))
+    }
+
+    /// Move a source to the top of the list. Only files on disk and urls are remembered.
+    pub fn add(&mut self, source: &DataSource) {
+        if !source.is_restorable() {
+            return;
+        }
+        let opened = web_time::SystemTime::now()
+            .duration_since(web_time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        self.entries.retain(|entry| entry.source != *source);
+        self.entries.insert(
+            0,
+            RecentEntry {
+                source: source.clone(),
+                opened,
+            },
+        );
+
+        for forgotten in self.entries.drain(MAX_RECENT.min(self.entries.len())..) {
+            if let Some(path) = forgotten.thumbnail_path() {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+
+        if let Err(e) = self.save() {
+            log::warn!("Failed to save recent files: {e:?}");
+        }
+    }
+
+    /// Forget an entry, eg. a file that doesn't exist anymore.
+    pub fn remove(&mut self, index: usize) {
+        let entry = self.entries.remove(index);
+        if let Some(path) = entry.thumbnail_path() {
+            let _ = std::fs::remove_file(path);
+        }
+        if let Err(e) = self.save() {
+            log::warn!("Failed to save recent files: {e:?}");
+        }
+    }
+
+    /// Cache the thumbnail of a source.
+    pub fn save_thumbnail(source: &DataSource, image: image::RgbImage) -> anyhow::Result<()> {
+        let Some(path) = thumbnail_path(source) else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut png = vec![];
+        image::DynamicImage::ImageRgb8(image)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+        rrfd::write_atomic(&path, &png, false)
+            .with_context(|| format!("Failed to write thumbnail {path:?}"))
+    }
+}
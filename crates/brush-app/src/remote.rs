@@ -0,0 +1,331 @@
+//! Watch a headless trainer from a viewer on another machine.
+//!
+//! The trainer listens on a TCP port, see `brush_app train --serve`. Clients send commands
+//! as single lines:
+//!
+//! - `snapshot <max_splats>`: Replies with a json line with the training step and the
+//!   total number of splats, then the length of a ply as a little endian u64, and the ply
+//!   itself. The ply has the `max_splats` most opaque splats, and is empty before the
+//!   first training step.
+//! - `pause` / `resume`: Pause or resume training. Replies with `ok`.
+//!
+//! There is no authentication, anyone who can connect can download the model and pause
+//! training. The trainer listens on the loopback interface unless another address is given,
+//! use eg. an SSH tunnel to reach it from another machine.
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use async_fn_stream::try_fn_stream;
+use brush_dataset::{splat_export, splat_import};
+use brush_render::gaussian_splats::Splats;
+use burn_wgpu::{Wgpu, WgpuDevice};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc::UnboundedSender, Mutex},
+};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::process_loop::{forward_progress, ControlMessage, ProcessMessage, RunningProcess};
+
+/// Number of splats sent to a viewer when it doesn't ask for a number.
+pub const DEFAULT_SNAPSHOT_SPLATS: usize = 250_000;
+
+/// How often the viewer pulls a new snapshot.
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(2);
+
+// Commands and replies are short, longer lines are from something else than a viewer or
+// trainer.
+const MAX_LINE_LENGTH: u64 = 4096;
+// Largest ply a viewer accepts. Snapshots are downsampled, so they're well below this.
+const MAX_SNAPSHOT_BYTES: u64 = 1 << 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub iter: u32,
+    pub total_splats: usize,
+}
+
+/// The latest splats of a training run, shared with connected viewers.
+#[derive(Clone, Default)]
+pub struct SnapshotState {
+    latest: Arc<Mutex<Option<(u32, Splats<Wgpu>)>>>,
+}
+
+impl SnapshotState {
+    pub async fn update(&self, iter: u32, splats: Splats<Wgpu>) {
+        *self.latest.lock().await = Some((iter, splats));
+    }
+}
+
+/// The address to serve on for a `--serve` argument. Just a port listens on the loopback
+/// interface only.
+pub fn serve_address(arg: &str) -> String {
+    if arg.parse::<u16>().is_ok() {
+        format!("127.0.0.1:{arg}")
+    } else {
+        arg.to_owned()
+    }
+}
+
+// Read a line of at most `MAX_LINE_LENGTH` bytes, or `None` at the end of the stream.
+pub(crate) async fn read_line(reader: &mut (impl AsyncBufRead + Unpin)) -> anyhow::Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader
+        .take(MAX_LINE_LENGTH + 1)
+        .read_line(&mut line)
+        .await?;
+    anyhow::ensure!(
+        read as u64 <= MAX_LINE_LENGTH,
+        "Line longer than {MAX_LINE_LENGTH} bytes"
+    );
+    Ok((read > 0).then_some(line))
+}
+
+// Keep only the most opaque splats, which contribute most to the image.
+fn downsample(splats: Splats<Wgpu>, max_splats: usize) -> Splats<Wgpu> {
+    if splats.num_splats() <= max_splats {
+        return splats;
+    }
+
+    let indices = splats
+        .raw_opacity
+        .val()
+        .argsort_descending(0)
+        .slice([0..max_splats]);
+
+    Splats::from_tensor_data(
+        splats.means.val().select(0, indices.clone()),
+        splats.rotation.val().select(0, indices.clone()),
+        splats.log_scales.val().select(0, indices.clone()),
+        splats.sh_coeffs.val().select(0, indices.clone()),
+        splats.raw_opacity.val().select(0, indices),
+    )
+}
+
+async fn handle_client(
+    stream: TcpStream,
+    state: SnapshotState,
+    control: UnboundedSender<ControlMessage>,
+) -> anyhow::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    while let Some(line) = read_line(&mut reader).await? {
+        let line = line.trim_end();
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("snapshot") => {
+                let max_splats = parts
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(DEFAULT_SNAPSHOT_SPLATS);
+
+                // Before the first training step there are no splats, reply with an empty ply.
+                let latest = state.latest.lock().await.clone();
+                let (header, ply) = match latest {
+                    Some((iter, splats)) => {
+                        let header = SnapshotHeader {
+                            iter,
+                            total_splats: splats.num_splats(),
+                        };
+                        let splats = downsample(splats, max_splats);
+                        (header, splat_export::splat_to_ply(splats).await?)
+                    }
+                    None => (
+                        SnapshotHeader {
+                            iter: 0,
+                            total_splats: 0,
+                        },
+                        vec![],
+                    ),
+                };
+
+                writer
+                    .write_all(format!("{}\n", serde_json::to_string(&header)?).as_bytes())
+                    .await?;
+                writer.write_all(&(ply.len() as u64).to_le_bytes()).await?;
+                writer.write_all(&ply).await?;
+            }
+            Some(command @ ("pause" | "resume")) => {
+                let _ = control.send(ControlMessage::Paused(command == "pause"));
+                writer.write_all(b"ok\n").await?;
+            }
+            _ => anyhow::bail!("Unknown command {line}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Serve snapshots of a training run to viewers, until the listener fails.
+pub async fn serve(
+    addr: &str,
+    state: SnapshotState,
+    control: UnboundedSender<ControlMessage>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {addr}"))?;
+    let local_addr = listener.local_addr()?;
+    log::info!("Serving training snapshots on {local_addr}");
+    if !local_addr.ip().is_loopback() {
+        log::warn!(
+            "Serving on {local_addr} without authentication, anyone who can connect can \
+             download the model and pause training"
+        );
+    }
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        log::info!("Viewer connected from {peer}");
+        let state = state.clone();
+        let control = control.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, state, control).await {
+                log::warn!("Viewer {peer} disconnected: {e}");
+            }
+        });
+    }
+}
+
+/// A snapshot pulled from a remote trainer.
+pub struct RemoteSnapshot {
+    pub header: SnapshotHeader,
+    pub splats: Splats<Wgpu>,
+}
+
+/// Connect to a remote trainer, and pull a snapshot every [`SNAPSHOT_INTERVAL`].
+///
+/// Pause and resume messages are passed on to the trainer.
+pub fn pull_snapshots(
+    addr: String,
+    max_splats: usize,
+    control: tokio::sync::mpsc::UnboundedReceiver<ControlMessage>,
+    device: WgpuDevice,
+) -> impl Stream<Item = anyhow::Result<RemoteSnapshot>> {
+    try_fn_stream(|emitter| async move {
+        let stream = TcpStream::connect(&addr)
+            .await
+            .with_context(|| format!("Failed to connect to trainer at {addr}"))?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let mut control = control;
+
+        loop {
+            while let Ok(ControlMessage::Paused(paused)) = control.try_recv() {
+                let command: &[u8] = if paused { b"pause\n" } else { b"resume\n" };
+                writer.write_all(command).await?;
+                read_line(&mut reader).await?;
+            }
+
+            writer
+                .write_all(format!("snapshot {max_splats}\n").as_bytes())
+                .await?;
+
+            let Some(header) = read_line(&mut reader).await? else {
+                anyhow::bail!("Trainer at {addr} closed the connection");
+            };
+            let header: SnapshotHeader =
+                serde_json::from_str(&header).context("Invalid snapshot header")?;
+
+            let mut len = [0; 8];
+            reader.read_exact(&mut len).await?;
+            let len = u64::from_le_bytes(len);
+            anyhow::ensure!(
+                len <= MAX_SNAPSHOT_BYTES,
+                "Snapshot of {len} bytes is larger than {MAX_SNAPSHOT_BYTES} bytes"
+            );
+            let mut ply = vec![0; len as usize];
+            reader.read_exact(&mut ply).await?;
+
+            if ply.is_empty() {
+                tokio::time::sleep(SNAPSHOT_INTERVAL).await;
+                continue;
+            }
+
+            let mut splat_stream = std::pin::pin!(splat_import::load_splat_from_ply::<_, Wgpu>(
+                std::io::Cursor::new(ply),
+                None,
+                device.clone(),
+            ));
+            let mut splats = None;
+            while let Some(message) = splat_stream.next().await {
+                splats = Some(message?.splats);
+            }
+
+            if let Some(splats) = splats {
+                emitter.emit(RemoteSnapshot { header, splats }).await;
+            }
+
+            tokio::time::sleep(SNAPSHOT_INTERVAL).await;
+        }
+    })
+}
+
+/// Show the splats of a remote trainer in the viewer.
+pub fn start_remote_process(addr: String, device: WgpuDevice) -> RunningProcess {
+    let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    let (control_sender, control_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let cancel = brush_tasks::CancellationToken::new();
+    let progress = forward_progress(&sender);
+
+    let token = cancel.clone();
+    let process = async move {
+        if sender
+            .send(ProcessMessage::StartLoading { training: false })
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let snapshots = pull_snapshots(addr, DEFAULT_SNAPSHOT_SPLATS, control_receiver, device);
+        let mut snapshots = std::pin::pin!(snapshots);
+        let mut first = true;
+
+        while let Some(snapshot) = snapshots.next().await {
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    let _ = sender.send(ProcessMessage::Error(e)).await;
+                    return;
+                }
+            };
+
+            log::info!(
+                "Remote trainer at step {} with {} splats",
+                snapshot.header.iter,
+                snapshot.header.total_splats
+            );
+
+            let msg = ProcessMessage::ViewSplats {
+                up_axis: Vec3::Y,
+                splats: Box::new(snapshot.splats),
+                frame: 0,
+                total_frames: 0,
+            };
+            if sender.send(msg).await.is_err() {
+                return;
+            }
+
+            if first {
+                first = false;
+                let _ = sender
+                    .send(ProcessMessage::DoneLoading { training: false })
+                    .await;
+            }
+        }
+    };
+    tokio::spawn(async move { brush_tasks::until_cancelled(&token, process).await });
+
+    RunningProcess {
+        messages: receiver,
+        control: control_sender,
+        cancel,
+        progress,
+    }
+}
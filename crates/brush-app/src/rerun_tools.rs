@@ -149,7 +149,7 @@ impl VisualizeTools {
                 )?;
                 rec.log_static(
                     path + "/image",
-                    &rerun::Image::from_dynamic_image(view.image.as_ref().clone())?,
+                    &rerun::Image::from_dynamic_image(view.image.load().as_ref().clone())?,
                 )?;
             }
 
@@ -202,7 +202,7 @@ impl VisualizeTools {
                     ),
                 )?;
 
-                let gt_img = samp.view.image;
+                let gt_img = samp.view.image.load();
                 let gt_rerun_img = if gt_img.color().has_alpha() {
                     rerun::Image::from_rgba32(gt_img.to_rgba8().into_vec(), [w, h])
                 } else {
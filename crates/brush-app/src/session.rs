@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use brush_render::ColorSpace;
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{data_source::DataSource, i18n::Language};
+
+/// Pose of the orbit camera, in the space of the orbit controls.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionCamera {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub focus: Vec3,
+    pub fov_y: f64,
+}
+
+/// How the viewer draws the splats.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub shaded: bool,
+    pub points: bool,
+    pub ellipsoids: bool,
+    pub sort_by_distance: bool,
+    pub sort_by_log_distance: bool,
+    pub order_independent: bool,
+    pub dynamic_resolution: bool,
+    pub quality_bias: f32,
+    pub temporal_aa: bool,
+    pub uncertainty: bool,
+    pub color_space: ColorSpace,
+}
+
+/// How the interface of the viewer looks.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterfaceSettings {
+    /// Scale of the interface, on top of the scale of the display.
+    pub ui_scale: f32,
+    pub high_contrast: bool,
+}
+
+impl Default for InterfaceSettings {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            high_contrast: false,
+        }
+    }
+}
+
+/// Viewer state that can be saved to a file, and restored to return to the same view later.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// The loaded model or dataset. Only files on disk and urls can be restored.
+    pub source: Option<DataSource>,
+    pub camera: Option<SessionCamera>,
+    pub display: DisplaySettings,
+    /// The language of the viewer, restored even when another source is loaded.
+    pub language: Language,
+    /// Like the language, restored even when another source is loaded.
+    pub interface: InterfaceSettings,
+}
+
+impl Session {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read session {path:?}"))?;
+        serde_json::from_slice(&data).with_context(|| format!("Invalid session file {path:?}"))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(self)?;
+        rrfd::write_atomic(path, &data, false)
+            .with_context(|| format!("Failed to write session {path:?}"))
+    }
+
+    /// Where the session is saved on exit, and restored from on the next launch.
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("session.json"))
+    }
+}
+
+/// The folder brush keeps its settings in, if there is one on this platform.
+pub fn config_dir() -> Option<PathBuf> {
+    if cfg!(any(target_family = "wasm", target_os = "android")) {
+        return None;
+    }
+
+    let config_dir = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+    };
+
+    config_dir.map(|dir| dir.join("brush"))
+}
@@ -0,0 +1,299 @@
+//! A binary model format that can be opened without parsing, for viewing big scenes.
+//!
+//! A `.splats` file stores the splats exactly as they're laid out on the GPU, so opening a
+//! file only has to memory-map it and upload the attributes. The uploads are split in chunks,
+//! and the splats loaded so far are shown while the rest is still uploading. The file starts
+//! with a header of [`HEADER_SIZE`] bytes:
+//!
+//! - The magic bytes `BRUSHSPL`.
+//! - The format version, as a little endian `u32`.
+//! - The number of SH coefficients per color channel, as a little endian `u32`.
+//! - The number of splats, as a little endian `u64`.
+//!
+//! Followed by the attributes of all splats as little endian `f32`s, one after the other:
+//! the means `[n, 3]`, rotations `[n, 4]`, log scales `[n, 3]`, raw opacities `[n]` and SH
+//! coefficients `[n, coeffs, 3]`. Everything is in Brush coordinates.
+use std::ops::{Deref, Range};
+
+use anyhow::Context;
+use async_fn_stream::try_fn_stream;
+use brush_render::{gaussian_splats::Splats, Backend};
+use burn::tensor::{Tensor, TensorData};
+use glam::Vec3;
+use tokio_stream::Stream;
+use tokio_with_wasm::alias as tokio_wasm;
+
+use crate::splat_import::{SplatMessage, SplatMetadata};
+
+const MAGIC: &[u8; 8] = b"BRUSHSPL";
+
+/// Version of the binary models written by this version of Brush.
+pub const BINARY_MODEL_VERSION: u32 = 1;
+
+/// Size of the header in bytes. The attributes start after it.
+pub const HEADER_SIZE: usize = 64;
+
+// Number of splats uploaded at once. Bigger chunks upload faster, smaller chunks show the
+// model sooner.
+const CHUNK_SPLATS: usize = 1 << 20;
+
+/// Whether the data starts like a binary model, see the [module docs](self).
+pub fn is_binary_model(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+async fn tensor_bytes<B: Backend, const D: usize>(tensor: Tensor<B, D>) -> anyhow::Result<Vec<u8>> {
+    let values = tensor
+        .into_data_async()
+        .await
+        .to_vec::<f32>()
+        .map_err(|e| anyhow::anyhow!("Failed to read splat data {e:?}"))?;
+    Ok(values.iter().flat_map(|v| v.to_le_bytes()).collect())
+}
+
+/// Write splats as a binary model, see the [module docs](self).
+pub async fn splat_to_binary<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u8>> {
+    let mut splats = splats;
+    splats.norm_rotations();
+
+    let [num_splats, coeffs, _] = splats.sh_coeffs.dims();
+    let mut data = Vec::with_capacity(HEADER_SIZE + num_splats * (11 + coeffs * 3) * 4);
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&BINARY_MODEL_VERSION.to_le_bytes());
+    data.extend_from_slice(&(coeffs as u32).to_le_bytes());
+    data.extend_from_slice(&(num_splats as u64).to_le_bytes());
+    data.resize(HEADER_SIZE, 0);
+
+    data.extend(tensor_bytes(splats.means.val()).await?);
+    data.extend(tensor_bytes(splats.rotation.val()).await?);
+    data.extend(tensor_bytes(splats.log_scales.val()).await?);
+    data.extend(tensor_bytes(splats.raw_opacity.val()).await?);
+    data.extend(tensor_bytes(splats.sh_coeffs.val()).await?);
+    Ok(data)
+}
+
+enum ModelData {
+    #[cfg(not(target_family = "wasm"))]
+    Mapped(memmap2::Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for ModelData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(not(target_family = "wasm"))]
+            Self::Mapped(map) => map,
+            Self::Owned(data) => data,
+        }
+    }
+}
+
+/// An opened binary model, see the [module docs](self).
+pub struct BinaryModel {
+    data: ModelData,
+    num_splats: usize,
+    sh_coeffs: usize,
+}
+
+impl BinaryModel {
+    /// Memory-map a binary model file. Nothing is read until the splats are uploaded.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn open(path: &std::path::Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path).with_context(|| format!("Failed to open {path:?}"))?;
+        // SAFETY: The file must not be changed by another process while it's mapped. Changing
+        // its contents gives wrong splats, but truncating it makes reads past the new end
+        // fault (SIGBUS on unix), crashing Brush. Like other viewers that map files, this is
+        // accepted for files the user opened themselves.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Self::new(ModelData::Mapped(map))
+    }
+
+    /// A binary model already in memory, eg. downloaded from a url.
+    pub fn from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
+        Self::new(ModelData::Owned(data))
+    }
+
+    fn new(data: ModelData) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            is_binary_model(&data) && data.len() >= HEADER_SIZE,
+            "Not a Brush binary model"
+        );
+        let read_u32 =
+            |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+
+        let version = read_u32(8);
+        anyhow::ensure!(
+            version <= BINARY_MODEL_VERSION,
+            "Binary model version {version} is newer than this version of Brush supports \
+             (version {BINARY_MODEL_VERSION}), update Brush to load it."
+        );
+        let sh_coeffs = read_u32(12) as usize;
+        let mut num_splats = [0u8; 8];
+        num_splats.copy_from_slice(&data[16..24]);
+        let num_splats = usize::try_from(u64::from_le_bytes(num_splats))?;
+
+        // The header can't be trusted, so check the size can't overflow. The attribute ranges
+        // all lie within it.
+        let expected = sh_coeffs
+            .checked_mul(3)
+            .and_then(|w| w.checked_add(11))
+            .and_then(|w| w.checked_mul(num_splats))
+            .and_then(|floats| floats.checked_mul(4))
+            .and_then(|bytes| bytes.checked_add(HEADER_SIZE))
+            .context("Invalid binary model header")?;
+        anyhow::ensure!(
+            data.len() >= expected,
+            "Binary model is truncated, expected {expected} bytes but got {}",
+            data.len()
+        );
+        Ok(Self {
+            data,
+            num_splats,
+            sh_coeffs,
+        })
+    }
+
+    pub fn num_splats(&self) -> usize {
+        self.num_splats
+    }
+
+    // Number of floats per splat of each attribute, in the order they're stored.
+    fn attribute_widths(&self) -> [usize; 5] {
+        [3, 4, 3, 1, self.sh_coeffs * 3]
+    }
+
+    // The byte range of some splats of an attribute.
+    fn attribute_range(&self, attribute: usize, splats: Range<usize>) -> Range<usize> {
+        let widths = self.attribute_widths();
+        let start = HEADER_SIZE
+            + widths[..attribute]
+                .iter()
+                .map(|w| w * self.num_splats * 4)
+                .sum::<usize>();
+        let width = widths[attribute] * 4;
+        start + splats.start * width..start + splats.end * width
+    }
+
+    // Upload some splats of an attribute.
+    fn upload<B: Backend, const D: usize>(
+        &self,
+        attribute: usize,
+        splats: Range<usize>,
+        shape: [usize; D],
+        device: &B::Device,
+    ) -> Tensor<B, D> {
+        let values: Vec<f32> = self.data[self.attribute_range(attribute, splats)]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Tensor::from_data(TensorData::new(values, shape), device)
+    }
+}
+
+/// Upload the splats of a binary model chunk by chunk, emitting the splats uploaded so far
+/// after every chunk.
+pub fn load_binary_model<B: Backend>(
+    model: BinaryModel,
+    device: B::Device,
+) -> impl Stream<Item = anyhow::Result<SplatMessage<B>>> + 'static {
+    try_fn_stream(|emitter| async move {
+        let n = model.num_splats;
+        let coeffs = model.sh_coeffs;
+
+        let mut means = Tensor::zeros([n, 3], &device);
+        let mut rotation = Tensor::zeros([n, 4], &device);
+        let mut log_scales = Tensor::zeros([n, 3], &device);
+        let mut raw_opacity = Tensor::zeros([n], &device);
+        let mut sh_coeffs = Tensor::zeros([n, coeffs, 3], &device);
+
+        let mut start = 0;
+        while start < n {
+            let end = (start + CHUNK_SPLATS).min(n);
+            let count = end - start;
+
+            means = means.slice_assign(
+                [start..end, 0..3],
+                model.upload(0, start..end, [count, 3], &device),
+            );
+            rotation = rotation.slice_assign(
+                [start..end, 0..4],
+                model.upload(1, start..end, [count, 4], &device),
+            );
+            log_scales = log_scales.slice_assign(
+                [start..end, 0..3],
+                model.upload(2, start..end, [count, 3], &device),
+            );
+            raw_opacity = raw_opacity
+                .slice_assign([start..end], model.upload(3, start..end, [count], &device));
+            sh_coeffs = sh_coeffs.slice_assign(
+                [start..end, 0..coeffs, 0..3],
+                model.upload(4, start..end, [count, coeffs, 3], &device),
+            );
+
+            let splats = Splats::from_tensor_data(
+                means.clone().slice([0..end, 0..3]),
+                rotation.clone().slice([0..end, 0..4]),
+                log_scales.clone().slice([0..end, 0..3]),
+                sh_coeffs.clone().slice([0..end, 0..coeffs, 0..3]),
+                raw_opacity.clone().slice([0..end]),
+            );
+            emitter
+                .emit(SplatMessage {
+                    meta: SplatMetadata {
+                        up_axis: Vec3::Y,
+                        total_splats: n,
+                        frame_count: 0,
+                        current_frame: 0,
+                    },
+                    splats,
+                })
+                .await;
+
+            start = end;
+            tokio_wasm::task::yield_now().await;
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BinaryModel, HEADER_SIZE};
+
+    #[test]
+    fn attribute_ranges() {
+        let mut data = b"BRUSHSPL".to_vec();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&10u64.to_le_bytes());
+        data.resize(HEADER_SIZE + 10 * (11 + 12) * 4, 0);
+
+        let model = BinaryModel::from_bytes(data.clone()).expect("Valid model");
+        assert_eq!(model.num_splats(), 10);
+        assert_eq!(
+            model.attribute_range(0, 0..10),
+            HEADER_SIZE..HEADER_SIZE + 120
+        );
+        assert_eq!(model.attribute_range(1, 2..3).start, HEADER_SIZE + 120 + 32);
+        assert_eq!(
+            model.attribute_range(4, 0..10).end,
+            HEADER_SIZE + 10 * (11 + 12) * 4
+        );
+
+        data.truncate(data.len() - 1);
+        assert!(BinaryModel::from_bytes(data).is_err());
+    }
+
+    #[test]
+    fn overflowing_header() {
+        let mut data = b"BRUSHSPL".to_vec();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(&(u64::MAX / 4).to_le_bytes());
+        data.resize(HEADER_SIZE + 1024, 0);
+        assert!(BinaryModel::from_bytes(data).is_err());
+    }
+}
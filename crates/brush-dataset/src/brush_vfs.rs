@@ -11,16 +11,13 @@ use std::{
     sync::Arc,
 };
 
-use anyhow::Context;
 use tokio::{
     io::{AsyncRead, AsyncReadExt},
     sync::Mutex,
 };
 
-use zip::{
-    result::{ZipError, ZipResult},
-    ZipArchive,
-};
+use crate::DatasetError;
+use zip::{result::ZipResult, ZipArchive};
 
 type DynRead = Box<dyn AsyncRead + Send + Unpin>;
 
@@ -58,10 +55,14 @@ impl PathReader {
         );
     }
 
-    async fn open(&mut self, path: &Path) -> anyhow::Result<Box<dyn AsyncRead + Send + Unpin>> {
-        let entry = self.paths.remove(path).context("File not found")?;
+    async fn open(&mut self, path: &Path) -> Result<DynRead, DatasetError> {
+        let entry = self
+            .paths
+            .remove(path)
+            .ok_or_else(|| DatasetError::PathNotFound(path.to_path_buf()))?;
         let reader = entry.lock().await.take();
-        reader.context("Missing reader")
+        // Readers can only be read once.
+        reader.ok_or_else(|| DatasetError::PathNotFound(path.to_path_buf()))
     }
 }
 
@@ -139,23 +140,49 @@ impl BrushVfs {
         iterator.filter(|p| !p.starts_with("__MACOSX"))
     }
 
-    pub async fn open_path(&mut self, path: &Path) -> anyhow::Result<DynRead> {
+    /// The size of a file in bytes, if it's known without reading it.
+    pub fn file_size(&mut self, path: &Path) -> Option<u64> {
+        match self {
+            Self::Zip(archive) => {
+                let name = archive
+                    .file_names()
+                    .find(|name| path == Path::new(name))?
+                    .to_owned();
+                archive.by_name(&name).ok().map(|file| file.size())
+            }
+            Self::Manual(_) => None,
+            #[cfg(not(target_family = "wasm"))]
+            Self::Directory(dir, _) => std::fs::metadata(dir.join(path)).ok().map(|m| m.len()),
+        }
+    }
+
+    pub async fn open_path(&mut self, path: &Path) -> Result<DynRead, DatasetError> {
         match self {
             Self::Zip(archive) => {
                 let name = archive
                     .file_names()
                     .find(|name| path == Path::new(name))
-                    .ok_or(ZipError::FileNotFound)?;
+                    .ok_or_else(|| DatasetError::PathNotFound(path.to_path_buf()))?;
                 let name = name.to_owned();
                 let mut buffer = vec![];
-                archive.by_name(&name)?.read_to_end(&mut buffer)?;
+                archive
+                    .by_name(&name)
+                    .map_err(|e| DatasetError::Other(e.into()))?
+                    .read_to_end(&mut buffer)?;
                 Ok(Box::new(Cursor::new(buffer)))
             }
             Self::Manual(map) => map.open(path).await,
             #[cfg(not(target_family = "wasm"))]
             Self::Directory(dir, _) => {
                 let total_path = dir.join(path);
-                let file = tokio::fs::File::open(total_path).await?;
+                let file = tokio::fs::File::open(total_path)
+                    .await
+                    .map_err(|e| match e.kind() {
+                        std::io::ErrorKind::NotFound => {
+                            DatasetError::PathNotFound(path.to_path_buf())
+                        }
+                        _ => DatasetError::Io(e),
+                    })?;
                 let file = tokio::io::BufReader::new(file);
                 Ok(Box::new(file))
             }
@@ -0,0 +1,328 @@
+//! Brush's own checkpoint format, to save and resume training quickly.
+//!
+//! PLY files are the format to share splats in, but they're slow to write and read back, and
+//! don't store how the splats were trained. A `.brush` checkpoint is laid out as:
+//!
+//! - The magic bytes `BRUSHCKP`.
+//! - The format version, as a little endian `u32`.
+//! - The length of the header, as a little endian `u32`, followed by the header as json. The
+//!   header holds the training step, the training config, the [`ModelInfo`] and where each
+//!   tensor is stored.
+//! - The tensors as little endian `f32`s, each compressed with zstd separately.
+//!
+//! Newer versions of Brush keep loading older checkpoint versions. Loading a checkpoint written
+//! by a newer version fails with an error.
+//!
+//! Only the splats are stored, not the state of the optimizer. Resuming from a checkpoint
+//! starts with fresh Adam moments and refinement statistics, and learned extras like the
+//! background or the appearance of each capture start over. The loss goes up for a few
+//! hundred steps after resuming while the moments build up again.
+use std::io::Cursor;
+
+use anyhow::Context;
+use brush_render::{gaussian_splats::Splats, Backend};
+use brush_train::train::TrainConfig;
+use burn::tensor::{Tensor, TensorData};
+use serde::{Deserialize, Serialize};
+
+use crate::model_info::ModelInfo;
+
+const MAGIC: &[u8; 8] = b"BRUSHCKP";
+
+/// Version of the checkpoints written by this version of Brush.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+// Fast levels compress splat data about as well as the slow ones, as floats don't compress
+// much beyond their sign and exponent bits.
+const COMPRESSION_LEVEL: i32 = 3;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TensorEntry {
+    name: String,
+    shape: Vec<usize>,
+    /// Offset of the compressed data from the end of the header.
+    offset: usize,
+    compressed_size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    iter: u32,
+    num_splats: usize,
+    /// The training config, kept as json so a config with unknown or missing fields doesn't
+    /// stop the splats from loading.
+    config: serde_json::Value,
+    /// Missing in checkpoints from before the info was stored.
+    #[serde(default)]
+    info: Option<ModelInfo>,
+    tensors: Vec<TensorEntry>,
+}
+
+/// Splats loaded from a checkpoint, with the state to resume training from.
+pub struct Checkpoint<B: Backend> {
+    pub splats: Splats<B>,
+    /// Training step the checkpoint was saved at.
+    pub iter: u32,
+    /// The config the splats were trained with, if it's still valid for this version.
+    pub config: Option<TrainConfig>,
+    pub info: Option<ModelInfo>,
+}
+
+/// Whether the data starts like a checkpoint, see the [module docs](self).
+pub fn is_checkpoint(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn encode(
+    iter: u32,
+    num_splats: usize,
+    config: serde_json::Value,
+    info: Option<ModelInfo>,
+    tensors: Vec<(&str, Vec<usize>, Vec<f32>)>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut entries = vec![];
+    let mut blobs = vec![];
+
+    for (name, shape, values) in tensors {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let compressed = zstd::encode_all(Cursor::new(bytes), COMPRESSION_LEVEL)?;
+        entries.push(TensorEntry {
+            name: name.to_owned(),
+            shape,
+            offset: blobs.len(),
+            compressed_size: compressed.len(),
+        });
+        blobs.extend(compressed);
+    }
+
+    let header = serde_json::to_string(&Header {
+        iter,
+        num_splats,
+        config,
+        info,
+        tensors: entries,
+    })?;
+
+    let mut data = Vec::with_capacity(16 + header.len() + blobs.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+    data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    data.extend_from_slice(header.as_bytes());
+    data.extend(blobs);
+    Ok(data)
+}
+
+fn read_u32(data: &[u8], at: usize) -> anyhow::Result<u32> {
+    let bytes = data
+        .get(at..at + 4)
+        .context("Checkpoint is truncated")?
+        .try_into()?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+// The header, and where the tensor data starts.
+fn read_header_v1(data: &[u8]) -> anyhow::Result<(Header, usize)> {
+    let header_len = read_u32(data, 12)? as usize;
+    let blobs_start = 16 + header_len;
+    let header: Header = serde_json::from_slice(
+        data.get(16..blobs_start)
+            .context("Checkpoint is truncated")?,
+    )
+    .context("Invalid checkpoint header")?;
+    Ok((header, blobs_start))
+}
+
+fn decode_v1(data: &[u8]) -> anyhow::Result<(Header, Vec<Vec<f32>>)> {
+    let (header, blobs_start) = read_header_v1(data)?;
+
+    let tensors = header
+        .tensors
+        .iter()
+        .map(|entry| {
+            let compressed = blobs_start
+                .checked_add(entry.offset)
+                .and_then(|start| Some(start..start.checked_add(entry.compressed_size)?))
+                .and_then(|range| data.get(range))
+                .with_context(|| format!("Checkpoint is missing data for {}", entry.name))?;
+            let bytes = zstd::decode_all(Cursor::new(compressed))?;
+            let values: Vec<f32> = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            anyhow::ensure!(
+                values.len() == entry.shape.iter().product::<usize>(),
+                "Tensor {} doesn't match its shape {:?}",
+                entry.name,
+                entry.shape
+            );
+            Ok(values)
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok((header, tensors))
+}
+
+fn read_version(data: &[u8]) -> anyhow::Result<u32> {
+    anyhow::ensure!(is_checkpoint(data), "Not a Brush checkpoint");
+    read_u32(data, MAGIC.len())
+}
+
+fn newer_version(version: u32) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Checkpoint version {version} is newer than this version of Brush supports \
+         (version {CHECKPOINT_VERSION}), update Brush to load it."
+    )
+}
+
+fn decode(data: &[u8]) -> anyhow::Result<(Header, Vec<Vec<f32>>)> {
+    match read_version(data)? {
+        1 => decode_v1(data),
+        version => Err(newer_version(version)),
+    }
+}
+
+/// Read the number of splats and the [`ModelInfo`] of a checkpoint, without decompressing
+/// the splats.
+pub fn read_checkpoint_info(data: &[u8]) -> anyhow::Result<(usize, Option<ModelInfo>)> {
+    let (header, _) = match read_version(data)? {
+        1 => read_header_v1(data)?,
+        version => return Err(newer_version(version)),
+    };
+    Ok((header.num_splats, header.info))
+}
+
+async fn tensor_values<B: Backend, const D: usize>(
+    tensor: Tensor<B, D>,
+) -> anyhow::Result<(Vec<usize>, Vec<f32>)> {
+    let shape = tensor.dims().to_vec();
+    let values = tensor
+        .into_data_async()
+        .await
+        .to_vec::<f32>()
+        .map_err(|e| anyhow::anyhow!("Failed to read splat data {e:?}"))?;
+    Ok((shape, values))
+}
+
+/// Save splats as a checkpoint at the given training step, see the [module docs](self).
+pub async fn save_checkpoint<B: Backend>(
+    splats: Splats<B>,
+    iter: u32,
+    config: &TrainConfig,
+    info: &ModelInfo,
+) -> anyhow::Result<Vec<u8>> {
+    let num_splats = splats.num_splats();
+    let (means_shape, means) = tensor_values(splats.means.val()).await?;
+    let (rotation_shape, rotation) = tensor_values(splats.rotation.val()).await?;
+    let (scales_shape, log_scales) = tensor_values(splats.log_scales.val()).await?;
+    let (coeffs_shape, sh_coeffs) = tensor_values(splats.sh_coeffs.val()).await?;
+    let (opacity_shape, raw_opacity) = tensor_values(splats.raw_opacity.val()).await?;
+
+    encode(
+        iter,
+        num_splats,
+        serde_json::to_value(config)?,
+        Some(info.clone()),
+        vec![
+            ("means", means_shape, means),
+            ("rotation", rotation_shape, rotation),
+            ("log_scales", scales_shape, log_scales),
+            ("sh_coeffs", coeffs_shape, sh_coeffs),
+            ("raw_opacity", opacity_shape, raw_opacity),
+        ],
+    )
+}
+
+/// Load a checkpoint written by [`save_checkpoint`], by this or an older version of Brush.
+pub fn load_checkpoint<B: Backend>(
+    data: &[u8],
+    device: &B::Device,
+) -> anyhow::Result<Checkpoint<B>> {
+    let (header, mut tensors) = decode(data)?;
+
+    let mut take = |name: &str| {
+        let index = header
+            .tensors
+            .iter()
+            .position(|t| t.name == name)
+            .with_context(|| format!("Checkpoint has no {name}"))?;
+        let values = std::mem::take(&mut tensors[index]);
+        anyhow::Ok(TensorData::new(values, header.tensors[index].shape.clone()))
+    };
+
+    let splats = Splats::from_tensor_data(
+        Tensor::from_data(take("means")?, device),
+        Tensor::from_data(take("rotation")?, device),
+        Tensor::from_data(take("log_scales")?, device),
+        Tensor::from_data(take("sh_coeffs")?, device),
+        Tensor::from_data(take("raw_opacity")?, device),
+    );
+
+    let config = match serde_json::from_value(header.config) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            log::warn!("Ignoring the training config of the checkpoint: {e}");
+            None
+        }
+    };
+
+    Ok(Checkpoint {
+        splats,
+        iter: header.iter,
+        config,
+        info: header.info,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+
+    #[test]
+    fn round_trip() {
+        let means = vec![0.0, 1.0, -2.5, 3.0, 4.0, 5.0];
+        let opacity = vec![0.25, -0.75];
+        let data = encode(
+            12,
+            2,
+            serde_json::json!({"seed": 4}),
+            None,
+            vec![
+                ("means", vec![2, 3], means.clone()),
+                ("raw_opacity", vec![2], opacity.clone()),
+            ],
+        )
+        .expect("Failed to encode");
+
+        let (header, tensors) = decode(&data).expect("Failed to decode");
+        assert_eq!(header.iter, 12);
+        assert_eq!(header.num_splats, 2);
+        assert_eq!(header.config["seed"], 4);
+        assert_eq!(header.info, None);
+        assert_eq!(tensors, vec![means, opacity]);
+
+        let mut newer = data;
+        newer[8..12].copy_from_slice(&2u32.to_le_bytes());
+        assert!(decode(&newer).is_err());
+    }
+
+    #[test]
+    fn overflowing_offset() {
+        let header = serde_json::json!({
+            "iter": 0,
+            "num_splats": 1,
+            "config": {},
+            "tensors": [{
+                "name": "means",
+                "shape": [1, 3],
+                "offset": usize::MAX,
+                "compressed_size": 16,
+            }],
+        })
+        .to_string();
+        let mut data = b"BRUSHCKP".to_vec();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        data.extend_from_slice(header.as_bytes());
+        assert!(decode(&data).is_err());
+    }
+}
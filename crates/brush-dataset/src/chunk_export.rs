@@ -0,0 +1,175 @@
+use std::{
+    io::{Cursor, Write},
+    path::PathBuf,
+};
+
+use brush_render::{gaussian_splats::Splats, spatial_index::SpatialIndex, Backend};
+use glam::Vec3;
+use serde_json::json;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    collision_export::{collision_obj, DEFAULT_COLLISION_RESOLUTION},
+    coordinates::{CoordinateConvention, Handedness},
+    splat_export::read_splats_in_convention,
+    splat_import::GaussianData,
+};
+
+/// Number of splats per chunk when not specified otherwise.
+pub const DEFAULT_SPLATS_PER_CHUNK: usize = 65536;
+
+// Splats are drawn out to about 3 standard deviations, so bounds include that much.
+const BOUNDS_SIGMA: f32 = 3.0;
+
+// Split the splats into spatially compact groups of at most `max_count` splats.
+fn split_chunks(data: &[GaussianData], max_count: usize) -> Vec<Vec<usize>> {
+    let means: Vec<Vec3> = data.iter().map(|d| d.means).collect();
+    SpatialIndex::from_means(&means).clusters(max_count)
+}
+
+pub(crate) fn splat_extent(splat: &GaussianData) -> Vec3 {
+    Vec3::splat(splat.log_scale.max_element().exp() * BOUNDS_SIGMA)
+}
+
+// Names of the values of a splat record, in the order they are packed.
+pub(crate) fn record_layout(sh_coeffs_num: usize) -> Vec<String> {
+    let mut layout = vec![
+        "x", "y", "z", "scale_0", "scale_1", "scale_2", "opacity", "rot_0", "rot_1", "rot_2",
+        "rot_3", "f_dc_0", "f_dc_1", "f_dc_2",
+    ]
+    .into_iter()
+    .map(|s| s.to_owned())
+    .collect::<Vec<_>>();
+    layout.extend((0..(sh_coeffs_num - 1) * 3).map(|i| format!("f_rest_{i}")));
+    layout
+}
+
+// Append the record of a splat to a blob, see [`record_layout`].
+pub(crate) fn pack_splat(splat: &GaussianData, blob: &mut Vec<u8>) {
+    let rot = splat.rotation;
+    let values = splat
+        .means
+        .to_array()
+        .into_iter()
+        .chain(splat.log_scale.to_array())
+        .chain([splat.opacity, rot.w, rot.x, rot.y, rot.z])
+        .chain(splat.sh_dc)
+        .chain(splat.sh_coeffs_rest.iter().copied());
+    for value in values {
+        blob.extend(value.to_le_bytes());
+    }
+}
+
+/// Export splats as spatial chunks for streaming into game engines, as a list of files to
+/// write.
+///
+/// This writes an `index.json` with the layout of the splat data and the bounds of every
+/// chunk, and a binary blob per chunk in `chunks/`. Blobs are tightly packed little endian
+/// f32 values, one record per splat with the properties listed in the index. Values are
+/// stored like in a ply: log scales, opacity before the sigmoid, and SH coefficients in the
+/// `[channel, coeff]` layout. Collision boxes for the model are written to `collision.obj`,
+/// see [`crate::collision_export`].
+pub async fn splats_to_chunks<B: Backend>(
+    splats: Splats<B>,
+    max_splats_per_chunk: usize,
+    convention: CoordinateConvention,
+) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let sh_coeffs_num = splats.sh_coeffs.dims()[1];
+    let data = read_splats_in_convention(splats, &[], convention).await?;
+
+    let layout = record_layout(sh_coeffs_num);
+    let groups = split_chunks(&data, max_splats_per_chunk);
+
+    let mut files = vec![];
+    let mut chunks = vec![];
+    let mut total_min = Vec3::splat(f32::INFINITY);
+    let mut total_max = Vec3::splat(f32::NEG_INFINITY);
+
+    for (i, group) in groups.iter().enumerate().filter(|(_, g)| !g.is_empty()) {
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        let mut blob = Vec::with_capacity(group.len() * layout.len() * 4);
+
+        for &idx in group {
+            let splat = &data[idx];
+            let extent = splat_extent(splat);
+            min = min.min(splat.means - extent);
+            max = max.max(splat.means + extent);
+            pack_splat(splat, &mut blob);
+        }
+
+        total_min = total_min.min(min);
+        total_max = total_max.max(max);
+
+        let file = format!("chunks/{i:05}.bin");
+        chunks.push(json!({
+            "file": file,
+            "count": group.len(),
+            "min": min.to_array(),
+            "max": max.to_array(),
+        }));
+        files.push((PathBuf::from(file), blob));
+    }
+
+    let left_handed = convention.handedness == Handedness::Left;
+    let collision = collision_obj(&data, DEFAULT_COLLISION_RESOLUTION, left_handed);
+    files.push((PathBuf::from("collision.obj"), collision.into_bytes()));
+
+    let index = json!({
+        "version": 1,
+        "coordinates": convention.name(),
+        "vertical_axis": convention.up_axis_name(),
+        "sh_degree": (sh_coeffs_num as f32).sqrt() as u32 - 1,
+        "total_splats": data.len(),
+        "layout": layout,
+        "stride": layout.len() * 4,
+        "min": total_min.to_array(),
+        "max": total_max.to_array(),
+        "chunks": chunks,
+        "collision": "collision.obj",
+    });
+    files.push((
+        PathBuf::from("index.json"),
+        serde_json::to_string_pretty(&index)?.into_bytes(),
+    ));
+
+    Ok(files)
+}
+
+/// Pack a list of files into an uncompressed zip, eg. to save chunks as a single file.
+pub fn files_to_zip(files: &[(PathBuf, Vec<u8>)]) -> anyhow::Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (path, data) in files {
+        zip.start_file(path.to_string_lossy(), options)?;
+        zip.write_all(data)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use ply_rs::ply::PropertyAccess;
+
+    use super::split_chunks;
+    use crate::splat_import::GaussianData;
+
+    #[test]
+    fn chunks_are_bounded_and_cover_all() {
+        let data: Vec<_> = (0..1000)
+            .map(|i| {
+                let mut splat = GaussianData::new();
+                splat.means = Vec3::new((i % 10) as f32, (i / 100) as f32, 0.0);
+                splat
+            })
+            .collect();
+
+        let chunks = split_chunks(&data, 100);
+
+        assert!(chunks.iter().all(|c| c.len() <= 100));
+        let mut all: Vec<_> = chunks.concat();
+        all.sort_unstable();
+        assert_eq!(all, (0..1000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,234 @@
+//! Coarse collision geometry for game engines, from where the splats are dense.
+//!
+//! The splats are binned into a voxel grid, weighted by their opacity. Voxels with enough
+//! opacity are solid, and are merged into as few boxes as possible. Each box is written as a
+//! separate convex object of an `.obj`, which engines can use as collision hulls directly.
+use std::fmt::Write;
+
+use glam::{UVec3, Vec3};
+
+use crate::splat_import::GaussianData;
+
+/// Number of voxels along the longest side of the model.
+pub const DEFAULT_COLLISION_RESOLUTION: u32 = 64;
+
+// Summed opacity a voxel needs to be solid, about one opaque splat.
+const SOLID_OPACITY: f32 = 0.9;
+// Samples along each axis of a big splat at most.
+const MAX_SAMPLES: usize = 16;
+// Splats with a lower opacity don't block anything.
+const MIN_OPACITY: f32 = 0.1;
+
+struct VoxelGrid {
+    min: Vec3,
+    voxel_size: f32,
+    dims: UVec3,
+    density: Vec<f32>,
+}
+
+impl VoxelGrid {
+    fn index(&self, voxel: UVec3) -> usize {
+        (voxel.x + self.dims.x * (voxel.y + self.dims.y * voxel.z)) as usize
+    }
+
+    fn add(&mut self, pos: Vec3, weight: f32) {
+        let voxel = ((pos - self.min) / self.voxel_size).floor();
+        if voxel.cmplt(Vec3::ZERO).any() || voxel.cmpge(self.dims.as_vec3()).any() {
+            return;
+        }
+        let index = self.index(voxel.as_uvec3());
+        self.density[index] += weight;
+    }
+
+    fn solid(&self, voxel: UVec3) -> bool {
+        self.density[self.index(voxel)] >= SOLID_OPACITY
+    }
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+// Bin the splats into a voxel grid. Splats bigger than a voxel are spread over the voxels
+// they cover, sampled on the plane of their two longest axes within one standard deviation.
+fn voxelize(data: &[GaussianData], resolution: u32) -> Option<VoxelGrid> {
+    let splats: Vec<_> = data
+        .iter()
+        .filter(|s| sigmoid(s.opacity) >= MIN_OPACITY && s.means.is_finite())
+        .collect();
+    let (min, max) = splats.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), s| (min.min(s.means), max.max(s.means)),
+    );
+    let extent = (max - min).max_element();
+    if splats.is_empty() || extent <= 0.0 {
+        return None;
+    }
+
+    let voxel_size = extent / resolution.max(1) as f32;
+    let dims = ((max - min) / voxel_size).floor().as_uvec3() + 1;
+    let mut grid = VoxelGrid {
+        min,
+        voxel_size,
+        dims,
+        density: vec![0.0; (dims.x * dims.y * dims.z) as usize],
+    };
+
+    for splat in splats {
+        let opacity = sigmoid(splat.opacity);
+        let scale = splat.log_scale.exp();
+
+        // The two longest axes, the shortest is about the thickness of the surface.
+        let mut axes = [(scale.x, Vec3::X), (scale.y, Vec3::Y), (scale.z, Vec3::Z)];
+        axes.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let [(s0, a0), (s1, a1), _] = axes;
+        let a0 = splat.rotation * a0;
+        let a1 = splat.rotation * a1;
+
+        let samples =
+            |s: f32| ((2.0 * s / (0.5 * voxel_size)).ceil() as usize).clamp(1, MAX_SAMPLES);
+        let (n0, n1) = (samples(s0), samples(s1));
+
+        for i in 0..n0 {
+            for j in 0..n1 {
+                // Offsets in [-1, 1] standard deviations, 0 for a single sample.
+                let u = if n0 > 1 {
+                    2.0 * i as f32 / (n0 - 1) as f32 - 1.0
+                } else {
+                    0.0
+                };
+                let v = if n1 > 1 {
+                    2.0 * j as f32 / (n1 - 1) as f32 - 1.0
+                } else {
+                    0.0
+                };
+                let pos = splat.means + a0 * s0 * u + a1 * s1 * v;
+                // Big splats block every voxel they cover as much as a small one would.
+                grid.add(pos, opacity);
+            }
+        }
+    }
+    Some(grid)
+}
+
+// Greedily merge solid voxels into boxes, as `(min, max)` voxel coordinates with an exclusive
+// max. Grows each box along x, then y, then z as far as all voxels are solid and unclaimed.
+fn merge_boxes(grid: &VoxelGrid) -> Vec<(UVec3, UVec3)> {
+    let dims = grid.dims;
+    let mut claimed = vec![false; grid.density.len()];
+    let free = |claimed: &[bool], v: UVec3| grid.solid(v) && !claimed[grid.index(v)];
+
+    let mut boxes = vec![];
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let start = UVec3::new(x, y, z);
+                if !free(&claimed, start) {
+                    continue;
+                }
+
+                let mut end = start + 1;
+                while end.x < dims.x && free(&claimed, UVec3::new(end.x, y, z)) {
+                    end.x += 1;
+                }
+                while end.y < dims.y && (x..end.x).all(|x| free(&claimed, UVec3::new(x, end.y, z)))
+                {
+                    end.y += 1;
+                }
+                while end.z < dims.z
+                    && (y..end.y)
+                        .all(|y| (x..end.x).all(|x| free(&claimed, UVec3::new(x, y, end.z))))
+                {
+                    end.z += 1;
+                }
+
+                for bz in z..end.z {
+                    for by in y..end.y {
+                        for bx in x..end.x {
+                            claimed[grid.index(UVec3::new(bx, by, bz))] = true;
+                        }
+                    }
+                }
+                boxes.push((start, end));
+            }
+        }
+    }
+    boxes
+}
+
+/// Build collision boxes for splats, as an `.obj` with one convex object per box.
+///
+/// `resolution` is the number of voxels along the longest side of the model, see
+/// [`DEFAULT_COLLISION_RESOLUTION`]. Set `left_handed` when the splats are in a left handed
+/// convention, so the faces still point outwards.
+pub(crate) fn collision_obj(data: &[GaussianData], resolution: u32, left_handed: bool) -> String {
+    let mut obj = String::from("# Collision boxes exported from Brush\n");
+    let Some(grid) = voxelize(data, resolution) else {
+        return obj;
+    };
+
+    // Corners of a unit box, and its faces winding counter clockwise seen from outside.
+    const CORNERS: [[u32; 3]; 8] = [
+        [0, 0, 0],
+        [1, 0, 0],
+        [1, 1, 0],
+        [0, 1, 0],
+        [0, 0, 1],
+        [1, 0, 1],
+        [1, 1, 1],
+        [0, 1, 1],
+    ];
+    const FACES: [[usize; 4]; 6] = [
+        [0, 3, 2, 1],
+        [4, 5, 6, 7],
+        [0, 1, 5, 4],
+        [2, 3, 7, 6],
+        [0, 4, 7, 3],
+        [1, 2, 6, 5],
+    ];
+
+    for (i, (start, end)) in merge_boxes(&grid).into_iter().enumerate() {
+        let _ = writeln!(obj, "o collision_{i}");
+        for corner in CORNERS {
+            let voxel = UVec3::from_array(corner).cmpeq(UVec3::ONE);
+            let pos = grid.min + UVec3::select(voxel, end, start).as_vec3() * grid.voxel_size;
+            let _ = writeln!(obj, "v {} {} {}", pos.x, pos.y, pos.z);
+        }
+        for face in FACES {
+            let mut face = face.map(|c| i * 8 + c + 1);
+            if left_handed {
+                face.reverse();
+            }
+            let _ = writeln!(obj, "f {} {} {} {}", face[0], face[1], face[2], face[3]);
+        }
+    }
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use ply_rs::ply::PropertyAccess;
+
+    use super::{merge_boxes, voxelize};
+    use crate::splat_import::GaussianData;
+
+    #[test]
+    fn solid_block_is_one_box() {
+        // A dense 4x4x4 block of small opaque splats.
+        let data: Vec<_> = (0..64)
+            .map(|i| {
+                let mut splat = GaussianData::new();
+                splat.means = Vec3::new((i % 4) as f32, ((i / 4) % 4) as f32, (i / 16) as f32);
+                splat.log_scale = Vec3::splat(-4.0);
+                splat.opacity = 5.0;
+                splat
+            })
+            .collect();
+
+        let grid = voxelize(&data, 3).expect("Splats aren't empty");
+        let boxes = merge_boxes(&grid);
+        assert_eq!(boxes.len(), 1);
+        assert_eq!(boxes[0].1 - boxes[0].0, grid.dims);
+    }
+}
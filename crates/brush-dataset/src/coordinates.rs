@@ -0,0 +1,376 @@
+use std::str::FromStr;
+
+use glam::{DMat3, DVec3, Mat3, Quat, Vec3};
+
+use crate::splat_import::GaussianData;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Handedness {
+    #[default]
+    Right,
+    Left,
+}
+
+/// The axis conventions of a tool that splats are exchanged with. Brush itself is
+/// right handed with Y up, like most splat plys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CoordinateConvention {
+    pub up: UpAxis,
+    pub handedness: Handedness,
+}
+
+impl CoordinateConvention {
+    pub const BRUSH: Self = Self {
+        up: UpAxis::Y,
+        handedness: Handedness::Right,
+    };
+    pub const BLENDER: Self = Self {
+        up: UpAxis::Z,
+        handedness: Handedness::Right,
+    };
+    pub const UNITY: Self = Self {
+        up: UpAxis::Y,
+        handedness: Handedness::Left,
+    };
+    pub const UNREAL: Self = Self {
+        up: UpAxis::Z,
+        handedness: Handedness::Left,
+    };
+
+    /// All conventions, with the name of a tool that uses them.
+    pub const ALL: [(&'static str, Self); 4] = [
+        ("Brush", Self::BRUSH),
+        ("Blender", Self::BLENDER),
+        ("Unity", Self::UNITY),
+        ("Unreal", Self::UNREAL),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, c)| c == self)
+            .map_or("Custom", |(name, _)| name)
+    }
+
+    /// Matrix that maps a point in Brush coordinates to this convention.
+    pub fn matrix(&self) -> Mat3 {
+        match (self.up, self.handedness) {
+            (UpAxis::Y, Handedness::Right) => Mat3::IDENTITY,
+            // Flip the forward axis.
+            (UpAxis::Y, Handedness::Left) => Mat3::from_cols(Vec3::X, Vec3::Y, -Vec3::Z),
+            // Rotate Y up to Z up.
+            (UpAxis::Z, Handedness::Right) => Mat3::from_cols(Vec3::X, Vec3::Z, -Vec3::Y),
+            // X forward, Y right, Z up, as in Unreal.
+            (UpAxis::Z, Handedness::Left) => Mat3::from_cols(Vec3::Y, Vec3::Z, -Vec3::X),
+        }
+    }
+
+    /// Name of the vertical axis, as written in the ply header.
+    pub(crate) fn up_axis_name(&self) -> &'static str {
+        match self.up {
+            UpAxis::Y => "y",
+            UpAxis::Z => "z",
+        }
+    }
+}
+
+impl FromStr for CoordinateConvention {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, c)| *c)
+            .ok_or_else(|| anyhow::anyhow!("Unknown coordinate convention {s}"))
+    }
+}
+
+// Real spherical harmonics basis up to degree 4, in the same order as the render shaders,
+// see `sh_coeffs_to_color` in project_visible.wgsl.
+fn sh_basis(degree: u32, dir: DVec3) -> Vec<f64> {
+    let (x, y, z) = (dir.x, dir.y, dir.z);
+    let mut basis = vec![0.282_094_791_773_878_1];
+    if degree == 0 {
+        return basis;
+    }
+
+    let tmp0a = 0.488_602_511_902_92;
+    basis.extend([-tmp0a * y, tmp0a * z, -tmp0a * x]);
+    if degree == 1 {
+        return basis;
+    }
+
+    let z2 = z * z;
+    let tmp0b = -1.092_548_430_592_079 * z;
+    let tmp1a = 0.546_274_215_296_039_5;
+    let c1 = x * x - y * y;
+    let s1 = 2.0 * x * y;
+    let sh6 = 0.946_174_695_757_560_1 * z2 - 0.315_391_565_252_520_1;
+    basis.extend([tmp1a * s1, tmp0b * y, sh6, tmp0b * x, tmp1a * c1]);
+    if degree == 2 {
+        return basis;
+    }
+
+    let tmp0c = -2.285_228_997_322_329 * z2 + 0.457_045_799_464_465_8;
+    let tmp1b = 1.445_305_721_320_277 * z;
+    let tmp2a = -0.590_043_589_926_643_5;
+    let c2 = x * c1 - y * s1;
+    let s2 = x * s1 + y * c1;
+    let sh12 = z * (1.865_881_662_950_577 * z2 - 1.119_528_997_770_346);
+    basis.extend([
+        tmp2a * s2,
+        tmp1b * s1,
+        tmp0c * y,
+        sh12,
+        tmp0c * x,
+        tmp1b * c1,
+        tmp2a * c2,
+    ]);
+    if degree == 3 {
+        return basis;
+    }
+
+    let tmp0d = z * (-4.683_325_804_901_025 * z2 + 2.007_139_630_671_868);
+    let tmp1c = 3.311_611_435_151_46 * z2 - 0.473_087_347_878_78;
+    let tmp2b = -1.770_130_769_779_931 * z;
+    let tmp3a = 0.625_835_735_449_176_3;
+    let c3 = x * c2 - y * s2;
+    let s3 = x * s2 + y * c2;
+    let sh20 = 1.984_313_483_298_443 * z * sh12 - 1.006_230_589_874_905 * sh6;
+    basis.extend([
+        tmp3a * s3,
+        tmp2b * s2,
+        tmp1c * s1,
+        tmp0d * y,
+        sh20,
+        tmp0d * x,
+        tmp1c * c1,
+        tmp2b * c2,
+        tmp3a * c3,
+    ]);
+    basis
+}
+
+// Solve `a * x = b` for x, with a n x n and b n x m, both row major.
+fn solve(mut a: Vec<f64>, mut b: Vec<f64>, n: usize, m: usize) -> Vec<f64> {
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i * n + col].abs().total_cmp(&a[j * n + col].abs()))
+            .expect("Non empty");
+        for k in 0..n {
+            a.swap(col * n + k, pivot * n + k);
+        }
+        for k in 0..m {
+            b.swap(col * m + k, pivot * m + k);
+        }
+
+        for row in 0..n {
+            if row == col {
+                continue;
+            }
+            let factor = a[row * n + col] / a[col * n + col];
+            for k in 0..n {
+                a[row * n + k] -= factor * a[col * n + k];
+            }
+            for k in 0..m {
+                b[row * m + k] -= factor * b[col * m + k];
+            }
+        }
+    }
+
+    for row in 0..n {
+        let diag = a[row * n + row];
+        for k in 0..m {
+            b[row * m + k] /= diag;
+        }
+    }
+    b
+}
+
+// Matrices that transform the SH coefficients of each band, for a change of coordinates
+// by the orthogonal matrix `mat`.
+//
+// Each band of the SH basis is closed under rotations and reflections, so the matrix of a
+// band is found by least squares from the basis evaluated at a set of directions.
+pub(crate) fn sh_band_matrices(degree: u32, mat: DMat3) -> Vec<Vec<f64>> {
+    const NUM_DIRS: usize = 128;
+
+    // Evenly spread directions on a Fibonacci sphere.
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+    let dirs: Vec<DVec3> = (0..NUM_DIRS)
+        .map(|i| {
+            let z = 1.0 - 2.0 * (i as f64 + 0.5) / NUM_DIRS as f64;
+            let r = (1.0 - z * z).sqrt();
+            let phi = golden_angle * i as f64;
+            DVec3::new(r * phi.cos(), r * phi.sin(), z)
+        })
+        .collect();
+
+    // A function f in the old coordinates is f(mat^-1 d) in the new coordinates.
+    let inv = mat.transpose();
+    let a_rows: Vec<_> = dirs.iter().map(|&d| sh_basis(degree, d)).collect();
+    let b_rows: Vec<_> = dirs.iter().map(|&d| sh_basis(degree, inv * d)).collect();
+
+    (1..=degree as usize)
+        .map(|band| {
+            let start = band * band;
+            let n = 2 * band + 1;
+
+            let mut ata = vec![0.0; n * n];
+            let mut atb = vec![0.0; n * n];
+            for (a, b) in a_rows.iter().zip(&b_rows) {
+                for i in 0..n {
+                    for j in 0..n {
+                        ata[i * n + j] += a[start + i] * a[start + j];
+                        atb[i * n + j] += a[start + i] * b[start + j];
+                    }
+                }
+            }
+            solve(ata, atb, n, n)
+        })
+        .collect()
+}
+
+/// Change of coordinates for splats, between Brush and another convention.
+pub(crate) struct CoordinateTransform {
+    mat: Mat3,
+    // Rotation part of `mat`, which is a reflection for left handed conventions.
+    rotation: Quat,
+    reflect: bool,
+    sh_bands: Vec<Vec<f64>>,
+}
+
+impl CoordinateTransform {
+    fn new(mat: Mat3) -> Self {
+        let reflect = mat.determinant() < 0.0;
+        // A reflection is a rotation after mirroring the Z axis.
+        let proper = if reflect {
+            mat * Mat3::from_diagonal(Vec3::new(1.0, 1.0, -1.0))
+        } else {
+            mat
+        };
+
+        Self {
+            mat,
+            rotation: Quat::from_mat3(&proper),
+            reflect,
+            sh_bands: sh_band_matrices(4, mat.as_dmat3()),
+        }
+    }
+
+    pub(crate) fn from_brush(convention: CoordinateConvention) -> Self {
+        Self::new(convention.matrix())
+    }
+
+    /// Convert to `convention` after rotating `up` to the up axis of Brush, eg. to level a
+    /// scene to its ground.
+    pub(crate) fn from_brush_leveled(convention: CoordinateConvention, up: Vec3) -> Self {
+        let level = Mat3::from_quat(Quat::from_rotation_arc(up.normalize(), Vec3::Y));
+        Self::new(convention.matrix() * level)
+    }
+
+    pub(crate) fn to_brush(convention: CoordinateConvention) -> Self {
+        Self::new(convention.matrix().transpose())
+    }
+
+    pub(crate) fn point(&self, point: Vec3) -> Vec3 {
+        self.mat * point
+    }
+
+    /// Transform a rotation. This is linear in the components of the quaternion, so also
+    /// applies to deltas between rotations.
+    pub(crate) fn rotation(&self, rot: Quat) -> Quat {
+        // For a reflection M, the new orientation is M * R * Mz with Mz mirroring Z. This
+        // keeps the scales as they are, as Mz only flips the sign of an axis.
+        let rot = if self.reflect {
+            Quat::from_xyzw(-rot.x, -rot.y, rot.z, rot.w)
+        } else {
+            rot
+        };
+        self.rotation * rot
+    }
+
+    /// Transform the higher SH bands, in the inria layout of `[channel, coeff]`. Supports
+    /// up to degree 4 like the renderer.
+    pub(crate) fn sh_rest(&self, rest: &mut [f32]) {
+        let coeffs_per_channel = rest.len() / 3;
+        if coeffs_per_channel == 0 {
+            return;
+        }
+        let degree = ((coeffs_per_channel + 1) as f64).sqrt() as usize - 1;
+
+        for channel in rest.chunks_exact_mut(coeffs_per_channel) {
+            for (band, mat) in self.sh_bands.iter().enumerate().take(degree) {
+                let band = band + 1;
+                let n = 2 * band + 1;
+                // The rest coefficients start after the DC term.
+                let start = band * band - 1;
+
+                let old: Vec<f64> = channel[start..start + n]
+                    .iter()
+                    .map(|&c| c as f64)
+                    .collect();
+                for i in 0..n {
+                    let new: f64 = (0..n).map(|j| mat[i * n + j] * old[j]).sum();
+                    channel[start + i] = new as f32;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn splat(&self, splat: &mut GaussianData) {
+        splat.means = self.point(splat.means);
+        splat.normal = self.point(splat.normal);
+        splat.rotation = self.rotation(splat.rotation);
+        self.sh_rest(&mut splat.sh_coeffs_rest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{sh_basis, CoordinateConvention, CoordinateTransform};
+
+    #[test]
+    fn transformed_sh_matches() {
+        let convention = CoordinateConvention::UNREAL;
+        let transform = CoordinateTransform::from_brush(convention);
+
+        // One channel of degree 3 coefficients, without the DC term.
+        let coeffs: Vec<f32> = (1..16).map(|i| (i as f32 * 0.37).sin()).collect();
+        let mut rest = [coeffs.clone(), coeffs.clone(), coeffs.clone()].concat();
+        transform.sh_rest(&mut rest);
+
+        let dir = Vec3::new(0.3, -0.5, 0.8).normalize();
+        let new_dir = transform.point(dir);
+
+        let eval = |dir: Vec3, coeffs: &[f32]| -> f64 {
+            let basis = sh_basis(3, dir.as_dvec3());
+            basis[1..]
+                .iter()
+                .zip(coeffs)
+                .map(|(b, &c)| b * c as f64)
+                .sum()
+        };
+
+        let old = eval(dir, &coeffs);
+        let new = eval(new_dir, &rest[..15]);
+        assert!((old - new).abs() < 1e-4, "{old} != {new}");
+    }
+
+    #[test]
+    fn leveled_maps_up_to_vertical() {
+        let up = Vec3::new(0.2, 0.9, -0.3).normalize();
+        let transform = CoordinateTransform::from_brush_leveled(CoordinateConvention::BLENDER, up);
+        assert!((transform.point(up) - Vec3::Z).length() < 1e-5);
+    }
+}
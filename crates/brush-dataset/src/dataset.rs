@@ -0,0 +1,344 @@
+//! The dataset types, and decoding the images of datasets.
+use crate::{
+    coordinates::CoordinateConvention, exif, npy, photometric, photometric::PhotometricOptions,
+    point_cloud::PointCloudFilter, DatasetError,
+};
+use anyhow::Context;
+use brush_tasks::ProgressSender;
+use brush_train::{
+    features::FeatureMap,
+    ground::GroundPlane,
+    residency::{HostImageCache, ViewImage},
+    scene::{Scene, SceneView},
+};
+use image::{DynamicImage, ImageDecoder};
+use rand::{seq::SliceRandom, SeedableRng};
+use std::sync::Arc;
+
+#[derive(Clone, Default, Debug)]
+pub struct LoadDatasetArgs {
+    pub max_frames: Option<usize>,
+    pub max_resolution: Option<u32>,
+    pub eval_split_every: Option<usize>,
+    /// Pick the eval views at random, as many as with [`Self::eval_split_every`], instead of
+    /// every nth view. The selection is random with the [`Self::seed`], or 0 without one.
+    pub random_eval_split: bool,
+    pub subsample_frames: Option<u32>,
+    pub subsample_points: Option<u32>,
+    /// Index of the COLMAP model to load, eg. 1 for `sparse/1`. By default the
+    /// model with the most images is used.
+    pub model_index: Option<usize>,
+    /// Folder containing the images of a COLMAP dataset, `images` by default.
+    pub images_dir: Option<String>,
+    /// Load downscaled images of a COLMAP dataset from eg. `images_4`, following
+    /// the MipNeRF-360 convention.
+    pub image_downscale: Option<u32>,
+    /// Seed for view shuffling and initialization, and for a [`Self::random_eval_split`].
+    /// When set, training waits for the full dataset so the data order is reproducible.
+    pub seed: Option<u64>,
+    /// Coordinate convention of ply files that are viewed. Splats are converted to Brush
+    /// coordinates when loaded.
+    pub coordinates: CoordinateConvention,
+    /// Uniform scale of splat models that are viewed, eg. 0.01 for a model in centimeters.
+    /// Applied after converting the coordinates.
+    pub scale: Option<f32>,
+    /// Vignette and white balance corrections of the images, see [`photometric`].
+    pub photometric: PhotometricOptions,
+    /// Memory to keep decoded images in, in megabytes. Other images are kept encoded and
+    /// decoded again when they're needed, for datasets that don't fit in memory. By default
+    /// all images are kept decoded.
+    pub host_image_budget_mb: Option<u32>,
+    /// Remove outliers from the initial point cloud and orient the initial splats along the
+    /// surface, for noisy points from lidar or depth sensors. See [`crate::point_cloud`].
+    pub point_filter: Option<PointCloudFilter>,
+    /// Reports how many views are loaded so far.
+    pub progress: ProgressSender,
+}
+
+#[derive(Clone, Debug)]
+pub struct LoadInitArgs {
+    pub sh_degree: u32,
+    /// A `.brush` checkpoint to resume training from, instead of starting from the initial
+    /// splats of the dataset. The optimizer state starts over, see [`crate::checkpoint`].
+    pub resume: Option<std::path::PathBuf>,
+}
+
+impl LoadDatasetArgs {
+    // The cache to keep decoded images in when there's a host image budget.
+    pub(crate) fn host_image_cache(&self) -> Option<Arc<HostImageCache>> {
+        self.host_image_budget_mb
+            .map(|mb| HostImageCache::new(mb as u64 * 1024 * 1024))
+    }
+}
+
+impl Default for LoadInitArgs {
+    fn default() -> Self {
+        Self {
+            sh_degree: 3,
+            resume: None,
+        }
+    }
+}
+
+/// Statistics on the structure from motion reconstruction a dataset was made with.
+#[derive(Clone, Debug, Default)]
+pub struct ReconstructionStats {
+    pub num_points: usize,
+    pub mean_track_length: f32,
+    pub mean_reprojection_error: f32,
+    /// Number of images with features in the COLMAP database.
+    pub num_database_images: usize,
+    /// Number of image pairs with geometrically verified matches.
+    pub num_verified_pairs: u64,
+    pub mean_keypoints: f32,
+    /// Mean number of verified matches per image.
+    pub mean_matches: f32,
+}
+
+#[derive(Clone)]
+pub struct Dataset {
+    pub train: Scene,
+    pub eval: Option<Scene>,
+    pub reconstruction: Option<ReconstructionStats>,
+    /// The ground the scene rests on, estimated from the SfM points, see
+    /// [`brush_train::ground`].
+    pub ground: Option<GroundPlane>,
+}
+
+impl Dataset {
+    pub fn empty() -> Self {
+        Self {
+            train: Scene::new(vec![]),
+            eval: None,
+            reconstruction: None,
+            ground: None,
+        }
+    }
+
+    pub fn from_views(train_views: Vec<SceneView>, eval_views: Vec<SceneView>) -> Self {
+        Self {
+            train: Scene::new(train_views),
+            eval: if eval_views.is_empty() {
+                None
+            } else {
+                Some(Scene::new(eval_views))
+            },
+            reconstruction: None,
+            ground: None,
+        }
+    }
+
+    /// Combine several captures of the same scene into one dataset. The views of each capture
+    /// are tagged with its index, and get their own camera ids. The captures need to be
+    /// registered in the same coordinate frame.
+    pub fn union(captures: &[Self]) -> Self {
+        let mut train = vec![];
+        let mut eval = vec![];
+        let mut camera_offset = 0;
+
+        for (capture_id, capture) in captures.iter().enumerate() {
+            let scenes = std::iter::once(&capture.train).chain(capture.eval.as_ref());
+            let max_camera_id = scenes
+                .flat_map(|s| s.views.iter().map(|v| v.camera_id))
+                .max()
+                .unwrap_or(0);
+
+            let tag = |view: &SceneView| SceneView {
+                capture_id: capture_id as u32,
+                camera_id: view.camera_id + camera_offset,
+                ..view.clone()
+            };
+            train.extend(capture.train.views.iter().map(tag));
+            if let Some(capture_eval) = &capture.eval {
+                eval.extend(capture_eval.views.iter().map(tag));
+            }
+
+            camera_offset += max_camera_id + 1;
+        }
+
+        let reconstruction = captures.first().and_then(|c| c.reconstruction.clone());
+        let ground = captures.first().and_then(|c| c.ground);
+        Self::from_views(train, eval)
+            .with_reconstruction(reconstruction)
+            .with_ground(ground)
+    }
+
+    pub fn with_reconstruction(mut self, reconstruction: Option<ReconstructionStats>) -> Self {
+        self.reconstruction = reconstruction;
+        self
+    }
+
+    pub fn with_ground(mut self, ground: Option<GroundPlane>) -> Self {
+        self.ground = ground;
+        self
+    }
+}
+
+pub(crate) fn clamp_img_to_max_size(image: DynamicImage, max_size: u32) -> DynamicImage {
+    if image.width() <= max_size && image.height() <= max_size {
+        return image;
+    }
+
+    let aspect_ratio = image.width() as f32 / image.height() as f32;
+    let (new_width, new_height) = if image.width() > image.height() {
+        (max_size, (max_size as f32 / aspect_ratio) as u32)
+    } else {
+        ((max_size as f32 * aspect_ratio) as u32, max_size)
+    };
+    image.resize(new_width, new_height, image::imageops::FilterType::Lanczos3)
+}
+
+// Rotate an image clockwise by quarter turns.
+pub(crate) fn rotate_image(image: DynamicImage, quarter_turns: u32) -> DynamicImage {
+    match quarter_turns % 4 {
+        1 => image.rotate90(),
+        2 => image.rotate180(),
+        3 => image.rotate270(),
+        _ => image,
+    }
+}
+
+// Decode an image, turned upright following its EXIF orientation. Returns the image and how
+// many clockwise quarter turns it was rotated by, see [`exif::rotate_camera`] to rotate its
+// camera to match.
+pub(crate) fn decode_oriented(
+    data: &[u8],
+    path: &std::path::Path,
+) -> Result<(DynamicImage, u32), DatasetError> {
+    let corrupt = |source| DatasetError::CorruptImage {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut decoder = image::ImageReader::new(std::io::Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()
+        .map_err(corrupt)?;
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let image = DynamicImage::from_decoder(decoder).map_err(corrupt)?;
+
+    let quarter_turns = exif::orientation_quarter_turns(orientation).unwrap_or_else(|| {
+        log::warn!("{path:?} is stored mirrored ({orientation:?}), loading it as stored.");
+        0
+    });
+    Ok((rotate_image(image, quarter_turns), quarter_turns))
+}
+
+// Decode an image in the background, turned upright, clamped to the max resolution if any,
+// and apply the photometric corrections. The encoded data is handed back, eg. to read its
+// EXIF tags, with the quarter turns of [`decode_oriented`].
+pub(crate) async fn decode_image(
+    data: Vec<u8>,
+    path: &std::path::Path,
+    max_resolution: Option<u32>,
+    photometric: PhotometricOptions,
+) -> Result<(DynamicImage, Vec<u8>, u32), DatasetError> {
+    let path = path.to_path_buf();
+    brush_tasks::run_blocking(move || {
+        let _span = tracing::trace_span!("Decode image").entered();
+        let (mut image, quarter_turns) = decode_oriented(&data, &path)?;
+        if let Some(max) = max_resolution {
+            image = clamp_img_to_max_size(image, max);
+        }
+        let image = photometric::correct_image(image, photometric);
+        Ok((image, data, quarter_turns))
+    })
+    .await
+}
+
+// The image of a view, decoded from `data` like [`decode_image`]. With a host image cache only
+// the encoded data is kept, and the image is decoded again when it's evicted.
+pub(crate) fn view_image(
+    image: DynamicImage,
+    data: Vec<u8>,
+    path: &std::path::Path,
+    load_args: &LoadDatasetArgs,
+    cache: Option<&Arc<HostImageCache>>,
+) -> ViewImage {
+    let Some(cache) = cache else {
+        return image.into();
+    };
+    let path = path.to_path_buf();
+    let max_resolution = load_args.max_resolution;
+    let photometric = load_args.photometric;
+    let decode = Arc::new(move |data: &[u8]| {
+        let (mut image, _) = decode_oriented(data, &path).expect("Image was decoded before");
+        if let Some(max) = max_resolution {
+            image = clamp_img_to_max_size(image, max);
+        }
+        photometric::correct_image(image, photometric)
+    });
+    ViewImage::encoded(image, data, decode, cache)
+}
+
+// Decode a mask or confidence map for `image`, resized to match it. Masks are made for the
+// stored pixels of the image, so they're rotated by the same quarter turns as the image.
+pub(crate) fn decode_mask(
+    bytes: &[u8],
+    path: &std::path::Path,
+    image: &DynamicImage,
+    quarter_turns: u32,
+) -> Result<DynamicImage, DatasetError> {
+    let mask = image::load_from_memory(bytes).map_err(|source| DatasetError::CorruptImage {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mask = rotate_image(mask, quarter_turns);
+    if mask.width() == image.width() && mask.height() == image.height() {
+        Ok(mask)
+    } else {
+        Ok(mask.resize_exact(
+            image.width(),
+            image.height(),
+            image::imageops::FilterType::Triangle,
+        ))
+    }
+}
+
+// Decode a feature map, a float `.npy` array of `[height, width, dim]` or `[height, width]`.
+pub(crate) fn decode_feature_map(
+    bytes: &[u8],
+    path: &std::path::Path,
+) -> Result<FeatureMap, DatasetError> {
+    let (shape, data) =
+        npy::read_npy_f32(bytes).with_context(|| format!("Failed to read feature map {path:?}"))?;
+    let (height, width, dim) = match shape[..] {
+        [h, w] => (h, w, 1),
+        [h, w, c] => (h, w, c),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Feature map {path:?} has shape {shape:?}, expected [height, width, dim]"
+            )
+            .into())
+        }
+    };
+    Ok(FeatureMap {
+        width: width as u32,
+        height: height as u32,
+        dim,
+        data,
+    })
+}
+
+// Whether each of `num_views` views is used for evaluation. This is every nth view, or a
+// random selection of as many views with `random_eval_split`.
+pub(crate) fn eval_split(num_views: usize, load_args: &LoadDatasetArgs) -> Vec<bool> {
+    let Some(every) = load_args.eval_split_every else {
+        return vec![false; num_views];
+    };
+
+    if !load_args.random_eval_split {
+        return (0..num_views).map(|i| i % every == 0).collect();
+    }
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(load_args.seed.unwrap_or(0));
+    let mut indices: Vec<_> = (0..num_views).collect();
+    indices.shuffle(&mut rng);
+
+    let mut is_eval = vec![false; num_views];
+    for &i in indices.iter().take(num_views.div_ceil(every)) {
+        is_eval[i] = true;
+    }
+    is_eval
+}
@@ -0,0 +1,88 @@
+use std::{io::Cursor, path::PathBuf};
+
+use brush_render::camera::Camera;
+use brush_train::scene::Scene;
+use serde_json::{json, Value};
+
+use crate::Dataset;
+
+// A nerfstudio frame for a camera with an image of the given size.
+pub(crate) fn frame_json(camera: &Camera, img_size: glam::UVec2, file_path: &str) -> Value {
+    let focal = camera.focal(img_size);
+    let center = camera.center(img_size);
+
+    // Nerfstudio matrices are row major.
+    let transform_matrix = camera
+        .opengl_world_from_camera()
+        .transpose()
+        .to_cols_array_2d();
+
+    json!({
+        "file_path": file_path,
+        "transform_matrix": transform_matrix,
+        "fl_x": focal.x,
+        "fl_y": focal.y,
+        "cx": center.x,
+        "cy": center.y,
+        "w": img_size.x,
+        "h": img_size.y,
+    })
+}
+
+// Write the frames of a scene, and queue up their images under `folder`.
+fn scene_frames(
+    scene: &Scene,
+    folder: &str,
+    files: &mut Vec<(PathBuf, Vec<u8>)>,
+) -> anyhow::Result<Vec<Value>> {
+    scene
+        .views
+        .iter()
+        .enumerate()
+        .map(|(i, view)| {
+            let img_size = glam::uvec2(view.image.width(), view.image.height());
+
+            // Prefix the index, names aren't unique when images are in subfolders.
+            let stem = std::path::Path::new(&view.name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let file_path = format!("{folder}/{i:05}_{stem}.png");
+
+            let mut png = vec![];
+            view.image
+                .load()
+                .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+            files.push((PathBuf::from(&file_path), png));
+
+            Ok(frame_json(&view.camera, img_size, &file_path))
+        })
+        .collect()
+}
+
+/// Convert a dataset to the nerfstudio format, as a list of files to write.
+///
+/// This writes a `transforms_train.json` and `transforms_val.json` (if there is an eval split)
+/// with per frame intrinsics, and all images as png.
+pub fn dataset_to_nerfstudio(dataset: &Dataset) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut files = vec![];
+
+    let mut scenes = vec![("transforms_train.json", "train", &dataset.train)];
+    if let Some(eval) = dataset.eval.as_ref() {
+        scenes.push(("transforms_val.json", "val", eval));
+    }
+
+    for (json_name, folder, scene) in scenes {
+        let frames = scene_frames(scene, folder, &mut files)?;
+        let transforms = json!({
+            "camera_model": "PINHOLE",
+            "frames": frames,
+        });
+        files.push((
+            PathBuf::from(json_name),
+            serde_json::to_string_pretty(&transforms)?.into_bytes(),
+        ));
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,152 @@
+//! Views to distill a large model into a smaller one, without the original dataset.
+//!
+//! The teacher model is rendered from cameras all around it, and these renders are the
+//! training views of a compact student model, eg. a simplified copy of the teacher. The
+//! cameras look at the center of the model from a distance where most of it is in view, so
+//! this suits models of objects or scenes seen from outside better than rooms seen from
+//! inside.
+use brush_render::{camera::Camera, gaussian_splats::Splats, Backend};
+use brush_tasks::ProgressSender;
+use brush_train::{
+    image::{image_sharpness, tensor_into_image},
+    scene::{Scene, SceneView},
+};
+use glam::{Mat3, Quat, UVec2, Vec3};
+use image::DynamicImage;
+
+/// How to render the views of the teacher.
+#[derive(Clone, Debug)]
+pub struct DistillViews {
+    /// Number of views around the model.
+    pub num_views: usize,
+    /// Width and height of the square views in pixels.
+    pub resolution: u32,
+    /// Field of view of the cameras, in degrees.
+    pub fov: f64,
+    /// Fraction of the splats, nearest to the center, that the views should contain. The
+    /// other splats are usually floaters and far background.
+    pub coverage: f32,
+}
+
+impl Default for DistillViews {
+    fn default() -> Self {
+        Self {
+            num_views: 256,
+            resolution: 512,
+            fov: 50.0,
+            coverage: 0.9,
+        }
+    }
+}
+
+// Camera looking from `position` at `target`, with +Y down in the image.
+fn look_at(position: Vec3, target: Vec3, fov: f64) -> Camera {
+    let forward = (target - position).normalize();
+    let up = if forward.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::NEG_Y
+    };
+    let right = forward.cross(up).normalize();
+    let down = forward.cross(right);
+    let rotation = Quat::from_mat3(&Mat3::from_cols(right, down, forward));
+    Camera::new(position, rotation, fov, fov, glam::vec2(0.5, 0.5))
+}
+
+/// Cameras evenly spread on a sphere around `points`, looking at their center.
+pub fn orbit_cameras(points: &[Vec3], config: &DistillViews) -> Vec<Camera> {
+    if points.is_empty() {
+        return vec![];
+    }
+
+    // The median is robust to floaters far away.
+    let median = |axis: usize| {
+        let mut values: Vec<_> = points.iter().map(|p| p[axis]).collect();
+        values.sort_by(|a, b| a.total_cmp(b));
+        values[values.len() / 2]
+    };
+    let center = Vec3::new(median(0), median(1), median(2));
+
+    let mut dists: Vec<_> = points.iter().map(|p| p.distance(center)).collect();
+    dists.sort_by(|a, b| a.total_cmp(b));
+    let index = (dists.len() as f32 * config.coverage.clamp(0.0, 1.0)) as usize;
+    let radius = dists[index.min(dists.len() - 1)].max(1e-3);
+
+    // Far enough for a sphere of the radius to be in view.
+    let fov = config.fov.to_radians();
+    let distance = radius / (fov as f32 * 0.5).sin();
+
+    // A Fibonacci sphere spreads the directions evenly.
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+    let n = config.num_views;
+    (0..n)
+        .map(|i| {
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / n as f32;
+            let ring = (1.0 - y * y).sqrt();
+            let theta = golden_angle * i as f32;
+            let dir = Vec3::new(ring * theta.cos(), y, ring * theta.sin());
+            look_at(center + dir * distance, center, fov)
+        })
+        .collect()
+}
+
+/// Render the teacher from each camera, as the training views of a student.
+pub async fn render_teacher_views<B: Backend>(
+    teacher: &Splats<B>,
+    cameras: Vec<Camera>,
+    resolution: u32,
+    progress: &ProgressSender,
+) -> Scene {
+    let size = UVec2::splat(resolution);
+    let total = cameras.len();
+    let mut views = vec![];
+
+    for (i, camera) in cameras.into_iter().enumerate() {
+        let (rendered, _) = teacher.render(&camera, size, false);
+        let data = rendered.clamp(0.0, 1.0).into_data_async().await;
+        // Keep the views at 8 bits, as there can be many.
+        let image = DynamicImage::ImageRgba8(tensor_into_image(data).to_rgba8());
+
+        views.push(SceneView {
+            name: format!("teacher_{i:05}"),
+            camera,
+            sharpness: image_sharpness(&image),
+            image: image.into(),
+            mask: None,
+            camera_id: 0,
+            capture_id: 0,
+            features: None,
+            depth: None,
+        });
+        progress.report("Rendering teacher views", i + 1, total);
+    }
+
+    Scene::new(views)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{orbit_cameras, DistillViews};
+
+    #[test]
+    fn cameras_look_at_center() {
+        let points: Vec<_> = (0..100)
+            .map(|i| Vec3::new(i as f32 / 50.0 - 1.0, 0.0, 0.0) + 3.0)
+            .collect();
+        let config = DistillViews {
+            num_views: 16,
+            ..Default::default()
+        };
+        let cameras = orbit_cameras(&points, &config);
+        assert_eq!(cameras.len(), 16);
+
+        let center = Vec3::splat(3.0);
+        for camera in cameras {
+            let forward = camera.rotation * Vec3::Z;
+            let to_center = (center - camera.position).normalize();
+            assert!(forward.dot(to_center) > 0.999);
+        }
+    }
+}
@@ -0,0 +1,541 @@
+//! Non-destructive edits of splats, as a stack of layers applied on top of a model.
+//!
+//! Cleaning up a model, eg. cropping it, removing floaters or fixing the colors of a region,
+//! leaves the model itself as it is. Every edit is a layer that picks some splats and deletes,
+//! moves, copies or recolors them. The layers are applied in order whenever the splats are shown or
+//! exported, so they can be switched off, tweaked or removed at any time.
+//!
+//! The stack is saved as json next to the model, see [`sidecar_path`], and loaded again with
+//! it.
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use brush_render::{bounding_box::BoundingBox, gaussian_splats::Splats, render::SH_C0, Backend};
+use burn::tensor::{activation::sigmoid, Bool, Tensor, TensorData};
+use glam::{Mat3, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::sh_band_matrices;
+
+/// The splats a layer applies to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Selection {
+    All,
+    /// The splats with their mean in a box, after the layers below.
+    Box(BoundingBox),
+    /// Splats by their index in the model, eg. picked by their features. Only applies to a
+    /// model with this many splats.
+    Indices {
+        indices: Vec<u32>,
+        num_splats: usize,
+    },
+}
+
+impl Selection {
+    /// Select the splats of a mask over the model.
+    pub async fn from_mask<B: Backend>(mask: Tensor<B, 1, Bool>) -> Self {
+        let num_splats = mask.dims()[0];
+        let indices = mask
+            .argwhere_async()
+            .await
+            .into_data_async()
+            .await
+            .to_vec::<i32>()
+            .expect("Wrong type")
+            .into_iter()
+            .map(|i| i as u32)
+            .collect();
+        Self::Indices {
+            indices,
+            num_splats,
+        }
+    }
+
+    /// Whether the selection can be made on a model with this many splats.
+    pub fn applies_to(&self, num_splats: usize) -> bool {
+        match self {
+            Self::Indices {
+                num_splats: count, ..
+            } => *count == num_splats,
+            Self::All | Self::Box(_) => true,
+        }
+    }
+
+    fn mask<B: Backend>(&self, splats: &Splats<B>) -> Tensor<B, 1, Bool> {
+        let num_splats = splats.num_splats();
+        let device = splats.means.device();
+        match self {
+            Self::All => Tensor::<B, 1>::ones([num_splats], &device).greater_elem(0.5),
+            Self::Box(region) => inside_distance(splats, region).greater_equal_elem(0.0),
+            Self::Indices { indices, .. } => {
+                let mut selected = vec![0.0f32; num_splats];
+                for &i in indices {
+                    if let Some(s) = selected.get_mut(i as usize) {
+                        *s = 1.0;
+                    }
+                }
+                Tensor::<B, 1>::from_data(TensorData::new(selected, [num_splats]), &device)
+                    .greater_elem(0.5)
+            }
+        }
+    }
+}
+
+// Distance of the splat means to the sides of a box, negative outside of it.
+fn inside_distance<B: Backend>(splats: &Splats<B>, region: &BoundingBox) -> Tensor<B, 1> {
+    let device = splats.means.device();
+    let center = Tensor::<B, 1>::from_floats(region.center.to_array(), &device).reshape([1, 3]);
+    let extent = Tensor::<B, 1>::from_floats(region.extent.to_array(), &device).reshape([1, 3]);
+    (extent - (splats.means.val() - center).abs())
+        .min_dim(1)
+        .squeeze(1)
+}
+
+/// Scale and rotate around a pivot, then move.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SplatTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: f32,
+    pub pivot: Vec3,
+}
+
+impl SplatTransform {
+    /// No change, with the pivot to rotate and scale around.
+    pub fn around(pivot: Vec3) -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: 1.0,
+            pivot,
+        }
+    }
+}
+
+/// What a layer does to its splats.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Edit {
+    Delete,
+    Transform(SplatTransform),
+    /// Add a transformed copy of the splats, eg. to patch a hole with similar content from
+    /// nearby. Copies fade out over `feather` from the sides of a box selection, to blend in
+    /// with the splats around them.
+    Duplicate {
+        transform: SplatTransform,
+        #[serde(default)]
+        feather: f32,
+    },
+    /// Adjust the colors, see [`color_matrix`].
+    Recolor {
+        tint: Vec3,
+        /// Change of exposure in stops, each doubles or halves the brightness.
+        #[serde(default)]
+        exposure: f32,
+        /// 0 for gray, 1 to keep the colors as they are.
+        #[serde(default = "one")]
+        saturation: f32,
+    },
+}
+
+impl Edit {
+    pub const IDENTITY_RECOLOR: Self = Self::Recolor {
+        tint: Vec3::ONE,
+        exposure: 0.0,
+        saturation: 1.0,
+    };
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+// Rec. 709 weights of the channels in the brightness of a color.
+const LUMA: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+
+/// The matrix of a color adjustment: changing the saturation, then multiplying by the tint
+/// per channel and by the exposure. It's linear, so applies the same to the base colors and
+/// to the view dependent changes of color.
+pub fn color_matrix(tint: Vec3, exposure: f32, saturation: f32) -> Mat3 {
+    // Mix each channel with the brightness, which keeps grays as they are.
+    let gray = Mat3::from_cols(
+        Vec3::splat(LUMA.x),
+        Vec3::splat(LUMA.y),
+        Vec3::splat(LUMA.z),
+    );
+    let saturate = Mat3::IDENTITY * saturation + gray * (1.0 - saturation);
+    Mat3::from_diagonal(tint * exposure.exp2()) * saturate
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EditLayer {
+    pub name: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    pub selection: Selection,
+    /// Apply the edit to the splats outside the selection instead, eg. to crop to a box.
+    #[serde(default)]
+    pub invert: bool,
+    pub edit: Edit,
+}
+
+impl EditLayer {
+    /// Delete everything outside a box.
+    pub fn crop(region: BoundingBox) -> Self {
+        Self {
+            name: "Crop".to_owned(),
+            enabled: true,
+            selection: Selection::Box(region),
+            invert: true,
+            edit: Edit::Delete,
+        }
+    }
+}
+
+/// The edits of a model, applied from first to last, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EditStack {
+    pub layers: Vec<EditLayer>,
+}
+
+/// Where the edits of a model are saved, eg. `scene.ply.edits.json` for `scene.ply`.
+pub fn sidecar_path(model: &Path) -> PathBuf {
+    let mut name = model.file_name().unwrap_or_default().to_owned();
+    name.push(".edits.json");
+    model.with_file_name(name)
+}
+
+impl EditStack {
+    /// Whether any layer changes the splats.
+    pub fn is_active(&self) -> bool {
+        self.layers.iter().any(|l| l.enabled)
+    }
+
+    /// The edited splats, and a mask of the splats that weren't deleted. Deleted splats
+    /// are still there, and copies are added after the splats of the model, so every layer
+    /// sees the splats of the model at the same indices.
+    pub fn apply_layers<B: Backend>(&self, splats: &Splats<B>) -> (Splats<B>, Tensor<B, 1, Bool>) {
+        let mut splats = splats.clone();
+        let num_splats = splats.num_splats();
+        let mut keep = Selection::All.mask(&splats);
+
+        for layer in &self.layers {
+            if !layer.enabled || !layer.selection.applies_to(num_splats) {
+                continue;
+            }
+            let mask = layer.selection.mask(&splats);
+            let mask = if layer.invert { mask.bool_not() } else { mask };
+
+            match &layer.edit {
+                Edit::Delete => keep = both(keep, mask.bool_not()),
+                Edit::Transform(transform) => transform_splats(&mut splats, mask, transform),
+                Edit::Duplicate { transform, feather } => {
+                    let mut copy = splats.clone();
+                    // Fade out the copies towards the sides of the box they're copied from.
+                    if let Selection::Box(region) = &layer.selection {
+                        if *feather > 0.0 {
+                            let distance = inside_distance(&splats, region);
+                            let distance = if layer.invert { -distance } else { distance };
+                            let weight = (distance / *feather).clamp(0.0, 1.0);
+                            Splats::map_param(&mut copy.raw_opacity, |raw| {
+                                let opacity = (sigmoid(raw) * weight).clamp(1e-6, 1.0 - 1e-6);
+                                (opacity.clone() / (opacity.neg() + 1.0)).log()
+                            });
+                        }
+                    }
+                    transform_splats(&mut copy, mask.clone(), transform);
+
+                    // Only the copies of selected splats are kept.
+                    let copy_keep = both(keep.clone(), mask);
+                    keep = Tensor::cat(vec![keep, copy_keep], 0);
+                    splats = Splats::from_tensor_data(
+                        Tensor::cat(vec![splats.means.val(), copy.means.val()], 0),
+                        Tensor::cat(vec![splats.rotation.val(), copy.rotation.val()], 0),
+                        Tensor::cat(vec![splats.log_scales.val(), copy.log_scales.val()], 0),
+                        Tensor::cat(vec![splats.sh_coeffs.val(), copy.sh_coeffs.val()], 0),
+                        Tensor::cat(vec![splats.raw_opacity.val(), copy.raw_opacity.val()], 0),
+                    );
+                }
+                Edit::Recolor {
+                    tint,
+                    exposure,
+                    saturation,
+                } => recolor(
+                    &mut splats,
+                    mask,
+                    color_matrix(*tint, *exposure, *saturation),
+                ),
+            }
+        }
+        (splats, keep)
+    }
+
+    /// The edited splats to show, with deleted splats made transparent. Cheap enough to
+    /// run every frame, as nothing is read back from the GPU.
+    pub fn preview<B: Backend>(&self, splats: &Splats<B>) -> Splats<B> {
+        if !self.is_active() {
+            return splats.clone();
+        }
+        let (mut splats, keep) = self.apply_layers(splats);
+        Splats::map_param(&mut splats.raw_opacity, |op| {
+            op.mask_fill(keep.bool_not(), -1e4)
+        });
+        splats
+    }
+
+    /// The edited splats without the deleted ones, eg. to export them.
+    pub async fn apply<B: Backend>(&self, splats: &Splats<B>) -> Splats<B> {
+        if !self.is_active() {
+            return splats.clone();
+        }
+        let (splats, keep) = self.apply_layers(splats);
+        let indices = keep.argwhere_async().await.squeeze(1);
+        Splats::from_tensor_data(
+            splats.means.val().select(0, indices.clone()),
+            splats.rotation.val().select(0, indices.clone()),
+            splats.log_scales.val().select(0, indices.clone()),
+            splats.sh_coeffs.val().select(0, indices.clone()),
+            splats.raw_opacity.val().select(0, indices),
+        )
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        serde_json::from_str(json).context("Invalid edit layers")
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Edits serialize")
+    }
+
+    /// Load the edits saved next to a model, if there are any.
+    pub fn load_sidecar(model: &Path) -> anyhow::Result<Option<Self>> {
+        let path = sidecar_path(model);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_json(&json).map(Some)
+    }
+}
+
+// Whether both masks are set.
+fn both<B: Backend>(a: Tensor<B, 1, Bool>, b: Tensor<B, 1, Bool>) -> Tensor<B, 1, Bool> {
+    let n = a.dims()[0];
+    Tensor::cat(vec![a.reshape([n, 1]), b.reshape([n, 1])], 1)
+        .all_dim(1)
+        .squeeze(1)
+}
+
+// Matrix of the left multiplication by `rot`, for quaternions in wxyz order.
+fn left_multiply_matrix(rot: Quat) -> [[f32; 4]; 4] {
+    let Quat { x, y, z, w } = rot;
+    [[w, -x, -y, -z], [x, w, -z, y], [y, z, w, -x], [z, -y, x, w]]
+}
+
+// Tensor of the transpose of a row major matrix, to multiply row vectors with.
+fn transposed<B: Backend>(mat: &[f32], n: usize, device: &B::Device) -> Tensor<B, 2> {
+    let data: Vec<f32> = (0..n * n).map(|i| mat[(i % n) * n + i / n]).collect();
+    Tensor::from_data(TensorData::new(data, [n, n]), device)
+}
+
+fn transform_splats<B: Backend>(
+    splats: &mut Splats<B>,
+    mask: Tensor<B, 1, Bool>,
+    transform: &SplatTransform,
+) {
+    let num_splats = splats.num_splats();
+    let device = splats.means.device();
+    let SplatTransform {
+        translation,
+        rotation,
+        scale,
+        pivot,
+    } = *transform;
+    let rotation = rotation.normalize();
+    let scale = scale.max(1e-6);
+
+    // x' = s * R * (x - p) + p + t
+    let linear = glam::Mat3::from_quat(rotation) * scale;
+    let offset = pivot + translation - linear * pivot;
+    let mask3 = mask
+        .clone()
+        .reshape([num_splats, 1])
+        .expand([num_splats, 3]);
+    Splats::map_param(&mut splats.means, |means| {
+        let moved =
+            means
+                .clone()
+                .matmul(transposed(&linear.transpose().to_cols_array(), 3, &device))
+                + Tensor::<B, 1>::from_floats(offset.to_array(), &device).reshape([1, 3]);
+        means.mask_where(mask3.clone(), moved)
+    });
+
+    let left = left_multiply_matrix(rotation).concat();
+    let mask4 = mask
+        .clone()
+        .reshape([num_splats, 1])
+        .expand([num_splats, 4]);
+    Splats::map_param(&mut splats.rotation, |rot| {
+        let rotated = rot.clone().matmul(transposed(&left, 4, &device));
+        rot.mask_where(mask4, rotated)
+    });
+
+    Splats::map_param(&mut splats.log_scales, |log_scales| {
+        log_scales
+            .clone()
+            .mask_where(mask3, log_scales + scale.ln())
+    });
+
+    // The colors seen from a direction move along with the splats.
+    let degree = splats.sh_degree();
+    if degree == 0 {
+        return;
+    }
+    let [_, coeffs, _] = splats.sh_coeffs.dims();
+    let bands = sh_band_matrices(degree, glam::Mat3::from_quat(rotation).as_dmat3());
+    let sh = splats.sh_coeffs.val();
+    let mut parts = vec![sh.clone().slice([0..num_splats, 0..1, 0..3])];
+    for (band, mat) in bands.iter().enumerate() {
+        let band = band + 1;
+        let n = 2 * band + 1;
+        let start = band * band;
+        let mat: Vec<f32> = mat.iter().map(|&v| v as f32).collect();
+        let rotated = sh
+            .clone()
+            .slice([0..num_splats, start..start + n, 0..3])
+            .swap_dims(1, 2)
+            .reshape([num_splats * 3, n])
+            .matmul(transposed(&mat, n, &device))
+            .reshape([num_splats, 3, n])
+            .swap_dims(1, 2);
+        parts.push(rotated);
+    }
+    let rotated = Tensor::cat(parts, 1);
+    let mask_sh = mask
+        .reshape([num_splats, 1, 1])
+        .expand([num_splats, coeffs, 3]);
+    Splats::map_param(&mut splats.sh_coeffs, |sh| sh.mask_where(mask_sh, rotated));
+}
+
+fn recolor<B: Backend>(splats: &mut Splats<B>, mask: Tensor<B, 1, Bool>, color: Mat3) {
+    let num_splats = splats.num_splats();
+    let [_, coeffs, _] = splats.sh_coeffs.dims();
+    let device = splats.means.device();
+
+    // Every coefficient is a color, mixed across the channels like the colors themselves.
+    let color_t = transposed(&color.transpose().to_cols_array(), 3, &device);
+    let sh = splats.sh_coeffs.val();
+    let mixed = sh
+        .clone()
+        .reshape([num_splats * coeffs, 3])
+        .matmul(color_t)
+        .reshape([num_splats, coeffs, 3]);
+
+    // The base color is SH_C0 * dc + 0.5, so the dc term also makes up for the change of
+    // the 0.5 offset.
+    let offset = (color * Vec3::splat(0.5) - 0.5) / SH_C0;
+    let offset = Tensor::<B, 1>::from_floats(offset.to_array(), &device).reshape([1, 1, 3]);
+    let dc = mixed.clone().slice([0..num_splats, 0..1, 0..3]) + offset;
+    let recolored = if coeffs > 1 {
+        Tensor::cat(vec![dc, mixed.slice([0..num_splats, 1..coeffs, 0..3])], 1)
+    } else {
+        dc
+    };
+    let mask = mask
+        .reshape([num_splats, 1, 1])
+        .expand([num_splats, coeffs, 3]);
+    Splats::map_param(&mut splats.sh_coeffs, |sh| sh.mask_where(mask, recolored));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use brush_render::bounding_box::BoundingBox;
+    use glam::{Quat, Vec3};
+
+    use super::{
+        color_matrix, left_multiply_matrix, sidecar_path, Edit, EditLayer, EditStack, Selection,
+        SplatTransform,
+    };
+
+    #[test]
+    fn left_multiply_matches_quat_product() {
+        let rot = Quat::from_euler(glam::EulerRot::XYZ, 0.3, -0.7, 1.1);
+        let q = Quat::from_euler(glam::EulerRot::XYZ, -0.2, 0.5, 0.4);
+        let mat = left_multiply_matrix(rot);
+        let q_wxyz = [q.w, q.x, q.y, q.z];
+        let product: Vec<f32> = mat
+            .iter()
+            .map(|row| row.iter().zip(q_wxyz).map(|(a, b)| a * b).sum())
+            .collect();
+        let expected = rot * q;
+        let expected = [expected.w, expected.x, expected.y, expected.z];
+        for (a, b) in product.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-6, "{product:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn color_adjustments() {
+        let gray = Vec3::splat(0.4);
+        let red = Vec3::new(0.8, 0.1, 0.1);
+        let desaturate = color_matrix(Vec3::ONE, 0.0, 0.0);
+        assert!((desaturate * gray - gray).length() < 1e-6);
+        let luma = desaturate * red;
+        assert!((luma.x - luma.y).abs() < 1e-6 && (luma.y - luma.z).abs() < 1e-6);
+
+        let brighter = color_matrix(Vec3::ONE, 1.0, 1.0);
+        assert!((brighter * red - red * 2.0).length() < 1e-6);
+        assert_eq!(color_matrix(Vec3::ONE, 0.0, 1.0), glam::Mat3::IDENTITY);
+    }
+
+    #[test]
+    fn stack_round_trips() {
+        let stack = EditStack {
+            layers: vec![
+                EditLayer::crop(BoundingBox::from_min_max(Vec3::NEG_ONE, Vec3::ONE)),
+                EditLayer {
+                    name: "Floaters".to_owned(),
+                    enabled: false,
+                    selection: Selection::Indices {
+                        indices: vec![3, 5, 8],
+                        num_splats: 10,
+                    },
+                    invert: false,
+                    edit: Edit::Recolor {
+                        tint: Vec3::new(1.0, 0.8, 0.6),
+                        exposure: 0.5,
+                        saturation: 1.2,
+                    },
+                },
+                EditLayer {
+                    name: "Patch".to_owned(),
+                    enabled: true,
+                    selection: Selection::Box(BoundingBox::from_min_max(Vec3::ZERO, Vec3::ONE)),
+                    invert: false,
+                    edit: Edit::Duplicate {
+                        transform: SplatTransform {
+                            translation: Vec3::X,
+                            ..SplatTransform::around(Vec3::splat(0.5))
+                        },
+                        feather: 0.1,
+                    },
+                },
+            ],
+        };
+        let json = stack.to_json();
+        assert_eq!(EditStack::from_json(&json).expect("Valid json"), stack);
+        assert!(stack.is_active());
+        assert!(!stack.layers[1].selection.applies_to(11));
+
+        assert_eq!(
+            sidecar_path(Path::new("scans/room.ply")),
+            Path::new("scans/room.ply.edits.json")
+        );
+    }
+}
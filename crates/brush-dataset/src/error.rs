@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+/// Errors when loading a dataset.
+#[derive(Debug, thiserror::Error)]
+pub enum DatasetError {
+    #[error("File not found: {0:?}")]
+    PathNotFound(PathBuf),
+
+    #[error("Couldn't parse dataset as any format. Only nerfstudio (transforms.json), COLMAP (cameras, images & points files), TUM RGB-D (rgb.txt & depth.txt), ScanNet (color, depth, pose & intrinsic folders) and Replica (traj.txt & results folder) are supported.")]
+    UnknownFormat,
+
+    #[error("No COLMAP cameras file found (cameras.bin or cameras.txt).")]
+    MissingCameras,
+
+    #[error("Found a COLMAP database but no sparse model. A COLMAP database has no camera poses, run the COLMAP mapper first.")]
+    MissingSparseModel,
+
+    #[error("{0}")]
+    UnsupportedCameraModel(String),
+
+    #[error("Failed to decode image {path:?}: {source}")]
+    CorruptImage {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for DatasetError {
+    fn from(error: anyhow::Error) -> Self {
+        // Loaders raise typed errors through anyhow, unwrap those again.
+        match error.downcast::<Self>() {
+            Ok(error) => error,
+            Err(error) => Self::Other(error),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+// Minimal EXIF reading, just enough to estimate camera intrinsics
+// for datasets that don't come with a calibration, and to orient images.
+use std::io::Cursor;
+
+use brush_render::camera::Camera;
+use image::{metadata::Orientation, ImageDecoder, ImageReader};
+
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_FOCAL_LENGTH: u16 = 0x920A;
+const TAG_FOCAL_LENGTH_35MM: u16 = 0xA405;
+const TAG_FOCAL_PLANE_X_RES: u16 = 0xA20E;
+const TAG_FOCAL_PLANE_RES_UNIT: u16 = 0xA210;
+const TAG_PIXEL_X_DIMENSION: u16 = 0xA002;
+
+// Diagonal of a full frame 36x24mm sensor.
+const FULL_FRAME_DIAGONAL_MM: f64 = 43.266_615;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ExifFocal {
+    pub(crate) focal_mm: Option<f64>,
+    pub(crate) focal_35mm: Option<f64>,
+    pub(crate) focal_plane_x_res: Option<f64>,
+    pub(crate) focal_plane_res_unit: Option<u32>,
+    pub(crate) pixel_x_dimension: Option<u32>,
+}
+
+struct TiffReader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl TiffReader<'_> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    // Read the numeric value of an IFD entry. Only handles the types needed here.
+    fn entry_value(&self, entry: usize) -> Option<f64> {
+        let typ = self.u16(entry + 2)?;
+        match typ {
+            // SHORT
+            3 => self.u16(entry + 8).map(|v| v as f64),
+            // LONG
+            4 => self.u32(entry + 8).map(|v| v as f64),
+            // RATIONAL
+            5 => {
+                let offset = self.u32(entry + 8)? as usize;
+                let num = self.u32(offset)? as f64;
+                let den = self.u32(offset + 4)? as f64;
+                (den != 0.0).then(|| num / den)
+            }
+            _ => None,
+        }
+    }
+
+    // Read the entries of an IFD and the sub IFDs it points to. `visited` holds the IFDs
+    // read so far, so a corrupt file with a cycle of IFDs can't recurse forever.
+    fn read_ifd(
+        &self,
+        offset: usize,
+        focal: &mut ExifFocal,
+        visited: &mut Vec<usize>,
+    ) -> Option<()> {
+        if visited.contains(&offset) {
+            return None;
+        }
+        visited.push(offset);
+
+        let count = self.u16(offset)? as usize;
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let tag = self.u16(entry)?;
+            match tag {
+                TAG_EXIF_IFD => {
+                    let sub_ifd = self.u32(entry + 8)? as usize;
+                    self.read_ifd(sub_ifd, focal, visited);
+                }
+                TAG_FOCAL_LENGTH => focal.focal_mm = self.entry_value(entry),
+                TAG_FOCAL_LENGTH_35MM => focal.focal_35mm = self.entry_value(entry),
+                TAG_FOCAL_PLANE_X_RES => focal.focal_plane_x_res = self.entry_value(entry),
+                TAG_FOCAL_PLANE_RES_UNIT => {
+                    focal.focal_plane_res_unit = self.entry_value(entry).map(|v| v as u32);
+                }
+                TAG_PIXEL_X_DIMENSION => {
+                    focal.pixel_x_dimension = self.entry_value(entry).map(|v| v as u32);
+                }
+                _ => {}
+            }
+        }
+        Some(())
+    }
+}
+
+pub(crate) fn parse_exif_focal(exif: &[u8]) -> Option<ExifFocal> {
+    // Some decoders include the APP1 header, some don't.
+    let data = exif.strip_prefix(b"Exif\0\0").unwrap_or(exif);
+
+    let little_endian = match data.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let reader = TiffReader {
+        data,
+        little_endian,
+    };
+    if reader.u16(2)? != 42 {
+        return None;
+    }
+    let ifd0 = reader.u32(4)? as usize;
+    let mut focal = ExifFocal::default();
+    reader.read_ifd(ifd0, &mut focal, &mut vec![])?;
+    Some(focal)
+}
+
+impl ExifFocal {
+    /// Estimate the horizontal focal length in pixels for an image of the given size.
+    pub(crate) fn focal_pixels(&self, width: u32, height: u32) -> Option<f64> {
+        // Prefer the focal plane resolution, as that is an actual measurement of the sensor.
+        if let (Some(focal_mm), Some(res)) = (self.focal_mm, self.focal_plane_x_res) {
+            let unit_mm = match self.focal_plane_res_unit.unwrap_or(2) {
+                3 => 10.0,
+                4 => 1.0,
+                5 => 0.001,
+                _ => 25.4,
+            };
+            let px_per_mm = res / unit_mm;
+            // The resolution is relative to the original capture, which might have been resized.
+            let scale = self
+                .pixel_x_dimension
+                .filter(|&w| w > 0)
+                .map_or(1.0, |w| width as f64 / w as f64);
+            let focal = focal_mm * px_per_mm * scale;
+            if focal.is_finite() && focal > 0.0 {
+                return Some(focal);
+            }
+        }
+
+        // Otherwise use the 35mm equivalent focal length, which is defined relative to the diagonal.
+        let focal_35mm = self.focal_35mm.filter(|&f| f > 0.0)?;
+        let diagonal_px = (width as f64).hypot(height as f64);
+        Some(focal_35mm / FULL_FRAME_DIAGONAL_MM * diagonal_px)
+    }
+}
+
+/// Read the focal length in pixels from the EXIF data of an encoded image, if present.
+pub(crate) fn exif_focal_pixels(img_bytes: &[u8], width: u32, height: u32) -> Option<f64> {
+    let mut decoder = ImageReader::new(Cursor::new(img_bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_decoder()
+        .ok()?;
+    let exif = decoder.exif_metadata().ok()??;
+    parse_exif_focal(&exif)?.focal_pixels(width, height)
+}
+
+/// How many clockwise quarter turns show an image upright, following its EXIF orientation.
+/// Mirrored orientations are `None`, as the camera can't be mirrored to match.
+pub(crate) fn orientation_quarter_turns(orientation: Orientation) -> Option<u32> {
+    match orientation {
+        Orientation::NoTransforms => Some(0),
+        Orientation::Rotate90 => Some(1),
+        Orientation::Rotate180 => Some(2),
+        Orientation::Rotate270 => Some(3),
+        _ => None,
+    }
+}
+
+/// The camera of an image that is rotated `quarter_turns` clockwise, from the camera of the
+/// image as stored. Calibrations are made on the stored pixels, so loaders build the camera
+/// from those and rotate it along with the image.
+pub(crate) fn rotate_camera(camera: Camera, quarter_turns: u32) -> Camera {
+    let turns = quarter_turns % 4;
+    if turns == 0 {
+        return camera;
+    }
+    // Turning the image clockwise turns the camera the other way around its view direction,
+    // as image y points down.
+    let roll = glam::Quat::from_rotation_z(-(turns as f32) * std::f32::consts::FRAC_PI_2);
+    let mut center = camera.center_uv - 0.5;
+    for _ in 0..turns {
+        center = glam::vec2(-center.y, center.x);
+    }
+    let (fov_x, fov_y) = if turns % 2 == 1 {
+        (camera.fov_y, camera.fov_x)
+    } else {
+        (camera.fov_x, camera.fov_y)
+    };
+    Camera {
+        fov_x,
+        fov_y,
+        center_uv: center + 0.5,
+        rotation: camera.rotation * roll,
+        rolling_shutter: camera.rolling_shutter.map(|mut rs| {
+            rs.end_rotation = rs.end_rotation * roll;
+            rs
+        }),
+        ..camera
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_render::camera::Camera;
+    use glam::{vec2, Quat, Vec3};
+
+    use super::{
+        parse_exif_focal, rotate_camera, ExifFocal, TAG_EXIF_IFD, TAG_FOCAL_LENGTH,
+        TAG_FOCAL_LENGTH_35MM,
+    };
+
+    // A little endian TIFF header, followed by IFDs of (tag, type, value) entries at the given
+    // offsets. Rationals are stored at the end, with a denominator of 10.
+    fn tiff(ifds: &[(usize, &[(u16, u16, u32)])]) -> Vec<u8> {
+        let mut data = b"II".to_vec();
+        data.extend_from_slice(&42u16.to_le_bytes());
+        data.extend_from_slice(&(ifds[0].0 as u32).to_le_bytes());
+        data.resize(256, 0);
+        for &(offset, entries) in ifds {
+            data[offset..offset + 2].copy_from_slice(&(entries.len() as u16).to_le_bytes());
+            for (i, &(tag, typ, value)) in entries.iter().enumerate() {
+                let value = if typ == 5 {
+                    let at = data.len() as u32;
+                    data.extend_from_slice(&value.to_le_bytes());
+                    data.extend_from_slice(&10u32.to_le_bytes());
+                    at
+                } else {
+                    value
+                };
+                let entry = offset + 2 + i * 12;
+                data[entry..entry + 2].copy_from_slice(&tag.to_le_bytes());
+                data[entry + 2..entry + 4].copy_from_slice(&typ.to_le_bytes());
+                data[entry + 4..entry + 8].copy_from_slice(&1u32.to_le_bytes());
+                data[entry + 8..entry + 12].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn focal_length_from_exif_ifd() {
+        let data = tiff(&[
+            (8, &[(TAG_EXIF_IFD, 4, 64)]),
+            (
+                64,
+                &[(TAG_FOCAL_LENGTH, 5, 45), (TAG_FOCAL_LENGTH_35MM, 3, 28)],
+            ),
+        ]);
+        let mut with_header = b"Exif\0\0".to_vec();
+        with_header.extend_from_slice(&data);
+
+        for data in [data, with_header] {
+            let focal = parse_exif_focal(&data).expect("Valid exif");
+            assert_eq!(focal.focal_mm, Some(4.5));
+            assert_eq!(focal.focal_35mm, Some(28.0));
+        }
+        assert!(parse_exif_focal(b"XX\0\0").is_none());
+    }
+
+    #[test]
+    fn cyclic_ifds_terminate() {
+        // The EXIF IFDs point at each other.
+        let data = tiff(&[
+            (8, &[(TAG_EXIF_IFD, 4, 64)]),
+            (64, &[(TAG_FOCAL_LENGTH, 5, 45), (TAG_EXIF_IFD, 4, 128)]),
+            (128, &[(TAG_EXIF_IFD, 4, 64)]),
+        ]);
+        let focal = parse_exif_focal(&data).expect("Valid exif");
+        assert_eq!(focal.focal_mm, Some(4.5));
+    }
+
+    #[test]
+    fn focal_pixels_from_sensor_or_35mm() {
+        // A 35mm equivalent of the full frame diagonal spans the image diagonal.
+        let focal = ExifFocal {
+            focal_35mm: Some(43.266_615),
+            ..Default::default()
+        };
+        let px = focal.focal_pixels(3000, 4000).expect("Has a focal length");
+        assert!((px - 5000.0).abs() < 1e-3);
+
+        // The focal plane resolution wins, scaled to the size of the image.
+        let focal = ExifFocal {
+            focal_mm: Some(4.0),
+            focal_35mm: Some(28.0),
+            focal_plane_x_res: Some(250.0),
+            focal_plane_res_unit: Some(4),
+            pixel_x_dimension: Some(4000),
+        };
+        let px = focal.focal_pixels(2000, 1500).expect("Has a focal length");
+        assert!((px - 500.0).abs() < 1e-9);
+
+        assert!(ExifFocal::default().focal_pixels(100, 100).is_none());
+    }
+
+    #[test]
+    fn rotated_camera_sees_the_same_rays() {
+        let camera = Camera::new(
+            Vec3::new(1.0, 2.0, 3.0),
+            Quat::from_rotation_y(0.3),
+            0.9,
+            0.6,
+            vec2(0.45, 0.55),
+        );
+        // A point of the stored image, and where it ends up after each quarter turn.
+        let uv = vec2(0.2, 0.1);
+        let mut rotated_uv = uv;
+        for turns in 0..4 {
+            let rotated = rotate_camera(camera.clone(), turns);
+            let (_, expected) = camera.ray(uv);
+            let (_, dir) = rotated.ray(rotated_uv);
+            assert!(dir.abs_diff_eq(expected, 1e-5), "{turns} turns");
+            rotated_uv = vec2(1.0 - rotated_uv.y, rotated_uv.x);
+        }
+    }
+}
@@ -7,19 +7,25 @@ use std::{
 use super::{DataStream, LoadDatasetArgs};
 use crate::{
     brush_vfs::{normalized_path, BrushVfs},
+    decode_mask, eval_split,
+    exif::rotate_camera,
     splat_import::SplatMessage,
-    stream_fut_parallel, Dataset,
+    view_image, Dataset, DatasetError, ReconstructionStats,
 };
 use anyhow::Result;
 use async_fn_stream::try_fn_stream;
 use brush_render::{
-    camera::{self, Camera},
-    gaussian_splats::Splats,
+    camera::Camera,
+    gaussian_splats::{inverse_sigmoid, Splats},
     render::rgb_to_sh,
     Backend,
 };
+use brush_train::ground::GroundPlane;
+use brush_train::image::image_sharpness;
 use brush_train::scene::SceneView;
+use colmap_reader::{database::Database, Image, Point3D};
 use glam::Vec3;
+use std::collections::HashMap;
 use tokio::io::AsyncReadExt;
 use tokio_stream::StreamExt;
 
@@ -36,44 +42,206 @@ fn find_base_path(archive: &BrushVfs, search_path: &str) -> Option<PathBuf> {
     None
 }
 
+// A sparse COLMAP model, eg. `sparse/0`.
+#[derive(Clone, Debug)]
+struct ColmapModel {
+    // Root of the dataset, which contains the sparse folder.
+    base_path: PathBuf,
+    model_path: PathBuf,
+    // Index of the model, eg. 1 for `sparse/1`.
+    index: usize,
+    is_binary: bool,
+}
+
+impl ColmapModel {
+    fn file(&self, name: &str) -> PathBuf {
+        let ext = if self.is_binary { "bin" } else { "txt" };
+        self.model_path.join(format!("{name}.{ext}"))
+    }
+
+    async fn read_images(&self, archive: &mut BrushVfs) -> Result<HashMap<i32, Image>> {
+        let img_file = archive.open_path(&self.file("images")).await?;
+        let mut buf_reader = tokio::io::BufReader::new(img_file);
+        Ok(colmap_reader::read_images(&mut buf_reader, self.is_binary).await?)
+    }
+}
+
+// Find all sparse models, in `sparse/N` folders, or directly in `sparse`.
+fn find_models(archive: &BrushVfs) -> Vec<ColmapModel> {
+    let mut models: Vec<_> = archive
+        .file_names()
+        .filter_map(|file| {
+            let path = normalized_path(file);
+            let is_binary = match path.file_name()?.to_str()? {
+                "cameras.bin" => true,
+                "cameras.txt" => false,
+                _ => return None,
+            };
+            let model_path = path.parent()?.to_owned();
+
+            let (base_path, index) = if model_path.file_name()? == "sparse" {
+                (model_path.parent()?.to_owned(), 0)
+            } else {
+                let index = model_path.file_name()?.to_str()?.parse().ok()?;
+                let sparse = model_path.parent()?;
+                if sparse.file_name()? != "sparse" {
+                    return None;
+                }
+                (sparse.parent()?.to_owned(), index)
+            };
+
+            Some(ColmapModel {
+                base_path,
+                model_path,
+                index,
+                is_binary,
+            })
+        })
+        .collect();
+    // Prefer binary models if both are present.
+    models.sort_by_key(|m| (m.index, !m.is_binary));
+    models.dedup_by_key(|m| m.index);
+    models
+}
+
+// Pick the model at `model_index`, or otherwise the model with the most images.
+async fn select_model(archive: &mut BrushVfs, model_index: Option<usize>) -> Result<ColmapModel> {
+    let models = find_models(archive);
+
+    if models.is_empty() {
+        if find_base_path(archive, "database.db").is_some() {
+            anyhow::bail!(DatasetError::MissingSparseModel)
+        }
+        anyhow::bail!(DatasetError::MissingCameras)
+    }
+
+    if let Some(index) = model_index {
+        return models
+            .iter()
+            .find(|m| m.index == index)
+            .cloned()
+            .ok_or_else(|| {
+                let available: Vec<_> = models.iter().map(|m| m.index).collect();
+                anyhow::anyhow!("COLMAP model {index} not found, available models: {available:?}")
+            });
+    }
+
+    if models.len() == 1 {
+        return Ok(models[0].clone());
+    }
+
+    let mut sizes = vec![];
+    for model in models {
+        let num_images = model.read_images(archive).await.map_or(0, |i| i.len());
+        log::info!("COLMAP model {} has {num_images} images", model.index);
+        sizes.push((num_images, model));
+    }
+    // Pick the first model of the largest ones.
+    let (_, model) = sizes
+        .into_iter()
+        .rev()
+        .max_by_key(|(num_images, _)| *num_images)
+        .expect("At least one model");
+    log::info!("Using largest COLMAP model {}", model.index);
+    Ok(model)
+}
+
+fn path_key(path: &Path) -> String {
+    normalized_path(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+        .to_lowercase()
+}
+
+// Finds image files in the images folder, tolerating differences in case and nested
+// directories. The files are indexed by name once, so every image is found quickly.
+struct ImageFinder {
+    // Files in the images folder by their lowercase file name. When several files have the
+    // same name, the one with the shortest path is first, so the same file is always picked.
+    by_name: HashMap<String, Vec<(String, PathBuf)>>,
+}
+
+impl ImageFinder {
+    fn new<'a>(files: impl Iterator<Item = &'a Path>, images_dir: &Path) -> Self {
+        let prefix = format!("{}/", path_key(images_dir));
+        let mut by_name: HashMap<String, Vec<(String, PathBuf)>> = HashMap::new();
+        for path in files {
+            let key = path_key(path);
+            if !key.starts_with(&prefix) {
+                continue;
+            }
+            let name = key.rsplit('/').next().unwrap_or(&key).to_owned();
+            by_name
+                .entry(name)
+                .or_default()
+                .push((key, path.to_owned()));
+        }
+        for files in by_name.values_mut() {
+            files.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        }
+        Self { by_name }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    fn find(&self, name: &str) -> Option<PathBuf> {
+        let name_key = path_key(Path::new(name));
+        let files = self.by_name.get(name_key.rsplit('/').next()?)?;
+
+        // A path ending in the image name, the exact path being the shortest, or otherwise a
+        // file with the same name.
+        let suffix = format!("/{name_key}");
+        files
+            .iter()
+            .find(|(key, _)| key.ends_with(&suffix))
+            .or_else(|| files.first())
+            .map(|(_, path)| path.clone())
+    }
+}
+
 async fn read_views(
     archive: BrushVfs,
+    model: &ColmapModel,
     load_args: &LoadDatasetArgs,
 ) -> Result<Vec<impl Future<Output = Result<SceneView>>>> {
     log::info!("Loading colmap dataset");
     let mut archive = archive;
-
-    let (is_binary, base_path) =
-        if let Some(path) = find_base_path(&archive, "sparse/0/cameras.bin") {
-            (true, path)
-        } else if let Some(path) = find_base_path(&archive, "sparse/0/cameras.txt") {
-            (false, path)
-        } else {
-            anyhow::bail!("No COLMAP data found (either text or binary.)")
-        };
-
-    let (cam_path, img_path) = if is_binary {
-        (
-            base_path.join("sparse/0/cameras.bin"),
-            base_path.join("sparse/0/images.bin"),
-        )
-    } else {
-        (
-            base_path.join("sparse/0/cameras.txt"),
-            base_path.join("sparse/0/images.txt"),
-        )
-    };
+    let is_binary = model.is_binary;
+    let base_path = model.base_path.clone();
 
     let cam_model_data = {
-        let mut cam_file = archive.open_path(&cam_path).await?;
-        colmap_reader::read_cameras(&mut cam_file, is_binary).await?
+        let mut cam_file = archive.open_path(&model.file("cameras")).await?;
+        colmap_reader::read_cameras(&mut cam_file, is_binary)
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::Unsupported => {
+                    DatasetError::UnsupportedCameraModel(e.to_string())
+                }
+                _ => DatasetError::Io(e),
+            })?
     };
 
-    let img_infos = {
-        let img_file = archive.open_path(&img_path).await?;
-        let mut buf_reader = tokio::io::BufReader::new(img_file);
-        colmap_reader::read_images(&mut buf_reader, is_binary).await?
+    let img_infos = model.read_images(&mut archive).await?;
+
+    let images_dir = {
+        let dir = load_args.images_dir.as_deref().unwrap_or("images");
+        match load_args.image_downscale {
+            Some(scale) if scale > 1 => format!("{dir}_{scale}"),
+            _ => dir.to_owned(),
+        }
     };
+    let images_dir = base_path.join(images_dir);
+
+    let image_finder = ImageFinder::new(archive.file_names(), &images_dir);
+    if image_finder.is_empty() {
+        anyhow::bail!(DatasetError::PathNotFound(images_dir));
+    }
+    let image_finder = Arc::new(image_finder);
+
+    // Masks follow the COLMAP convention of `masks/{image name}.png`.
+    let masks_dir = base_path.join("masks");
 
     let mut img_info_list = img_infos.into_iter().collect::<Vec<_>>();
 
@@ -84,26 +252,24 @@ async fn read_views(
     // it is consistent
     img_info_list.sort_by_key(|key_img| key_img.0);
 
+    let host_cache = load_args.host_image_cache();
     let handles = img_info_list
         .into_iter()
         .take(load_args.max_frames.unwrap_or(usize::MAX))
         .map(move |(_, img_info)| {
             let cam_data = cam_model_data[&img_info.camera_id].clone();
             let load_args = load_args.clone();
-            let base_path = base_path.clone();
+            let host_cache = host_cache.clone();
+            let images_dir = images_dir.clone();
+            let masks_dir = masks_dir.clone();
+            let image_finder = image_finder.clone();
             let mut archive = archive.clone();
 
             // Create a future to handle loading the image.
             async move {
-                let focal = cam_data.focal();
-
-                let fovx = camera::focal_to_fov(focal.0, cam_data.width as u32);
-                let fovy = camera::focal_to_fov(focal.1, cam_data.height as u32);
-
-                let center = cam_data.principal_point();
-                let center_uv = center / glam::vec2(cam_data.width as f32, cam_data.height as f32);
-
-                let img_path = base_path.join(format!("images/{}", img_info.name));
+                let img_path = image_finder
+                    .find(&img_info.name)
+                    .ok_or_else(|| DatasetError::PathNotFound(images_dir.join(&img_info.name)))?;
 
                 let mut img_bytes = vec![];
                 archive
@@ -111,24 +277,53 @@ async fn read_views(
                     .await?
                     .read_to_end(&mut img_bytes)
                     .await?;
-                let mut img = image::load_from_memory(&img_bytes)?;
-
-                if let Some(max) = load_args.max_resolution {
-                    img = crate::clamp_img_to_max_size(img, max);
-                }
+                let (img, img_bytes, quarter_turns) = crate::decode_image(
+                    img_bytes,
+                    &img_path,
+                    load_args.max_resolution,
+                    load_args.photometric,
+                )
+                .await?;
 
-                // Convert w2c to c2w.
-                let world_to_cam =
-                    glam::Affine3A::from_rotation_translation(img_info.quat, img_info.tvec);
-                let cam_to_world = world_to_cam.inverse();
-                let (_, quat, translation) = cam_to_world.to_scale_rotation_translation();
-
-                let camera = Camera::new(translation, quat, fovx, fovy, center_uv);
+                let focal = cam_data.focal();
+                let camera = Camera::from_focal(
+                    glam::vec2(focal.0 as f32, focal.1 as f32),
+                    cam_data.principal_point(),
+                    glam::uvec2(cam_data.width as u32, cam_data.height as u32),
+                )
+                .with_colmap_pose(img_info.quat, img_info.tvec);
+                let camera = rotate_camera(camera, quarter_turns);
+
+                let mask_path = image_finder
+                    .find(&masks_dir, &format!("{}.png", img_info.name))
+                    .or_else(|| image_finder.find(&masks_dir, &img_info.name));
+                let mask = if let Some(mask_path) = mask_path {
+                    let mut mask_bytes = vec![];
+                    archive
+                        .open_path(&mask_path)
+                        .await?
+                        .read_to_end(&mut mask_bytes)
+                        .await?;
+                    Some(Arc::new(decode_mask(
+                        &mask_bytes,
+                        &mask_path,
+                        &img,
+                        quarter_turns,
+                    )?))
+                } else {
+                    None
+                };
 
                 let view = SceneView {
                     name: img_path.to_string_lossy().to_string(),
                     camera,
-                    image: Arc::new(img),
+                    sharpness: image_sharpness(&img),
+                    image: view_image(img, img_bytes, &img_path, &load_args, host_cache.as_ref()),
+                    mask,
+                    camera_id: img_info.camera_id as u32,
+                    capture_id: 0,
+                    features: None,
+                    depth: None,
                 };
                 Ok(view)
             }
@@ -138,12 +333,139 @@ async fn read_views(
     Ok(handles)
 }
 
+// COLMAP databases are often several gigabytes because of the descriptors. Databases on disk
+// are read a page at a time, but others have to be read to memory, so skip them when larger
+// than this.
+const MAX_DATABASE_READ_SIZE: u64 = 256 * 1024 * 1024;
+
+async fn read_database(archive: &mut BrushVfs) -> Option<Database> {
+    let path = find_base_path(archive, "database.db")?.join("database.db");
+
+    let read = async {
+        #[cfg(not(target_family = "wasm"))]
+        if let BrushVfs::Directory(dir, _) = archive {
+            let file = std::fs::File::open(dir.join(&path))?;
+            let db = colmap_reader::database::read_database(std::io::BufReader::new(file))?;
+            return anyhow::Ok(db);
+        }
+
+        let too_large = || {
+            anyhow::anyhow!(
+                "database is larger than {} MB, skipping it",
+                MAX_DATABASE_READ_SIZE / (1024 * 1024)
+            )
+        };
+        if archive
+            .file_size(&path)
+            .is_some_and(|size| size > MAX_DATABASE_READ_SIZE)
+        {
+            return Err(too_large());
+        }
+        let mut bytes = vec![];
+        archive
+            .open_path(&path)
+            .await?
+            .take(MAX_DATABASE_READ_SIZE + 1)
+            .read_to_end(&mut bytes)
+            .await?;
+        if bytes.len() as u64 > MAX_DATABASE_READ_SIZE {
+            return Err(too_large());
+        }
+        anyhow::Ok(colmap_reader::database::read_database(
+            std::io::Cursor::new(bytes),
+        )?)
+    };
+
+    match read.await {
+        Ok(db) => {
+            log::info!(
+                "Read COLMAP database with {} images and {} matched pairs",
+                db.images.len(),
+                db.num_verified_pairs
+            );
+            Some(db)
+        }
+        Err(e) => {
+            log::warn!("Failed to read COLMAP database: {e}");
+            None
+        }
+    }
+}
+
+fn reconstruction_stats(
+    points: &HashMap<i64, Point3D>,
+    database: Option<&Database>,
+) -> ReconstructionStats {
+    let num_points = points.len().max(1) as f32;
+    let mut stats = ReconstructionStats {
+        num_points: points.len(),
+        mean_track_length: points
+            .values()
+            .map(|p| p.image_ids.len() as f32)
+            .sum::<f32>()
+            / num_points,
+        mean_reprojection_error: points.values().map(|p| p.error as f32).sum::<f32>() / num_points,
+        ..Default::default()
+    };
+
+    if let Some(db) = database {
+        let num_images = db.images.len().max(1) as f32;
+        stats.num_database_images = db.images.len();
+        stats.num_verified_pairs = db.num_verified_pairs;
+        stats.mean_keypoints = db
+            .images
+            .values()
+            .map(|i| i.num_keypoints as f32)
+            .sum::<f32>()
+            / num_images;
+        stats.mean_matches = db
+            .images
+            .values()
+            .map(|i| i.num_matches as f32)
+            .sum::<f32>()
+            / num_images;
+    }
+    stats
+}
+
+// Initial opacity of a point, based on how well it is supported by the reconstruction.
+// Points seen in many well matched images with a low reprojection error start out more opaque.
+fn point_confidence_opacity(point: &Point3D, database: &Database) -> f32 {
+    let track = 1.0 - (-(point.image_ids.len() as f32 - 1.0).max(0.0) / 4.0).exp();
+    let error = 1.0 / (1.0 + point.error as f32);
+    let matched = if point.image_ids.is_empty() {
+        0.0
+    } else {
+        point
+            .image_ids
+            .iter()
+            .filter_map(|id| database.images.get(&(*id as i64)))
+            .map(|img| img.match_ratio().min(1.0))
+            .sum::<f32>()
+            / point.image_ids.len() as f32
+    };
+    0.05 + 0.15 * track * error * matched
+}
+
 pub(crate) async fn load_dataset<B: Backend>(
     mut archive: BrushVfs,
     load_args: &LoadDatasetArgs,
     device: &B::Device,
 ) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
-    let mut handles = read_views(archive.clone(), load_args).await?;
+    let model = select_model(&mut archive, load_args.model_index).await?;
+    let mut handles = read_views(archive.clone(), &model, load_args).await?;
+
+    // Extract COLMAP sfm points.
+    let points_data = {
+        let mut points_file = archive.open_path(&model.file("points3D")).await?;
+        colmap_reader::read_points3d(&mut points_file, model.is_binary).await
+    };
+
+    let database = read_database(&mut archive).await;
+    let reconstruction = points_data
+        .as_ref()
+        .ok()
+        .map(|points| reconstruction_stats(points, database.as_ref()));
 
     if let Some(subsample) = load_args.subsample_frames {
         handles = handles.into_iter().step_by(subsample as usize).collect();
@@ -155,48 +477,45 @@ pub(crate) async fn load_dataset<B: Backend>(
     let load_args = load_args.clone();
     let device = device.clone();
 
+    let is_eval = eval_split(handles.len(), &load_args);
+    let total = handles.len();
+    let progress = load_args.progress.clone();
+
+    let sfm_points: Vec<Vec3> = points_data
+        .as_ref()
+        .map(|points| points.values().map(|p| p.xyz).collect())
+        .unwrap_or_default();
+    let mut ground = None;
+    let mut ground_views = 0;
+
     let mut i = 0;
-    let stream = stream_fut_parallel(handles).map(move |view| {
+    let stream = brush_tasks::stream_parallel(handles).map(move |view| {
+        progress.report("Loading views", i + 1, total);
         if let Ok(view) = view {
-            // I cannot wait for let chains.
-            if let Some(eval_period) = load_args.eval_split_every {
-                if i % eval_period == 0 {
-                    log::info!("Adding split eval view");
-                    eval_views.push(view);
-                } else {
-                    train_views.push(view);
-                }
+            if is_eval[i] {
+                log::info!("Adding split eval view");
+                eval_views.push(view);
             } else {
                 train_views.push(view);
             }
         }
 
         i += 1;
-        Ok(Dataset::from_views(train_views.clone(), eval_views.clone()))
-    });
 
-    let init_stream = try_fn_stream(|emitter| async move {
-        let (is_binary, base_path) =
-            if let Some(path) = find_base_path(&archive, "sparse/0/cameras.bin") {
-                (true, path)
-            } else if let Some(path) = find_base_path(&archive, "sparse/0/cameras.txt") {
-                (false, path)
-            } else {
-                anyhow::bail!("No COLMAP data found (either text or binary.")
-            };
-
-        let points_path = if is_binary {
-            base_path.join("sparse/0/points3D.bin")
-        } else {
-            base_path.join("sparse/0/points3D.txt")
-        };
+        // Cameras refine the up direction as they load, but not by much after the first few.
+        let num_views = train_views.len();
+        if num_views != ground_views && (num_views.is_power_of_two() || i == total) {
+            ground_views = num_views;
+            let cameras: Vec<_> = train_views.iter().map(|v| v.camera.clone()).collect();
+            ground = GroundPlane::estimate(&sfm_points, &cameras);
+        }
 
-        // Extract COLMAP sfm points.
-        let points_data = {
-            let mut points_file = archive.open_path(&points_path).await?;
-            colmap_reader::read_points3d(&mut points_file, is_binary).await
-        };
+        Ok(Dataset::from_views(train_views.clone(), eval_views.clone())
+            .with_reconstruction(reconstruction.clone())
+            .with_ground(ground))
+    });
 
+    let init_stream = try_fn_stream(|emitter| async move {
         // Ignore empty points data.
         if let Ok(points_data) = points_data {
             if !points_data.is_empty() {
@@ -214,15 +533,31 @@ pub(crate) async fn load_dataset<B: Backend>(
                     })
                     .collect();
 
+                // Weight the initial opacity by the confidence of each point.
+                let mut opacities: Option<Vec<f32>> = database.as_ref().map(|db| {
+                    points_data
+                        .values()
+                        .map(|p| inverse_sigmoid(point_confidence_opacity(p, db)))
+                        .collect()
+                });
+
                 // Other dataloaders handle subsampling in the ply import. Here just
                 // do it manually, maybe nice to unify at some point.
                 if let Some(subsample) = load_args.subsample_points {
                     positions = positions.into_iter().step_by(subsample as usize).collect();
                     colors = colors.into_iter().step_by(subsample as usize * 3).collect();
+                    opacities =
+                        opacities.map(|o| o.into_iter().step_by(subsample as usize).collect());
                 }
 
-                let init_splat =
-                    Splats::from_raw(&positions, None, None, Some(&colors), None, &device);
+                let init_splat = Splats::from_raw(
+                    &positions,
+                    None,
+                    None,
+                    Some(&colors),
+                    opacities.as_deref(),
+                    &device,
+                );
                 emitter
                     .emit(SplatMessage {
                         meta: crate::splat_import::SplatMetadata {
@@ -242,3 +577,46 @@ pub(crate) async fn load_dataset<B: Backend>(
 
     Ok((Box::pin(init_stream), Box::pin(stream)))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::ImageFinder;
+
+    #[test]
+    fn finds_images() {
+        let files = [
+            "scene/Images/b/IMG_1.JPG",
+            "scene/images/a/img_1.jpg",
+            "scene/images/IMG_2.jpg",
+            "scene/images/nested/cam0/img_3.jpg",
+            "scene/images/other/img_3.jpg",
+            "scene/sparse/0/cameras.bin",
+        ]
+        .map(PathBuf::from);
+        let finder = ImageFinder::new(files.iter().map(|p| p.as_path()), Path::new("scene/images"));
+        let find = |name| finder.find(name).map(|p| p.to_string_lossy().into_owned());
+
+        // Exact matches ignore case.
+        assert_eq!(find("img_2.JPG").as_deref(), Some("scene/images/IMG_2.jpg"));
+        // A nested path matches its end, before other files with the same name.
+        assert_eq!(
+            find("cam0/img_3.jpg").as_deref(),
+            Some("scene/images/nested/cam0/img_3.jpg")
+        );
+        // Ties between files with the same name are broken by the shortest path, then by path.
+        assert_eq!(
+            find("img_3.jpg").as_deref(),
+            Some("scene/images/other/img_3.jpg")
+        );
+        assert_eq!(
+            find("img_1.jpg").as_deref(),
+            Some("scene/images/a/img_1.jpg")
+        );
+        assert_eq!(find("missing.jpg"), None);
+        assert!(
+            ImageFinder::new(files.iter().map(|p| p.as_path()), Path::new("photos")).is_empty()
+        );
+    }
+}
@@ -1,62 +1,160 @@
 use crate::{
     brush_vfs::BrushVfs,
-    splat_import::{load_splat_from_ply, SplatMessage},
-    Dataset, LoadDatasetArgs,
+    point_cloud::{filter_point_cloud, PointCloudFilter},
+    splat_import::{load_splat_from_ply, SplatMessage, SplatMetadata},
+    Dataset, DatasetError, LoadDatasetArgs,
 };
+use async_fn_stream::try_fn_stream;
 use brush_render::Backend;
 use std::path::Path;
+use tokio_stream::StreamExt;
 
 pub mod colmap;
 pub mod nerfstudio;
+mod replica;
+mod rgbd;
+mod scannet;
+pub mod source;
+mod tum;
+pub mod webdataset;
 
 #[cfg(target_family = "wasm")]
 mod data_stream {
-    use anyhow::Result;
     use std::pin::Pin;
     use tokio_stream::Stream;
-    pub type DataStream<T> = Pin<Box<dyn Stream<Item = Result<T>> + 'static>>;
+    pub type DataStream<T, E = anyhow::Error> = Pin<Box<dyn Stream<Item = Result<T, E>> + 'static>>;
 }
 
 #[cfg(not(target_family = "wasm"))]
 mod data_stream {
-    use anyhow::Result;
     use std::pin::Pin;
     use tokio_stream::Stream;
-    pub type DataStream<T> = Pin<Box<dyn Stream<Item = Result<T>> + Send + 'static>>;
+    pub type DataStream<T, E = anyhow::Error> =
+        Pin<Box<dyn Stream<Item = Result<T, E>> + Send + 'static>>;
 }
 
 pub use data_stream::*;
 
+/// Load several captures of the same scene as one dataset, see [`Dataset::union`]. Captures
+/// are loaded one after another, and the initial splats come from the first capture.
+pub async fn load_captures<B: Backend>(
+    vfs: Vec<BrushVfs>,
+    load_args: &LoadDatasetArgs,
+    device: &B::Device,
+) -> Result<
+    (
+        DataStream<SplatMessage<B>, DatasetError>,
+        DataStream<Dataset, DatasetError>,
+    ),
+    DatasetError,
+> {
+    let mut init_stream = None;
+    let mut data_streams = vec![];
+    for vfs in vfs {
+        let (splats, data) = load_dataset(vfs, load_args, device).await?;
+        init_stream.get_or_insert(splats);
+        data_streams.push(data);
+    }
+    let init_stream = init_stream.ok_or(DatasetError::UnknownFormat)?;
+
+    if data_streams.len() == 1 {
+        return Ok((init_stream, data_streams.remove(0)));
+    }
+
+    let stream = try_fn_stream(|emitter| async move {
+        let mut captures = vec![Dataset::empty(); data_streams.len()];
+        for (i, mut stream) in data_streams.into_iter().enumerate() {
+            while let Some(dataset) = stream.next().await {
+                captures[i] = dataset?;
+                emitter.emit(Dataset::union(&captures)).await;
+            }
+        }
+        Ok::<(), DatasetError>(())
+    });
+
+    Ok((init_stream, Box::pin(stream)))
+}
+
+// Filter the final initial splats. The partial splats while loading are skipped, filtering
+// them too would only slow down loading.
+fn filter_init_stream<B: Backend>(
+    mut init_stream: DataStream<SplatMessage<B>>,
+    filter: PointCloudFilter,
+) -> DataStream<SplatMessage<B>> {
+    Box::pin(try_fn_stream(|emitter| async move {
+        let mut last = None;
+        while let Some(message) = init_stream.next().await {
+            last = Some(message?);
+        }
+        if let Some(message) = last {
+            let splats = filter_point_cloud(message.splats, &filter).await?;
+            let meta = SplatMetadata {
+                total_splats: splats.num_splats(),
+                ..message.meta
+            };
+            emitter.emit(SplatMessage { meta, splats }).await;
+        }
+        Ok::<(), anyhow::Error>(())
+    }))
+}
+
 pub async fn load_dataset<B: Backend>(
     mut vfs: BrushVfs,
     load_args: &LoadDatasetArgs,
     device: &B::Device,
-) -> anyhow::Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
-    let stream = nerfstudio::read_dataset(vfs.clone(), load_args, device).await;
-
-    let stream = match stream {
-        Ok(s) => Ok(s),
-        Err(_) => colmap::load_dataset::<B>(vfs.clone(), load_args, device).await,
+) -> Result<
+    (
+        DataStream<SplatMessage<B>, DatasetError>,
+        DataStream<Dataset, DatasetError>,
+    ),
+    DatasetError,
+> {
+    let stream = if let Some(source) = source::find_source(&vfs) {
+        log::info!("Loading {} dataset", source.name());
+        let init_stream: DataStream<SplatMessage<B>> = Box::pin(tokio_stream::empty());
+        Ok((init_stream, source.load(vfs.clone(), load_args)))
+    } else if let Some(base) = tum::find_sequence(&vfs) {
+        tum::load_dataset(vfs.clone(), base, load_args, device).await
+    } else if let Some(base) = scannet::find_scene(&vfs) {
+        scannet::load_dataset(vfs.clone(), base, load_args, device).await
+    } else if let Some(base) = replica::find_scene(&vfs) {
+        replica::load_dataset(vfs.clone(), base, load_args, device).await
+    } else {
+        match nerfstudio::read_dataset(vfs.clone(), load_args, device).await {
+            Ok(s) => Ok(s),
+            Err(_) => colmap::load_dataset::<B>(vfs.clone(), load_args, device).await,
+        }
     };
 
     let stream = match stream {
         Ok(stream) => stream,
-        Err(e) => anyhow::bail!(
-            "Couldn't parse dataset as any format. Only some formats are supported. {e}"
-        ),
+        // Without any COLMAP cameras this isn't a COLMAP dataset either.
+        Err(e) => match DatasetError::from(e) {
+            DatasetError::MissingCameras => return Err(DatasetError::UnknownFormat),
+            e => return Err(e),
+        },
     };
 
     // If there's an init.ply definitey override the init stream with that.
-    let init_stream = if let Ok(reader) = vfs.open_path(Path::new("init.ply")).await {
-        log::info!("Using init.ply as initial point cloud.");
-        Box::pin(load_splat_from_ply(
-            reader,
-            load_args.subsample_points,
-            device.clone(),
-        ))
-    } else {
-        stream.0
+    let init_stream: DataStream<SplatMessage<B>> =
+        if let Ok(reader) = vfs.open_path(Path::new("init.ply")).await {
+            log::info!("Using init.ply as initial point cloud.");
+            Box::pin(load_splat_from_ply(
+                reader,
+                load_args.subsample_points,
+                device.clone(),
+            ))
+        } else {
+            stream.0
+        };
+
+    let init_stream = match load_args.point_filter {
+        Some(filter) => filter_init_stream(init_stream, filter),
+        None => init_stream,
     };
 
-    Ok((init_stream, stream.1))
+    Ok((
+        Box::pin(init_stream.map(|m| m.map_err(DatasetError::from))),
+        Box::pin(stream.1.map(|d| d.map_err(DatasetError::from))),
+    ))
 }
@@ -1,15 +1,20 @@
 use super::DataStream;
 use super::LoadDatasetArgs;
 use crate::brush_vfs::BrushVfs;
+use crate::eval_split;
+use crate::exif::{exif_focal_pixels, rotate_camera};
+use crate::photometric::{self, PhotometricOptions};
 use crate::splat_import::load_splat_from_ply;
 use crate::splat_import::SplatMessage;
-use crate::stream_fut_parallel;
-use crate::{clamp_img_to_max_size, Dataset};
+use crate::{
+    clamp_img_to_max_size, decode_feature_map, decode_mask, view_image, Dataset, DatasetError,
+};
 use anyhow::Context;
 use anyhow::Result;
 use async_fn_stream::try_fn_stream;
-use brush_render::camera::{focal_to_fov, fov_to_focal, Camera};
+use brush_render::camera::{fov_to_focal, Camera};
 use brush_render::Backend;
+use brush_train::image::image_sharpness;
 use brush_train::scene::SceneView;
 use std::future::Future;
 use std::path::PathBuf;
@@ -91,6 +96,10 @@ struct FrameData {
 
     transform_matrix: Vec<Vec<f32>>,
     file_path: String,
+    /// Optional mask, pixels that are black are ignored in training.
+    mask_path: Option<String>,
+    /// Optional feature map of the frame, a float `.npy` array of `[height, width, dim]`.
+    feature_path: Option<String>,
 }
 
 fn read_transforms_file(
@@ -99,24 +108,23 @@ fn read_transforms_file(
     vfs: BrushVfs,
     load_args: &LoadDatasetArgs,
 ) -> Vec<impl Future<Output = anyhow::Result<SceneView>>> {
+    let host_cache = load_args.host_image_cache();
     let iter = scene
         .frames
         .into_iter()
         .take(load_args.max_frames.unwrap_or(usize::MAX))
-        .map(move |frame| {
+        .enumerate()
+        .map(move |(i, frame)| {
             let mut archive = vfs.clone();
             let load_args = load_args.clone();
+            let host_cache = host_cache.clone();
             let transforms_path = transforms_path.clone();
 
             async move {
                 // NeRF 'transform_matrix' is a camera-to-world transform
                 let transform_matrix: Vec<f32> =
                     frame.transform_matrix.iter().flatten().copied().collect();
-                let mut transform = glam::Mat4::from_cols_slice(&transform_matrix).transpose();
-                // Swap basis to match camera format and reconstrunstion ply (if included).
-                transform.y_axis *= -1.0;
-                transform.z_axis *= -1.0;
-                let (_, rotation, translation) = transform.to_scale_rotation_translation();
+                let transform = glam::Mat4::from_cols_slice(&transform_matrix).transpose();
 
                 // Read the imageat the specified path, fallback to default .png extension.
                 let mut path = transforms_path
@@ -135,41 +143,120 @@ fn read_transforms_file(
                     .read_to_end(&mut img_buffer)
                     .await?;
 
-                let comp_span = tracing::trace_span!("Decompress image").entered();
-                drop(comp_span);
-
-                // Create a cursor from the buffer
-                let mut image = tracing::trace_span!("Decode image")
-                    .in_scope(|| image::load_from_memory(&img_buffer))?;
-
-                let w = frame.w.or(scene.w).unwrap_or(image.width() as f64) as u32;
-                let h = frame.h.or(scene.h).unwrap_or(image.height() as f64) as u32;
+                // Decode at full size first, to know the original size of the image.
+                let (image, img_buffer, quarter_turns) = crate::decode_image(
+                    img_buffer,
+                    &path,
+                    None,
+                    PhotometricOptions::default(),
+                )
+                .await?;
 
-                if let Some(max_resolution) = load_args.max_resolution {
-                    image = clamp_img_to_max_size(image, max_resolution);
-                }
+                // The calibration is for the image as stored, before it's turned upright.
+                let (stored_w, stored_h) = if quarter_turns % 2 == 1 {
+                    (image.height(), image.width())
+                } else {
+                    (image.width(), image.height())
+                };
+                let w = frame.w.or(scene.w).unwrap_or(stored_w as f64) as u32;
+                let h = frame.h.or(scene.h).unwrap_or(stored_h as f64) as u32;
+
+                // Downscale and correct the image like `decode_image` does.
+                let (max_resolution, photometric) = (load_args.max_resolution, load_args.photometric);
+                let image = brush_tasks::run_blocking(move || {
+                    let image = match max_resolution {
+                        Some(max_resolution) => clamp_img_to_max_size(image, max_resolution),
+                        None => image,
+                    };
+                    photometric::correct_image(image, photometric)
+                })
+                .await;
 
                 let focal_x = frame
                     .fl_x
                     .or(scene.fl_x)
-                    .or(scene.camera_angle_x.map(|fx| fov_to_focal(fx, w)))
-                    .context("Must have a focal length of some kind.")?;
+                    .or(scene.camera_angle_x.map(|fx| fov_to_focal(fx, w)));
+
+                // Without a calibration, fall back to estimating the focal length from EXIF.
+                let focal_x = match focal_x {
+                    Some(focal_x) => focal_x,
+                    None => {
+                        let focal_x = exif_focal_pixels(&img_buffer, w, h).with_context(|| {
+                            format!(
+                                "{} has no focal length, and no EXIF focal length to fall back to.",
+                                frame.file_path
+                            )
+                        })?;
+                        log::warn!(
+                            "{} has no calibrated intrinsics, estimated focal length {focal_x:.1}px from EXIF. Results might be inaccurate.",
+                            frame.file_path
+                        );
+                        focal_x
+                    }
+                };
 
                 // Read fov y or derive it from the input.
                 let focal_y = frame.fl_y.or(scene.fl_y).unwrap_or(focal_x);
 
-                let fovx = focal_to_fov(focal_x, w);
-                let fovy = focal_to_fov(focal_y, h);
-
                 let cx = frame.cx.or(scene.cx).unwrap_or(w as f64 / 2.0);
                 let cy = frame.cy.or(scene.cy).unwrap_or(h as f64 / 2.0);
 
-                let cuv = glam::vec2((cx / w as f64) as f32, (cy / h as f64) as f32);
+                let camera = Camera::from_focal(
+                    glam::vec2(focal_x as f32, focal_y as f32),
+                    glam::vec2(cx as f32, cy as f32),
+                    glam::uvec2(w, h),
+                )
+                .with_opengl_world_from_camera(transform);
+
+                let mask = if let Some(mask_path) = &frame.mask_path {
+                    let mask_path = transforms_path
+                        .parent()
+                        .expect("Transforms path must be a filename")
+                        .join(mask_path);
+                    let mut mask_buffer = vec![];
+                    archive
+                        .open_path(&mask_path)
+                        .await?
+                        .read_to_end(&mut mask_buffer)
+                        .await?;
+                    Some(Arc::new(decode_mask(&mask_buffer, &mask_path, &image, quarter_turns)?))
+                } else {
+                    None
+                };
+
+                let features = if let Some(feature_path) = &frame.feature_path {
+                    let feature_path = transforms_path
+                        .parent()
+                        .expect("Transforms path must be a filename")
+                        .join(feature_path);
+                    let mut feature_buffer = vec![];
+                    archive
+                        .open_path(&feature_path)
+                        .await?
+                        .read_to_end(&mut feature_buffer)
+                        .await?;
+                    Some(Arc::new(decode_feature_map(&feature_buffer, &feature_path)?))
+                } else {
+                    None
+                };
 
                 let view = SceneView {
                     name: frame.file_path.clone(),
-                    camera: Camera::new(translation, rotation, fovx, fovy, cuv),
-                    image: Arc::new(image),
+                    camera: rotate_camera(camera, quarter_turns),
+                    sharpness: image_sharpness(&image),
+                    image: view_image(
+                        image,
+                        img_buffer,
+                        &path,
+                        &load_args,
+                        host_cache.as_ref(),
+                    ),
+                    mask,
+                    // Frames with their own focal length are treated as separate cameras.
+                    camera_id: if frame.fl_x.is_some() { i as u32 + 1 } else { 0 },
+                    capture_id: 0,
+                    features,
+                    depth: None,
                 };
                 anyhow::Result::<SceneView>::Ok(view)
             }
@@ -261,34 +348,36 @@ pub async fn read_dataset<B: Backend>(
         // Not entirely sure yet if we want to report stats on both test
         // and eval, atm this skips "transforms_test.json" even if it's there.
 
-        let train_handles = stream_fut_parallel(train_handles);
+        let is_eval = eval_split(train_handles.len(), &load_args_clone);
+        let total = train_handles.len() + val_stream.as_ref().map_or(0, Vec::len);
+        let progress = load_args_clone.progress.clone();
+        let train_handles = brush_tasks::stream_parallel(train_handles);
         let mut train_handles = std::pin::pin!(train_handles);
 
         let mut i = 0;
         while let Some(view) = train_handles.next().await {
-            if let Some(eval_period) = load_args_clone.eval_split_every {
-                // Include extra eval images only when the dataset doesn't have them.
-                if i % eval_period == 0 && val_stream.is_some() {
-                    eval_views.push(view?);
-                } else {
-                    train_views.push(view?);
-                }
+            // Include extra eval images only when the dataset doesn't have them.
+            if is_eval[i] && val_stream.is_some() {
+                eval_views.push(view?);
             } else {
                 train_views.push(view?);
             }
 
+            i += 1;
+            progress.report("Loading views", i, total);
+
             emitter
                 .emit(Dataset::from_views(train_views.clone(), eval_views.clone()))
                 .await;
-
-            i += 1;
         }
 
         if let Some(val_stream) = val_stream {
-            let val_handles = stream_fut_parallel(val_stream);
+            let val_handles = brush_tasks::stream_parallel(val_stream);
             let mut val_handles = std::pin::pin!(val_handles);
             while let Some(view) = val_handles.next().await {
                 eval_views.push(view?);
+                i += 1;
+                progress.report("Loading views", i, total);
                 emitter
                     .emit(Dataset::from_views(train_views.clone(), eval_views.clone()))
                     .await;
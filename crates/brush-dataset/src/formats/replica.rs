@@ -0,0 +1,113 @@
+//! [Replica](https://github.com/facebookresearch/Replica-Dataset) scenes as rendered for
+//! NICE-SLAM and the papers following it: `results/frame<i>.jpg`, `results/depth<i>.png` and
+//! a `traj.txt` with the 4x4 camera to world transform of every frame, row by row, in the
+//! OpenCV convention.
+//!
+//! The intrinsics and depth scale are read from a `cam_params.json`, which is usually next to
+//! the scene folders, and otherwise the published values of the renders are used. The frames
+//! of a benchmark's eval split can be listed in a `test.txt` next to `traj.txt`, see
+//! [`super::rgbd::read_split`].
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use brush_render::Backend;
+use glam::{Affine3A, Vec2};
+
+use super::{
+    rgbd::{in_split, load_rgbd, read_file, read_split, RgbdFrame},
+    scannet::parse_matrix,
+    DataStream,
+};
+use crate::{brush_vfs::BrushVfs, splat_import::SplatMessage, Dataset, LoadDatasetArgs};
+
+#[derive(serde::Deserialize)]
+struct CamParams {
+    camera: CameraParams,
+}
+
+#[derive(serde::Deserialize)]
+struct CameraParams {
+    fx: f32,
+    fy: f32,
+    cx: f32,
+    cy: f32,
+    scale: f32,
+}
+
+impl Default for CameraParams {
+    // The renders of NICE-SLAM, at 1200x680.
+    fn default() -> Self {
+        Self {
+            fx: 600.0,
+            fy: 600.0,
+            cx: 599.5,
+            cy: 339.5,
+            scale: 6553.5,
+        }
+    }
+}
+
+/// The folder of a Replica scene, with `traj.txt` and the `results` folder.
+pub(crate) fn find_scene(vfs: &BrushVfs) -> Option<PathBuf> {
+    let traj = vfs
+        .file_names()
+        .find(|p| p.file_name().is_some_and(|n| n == "traj.txt"))?;
+    let base = traj.parent()?.to_path_buf();
+    let results = base.join("results");
+    vfs.file_names()
+        .any(|p| p.starts_with(&results))
+        .then_some(base)
+}
+
+pub(crate) async fn load_dataset<B: Backend>(
+    mut vfs: BrushVfs,
+    base: PathBuf,
+    load_args: &LoadDatasetArgs,
+    device: &B::Device,
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
+    log::info!("Loading Replica dataset");
+
+    let cam_params = vfs
+        .file_names()
+        .find(|p| p.file_name().is_some_and(|n| n == "cam_params.json"))
+        .map(|p| p.to_path_buf());
+    let camera = match cam_params {
+        Some(path) => {
+            let json = read_file(&mut vfs, &path).await?;
+            serde_json::from_slice::<CamParams>(&json)
+                .context("Invalid Replica cam_params.json")?
+                .camera
+        }
+        None => CameraParams::default(),
+    };
+
+    let traj = read_file(&mut vfs, &base.join("traj.txt")).await?;
+    let traj = String::from_utf8_lossy(&traj);
+    let split = read_split(&mut vfs, &base.join("test.txt")).await;
+
+    let results = base.join("results");
+    let focal = Vec2::new(camera.fx, camera.fy);
+    let center = Vec2::new(camera.cx, camera.cy);
+    let frames = traj
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let pose = parse_matrix(line)?;
+            let image_path = results.join(format!("frame{index:06}.jpg"));
+            let eval = split
+                .as_ref()
+                .map(|split| in_split(split, index as u32, &image_path));
+            Some(RgbdFrame {
+                depth_path: results.join(format!("depth{index:06}.png")),
+                image_path,
+                focal,
+                center,
+                world_from_camera: Affine3A::from_mat4(pose),
+                eval,
+            })
+        })
+        .collect();
+
+    load_rgbd(vfs, frames, camera.scale, load_args, device)
+}
@@ -0,0 +1,286 @@
+//! Loading of RGB-D sequences, where every image has a depth map, eg. from TUM RGB-D or
+//! ScanNet. The depth maps supervise the depth of the splats while training, and the initial
+//! splats are backprojected from the depth of a selection of frames.
+use std::{
+    collections::HashSet,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use async_fn_stream::try_fn_stream;
+use brush_render::{camera::Camera, gaussian_splats::Splats, render::rgb_to_sh, Backend};
+use brush_train::{
+    image::image_sharpness,
+    scene::{DepthMap, SceneView},
+};
+use glam::{Affine3A, Vec2, Vec3};
+use image::{DynamicImage, ImageReader};
+use tokio::io::AsyncReadExt;
+use tokio_stream::StreamExt;
+
+use super::DataStream;
+use crate::{
+    brush_vfs::BrushVfs,
+    decode_image, eval_split,
+    splat_import::{SplatMessage, SplatMetadata},
+    view_image, Dataset, DatasetError, LoadDatasetArgs,
+};
+
+// Number of frames the initial points are backprojected from.
+const INIT_FRAMES: usize = 32;
+// Backproject every this many pixels of a depth map, in both directions.
+const INIT_STRIDE: u32 = 8;
+
+/// A color image with a depth map.
+#[derive(Clone, Debug)]
+pub(crate) struct RgbdFrame {
+    pub image_path: PathBuf,
+    pub depth_path: PathBuf,
+    /// Focal length and principal point in pixels of the full resolution image.
+    pub focal: Vec2,
+    pub center: Vec2,
+    /// The camera to world transform, of a camera looking down +Z with +Y down.
+    pub world_from_camera: Affine3A,
+    /// Whether this is an eval frame, when the dataset has its own split. Otherwise the
+    /// split follows [`LoadDatasetArgs::eval_split_every`].
+    pub eval: Option<bool>,
+}
+
+/// Decode a 16 bit depth png, with `scale` units per meter, eg. 1000 for millimeters.
+pub(crate) async fn decode_depth(
+    data: Vec<u8>,
+    path: PathBuf,
+    scale: f32,
+) -> Result<DepthMap, DatasetError> {
+    brush_tasks::run_blocking(move || {
+        let depth = image::load_from_memory(&data)
+            .map_err(|source| DatasetError::CorruptImage { path, source })?
+            .to_luma16();
+        Ok(DepthMap {
+            width: depth.width(),
+            height: depth.height(),
+            data: depth.pixels().map(|p| p.0[0] as f32 / scale).collect(),
+        })
+    })
+    .await
+}
+
+/// Colored points backprojected from every `stride`th pixel of a depth map, with the colors
+/// as SH coefficients.
+pub(crate) fn backproject(
+    camera: &Camera,
+    depth: &DepthMap,
+    image: &DynamicImage,
+    stride: u32,
+) -> (Vec<Vec3>, Vec<f32>) {
+    let size = glam::uvec2(depth.width, depth.height);
+    let (focal, center) = (camera.focal(size), camera.center(size));
+    let world_from_camera = camera.world_from_camera();
+    let image = image.to_rgb8();
+    let scale = glam::vec2(
+        image.width() as f32 / depth.width as f32,
+        image.height() as f32 / depth.height as f32,
+    );
+
+    let mut points = vec![];
+    let mut colors = vec![];
+    for y in (0..depth.height).step_by(stride as usize) {
+        for x in (0..depth.width).step_by(stride as usize) {
+            let z = depth.get(x, y);
+            if z <= 0.0 {
+                continue;
+            }
+            let pixel = glam::vec2(x as f32, y as f32) + 0.5;
+            let local = ((pixel - center) / focal * z).extend(z);
+            points.push(world_from_camera.transform_point3(local));
+
+            let color_pixel = (pixel * scale)
+                .as_uvec2()
+                .min(glam::uvec2(image.width() - 1, image.height() - 1));
+            let rgb = image.get_pixel(color_pixel.x, color_pixel.y).0;
+            colors.extend(rgb.map(|c| rgb_to_sh(c as f32 / 255.0)));
+        }
+    }
+    (points, colors)
+}
+
+pub(crate) async fn read_file(vfs: &mut BrushVfs, path: &Path) -> Result<Vec<u8>, DatasetError> {
+    let mut bytes = vec![];
+    vfs.open_path(path).await?.read_to_end(&mut bytes).await?;
+    Ok(bytes)
+}
+
+async fn load_view(
+    mut vfs: BrushVfs,
+    frame: RgbdFrame,
+    depth_scale: f32,
+    load_args: LoadDatasetArgs,
+) -> Result<SceneView, DatasetError> {
+    let img_bytes = read_file(&mut vfs, &frame.image_path).await?;
+    // The intrinsics are for the full resolution image.
+    let img_size = ImageReader::new(Cursor::new(&img_bytes))
+        .with_guessed_format()?
+        .into_dimensions()
+        .map_err(|source| DatasetError::CorruptImage {
+            path: frame.image_path.clone(),
+            source,
+        })?;
+    let (img, img_bytes, _) = decode_image(
+        img_bytes,
+        &frame.image_path,
+        load_args.max_resolution,
+        load_args.photometric,
+    )
+    .await?;
+
+    let depth_bytes = read_file(&mut vfs, &frame.depth_path).await?;
+    let depth = decode_depth(depth_bytes, frame.depth_path.clone(), depth_scale).await?;
+
+    let camera = Camera::from_focal(frame.focal, frame.center, img_size.into())
+        .with_world_from_camera(frame.world_from_camera);
+    let host_cache = load_args.host_image_cache();
+
+    Ok(SceneView {
+        name: frame.image_path.to_string_lossy().to_string(),
+        camera,
+        sharpness: image_sharpness(&img),
+        image: view_image(
+            img,
+            img_bytes,
+            &frame.image_path,
+            &load_args,
+            host_cache.as_ref(),
+        ),
+        mask: None,
+        camera_id: 0,
+        capture_id: 0,
+        features: None,
+        depth: Some(Arc::new(depth)),
+    })
+}
+
+/// The frames listed in a split file of a benchmark, eg. `test.txt`, by frame number or
+/// image name, one per line.
+pub(crate) async fn read_split(vfs: &mut BrushVfs, path: &Path) -> Option<HashSet<String>> {
+    let text = read_file(vfs, path).await.ok()?;
+    let split = String::from_utf8_lossy(&text)
+        .lines()
+        .map(|l| l.trim().to_owned())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect();
+    Some(split)
+}
+
+/// Whether a frame is listed in a split, by its number or by its image name with or without
+/// the extension.
+pub(crate) fn in_split(split: &HashSet<String>, index: u32, image_path: &Path) -> bool {
+    let name = |p: Option<&std::ffi::OsStr>| p.map(|n| n.to_string_lossy().into_owned());
+    split.contains(&index.to_string())
+        || name(image_path.file_name()).is_some_and(|n| split.contains(&n))
+        || name(image_path.file_stem()).is_some_and(|n| split.contains(&n))
+}
+
+/// Load the frames of an RGB-D sequence, with `depth_scale` depth units per meter.
+pub(crate) fn load_rgbd<B: Backend>(
+    vfs: BrushVfs,
+    frames: Vec<RgbdFrame>,
+    depth_scale: f32,
+    load_args: &LoadDatasetArgs,
+    device: &B::Device,
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
+    let frames: Vec<_> = frames
+        .into_iter()
+        .take(load_args.max_frames.unwrap_or(usize::MAX))
+        .step_by(load_args.subsample_frames.unwrap_or(1).max(1) as usize)
+        .collect();
+    if frames.is_empty() {
+        anyhow::bail!("RGB-D sequence has no frames with a depth map and pose.");
+    }
+    log::info!("Loading RGB-D sequence of {} frames", frames.len());
+
+    let init_frames: Vec<_> = frames
+        .iter()
+        .step_by(frames.len().div_ceil(INIT_FRAMES))
+        .cloned()
+        .collect();
+    let init_vfs = vfs.clone();
+    let init_args = load_args.clone();
+    let device = device.clone();
+    let init_stream = try_fn_stream(|emitter| async move {
+        let mut points = vec![];
+        let mut colors = vec![];
+        for frame in init_frames {
+            let view = load_view(init_vfs.clone(), frame, depth_scale, init_args.clone()).await?;
+            let Some(depth) = &view.depth else {
+                continue;
+            };
+            let (p, c) = backproject(&view.camera, depth, &view.image.load(), INIT_STRIDE);
+            points.extend(p);
+            colors.extend(c);
+        }
+
+        if let Some(subsample) = init_args.subsample_points {
+            points = points.into_iter().step_by(subsample as usize).collect();
+            colors = colors
+                .chunks_exact(3)
+                .step_by(subsample as usize)
+                .flatten()
+                .copied()
+                .collect();
+        }
+
+        if !points.is_empty() {
+            log::info!("Starting from {} backprojected depth points", points.len());
+            let splats = Splats::from_raw(&points, None, None, Some(&colors), None, &device);
+            emitter
+                .emit(SplatMessage {
+                    meta: SplatMetadata {
+                        up_axis: Vec3::Y,
+                        total_splats: splats.num_splats(),
+                        frame_count: 1,
+                        current_frame: 0,
+                    },
+                    splats,
+                })
+                .await;
+        }
+        Ok::<(), anyhow::Error>(())
+    });
+
+    let load_args = load_args.clone();
+    let dataset_stream = try_fn_stream(|emitter| async move {
+        let is_eval: Vec<bool> = frames
+            .iter()
+            .zip(eval_split(frames.len(), &load_args))
+            .map(|(frame, every)| frame.eval.unwrap_or(every))
+            .collect();
+        let total = frames.len();
+        let handles: Vec<_> = frames
+            .into_iter()
+            .map(|frame| load_view(vfs.clone(), frame, depth_scale, load_args.clone()))
+            .collect();
+        let views = brush_tasks::stream_parallel(handles);
+        let mut views = std::pin::pin!(views);
+
+        let mut train_views = vec![];
+        let mut eval_views = vec![];
+        let mut i = 0;
+        while let Some(view) = views.next().await {
+            if is_eval[i] {
+                eval_views.push(view?);
+            } else {
+                train_views.push(view?);
+            }
+            i += 1;
+            load_args.progress.report("Loading views", i, total);
+            emitter
+                .emit(Dataset::from_views(train_views.clone(), eval_views.clone()))
+                .await;
+        }
+        Ok::<(), anyhow::Error>(())
+    });
+
+    Ok((Box::pin(init_stream), Box::pin(dataset_stream)))
+}
@@ -0,0 +1,89 @@
+//! [ScanNet](http://www.scan-net.org) scenes as exported by its SensReader: `color/<i>.jpg`,
+//! `depth/<i>.png` in millimeters, `pose/<i>.txt` with the 4x4 camera to world transform,
+//! and the 4x4 intrinsics of the color camera in `intrinsic/intrinsic_color.txt`.
+//!
+//! Poses are in the OpenCV convention, and frames without a tracked pose are skipped. The
+//! frames of a benchmark's eval split can be listed in a `test.txt` next to these folders, by
+//! frame number or image name, see [`super::rgbd::read_split`].
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use brush_render::Backend;
+use glam::{Affine3A, Mat4, Vec2};
+
+use super::{
+    rgbd::{in_split, load_rgbd, read_file, read_split, RgbdFrame},
+    DataStream,
+};
+use crate::{brush_vfs::BrushVfs, splat_import::SplatMessage, Dataset, LoadDatasetArgs};
+
+const DEPTH_SCALE: f32 = 1000.0;
+
+/// The folder of a ScanNet scene, with the `color`, `depth`, `pose` and `intrinsic` folders.
+pub(crate) fn find_scene(vfs: &BrushVfs) -> Option<PathBuf> {
+    let intrinsics = vfs
+        .file_names()
+        .find(|p| p.ends_with("intrinsic/intrinsic_color.txt"))?;
+    Some(intrinsics.parent()?.parent()?.to_path_buf())
+}
+
+/// Parse a 4x4 matrix written row by row. ScanNet marks frames without a pose with `-inf`.
+pub(crate) fn parse_matrix(text: &str) -> Option<Mat4> {
+    let values: Vec<f32> = text
+        .split_whitespace()
+        .map(|v| v.parse().ok())
+        .collect::<Option<_>>()?;
+    let values: [f32; 16] = values.try_into().ok()?;
+    let matrix = Mat4::from_cols_array(&values).transpose();
+    matrix.is_finite().then_some(matrix)
+}
+
+pub(crate) async fn load_dataset<B: Backend>(
+    mut vfs: BrushVfs,
+    base: PathBuf,
+    load_args: &LoadDatasetArgs,
+    device: &B::Device,
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
+    log::info!("Loading ScanNet dataset");
+
+    let intrinsics = read_file(&mut vfs, &base.join("intrinsic/intrinsic_color.txt")).await?;
+    let intrinsics = parse_matrix(&String::from_utf8_lossy(&intrinsics))
+        .context("Invalid ScanNet intrinsic_color.txt")?;
+    let focal = Vec2::new(intrinsics.x_axis.x, intrinsics.y_axis.y);
+    let center = Vec2::new(intrinsics.z_axis.x, intrinsics.z_axis.y);
+
+    // Frames are numbered, sort them numerically rather than by name.
+    let color_dir = base.join("color");
+    let mut images: Vec<(u32, PathBuf)> = vfs
+        .file_names()
+        .filter(|p| p.parent() == Some(color_dir.as_path()))
+        .filter_map(|p| Some((p.file_stem()?.to_str()?.parse().ok()?, p.to_path_buf())))
+        .collect();
+    images.sort_by_key(|(index, _)| *index);
+
+    let split = read_split(&mut vfs, &base.join("test.txt")).await;
+
+    let mut frames = vec![];
+    for (index, image_path) in images {
+        let pose = read_file(&mut vfs, &base.join(format!("pose/{index}.txt"))).await;
+        let Some(pose) = pose
+            .ok()
+            .and_then(|p| parse_matrix(&String::from_utf8_lossy(&p)))
+        else {
+            continue;
+        };
+        let eval = split
+            .as_ref()
+            .map(|split| in_split(split, index, &image_path));
+        frames.push(RgbdFrame {
+            image_path,
+            depth_path: base.join(format!("depth/{index}.png")),
+            focal,
+            center,
+            world_from_camera: Affine3A::from_mat4(pose),
+            eval,
+        });
+    }
+
+    load_rgbd(vfs, frames, DEPTH_SCALE, load_args, device)
+}
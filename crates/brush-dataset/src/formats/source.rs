@@ -0,0 +1,88 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{brush_vfs::BrushVfs, Dataset, LoadDatasetArgs};
+
+use super::DataStream;
+
+/// A dataset format from outside of Brush, eg. a proprietary capture format.
+///
+/// Sources are registered with [`register_source`], after which [`super::load_dataset`] loads
+/// any files a source detects with that source, before trying the built-in formats.
+pub trait DatasetSource: Send + Sync {
+    /// Name of the format, for the log.
+    fn name(&self) -> &str;
+
+    /// Whether the files are in this format. Only the file names are available, so this should
+    /// be quick, eg. look for a manifest file.
+    fn detect(&self, vfs: &BrushVfs) -> bool;
+
+    /// Load the dataset. Like the built-in formats, the stream can emit the dataset several
+    /// times as more views are loaded, and the last dataset is the complete one.
+    ///
+    /// The initial splats are read from an `init.ply` file if there is one, otherwise training
+    /// starts from random splats.
+    fn load(&self, vfs: BrushVfs, load_args: &LoadDatasetArgs) -> DataStream<Dataset>;
+}
+
+static SOURCES: RwLock<Vec<Arc<dyn DatasetSource>>> = RwLock::new(Vec::new());
+
+/// Register a dataset source. Sources registered first are tried first.
+pub fn register_source(source: impl DatasetSource + 'static) {
+    SOURCES
+        .write()
+        .expect("Dataset sources poisoned")
+        .push(Arc::new(source));
+}
+
+/// The first registered source that detects these files, if any.
+pub(crate) fn find_source(vfs: &BrushVfs) -> Option<Arc<dyn DatasetSource>> {
+    SOURCES
+        .read()
+        .expect("Dataset sources poisoned")
+        .iter()
+        .find(|source| source.detect(vfs))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        brush_vfs::{BrushVfs, PathReader},
+        formats::DataStream,
+        Dataset, LoadDatasetArgs,
+    };
+
+    use super::{find_source, register_source, DatasetSource};
+
+    struct ScanSource;
+
+    impl DatasetSource for ScanSource {
+        fn name(&self) -> &str {
+            "Scan"
+        }
+
+        fn detect(&self, vfs: &BrushVfs) -> bool {
+            vfs.file_names().any(|p| p == Path::new("scan.manifest"))
+        }
+
+        fn load(&self, _vfs: BrushVfs, _load_args: &LoadDatasetArgs) -> DataStream<Dataset> {
+            Box::pin(tokio_stream::once(Ok(Dataset::empty())))
+        }
+    }
+
+    #[test]
+    fn registered_source_detects_its_files() {
+        register_source(ScanSource);
+
+        let mut paths = PathReader::default();
+        paths.add(Path::new("scan.manifest"), std::io::Cursor::new(vec![]));
+        let source = find_source(&BrushVfs::from_paths(paths)).expect("Source should match");
+        assert_eq!(source.name(), "Scan");
+
+        let mut paths = PathReader::default();
+        paths.add(Path::new("transforms.json"), std::io::Cursor::new(vec![]));
+        assert!(find_source(&BrushVfs::from_paths(paths)).is_none());
+    }
+}
@@ -0,0 +1,163 @@
+//! The [TUM RGB-D](https://cvg.cit.tum.de/data/datasets/rgbd-dataset/file_formats) format:
+//! `rgb.txt` and `depth.txt` list the images by timestamp, and `groundtruth.txt` has the
+//! camera to world poses as `timestamp tx ty tz qx qy qz qw`. Depth pngs are in units of
+//! 1/5000 meter.
+//!
+//! The intrinsics are read from a `calibration.txt` of `fx fy cx cy`, as written by the
+//! ETH3D SLAM benchmark, and otherwise the calibration of the TUM sensor the sequence name
+//! refers to (`freiburg1` to `freiburg3`) is used.
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use brush_render::Backend;
+use glam::{Affine3A, Quat, Vec2, Vec3};
+
+use super::{
+    rgbd::{load_rgbd, read_file, RgbdFrame},
+    DataStream,
+};
+use crate::{brush_vfs::BrushVfs, splat_import::SplatMessage, Dataset, LoadDatasetArgs};
+
+const DEPTH_SCALE: f32 = 5000.0;
+// Images are matched to the depth map and pose nearest in time, at most this far apart.
+const MAX_TIME_DIFFERENCE: f64 = 0.02;
+
+/// The folder with the `rgb.txt` and `depth.txt` of a TUM sequence, if any.
+pub(crate) fn find_sequence(vfs: &BrushVfs) -> Option<PathBuf> {
+    let rgb = vfs
+        .file_names()
+        .find(|p| p.file_name().is_some_and(|n| n == "rgb.txt"))?;
+    let base = rgb.parent().unwrap_or(Path::new("")).to_path_buf();
+    vfs.file_names()
+        .any(|p| p == base.join("depth.txt"))
+        .then_some(base)
+}
+
+// Rows of a TUM text file, without comments.
+fn rows(text: &str) -> impl Iterator<Item = Vec<&str>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.split_whitespace().collect())
+}
+
+// Parse `timestamp value...` rows, sorted by time.
+fn timed<T>(text: &str, parse: impl Fn(&[&str]) -> Option<T>) -> Vec<(f64, T)> {
+    let mut rows: Vec<(f64, T)> = rows(text)
+        .filter_map(|row| Some((row.first()?.parse().ok()?, parse(&row[1..])?)))
+        .collect();
+    rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    rows
+}
+
+// The entry nearest to `time`, if it's near enough.
+fn nearest<T>(rows: &[(f64, T)], time: f64) -> Option<&T> {
+    let i = rows.partition_point(|(t, _)| *t < time);
+    [i.checked_sub(1), Some(i)]
+        .into_iter()
+        .flatten()
+        .filter_map(|i| rows.get(i))
+        .min_by(|a, b| (a.0 - time).abs().total_cmp(&(b.0 - time).abs()))
+        .filter(|(t, _)| (t - time).abs() <= MAX_TIME_DIFFERENCE)
+        .map(|(_, v)| v)
+}
+
+// Focal length and principal point of the sensor of a sequence.
+fn default_intrinsics(base: &Path) -> (Vec2, Vec2) {
+    let name = base.to_string_lossy();
+    if name.contains("freiburg1") {
+        (Vec2::new(517.3, 516.5), Vec2::new(318.6, 255.3))
+    } else if name.contains("freiburg2") {
+        (Vec2::new(520.9, 521.0), Vec2::new(325.1, 249.7))
+    } else if name.contains("freiburg3") {
+        (Vec2::new(535.4, 539.2), Vec2::new(320.1, 247.6))
+    } else {
+        // The default calibration TUM recommends for unknown sensors.
+        (Vec2::new(525.0, 525.0), Vec2::new(319.5, 239.5))
+    }
+}
+
+pub(crate) async fn load_dataset<B: Backend>(
+    mut vfs: BrushVfs,
+    base: PathBuf,
+    load_args: &LoadDatasetArgs,
+    device: &B::Device,
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
+    log::info!("Loading TUM RGB-D dataset");
+
+    let read_text = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned();
+    let rgb = read_text(read_file(&mut vfs, &base.join("rgb.txt")).await?);
+    let depth = read_text(read_file(&mut vfs, &base.join("depth.txt")).await?);
+    let groundtruth = read_text(
+        read_file(&mut vfs, &base.join("groundtruth.txt"))
+            .await
+            .context("TUM sequence has no groundtruth.txt with the camera poses")?,
+    );
+
+    let (focal, center) = match read_file(&mut vfs, &base.join("calibration.txt")).await {
+        Ok(bytes) => {
+            let text = read_text(bytes);
+            let values: Vec<f32> = rows(&text)
+                .next()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            let [fx, fy, cx, cy] = values[..] else {
+                anyhow::bail!("calibration.txt should contain fx fy cx cy");
+            };
+            (Vec2::new(fx, fy), Vec2::new(cx, cy))
+        }
+        Err(_) => default_intrinsics(&base),
+    };
+
+    let path = |row: &[&str]| row.first().map(|p| base.join(p));
+    let images = timed(&rgb, path);
+    let depths = timed(&depth, path);
+    let poses = timed(&groundtruth, |row| {
+        let v: Vec<f32> = row.iter().map(|v| v.parse().ok()).collect::<Option<_>>()?;
+        let [tx, ty, tz, qx, qy, qz, qw] = v[..] else {
+            return None;
+        };
+        Some(Affine3A::from_rotation_translation(
+            Quat::from_xyzw(qx, qy, qz, qw).normalize(),
+            Vec3::new(tx, ty, tz),
+        ))
+    });
+
+    let frames = images
+        .iter()
+        .filter_map(|(time, image_path)| {
+            Some(RgbdFrame {
+                image_path: image_path.clone(),
+                depth_path: nearest(&depths, *time)?.clone(),
+                focal,
+                center,
+                world_from_camera: *nearest(&poses, *time)?,
+                eval: None,
+            })
+        })
+        .collect();
+
+    load_rgbd(vfs, frames, DEPTH_SCALE, load_args, device)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nearest, timed};
+
+    #[test]
+    fn matches_nearest_timestamp() {
+        let text = "# depth maps\n1.00 depth/a.png\n1.05 depth/b.png\n";
+        let rows = timed(text, |row| row.first().map(|s| s.to_string()));
+        assert_eq!(
+            nearest(&rows, 1.01).map(String::as_str),
+            Some("depth/a.png")
+        );
+        assert_eq!(
+            nearest(&rows, 1.04).map(String::as_str),
+            Some("depth/b.png")
+        );
+        assert_eq!(nearest(&rows, 1.2), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! Datasets stored as WebDataset style tar shards, which can be streamed over HTTP while
+//! training instead of downloading the whole dataset first.
+//!
+//! The dataset is described by an `index.json` with the shards, relative to the index:
+//!
+//! ```json
+//! {"shards": ["shard-000000.tar", "shard-000001.tar"], "num_samples": 20000}
+//! ```
+//!
+//! Every sample in a shard is a group of consecutive files sharing a key, eg.
+//! `000042.jpg` and `000042.json`. The json holds the camera like a nerfstudio frame: a
+//! `transform_matrix` (camera to world, OpenGL convention), the focal length `fl_x` and
+//! optionally `fl_y`, `cx` and `cy` in pixels of the image, and optionally `"split": "eval"`
+//! to hold out the view for evaluation. An optional `mask.png` is used as the loss mask.
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use async_fn_stream::try_fn_stream;
+use brush_render::camera::Camera;
+use brush_train::{image::image_sharpness, residency::HostImageCache, scene::SceneView};
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::{
+    clamp_img_to_max_size, decode_mask, decode_oriented, exif::rotate_camera, photometric,
+    view_image, Dataset, DatasetError, LoadDatasetArgs,
+};
+
+use super::DataStream;
+
+const BLOCK_SIZE: usize = 512;
+// Largest file in a shard. The size comes from the shard, which can be any url, so it's
+// capped before allocating a buffer for it. Plenty for the image of a view.
+const MAX_ENTRY_SIZE: usize = 256 * 1024 * 1024;
+
+/// The shards of a WebDataset, see the [module docs](self).
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebDatasetIndex {
+    /// Shard paths or urls, relative to the index.
+    pub shards: Vec<String>,
+    /// Total number of samples, if known. Only used to report progress.
+    #[serde(default)]
+    pub num_samples: Option<usize>,
+}
+
+impl WebDatasetIndex {
+    /// Resolve the shards relative to the url or path the index was read from.
+    pub fn shard_urls(&self, index_url: &str) -> Vec<String> {
+        let base = index_url
+            .rsplit_once('/')
+            .map_or("", |(base, _)| base)
+            .to_owned();
+        self.shards
+            .iter()
+            .map(|shard| {
+                if shard.contains("://") || shard.starts_with('/') || base.is_empty() {
+                    shard.clone()
+                } else {
+                    format!("{base}/{shard}")
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SampleMeta {
+    transform_matrix: [[f32; 4]; 4],
+    fl_x: f64,
+    fl_y: Option<f64>,
+    cx: Option<f64>,
+    cy: Option<f64>,
+    split: Option<String>,
+}
+
+fn parse_octal(field: &[u8]) -> anyhow::Result<usize> {
+    let text = std::str::from_utf8(field)?.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    Ok(usize::from_str_radix(text, 8)?)
+}
+
+// Size of the file of a tar header block.
+fn entry_size(header: &[u8; BLOCK_SIZE]) -> anyhow::Result<usize> {
+    let size = parse_octal(&header[124..136]).context("Invalid tar entry size")?;
+    anyhow::ensure!(
+        size <= MAX_ENTRY_SIZE,
+        "Tar entry of {size} bytes is larger than the limit of {MAX_ENTRY_SIZE} bytes"
+    );
+    Ok(size)
+}
+
+fn parse_name(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+// Read the regular files of a tar archive, in order, as (path, data). Supports the ustar
+// prefix, and the long names of GNU and pax archives.
+fn read_tar<R: AsyncRead + Unpin + Send + 'static>(
+    reader: R,
+) -> impl Stream<Item = anyhow::Result<(String, Vec<u8>)>> {
+    try_fn_stream(|emitter| async move {
+        let mut reader = reader;
+        let mut long_name: Option<String> = None;
+
+        loop {
+            let mut header = [0u8; BLOCK_SIZE];
+            if let Err(e) = reader.read_exact(&mut header).await {
+                // Some writers leave out the end of archive blocks.
+                if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                    break;
+                }
+                return Err(e.into());
+            }
+            // The archive ends with zero blocks.
+            if header.iter().all(|&b| b == 0) {
+                break;
+            }
+
+            let size = entry_size(&header)?;
+            let mut data = vec![0; size];
+            reader.read_exact(&mut data).await?;
+            let padding = size.next_multiple_of(BLOCK_SIZE) - size;
+            reader.read_exact(&mut vec![0; padding]).await?;
+
+            let mut name = parse_name(&header[0..100]);
+            if &header[257..262] == b"ustar" {
+                let prefix = parse_name(&header[345..500]);
+                if !prefix.is_empty() {
+                    name = format!("{prefix}/{name}");
+                }
+            }
+
+            match header[156] {
+                b'0' | 0 => {
+                    let name = long_name.take().unwrap_or(name);
+                    emitter.emit((name, data)).await;
+                }
+                // GNU long name of the next entry.
+                b'L' => long_name = Some(parse_name(&data)),
+                // Pax extended header, only the path is used.
+                b'x' => {
+                    let records = String::from_utf8_lossy(&data);
+                    long_name = records
+                        .lines()
+                        .find_map(|r| r.split_once(" path=").map(|(_, path)| path.to_owned()));
+                }
+                // Directories, links and global headers.
+                _ => {}
+            }
+        }
+
+        Ok(())
+    })
+}
+
+// Split a path into the sample key and the file type, eg. `a/000042.seg.png` into `a/000042`
+// and `seg.png`.
+fn split_key(path: &str) -> (&str, &str) {
+    let file_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[file_start..].find('.') {
+        Some(dot) => (&path[..file_start + dot], &path[file_start + dot + 1..]),
+        None => (path, ""),
+    }
+}
+
+fn decode_sample(
+    key: &str,
+    files: &[(String, Vec<u8>)],
+    load_args: &LoadDatasetArgs,
+    host_cache: Option<&Arc<HostImageCache>>,
+) -> Result<(SceneView, bool), DatasetError> {
+    let find = |exts: &[&str]| {
+        files
+            .iter()
+            .find(|(ext, _)| exts.contains(&ext.to_lowercase().as_str()))
+    };
+
+    let (_, meta) = find(&["json"]).with_context(|| format!("Sample {key} has no json"))?;
+    let meta: SampleMeta =
+        serde_json::from_slice(meta).with_context(|| format!("Invalid camera for sample {key}"))?;
+    let (image_ext, image_data) = find(&["jpg", "jpeg", "png", "webp"])
+        .with_context(|| format!("Sample {key} has no image"))?;
+
+    let path = PathBuf::from(format!("{key}.{image_ext}"));
+    let (mut image, quarter_turns) = decode_oriented(image_data, &path)?;
+    // The camera is for the image as stored, before it's turned upright.
+    let (w, h) = if quarter_turns % 2 == 1 {
+        (image.height(), image.width())
+    } else {
+        (image.width(), image.height())
+    };
+    if let Some(max_resolution) = load_args.max_resolution {
+        image = clamp_img_to_max_size(image, max_resolution);
+    }
+    let image = photometric::correct_image(image, load_args.photometric);
+
+    let mask = match find(&["mask.png"]) {
+        Some((_, mask)) => Some(Arc::new(decode_mask(mask, &path, &image, quarter_turns)?)),
+        None => None,
+    };
+
+    // Same conventions as a nerfstudio frame.
+    let transform = glam::Mat4::from_cols_array_2d(&meta.transform_matrix).transpose();
+    let fl_y = meta.fl_y.unwrap_or(meta.fl_x);
+    let cx = meta.cx.unwrap_or(w as f64 / 2.0);
+    let cy = meta.cy.unwrap_or(h as f64 / 2.0);
+    let camera = Camera::from_focal(
+        glam::vec2(meta.fl_x as f32, fl_y as f32),
+        glam::vec2(cx as f32, cy as f32),
+        glam::uvec2(w, h),
+    )
+    .with_opengl_world_from_camera(transform);
+    let camera = rotate_camera(camera, quarter_turns);
+
+    let view = SceneView {
+        name: path.to_string_lossy().into_owned(),
+        camera,
+        sharpness: image_sharpness(&image),
+        image: view_image(image, image_data.clone(), &path, load_args, host_cache),
+        mask,
+        camera_id: 0,
+        capture_id: 0,
+        features: None,
+        depth: None,
+    };
+    Ok((view, meta.split.as_deref() == Some("eval")))
+}
+
+/// Load a WebDataset from a stream of shard readers, in the order of the index.
+///
+/// The dataset is emitted as samples arrive, so training can start on the first shard while
+/// the others are still downloading. Progress is only reported when the number of samples is
+/// known, see [`WebDatasetIndex::num_samples`].
+pub fn load_webdataset<R: AsyncRead + Unpin + Send + 'static>(
+    shards: impl Stream<Item = anyhow::Result<R>> + Send + 'static,
+    num_samples: Option<usize>,
+    load_args: &LoadDatasetArgs,
+) -> DataStream<Dataset, DatasetError> {
+    let load_args = load_args.clone();
+    let host_cache = load_args.host_image_cache();
+
+    let stream = try_fn_stream(|emitter| async move {
+        let mut train_views = vec![];
+        let mut eval_views = vec![];
+        let total_samples = num_samples;
+        let mut num_samples = 0;
+        let max_frames = load_args.max_frames.unwrap_or(usize::MAX);
+
+        let mut shards = std::pin::pin!(shards);
+        while let Some(shard) = shards.next().await {
+            let mut entries = std::pin::pin!(read_tar(shard?));
+            // Files of the current sample, as (type, data).
+            let mut current: Option<(String, Vec<(String, Vec<u8>)>)> = None;
+
+            loop {
+                let entry = entries.next().await.transpose()?;
+                let next_key = entry.as_ref().map(|(path, _)| split_key(path).0.to_owned());
+
+                // A sample is complete when the key changes, or the shard ends.
+                let key_changed = current
+                    .as_ref()
+                    .is_some_and(|(key, _)| Some(key) != next_key.as_ref());
+                if key_changed || entry.is_none() {
+                    if let Some((key, files)) = current.take() {
+                        let every = load_args.subsample_frames.unwrap_or(1).max(1) as usize;
+                        let keep = num_samples % every == 0;
+                        if keep && train_views.len() + eval_views.len() < max_frames {
+                            let sample_args = load_args.clone();
+                            let sample_cache = host_cache.clone();
+                            let (view, is_eval) = brush_tasks::run_blocking(move || {
+                                decode_sample(&key, &files, &sample_args, sample_cache.as_ref())
+                            })
+                            .await?;
+                            let split_eval = load_args
+                                .eval_split_every
+                                .is_some_and(|every| num_samples % every == 0);
+                            if is_eval || split_eval {
+                                eval_views.push(view);
+                            } else {
+                                train_views.push(view);
+                            }
+                            emitter
+                                .emit(Dataset::from_views(train_views.clone(), eval_views.clone()))
+                                .await;
+                        }
+                        num_samples += 1;
+                        if let Some(total) = total_samples {
+                            load_args
+                                .progress
+                                .report("Loading views", num_samples, total);
+                        }
+                    }
+                }
+
+                let Some((path, data)) = entry else {
+                    break;
+                };
+                let (key, ext) = split_key(&path);
+                let (key, ext) = (key.to_owned(), ext.to_owned());
+                current
+                    .get_or_insert_with(|| (key, vec![]))
+                    .1
+                    .push((ext, data));
+            }
+        }
+
+        Ok::<(), DatasetError>(())
+    });
+
+    Box::pin(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entry_size, split_key, BLOCK_SIZE};
+
+    #[test]
+    fn sample_keys() {
+        assert_eq!(split_key("a/000042.jpg"), ("a/000042", "jpg"));
+        assert_eq!(split_key("a.b/000042.mask.png"), ("a.b/000042", "mask.png"));
+        assert_eq!(split_key("000042"), ("000042", ""));
+    }
+
+    #[test]
+    fn entry_sizes() {
+        let header = |size: &[u8]| {
+            let mut header = [0; BLOCK_SIZE];
+            header[124..124 + size.len()].copy_from_slice(size);
+            header
+        };
+        assert_eq!(entry_size(&header(b"00000001750\0")).ok(), Some(1000));
+        assert_eq!(entry_size(&header(b"")).ok(), Some(0));
+        assert!(entry_size(&header(b"77777777777\0")).is_err());
+        assert!(entry_size(&header(b"12x\0")).is_err());
+    }
+}
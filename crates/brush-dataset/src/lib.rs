@@ -1,106 +1,63 @@
+#[cfg(feature = "dataset")]
+pub mod binary_model;
+#[cfg(feature = "dataset")]
 pub mod brush_vfs;
+#[cfg(all(feature = "dataset", not(target_family = "wasm")))]
+pub mod checkpoint;
+#[cfg(feature = "dataset")]
+pub mod chunk_export;
+#[cfg(feature = "dataset")]
+pub mod collision_export;
+pub mod coordinates;
+#[cfg(feature = "dataset")]
+mod dataset;
+#[cfg(feature = "dataset")]
+pub mod dataset_export;
+#[cfg(feature = "dataset")]
+pub mod distill;
+pub mod edit_layers;
+#[cfg(feature = "dataset")]
+mod error;
+#[cfg(feature = "dataset")]
+mod exif;
+#[cfg(feature = "dataset")]
 mod formats;
+pub mod model_info;
+#[cfg(feature = "dataset")]
+pub mod novel_views;
+#[cfg(feature = "dataset")]
+pub mod npy;
+#[cfg(feature = "dataset")]
+pub mod octree_export;
+#[cfg(feature = "dataset")]
+pub mod photometric;
+#[cfg(feature = "dataset")]
+pub mod point_cloud;
+#[cfg(feature = "dataset")]
+pub mod presets;
+pub mod progressive_export;
+#[cfg(feature = "dataset")]
+pub mod render_views;
+#[cfg(feature = "dataset")]
 pub mod scene_loader;
 pub mod splat_export;
 pub mod splat_import;
-
-pub use formats::load_dataset;
-
-use async_fn_stream::fn_stream;
-use brush_train::scene::{Scene, SceneView};
-use image::DynamicImage;
-use std::future::Future;
-
-use tokio_stream::Stream;
-use tokio_with_wasm::alias as tokio_wasm;
-
-#[derive(Clone, Default, Debug)]
-pub struct LoadDatasetArgs {
-    pub max_frames: Option<usize>,
-    pub max_resolution: Option<u32>,
-    pub eval_split_every: Option<usize>,
-    pub subsample_frames: Option<u32>,
-    pub subsample_points: Option<u32>,
-}
-
-#[derive(Clone, Debug)]
-pub struct LoadInitArgs {
-    pub sh_degree: u32,
-}
-
-impl Default for LoadInitArgs {
-    fn default() -> Self {
-        Self { sh_degree: 3 }
-    }
-}
-
-#[derive(Clone)]
-pub struct Dataset {
-    pub train: Scene,
-    pub eval: Option<Scene>,
-}
-
-impl Dataset {
-    pub fn empty() -> Self {
-        Self {
-            train: Scene::new(vec![]),
-            eval: None,
-        }
-    }
-
-    pub fn from_views(train_views: Vec<SceneView>, eval_views: Vec<SceneView>) -> Self {
-        Self {
-            train: Scene::new(train_views),
-            eval: if eval_views.is_empty() {
-                None
-            } else {
-                Some(Scene::new(eval_views))
-            },
-        }
-    }
-}
-
-pub(crate) fn clamp_img_to_max_size(image: DynamicImage, max_size: u32) -> DynamicImage {
-    if image.width() <= max_size && image.height() <= max_size {
-        return image;
-    }
-
-    let aspect_ratio = image.width() as f32 / image.height() as f32;
-    let (new_width, new_height) = if image.width() > image.height() {
-        (max_size, (max_size as f32 / aspect_ratio) as u32)
-    } else {
-        ((max_size as f32 * aspect_ratio) as u32, max_size)
-    };
-    image.resize(new_width, new_height, image::imageops::FilterType::Lanczos3)
-}
-
-pub(crate) fn stream_fut_parallel<T: Send + 'static>(
-    futures: Vec<impl Future<Output = T> + Send + 'static>,
-) -> impl Stream<Item = T> {
-    let parallel = if cfg!(target_family = "wasm") {
-        1
-    } else {
-        std::thread::available_parallelism()
-            .map(|x| x.get())
-            .unwrap_or(8)
-    };
-
-    log::info!("Loading stream with {parallel} threads");
-
-    let mut futures = futures;
-    fn_stream(|emitter| async move {
-        while !futures.is_empty() {
-            // Spawn a batch of threads.
-            let handles: Vec<_> = futures
-                .drain(..futures.len().min(parallel))
-                .map(|fut| tokio_wasm::spawn(fut))
-                .collect();
-            // Stream each of them.
-            for handle in handles {
-                emitter
-                    .emit(handle.await.expect("Underlying stream panicked"))
-                    .await;
-            }
-        }
-    })
-}
+#[cfg(feature = "dataset")]
+pub mod survey_diff;
+
+#[cfg(feature = "dataset")]
+pub use dataset::{Dataset, LoadDatasetArgs, LoadInitArgs, ReconstructionStats};
+#[cfg(feature = "dataset")]
+pub use error::DatasetError;
+#[cfg(feature = "dataset")]
+pub use formats::{
+    load_captures, load_dataset,
+    source::{register_source, DatasetSource},
+    webdataset::{load_webdataset, WebDatasetIndex},
+    DataStream,
+};
+
+#[cfg(feature = "dataset")]
+pub(crate) use dataset::{
+    clamp_img_to_max_size, decode_feature_map, decode_image, decode_mask, eval_split, view_image,
+};
@@ -0,0 +1,183 @@
+//! Where a model came from, stored with exports and checkpoints.
+//!
+//! Models get copied around, and a ply on its own says nothing about how it was made. The
+//! provenance is written as a `Brush metadata: {json}` comment in ply headers, and in the
+//! header of checkpoints, and can be printed with `brush_app info <model>`.
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "dataset")]
+use brush_render::Backend;
+#[cfg(feature = "dataset")]
+use brush_train::{eval::EvalStats, train::TrainConfig};
+
+#[cfg(feature = "dataset")]
+use crate::Dataset;
+
+const PLY_COMMENT_PREFIX: &str = "Brush metadata: ";
+
+/// Provenance of a trained model, see the [module docs](self).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// Version of Brush that wrote the model.
+    pub brush_version: String,
+    /// Hash of the training config, to tell apart models trained with different settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_hash: Option<String>,
+    /// Hash of the views the model was trained on, see [`dataset_fingerprint`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dataset_fingerprint: Option<String>,
+    /// Training step the model was saved at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iter: Option<u32>,
+    /// Mean eval PSNR of the last eval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub psnr: Option<f32>,
+    /// Mean eval SSIM of the last eval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssim: Option<f32>,
+}
+
+impl Default for ModelInfo {
+    fn default() -> Self {
+        Self {
+            brush_version: env!("CARGO_PKG_VERSION").to_owned(),
+            config_hash: None,
+            dataset_fingerprint: None,
+            iter: None,
+            psnr: None,
+            ssim: None,
+        }
+    }
+}
+
+// FNV-1a, which unlike the std hasher gives the same hash on every platform and version.
+#[cfg(feature = "dataset")]
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> String {
+    let hash = bytes.into_iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Hash of a training config.
+#[cfg(feature = "dataset")]
+pub fn config_hash(config: &TrainConfig) -> String {
+    let json = serde_json::to_string(config).unwrap_or_default();
+    fnv1a(json.into_bytes())
+}
+
+/// Hash of the views of a dataset: their names, image sizes and poses. The order the views
+/// loaded in doesn't matter.
+#[cfg(feature = "dataset")]
+pub fn dataset_fingerprint(dataset: &Dataset) -> String {
+    let mut views: Vec<_> = dataset
+        .train
+        .views
+        .iter()
+        .chain(dataset.eval.iter().flat_map(|s| s.views.iter()))
+        .map(|view| {
+            let mut bytes = view.name.as_bytes().to_vec();
+            bytes.extend(view.image.width().to_le_bytes());
+            bytes.extend(view.image.height().to_le_bytes());
+            let pose = view.camera.position.to_array().into_iter();
+            for v in pose.chain(view.camera.rotation.to_array()) {
+                bytes.extend(v.to_le_bytes());
+            }
+            bytes
+        })
+        .collect();
+    views.sort();
+    fnv1a(views.into_iter().flatten())
+}
+
+impl ModelInfo {
+    /// Info of a model trained with the given config, on the given dataset.
+    #[cfg(feature = "dataset")]
+    pub fn trained(config: &TrainConfig, dataset: &Dataset) -> Self {
+        Self {
+            config_hash: Some(config_hash(config)),
+            dataset_fingerprint: (!dataset.train.views.is_empty())
+                .then(|| dataset_fingerprint(dataset)),
+            ..Default::default()
+        }
+    }
+
+    /// Set the metrics to the means of an eval.
+    #[cfg(feature = "dataset")]
+    pub fn set_eval<B: Backend>(&mut self, eval: &EvalStats<B>) {
+        if eval.samples.is_empty() {
+            return;
+        }
+        let n = eval.samples.len() as f32;
+        self.psnr = Some(eval.samples.iter().map(|s| s.psnr).sum::<f32>() / n);
+        self.ssim = Some(eval.samples.iter().map(|s| s.ssim).sum::<f32>() / n);
+    }
+
+    /// The info as a ply header comment.
+    pub fn to_ply_comment(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        format!("{PLY_COMMENT_PREFIX}{json}")
+    }
+
+    /// Find the info in the comments of a ply header, if it was written by Brush.
+    pub fn from_ply_comments(comments: &[String]) -> Option<Self> {
+        comments
+            .iter()
+            .find_map(|c| c.strip_prefix(PLY_COMMENT_PREFIX))
+            .and_then(|json| serde_json::from_str(json).ok())
+    }
+}
+
+impl std::fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Brush version:       {}", self.brush_version)?;
+        let unknown = || "unknown".to_owned();
+        writeln!(
+            f,
+            "Training step:       {}",
+            self.iter.map_or_else(unknown, |i| i.to_string())
+        )?;
+        writeln!(
+            f,
+            "Eval PSNR:           {}",
+            self.psnr.map_or_else(unknown, |p| format!("{p:.2}"))
+        )?;
+        writeln!(
+            f,
+            "Eval SSIM:           {}",
+            self.ssim.map_or_else(unknown, |s| format!("{s:.4}"))
+        )?;
+        writeln!(
+            f,
+            "Config hash:         {}",
+            self.config_hash.clone().unwrap_or_else(unknown)
+        )?;
+        write!(
+            f,
+            "Dataset fingerprint: {}",
+            self.dataset_fingerprint.clone().unwrap_or_else(unknown)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelInfo;
+
+    #[test]
+    fn ply_comment_round_trip() {
+        let info = ModelInfo {
+            iter: Some(30000),
+            psnr: Some(27.5),
+            dataset_fingerprint: Some("0123456789abcdef".to_owned()),
+            ..Default::default()
+        };
+        let comments = vec![
+            "Exported from Brush".to_owned(),
+            info.to_ply_comment(),
+            "Vertical axis: y".to_owned(),
+        ];
+        assert_eq!(ModelInfo::from_ply_comments(&comments), Some(info));
+        assert_eq!(ModelInfo::from_ply_comments(&comments[..1]), None);
+    }
+}
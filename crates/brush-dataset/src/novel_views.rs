@@ -0,0 +1,190 @@
+//! Synthesize a dataset from new viewpoints of a trained model.
+//!
+//! New cameras are sampled along the capture trajectory: between consecutive training views,
+//! moved and turned a little. The model is rendered from each of them, and written as a
+//! nerfstudio dataset with depth maps, eg. to bootstrap other models, or to fill in sparse
+//! captures.
+use std::{io::Cursor, path::PathBuf};
+
+use async_fn_stream::try_fn_stream;
+use brush_render::{camera::Camera, depth::render_depth, gaussian_splats::Splats, Backend};
+use brush_tasks::ProgressSender;
+use brush_train::{image::tensor_into_image, scene::Scene};
+use glam::{Quat, Vec3};
+use image::{DynamicImage, ImageBuffer, Luma};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::json;
+use tokio_stream::Stream;
+
+use crate::dataset_export::frame_json;
+
+/// Depth maps are written as 16 bit pngs in this unit, so depths up to 65 scene units fit.
+pub const DEPTH_UNIT: f32 = 1e-3;
+
+/// How to sample the new views.
+#[derive(Clone, Debug)]
+pub struct NovelViewConfig {
+    /// Number of new views between every two consecutive training views.
+    pub views_per_pair: usize,
+    /// How far views move off the trajectory at most, as a fraction of the scene extent.
+    pub jitter: f32,
+    /// How far views turn off the trajectory at most, in degrees.
+    pub max_angle: f32,
+    pub seed: u64,
+}
+
+impl Default for NovelViewConfig {
+    fn default() -> Self {
+        Self {
+            views_per_pair: 2,
+            jitter: 0.05,
+            max_angle: 10.0,
+            seed: 42,
+        }
+    }
+}
+
+fn random_direction(rng: &mut impl Rng) -> Vec3 {
+    loop {
+        let v = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let len = v.length();
+        if len > 1e-3 && len <= 1.0 {
+            return v / len;
+        }
+    }
+}
+
+/// Sample new cameras along the trajectory of the views of a scene, with the image size they
+/// should be rendered at. Views are taken in the order of the scene, which follows the
+/// capture for video and most photo captures.
+pub fn sample_novel_views(scene: &Scene, config: &NovelViewConfig) -> Vec<(Camera, glam::UVec2)> {
+    let views = &scene.views;
+    if views.is_empty() {
+        return vec![];
+    }
+
+    let extent = scene.extent(&[]);
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    // A single view still gets views around it.
+    let pairs: Vec<_> = if views.len() == 1 {
+        vec![(&views[0], &views[0])]
+    } else {
+        views.windows(2).map(|w| (&w[0], &w[1])).collect()
+    };
+
+    let mut cameras = vec![];
+    for (a, b) in pairs {
+        for _ in 0..config.views_per_pair {
+            let t = rng.gen::<f32>();
+            let offset = random_direction(&mut rng) * config.jitter * extent * rng.gen::<f32>();
+            let position = a.camera.position.lerp(b.camera.position, t) + offset;
+
+            let angle = rng
+                .gen_range(-config.max_angle..=config.max_angle)
+                .to_radians();
+            let turn = Quat::from_axis_angle(random_direction(&mut rng), angle);
+            let rotation = (a.camera.rotation.slerp(b.camera.rotation, t) * turn).normalize();
+
+            let camera = Camera::new(
+                position,
+                rotation,
+                a.camera.fov_x,
+                a.camera.fov_y,
+                a.camera.center_uv,
+            );
+            let size = glam::uvec2(a.image.width(), a.image.height());
+            cameras.push((camera, size));
+        }
+    }
+    cameras
+}
+
+fn encode_png(image: DynamicImage) -> anyhow::Result<Vec<u8>> {
+    let mut png = vec![];
+    image.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Render the model from new views of a scene, see the [module docs](self). Streams the
+/// images, depth maps and finally the `transforms.json` as files to write.
+pub fn render_novel_views<B: Backend>(
+    splats: Splats<B>,
+    scene: &Scene,
+    config: &NovelViewConfig,
+    progress: ProgressSender,
+) -> impl Stream<Item = anyhow::Result<(PathBuf, Vec<u8>)>> + 'static {
+    let cameras = sample_novel_views(scene, config);
+
+    try_fn_stream(|emitter| async move {
+        let mut frames = vec![];
+        let total = cameras.len();
+
+        for (i, (camera, size)) in cameras.into_iter().enumerate() {
+            let (w, h) = (size.x as usize, size.y as usize);
+            let (rendered, _) = splats.render(&camera, size, false);
+            let rgb = rendered.slice([0..h, 0..w, 0..3]).clamp(0.0, 1.0);
+            let depth = render_depth(&splats, &camera, size);
+
+            let rgb = rgb.into_data_async().await;
+            let depth = depth
+                .into_data_async()
+                .await
+                .to_vec::<f32>()
+                .map_err(|e| anyhow::anyhow!("Failed to read depth {e:?}"))?;
+
+            let (png, depth_png) = brush_tasks::run_blocking(move || {
+                let png = encode_png(DynamicImage::ImageRgb8(tensor_into_image(rgb).to_rgb8()))?;
+                let depth: Vec<u16> = depth
+                    .iter()
+                    .map(|d| (d / DEPTH_UNIT).round().clamp(0.0, u16::MAX as f32) as u16)
+                    .collect();
+                let depth = ImageBuffer::<Luma<u16>, _>::from_raw(size.x, size.y, depth)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid depth map size"))?;
+                let depth_png = encode_png(DynamicImage::ImageLuma16(depth))?;
+                anyhow::Ok((png, depth_png))
+            })
+            .await?;
+
+            let file_path = format!("images/{i:05}.png");
+            let depth_path = format!("depths/{i:05}.png");
+            let mut frame = frame_json(&camera, size, &file_path);
+            frame["depth_file_path"] = json!(depth_path);
+            frames.push(frame);
+
+            emitter.emit((PathBuf::from(file_path), png)).await;
+            emitter.emit((PathBuf::from(depth_path), depth_png)).await;
+            progress.report("Rendering novel views", i + 1, total);
+        }
+
+        let transforms = json!({
+            "camera_model": "PINHOLE",
+            "depth_unit_scale_factor": DEPTH_UNIT,
+            "frames": frames,
+        });
+        emitter
+            .emit((
+                PathBuf::from("transforms.json"),
+                serde_json::to_string_pretty(&transforms)?.into_bytes(),
+            ))
+            .await;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::random_direction;
+    use rand::SeedableRng;
+
+    #[test]
+    fn directions_are_normalized() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert!((random_direction(&mut rng).length() - 1.0).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Minimal reading and writing of `.npy` arrays, as saved by `numpy.save`, for feature maps
+//! and exported splat features.
+
+use anyhow::{bail, Context, Result};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Read a little endian float32 or float64 array, returning its shape and values in row major
+/// order.
+pub fn read_npy_f32(bytes: &[u8]) -> Result<(Vec<usize>, Vec<f32>)> {
+    if bytes.len() < 10 || &bytes[..6] != MAGIC {
+        bail!("Not a .npy file");
+    }
+    let major = bytes[6];
+    let (header_len, header_start) = if major == 1 {
+        (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10)
+    } else {
+        let len = bytes.get(8..12).context("Truncated .npy header")?;
+        (
+            u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize,
+            12,
+        )
+    };
+    let header = bytes
+        .get(header_start..header_start + header_len)
+        .context("Truncated .npy header")?;
+    let header = std::str::from_utf8(header).context("Invalid .npy header")?;
+    let data = &bytes[header_start + header_len..];
+
+    let descr = header_value(header, "descr").context("No dtype in .npy header")?;
+    let descr = descr
+        .split(',')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches(|c| c == '\'' || c == '"');
+    if header_value(header, "fortran_order").is_some_and(|v| v.starts_with("True")) {
+        bail!("Fortran ordered .npy arrays aren't supported");
+    }
+    let shape = header_value(header, "shape").context("No shape in .npy header")?;
+    let shape = shape
+        .trim_start_matches('(')
+        .split(')')
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid .npy shape")?;
+    let count: usize = shape.iter().product();
+
+    let values: Vec<f32> = match descr {
+        "<f4" => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        "<f8" => data
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32)
+            .collect(),
+        other => bail!("Unsupported .npy dtype {other}, expected float32 or float64"),
+    };
+    if values.len() < count {
+        bail!("Truncated .npy data");
+    }
+    Ok((shape, values[..count].to_vec()))
+}
+
+/// Write a float32 array of the given shape as a `.npy` file.
+pub fn write_npy_f32(shape: &[usize], values: &[f32]) -> Vec<u8> {
+    let shape = match shape {
+        [n] => format!("({n},)"),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {shape}, }}");
+    // The data starts 64 byte aligned, and the header ends with a newline.
+    let total = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat(total.next_multiple_of(64) - total));
+    header.push('\n');
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend([1, 0]);
+    bytes.extend((header.len() as u16).to_le_bytes());
+    bytes.extend(header.as_bytes());
+    for v in values {
+        bytes.extend(v.to_le_bytes());
+    }
+    bytes
+}
+
+// Raw value of a key in the python dict literal of a header.
+fn header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{key}'"))? + key.len() + 2;
+    let rest = header[start..].trim_start().strip_prefix(':')?;
+    Some(rest.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_npy_f32, write_npy_f32};
+
+    #[test]
+    fn roundtrip() {
+        let values: Vec<f32> = (0..24).map(|i| i as f32 * 0.5 - 3.0).collect();
+        let bytes = write_npy_f32(&[2, 3, 4], &values);
+        assert_eq!((bytes.len() - values.len() * 4) % 64, 0);
+
+        let (shape, read) = read_npy_f32(&bytes).expect("Failed to read npy");
+        assert_eq!(shape, vec![2, 3, 4]);
+        assert_eq!(read, values);
+    }
+}
@@ -0,0 +1,265 @@
+//! Octree chunk export with levels of detail, for viewers that stream large scenes.
+//!
+//! The model is split into an octree: a node with too many splats is split into its 8
+//! octants, until each leaf has at most a given number of splats. Every leaf is a chunk, written
+//! at several levels of detail: the full chunk, and decimated copies with 4 times fewer
+//! splats each. Decimated levels keep the splats that cover the most of the image, see
+//! [`crate::progressive_export`], so far away chunks can be drawn with a fraction of the
+//! splats.
+//!
+//! The `index.json` manifest lists the nodes of the octree, with their bounds, children and
+//! for leaves the files of each level, from full to coarsest. The chunk files use the same
+//! record layout as [`crate::chunk_export`].
+use std::path::PathBuf;
+
+use brush_render::{gaussian_splats::Splats, Backend};
+use glam::Vec3;
+use serde_json::json;
+
+use crate::{
+    chunk_export::{pack_splat, record_layout, splat_extent},
+    collision_export::{collision_obj, DEFAULT_COLLISION_RESOLUTION},
+    coordinates::{CoordinateConvention, Handedness},
+    progressive_export::importance,
+    splat_export::read_splats_in_convention,
+    splat_import::GaussianData,
+};
+
+/// Number of levels of detail per chunk when not specified otherwise, including the full one.
+pub const DEFAULT_LOD_LEVELS: usize = 3;
+
+// Each level of detail has this many times fewer splats than the previous one.
+const LOD_REDUCTION: usize = 4;
+
+// Levels aren't decimated below this many splats, it wouldn't save much.
+const MIN_LOD_SPLATS: usize = 256;
+
+// Stop splitting here, eg. when many splats are at the exact same position.
+const MAX_DEPTH: u32 = 16;
+
+struct OctreeNode {
+    // Name of the node, `r` for the root and a digit per octant below it, eg. `r05`.
+    name: String,
+    depth: u32,
+    min: Vec3,
+    size: f32,
+    // Indices of the splats, only for leaves.
+    splats: Vec<usize>,
+    children: Vec<usize>,
+}
+
+// Split the splats into an octree of leaves with at most `max_count` splats, the root first.
+fn build_octree(data: &[GaussianData], max_count: usize) -> Vec<OctreeNode> {
+    let (min, max) = data.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), d| (min.min(d.means), max.max(d.means)),
+    );
+    let min = if data.is_empty() { Vec3::ZERO } else { min };
+    let size = (max - min).max_element().max(1e-6);
+
+    let mut nodes = vec![OctreeNode {
+        name: "r".to_owned(),
+        depth: 0,
+        min,
+        size,
+        splats: (0..data.len()).collect(),
+        children: vec![],
+    }];
+
+    let mut i = 0;
+    while i < nodes.len() {
+        if nodes[i].splats.len() > max_count && nodes[i].depth < MAX_DEPTH {
+            let splats = std::mem::take(&mut nodes[i].splats);
+            let half = nodes[i].size / 2.0;
+            let center = nodes[i].min + half;
+
+            let mut octants = vec![vec![]; 8];
+            for idx in splats {
+                let offset = data[idx].means.cmpge(center);
+                let octant =
+                    offset.x as usize | (offset.y as usize) << 1 | (offset.z as usize) << 2;
+                octants[octant].push(idx);
+            }
+
+            for (octant, splats) in octants.into_iter().enumerate() {
+                if splats.is_empty() {
+                    continue;
+                }
+                let offset = Vec3::new(
+                    (octant & 1) as f32,
+                    ((octant >> 1) & 1) as f32,
+                    ((octant >> 2) & 1) as f32,
+                );
+                let child = OctreeNode {
+                    name: format!("{}{octant}", nodes[i].name),
+                    depth: nodes[i].depth + 1,
+                    min: nodes[i].min + offset * half,
+                    size: half,
+                    splats,
+                    children: vec![],
+                };
+                let child_index = nodes.len();
+                nodes.push(child);
+                nodes[i].children.push(child_index);
+            }
+        }
+        i += 1;
+    }
+    nodes
+}
+
+// Number of splats of each level of detail of a chunk of `count` splats, from full to coarsest.
+fn lod_counts(count: usize, levels: usize) -> Vec<usize> {
+    let mut counts = vec![count];
+    while counts.len() < levels {
+        let next = counts[counts.len() - 1] / LOD_REDUCTION;
+        if next < MIN_LOD_SPLATS {
+            break;
+        }
+        counts.push(next);
+    }
+    counts
+}
+
+/// Export splats as an octree of chunks with levels of detail, see the
+/// [module docs](self), as a list of files to write.
+pub async fn splats_to_octree<B: Backend>(
+    splats: Splats<B>,
+    max_splats_per_chunk: usize,
+    lod_levels: usize,
+    convention: CoordinateConvention,
+) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let sh_coeffs_num = splats.sh_coeffs.dims()[1];
+    let data = read_splats_in_convention(splats, &[], convention).await?;
+    let layout = record_layout(sh_coeffs_num);
+    let nodes = build_octree(&data, max_splats_per_chunk.max(1));
+
+    let mut files = vec![];
+    let mut levels = vec![vec![]; nodes.len()];
+    let mut bounds =
+        vec![(Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)); nodes.len()];
+
+    for (i, node) in nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !n.splats.is_empty())
+    {
+        // Most important first, so each level is a prefix of the full chunk.
+        let weights: Vec<_> = node
+            .splats
+            .iter()
+            .map(|&idx| importance(&data[idx]))
+            .collect();
+        let mut order: Vec<_> = (0..node.splats.len()).collect();
+        order.sort_by(|&a, &b| weights[b].total_cmp(&weights[a]));
+
+        let mut blob = Vec::with_capacity(node.splats.len() * layout.len() * 4);
+        let (mut min, mut max) = bounds[i];
+        for &o in &order {
+            let splat = &data[node.splats[o]];
+            let extent = splat_extent(splat);
+            min = min.min(splat.means - extent);
+            max = max.max(splat.means + extent);
+            pack_splat(splat, &mut blob);
+        }
+        bounds[i] = (min, max);
+
+        let stride = layout.len() * 4;
+        for (level, count) in lod_counts(node.splats.len(), lod_levels.max(1))
+            .into_iter()
+            .enumerate()
+        {
+            let file = format!("chunks/{}_{level}.bin", node.name);
+            levels[i].push(json!({ "file": file, "count": count }));
+            files.push((PathBuf::from(file), blob[..count * stride].to_vec()));
+        }
+    }
+
+    // Children come after their parents, so walking backwards gathers the bounds bottom up.
+    for i in (0..nodes.len()).rev() {
+        for &child in &nodes[i].children {
+            bounds[i] = (
+                bounds[i].0.min(bounds[child].0),
+                bounds[i].1.max(bounds[child].1),
+            );
+        }
+    }
+
+    let manifest_nodes: Vec<_> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            json!({
+                "name": node.name,
+                "depth": node.depth,
+                "min": bounds[i].0.to_array(),
+                "max": bounds[i].1.to_array(),
+                "children": node.children,
+                "count": node.splats.len(),
+                "levels": levels[i],
+            })
+        })
+        .collect();
+
+    let left_handed = convention.handedness == Handedness::Left;
+    let collision = collision_obj(&data, DEFAULT_COLLISION_RESOLUTION, left_handed);
+    files.push((PathBuf::from("collision.obj"), collision.into_bytes()));
+
+    let index = json!({
+        "version": 1,
+        "kind": "octree",
+        "coordinates": convention.name(),
+        "vertical_axis": convention.up_axis_name(),
+        "sh_degree": (sh_coeffs_num as f32).sqrt() as u32 - 1,
+        "total_splats": data.len(),
+        "layout": layout,
+        "stride": layout.len() * 4,
+        "lod_reduction": LOD_REDUCTION,
+        "min": bounds[0].0.to_array(),
+        "max": bounds[0].1.to_array(),
+        "nodes": manifest_nodes,
+        "collision": "collision.obj",
+    });
+    files.push((
+        PathBuf::from("index.json"),
+        serde_json::to_string_pretty(&index)?.into_bytes(),
+    ));
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use ply_rs::ply::PropertyAccess;
+
+    use super::{build_octree, lod_counts};
+    use crate::splat_import::GaussianData;
+
+    #[test]
+    fn octree_leaves_are_bounded_and_cover_all() {
+        let data: Vec<_> = (0..1000)
+            .map(|i| {
+                let mut splat = GaussianData::new();
+                splat.means = Vec3::new((i % 10) as f32, (i / 100) as f32, ((i / 10) % 10) as f32);
+                splat
+            })
+            .collect();
+
+        let nodes = build_octree(&data, 100);
+        assert_eq!(nodes[0].name, "r");
+
+        let leaves: Vec<_> = nodes.iter().filter(|n| n.children.is_empty()).collect();
+        assert!(leaves.iter().all(|n| n.splats.len() <= 100));
+        assert!(nodes
+            .iter()
+            .all(|n| n.children.is_empty() || n.splats.is_empty()));
+
+        let mut all: Vec<_> = leaves.iter().flat_map(|n| n.splats.clone()).collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..1000).collect::<Vec<_>>());
+
+        assert_eq!(lod_counts(65536, 3), vec![65536, 16384, 4096]);
+        assert_eq!(lod_counts(600, 3), vec![600]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Photometric corrections of dataset images, for captures where the colors of the same
+//! surface differ between images, eg. phone captures with aggressive ISP processing.
+//!
+//! Both corrections are estimated from each image on its own, as images are loaded in
+//! parallel:
+//! - Vignetting is modelled as a radial falloff `1 + k1 r² + k2 r⁴` of the brightness, fit to
+//!   the mean brightness of rings around the image center, and divided out.
+//! - White balance is normalized with the gray world assumption: each image is scaled per
+//!   channel so its average color is neutral, keeping its brightness. This removes casts that
+//!   change between images, but also the overall tint of scenes with one dominant color.
+use image::{DynamicImage, Rgb32FImage};
+
+// Number of rings to average the brightness over.
+const RINGS: usize = 16;
+// Pixels to sample at most when estimating, the corrections are smooth.
+const MAX_SAMPLES: usize = 1 << 18;
+// Clipped pixels don't tell how bright the surface is.
+const MAX_VALUE: f32 = 0.98;
+// Corrections are limited to this gain, to not blow up noise in dark corners or in images
+// of a single color.
+const MAX_GAIN: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PhotometricOptions {
+    /// Estimate and divide out the vignetting of each image.
+    pub vignette: bool,
+    /// Normalize the white balance of each image.
+    pub white_balance: bool,
+}
+
+impl PhotometricOptions {
+    pub fn any(&self) -> bool {
+        self.vignette || self.white_balance
+    }
+}
+
+/// Radial brightness falloff of an image, relative to its center.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vignette {
+    pub k1: f32,
+    pub k2: f32,
+}
+
+impl Vignette {
+    pub const NONE: Self = Self { k1: 0.0, k2: 0.0 };
+
+    /// The falloff at `r`, the distance to the center relative to half the diagonal.
+    pub fn falloff(&self, r: f32) -> f32 {
+        let r2 = r * r;
+        (1.0 + self.k1 * r2 + self.k2 * r2 * r2).clamp(1.0 / MAX_GAIN, 1.0)
+    }
+}
+
+fn luminance(p: [f32; 3]) -> f32 {
+    0.2126 * p[0] + 0.7152 * p[1] + 0.0722 * p[2]
+}
+
+// Normalized distance of each pixel to the image center.
+fn radius_fn(width: u32, height: u32) -> impl Fn(u32, u32) -> f32 {
+    let cx = width as f32 / 2.0;
+    let cy = height as f32 / 2.0;
+    let inv_diag = 1.0 / (cx * cx + cy * cy).sqrt().max(1.0);
+    move |x, y| {
+        let dx = x as f32 + 0.5 - cx;
+        let dy = y as f32 + 0.5 - cy;
+        (dx * dx + dy * dy).sqrt() * inv_diag
+    }
+}
+
+fn sample_stride(image: &Rgb32FImage) -> usize {
+    let pixels = image.width() as usize * image.height() as usize;
+    (pixels / MAX_SAMPLES).max(1)
+}
+
+/// Estimate the vignetting of an image. Returns [`Vignette::NONE`] when the brightness doesn't
+/// fall off towards the corners.
+pub fn estimate_vignette(image: &Rgb32FImage) -> Vignette {
+    let radius = radius_fn(image.width(), image.height());
+    // Per ring: the summed brightness, r² and r⁴ of the samples.
+    let mut sums = [[0.0f64; 3]; RINGS];
+    let mut counts = [0u32; RINGS];
+
+    let stride = sample_stride(image);
+    for (i, (x, y, p)) in image.enumerate_pixels().enumerate() {
+        if i % stride != 0 || p.0.iter().any(|&c| c >= MAX_VALUE) {
+            continue;
+        }
+        let r = radius(x, y);
+        let ring = ((r * RINGS as f32) as usize).min(RINGS - 1);
+        let r2 = (r * r) as f64;
+        sums[ring][0] += luminance(p.0) as f64;
+        sums[ring][1] += r2;
+        sums[ring][2] += r2 * r2;
+        counts[ring] += 1;
+    }
+
+    // Fit brightness = c + a r² + b r⁴ to the rings by weighted least squares.
+    let mut ata = [[0.0f64; 3]; 3];
+    let mut atb = [0.0f64; 3];
+    for (sum, &count) in sums.iter().zip(&counts) {
+        if count == 0 {
+            continue;
+        }
+        let w = count as f64;
+        let [value, r2, r4] = sum.map(|s| s / w);
+        let row = [1.0, r2, r4];
+        for ((ata_row, atb), &ri) in ata.iter_mut().zip(&mut atb).zip(&row) {
+            *atb += w * ri * value;
+            for (a, &rj) in ata_row.iter_mut().zip(&row) {
+                *a += w * ri * rj;
+            }
+        }
+    }
+
+    let Some([c, a, b]) = solve3(ata, atb) else {
+        return Vignette::NONE;
+    };
+    if c <= 1e-4 {
+        return Vignette::NONE;
+    }
+    let vignette = Vignette {
+        k1: (a / c) as f32,
+        k2: (b / c) as f32,
+    };
+    // Only correct a clear darkening towards the corners, brighter corners are the scene
+    // itself.
+    if vignette.falloff(1.0) >= 0.99 {
+        return Vignette::NONE;
+    }
+    vignette
+}
+
+// Solve a 3x3 system with Cramer's rule.
+fn solve3(m: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(m);
+    if d.abs() < 1e-12 {
+        return None;
+    }
+    let mut x = [0.0; 3];
+    for (col, x) in x.iter_mut().enumerate() {
+        let mut mc = m;
+        for (row, &b) in mc.iter_mut().zip(&b) {
+            row[col] = b;
+        }
+        *x = det(mc) / d;
+    }
+    Some(x)
+}
+
+/// Per channel gains that make the average color of an image neutral, keeping its brightness.
+pub fn estimate_white_balance(image: &Rgb32FImage) -> [f32; 3] {
+    let mut sums = [0.0f64; 3];
+    let stride = sample_stride(image);
+    for p in image.pixels().step_by(stride) {
+        if p.0.iter().any(|&c| c >= MAX_VALUE) {
+            continue;
+        }
+        for (sum, &c) in sums.iter_mut().zip(&p.0) {
+            *sum += c as f64;
+        }
+    }
+    let mean = sums.map(|s| s as f32);
+    let gray = luminance(mean);
+    if mean.iter().any(|&m| m <= 1e-6) || gray <= 1e-6 {
+        return [1.0; 3];
+    }
+    mean.map(|m| (gray / m).clamp(1.0 / MAX_GAIN, MAX_GAIN))
+}
+
+/// Apply the enabled corrections to an image. The alpha channel, if any, is kept.
+pub fn correct_image(image: DynamicImage, options: PhotometricOptions) -> DynamicImage {
+    if !options.any() {
+        return image;
+    }
+    let _span = tracing::trace_span!("Photometric correction").entered();
+
+    let alpha = image.color().has_alpha().then(|| image.to_luma_alpha8());
+    let mut rgb = image.into_rgb32f();
+
+    let vignette = if options.vignette {
+        estimate_vignette(&rgb)
+    } else {
+        Vignette::NONE
+    };
+    if vignette != Vignette::NONE {
+        let radius = radius_fn(rgb.width(), rgb.height());
+        for (x, y, p) in rgb.enumerate_pixels_mut() {
+            let gain = 1.0 / vignette.falloff(radius(x, y));
+            p.0 = p.0.map(|c| c * gain);
+        }
+    }
+
+    if options.white_balance {
+        let gains = estimate_white_balance(&rgb);
+        for p in rgb.pixels_mut() {
+            p.0 = [p.0[0] * gains[0], p.0[1] * gains[1], p.0[2] * gains[2]];
+        }
+    }
+
+    let rgb = DynamicImage::ImageRgb32F(rgb).into_rgb8();
+    match alpha {
+        None => DynamicImage::ImageRgb8(rgb),
+        Some(alpha) => {
+            let rgba = image::RgbaImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+                let [r, g, b] = rgb.get_pixel(x, y).0;
+                image::Rgba([r, g, b, alpha.get_pixel(x, y).0[1]])
+            });
+            DynamicImage::ImageRgba8(rgba)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, Rgb32FImage};
+
+    use super::{estimate_vignette, estimate_white_balance, radius_fn, Vignette};
+
+    #[test]
+    fn estimates_vignette_and_white_balance() {
+        let truth = Vignette { k1: -0.3, k2: 0.0 };
+        let radius = radius_fn(200, 100);
+        let image = Rgb32FImage::from_fn(200, 100, |x, y| {
+            let f = truth.falloff(radius(x, y));
+            Rgb([0.6 * f, 0.5 * f, 0.3 * f])
+        });
+
+        let vignette = estimate_vignette(&image);
+        assert!((vignette.falloff(1.0) - truth.falloff(1.0)).abs() < 0.02);
+        assert!((vignette.falloff(0.5) - truth.falloff(0.5)).abs() < 0.02);
+
+        let gains = estimate_white_balance(&image);
+        assert!(gains[0] < 1.0 && gains[2] > 1.0);
+        let balanced = [0.6 * gains[0], 0.5 * gains[1], 0.3 * gains[2]];
+        assert!((balanced[0] - balanced[2]).abs() < 1e-4);
+    }
+
+    #[test]
+    fn flat_image_has_no_vignette() {
+        let image = Rgb32FImage::from_pixel(64, 64, Rgb([0.5, 0.5, 0.5]));
+        assert_eq!(estimate_vignette(&image), Vignette::NONE);
+    }
+}
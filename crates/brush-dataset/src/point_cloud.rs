@@ -0,0 +1,222 @@
+//! Initial splats from noisy point clouds, eg. from lidar or depth sensors.
+//!
+//! These point clouds have stray points floating in front of and behind the surfaces, which
+//! early training spends splats on. Points far from their neighbours are removed
+//! (statistical outlier removal), and the remaining splats are flattened along the surface
+//! around them.
+use brush_render::{gaussian_splats::Splats, spatial_index::SpatialIndex, Backend};
+use glam::{Mat3, Quat, Vec3};
+
+/// How to filter the initial point cloud, see the [module docs](self).
+#[derive(Clone, Copy, Debug)]
+pub struct PointCloudFilter {
+    /// Number of neighbours each point is compared to.
+    pub neighbours: usize,
+    /// Points with a mean distance to their neighbours of more than this many standard
+    /// deviations above the average are removed.
+    pub std_ratio: f32,
+}
+
+impl Default for PointCloudFilter {
+    fn default() -> Self {
+        Self {
+            neighbours: 16,
+            std_ratio: 2.0,
+        }
+    }
+}
+
+/// Which points are outliers, as points with a mean distance to their `neighbours` nearest
+/// points of more than `std_ratio` standard deviations above the average.
+pub fn statistical_outliers(points: &[Vec3], neighbours: usize, std_ratio: f32) -> Vec<bool> {
+    if points.len() <= neighbours {
+        return vec![false; points.len()];
+    }
+
+    let index = SpatialIndex::from_means(points);
+    let mean_distances: Vec<f32> = points
+        .iter()
+        .map(|&p| {
+            // The nearest point is the point itself.
+            let nearest = index.nearest(p, neighbours + 1);
+            nearest[1..].iter().map(|&(_, d)| d.sqrt()).sum::<f32>() / neighbours as f32
+        })
+        .collect();
+
+    let n = mean_distances.len() as f32;
+    let mean = mean_distances.iter().sum::<f32>() / n;
+    let std = (mean_distances
+        .iter()
+        .map(|d| (d - mean).powi(2))
+        .sum::<f32>()
+        / n)
+        .sqrt();
+    let threshold = mean + std_ratio * std;
+
+    mean_distances.iter().map(|&d| d > threshold).collect()
+}
+
+/// Eigenvalues and eigenvectors (as columns) of a symmetric matrix, with the Jacobi method.
+fn symmetric_eigen(m: Mat3) -> (Vec3, Mat3) {
+    let mut a = m.to_cols_array_2d();
+    let mut v = Mat3::IDENTITY.to_cols_array_2d();
+
+    for _ in 0..16 {
+        let off_diagonal = a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2);
+        if off_diagonal < 1e-20 {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q].abs() < 1e-20 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+
+            for k in 0..3 {
+                let (akp, akq) = (a[k][p], a[k][q]);
+                a[k][p] = c * akp - s * akq;
+                a[k][q] = s * akp + c * akq;
+            }
+            for k in 0..3 {
+                let (apk, aqk) = (a[p][k], a[q][k]);
+                a[p][k] = c * apk - s * aqk;
+                a[q][k] = s * apk + c * aqk;
+            }
+            for row in &mut v {
+                let (vp, vq) = (row[p], row[q]);
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+
+    // `v` is indexed as [row][column], the eigenvectors are its columns.
+    let v = Mat3::from_cols_array_2d(&v).transpose();
+    (Vec3::new(a[0][0], a[1][1], a[2][2]), v)
+}
+
+/// Rotations and log scales for splats at `points`, flattened along the surface through
+/// their `neighbours` nearest points. The shortest axis of each splat is the surface normal.
+pub fn surface_frames(points: &[Vec3], neighbours: usize) -> (Vec<Quat>, Vec<Vec3>) {
+    let index = SpatialIndex::from_means(points);
+
+    points
+        .iter()
+        .map(|&p| {
+            let nearest = index.nearest(p, neighbours.max(4));
+
+            // Size like unfiltered points, by the distance to the 3 nearest points.
+            let size = (nearest.iter().take(4).map(|&(_, d)| d).sum::<f32>() / 4.0)
+                .sqrt()
+                .max(1e-12);
+
+            if nearest.len() < 4 {
+                return (Quat::IDENTITY, Vec3::splat(size.ln()));
+            }
+
+            let centroid =
+                nearest.iter().map(|&(i, _)| points[i]).sum::<Vec3>() / nearest.len() as f32;
+            let covariance = nearest
+                .iter()
+                .map(|&(i, _)| {
+                    let d = points[i] - centroid;
+                    Mat3::from_cols(d * d.x, d * d.y, d * d.z)
+                })
+                .fold(Mat3::ZERO, |acc, m| acc + m)
+                * (1.0 / nearest.len() as f32);
+
+            let (values, vectors) = symmetric_eigen(covariance);
+            let mut order = [0, 1, 2];
+            order.sort_by(|&a, &b| values[b].total_cmp(&values[a]));
+            let [major, minor, normal] = order.map(|i| vectors.col(i).normalize_or_zero());
+            if major == Vec3::ZERO || minor == Vec3::ZERO || normal == Vec3::ZERO {
+                return (Quat::IDENTITY, Vec3::splat(size.ln()));
+            }
+            // Keep the frame right handed, so it's a rotation.
+            let normal = if major.cross(minor).dot(normal) < 0.0 {
+                -normal
+            } else {
+                normal
+            };
+
+            // How flat the neighbourhood is, not flattening to a sliver for noisy surfaces.
+            let flatness = (values[order[2]].max(0.0) / values[order[0]].max(1e-20))
+                .sqrt()
+                .clamp(0.1, 1.0);
+            let rotation = Quat::from_mat3(&Mat3::from_cols(major, minor, normal)).normalize();
+            let log_scales = Vec3::new(size.ln(), size.ln(), (size * flatness).ln());
+            (rotation, log_scales)
+        })
+        .unzip()
+}
+
+/// Remove outliers from initial splats made from a point cloud, and orient the others along
+/// the surface, see the [module docs](self).
+pub async fn filter_point_cloud<B: Backend>(
+    splats: Splats<B>,
+    filter: &PointCloudFilter,
+) -> anyhow::Result<Splats<B>> {
+    let device = splats.means.device();
+    let [_, n_coeffs, _] = splats.sh_coeffs.dims();
+    let means: Vec<f32> = splats.means.val().into_data_async().await.to_vec()?;
+    let sh_coeffs: Vec<f32> = splats.sh_coeffs.val().into_data_async().await.to_vec()?;
+    let opacities: Vec<f32> = splats.raw_opacity.val().into_data_async().await.to_vec()?;
+
+    let means: Vec<Vec3> = means.chunks_exact(3).map(Vec3::from_slice).collect();
+    let outliers = statistical_outliers(&means, filter.neighbours, filter.std_ratio);
+
+    let kept: Vec<usize> = (0..means.len()).filter(|&i| !outliers[i]).collect();
+    log::info!(
+        "Removed {} outliers from the initial points",
+        means.len() - kept.len()
+    );
+
+    let means: Vec<Vec3> = kept.iter().map(|&i| means[i]).collect();
+    let sh_coeffs: Vec<f32> = kept
+        .iter()
+        .flat_map(|&i| &sh_coeffs[i * n_coeffs * 3..(i + 1) * n_coeffs * 3])
+        .copied()
+        .collect();
+    let opacities: Vec<f32> = kept.iter().map(|&i| opacities[i]).collect();
+    let (rotations, log_scales) = surface_frames(&means, filter.neighbours);
+
+    Ok(Splats::from_raw(
+        &means,
+        Some(&rotations),
+        Some(&log_scales),
+        Some(&sh_coeffs),
+        Some(&opacities),
+        &device,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::{statistical_outliers, surface_frames};
+
+    #[test]
+    fn removes_stray_points_and_flattens_along_surface() {
+        // A slightly bumpy plane at z = 0, with one point floating above it.
+        let mut points: Vec<Vec3> = (0..20)
+            .flat_map(|x| {
+                (0..20).map(move |y| vec3(x as f32, y as f32, ((x * 7 + y * 3) % 5) as f32 * 0.01))
+            })
+            .collect();
+        points.push(vec3(10.0, 10.0, 8.0));
+
+        let outliers = statistical_outliers(&points, 8, 2.0);
+        assert!(outliers[points.len() - 1]);
+        assert_eq!(outliers.iter().filter(|&&o| o).count(), 1);
+
+        let (rotations, log_scales) = surface_frames(&points[..points.len() - 1], 8);
+        let (rotation, log_scale) = (rotations[210], log_scales[210]);
+        let normal = rotation * Vec3::Z;
+        assert!(normal.z.abs() > 0.99, "Normal {normal} isn't along z");
+        assert!(log_scale.z < log_scale.x);
+    }
+}
@@ -0,0 +1,122 @@
+//! Settings of common academic benchmarks, to reproduce their reference numbers.
+//!
+//! Each preset follows the protocol of 3D Gaussian Splatting (Kerbl et al. 2023): every 8th
+//! view is held out for eval, starting at the first one.
+use std::{fmt, str::FromStr};
+
+use brush_train::train::TrainConfig;
+
+use crate::LoadDatasetArgs;
+
+// Outdoor scenes of MipNeRF-360, trained on a quarter of the resolution. The indoor scenes
+// are trained on half the resolution.
+const MIPNERF360_OUTDOOR: [&str; 5] = ["bicycle", "flowers", "garden", "stump", "treehill"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// MipNeRF-360, with the downscaled `images_2` or `images_4` of each scene.
+    MipNerf360,
+    /// Tanks and Temples, at the resolution of the images.
+    TanksTemples,
+    /// Deep Blending, at the resolution of the images.
+    DeepBlending,
+    /// The NeRF synthetic (Blender) scenes, composited onto a white background.
+    NerfSynthetic,
+}
+
+impl Preset {
+    pub const ALL: [Self; 4] = [
+        Self::MipNerf360,
+        Self::TanksTemples,
+        Self::DeepBlending,
+        Self::NerfSynthetic,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MipNerf360 => "mipnerf360",
+            Self::TanksTemples => "tanks-temples",
+            Self::DeepBlending => "deep-blending",
+            Self::NerfSynthetic => "nerf-synthetic",
+        }
+    }
+
+    /// Apply the preset to the settings of a scene. `scene` is the name of the scene, eg. the
+    /// name of its folder, to pick settings that differ between the scenes of a benchmark.
+    pub fn apply(&self, scene: &str, load_args: &mut LoadDatasetArgs, config: &mut TrainConfig) {
+        load_args.max_resolution = None;
+        config.background_model = false;
+
+        match self {
+            Self::MipNerf360 => {
+                let scene = scene.to_lowercase();
+                let outdoor = MIPNERF360_OUTDOOR.iter().any(|s| scene.contains(s));
+                load_args.image_downscale = Some(if outdoor { 4 } else { 2 });
+                load_args.eval_split_every = Some(8);
+            }
+            Self::TanksTemples | Self::DeepBlending => {
+                load_args.image_downscale = None;
+                load_args.eval_split_every = Some(8);
+            }
+            // The eval views are the separate val split of the scenes.
+            Self::NerfSynthetic => {
+                load_args.image_downscale = None;
+                load_args.eval_split_every = None;
+                config.white_background = true;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase().replace('_', "-");
+        match s.as_str() {
+            "tnt" => return Ok(Self::TanksTemples),
+            "blender" => return Ok(Self::NerfSynthetic),
+            _ => {}
+        }
+        Self::ALL
+            .into_iter()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|p| p.name()).collect();
+                anyhow::anyhow!("Unknown preset {s}, expected one of {}", names.join(", "))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_train::train::TrainConfig;
+
+    use super::Preset;
+    use crate::LoadDatasetArgs;
+
+    #[test]
+    fn mipnerf360_downscales_outdoor_scenes_more() {
+        let preset: Preset = "mipnerf360".parse().expect("Known preset");
+        let mut load_args = LoadDatasetArgs::default();
+        let mut config = TrainConfig::new();
+
+        preset.apply("garden", &mut load_args, &mut config);
+        assert_eq!(load_args.image_downscale, Some(4));
+        preset.apply("counter", &mut load_args, &mut config);
+        assert_eq!(load_args.image_downscale, Some(2));
+        assert_eq!(load_args.eval_split_every, Some(8));
+
+        assert_eq!(
+            "blender".parse::<Preset>().ok(),
+            Some(Preset::NerfSynthetic)
+        );
+        assert!("llff".parse::<Preset>().is_err());
+    }
+}
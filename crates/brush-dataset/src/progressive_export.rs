@@ -0,0 +1,93 @@
+//! Progressive ply export, for web viewers that show the model while it downloads.
+//!
+//! The splats are written from most to least important, the importance being how much of
+//! the image a splat covers: its opacity times the area of its two largest axes. Any prefix
+//! of the file is then a coarse but complete model, which gains detail as more arrives, as
+//! the large splats of surfaces and background come before the small splats of fine detail.
+//!
+//! The header lists the splat counts where the levels of detail end, as a comment like
+//! `progressive levels 4096 16384 65536 100000`, so viewers can refresh the model at these
+//! counts. The file is an ordinary ply otherwise.
+use brush_render::{gaussian_splats::Splats, Backend};
+
+use crate::{
+    coordinates::CoordinateConvention,
+    model_info::ModelInfo,
+    splat_export::{read_splats_in_convention, write_ply},
+    splat_import::GaussianData,
+};
+
+/// Number of splats of the first level of detail, each next level has 4 times as many.
+pub const FIRST_LEVEL_SPLATS: usize = 4096;
+
+pub(crate) fn importance(splat: &GaussianData) -> f32 {
+    let opacity = 1.0 / (1.0 + (-splat.opacity).exp());
+    let mut scales = splat.log_scale.to_array();
+    scales.sort_by(|a, b| b.total_cmp(a));
+    opacity * (scales[0] + scales[1]).exp()
+}
+
+// Indices of the splats, from the most to the least important.
+pub(crate) fn progressive_order(data: &[GaussianData]) -> Vec<usize> {
+    let importance: Vec<_> = data.iter().map(importance).collect();
+    let mut order: Vec<_> = (0..data.len()).collect();
+    order.sort_by(|&a, &b| importance[b].total_cmp(&importance[a]));
+    order
+}
+
+/// Splat counts where the levels of detail of a progressive file of `total` splats end.
+pub fn level_counts(total: usize) -> Vec<usize> {
+    let mut counts = vec![];
+    let mut count = FIRST_LEVEL_SPLATS;
+    while count < total {
+        counts.push(count);
+        count *= 4;
+    }
+    counts.push(total);
+    counts
+}
+
+/// Export splats as a progressive ply, see the [module docs](self).
+pub async fn splat_to_progressive_ply<B: Backend>(
+    splats: Splats<B>,
+    convention: CoordinateConvention,
+    info: &ModelInfo,
+) -> anyhow::Result<Vec<u8>> {
+    let sh_coeffs_num = splats.sh_coeffs.dims()[1];
+    let data = read_splats_in_convention(splats, &[], convention).await?;
+
+    let order = progressive_order(&data);
+    let mut slots: Vec<_> = data.into_iter().map(Some).collect();
+    let data: Vec<_> = order.into_iter().filter_map(|i| slots[i].take()).collect();
+
+    let levels: Vec<_> = level_counts(data.len())
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let comment = format!("progressive levels {}", levels.join(" "));
+    write_ply(data, sh_coeffs_num, convention, info, vec![comment]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use ply_rs::ply::PropertyAccess;
+
+    use super::{level_counts, progressive_order};
+    use crate::splat_import::GaussianData;
+
+    #[test]
+    fn large_opaque_splats_come_first() {
+        let splat = |log_scale: f32, opacity: f32| {
+            let mut splat = GaussianData::new();
+            splat.log_scale = Vec3::splat(log_scale);
+            splat.opacity = opacity;
+            splat
+        };
+        let data = [splat(-4.0, 2.0), splat(0.0, 2.0), splat(0.0, -6.0)];
+        assert_eq!(progressive_order(&data), vec![1, 2, 0]);
+
+        assert_eq!(level_counts(100), vec![100]);
+        assert_eq!(level_counts(20000), vec![4096, 16384, 20000]);
+    }
+}
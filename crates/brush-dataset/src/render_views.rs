@@ -0,0 +1,113 @@
+//! Render the views of a dataset from a trained model, eg. for comparison grids, or to train
+//! another model on the renders.
+use std::{io::Cursor, path::PathBuf};
+
+use async_fn_stream::try_fn_stream;
+use brush_render::{gaussian_splats::Splats, Backend};
+use brush_tasks::ProgressSender;
+use brush_train::{
+    image::{image_to_tensor, tensor_into_image},
+    scene::SceneView,
+};
+use burn::tensor::{Tensor, TensorData};
+use image::DynamicImage;
+use tokio_stream::Stream;
+
+use crate::Dataset;
+
+/// Which images to write for every view, besides the render.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderViewsOptions {
+    /// Also write the ground truth image, as `<name>_gt.png`.
+    pub ground_truth: bool,
+    /// Also write the absolute difference between the render and the ground truth, as
+    /// `<name>_diff.png`.
+    pub diff: bool,
+}
+
+fn encode_png(image: DynamicImage) -> anyhow::Result<Vec<u8>> {
+    let mut png = vec![];
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+async fn tensor_png<B: Backend>(tensor: Tensor<B, 3>) -> anyhow::Result<Vec<u8>> {
+    let data: TensorData = tensor.into_data_async().await;
+    brush_tasks::run_blocking(move || encode_png(tensor_into_image(data))).await
+}
+
+/// Render every training and eval view of a dataset, as png files named
+/// `train/00012_<name>.png` and `eval/00003_<name>.png`.
+///
+/// Files are streamed as they're rendered, so big datasets don't have to fit in memory.
+pub fn render_views<B: Backend>(
+    splats: Splats<B>,
+    dataset: &Dataset,
+    options: RenderViewsOptions,
+    progress: ProgressSender,
+) -> impl Stream<Item = anyhow::Result<(PathBuf, Vec<u8>)>> + 'static {
+    let views: Vec<(&str, usize, SceneView)> = dataset
+        .train
+        .views
+        .iter()
+        .enumerate()
+        .map(|(i, v)| ("train", i, v.clone()))
+        .chain(
+            dataset
+                .eval
+                .iter()
+                .flat_map(|s| s.views.iter().enumerate())
+                .map(|(i, v)| ("eval", i, v.clone())),
+        )
+        .collect();
+
+    try_fn_stream(|emitter| async move {
+        let total = views.len();
+        let device = splats.means.device();
+
+        for (done, (folder, index, view)) in views.into_iter().enumerate() {
+            let (w, h) = (view.image.width(), view.image.height());
+            let (rendered, _) = splats.render(&view.camera, glam::uvec2(w, h), false);
+            let rendered = rendered
+                .slice([0..h as usize, 0..w as usize, 0..3])
+                .clamp(0.0, 1.0);
+
+            // Prefix the index, names aren't unique when images are in subfolders.
+            let stem = std::path::Path::new(&view.name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let base = format!("{folder}/{index:05}_{stem}");
+
+            if options.diff {
+                let image = view.image.load();
+                let ground_truth: DynamicImage =
+                    brush_tasks::run_blocking(move || image.to_rgb32f().into()).await;
+                let ground_truth = image_to_tensor::<B>(&ground_truth, &device);
+                let diff = (rendered.clone() - ground_truth).abs();
+                let png = tensor_png(diff).await?;
+                emitter
+                    .emit((PathBuf::from(format!("{base}_diff.png")), png))
+                    .await;
+            }
+
+            if options.ground_truth {
+                let image = (*view.image.load()).clone();
+                let png = brush_tasks::run_blocking(move || encode_png(image)).await?;
+                emitter
+                    .emit((PathBuf::from(format!("{base}_gt.png")), png))
+                    .await;
+            }
+
+            let png = tensor_png(rendered).await?;
+            emitter
+                .emit((PathBuf::from(format!("{base}.png")), png))
+                .await;
+
+            progress.report("Rendering views", done + 1, total);
+        }
+
+        Ok(())
+    })
+}
@@ -1,76 +1,171 @@
 use brush_render::Backend;
-use brush_train::image::image_to_tensor;
-use brush_train::scene::Scene;
+use brush_train::pyramid::ImagePyramid;
+use brush_train::residency::LruBudget;
+use brush_train::sampler::{ViewSampler, ViewSampling};
+use brush_train::scene::{Scene, SceneView};
 use brush_train::train::SceneBatch;
-use burn::tensor::Tensor;
-use rand::{seq::SliceRandom, SeedableRng};
+use burn::tensor::{ElementConversion, Tensor};
+use rand::SeedableRng;
+use std::collections::HashMap;
 use tokio::sync::mpsc;
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{Receiver, UnboundedSender};
 use tokio_with_wasm::alias as tokio_wasm;
 
+// Views picked for a batch, the pyramids are stacked at a level when the batch is taken.
+struct PendingBatch<B: Backend> {
+    images: Vec<ImagePyramid<B>>,
+    masks: Vec<Option<ImagePyramid<B>>>,
+    views: Vec<SceneView>,
+}
+
+type CachedView<B> = (ImagePyramid<B>, Option<ImagePyramid<B>>);
+
+// Upload the pyramids of a view, or take them from the cache.
+async fn cached_view<B: Backend>(
+    cache: &mut LruBudget<usize, CachedView<B>>,
+    index: usize,
+    view: &SceneView,
+    device: &B::Device,
+) -> CachedView<B> {
+    if let Some(cached) = cache.get(&index) {
+        return cached;
+    }
+    // The image might have to be decoded again, see `ViewImage::load`.
+    let image = view.image.clone();
+    let image = brush_tasks::run_blocking(move || image.load()).await;
+    let image = ImagePyramid::from_image(&image, device);
+    let mask = view
+        .mask
+        .as_ref()
+        .map(|mask| ImagePyramid::from_mask(mask, device));
+    let bytes = image.size_bytes() + mask.as_ref().map_or(0, |m| m.size_bytes());
+    cache.insert(index, (image.clone(), mask.clone()), bytes);
+    (image, mask)
+}
+
 pub struct SceneLoader<B: Backend> {
-    receiver: Receiver<SceneBatch<B>>,
+    receiver: Receiver<PendingBatch<B>>,
+    loss_sender: Option<UnboundedSender<(Vec<String>, Tensor<B, 1>)>>,
+    scene_extent: f32,
 }
 
 impl<B: Backend> SceneLoader<B> {
-    pub fn new(scene: &Scene, batch_size: usize, seed: u64, device: &B::Device) -> Self {
+    /// Load batches of `scene`, with an extent as given by [`Scene::extent`].
+    ///
+    /// The images are kept on the GPU up to `gpu_image_budget_mb` megabytes, the views that
+    /// were sampled longest ago are uploaded again when they don't fit.
+    pub fn new(
+        scene: &Scene,
+        scene_extent: f32,
+        batch_size: usize,
+        seed: u64,
+        sampling: ViewSampling,
+        gpu_image_budget_mb: u32,
+        device: &B::Device,
+    ) -> Self {
         let scene = scene.clone();
         // The bounded size == number of batches to prefetch.
         let (tx, rx) = mpsc::channel(5);
         let device = device.clone();
 
-        let center = scene.bounds().center;
-        let dists = scene
+        let mut sampler = ViewSampler::new(scene.views.len(), sampling);
+        let (loss_sender, mut loss_receiver) = mpsc::unbounded_channel();
+        let loss_sender = sampler.needs_loss().then_some(loss_sender);
+        let view_indices: HashMap<_, _> = scene
             .views
             .iter()
-            .map(|v| (v.camera.position - center).length())
-            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Less))
-            .unwrap_or(1.0);
-
-        let scene_extent = dists * 1.1; // Idk why exactly, but gsplat multiplies this by 1.1
+            .enumerate()
+            .map(|(i, v)| (v.name.clone(), i))
+            .collect();
 
         let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut cache = LruBudget::new(gpu_image_budget_mb as u64 * 1024 * 1024);
 
         let fut = async move {
-            let mut shuf_indices = vec![];
-
             loop {
-                let (selected_tensors, gt_views) = (0..batch_size)
-                    .map(|_| {
-                        let index = shuf_indices.pop().unwrap_or_else(|| {
-                            shuf_indices = (0..scene.views.len()).collect();
-                            shuf_indices.shuffle(&mut rng);
-                            shuf_indices
-                                .pop()
-                                .expect("Need at least one view in dataset")
-                        });
-                        let view = scene.views[index].clone();
-                        (image_to_tensor(&view.image, &device), view)
-                    })
-                    .unzip();
-
-                let batch_tensor = Tensor::stack(selected_tensors, 0);
-
-                let scene_batch = SceneBatch {
-                    gt_images: batch_tensor,
-                    gt_views,
-                    scene_extent,
+                // Losses come in from the training steps of earlier batches. Reading them
+                // back waits on the GPU, but that's fine as this runs ahead of training.
+                while let Ok((names, loss)) = loss_receiver.try_recv() {
+                    let loss = loss.into_scalar_async().await.elem::<f32>();
+                    for name in names {
+                        if let Some(&index) = view_indices.get(&name) {
+                            sampler.report_loss(index, loss);
+                        }
+                    }
+                }
+
+                let mut batch = PendingBatch {
+                    images: vec![],
+                    masks: vec![],
+                    views: vec![],
                 };
+                for _ in 0..batch_size {
+                    let index = sampler.next(&mut rng);
+                    let view = scene.views[index].clone();
+                    let (image, mask) = cached_view(&mut cache, index, &view, &device).await;
+                    batch.images.push(image);
+                    batch.masks.push(mask);
+                    batch.views.push(view);
+                }
 
-                if tx.send(scene_batch).await.is_err() {
+                if tx.send(batch).await.is_err() {
                     break;
                 }
             }
         };
 
         tokio_wasm::spawn(fut);
-        Self { receiver: rx }
+        Self {
+            receiver: rx,
+            loss_sender,
+            scene_extent,
+        }
+    }
+
+    /// Report the training loss of a batch, used to prioritize views. Does nothing unless
+    /// the loader samples by priority.
+    pub fn report_loss(&self, views: &[SceneView], loss: Tensor<B, 1>) {
+        if let Some(sender) = &self.loss_sender {
+            let names = views.iter().map(|v| v.name.clone()).collect();
+            let _ = sender.send((names, loss.detach()));
+        }
     }
 
-    pub async fn next_batch(&mut self) -> SceneBatch<B> {
-        self.receiver
+    /// The next batch, with the images at `level` of their pyramids, see
+    /// [`brush_train::train::TrainConfig::resolution_level`].
+    pub async fn next_batch(&mut self, level: usize) -> SceneBatch<B> {
+        let batch = self
+            .receiver
             .recv()
             .await
-            .expect("Somehow lost data loading channel!")
+            .expect("Somehow lost data loading channel!");
+
+        let images: Vec<_> = batch.images.iter().map(|p| p.level(level)).collect();
+
+        // Views without a mask are fully weighted.
+        let loss_weights = if batch.masks.iter().any(|m| m.is_some()) {
+            let weights = batch
+                .masks
+                .iter()
+                .zip(&images)
+                .map(|(mask, image)| match mask {
+                    Some(mask) => mask.level(level),
+                    None => {
+                        let [h, w, _] = image.dims();
+                        Tensor::ones([h, w, 1], &image.device())
+                    }
+                })
+                .collect();
+            Some(Tensor::stack(weights, 0))
+        } else {
+            None
+        };
+
+        SceneBatch {
+            gt_images: Tensor::stack(images, 0),
+            loss_weights,
+            gt_views: batch.views,
+            scene_extent: self.scene_extent,
+        }
     }
 }
@@ -1,5 +1,8 @@
 use anyhow::anyhow;
-use brush_render::{gaussian_splats::Splats, Backend};
+use brush_render::{
+    gaussian_splats::{estimate_normal, Splats},
+    Backend,
+};
 use burn::tensor::DataError;
 use glam::{Quat, Vec3};
 use ply_rs::{
@@ -7,9 +10,66 @@ use ply_rs::{
     writer::Writer,
 };
 
-use crate::splat_import::GaussianData;
+use crate::{
+    coordinates::{CoordinateConvention, CoordinateTransform},
+    model_info::ModelInfo,
+    splat_import::GaussianData,
+};
+
+// A kd-tree of the camera positions, to find the nearest camera of every splat without
+// comparing it to all cameras. The median of each range of points splits the rest of it.
+struct CameraTree(Vec<Vec3>);
+
+impl CameraTree {
+    fn new(positions: &[Vec3]) -> Self {
+        fn build(points: &mut [Vec3], axis: usize) {
+            if points.len() <= 1 {
+                return;
+            }
+            let mid = points.len() / 2;
+            points.select_nth_unstable_by(mid, |a, b| a[axis].total_cmp(&b[axis]));
+            let (left, right) = points.split_at_mut(mid);
+            build(left, (axis + 1) % 3);
+            build(&mut right[1..], (axis + 1) % 3);
+        }
+        let mut points = positions.to_vec();
+        build(&mut points, 0);
+        Self(points)
+    }
+
+    fn nearest(&self, point: Vec3) -> Option<Vec3> {
+        fn search(points: &[Vec3], axis: usize, point: Vec3, best: &mut (f32, Vec3)) {
+            if points.is_empty() {
+                return;
+            }
+            let mid = points.len() / 2;
+            let node = points[mid];
+            let dist = node.distance_squared(point);
+            if dist < best.0 {
+                *best = (dist, node);
+            }
+            let offset = point[axis] - node[axis];
+            let (near, far) = if offset < 0.0 {
+                (&points[..mid], &points[mid + 1..])
+            } else {
+                (&points[mid + 1..], &points[..mid])
+            };
+            search(near, (axis + 1) % 3, point, best);
+            // The far side can only be nearer when the splitting plane is.
+            if offset * offset < best.0 {
+                search(far, (axis + 1) % 3, point, best);
+            }
+        }
+        let mut best = (f32::INFINITY, Vec3::ZERO);
+        search(&self.0, 0, point, &mut best);
+        (!self.0.is_empty()).then_some(best.1)
+    }
+}
 
-async fn read_splat_data<B: Backend>(splats: Splats<B>) -> Result<Vec<GaussianData>, DataError> {
+async fn read_splat_data<B: Backend>(
+    splats: Splats<B>,
+    view_positions: &[Vec3],
+) -> Result<Vec<GaussianData>, DataError> {
     let means = splats.means.val().into_data_async().await.to_vec()?;
     let log_scales = splats.log_scales.val().into_data_async().await.to_vec()?;
     let rotations = splats.rotation.val().into_data_async().await.to_vec()?;
@@ -25,6 +85,18 @@ async fn read_splat_data<B: Backend>(splats: Splats<B>) -> Result<Vec<GaussianDa
 
     let sh_coeffs_num = splats.sh_coeffs.dims()[1];
 
+    let centroid = if view_positions.is_empty() {
+        let n = splats.num_splats().max(1) as f32;
+        means
+            .chunks_exact(3)
+            .fold(Vec3::ZERO, |acc, m| acc + Vec3::new(m[0], m[1], m[2]))
+            / n
+    } else {
+        Vec3::ZERO
+    };
+
+    let cameras = CameraTree::new(view_positions);
+
     let splats = (0..splats.num_splats())
         .map(|i| {
             // Read SH data from [coeffs, channel] format to
@@ -42,20 +114,31 @@ async fn read_splat_data<B: Backend>(splats: Splats<B>) -> Result<Vec<GaussianDa
             let sh_dc = [sh_red[0], sh_green[0], sh_blue[0]];
             let sh_coeffs_rest = [&sh_red[1..], &sh_green[1..], &sh_blue[1..]].concat();
 
+            let mean = Vec3::new(means[i * 3], means[i * 3 + 1], means[i * 3 + 2]);
+            let log_scale = Vec3::new(
+                log_scales[i * 3],
+                log_scales[i * 3 + 1],
+                log_scales[i * 3 + 2],
+            );
+            let rotation = Quat::from_xyzw(
+                rotations[i * 4 + 1],
+                rotations[i * 4 + 2],
+                rotations[i * 4 + 3],
+                rotations[i * 4],
+            );
+
+            // Orient normals towards the nearest camera if there are any, otherwise
+            // point them away from the center of the model.
+            let toward = cameras
+                .nearest(mean)
+                .map_or(mean - centroid, |cam| cam - mean);
+
             GaussianData {
-                means: Vec3::new(means[i * 3], means[i * 3 + 1], means[i * 3 + 2]),
-                log_scale: Vec3::new(
-                    log_scales[i * 3],
-                    log_scales[i * 3 + 1],
-                    log_scales[i * 3 + 2],
-                ),
+                means: mean,
+                log_scale,
                 opacity: opacities[i],
-                rotation: Quat::from_xyzw(
-                    rotations[i * 4 + 1],
-                    rotations[i * 4 + 2],
-                    rotations[i * 4 + 3],
-                    rotations[i * 4],
-                ),
+                rotation,
+                normal: estimate_normal(rotation, log_scale, toward),
                 sh_dc,
                 sh_coeffs_rest,
             }
@@ -66,16 +149,96 @@ async fn read_splat_data<B: Backend>(splats: Splats<B>) -> Result<Vec<GaussianDa
 }
 
 pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u8>> {
+    splat_to_ply_with_views(splats, &[]).await
+}
+
+/// Export splats to a ply, using the given camera positions to orient the exported normals.
+pub async fn splat_to_ply_with_views<B: Backend>(
+    splats: Splats<B>,
+    view_positions: &[Vec3],
+) -> anyhow::Result<Vec<u8>> {
+    splat_to_ply_in_convention(splats, view_positions, CoordinateConvention::BRUSH).await
+}
+
+// Read back the splats with normalized rotations, converted to the given convention.
+pub(crate) async fn read_splats_in_convention<B: Backend>(
+    splats: Splats<B>,
+    view_positions: &[Vec3],
+    convention: CoordinateConvention,
+) -> anyhow::Result<Vec<GaussianData>> {
+    let transform = (convention != CoordinateConvention::BRUSH)
+        .then(|| CoordinateTransform::from_brush(convention));
+    read_splats_transformed(splats, view_positions, transform).await
+}
+
+async fn read_splats_transformed<B: Backend>(
+    splats: Splats<B>,
+    view_positions: &[Vec3],
+    transform: Option<CoordinateTransform>,
+) -> anyhow::Result<Vec<GaussianData>> {
     let mut splats = splats;
     splats.norm_rotations();
 
-    let data = read_splat_data(splats.clone())
+    let mut data = read_splat_data(splats, view_positions)
         .await
         .map_err(|e| anyhow!("Failed to read data from splat {e:?}"))?;
 
+    if let Some(transform) = transform {
+        for splat in &mut data {
+            transform.splat(splat);
+        }
+    }
+    Ok(data)
+}
+
+/// Export splats to a ply in the coordinate convention of another tool, eg. Z up for Blender.
+pub async fn splat_to_ply_in_convention<B: Backend>(
+    splats: Splats<B>,
+    view_positions: &[Vec3],
+    convention: CoordinateConvention,
+) -> anyhow::Result<Vec<u8>> {
+    splat_to_ply_with_info(splats, view_positions, convention, &ModelInfo::default()).await
+}
+
+/// Export splats to a ply, with where the model came from in the header, see
+/// [`crate::model_info`].
+pub async fn splat_to_ply_with_info<B: Backend>(
+    splats: Splats<B>,
+    view_positions: &[Vec3],
+    convention: CoordinateConvention,
+    info: &ModelInfo,
+) -> anyhow::Result<Vec<u8>> {
+    let sh_coeffs_num = splats.sh_coeffs.dims()[1];
+    let data = read_splats_in_convention(splats, view_positions, convention).await?;
+    write_ply(data, sh_coeffs_num, convention, info, vec![]).await
+}
+
+/// Export splats to a ply like [`splat_to_ply_with_info`], leveled so that `up`, eg. the
+/// normal of the ground, becomes the vertical axis.
+pub async fn splat_to_ply_leveled<B: Backend>(
+    splats: Splats<B>,
+    view_positions: &[Vec3],
+    convention: CoordinateConvention,
+    up: Vec3,
+    info: &ModelInfo,
+) -> anyhow::Result<Vec<u8>> {
+    let sh_coeffs_num = splats.sh_coeffs.dims()[1];
+    let transform = CoordinateTransform::from_brush_leveled(convention, up);
+    let data = read_splats_transformed(splats, view_positions, Some(transform)).await?;
+    write_ply(data, sh_coeffs_num, convention, info, vec![]).await
+}
+
+// Write a ply of the splats, with the extra comments in the header.
+pub(crate) async fn write_ply(
+    data: Vec<GaussianData>,
+    sh_coeffs_num: usize,
+    convention: CoordinateConvention,
+    info: &ModelInfo,
+    comments: Vec<String>,
+) -> anyhow::Result<Vec<u8>> {
     let property_names = vec![
-        "x", "y", "z", "scale_0", "scale_1", "scale_2", "opacity", "rot_0", "rot_1", "rot_2",
-        "rot_3", "f_dc_0", "f_dc_1", "f_dc_2",
+        "x", "y", "z", "nx", "ny", "nz", "scale_0", "scale_1", "scale_2", "opacity", "rot_0",
+        "rot_1", "rot_2", "rot_3", "f_dc_0", "f_dc_1", "f_dc_2",
     ];
 
     let mut properties: Vec<PropertyDef> = property_names
@@ -83,7 +246,7 @@ pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u
         .map(|name| PropertyDef::new(name, PropertyType::Scalar(ScalarType::Float)))
         .collect();
 
-    let sh_coeffs_rest = (splats.sh_coeffs.dims()[1] - 1) * 3;
+    let sh_coeffs_rest = (sh_coeffs_num - 1) * 3;
 
     for i in 0..sh_coeffs_rest {
         properties.push(PropertyDef::new(
@@ -100,11 +263,56 @@ pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u
     ply.header.elements.push(vertex);
     ply.header.encoding = ply::Encoding::BinaryLittleEndian;
     ply.header.comments.push("Exported from Brush".to_owned());
-    ply.header.comments.push("Vertical axis: y".to_owned());
+    ply.header
+        .comments
+        .push(format!("Vertical axis: {}", convention.up_axis_name()));
+    ply.header.comments.push(info.to_ply_comment());
+    ply.header.comments.extend(comments);
     ply.payload.insert("vertex".to_owned(), data);
 
-    let mut buf = vec![];
-    let writer = Writer::<GaussianData>::new();
-    writer.write_ply(&mut buf, &mut ply)?;
-    Ok(buf)
+    // Writing a big ply takes a while, keep it off the UI thread.
+    brush_tasks::run_blocking(move || {
+        let mut buf = vec![];
+        let writer = Writer::<GaussianData>::new();
+        writer.write_ply(&mut buf, &mut ply)?;
+        Ok(buf)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::CameraTree;
+
+    #[test]
+    fn nearest_camera() {
+        assert_eq!(CameraTree::new(&[]).nearest(Vec3::ZERO), None);
+
+        // A ring of cameras at the same height, as on a turntable, and a few duplicates.
+        let mut cameras: Vec<_> = (0..100)
+            .map(|i| {
+                let angle = i as f32 * 0.0628;
+                Vec3::new(angle.cos() * 3.0, 1.0, angle.sin() * 3.0)
+            })
+            .collect();
+        cameras.extend([Vec3::ONE; 5]);
+        let tree = CameraTree::new(&cameras);
+
+        for i in 0..200 {
+            let t = i as f32;
+            let point = Vec3::new(
+                (t * 0.37).sin() * 4.0,
+                (t * 0.11).cos(),
+                (t * 0.73).sin() * 4.0,
+            );
+            let brute = cameras
+                .iter()
+                .map(|c| c.distance_squared(point))
+                .fold(f32::INFINITY, f32::min);
+            let nearest = tree.nearest(point).expect("Cameras in the tree");
+            assert_eq!(nearest.distance_squared(point), brute);
+        }
+    }
 }
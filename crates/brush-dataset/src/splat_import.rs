@@ -6,21 +6,28 @@ use burn::tensor::{Tensor, TensorData};
 use glam::{Quat, Vec3, Vec4};
 use ply_rs::{
     parser::Parser,
-    ply::{ElementDef, Header, Property, PropertyAccess},
+    ply::{ElementDef, Header, Property, PropertyAccess, PropertyType, ScalarType},
 };
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+use rayon::prelude::*;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
 use tokio_stream::Stream;
 use tokio_with_wasm::alias as tokio_wasm;
 use tracing::trace_span;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use brush_render::gaussian_splats::Splats;
 
+use crate::{
+    coordinates::{CoordinateConvention, CoordinateTransform},
+    model_info::ModelInfo,
+};
+
 pub(crate) struct GaussianData {
     pub(crate) means: Vec3,
     pub(crate) log_scale: Vec3,
     pub(crate) opacity: f32,
     pub(crate) rotation: Quat,
+    pub(crate) normal: Vec3,
     pub(crate) sh_dc: [f32; 3],
     // NB: This is in the inria format, aka [channels, coeffs]
     // not [coeffs, channels].
@@ -34,6 +41,7 @@ impl PropertyAccess for GaussianData {
             log_scale: Vec3::ZERO,
             opacity: 0.0,
             rotation: Quat::IDENTITY,
+            normal: Vec3::ZERO,
             sh_dc: [0.0, 0.0, 0.0],
             sh_coeffs_rest: Vec::new(),
         }
@@ -69,6 +77,9 @@ impl PropertyAccess for GaussianData {
             b"rot_1" => self.rotation.x = value,
             b"rot_2" => self.rotation.y = value,
             b"rot_3" => self.rotation.z = value,
+            b"nx" => self.normal[0] = value,
+            b"ny" => self.normal[1] = value,
+            b"nz" => self.normal[2] = value,
             b"f_dc_0" => self.sh_dc[0] = value,
             b"f_dc_1" => self.sh_dc[1] = value,
             b"f_dc_2" => self.sh_dc[2] = value,
@@ -102,6 +113,9 @@ impl PropertyAccess for GaussianData {
             b"rot_1" => Some(self.rotation.x),
             b"rot_2" => Some(self.rotation.y),
             b"rot_3" => Some(self.rotation.z),
+            b"nx" => Some(self.normal[0]),
+            b"ny" => Some(self.normal[1]),
+            b"nz" => Some(self.normal[2]),
             b"f_dc_0" => Some(self.sh_dc[0]),
             b"f_dc_1" => Some(self.sh_dc[1]),
             b"f_dc_2" => Some(self.sh_dc[2]),
@@ -117,6 +131,102 @@ impl PropertyAccess for GaussianData {
     }
 }
 
+// Number of rows of a binary ply decoded in parallel at once.
+const PARSE_CHUNK: usize = 65536;
+
+// Where each property is in a row of a binary ply. Only used when all properties are scalars,
+// so every row has the same size and rows can be decoded independently.
+struct RowLayout<'a> {
+    properties: Vec<(&'a str, &'a ScalarType, usize)>,
+    size: usize,
+    big_endian: bool,
+}
+
+fn scalar_size(ty: &ScalarType) -> usize {
+    match ty {
+        ScalarType::Char | ScalarType::UChar => 1,
+        ScalarType::Short | ScalarType::UShort => 2,
+        ScalarType::Int | ScalarType::UInt | ScalarType::Float => 4,
+        ScalarType::Double => 8,
+    }
+}
+
+fn read_scalar(bytes: &[u8], ty: &ScalarType, big_endian: bool) -> Property {
+    macro_rules! read {
+        ($t:ty) => {{
+            let mut b = [0; std::mem::size_of::<$t>()];
+            b.copy_from_slice(&bytes[..std::mem::size_of::<$t>()]);
+            if big_endian {
+                <$t>::from_be_bytes(b)
+            } else {
+                <$t>::from_le_bytes(b)
+            }
+        }};
+    }
+
+    match ty {
+        ScalarType::Char => Property::Char(read!(i8)),
+        ScalarType::UChar => Property::UChar(read!(u8)),
+        ScalarType::Short => Property::Short(read!(i16)),
+        ScalarType::UShort => Property::UShort(read!(u16)),
+        ScalarType::Int => Property::Int(read!(i32)),
+        ScalarType::UInt => Property::UInt(read!(u32)),
+        ScalarType::Float => Property::Float(read!(f32)),
+        ScalarType::Double => Property::Double(read!(f64)),
+    }
+}
+
+impl<'a> RowLayout<'a> {
+    fn new(header: &Header, element: &'a ElementDef) -> Option<Self> {
+        let big_endian = match header.encoding {
+            ply_rs::ply::Encoding::BinaryLittleEndian => false,
+            ply_rs::ply::Encoding::BinaryBigEndian => true,
+            ply_rs::ply::Encoding::Ascii => return None,
+        };
+
+        let mut properties = vec![];
+        let mut size = 0;
+        for property in &element.properties {
+            let PropertyType::Scalar(ty) = &property.data_type else {
+                return None;
+            };
+            properties.push((property.name.as_str(), ty, size));
+            size += scalar_size(ty);
+        }
+
+        Some(Self {
+            properties,
+            size,
+            big_endian,
+        })
+    }
+
+    // Read and decode the next rows, in parallel.
+    async fn read_rows<T: AsyncRead + Unpin>(
+        &self,
+        reader: &mut T,
+        count: usize,
+        transform: Option<&CoordinateTransform>,
+    ) -> tokio::io::Result<Vec<GaussianData>> {
+        let mut data = vec![0; count * self.size];
+        reader.read_exact(&mut data).await?;
+
+        Ok(data
+            .par_chunks_exact(self.size.max(1))
+            .map(|row| {
+                let mut splat = GaussianData::new();
+                for &(name, ty, offset) in &self.properties {
+                    splat.set_property(name, read_scalar(&row[offset..], ty, self.big_endian));
+                }
+                if let Some(transform) = transform {
+                    transform.splat(&mut splat);
+                }
+                splat
+            })
+            .collect())
+    }
+}
+
 fn interleave_coeffs(sh_dc: [f32; 3], sh_rest: &[f32]) -> Vec<f32> {
     let channels = 3;
     let coeffs_per_channel = sh_rest.len() / channels;
@@ -174,10 +284,40 @@ struct QuantMeta {
     scale: Vec3,
 }
 
+/// Read the number of splats and the [`ModelInfo`] of a ply, without reading the splats.
+pub async fn read_ply_info<T: AsyncRead + Unpin>(reader: T) -> Result<(usize, Option<ModelInfo>)> {
+    let mut reader = BufReader::new(reader);
+    let header = Parser::<GaussianData>::new()
+        .read_header(&mut reader)
+        .await?;
+    let num_splats = header
+        .elements
+        .iter()
+        .find(|e| e.name == "vertex")
+        .map_or(0, |e| e.count);
+    Ok((num_splats, ModelInfo::from_ply_comments(&header.comments)))
+}
+
 pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
     reader: T,
     subsample_points: Option<u32>,
     device: B::Device,
+) -> impl Stream<Item = Result<SplatMessage<B>>> + 'static {
+    load_splat_from_ply_with_convention(
+        reader,
+        subsample_points,
+        CoordinateConvention::BRUSH,
+        device,
+    )
+}
+
+/// Load splats from a ply written in the coordinate convention of another tool, converting
+/// them to Brush coordinates.
+pub fn load_splat_from_ply_with_convention<T: AsyncRead + Unpin + 'static, B: Backend>(
+    reader: T,
+    subsample_points: Option<u32>,
+    convention: CoordinateConvention,
+    device: B::Device,
 ) -> impl Stream<Item = Result<SplatMessage<B>>> + 'static {
     // set up a reader, in this case a file.
     let mut reader = BufReader::new(reader);
@@ -189,7 +329,11 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
 
         let header = gaussian_parser.read_header(&mut reader).await?;
 
-        let up_axis = header
+        let transform = (convention != CoordinateConvention::BRUSH)
+            .then(|| CoordinateTransform::to_brush(convention));
+
+        // Once converted, the splats are Y up like everything else in Brush.
+        let header_up_axis = header
             .comments
             .iter()
             .filter_map(|c| match c.to_lowercase().strip_prefix("vertical axis: ") {
@@ -200,6 +344,11 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
             })
             .last()
             .unwrap_or(Vec3::Y);
+        let up_axis = if transform.is_some() {
+            Vec3::Y
+        } else {
+            header_up_axis
+        };
 
         let frame_count = header
             .elements
@@ -254,8 +403,36 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
                 }
 
                 let update_every = element.count.div_ceil(25);
+                let layout = RowLayout::new(&header, element);
+                let mut rows = Vec::new().into_iter();
 
                 for i in 0..element.count {
+                    // Decode the next rows. Binary rows are decoded in parallel chunks, other
+                    // rows one at a time.
+                    let splat = match rows.next() {
+                        Some(splat) => splat,
+                        None => {
+                            let mut decoded = if let Some(layout) = &layout {
+                                let count = PARSE_CHUNK.min(element.count - i);
+                                layout
+                                    .read_rows(&mut reader, count, transform.as_ref())
+                                    .await?
+                            } else {
+                                let mut splat =
+                                    decode_splat(&mut reader, &gaussian_parser, &header, element)
+                                        .await?;
+                                if let Some(transform) = &transform {
+                                    transform.splat(&mut splat);
+                                }
+                                vec![splat]
+                            }
+                            .into_iter();
+                            let splat = decoded.next().context("Missing splat in ply")?;
+                            rows = decoded;
+                            splat
+                        }
+                    };
+
                     // Ocassionally yield.
                     if i % 500 == 0 {
                         tokio_wasm::task::yield_now().await;
@@ -293,9 +470,6 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
                         }
                     }
 
-                    let splat =
-                        decode_splat(&mut reader, &gaussian_parser, &header, element).await?;
-
                     means.push(splat.means);
                     if let Some(scales) = log_scales.as_mut() {
                         scales.push(splat.log_scale);
@@ -359,12 +533,22 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
                         decode_splat(&mut reader, &gaussian_parser, &header, element).await?;
 
                     // Let's only animate transforms for now.
-                    means.push(splat_enc.means * (meta_max.mean - meta_min.mean) + meta_min.mean);
+                    let mut mean =
+                        splat_enc.means * (meta_max.mean - meta_min.mean) + meta_min.mean;
+                    if let Some(transform) = &transform {
+                        mean = transform.point(mean);
+                    }
+                    means.push(mean);
 
                     if let Some(rotation) = rotations.as_mut() {
                         let val: Vec4 = splat_enc.rotation.into();
                         let val = val * (meta_max.rotation - meta_min.rotation) + meta_min.rotation;
-                        rotation.push(Quat::from_vec4(val));
+                        // The rotation transform is linear, so also applies to the deltas.
+                        let mut val = Quat::from_vec4(val);
+                        if let Some(transform) = &transform {
+                            val = transform.rotation(val);
+                        }
+                        rotation.push(val);
                     }
 
                     if let Some(log_scales) = log_scales.as_mut() {
@@ -435,3 +619,105 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
         Ok(())
     })
 }
+
+// Size of a splat in a .splat file: position and scale as 3 f32s, RGBA color as 4 bytes,
+// and the rotation quaternion as 4 bytes.
+const SPLAT_FILE_ROW: usize = 32;
+
+// Decode the splats of a .splat file, see [`load_splat_file`].
+fn decode_splat_file(
+    data: &[u8],
+    transform: Option<&CoordinateTransform>,
+) -> Result<Vec<GaussianData>> {
+    anyhow::ensure!(
+        data.len() % SPLAT_FILE_ROW == 0,
+        "Invalid .splat file, its size isn't a multiple of {SPLAT_FILE_ROW} bytes"
+    );
+    let f32_at = |row: &[u8], i: usize| {
+        f32::from_le_bytes([row[4 * i], row[4 * i + 1], row[4 * i + 2], row[4 * i + 3]])
+    };
+    let byte = |b: u8| b as f32 / 255.0;
+
+    Ok(data
+        .par_chunks_exact(SPLAT_FILE_ROW)
+        .map(|row| {
+            let vec =
+                |at: usize| Vec3::new(f32_at(row, at), f32_at(row, at + 1), f32_at(row, at + 2));
+            let [r, g, b, a] = [row[24], row[25], row[26], row[27]].map(byte);
+            // Quantized as (q * 128 + 128), in w, x, y, z order.
+            let [w, x, y, z] =
+                [row[28], row[29], row[30], row[31]].map(|q| (q as f32 - 128.0) / 128.0);
+            // A fully opaque or transparent byte would give an infinite logit.
+            let a = a.clamp(0.5 / 255.0, 254.5 / 255.0);
+
+            let mut splat = GaussianData::new();
+            splat.means = vec(0);
+            splat.log_scale =
+                Vec3::from_array(vec(3).max(Vec3::splat(1e-12)).to_array().map(f32::ln));
+            splat.opacity = (a / (1.0 - a)).ln();
+            splat.rotation = Quat::from_xyzw(x, y, z, w).normalize();
+            splat.sh_dc = [rgb_to_sh(r), rgb_to_sh(g), rgb_to_sh(b)];
+            if let Some(transform) = transform {
+                transform.splat(&mut splat);
+            }
+            splat
+        })
+        .collect())
+}
+
+/// Load a `.splat` file, the compact format of the antimatter15 WebGL viewer. It stores
+/// only the base color of each splat, no view dependent colors. Like plys, its coordinates
+/// are in the given convention.
+pub fn load_splat_file<B: Backend>(
+    data: &[u8],
+    convention: CoordinateConvention,
+    device: &B::Device,
+) -> Result<Splats<B>> {
+    let transform = (convention != CoordinateConvention::BRUSH)
+        .then(|| CoordinateTransform::to_brush(convention));
+    let splats = decode_splat_file(data, transform.as_ref())?;
+    anyhow::ensure!(!splats.is_empty(), "The .splat file has no splats");
+
+    let means: Vec<_> = splats.iter().map(|s| s.means).collect();
+    let rotations: Vec<_> = splats.iter().map(|s| s.rotation).collect();
+    let log_scales: Vec<_> = splats.iter().map(|s| s.log_scale).collect();
+    let sh_coeffs: Vec<_> = splats.iter().flat_map(|s| s.sh_dc).collect();
+    let opacity: Vec<_> = splats.iter().map(|s| s.opacity).collect();
+    Ok(Splats::from_raw(
+        &means,
+        Some(&rotations),
+        Some(&log_scales),
+        Some(&sh_coeffs),
+        Some(&opacity),
+        device,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use super::decode_splat_file;
+
+    #[test]
+    fn decodes_splat_rows() {
+        let mut row = vec![];
+        for v in [1.0f32, 2.0, 3.0, 0.5, 1.0, 2.0] {
+            row.extend_from_slice(&v.to_le_bytes());
+        }
+        // Color and a rotation of 90 degrees around Y.
+        row.extend_from_slice(&[255, 128, 0, 128]);
+        row.extend_from_slice(&[219, 128, 219, 128]);
+
+        let splats = decode_splat_file(&row, None).expect("Valid splat");
+        let splat = &splats[0];
+        assert_eq!(splat.means, Vec3::new(1.0, 2.0, 3.0));
+        assert!((splat.log_scale - Vec3::new(0.5f32.ln(), 0.0, 2.0f32.ln())).length() < 1e-6);
+        assert!(splat.opacity.abs() < 0.01);
+        let expected = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        assert!(splat.rotation.angle_between(expected) < 0.02);
+        assert!(splat.sh_dc[0] > 0.0 && splat.sh_dc[2] < 0.0);
+
+        assert!(decode_splat_file(&row[..31], None).is_err());
+    }
+}
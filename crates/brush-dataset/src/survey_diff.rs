@@ -0,0 +1,603 @@
+//! Compare the geometry of a model against a reference survey, eg. a lidar point cloud or a
+//! mesh, to check a capture before it's used for measurements.
+//!
+//! The surface of the splats is their expected depth, rendered from every view of a dataset.
+//! Each pixel is unprojected to a point on that surface, and compared to the closest point of
+//! the reference. The distance is signed along the view ray: positive when the splats are
+//! behind the reference, negative when they're in front of it. The reference has to be in the
+//! coordinates of the model, eg. by training with the survey as initial points.
+use std::{io::Cursor, path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use async_fn_stream::try_fn_stream;
+use brush_render::{
+    camera::Camera, depth::render_depth, gaussian_splats::Splats, spatial_index::SpatialIndex,
+    Backend,
+};
+use brush_tasks::ProgressSender;
+use brush_train::scene::Scene;
+use glam::Vec3;
+use image::{DynamicImage, RgbImage};
+use rayon::prelude::*;
+use serde::Serialize;
+use tokio_stream::Stream;
+
+// Triangles per leaf of the hierarchy over mesh triangles.
+const LEAF_TRIANGLES: usize = 8;
+// Every this many rows and columns of a view are pooled into the statistics of all views,
+// so big datasets don't need all distances in memory.
+const POOLED_STRIDE: usize = 4;
+
+const UNCOVERED: [u8; 3] = [32, 32, 32];
+const UNSURVEYED: [u8; 3] = [128, 128, 128];
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    min: Vec3,
+    max: Vec3,
+    // The triangles under this node are order[start..start + count].
+    start: u32,
+    count: u32,
+    // The two children are at nodes[children] and nodes[children + 1], leaves have none.
+    children: u32,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.children == 0
+    }
+
+    fn range(&self) -> std::ops::Range<usize> {
+        self.start as usize..(self.start + self.count) as usize
+    }
+
+    fn distance_squared(&self, point: Vec3) -> f32 {
+        point.clamp(self.min, self.max).distance_squared(point)
+    }
+}
+
+// Bounding volume hierarchy over the bounds of triangles, to find the exact closest triangle.
+// Triangles are bounded by their corners, so a large triangle is never missed because its
+// center is far away.
+struct TriangleBvh {
+    nodes: Vec<Node>,
+    order: Vec<u32>,
+}
+
+impl TriangleBvh {
+    fn new(bounds: &[(Vec3, Vec3)]) -> Self {
+        let mut bvh = Self {
+            nodes: vec![],
+            order: (0..bounds.len() as u32).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.nodes.push(Node {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+                start: 0,
+                count: 0,
+                children: 0,
+            });
+            bvh.build(0, 0, bounds.len(), bounds);
+        }
+        bvh
+    }
+
+    fn build(&mut self, node: usize, start: usize, end: usize, bounds: &[(Vec3, Vec3)]) {
+        let ids = &mut self.order[start..end];
+        let (min, max) = ids.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), &i| {
+                let (lo, hi) = bounds[i as usize];
+                (min.min(lo), max.max(hi))
+            },
+        );
+        self.nodes[node] = Node {
+            min,
+            max,
+            start: start as u32,
+            count: ids.len() as u32,
+            children: 0,
+        };
+
+        if ids.len() <= LEAF_TRIANGLES {
+            return;
+        }
+
+        // Split at the median of the centers along the widest axis.
+        let size = max - min;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        let mid = ids.len() / 2;
+        let center = |i: u32| {
+            let (lo, hi) = bounds[i as usize];
+            lo[axis] + hi[axis]
+        };
+        ids.select_nth_unstable_by(mid, |&a, &b| center(a).total_cmp(&center(b)));
+
+        let children = self.nodes.len();
+        let empty = self.nodes[node];
+        self.nodes.push(empty);
+        self.nodes.push(empty);
+        self.nodes[node].children = children as u32;
+        self.build(children, start, start + mid, bounds);
+        self.build(children + 1, start + mid, end, bounds);
+    }
+
+    // The closest point over all triangles, where `closest` gives the closest point of a
+    // triangle. Nodes further away than the closest point so far are skipped.
+    fn closest(&self, point: Vec3, closest: impl Fn(usize) -> Vec3) -> Option<Vec3> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut best = (point, f32::INFINITY);
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node: Node = self.nodes[node];
+            if node.distance_squared(point) >= best.1 {
+                continue;
+            }
+
+            if !node.is_leaf() {
+                // Visit the nearer child first, so more of the other one can be skipped.
+                let (a, b) = (node.children as usize, node.children as usize + 1);
+                if self.nodes[a].distance_squared(point) < self.nodes[b].distance_squared(point) {
+                    stack.extend([b, a]);
+                } else {
+                    stack.extend([a, b]);
+                }
+                continue;
+            }
+
+            for &index in &self.order[node.range()] {
+                let candidate = closest(index as usize);
+                let dist = candidate.distance_squared(point);
+                if dist < best.1 {
+                    best = (candidate, dist);
+                }
+            }
+        }
+        best.1.is_finite().then_some(best.0)
+    }
+}
+
+// How to find the closest point of the reference.
+enum Lookup {
+    Points(SpatialIndex),
+    Triangles(TriangleBvh),
+}
+
+/// Reference geometry to compare against, as points, or a mesh when there are triangles.
+pub struct ReferenceGeometry {
+    vertices: Vec<Vec3>,
+    triangles: Vec<[u32; 3]>,
+    lookup: Lookup,
+}
+
+impl ReferenceGeometry {
+    /// A point cloud, eg. the vertices of a lidar scan.
+    pub fn from_points(points: Vec<Vec3>) -> Self {
+        Self::from_mesh(points, vec![])
+    }
+
+    /// A triangle mesh, as indices into the vertices.
+    pub fn from_mesh(vertices: Vec<Vec3>, triangles: Vec<[u32; 3]>) -> Self {
+        let lookup = if triangles.is_empty() {
+            Lookup::Points(SpatialIndex::from_means(&vertices))
+        } else {
+            let bounds: Vec<_> = triangles
+                .iter()
+                .map(|t| {
+                    let [a, b, c] = t.map(|i| vertices[i as usize]);
+                    (a.min(b).min(c), a.max(b).max(c))
+                })
+                .collect();
+            Lookup::Triangles(TriangleBvh::new(&bounds))
+        };
+        Self {
+            vertices,
+            triangles,
+            lookup,
+        }
+    }
+
+    /// Read the vertices and faces of an `.obj`. Polygons are split into triangles, and a
+    /// file without faces is a point cloud.
+    pub fn from_obj(text: &str) -> anyhow::Result<Self> {
+        let mut vertices = vec![];
+        let mut triangles = vec![];
+
+        for (line, content) in text.lines().enumerate() {
+            let mut parts = content.split_whitespace();
+            match parts.next() {
+                Some("v") => {
+                    let coords: Vec<f32> = parts
+                        .take(3)
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .with_context(|| format!("Invalid vertex on line {}", line + 1))?;
+                    let [x, y, z] = coords[..] else {
+                        anyhow::bail!("Vertex on line {} needs 3 coordinates", line + 1);
+                    };
+                    vertices.push(Vec3::new(x, y, z));
+                }
+                Some("f") => {
+                    // Indices start at 1, negative indices count back from the last vertex.
+                    let face: Vec<u32> = parts
+                        .map(|p| {
+                            let index: i64 =
+                                p.split('/').next().unwrap_or_default().parse().ok()?;
+                            let index = if index < 0 {
+                                vertices.len() as i64 + index
+                            } else {
+                                index - 1
+                            };
+                            (0..vertices.len() as i64)
+                                .contains(&index)
+                                .then_some(index as u32)
+                        })
+                        .collect::<Option<_>>()
+                        .with_context(|| format!("Invalid face on line {}", line + 1))?;
+                    for i in 1..face.len().saturating_sub(1) {
+                        triangles.push([face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        anyhow::ensure!(!vertices.is_empty(), "The reference has no vertices");
+        Ok(Self::from_mesh(vertices, triangles))
+    }
+
+    pub fn vertices(&self) -> &[Vec3] {
+        &self.vertices
+    }
+
+    pub fn is_mesh(&self) -> bool {
+        matches!(self.lookup, Lookup::Triangles(_))
+    }
+
+    /// The point of the reference closest to `point`.
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        match &self.lookup {
+            Lookup::Points(index) => index
+                .nearest(point, 1)
+                .first()
+                .map_or(point, |&(i, _)| self.vertices[i]),
+            Lookup::Triangles(bvh) => bvh
+                .closest(point, |i| {
+                    let [a, b, c] = self.triangles[i].map(|v| self.vertices[v as usize]);
+                    closest_on_triangle(point, a, b, c)
+                })
+                .unwrap_or(point),
+        }
+    }
+
+    /// Distance from `point` to the reference, positive when `point` is further along `dir`
+    /// than the closest point of the reference.
+    pub fn signed_distance(&self, point: Vec3, dir: Vec3) -> f32 {
+        let offset = point - self.closest_point(point);
+        offset.length().copysign(offset.dot(dir))
+    }
+}
+
+// The closest point of a triangle, from Real-Time Collision Detection (Ericson, 5.1.5).
+fn closest_on_triangle(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let denom = va + vb + vc;
+    if denom.abs() < f32::EPSILON {
+        // A degenerate triangle, the nearest corner is close enough.
+        return [a, b, c]
+            .into_iter()
+            .min_by(|x, y| x.distance_squared(p).total_cmp(&y.distance_squared(p)))
+            .unwrap_or(a);
+    }
+    a + ab * (vb / denom) + ac * (vc / denom)
+}
+
+/// How to compare against the reference.
+#[derive(Clone, Copy, Debug)]
+pub struct SurveyDiffOptions {
+    /// Distance that is fully red or blue in the deviation renders, in scene units.
+    pub range: f32,
+    /// Distances within this are counted as matching the reference.
+    pub tolerance: f32,
+    /// Points further than this from the reference are outside the survey, and not
+    /// counted, eg. the sky or the surroundings of a scanned building.
+    pub max_distance: Option<f32>,
+}
+
+impl Default for SurveyDiffOptions {
+    fn default() -> Self {
+        Self {
+            range: 0.1,
+            tolerance: 0.02,
+            max_distance: None,
+        }
+    }
+}
+
+/// Statistics of the signed distances to the reference.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DeviationStats {
+    pub samples: usize,
+    pub mean: f32,
+    pub std: f32,
+    pub mean_abs: f32,
+    pub rms: f32,
+    pub median: f32,
+    /// 95% of the samples are at most this far from the reference.
+    pub p95_abs: f32,
+    pub max_abs: f32,
+    /// Fraction of the samples within the tolerance.
+    pub within_tolerance: f32,
+}
+
+impl DeviationStats {
+    pub fn from_distances(distances: &[f32], tolerance: f32) -> Self {
+        if distances.is_empty() {
+            return Self::default();
+        }
+
+        let n = distances.len() as f32;
+        let mean = distances.iter().sum::<f32>() / n;
+        let variance = distances.iter().map(|d| (d - mean).powi(2)).sum::<f32>() / n;
+        let rms = (distances.iter().map(|d| d * d).sum::<f32>() / n).sqrt();
+
+        let mut sorted = distances.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let mut abs: Vec<f32> = distances.iter().map(|d| d.abs()).collect();
+        abs.sort_by(f32::total_cmp);
+        let quantile =
+            |values: &[f32], q: f32| values[((values.len() - 1) as f32 * q).round() as usize];
+
+        Self {
+            samples: distances.len(),
+            mean,
+            std: variance.sqrt(),
+            mean_abs: abs.iter().sum::<f32>() / n,
+            rms,
+            median: quantile(&sorted, 0.5),
+            p95_abs: quantile(&abs, 0.95),
+            max_abs: abs[abs.len() - 1],
+            within_tolerance: abs.iter().filter(|&&d| d <= tolerance).count() as f32 / n,
+        }
+    }
+}
+
+/// Color of a signed distance, green on the reference, turning blue in front of it and red
+/// behind it until `range`.
+pub fn deviation_color(distance: f32, range: f32) -> [u8; 3] {
+    let t = (distance / range.max(f32::EPSILON)).clamp(-1.0, 1.0);
+    let target = if t < 0.0 {
+        Vec3::new(0.0, 0.2, 1.0)
+    } else {
+        Vec3::new(1.0, 0.1, 0.0)
+    };
+    let color = Vec3::new(0.1, 0.8, 0.2).lerp(target, t.abs()) * 255.0;
+    color.round().to_array().map(|c| c as u8)
+}
+
+// Signed distances of the pixels of a depth map, `None` where the splats don't cover the
+// pixel or the reference is too far.
+fn pixel_distances(
+    camera: &Camera,
+    depth: &[f32],
+    width: usize,
+    height: usize,
+    reference: &ReferenceGeometry,
+    max_distance: Option<f32>,
+) -> Vec<Option<f32>> {
+    let tan_half = glam::vec2(
+        (camera.fov_x * 0.5).tan() as f32,
+        (camera.fov_y * 0.5).tan() as f32,
+    );
+    depth
+        .par_iter()
+        .enumerate()
+        .map(|(i, &z)| {
+            if z <= 0.0 {
+                return None;
+            }
+            let pixel = glam::vec2((i % width) as f32 + 0.5, (i / width) as f32 + 0.5);
+            let uv = pixel / glam::vec2(width as f32, height as f32);
+            // The depth is along the view axis, not the length of the ray.
+            let local = ((uv - camera.center_uv) * 2.0 * tan_half).extend(1.0);
+            let dir = camera.rotation * local;
+            let distance = reference.signed_distance(camera.position + dir * z, dir);
+            match max_distance {
+                Some(max) if distance.abs() > max => None,
+                _ => Some(distance),
+            }
+        })
+        .collect()
+}
+
+fn encode_png(image: RgbImage) -> anyhow::Result<Vec<u8>> {
+    let mut png = vec![];
+    DynamicImage::ImageRgb8(image).write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Compare the expected depth of `splats` on every view of `scene` against the reference.
+/// Streams a deviation render of every view as `deviation/00012_<name>.png`, and finally a
+/// `deviation.json` report with the statistics of all views and of each view.
+pub fn survey_diff<B: Backend>(
+    splats: Splats<B>,
+    scene: &Scene,
+    reference: Arc<ReferenceGeometry>,
+    options: SurveyDiffOptions,
+    progress: ProgressSender,
+) -> impl Stream<Item = anyhow::Result<(PathBuf, Vec<u8>)>> + 'static {
+    let views = scene.views.clone();
+
+    try_fn_stream(|emitter| async move {
+        let mut pooled = vec![];
+        let mut view_stats = vec![];
+
+        for (index, view) in views.iter().enumerate() {
+            let (w, h) = (view.image.width() as usize, view.image.height() as usize);
+            let size = glam::uvec2(w as u32, h as u32);
+            let depth = render_depth(&splats, &view.camera, size)
+                .into_data_async()
+                .await
+                .to_vec::<f32>()
+                .map_err(|e| anyhow::anyhow!("Failed to read depth {e:?}"))?;
+
+            let camera = view.camera.clone();
+            let reference = reference.clone();
+            let (distances, png) = brush_tasks::run_blocking(move || {
+                let distances =
+                    pixel_distances(&camera, &depth, w, h, &reference, options.max_distance);
+                let image = RgbImage::from_fn(w as u32, h as u32, |x, y| {
+                    let color = match depth[y as usize * w + x as usize] {
+                        z if z <= 0.0 => UNCOVERED,
+                        _ => distances[y as usize * w + x as usize]
+                            .map_or(UNSURVEYED, |d| deviation_color(d, options.range)),
+                    };
+                    image::Rgb(color)
+                });
+                anyhow::Ok((distances, encode_png(image)?))
+            })
+            .await?;
+
+            let measured: Vec<f32> = distances.iter().flatten().copied().collect();
+            pooled.extend(
+                distances
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| (i % w) % POOLED_STRIDE == 0 && (i / w) % POOLED_STRIDE == 0)
+                    .filter_map(|(_, d)| *d),
+            );
+            view_stats.push(serde_json::json!({
+                "name": view.name,
+                "stats": DeviationStats::from_distances(&measured, options.tolerance),
+            }));
+
+            // Prefix the index, names aren't unique when images are in subfolders.
+            let stem = std::path::Path::new(&view.name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            emitter
+                .emit((
+                    PathBuf::from(format!("deviation/{index:05}_{stem}.png")),
+                    png,
+                ))
+                .await;
+
+            progress.report("Comparing to the reference", index + 1, views.len());
+        }
+
+        let report = serde_json::json!({
+            "tolerance": options.tolerance,
+            "max_distance": options.max_distance,
+            "all": DeviationStats::from_distances(&pooled, options.tolerance),
+            "views": view_stats,
+        });
+        emitter
+            .emit((
+                PathBuf::from("deviation.json"),
+                serde_json::to_vec_pretty(&report)?,
+            ))
+            .await;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_to_a_square() {
+        let reference =
+            ReferenceGeometry::from_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1/1 2/2 3/3 -1/4\n")
+                .expect("Valid obj");
+        assert!(reference.is_mesh());
+
+        // Above the middle of the square, and past its corner.
+        let down = Vec3::NEG_Z;
+        assert!((reference.signed_distance(Vec3::new(0.5, 0.5, 0.25), down) + 0.25).abs() < 1e-5);
+        assert!((reference.signed_distance(Vec3::new(0.5, 0.5, -0.5), down) - 0.5).abs() < 1e-5);
+        let corner = reference.closest_point(Vec3::new(2.0, 2.0, 0.0));
+        assert!(corner.distance(Vec3::new(1.0, 1.0, 0.0)) < 1e-5);
+    }
+
+    #[test]
+    fn closest_point_next_to_large_triangle() {
+        // A large ground triangle, next to a fan of small triangles whose centers are all
+        // nearer to the query point than the center of the ground.
+        let mut obj = String::from("v -100 -100 0\nv 100 -100 0\nv 0 100 0\nf 1 2 3\n");
+        for i in 0..64 {
+            let x = 1.0 + i as f32 * 0.01;
+            obj += &format!("v {x} 0 1\nv {} 0 1\nv {x} 0.01 1\n", x + 0.01);
+            let v = 4 + i * 3;
+            obj += &format!("f {v} {} {}\n", v + 1, v + 2);
+        }
+        let reference = ReferenceGeometry::from_obj(&obj).expect("Valid obj");
+
+        // Closer to the ground below than to the small triangles above.
+        let point = Vec3::new(40.0, 0.0, 0.4);
+        let closest = reference.closest_point(point);
+        assert!(closest.distance(Vec3::new(40.0, 0.0, 0.0)) < 1e-4);
+        assert!((reference.signed_distance(point, Vec3::NEG_Z) + 0.4).abs() < 1e-4);
+    }
+
+    #[test]
+    fn invalid_faces_are_errors() {
+        assert!(ReferenceGeometry::from_obj("v 0 0 0\nf 1 2 3\n").is_err());
+        assert!(ReferenceGeometry::from_obj("f 1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn stats_of_distances() {
+        let stats = DeviationStats::from_distances(&[-0.1, 0.0, 0.1, 0.2, 0.3], 0.15);
+        assert_eq!(stats.samples, 5);
+        assert!((stats.mean - 0.1).abs() < 1e-6);
+        assert!((stats.median - 0.1).abs() < 1e-6);
+        assert!((stats.max_abs - 0.3).abs() < 1e-6);
+        assert!((stats.within_tolerance - 0.6).abs() < 1e-6);
+        assert_eq!(deviation_color(-1.0, 0.1)[2], 255);
+        assert_eq!(deviation_color(1.0, 0.1)[0], 255);
+    }
+}
@@ -1,7 +1,9 @@
 // While most wgsl binding code is autogenerated, some more glue is needed for
 // wgsl burn interop. This file contains some of this glue code, it's mainly
 // generated by the macro below.
+pub mod pool;
 mod shaders;
+pub mod texture;
 
 use burn::tensor::{DType, Shape};
 pub use burn_jit::cubecl::{
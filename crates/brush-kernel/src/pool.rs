@@ -0,0 +1,133 @@
+// A pool of scratch buffers, so renders don't allocate all their intermediate buffers anew
+// every frame. Buffers are grouped in size buckets, and handed out again once nothing
+// but the pool holds on to them.
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+};
+
+use burn::tensor::{
+    backend::{DeviceId, DeviceOps},
+    DType, Shape,
+};
+use burn_jit::{
+    cubecl::{client::ComputeClient, server::Handle},
+    tensor::JitTensor,
+    JitRuntime,
+};
+
+// Buffers that haven't been handed out for this many epochs are released.
+const MAX_IDLE_EPOCHS: u64 = 16;
+
+// Number of buckets between each power of two. Buffers are at most 1/8th larger than needed.
+const BUCKETS_PER_OCTAVE: u64 = 8;
+
+fn bucket_size(bytes: u64) -> u64 {
+    let bytes = bytes.max(256);
+    let octave = bytes.next_power_of_two() / 2;
+    let step = (octave / BUCKETS_PER_OCTAVE).max(1);
+    bytes.next_multiple_of(step)
+}
+
+struct PooledBuffer {
+    handle: Handle,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct BufferPool {
+    epoch: u64,
+    buckets: HashMap<u64, Vec<PooledBuffer>>,
+}
+
+impl BufferPool {
+    fn next_epoch(&mut self) {
+        self.epoch += 1;
+
+        let epoch = self.epoch;
+        for buffers in self.buckets.values_mut() {
+            buffers.retain(|b| epoch - b.last_used <= MAX_IDLE_EPOCHS);
+        }
+        self.buckets.retain(|_, buffers| !buffers.is_empty());
+    }
+
+    fn take<R: JitRuntime>(
+        &mut self,
+        bytes: u64,
+        client: &ComputeClient<R::Server, R::Channel>,
+    ) -> Handle {
+        let bucket = bucket_size(bytes);
+        let epoch = self.epoch;
+        let buffers = self.buckets.entry(bucket).or_default();
+
+        // Buffers are only recycled in a later epoch than they were last handed out in,
+        // and only if nothing outside of the pool still references them.
+        let free = buffers
+            .iter_mut()
+            .find(|b| b.last_used < epoch && b.handle.can_mut());
+
+        let handle = if let Some(buffer) = free {
+            buffer.last_used = epoch;
+            buffer.handle.clone()
+        } else {
+            let handle = client.empty(bucket as usize);
+            buffers.push(PooledBuffer {
+                handle: handle.clone(),
+                last_used: epoch,
+            });
+            handle
+        };
+
+        // Only expose the requested size, shaders might rely on the length of a binding.
+        handle.offset_end(bucket - bytes)
+    }
+}
+
+static POOLS: Mutex<Option<HashMap<DeviceId, BufferPool>>> = Mutex::new(None);
+
+fn with_pool<T>(device_id: DeviceId, f: impl FnOnce(&mut BufferPool) -> T) -> T {
+    let mut pools = POOLS.lock().unwrap_or_else(PoisonError::into_inner);
+    f(pools
+        .get_or_insert_with(HashMap::new)
+        .entry(device_id)
+        .or_default())
+}
+
+/// Start a new epoch for the pool of a device.
+///
+/// Buffers handed out in an epoch are never handed out again in the same epoch, so this
+/// should be called once per frame or render. Buffers unused for a while are released.
+pub fn next_epoch<D: DeviceOps>(device: &D) {
+    with_pool(device.id(), BufferPool::next_epoch);
+}
+
+/// Like [`crate::create_tensor`], but takes the buffer from a pool of the device when possible.
+///
+/// This is meant for scratch buffers that are created for every render. The contents of the
+/// buffer are undefined.
+pub fn create_pooled_tensor<const D: usize, R: JitRuntime>(
+    shape: [usize; D],
+    device: &R::Device,
+    client: &ComputeClient<R::Server, R::Channel>,
+    dtype: DType,
+) -> JitTensor<R> {
+    let shape = Shape::from(shape.to_vec());
+    let bytes = (shape.num_elements() * dtype.size()) as u64;
+    let handle = with_pool(device.id(), |pool| pool.take::<R>(bytes, client));
+    JitTensor::new_contiguous(client.clone(), device.clone(), shape, handle, dtype)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bucket_size;
+
+    #[test]
+    fn buckets_fit() {
+        for bytes in [1, 255, 256, 257, 1000, 4096, 4097, 123_456_789] {
+            let bucket = bucket_size(bytes);
+            assert!(bucket >= bytes);
+            assert!(bucket <= bytes.max(256) + bytes.max(256) / 8);
+        }
+        assert_eq!(bucket_size(4097), bucket_size(4100));
+    }
+}
@@ -0,0 +1,65 @@
+// Interop between burn-jit buffers and wgpu textures. Images rendered by burn live in
+// a storage buffer, this copies them into a texture on the GPU, without a roundtrip
+// through the CPU.
+use burn_jit::tensor::JitTensor;
+use burn_wgpu::WgpuRuntime;
+
+/// Rows of an rgba8 image have to be a multiple of this many pixels to be copied
+/// between a buffer and a texture.
+pub const ROW_ALIGN_PIXELS: u32 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT / 4;
+
+/// The smallest width that can be copied to a texture directly, see [`ROW_ALIGN_PIXELS`].
+pub fn aligned_width(width: u32) -> u32 {
+    width.next_multiple_of(ROW_ALIGN_PIXELS)
+}
+
+/// Record a copy of an image of packed rgba8 pixels into `texture`.
+///
+/// The image is a `[height, stride]` buffer of u32s, where the stride is aligned with
+/// [`aligned_width`]. Only the `width` x `height` region that fits the texture is copied,
+/// so an image can be rendered at the aligned width and then cropped for free.
+pub fn copy_to_texture(
+    img: &JitTensor<WgpuRuntime>,
+    texture: &wgpu::Texture,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    let [height, stride, channels] = img.shape.dims[..] else {
+        panic!("Expected an image of [h, w, 1]");
+    };
+    assert_eq!(channels, 1, "Expected an image of packed rgba8 pixels");
+    assert_eq!(
+        stride as u32 % ROW_ALIGN_PIXELS,
+        0,
+        "Image rows need to be aligned to be copied to a texture"
+    );
+
+    let width = texture.width();
+    let height = (height as u32).min(texture.height());
+    assert!(width <= stride as u32, "Image is narrower than the texture");
+
+    // Make sure all pending kernels writing to the image are submitted before the copy.
+    img.client.flush();
+    let resource = img.client.get_resource(img.handle.clone().binding());
+
+    encoder.copy_buffer_to_texture(
+        wgpu::ImageCopyBuffer {
+            buffer: resource.resource().buffer.as_ref(),
+            layout: wgpu::ImageDataLayout {
+                offset: resource.resource().offset(),
+                bytes_per_row: Some(4 * stride as u32),
+                rows_per_image: None,
+            },
+        },
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
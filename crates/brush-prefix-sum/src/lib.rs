@@ -15,6 +15,19 @@ kernel_source_gen!(PrefixSumAddScannedSums {}, prefix_sum_add_scanned_sums);
 
 use burn_wgpu::JitTensor;
 
+/// The most kernels a [`prefix_sum`] dispatches, for inputs of up to `u32::MAX` elements:
+/// one scan, then a scan and an add for each level of group sums.
+pub const MAX_DISPATCHES: usize = {
+    let threads_per_group = shaders::prefix_sum_helpers::THREADS_PER_GROUP as usize;
+    let mut levels = 0;
+    let mut work_sz = u32::MAX as usize;
+    while work_sz > threads_per_group {
+        work_sz = work_sz.div_ceil(threads_per_group);
+        levels += 1;
+    }
+    1 + 2 * levels
+};
+
 pub fn prefix_sum(input: JitTensor<WgpuRuntime>) -> JitTensor<WgpuRuntime> {
     let threads_per_group = shaders::prefix_sum_helpers::THREADS_PER_GROUP as usize;
     let num = input.shape.dims[0];
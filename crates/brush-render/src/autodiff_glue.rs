@@ -0,0 +1,215 @@
+//! Gradients of rendering, for training. Only built with the `autodiff` feature, a viewer
+//! doesn't need them.
+use burn::{
+    backend::{
+        autodiff::{
+            checkpoint::{base::Checkpointer, strategy::CheckpointStrategy},
+            grads::Gradients,
+            ops::{Backward, Ops, OpsKind},
+        },
+        Autodiff,
+    },
+    tensor::{backend::AutodiffBackend, ops::FloatTensor, Tensor, TensorPrimitive},
+};
+
+use crate::{
+    camera::Camera, render::sh_degree_from_coeffs, Backend, GaussianBackwardState,
+    RenderAuxPrimitive, RenderOptions,
+};
+
+#[derive(Debug)]
+struct RenderBackwards;
+
+const NUM_ARGS: usize = 7;
+
+// Implement gradient registration when rendering backwards.
+impl<B: Backend> Backward<B, NUM_ARGS> for RenderBackwards {
+    type State = GaussianBackwardState<B>;
+
+    fn backward(
+        self,
+        ops: Ops<Self::State, NUM_ARGS>,
+        grads: &mut Gradients,
+        _checkpointer: &mut Checkpointer,
+    ) {
+        let _span = tracing::trace_span!("render_gaussians backwards").entered();
+
+        let state = ops.state;
+
+        let v_output = grads.consume::<B>(&ops.node);
+
+        // Register gradients for parent nodes (This code is already skipped entirely
+        // if no parent nodes require gradients).
+        let [mean_parent, xys_parent, camera_parent, log_scales_parent, quats_parent, coeffs_parent, raw_opacity_parent] =
+            ops.parents;
+
+        let v_tens = B::render_splats_bwd(state, v_output);
+
+        if let Some(node) = mean_parent {
+            grads.register::<B>(node.id, v_tens.v_means);
+        }
+
+        // Register the gradients for the dummy xy input.
+        if let Some(node) = xys_parent {
+            grads.register::<B>(node.id, v_tens.v_xy);
+        }
+
+        // Register the gradients for the dummy camera input.
+        if let Some(node) = camera_parent {
+            grads.register::<B>(node.id, v_tens.v_camera);
+        }
+
+        if let Some(node) = log_scales_parent {
+            grads.register::<B>(node.id, v_tens.v_scales);
+        }
+
+        if let Some(node) = quats_parent {
+            grads.register::<B>(node.id, v_tens.v_quats);
+        }
+
+        if let Some(node) = coeffs_parent {
+            grads.register::<B>(node.id, v_tens.v_coeffs);
+        }
+
+        if let Some(node) = raw_opacity_parent {
+            grads.register::<B>(node.id, v_tens.v_raw_opac);
+        }
+    }
+}
+
+// Implement
+impl<B: Backend, C: CheckpointStrategy> Backend for Autodiff<B, C> {
+    fn render_splats(
+        camera: &Camera,
+        img_size: glam::UVec2,
+        means: FloatTensor<Self>,
+        xy_dummy: FloatTensor<Self>,
+        camera_dummy: FloatTensor<Self>,
+        log_scales: FloatTensor<Self>,
+        quats: FloatTensor<Self>,
+        sh_coeffs: FloatTensor<Self>,
+        raw_opacity: FloatTensor<Self>,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> (FloatTensor<Self>, RenderAuxPrimitive<Self>) {
+        // Get backend tensors & dequantize if needed. Could try and support quantized inputs
+        // in the future.
+
+        // Prepare backward pass, and check if we even need to do it. Store nodes that need gradients.
+        let prep_nodes = RenderBackwards
+            .prepare::<C>([
+                means.node.clone(),
+                xy_dummy.node.clone(),
+                camera_dummy.node.clone(),
+                log_scales.node.clone(),
+                quats.node.clone(),
+                sh_coeffs.node.clone(),
+                raw_opacity.node.clone(),
+            ])
+            .compute_bound()
+            .stateful();
+
+        // Camera gradients are only computed when something asks for them.
+        let camera_grad = !camera_dummy.node.requirement.is_none();
+
+        // Render complete forward pass.
+        let (out_img, aux) = B::render_splats(
+            camera,
+            img_size,
+            means.clone().into_primitive(),
+            xy_dummy.into_primitive(),
+            camera_dummy.into_primitive(),
+            log_scales.clone().into_primitive(),
+            quats.clone().into_primitive(),
+            sh_coeffs.clone().into_primitive(),
+            raw_opacity.clone().into_primitive(),
+            render_u32_buffer,
+            options,
+        );
+
+        let (send, rx) = tokio::sync::watch::channel(crate::BwdAux::default());
+
+        let wrapped_aux = RenderAuxPrimitive::<Self> {
+            projected_splats: <Self as AutodiffBackend>::from_inner(aux.projected_splats.clone()),
+            radii: <Self as AutodiffBackend>::from_inner(aux.radii),
+            contributions: <Self as AutodiffBackend>::from_inner(aux.contributions),
+            num_intersections: aux.num_intersections.clone(),
+            num_visible: aux.num_visible.clone(),
+            final_index: aux.final_index.clone(),
+            tile_offsets: aux.tile_offsets.clone(),
+            compact_gid_from_isect: aux.compact_gid_from_isect.clone(),
+            global_from_compact_gid: aux.global_from_compact_gid.clone(),
+            uniforms_buffer: aux.uniforms_buffer.clone(),
+            sender: Some(send),
+        };
+
+        match prep_nodes {
+            OpsKind::Tracked(prep) => {
+                // Splats::render_with_options returns these errors before rendering.
+                if let Err(e) = options.check_differentiable() {
+                    panic!("{e}");
+                }
+
+                // Save state needed for backward pass.
+                let state = GaussianBackwardState {
+                    means: means.into_primitive(),
+                    log_scales: log_scales.into_primitive(),
+                    quats: quats.into_primitive(),
+                    raw_opac: raw_opacity.into_primitive(),
+                    camera_grad,
+                    sh_degree: sh_degree_from_coeffs(
+                        Tensor::<Self, 3>::from_primitive(TensorPrimitive::Float(sh_coeffs)).dims()
+                            [1] as u32,
+                    ),
+                    out_img: out_img.clone(),
+                    rx,
+                    projected_splats: aux.projected_splats,
+                    uniforms_buffer: aux.uniforms_buffer,
+                    final_index: aux.final_index,
+                    tile_offsets: aux.tile_offsets,
+                    compact_gid_from_isect: aux.compact_gid_from_isect,
+                    global_from_compact_gid: aux.global_from_compact_gid,
+                };
+
+                let finish = prep.finish(state, out_img);
+
+                (finish, wrapped_aux)
+            }
+            OpsKind::UnTracked(prep) => {
+                // When no node is tracked, we can just use the original operation without
+                // keeping any state.
+                (prep.finish(out_img), wrapped_aux)
+            }
+        }
+    }
+
+    fn render_splats_inference(
+        camera: &Camera,
+        img_size: glam::UVec2,
+        means: FloatTensor<Self>,
+        log_scales: FloatTensor<Self>,
+        quats: FloatTensor<Self>,
+        sh_coeffs: FloatTensor<Self>,
+        raw_opacity: FloatTensor<Self>,
+        depth_buffer: Option<FloatTensor<Self>>,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> FloatTensor<Self> {
+        // Render with the inner backend, this doesn't touch the autodiff graph at all.
+        let img = B::render_splats_inference(
+            camera,
+            img_size,
+            <Self as AutodiffBackend>::inner(means),
+            <Self as AutodiffBackend>::inner(log_scales),
+            <Self as AutodiffBackend>::inner(quats),
+            <Self as AutodiffBackend>::inner(sh_coeffs),
+            <Self as AutodiffBackend>::inner(raw_opacity),
+            depth_buffer.map(<Self as AutodiffBackend>::inner),
+            render_u32_buffer,
+            options,
+        );
+        <Self as AutodiffBackend>::from_inner(img)
+    }
+}
+
+impl<B: Backend, C: CheckpointStrategy> crate::AutodiffBackend for Autodiff<B, C> {}
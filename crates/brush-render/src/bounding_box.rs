@@ -1,4 +1,4 @@
-#[derive(Clone, Copy, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct BoundingBox {
     pub center: glam::Vec3,
     pub extent: glam::Vec3,
@@ -19,4 +19,20 @@ impl BoundingBox {
     pub fn max(&self) -> glam::Vec3 {
         self.center + self.extent
     }
+
+    pub fn contains(&self, point: glam::Vec3) -> bool {
+        (point - self.center).abs().cmple(self.extent).all()
+    }
+
+    /// The 8 corners of the box.
+    pub fn corners(&self) -> [glam::Vec3; 8] {
+        std::array::from_fn(|i| {
+            let sign = glam::vec3(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            self.center + self.extent * sign
+        })
+    }
 }
@@ -1,18 +1,7 @@
-use burn::{
-    backend::{
-        autodiff::{
-            checkpoint::{base::Checkpointer, strategy::CheckpointStrategy},
-            grads::Gradients,
-            ops::{Backward, Ops, OpsKind},
-        },
-        Autodiff,
-    },
-    tensor::{
-        backend::AutodiffBackend,
-        ops::FloatTensor,
-        repr::{CustomOpDescription, HandleContainer, OperationDescription},
-        DType, Tensor, TensorPrimitive,
-    },
+use burn::tensor::{
+    ops::FloatTensor,
+    repr::{CustomOpDescription, HandleContainer, OperationDescription},
+    DType,
 };
 use burn_fusion::{client::FusionClient, stream::Operation, Fusion};
 use burn_jit::fusion::{FusionJitRuntime, JitFusionHandle};
@@ -22,9 +11,9 @@ use crate::{
     camera::Camera,
     render::{
         calc_tile_bounds, max_intersections, render_backward, render_forward, sh_coeffs_for_degree,
-        sh_degree_from_coeffs,
     },
-    shaders, BBase, Backend, GaussianBackwardState, RenderAuxPrimitive, SplatGrads,
+    shaders, BBase, Backend, GaussianBackwardState, RenderAuxPrimitive, RenderOptions, SplatGrads,
+    CAMERA_GRAD_SIZE,
 };
 
 // Implement forward functions for the inner wgpu backend.
@@ -34,11 +23,13 @@ impl Backend for BBase {
         img_size: glam::UVec2,
         means: FloatTensor<Self>,
         _xy_dummy: FloatTensor<Self>,
+        _camera_dummy: FloatTensor<Self>,
         log_scales: FloatTensor<Self>,
         quats: FloatTensor<Self>,
         sh_coeffs: FloatTensor<Self>,
         raw_opacity: FloatTensor<Self>,
         render_u32_buffer: bool,
+        options: RenderOptions,
     ) -> (FloatTensor<Self>, RenderAuxPrimitive<Self>) {
         render_forward(
             camera,
@@ -49,9 +40,40 @@ impl Backend for BBase {
             sh_coeffs,
             raw_opacity,
             render_u32_buffer,
+            options,
+            None,
+            false,
         )
     }
 
+    fn render_splats_inference(
+        camera: &Camera,
+        img_size: glam::UVec2,
+        means: FloatTensor<Self>,
+        log_scales: FloatTensor<Self>,
+        quats: FloatTensor<Self>,
+        sh_coeffs: FloatTensor<Self>,
+        raw_opacity: FloatTensor<Self>,
+        depth_buffer: Option<FloatTensor<Self>>,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> FloatTensor<Self> {
+        let (img, _) = render_forward(
+            camera,
+            img_size,
+            means,
+            log_scales,
+            quats,
+            sh_coeffs,
+            raw_opacity,
+            render_u32_buffer,
+            options,
+            depth_buffer,
+            true,
+        );
+        img
+    }
+
     fn render_splats_bwd(
         state: GaussianBackwardState<Self>,
         v_output: FloatTensor<Self>,
@@ -76,181 +98,38 @@ impl Backend for BBase {
             state.final_index,
             bwd_state.num_visible,
             state.sh_degree,
+            state.camera_grad,
         )
     }
 }
 
-#[derive(Debug)]
-struct RenderBackwards;
-
-const NUM_ARGS: usize = 6;
-
-// Implement gradient registration when rendering backwards.
-impl<B: Backend> Backward<B, NUM_ARGS> for RenderBackwards {
-    type State = GaussianBackwardState<B>;
-
-    fn backward(
-        self,
-        ops: Ops<Self::State, NUM_ARGS>,
-        grads: &mut Gradients,
-        _checkpointer: &mut Checkpointer,
-    ) {
-        let _span = tracing::trace_span!("render_gaussians backwards").entered();
-
-        let state = ops.state;
-
-        let v_output = grads.consume::<B>(&ops.node);
-
-        // Register gradients for parent nodes (This code is already skipped entirely
-        // if no parent nodes require gradients).
-        let [mean_parent, xys_parent, log_scales_parent, quats_parent, coeffs_parent, raw_opacity_parent] =
-            ops.parents;
-
-        let v_tens = B::render_splats_bwd(state, v_output);
-
-        if let Some(node) = mean_parent {
-            grads.register::<B>(node.id, v_tens.v_means);
-        }
-
-        // Register the gradients for the dummy xy input.
-        if let Some(node) = xys_parent {
-            grads.register::<B>(node.id, v_tens.v_xy);
-        }
-
-        if let Some(node) = log_scales_parent {
-            grads.register::<B>(node.id, v_tens.v_scales);
-        }
-
-        if let Some(node) = quats_parent {
-            grads.register::<B>(node.id, v_tens.v_quats);
-        }
-
-        if let Some(node) = coeffs_parent {
-            grads.register::<B>(node.id, v_tens.v_coeffs);
-        }
-
-        if let Some(node) = raw_opacity_parent {
-            grads.register::<B>(node.id, v_tens.v_raw_opac);
-        }
-    }
-}
-
-// Implement
-impl<B: Backend, C: CheckpointStrategy> Backend for Autodiff<B, C> {
-    fn render_splats(
-        camera: &Camera,
-        img_size: glam::UVec2,
-        means: FloatTensor<Self>,
-        xy_dummy: FloatTensor<Self>,
-        log_scales: FloatTensor<Self>,
-        quats: FloatTensor<Self>,
-        sh_coeffs: FloatTensor<Self>,
-        raw_opacity: FloatTensor<Self>,
-        render_u32_buffer: bool,
-    ) -> (FloatTensor<Self>, RenderAuxPrimitive<Self>) {
-        // Get backend tensors & dequantize if needed. Could try and support quantized inputs
-        // in the future.
-
-        // Prepare backward pass, and check if we even need to do it. Store nodes that need gradients.
-        let prep_nodes = RenderBackwards
-            .prepare::<C>([
-                means.node.clone(),
-                xy_dummy.node.clone(),
-                log_scales.node.clone(),
-                quats.node.clone(),
-                sh_coeffs.node.clone(),
-                raw_opacity.node.clone(),
-            ])
-            .compute_bound()
-            .stateful();
-
-        // Render complete forward pass.
-        let (out_img, aux) = B::render_splats(
-            camera,
-            img_size,
-            means.clone().into_primitive(),
-            xy_dummy.into_primitive(),
-            log_scales.clone().into_primitive(),
-            quats.clone().into_primitive(),
-            sh_coeffs.clone().into_primitive(),
-            raw_opacity.clone().into_primitive(),
-            render_u32_buffer,
-        );
-
-        let (send, rx) = tokio::sync::watch::channel(crate::BwdAux::default());
-
-        let wrapped_aux = RenderAuxPrimitive::<Self> {
-            projected_splats: <Self as AutodiffBackend>::from_inner(aux.projected_splats.clone()),
-            radii: <Self as AutodiffBackend>::from_inner(aux.radii),
-            num_intersections: aux.num_intersections.clone(),
-            num_visible: aux.num_visible.clone(),
-            final_index: aux.final_index.clone(),
-            tile_offsets: aux.tile_offsets.clone(),
-            compact_gid_from_isect: aux.compact_gid_from_isect.clone(),
-            global_from_compact_gid: aux.global_from_compact_gid.clone(),
-            uniforms_buffer: aux.uniforms_buffer.clone(),
-            sender: Some(send),
-        };
-
-        match prep_nodes {
-            OpsKind::Tracked(prep) => {
-                // Save state needed for backward pass.
-                let state = GaussianBackwardState {
-                    means: means.into_primitive(),
-                    log_scales: log_scales.into_primitive(),
-                    quats: quats.into_primitive(),
-                    raw_opac: raw_opacity.into_primitive(),
-                    sh_degree: sh_degree_from_coeffs(
-                        Tensor::<Self, 3>::from_primitive(TensorPrimitive::Float(sh_coeffs)).dims()
-                            [1] as u32,
-                    ),
-                    out_img: out_img.clone(),
-                    rx,
-                    projected_splats: aux.projected_splats,
-                    uniforms_buffer: aux.uniforms_buffer,
-                    final_index: aux.final_index,
-                    tile_offsets: aux.tile_offsets,
-                    compact_gid_from_isect: aux.compact_gid_from_isect,
-                    global_from_compact_gid: aux.global_from_compact_gid,
-                };
-
-                let finish = prep.finish(state, out_img);
-
-                (finish, wrapped_aux)
-            }
-            OpsKind::UnTracked(prep) => {
-                // When no node is tracked, we can just use the original operation without
-                // keeping any state.
-                (prep.finish(out_img), wrapped_aux)
-            }
-        }
-    }
-}
-
 impl Backend for Fusion<BBase> {
     fn render_splats(
         cam: &Camera,
         img_size: glam::UVec2,
         means: FloatTensor<Self>,
         xy_grad_dummy: FloatTensor<Self>,
+        camera_grad_dummy: FloatTensor<Self>,
         log_scales: FloatTensor<Self>,
         quats: FloatTensor<Self>,
         sh_coeffs: FloatTensor<Self>,
         raw_opacity: FloatTensor<Self>,
         render_u32_buffer: bool,
+        options: RenderOptions,
     ) -> (FloatTensor<Self>, RenderAuxPrimitive<Self>) {
         struct CustomOp {
             cam: Camera,
             img_size: glam::UVec2,
             render_u32_buffer: bool,
+            options: RenderOptions,
             desc: CustomOpDescription,
         }
 
         impl Operation<FusionJitRuntime<WgpuRuntime, u32>> for CustomOp {
             fn execute(self: Box<Self>, h: &mut HandleContainer<JitFusionHandle<WgpuRuntime>>) {
                 let (
-                    [means, xy_dummy, log_scales, quats, sh_coeffs, raw_opacity],
-                    [projected_splats, uniforms_buffer, num_intersections, num_visible, final_index, tile_offsets, compact_gid_from_isect, global_from_compact_gid, radii, out_img],
+                    [means, xy_dummy, camera_dummy, log_scales, quats, sh_coeffs, raw_opacity],
+                    [projected_splats, uniforms_buffer, num_intersections, num_visible, final_index, tile_offsets, compact_gid_from_isect, global_from_compact_gid, radii, contributions, out_img],
                 ) = self.desc.consume();
 
                 let (img, aux) = BBase::render_splats(
@@ -258,11 +137,13 @@ impl Backend for Fusion<BBase> {
                     self.img_size,
                     h.get_float_tensor::<BBase>(&means),
                     h.get_float_tensor::<BBase>(&xy_dummy),
+                    h.get_float_tensor::<BBase>(&camera_dummy),
                     h.get_float_tensor::<BBase>(&log_scales),
                     h.get_float_tensor::<BBase>(&quats),
                     h.get_float_tensor::<BBase>(&sh_coeffs),
                     h.get_float_tensor::<BBase>(&raw_opacity),
                     self.render_u32_buffer,
+                    self.options,
                 );
 
                 // Register output.
@@ -282,6 +163,7 @@ impl Backend for Fusion<BBase> {
                     aux.global_from_compact_gid,
                 );
                 h.register_float_tensor::<BBase>(&radii.id, aux.radii);
+                h.register_float_tensor::<BBase>(&contributions.id, aux.contributions);
             }
         }
 
@@ -319,6 +201,10 @@ impl Backend for Fusion<BBase> {
                 .tensor_uninitialized(vec![max_intersects as usize], DType::I32),
             global_from_compact_gid: client.tensor_uninitialized(vec![num_points], DType::I32),
             radii: client.tensor_uninitialized(vec![num_points], DType::F32),
+            contributions: client.tensor_uninitialized(
+                vec![if options.contributions { num_points } else { 1 }],
+                DType::F32,
+            ),
             sender: None,
         };
 
@@ -327,6 +213,7 @@ impl Backend for Fusion<BBase> {
             &[
                 means.into_description(),
                 xy_grad_dummy.into_description(),
+                camera_grad_dummy.into_description(),
                 log_scales.into_description(),
                 quats.into_description(),
                 sh_coeffs.into_description(),
@@ -342,6 +229,7 @@ impl Backend for Fusion<BBase> {
                 aux.compact_gid_from_isect.to_description_out(),
                 aux.global_from_compact_gid.to_description_out(),
                 aux.radii.to_description_out(),
+                aux.contributions.to_description_out(),
                 out_img.to_description_out(),
             ],
         );
@@ -350,6 +238,7 @@ impl Backend for Fusion<BBase> {
             cam: cam.clone(),
             img_size,
             render_u32_buffer,
+            options,
             desc: desc.clone(),
         };
 
@@ -358,6 +247,104 @@ impl Backend for Fusion<BBase> {
         (out_img, aux)
     }
 
+    fn render_splats_inference(
+        cam: &Camera,
+        img_size: glam::UVec2,
+        means: FloatTensor<Self>,
+        log_scales: FloatTensor<Self>,
+        quats: FloatTensor<Self>,
+        sh_coeffs: FloatTensor<Self>,
+        raw_opacity: FloatTensor<Self>,
+        depth_buffer: Option<FloatTensor<Self>>,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> FloatTensor<Self> {
+        struct CustomOp {
+            cam: Camera,
+            img_size: glam::UVec2,
+            render_u32_buffer: bool,
+            options: RenderOptions,
+            has_depth_buffer: bool,
+            desc: CustomOpDescription,
+        }
+
+        impl Operation<FusionJitRuntime<WgpuRuntime, u32>> for CustomOp {
+            fn execute(self: Box<Self>, h: &mut HandleContainer<JitFusionHandle<WgpuRuntime>>) {
+                // The depth buffer is an extra input when there is one.
+                let (inputs, depth_buffer, out_img) = if self.has_depth_buffer {
+                    let (
+                        [means, log_scales, quats, sh_coeffs, raw_opacity, depth_buffer],
+                        [out_img],
+                    ) = self.desc.consume();
+                    (
+                        [means, log_scales, quats, sh_coeffs, raw_opacity],
+                        Some(depth_buffer),
+                        out_img,
+                    )
+                } else {
+                    let (inputs, [out_img]) = self.desc.consume();
+                    (inputs, None, out_img)
+                };
+                let [means, log_scales, quats, sh_coeffs, raw_opacity] = inputs;
+                let depth_buffer = depth_buffer.map(|d| h.get_float_tensor::<BBase>(&d));
+
+                let img = BBase::render_splats_inference(
+                    &self.cam,
+                    self.img_size,
+                    h.get_float_tensor::<BBase>(&means),
+                    h.get_float_tensor::<BBase>(&log_scales),
+                    h.get_float_tensor::<BBase>(&quats),
+                    h.get_float_tensor::<BBase>(&sh_coeffs),
+                    h.get_float_tensor::<BBase>(&raw_opacity),
+                    depth_buffer,
+                    self.render_u32_buffer,
+                    self.options,
+                );
+
+                h.register_float_tensor::<BBase>(&out_img.id, img);
+            }
+        }
+
+        let stream = means.stream;
+        let client = means.client.clone();
+
+        let channels = if render_u32_buffer { 1 } else { 4 };
+        let out_img = client.tensor_uninitialized(
+            vec![img_size.y as usize, img_size.x as usize, channels],
+            DType::F32,
+        );
+
+        let has_depth_buffer = depth_buffer.is_some();
+        let mut inputs = vec![
+            means.into_description(),
+            log_scales.into_description(),
+            quats.into_description(),
+            sh_coeffs.into_description(),
+            raw_opacity.into_description(),
+        ];
+        if let Some(depth_buffer) = depth_buffer {
+            inputs.push(depth_buffer.into_description());
+        }
+        let desc = CustomOpDescription::new(
+            "render_splats_inference",
+            &inputs,
+            &[out_img.to_description_out()],
+        );
+
+        let op = CustomOp {
+            cam: cam.clone(),
+            img_size,
+            render_u32_buffer,
+            options,
+            has_depth_buffer,
+            desc: desc.clone(),
+        };
+
+        client.register(vec![stream], OperationDescription::Custom(desc), op);
+
+        out_img
+    }
+
     fn render_splats_bwd(
         state: GaussianBackwardState<Self>,
         v_output: FloatTensor<Self>,
@@ -369,8 +356,10 @@ impl Backend for Fusion<BBase> {
 
         impl Operation<FusionJitRuntime<WgpuRuntime, u32>> for CustomOp {
             fn execute(self: Box<Self>, h: &mut HandleContainer<JitFusionHandle<WgpuRuntime>>) {
-                let ([v_output], [v_means, v_quats, v_scales, v_coeffs, v_raw_opac, v_xy]) =
-                    self.desc.consume();
+                let (
+                    [v_output],
+                    [v_means, v_quats, v_scales, v_coeffs, v_raw_opac, v_xy, v_camera],
+                ) = self.desc.consume();
 
                 let state = self.state;
 
@@ -391,6 +380,7 @@ impl Backend for Fusion<BBase> {
                     global_from_compact_gid: h
                         .get_int_tensor::<BBase>(&state.global_from_compact_gid.into_description()),
                     sh_degree: state.sh_degree,
+                    camera_grad: state.camera_grad,
                     rx: state.rx,
                 };
 
@@ -404,6 +394,7 @@ impl Backend for Fusion<BBase> {
                 h.register_float_tensor::<BBase>(&v_coeffs.id, grads.v_coeffs);
                 h.register_float_tensor::<BBase>(&v_raw_opac.id, grads.v_raw_opac);
                 h.register_float_tensor::<BBase>(&v_xy.id, grads.v_xy);
+                h.register_float_tensor::<BBase>(&v_camera.id, grads.v_camera);
             }
         }
 
@@ -422,6 +413,7 @@ impl Backend for Fusion<BBase> {
             v_coeffs: client.tensor_uninitialized(vec![num_points, coeffs, 3], DType::F32),
             v_raw_opac: client.tensor_uninitialized(vec![num_points], DType::F32),
             v_xy: client.tensor_uninitialized(vec![num_visible as usize, 2], DType::F32),
+            v_camera: client.tensor_uninitialized(vec![CAMERA_GRAD_SIZE], DType::F32),
         };
 
         let desc = CustomOpDescription::new(
@@ -434,6 +426,7 @@ impl Backend for Fusion<BBase> {
                 grads.v_coeffs.to_description_out(),
                 grads.v_raw_opac.to_description_out(),
                 grads.v_xy.to_description_out(),
+                grads.v_camera.to_description_out(),
             ],
         );
 
@@ -446,5 +439,3 @@ impl Backend for Fusion<BBase> {
         grads
     }
 }
-
-impl<B: Backend, C: CheckpointStrategy> crate::AutodiffBackend for Autodiff<B, C> {}
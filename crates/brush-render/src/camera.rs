@@ -1,10 +1,34 @@
+//! Pinhole cameras, as used to render and train views.
+//!
+//! A [`Camera`] looks down its local +Z axis, with +X to the right and +Y down in the image,
+//! the same convention as OpenCV and COLMAP. Its intrinsics are stored as fields of view and a
+//! principal point relative to the image size, so the same camera renders any resolution.
+//!
+//! Cameras from other tools can be converted with the builders, eg.
+//! [`Camera::with_colmap_pose`] or [`Camera::with_opengl_world_from_camera`] for nerfstudio
+//! and Blender.
+/// Pose of a rolling shutter camera when the last image row is exposed.
+///
+/// The pose of the [`Camera`] itself is used for the first row, rows in between are interpolated.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RollingShutter {
+    pub end_position: glam::Vec3,
+    pub end_rotation: glam::Quat,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Camera {
+    /// Horizontal field of view in radians.
     pub fov_x: f64,
+    /// Vertical field of view in radians.
     pub fov_y: f64,
+    /// The principal point as a fraction of the image size, `(0.5, 0.5)` is the center.
     pub center_uv: glam::Vec2,
+    /// Position of the camera in world space.
     pub position: glam::Vec3,
+    /// Rotation from camera space to world space.
     pub rotation: glam::Quat,
+    pub rolling_shutter: Option<RollingShutter>,
 }
 
 impl Camera {
@@ -21,9 +45,127 @@ impl Camera {
             center_uv,
             position,
             rotation,
+            rolling_shutter: None,
         }
     }
 
+    /// A camera at the origin with the given fields of view in radians, with the principal
+    /// point in the center. Place it with eg. [`Self::with_world_from_camera`].
+    pub fn from_fov(fov_x: f64, fov_y: f64) -> Self {
+        Self::new(
+            glam::Vec3::ZERO,
+            glam::Quat::IDENTITY,
+            fov_x,
+            fov_y,
+            glam::vec2(0.5, 0.5),
+        )
+    }
+
+    /// A camera at the origin with focal lengths and a principal point in pixels of an image
+    /// of `img_size`.
+    pub fn from_focal(focal: glam::Vec2, center: glam::Vec2, img_size: glam::UVec2) -> Self {
+        let mut camera = Self::from_fov(
+            focal_to_fov(focal.x as f64, img_size.x),
+            focal_to_fov(focal.y as f64, img_size.y),
+        );
+        camera.center_uv = center / img_size.as_vec2();
+        camera
+    }
+
+    /// A camera at the origin with the intrinsics matrix `K` of an image of `img_size`. Skew
+    /// isn't supported and ignored.
+    pub fn from_intrinsics(k: glam::Mat3, img_size: glam::UVec2) -> Self {
+        Self::from_focal(
+            glam::vec2(k.x_axis.x, k.y_axis.y),
+            glam::vec2(k.z_axis.x, k.z_axis.y),
+            img_size,
+        )
+    }
+
+    pub fn with_pose(mut self, position: glam::Vec3, rotation: glam::Quat) -> Self {
+        self.position = position;
+        self.rotation = rotation;
+        self
+    }
+
+    /// Place the camera with a camera to world transform. Any scale is dropped.
+    pub fn with_world_from_camera(self, transform: glam::Affine3A) -> Self {
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
+        self.with_pose(position, rotation.normalize())
+    }
+
+    /// Place the camera with a world to camera transform, a view matrix. Any scale is dropped.
+    pub fn with_camera_from_world(self, transform: glam::Affine3A) -> Self {
+        self.with_world_from_camera(transform.inverse())
+    }
+
+    /// Place the camera with a COLMAP pose, the rotation and translation of the world to
+    /// camera transform, as in `images.txt`.
+    pub fn with_colmap_pose(self, quat: glam::Quat, tvec: glam::Vec3) -> Self {
+        self.with_camera_from_world(glam::Affine3A::from_rotation_translation(quat, tvec))
+    }
+
+    /// Place the camera with an OpenCV pose, the `rvec` and `tvec` of eg. `solvePnP`.
+    pub fn with_opencv_pose(self, rvec: glam::Vec3, tvec: glam::Vec3) -> Self {
+        self.with_colmap_pose(glam::Quat::from_scaled_axis(rvec), tvec)
+    }
+
+    /// Place the camera with a camera to world transform of a camera looking down -Z with +Y
+    /// up, as used by OpenGL, nerfstudio, Blender and ARKit.
+    pub fn with_opengl_world_from_camera(self, transform: glam::Mat4) -> Self {
+        let mut transform = transform;
+        transform.y_axis *= -1.0;
+        transform.z_axis *= -1.0;
+        self.with_world_from_camera(glam::Affine3A::from_mat4(transform))
+    }
+
+    pub fn with_rolling_shutter(mut self, rolling_shutter: RollingShutter) -> Self {
+        self.rolling_shutter = Some(rolling_shutter);
+        self
+    }
+
+    /// The intrinsics matrix `K` for an image of `img_size`.
+    pub fn intrinsics(&self, img_size: glam::UVec2) -> glam::Mat3 {
+        let focal = self.focal(img_size);
+        let center = self.center(img_size);
+        glam::Mat3::from_cols(
+            glam::vec3(focal.x, 0.0, 0.0),
+            glam::vec3(0.0, focal.y, 0.0),
+            center.extend(1.0),
+        )
+    }
+
+    /// The camera to world transform.
+    pub fn world_from_camera(&self) -> glam::Affine3A {
+        glam::Affine3A::from_rotation_translation(self.rotation, self.position)
+    }
+
+    /// The world to camera transform, a view matrix.
+    pub fn camera_from_world(&self) -> glam::Affine3A {
+        self.world_from_camera().inverse()
+    }
+
+    /// The COLMAP pose of the camera, see [`Self::with_colmap_pose`].
+    pub fn colmap_pose(&self) -> (glam::Quat, glam::Vec3) {
+        let (_, quat, tvec) = self.camera_from_world().to_scale_rotation_translation();
+        (quat, tvec)
+    }
+
+    /// The OpenCV pose of the camera, see [`Self::with_opencv_pose`].
+    pub fn opencv_pose(&self) -> (glam::Vec3, glam::Vec3) {
+        let (quat, tvec) = self.colmap_pose();
+        (quat.to_scaled_axis(), tvec)
+    }
+
+    /// The camera to world transform of the camera looking down -Z with +Y up, see
+    /// [`Self::with_opengl_world_from_camera`].
+    pub fn opengl_world_from_camera(&self) -> glam::Mat4 {
+        let mut transform = glam::Mat4::from(self.world_from_camera());
+        transform.y_axis *= -1.0;
+        transform.z_axis *= -1.0;
+        transform
+    }
+
     pub fn focal(&self, img_size: glam::UVec2) -> glam::Vec2 {
         glam::vec2(
             fov_to_focal(self.fov_x, img_size.x) as f32,
@@ -38,6 +180,17 @@ impl Camera {
         )
     }
 
+    /// The ray through a point of the image, given as a fraction of the image size. Returns
+    /// the origin and normalized direction of the ray in world space.
+    pub fn ray(&self, uv: glam::Vec2) -> (glam::Vec3, glam::Vec3) {
+        let tan_half = glam::vec2(
+            (self.fov_x * 0.5).tan() as f32,
+            (self.fov_y * 0.5).tan() as f32,
+        );
+        let local = ((uv - self.center_uv) * 2.0 * tan_half).extend(1.0);
+        (self.position, (self.rotation * local).normalize())
+    }
+
     pub fn local_to_world(&self) -> glam::Mat4 {
         glam::Mat4::from_rotation_translation(self.rotation, self.position)
     }
@@ -45,7 +198,19 @@ impl Camera {
     pub fn world_to_local(&self) -> glam::Mat4 {
         self.local_to_world().inverse()
     }
+
+    /// World to local transform at the end of the exposure. Equal to [`Self::world_to_local`]
+    /// for global shutter cameras.
+    pub fn world_to_local_end(&self) -> glam::Mat4 {
+        match self.rolling_shutter {
+            Some(rs) => {
+                glam::Mat4::from_rotation_translation(rs.end_rotation, rs.end_position).inverse()
+            }
+            None => self.world_to_local(),
+        }
+    }
 }
+
 // Converts field of view to focal length
 pub fn fov_to_focal(fov_rad: f64, pixels: u32) -> f64 {
     0.5 * (pixels as f64) / (fov_rad * 0.5).tan()
@@ -55,3 +220,48 @@ pub fn fov_to_focal(fov_rad: f64, pixels: u32) -> f64 {
 pub fn focal_to_fov(focal: f64, pixels: u32) -> f64 {
     2.0 * f64::atan((pixels as f64) / (2.0 * focal))
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec2, vec3, Quat, UVec2};
+
+    use super::Camera;
+
+    #[test]
+    fn conventions_round_trip() {
+        let img_size = UVec2::new(640, 480);
+        let camera = Camera::from_focal(vec2(500.0, 520.0), vec2(300.0, 250.0), img_size)
+            .with_pose(
+                vec3(1.0, -2.0, 3.0),
+                Quat::from_euler(glam::EulerRot::XYZ, 0.3, -0.2, 1.1),
+            );
+
+        let k = camera.intrinsics(img_size);
+        assert!((k.x_axis.x - 500.0).abs() < 1e-3 && (k.z_axis.y - 250.0).abs() < 1e-3);
+        let same_k = Camera::from_intrinsics(k, img_size);
+        assert!((same_k.fov_y - camera.fov_y).abs() < 1e-6);
+
+        let (quat, tvec) = camera.colmap_pose();
+        let colmap = Camera::from_fov(1.0, 1.0).with_colmap_pose(quat, tvec);
+        let (rvec, tvec) = camera.opencv_pose();
+        let opencv = Camera::from_fov(1.0, 1.0).with_opencv_pose(rvec, tvec);
+        let opengl = Camera::from_fov(1.0, 1.0)
+            .with_opengl_world_from_camera(camera.opengl_world_from_camera());
+        for other in [colmap, opencv, opengl] {
+            assert!(other.position.abs_diff_eq(camera.position, 1e-4));
+            assert!(other.rotation.angle_between(camera.rotation) < 1e-3);
+        }
+
+        // The OpenGL camera looks the other way down its Z axis.
+        let forward = camera.rotation * glam::Vec3::Z;
+        let gl_forward = -camera.opengl_world_from_camera().z_axis.truncate();
+        assert!(forward.abs_diff_eq(gl_forward, 1e-5));
+
+        let view = camera.camera_from_world();
+        let point = vec3(0.5, 0.2, 4.0);
+        let back = camera
+            .world_from_camera()
+            .transform_point3(view.transform_point3(point));
+        assert!(back.abs_diff_eq(point, 1e-4));
+    }
+}
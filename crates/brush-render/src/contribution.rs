@@ -0,0 +1,55 @@
+//! Find which splats matter for a view.
+//!
+//! The contribution of a splat to a view is the sum of its blend weights over all pixels, so
+//! a splat that fully covers 10 pixels contributes 10. Rendering [`contribution_splats`]
+//! shows which splats make up a problematic view, eg. floaters that cover a lot of it.
+use burn::tensor::Tensor;
+
+use crate::{camera::Camera, gaussian_splats::Splats, render::SH_C0, Backend, RenderOptions};
+
+/// The contribution of each splat to the view, as a `[num_splats]` tensor in pixels.
+pub fn view_contributions<B: Backend>(
+    splats: &Splats<B>,
+    camera: &Camera,
+    img_size: glam::UVec2,
+) -> Tensor<B, 1> {
+    let options = RenderOptions {
+        contributions: true,
+        ..Default::default()
+    };
+    let (_, aux) = splats
+        .render_with_options(camera, img_size, false, options)
+        .expect("Sorted renders accumulate contributions");
+    aux.contributions
+}
+
+/// The splats colored by their contribution on a log scale, from blue for splats that barely
+/// contribute, through red, to yellow for the splats that contribute most.
+pub fn contribution_splats<B: Backend>(
+    splats: &Splats<B>,
+    contributions: Tensor<B, 1>,
+) -> Splats<B> {
+    let n = splats.num_splats();
+    let log_contribution = (contributions + 1.0).log();
+    let max = log_contribution.clone().max().clamp_min(1e-6);
+    let t = (log_contribution / max).reshape([n, 1]);
+
+    let rgb = Tensor::cat(
+        vec![
+            (t.clone() * 2.0).clamp(0.0, 1.0),
+            (t.clone() * 2.0 - 1.0).clamp(0.0, 1.0),
+            (-t * 2.0 + 1.0).clamp(0.0, 1.0),
+        ],
+        1,
+    );
+    // Only a base color, so the color doesn't change with the view direction.
+    let sh_coeffs = ((rgb - 0.5) / SH_C0).reshape([n, 1, 3]);
+
+    Splats::from_tensor_data(
+        splats.means.val(),
+        splats.rotation.val(),
+        splats.log_scales.val(),
+        sh_coeffs,
+        splats.raw_opacity.val(),
+    )
+}
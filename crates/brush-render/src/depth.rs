@@ -0,0 +1,56 @@
+//! Render depth maps of splats.
+//!
+//! Every splat is colored by its view space depth, so the blended color of a pixel is the
+//! alpha weighted depth of the splats covering it. Dividing by the alpha gives the expected
+//! depth.
+use burn::tensor::Tensor;
+
+use crate::{camera::Camera, gaussian_splats::Splats, render::SH_C0, Backend, RenderAux};
+
+// Pixels less covered than this have no meaningful depth.
+const MIN_ALPHA: f32 = 1e-3;
+
+/// Render the expected view space depth of every pixel as a `[h, w]` tensor. Pixels that no
+/// splats cover are 0.
+pub fn render_depth<B: Backend>(
+    splats: &Splats<B>,
+    camera: &Camera,
+    img_size: glam::UVec2,
+) -> Tensor<B, 2> {
+    render_depth_with_aux(splats, camera, img_size).0
+}
+
+/// Like [`render_depth`], with the aux of the render, eg. to train on the depth. Gradients
+/// flow back to the geometry of the splats.
+pub fn render_depth_with_aux<B: Backend>(
+    splats: &Splats<B>,
+    camera: &Camera,
+    img_size: glam::UVec2,
+) -> (Tensor<B, 2>, RenderAux<B>) {
+    let n = splats.num_splats();
+    let device = splats.means.device();
+
+    let row = camera.world_to_local().row(2);
+    let axis = Tensor::<B, 2>::from_floats([[row.x], [row.y], [row.z]], &device);
+    let depth = splats.means.val().matmul(axis) + row.w;
+
+    let rgb = Tensor::cat(vec![depth.clone(), depth.clone(), depth], 1);
+    // Only a base color, so the color doesn't change with the view direction.
+    let sh_coeffs = ((rgb - 0.5) / SH_C0).reshape([n, 1, 3]);
+    let depth_splats = Splats::from_tensor_data(
+        splats.means.val(),
+        splats.rotation.val(),
+        splats.log_scales.val(),
+        sh_coeffs,
+        splats.raw_opacity.val(),
+    );
+
+    let (img, aux) = depth_splats.render(camera, img_size, false);
+    let [h, w] = [img_size.y as usize, img_size.x as usize];
+    let weighted = img.clone().slice([0..h, 0..w, 0..1]).reshape([h, w]);
+    let alpha = img.slice([0..h, 0..w, 3..4]).reshape([h, w]);
+
+    let uncovered = alpha.clone().lower_elem(MIN_ALPHA);
+    let depth = (weighted / alpha.clamp_min(MIN_ALPHA)).mask_fill(uncovered, 0.0);
+    (depth, aux)
+}
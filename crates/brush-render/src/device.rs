@@ -0,0 +1,63 @@
+//! Create the devices brush renders on.
+
+use std::sync::Arc;
+
+use burn_wgpu::{RuntimeOptions, WgpuDevice};
+use wgpu::{Adapter, Device, Queue};
+
+use crate::render::{set_adapter_capabilities, MAX_TASKS_PER_RENDER};
+
+/// Runtime options for devices that render splats.
+pub fn runtime_options() -> RuntimeOptions {
+    RuntimeOptions {
+        // Submit a whole frame at once, viewers flush after each render.
+        tasks_max: MAX_TASKS_PER_RENDER,
+        memory_config: burn_wgpu::MemoryConfiguration::ExclusivePages,
+    }
+}
+
+/// Pick the kernel variants the device can compile, older integrated GPUs lack some features.
+fn detect_capabilities(adapter: &Adapter, device: &Device) {
+    // The GL backend is a last resort for machines without Vulkan, Metal or DX12, where the
+    // sorting kernels are unreliable, so sort on the CPU there.
+    set_adapter_capabilities(
+        device.features().contains(wgpu::Features::SUBGROUP),
+        device.limits().max_compute_workgroup_storage_size,
+        adapter.get_info().backend != wgpu::Backend::Gl,
+    );
+}
+
+/// Render on a device that was already created, eg. the one a viewer displays with.
+pub fn init_device(adapter: Arc<Adapter>, device: Arc<Device>, queue: Arc<Queue>) -> WgpuDevice {
+    detect_capabilities(&adapter, &device);
+
+    let setup = burn_wgpu::WgpuSetup {
+        instance: Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor::default())), // unused... need to fix this in Burn.
+        adapter,
+        device,
+        queue,
+    };
+    burn_wgpu::init_device(setup, runtime_options())
+}
+
+/// The default device, for headless use like the CLI and the library API.
+///
+/// The first call creates the device on the default adapter with [`runtime_options`] and
+/// picks the kernel variants the adapter supports, so call this instead of using
+/// [`WgpuDevice::DefaultDevice`] directly.
+pub fn default_device() -> WgpuDevice {
+    // Creating the setup synchronously isn't possible on wasm, the device is then created
+    // lazily with the default options and kernels.
+    #[cfg(not(target_family = "wasm"))]
+    {
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            let setup = burn_wgpu::init_setup::<burn_wgpu::graphics::AutoGraphicsApi>(
+                &WgpuDevice::DefaultDevice,
+                runtime_options(),
+            );
+            detect_capabilities(&setup.adapter, &setup.device);
+        });
+    }
+    WgpuDevice::DefaultDevice
+}
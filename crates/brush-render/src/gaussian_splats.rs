@@ -3,7 +3,8 @@ use crate::{
     camera::Camera,
     render::{sh_coeffs_for_degree, sh_degree_from_coeffs},
     safetensor_utils::safetensor_to_burn,
-    Backend, RenderAux,
+    spatial_index::SpatialIndex,
+    Backend, ColorSpace, RenderAux, RenderOptions, CAMERA_GRAD_SIZE,
 };
 use burn::{
     config::Config,
@@ -11,7 +12,6 @@ use burn::{
     tensor::{activation::sigmoid, Shape, Tensor, TensorData, TensorPrimitive},
 };
 use glam::{Quat, Vec3};
-use kiddo::{KdTree, SquaredEuclidean};
 use rand::Rng;
 use safetensors::SafeTensors;
 
@@ -37,6 +37,26 @@ pub fn inverse_sigmoid(x: f32) -> f32 {
     (x / (1.0 - x)).ln()
 }
 
+/// Estimate the normal of a gaussian as its shortest axis.
+///
+/// The sign of a covariance axis is arbitrary, so the normal is flipped to point
+/// towards `toward`, eg. the direction from the gaussian to the nearest camera.
+pub fn estimate_normal(rotation: Quat, log_scale: Vec3, toward: Vec3) -> Vec3 {
+    let axis = if log_scale.x <= log_scale.y && log_scale.x <= log_scale.z {
+        Vec3::X
+    } else if log_scale.y <= log_scale.z {
+        Vec3::Y
+    } else {
+        Vec3::Z
+    };
+    let normal = rotation.normalize() * axis;
+    if normal.dot(toward) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
 impl<B: Backend> Splats<B> {
     pub fn from_random_config(
         config: &RandomSplatsConfig,
@@ -99,16 +119,15 @@ impl<B: Backend> Splats<B> {
             let log_scales: Vec<f32> = log_scales.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
             Tensor::from_data(TensorData::new(log_scales, [n_splats, 3]), device)
         } else {
-            let tree_pos: Vec<[f32; 3]> = means.iter().map(|v| [v.x, v.y, v.z]).collect();
-            let tree: KdTree<_, 3> = (&tree_pos).into();
-            let extents: Vec<_> = tree_pos
+            let index = SpatialIndex::from_means(means);
+            let extents: Vec<_> = means
                 .iter()
-                .map(|p| {
+                .map(|&p| {
                     // Get average of 3 nearest squared distances.
-                    (tree
-                        .nearest_n::<SquaredEuclidean>(p, 4)
+                    (index
+                        .nearest(p, 4)
                         .iter()
-                        .map(|x| x.distance)
+                        .map(|&(_, distance)| distance)
                         .sum::<f32>()
                         / 4.0)
                         .sqrt()
@@ -212,17 +231,138 @@ impl<B: Backend> Splats<B> {
         camera: &Camera,
         img_size: glam::UVec2,
         render_u32_buffer: bool,
+    ) -> (Tensor<B, 3>, RenderAux<B>) {
+        let camera_dummy = Tensor::zeros([CAMERA_GRAD_SIZE], &self.means.device());
+        self.render_inner(
+            camera,
+            img_size,
+            render_u32_buffer,
+            RenderOptions::default(),
+            camera_dummy,
+        )
+    }
+
+    /// Render the splats like [`Self::render`] with other options. Foveation and color
+    /// spaces are only for [`Self::render_inference`], and with gradients tracked the options
+    /// have to be differentiable, see [`RenderOptions::check_differentiable`].
+    pub fn render_with_options(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> anyhow::Result<(Tensor<B, 3>, RenderAux<B>)> {
+        anyhow::ensure!(
+            options.foveation.is_none() && options.color_space == ColorSpace::Srgb,
+            "Only inference renders can be foveated or converted to another color space"
+        );
+        anyhow::ensure!(
+            !(options.contributions && options.order_independent),
+            "Contributions aren't accumulated with order independent blending"
+        );
+        if B::ad_enabled() {
+            options.check_differentiable()?;
+        }
+        let camera_dummy = Tensor::zeros([CAMERA_GRAD_SIZE], &self.means.device());
+        Ok(self.render_inner(camera, img_size, render_u32_buffer, options, camera_dummy))
+    }
+
+    /// Render the splats without support for gradients, see [`Backend::render_splats_inference`].
+    ///
+    /// This is the cheapest way to render when no gradients are needed, eg. in a viewer.
+    pub fn render_inference(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> Tensor<B, 3> {
+        self.render_inference_inner(camera, img_size, None, render_u32_buffer, options)
+    }
+
+    /// Render the splats like [`Self::render_inference`], hidden where they are behind
+    /// `depth_buffer`, the `[h, w]` view space depth of a scene rendered by another renderer.
+    ///
+    /// The image is premultiplied by its alpha, so blending it over the color of the scene
+    /// composites the splats into it. Pixels without any depth should be infinitely far.
+    pub fn render_inference_over_depth(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        depth_buffer: Tensor<B, 2>,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> Tensor<B, 3> {
+        self.render_inference_inner(
+            camera,
+            img_size,
+            Some(depth_buffer),
+            render_u32_buffer,
+            options,
+        )
+    }
+
+    fn render_inference_inner(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        depth_buffer: Option<Tensor<B, 2>>,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> Tensor<B, 3> {
+        let img = B::render_splats_inference(
+            camera,
+            img_size,
+            self.means.val().into_primitive().tensor(),
+            self.log_scales.val().into_primitive().tensor(),
+            self.rotation.val().into_primitive().tensor(),
+            self.sh_coeffs.val().into_primitive().tensor(),
+            self.raw_opacity.val().into_primitive().tensor(),
+            depth_buffer.map(|d| d.into_primitive().tensor()),
+            render_u32_buffer,
+            options,
+        );
+        Tensor::from_primitive(TensorPrimitive::Float(img))
+    }
+
+    /// Render the splats, and track the gradient of the camera in `camera_dummy`.
+    ///
+    /// See [`Backend::render_splats`] for the layout of the camera gradient.
+    pub fn render_with_camera_grad(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        camera_dummy: Tensor<B, 1>,
+    ) -> (Tensor<B, 3>, RenderAux<B>) {
+        self.render_inner(
+            camera,
+            img_size,
+            false,
+            RenderOptions::default(),
+            camera_dummy,
+        )
+    }
+
+    fn render_inner(
+        &self,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+        camera_dummy: Tensor<B, 1>,
     ) -> (Tensor<B, 3>, RenderAux<B>) {
         let (img, aux) = B::render_splats(
             camera,
             img_size,
             self.means.val().into_primitive().tensor(),
             self.xys_dummy.clone().into_primitive().tensor(),
+            camera_dummy.into_primitive().tensor(),
             self.log_scales.val().into_primitive().tensor(),
             self.rotation.val().into_primitive().tensor(),
             self.sh_coeffs.val().into_primitive().tensor(),
             self.raw_opacity.val().into_primitive().tensor(),
             render_u32_buffer,
+            options,
         );
 
         let img = Tensor::from_primitive(TensorPrimitive::Float(img));
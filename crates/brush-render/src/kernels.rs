@@ -5,10 +5,55 @@ use super::shaders::{
 use crate::shaders::gather_grads;
 use brush_kernel::kernel_source_gen;
 
-kernel_source_gen!(ProjectSplats {}, project_forward);
-kernel_source_gen!(ProjectVisible {}, project_visible);
+kernel_source_gen!(
+    ProjectSplats {
+        sort_distance,
+        log_depth,
+        relative_eps,
+        forward_only
+    },
+    project_forward
+);
+kernel_source_gen!(
+    ProjectVisible {
+        shaded,
+        dc_only,
+        iso_points,
+        iso_ellipsoids,
+        relative_eps
+    },
+    project_visible
+);
 kernel_source_gen!(MapGaussiansToIntersect {}, map_gaussian_to_intersects);
-kernel_source_gen!(Rasterize { raster_u32 }, rasterize);
-kernel_source_gen!(RasterizeBackwards { hard_float }, rasterize_backwards);
+kernel_source_gen!(
+    Rasterize {
+        raster_u32,
+        oit,
+        forward_only,
+        iso_points,
+        iso_ellipsoids,
+        contributions,
+        depth_test,
+        distance_keys,
+        foveated,
+        display_p3,
+        hdr10
+    },
+    rasterize
+);
+kernel_source_gen!(
+    RasterizeBackwards {
+        hard_float,
+        subgroups,
+        small_wg_mem
+    },
+    rasterize_backwards
+);
 kernel_source_gen!(GatherGrads {}, gather_grads);
-kernel_source_gen!(ProjectBackwards {}, project_backwards);
+kernel_source_gen!(
+    ProjectBackwards {
+        relative_eps,
+        camera_grad
+    },
+    project_backwards
+);
@@ -9,19 +9,30 @@ use camera::Camera;
 use shaders::helpers::TILE_WIDTH;
 use tokio::sync::watch::{Receiver, Sender};
 
+#[cfg(feature = "autodiff")]
+mod autodiff_glue;
 mod burn_glue;
 mod dim_check;
 mod kernels;
-mod safetensor_utils;
 mod shaders;
 
-#[cfg(all(test, not(target_family = "wasm")))]
+#[cfg(all(test, feature = "autodiff", not(target_family = "wasm")))]
 mod tests;
 
 pub mod bounding_box;
 pub mod camera;
+pub mod contribution;
+pub mod depth;
+pub mod device;
 pub mod gaussian_splats;
+pub mod motion;
+pub mod raycast;
 pub mod render;
+pub mod safetensor_utils;
+pub mod spatial_index;
+pub mod statistics;
+pub mod temporal;
+pub mod uncertainty;
 
 #[derive(Default, Debug, Clone)]
 struct BwdAuxData {
@@ -63,6 +74,7 @@ pub struct RenderAuxPrimitive<B: Backend> {
     pub compact_gid_from_isect: IntTensor<B>,
     pub global_from_compact_gid: IntTensor<B>,
     pub radii: FloatTensor<B>,
+    pub contributions: FloatTensor<B>,
     sender: Option<Sender<BwdAux>>,
 }
 
@@ -76,6 +88,7 @@ impl<B: Backend> RenderAuxPrimitive<B> {
             compact_gid_from_isect: Tensor::from_primitive(self.compact_gid_from_isect),
             global_from_compact_gid: Tensor::from_primitive(self.global_from_compact_gid),
             radii: Tensor::from_primitive(TensorPrimitive::Float(self.radii)),
+            contributions: Tensor::from_primitive(TensorPrimitive::Float(self.contributions)),
             sender: self.sender,
         }
     }
@@ -91,15 +104,147 @@ pub struct RenderAux<B: Backend> {
     pub compact_gid_from_isect: Tensor<B, 1, Int>,
     pub global_from_compact_gid: Tensor<B, 1, Int>,
     pub radii: Tensor<B, 1>,
+    /// How much each splat contributes to the image: the sum of its blend weights over all
+    /// pixels, in pixels. Only computed with [`RenderOptions::contributions`].
+    pub contributions: Tensor<B, 1>,
     sender: Option<Sender<BwdAux>>,
 }
 
+/// Selects how the color of each splat is computed when rasterizing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Regular view dependent color from the SH coefficients.
+    #[default]
+    Color,
+    /// SH color modulated by a headlight term, using the shortest axis of each
+    /// gaussian as its normal. Useful to inspect surface orientation. Not differentiable.
+    Shaded,
+    /// Every gaussian as a small solid disc of its color, whatever its size and opacity.
+    /// Shows the structure and density of a model, eg. to debug pruning. Not differentiable.
+    Points,
+    /// Every gaussian as the outline of its ellipse at two standard deviations, whatever its
+    /// opacity. Shows the shape and overlap of the gaussians. Not differentiable.
+    Ellipsoids,
+}
+
+/// Selects the key splats are depth sorted on.
+///
+/// Splats are sorted once for the whole image, not per tile. All keys have the same relative
+/// precision from the nearest to the farthest splat, so scenes with content both near and far
+/// don't need a key normalized to the depth range of each tile.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DepthKey {
+    /// The view space depth, sorted on its floating point bits. The bits of a float are
+    /// already a piecewise log encoding, so this has the same relative precision at any depth.
+    #[default]
+    Depth,
+    /// The distance from the camera. The order of splats doesn't change when the camera
+    /// rotates, which removes popping when looking around in large scenes with content
+    /// both near and far.
+    Distance,
+    /// The log of the distance from the camera quantized to 24 bits, over the range of depths
+    /// splats are rendered at, 0.01 to 1e10. This sorts fewer bits, trading a bit of precision
+    /// for speed on scenes with many visible splats: splats are told apart when their
+    /// distances differ by more than about 2 in a million.
+    LogDistance,
+}
+
+/// The color space of the rendered colors, for the display they're shown on.
+///
+/// Splats are trained on sRGB images, so their colors are sRGB. Shown as is on a wide gamut
+/// display, they'd be taken as the more saturated colors of its gamut.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ColorSpace {
+    /// The colors of the splats as they are.
+    #[default]
+    Srgb,
+    /// Display P3 primaries with the sRGB transfer function, as used by most wide gamut
+    /// laptop and phone displays.
+    DisplayP3,
+    /// Rec. 2020 primaries with the PQ transfer function, for HDR10 displays. White is
+    /// placed at 203 nits, the reference white of SDR content in HDR.
+    Hdr10,
+}
+
+/// Where the eye looks in a foveated render, see [`RenderOptions::foveation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Foveation {
+    /// Center of the fovea in pixels, eg. from eye tracking.
+    pub center: glam::UVec2,
+    /// Radius of the fovea in pixels. Tiles within this radius are rendered at full
+    /// resolution, tiles within twice this radius in blocks of 2x2 pixels, and tiles
+    /// further out in blocks of 4x4 pixels.
+    pub radius: u32,
+}
+
+impl Foveation {
+    /// The fovea around a gaze direction in uv coordinates of the image, with a radius as a
+    /// fraction of the larger side of the image.
+    pub fn from_gaze(gaze_uv: glam::Vec2, img_size: glam::UVec2, radius: f32) -> Self {
+        let center =
+            (gaze_uv.clamp(glam::Vec2::ZERO, glam::Vec2::ONE) * img_size.as_vec2()).as_uvec2();
+        Self {
+            center,
+            radius: (radius * img_size.max_element() as f32) as u32,
+        }
+    }
+}
+
+/// Options for how splats are rendered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub mode: RenderMode,
+    pub depth_key: DepthKey,
+    /// Blend splats with weighted blended order independent transparency instead of
+    /// depth sorting them. This skips the depth sort, which scales much better to scenes with
+    /// tens of millions of splats, at the cost of some blending errors where splats overlap
+    /// in depth. Not differentiable.
+    pub order_independent: bool,
+    /// Only use the view independent base color of each splat, skipping the evaluation of the
+    /// higher SH bands. Useful to render faster while interacting, or on low power devices.
+    /// Not differentiable.
+    pub view_independent: bool,
+    /// Accumulate how much each splat contributes to the image, see
+    /// [`RenderAux::contributions`]. Not supported with order independent blending.
+    pub contributions: bool,
+    /// Render at full resolution only around where the eye looks, and in coarser blocks of
+    /// pixels further away, eg. for each eye of a headset. Most of the image is away from the
+    /// fovea, so this saves much of the rasterization. Only for inference renders.
+    pub foveation: Option<Foveation>,
+    /// The color space to convert the rendered colors to. Only for inference renders.
+    pub color_space: ColorSpace,
+}
+
+impl RenderOptions {
+    /// Check that the gradients of renders with these options can be taken. Order and view
+    /// independent renders, and the render modes other than color, are only for viewing.
+    pub fn check_differentiable(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.order_independent,
+            "Order independent rendering is not differentiable"
+        );
+        anyhow::ensure!(
+            !self.view_independent,
+            "View independent rendering is not differentiable"
+        );
+        anyhow::ensure!(
+            self.mode == RenderMode::Color,
+            "Only the color render mode is differentiable, not {:?}",
+            self.mode
+        );
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RenderStats {
     pub num_visible: u32,
     pub num_intersections: u32,
 }
 
+/// Number of elements in the camera gradient, see [`Backend::render_splats`].
+pub const CAMERA_GRAD_SIZE: usize = shaders::project_backwards::CAMERA_GRAD_SIZE as usize;
+
 const INTERSECTS_UPPER_BOUND: u32 = shaders::map_gaussian_to_intersects::WORKGROUP_SIZE[0] * 65535;
 const GAUSSIANS_UPPER_BOUND: u32 = 256 * 65535;
 
@@ -244,6 +389,7 @@ pub struct SplatGrads<B: Backend> {
     v_coeffs: FloatTensor<B>,
     v_raw_opac: FloatTensor<B>,
     v_xy: FloatTensor<B>,
+    v_camera: FloatTensor<B>,
 }
 
 #[derive(Debug, Clone)]
@@ -263,6 +409,7 @@ pub struct GaussianBackwardState<B: Backend> {
     final_index: IntTensor<B>,
 
     sh_degree: u32,
+    camera_grad: bool,
     rx: Receiver<BwdAux>,
 }
 
@@ -274,20 +421,49 @@ pub trait Backend: burn::tensor::backend::Backend {
     /// differentiable way.
     /// The arguments are all passed as raw tensors. See [`Splats`] for a convenient Module that wraps this fun
     /// The [`xy_grad_dummy`] variable is only used to carry screenspace xy gradients.
+    /// Similarly, [`camera_grad_dummy`] only carries the gradient of the camera, as
+    /// `[fx, fy, cx, cy, start translation (3), end translation (3), start rotation (3), end rotation (3)]`.
+    /// The focal length and principal point are in pixels, the translations are those of the view
+    /// matrix at the start and end of the exposure, see [`camera::RollingShutter`]. The rotation
+    /// gradients are those of a small rotation `w` applied to the view rotation, as `exp([w]x) * R`.
     /// This function can optionally render a "u32" buffer, which is a packed RGBA (8 bits per channel)
     /// buffer. This is useful when the results need to be displayed immediatly.
+    /// The [`RenderOptions`] select how splat colors are shaded and how splats are depth sorted.
     fn render_splats(
         camera: &Camera,
         img_size: glam::UVec2,
         means: FloatTensor<Self>,
         xy_grad_dummy: FloatTensor<Self>,
+        camera_grad_dummy: FloatTensor<Self>,
         log_scales: FloatTensor<Self>,
         quats: FloatTensor<Self>,
         sh_coeffs: FloatTensor<Self>,
         raw_opacity: FloatTensor<Self>,
         render_u32_buffer: bool,
+        options: RenderOptions,
     ) -> (FloatTensor<Self>, RenderAuxPrimitive<Self>);
 
+    /// Render splats to a buffer, without support for gradients.
+    ///
+    /// Unlike `render_splats` this doesn't record anything for the backward pass, or any of the
+    /// statistics used while training, which makes it cheaper for viewing and exporting renders.
+    ///
+    /// With a `depth_buffer`, a `[h, w]` tensor of view space depths, splats are depth tested
+    /// against it: splats behind the depth of a pixel don't cover it. This composites splats
+    /// into a scene rendered by another renderer.
+    fn render_splats_inference(
+        camera: &Camera,
+        img_size: glam::UVec2,
+        means: FloatTensor<Self>,
+        log_scales: FloatTensor<Self>,
+        quats: FloatTensor<Self>,
+        sh_coeffs: FloatTensor<Self>,
+        raw_opacity: FloatTensor<Self>,
+        depth_buffer: Option<FloatTensor<Self>>,
+        render_u32_buffer: bool,
+        options: RenderOptions,
+    ) -> FloatTensor<Self>;
+
     /// Backward pass for `render_splats`.
     ///
     /// Do not use directly, `render_splats` will use this to calculate gradients.
@@ -300,6 +476,8 @@ pub trait Backend: burn::tensor::backend::Backend {
     }
 }
 
+/// A [`Backend`] that can also render with gradients, for training.
+#[cfg(feature = "autodiff")]
 pub trait AutodiffBackend:
     Backend + burn::tensor::backend::AutodiffBackend<InnerBackend: Backend>
 {
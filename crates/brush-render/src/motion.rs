@@ -0,0 +1,108 @@
+//! Render screen space motion vectors between consecutive frames.
+//!
+//! The motion vector of a pixel points to where the splats covering it were in the previous
+//! frame, in pixels. It combines the motion of the camera and, for animated scenes, the
+//! motion of the splats themselves. As for [`crate::depth`], every splat is colored by its
+//! motion, so the blended color of a pixel divided by its alpha is the alpha weighted motion
+//! of the splats covering it.
+//!
+//! Motion vectors let post effects follow the image between frames, eg. temporal
+//! anti-aliasing, upscalers like DLSS or FSR, or motion blur.
+use burn::tensor::Tensor;
+use glam::UVec2;
+
+use crate::{camera::Camera, gaussian_splats::Splats, render::SH_C0, Backend, RenderOptions};
+
+// Pixels less covered than this have no meaningful motion.
+const MIN_ALPHA: f32 = 1e-3;
+
+// Points closer than this to the camera plane don't have a meaningful pixel position.
+const MIN_DEPTH: f32 = 1e-4;
+
+// Pixel coordinates of `[n, 3]` points seen by `camera`, as `[n, 1]` x and y, and whether each
+// point is in front of the camera.
+fn project<B: Backend>(
+    points: Tensor<B, 2>,
+    camera: &Camera,
+    img_size: UVec2,
+) -> (Tensor<B, 2>, Tensor<B, 2>, Tensor<B, 2>) {
+    let device = points.device();
+    let view = camera.world_to_local();
+    let local = |row: usize| {
+        let r = view.row(row);
+        let axis = Tensor::<B, 2>::from_floats([[r.x], [r.y], [r.z]], &device);
+        points.clone().matmul(axis) + r.w
+    };
+    let (x, y, z) = (local(0), local(1), local(2));
+
+    let focal = camera.focal(img_size);
+    let center = camera.center(img_size);
+    let in_front = z.clone().greater_elem(MIN_DEPTH).float();
+    let z = z.clamp_min(MIN_DEPTH);
+    (
+        x / z.clone() * focal.x + center.x,
+        y / z * focal.y + center.y,
+        in_front,
+    )
+}
+
+/// Motion vector of every splat as `[n, 2]`, from where it is seen by `camera` to where it
+/// was seen by `prev_camera`, in pixels of an image of `img_size`.
+///
+/// `prev_means` are the positions of the splats in the previous frame of an animated scene,
+/// which has the same splats in every frame. Without them the splats are static. Splats
+/// behind either camera don't move.
+pub fn splat_motion<B: Backend>(
+    splats: &Splats<B>,
+    prev_means: Option<Tensor<B, 2>>,
+    camera: &Camera,
+    prev_camera: &Camera,
+    img_size: UVec2,
+) -> Tensor<B, 2> {
+    let means = splats.means.val();
+    let prev_means = prev_means.unwrap_or_else(|| means.clone());
+    let (x, y, in_front) = project(means, camera, img_size);
+    let (prev_x, prev_y, prev_in_front) = project(prev_means, prev_camera, img_size);
+    let valid = in_front * prev_in_front;
+    Tensor::cat(vec![prev_x - x, prev_y - y], 1) * valid
+}
+
+/// Render the motion vector of every pixel as a `[h, w, 2]` tensor, see the
+/// [module docs](self). Pixels that no splats cover don't move.
+///
+/// `prev_splats` is the previous frame of an animated scene. It's only used when it has the
+/// same number of splats, as the splats of both frames have to match one to one.
+pub fn render_motion_vectors<B: Backend>(
+    splats: &Splats<B>,
+    prev_splats: Option<&Splats<B>>,
+    camera: &Camera,
+    prev_camera: &Camera,
+    img_size: UVec2,
+) -> Tensor<B, 3> {
+    let n = splats.num_splats();
+    let device = splats.means.device();
+
+    let prev_means = prev_splats
+        .filter(|prev| prev.num_splats() == n)
+        .map(|prev| prev.means.val());
+    let motion = splat_motion(splats, prev_means, camera, prev_camera, img_size);
+
+    let rgb = Tensor::cat(vec![motion, Tensor::zeros([n, 1], &device)], 1);
+    // Only a base color, so the color doesn't change with the view direction.
+    let sh_coeffs = ((rgb - 0.5) / SH_C0).reshape([n, 1, 3]);
+    let motion_splats = Splats::from_tensor_data(
+        splats.means.val(),
+        splats.rotation.val(),
+        splats.log_scales.val(),
+        sh_coeffs,
+        splats.raw_opacity.val(),
+    );
+
+    let img = motion_splats.render_inference(camera, img_size, false, RenderOptions::default());
+    let [h, w] = [img_size.y as usize, img_size.x as usize];
+    let weighted = img.clone().slice([0..h, 0..w, 0..2]);
+    let alpha = img.slice([0..h, 0..w, 3..4]);
+
+    let uncovered = alpha.clone().lower_elem(MIN_ALPHA).repeat_dim(2, 2);
+    (weighted / alpha.clamp_min(MIN_ALPHA)).mask_fill(uncovered, 0.0)
+}
@@ -0,0 +1,350 @@
+//! Cast rays against splats on the CPU, eg. to find what's under the cursor or to measure
+//! distances, without rendering a view.
+//!
+//! Splats are put in a bounding volume hierarchy over their 3 sigma bounds, so a ray only
+//! tests the splats close to it.
+use glam::{Mat3, Quat, Vec3};
+
+use crate::{gaussian_splats::Splats, Backend};
+
+// Splats per leaf of the hierarchy.
+const LEAF_SIZE: usize = 8;
+// Splats are bounded at this many standard deviations, like the rasterizer.
+const BOUND_SIGMAS: f32 = 3.0;
+// Hits more transparent than this are skipped, like the rasterizer.
+const MIN_ALPHA: f32 = 1.0 / 255.0;
+
+/// A splat hit by a ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// Index of the splat.
+    pub index: usize,
+    /// Where the ray passes the densest point of the splat, in lengths of the ray direction.
+    pub t: f32,
+    /// Opacity of the splat at that point.
+    pub alpha: f32,
+}
+
+/// The distance along the ray where the hits, blended front to back, become more opaque than
+/// `threshold`, like the depth of a render. `hits` have to be sorted, as returned by
+/// [`SplatBvh::raycast`].
+pub fn surface_depth(hits: &[RayHit], threshold: f32) -> Option<f32> {
+    let mut transmittance = 1.0;
+    for hit in hits {
+        transmittance *= 1.0 - hit.alpha;
+        if 1.0 - transmittance >= threshold {
+            return Some(hit.t);
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone)]
+struct SplatShape {
+    mean: Vec3,
+    // Maps world space to the space where the splat is a unit sphere.
+    to_unit: Mat3,
+    opacity: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    min: Vec3,
+    max: Vec3,
+    // The splats under this node are order[start..start + count].
+    start: u32,
+    count: u32,
+    // The two children are at nodes[children] and nodes[children + 1], leaves have none.
+    children: u32,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.children == 0
+    }
+
+    fn range(&self) -> std::ops::Range<usize> {
+        self.start as usize..(self.start + self.count) as usize
+    }
+}
+
+/// Bounding volume hierarchy over splats, to cast many rays against the same splats.
+#[derive(Debug, Clone)]
+pub struct SplatBvh {
+    nodes: Vec<Node>,
+    order: Vec<u32>,
+    shapes: Vec<SplatShape>,
+}
+
+impl SplatBvh {
+    /// Build the hierarchy from the means, normalized rotations, scales and opacities of the
+    /// splats.
+    pub fn new(means: &[Vec3], rotations: &[Quat], scales: &[Vec3], opacities: &[f32]) -> Self {
+        let mut shapes = Vec::with_capacity(means.len());
+        let mut bounds = Vec::with_capacity(means.len());
+        for (((&mean, &rotation), &scale), &opacity) in
+            means.iter().zip(rotations).zip(scales).zip(opacities)
+        {
+            let rot = Mat3::from_quat(rotation);
+            let scale = scale.max(Vec3::splat(1e-12));
+            let axes = rot * Mat3::from_diagonal(scale);
+            // Extent of the ellipsoid along each world axis.
+            let extent = Vec3::new(
+                axes.row(0).length(),
+                axes.row(1).length(),
+                axes.row(2).length(),
+            ) * BOUND_SIGMAS;
+            bounds.push((mean - extent, mean + extent));
+            shapes.push(SplatShape {
+                mean,
+                to_unit: Mat3::from_diagonal(scale.recip()) * rot.transpose(),
+                opacity,
+            });
+        }
+
+        let mut bvh = Self {
+            nodes: vec![],
+            order: (0..shapes.len() as u32).collect(),
+            shapes,
+        };
+        if !bvh.shapes.is_empty() {
+            bvh.nodes.push(Node {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+                start: 0,
+                count: 0,
+                children: 0,
+            });
+            bvh.build(0, 0, bvh.order.len(), &bounds);
+        }
+        bvh
+    }
+
+    /// Read back the splats and build the hierarchy over them.
+    pub async fn from_splats<B: Backend>(splats: &Splats<B>) -> Self {
+        async fn read<B: Backend, const D: usize>(t: burn::tensor::Tensor<B, D>) -> Vec<f32> {
+            t.into_data_async()
+                .await
+                .to_vec()
+                .expect("Failed to read splats")
+        }
+
+        let means = read(splats.means.val()).await;
+        let rotations = read(splats.rotation.val()).await;
+        let scales = read(splats.scales()).await;
+        let opacities = read(splats.opacity()).await;
+
+        let means: Vec<_> = means.chunks_exact(3).map(Vec3::from_slice).collect();
+        let rotations: Vec<_> = rotations
+            .chunks_exact(4)
+            .map(|q| Quat::from_xyzw(q[1], q[2], q[3], q[0]).normalize())
+            .collect();
+        let scales: Vec<_> = scales.chunks_exact(3).map(Vec3::from_slice).collect();
+        Self::new(&means, &rotations, &scales, &opacities)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// The means of the splats, in order.
+    pub fn means(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.shapes.iter().map(|s| s.mean)
+    }
+
+    fn build(&mut self, node: usize, start: usize, end: usize, bounds: &[(Vec3, Vec3)]) {
+        let ids = &mut self.order[start..end];
+        let (min, max) = ids.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), &i| {
+                let (lo, hi) = bounds[i as usize];
+                (min.min(lo), max.max(hi))
+            },
+        );
+        self.nodes[node] = Node {
+            min,
+            max,
+            start: start as u32,
+            count: ids.len() as u32,
+            children: 0,
+        };
+
+        if ids.len() <= LEAF_SIZE {
+            return;
+        }
+
+        // Split at the median of the means along the widest axis.
+        let size = max - min;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        let mid = ids.len() / 2;
+        let shapes = &self.shapes;
+        ids.select_nth_unstable_by(mid, |&a, &b| {
+            shapes[a as usize].mean[axis].total_cmp(&shapes[b as usize].mean[axis])
+        });
+
+        let children = self.nodes.len();
+        let empty = self.nodes[node];
+        self.nodes.push(empty);
+        self.nodes.push(empty);
+        self.nodes[node].children = children as u32;
+        self.build(children, start, start + mid, bounds);
+        self.build(children + 1, start + mid, end, bounds);
+    }
+
+    /// All splats along the ray, sorted front to back. Only the part of the ray in front of
+    /// the origin counts.
+    pub fn raycast(&self, origin: Vec3, dir: Vec3) -> Vec<RayHit> {
+        let mut hits = vec![];
+        if self.nodes.is_empty() {
+            return hits;
+        }
+
+        let inv_dir = dir.recip();
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node: Node = self.nodes[node];
+
+            // Slab test against the bounds of the node.
+            let t0 = (node.min - origin) * inv_dir;
+            let t1 = (node.max - origin) * inv_dir;
+            let t_near = t0.min(t1).max_element();
+            let t_far = t0.max(t1).min_element();
+            if t_far < t_near.max(0.0) {
+                continue;
+            }
+
+            if !node.is_leaf() {
+                stack.push(node.children as usize);
+                stack.push(node.children as usize + 1);
+                continue;
+            }
+
+            for &index in &self.order[node.range()] {
+                let shape = &self.shapes[index as usize];
+                let o = shape.to_unit * (origin - shape.mean);
+                let d = shape.to_unit * dir;
+                // The densest point along the ray is closest to the center in unit space.
+                let t = -o.dot(d) / d.length_squared().max(1e-12);
+                if t < 0.0 {
+                    continue;
+                }
+                let alpha = shape.opacity * (-0.5 * (o + d * t).length_squared()).exp();
+                if alpha >= MIN_ALPHA {
+                    hits.push(RayHit {
+                        index: index as usize,
+                        t,
+                        alpha,
+                    });
+                }
+            }
+        }
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        hits
+    }
+
+    /// Split the splats into spatially compact groups of at most `max_count` splats.
+    pub fn clusters(&self, max_count: usize) -> Vec<Vec<usize>> {
+        let max_count = max_count.max(1);
+        let mut clusters = vec![];
+        if self.nodes.is_empty() {
+            return clusters;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node: Node = self.nodes[node];
+            if node.count as usize > max_count && !node.is_leaf() {
+                // Visit the first child first, to keep neighbouring clusters together.
+                stack.push(node.children as usize + 1);
+                stack.push(node.children as usize);
+                continue;
+            }
+            for group in self.order[node.range()].chunks(max_count) {
+                clusters.push(group.iter().map(|&i| i as usize).collect());
+            }
+        }
+        clusters
+    }
+
+    /// Indices of the splats with their mean inside the box.
+    pub fn query_box(&self, min: Vec3, max: Vec3) -> Vec<usize> {
+        let mut found = vec![];
+        if self.nodes.is_empty() {
+            return found;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node: Node = self.nodes[node];
+            if node.min.cmpgt(max).any() || node.max.cmplt(min).any() {
+                continue;
+            }
+            if !node.is_leaf() {
+                stack.push(node.children as usize);
+                stack.push(node.children as usize + 1);
+                continue;
+            }
+            for &index in &self.order[node.range()] {
+                let mean = self.shapes[index as usize].mean;
+                if mean.cmpge(min).all() && mean.cmple(max).all() {
+                    found.push(index as usize);
+                }
+            }
+        }
+        found
+    }
+}
+
+impl<B: Backend> Splats<B> {
+    /// All splats along the ray from `origin` in direction `dir`, sorted front to back.
+    ///
+    /// This builds a [`SplatBvh`] for every call, use a
+    /// [`crate::spatial_index::SpatialIndex`] to cast many rays.
+    pub async fn raycast(&self, origin: Vec3, dir: Vec3) -> Vec<RayHit> {
+        SplatBvh::from_splats(self).await.raycast(origin, dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use super::{surface_depth, SplatBvh};
+
+    #[test]
+    fn raycast_hits_in_order() {
+        let means: Vec<_> = (0..100).map(|i| Vec3::new(0.0, 0.0, i as f32)).collect();
+        let rotations = vec![Quat::IDENTITY; means.len()];
+        let scales = vec![Vec3::splat(0.1); means.len()];
+        let opacities = vec![0.5; means.len()];
+        let bvh = SplatBvh::new(&means, &rotations, &scales, &opacities);
+
+        let hits = bvh.raycast(Vec3::new(0.0, 0.0, -10.0), Vec3::Z);
+        assert_eq!(hits.len(), 100);
+        assert!(hits.iter().enumerate().all(|(i, hit)| hit.index == i));
+        assert!((hits[3].t - 13.0).abs() < 1e-4);
+        assert!((hits[3].alpha - 0.5).abs() < 1e-4);
+        assert_eq!(surface_depth(&hits, 0.7), Some(11.0));
+
+        // Passes the splats at 10 standard deviations.
+        assert!(bvh.raycast(Vec3::new(1.0, 0.0, -10.0), Vec3::Z).is_empty());
+        // Points away from the splats.
+        assert!(bvh.raycast(Vec3::new(0.0, 0.0, -10.0), -Vec3::Z).is_empty());
+
+        assert_eq!(
+            bvh.query_box(Vec3::new(-1.0, -1.0, 9.5), Vec3::new(1.0, 1.0, 12.5))
+                .len(),
+            3
+        );
+    }
+}
@@ -9,15 +9,17 @@ use crate::{
         GatherGrads, MapGaussiansToIntersect, ProjectBackwards, ProjectSplats, ProjectVisible,
         Rasterize, RasterizeBackwards,
     },
-    RenderAuxPrimitive, SplatGrads, INTERSECTS_UPPER_BOUND,
+    ColorSpace, DepthKey, RenderAuxPrimitive, RenderMode, RenderOptions, SplatGrads,
+    CAMERA_GRAD_SIZE, INTERSECTS_UPPER_BOUND,
 };
 
 use brush_kernel::create_dispatch_buffer;
 use brush_kernel::create_tensor;
 use brush_kernel::create_uniform_buffer;
+use brush_kernel::pool::{self, create_pooled_tensor};
 use brush_kernel::{calc_cube_count, CubeCount};
 use brush_prefix_sum::prefix_sum;
-use brush_sort::radix_argsort;
+use brush_sort::{cpu_argsort, radix_argsort};
 use burn::tensor::ops::IntTensorOps;
 use burn::tensor::{ops::IntTensor, DType};
 use burn_jit::JitBackend;
@@ -31,6 +33,25 @@ type InnerWgpu = JitBackend<WgpuRuntime, f32, i32, u32>;
 
 pub const SH_C0: f32 = shaders::gather_grads::SH_C0;
 
+// Kernels of a forward render besides the sorts and prefix sums. Projecting, mapping
+// intersections and rasterizing take 4, zeroing buffers 5, reading back the visible and
+// intersection counts 2 each, sizing the dispatches of the visible splats and intersections 2,
+// and normalizing the contributions 2.
+const FORWARD_DISPATCHES: usize = 4 + 5 + 2 * 2 + 2 + 2;
+
+/// Upper bound on the number of kernel launches of a single forward render: a depth sort and
+/// a tile sort at their maximum number of passes, and two prefix sums.
+///
+/// Compute clients should batch at least this many tasks before submitting, so a frame
+/// is recorded and submitted as one command buffer. Submission overhead otherwise
+/// dominates the frame time at small resolutions.
+pub const MAX_TASKS_PER_RENDER: usize =
+    FORWARD_DISPATCHES + 2 * brush_sort::MAX_DISPATCHES + 2 * brush_prefix_sum::MAX_DISPATCHES;
+
+// Which epsilon the projection kernels use to keep the 2D covariance invertible, see
+// `clamp_covariance` in helpers.wgsl. The relative epsilon is robust to extreme scales.
+const RELATIVE_COV_EPS: bool = true;
+
 pub const fn sh_coeffs_for_degree(degree: u32) -> u32 {
     (degree + 1).pow(2)
 }
@@ -88,15 +109,37 @@ pub(crate) fn render_forward(
     sh_coeffs: JitTensor<WgpuRuntime>,
     raw_opacities: JitTensor<WgpuRuntime>,
     raster_u32: bool,
+    options: RenderOptions,
+    depth_buffer: Option<JitTensor<WgpuRuntime>>,
+    forward_only: bool,
 ) -> (JitTensor<WgpuRuntime>, RenderAuxPrimitive<InnerWgpu>) {
     assert!(
         img_size[0] > 0 && img_size[1] > 0,
         "Can't render 0 sized images"
     );
+    assert!(
+        !options.contributions || (!forward_only && !options.order_independent),
+        "Contributions are only accumulated by sorted, non inference renders"
+    );
+    assert!(
+        depth_buffer.is_none() || forward_only,
+        "Only inference renders can be depth tested"
+    );
+    assert!(
+        options.foveation.is_none() || forward_only,
+        "Only inference renders can be foveated"
+    );
+    assert!(
+        options.color_space == ColorSpace::Srgb || forward_only,
+        "Only inference renders can be converted to another color space"
+    );
 
     let device = &means.device.clone();
     let client = means.client.clone();
 
+    // Scratch buffers from earlier renders can be recycled from here on.
+    pool::next_epoch(device);
+
     // Check whether any work needs to be flushed.
     tracing::trace_span!("pre setup", sync_burn = true).in_scope(|| {});
 
@@ -109,6 +152,13 @@ pub(crate) fn render_forward(
         .check_dims(&quats, &["D".into(), 4.into()])
         .check_dims(&sh_coeffs, &["D".into(), "C".into(), 3.into()])
         .check_dims(&raw_opacities, &["D".into()]);
+    if let Some(depth_buffer) = &depth_buffer {
+        assert_eq!(
+            depth_buffer.shape.dims,
+            [img_size.y as usize, img_size.x as usize],
+            "The depth buffer must be the size of the image"
+        );
+    }
 
     // Divide screen into tiles.
     let tile_bounds = ivec2(
@@ -135,6 +185,7 @@ pub(crate) fn render_forward(
     let uniforms_buffer = create_uniform_buffer(
         shaders::helpers::RenderUniforms {
             viewmat: camera.world_to_local().to_cols_array_2d(),
+            viewmat_end: camera.world_to_local_end().to_cols_array_2d(),
             camera_position: [camera.position.x, camera.position.y, camera.position.z, 0.0],
             focal: camera.focal(img_size).into(),
             pixel_center: camera.center(img_size).into(),
@@ -154,28 +205,46 @@ pub(crate) fn render_forward(
     let num_points = means.shape.dims[0];
     let client = &means.client.clone();
 
-    let radii = InnerWgpu::float_zeros([num_points].into(), device);
+    // Forward only renders don't gather any statistics, and the buffers are left empty.
+    let radii = if forward_only {
+        create_tensor([1], device, client, DType::F32)
+    } else {
+        InnerWgpu::float_zeros([num_points].into(), device)
+    };
 
-    let (global_from_compact_gid, num_visible) = {
+    let (global_from_compact_gid, num_visible, depths) = {
         let global_from_presort_gid = InnerWgpu::int_zeros([num_points].into(), device);
         let depths = create_tensor([num_points], device, client, DType::F32);
 
+        let mut bindings = vec![
+            uniforms_buffer.clone().handle.binding(),
+            means.clone().handle.binding(),
+            quats.clone().handle.binding(),
+            log_scales.clone().handle.binding(),
+            raw_opacities.clone().handle.binding(),
+            global_from_presort_gid.clone().handle.binding(),
+            depths.clone().handle.binding(),
+        ];
+        if !forward_only {
+            bindings.push(radii.clone().handle.binding());
+        }
+
         tracing::trace_span!("ProjectSplats", sync_burn = true).in_scope(||
             // SAFETY: wgsl FFI, kernel checked to have no OOB.
             unsafe {
             client.execute_unchecked(
-                ProjectSplats::task(),
+                ProjectSplats::task(
+                    options.depth_key != DepthKey::Depth,
+                    // Order independent blending and depth testing read back the depth as
+                    // a float.
+                    options.depth_key == DepthKey::LogDistance
+                        && !options.order_independent
+                        && depth_buffer.is_none(),
+                    RELATIVE_COV_EPS,
+                    forward_only,
+                ),
                 calc_cube_count([num_points as u32], ProjectSplats::WORKGROUP_SIZE),
-                vec![
-                    uniforms_buffer.clone().handle.binding(),
-                    means.clone().handle.binding(),
-                    quats.clone().handle.binding(),
-                    log_scales.clone().handle.binding(),
-                    raw_opacities.clone().handle.binding(),
-                    global_from_presort_gid.clone().handle.binding(),
-                    depths.clone().handle.binding(),
-                    radii.clone().handle.binding(),
-                ],
+                bindings,
             );
         });
 
@@ -186,14 +255,27 @@ pub(crate) fn render_forward(
             &[num_vis_field_offset..num_vis_field_offset + 1],
         ));
 
-        let (_, global_from_compact_gid) = tracing::trace_span!("DepthSort", sync_burn = true)
-            .in_scope(|| {
-                // Interpret the depth as a u32. This is fine for a radix sort, as long as the depth > 0.0,
-                // which we know to be the case given how we cull splats.
-                radix_argsort(depths, global_from_presort_gid, &num_visible, 32)
-            });
-
-        (global_from_compact_gid, num_visible)
+        if options.order_independent {
+            // Order independent blending doesn't need the splats to be depth sorted, the
+            // rasterizer instead needs the depth of each splat.
+            (global_from_presort_gid, num_visible, Some(depths))
+        } else {
+            let (depths, global_from_compact_gid) =
+                tracing::trace_span!("DepthSort", sync_burn = true).in_scope(|| {
+                    // The depths are written as u32 keys, see project_forward.
+                    let sorting_bits =
+                        if options.depth_key == DepthKey::LogDistance && depth_buffer.is_none() {
+                            shaders::project_forward::LOG_DEPTH_BITS
+                        } else {
+                            32
+                        };
+                    argsort(depths, global_from_presort_gid, &num_visible, sorting_bits)
+                });
+
+            // The depth test compares the sorted depths to the depth buffer.
+            let depths = depth_buffer.is_some().then_some(depths);
+            (global_from_compact_gid, num_visible, depths)
+        }
     };
 
     let projected_size = size_of::<shaders::helpers::ProjectedSplat>() / size_of::<f32>();
@@ -205,14 +287,24 @@ pub(crate) fn render_forward(
     let max_intersects = max_intersections(img_size, num_points as u32);
     // 1 extra length to make this an exclusive sum.
     let tiles_hit_per_splat = InnerWgpu::int_zeros([num_points + 1].into(), device);
-    let isect_info =
-        create_tensor::<2, WgpuRuntime>([max_intersects as usize, 2], device, client, DType::I32);
+    let isect_info = create_pooled_tensor::<2, WgpuRuntime>(
+        [max_intersects as usize, 2],
+        device,
+        client,
+        DType::I32,
+    );
 
     tracing::trace_span!("ProjectVisibile", sync_burn = true).in_scope(|| 
         // SAFETY: Kernel has to contain no OOB indexing.
         unsafe {
         client.execute_unchecked(
-            ProjectVisible::task(),
+            ProjectVisible::task(
+                options.mode == RenderMode::Shaded,
+                options.view_independent,
+                options.mode == RenderMode::Points,
+                options.mode == RenderMode::Ellipsoids,
+                RELATIVE_COV_EPS,
+            ),
             CubeCount::Dynamic(num_vis_wg.clone().handle.binding()),
             vec![
                 uniforms_buffer.clone().handle.binding(),
@@ -246,9 +338,9 @@ pub(crate) fn render_forward(
         let num_tiles = tile_bounds.x * tile_bounds.y;
 
         let tile_id_from_isect =
-            create_tensor::<1, _>([max_intersects as usize], device, client, DType::I32);
+            create_pooled_tensor::<1, _>([max_intersects as usize], device, client, DType::I32);
         let compact_gid_from_isect =
-            create_tensor::<1, _>([max_intersects as usize], device, client, DType::I32);
+            create_pooled_tensor::<1, _>([max_intersects as usize], device, client, DType::I32);
 
         let tile_counts = InnerWgpu::int_zeros(
             [(tile_bounds.y * tile_bounds.x) as usize + 1].into(),
@@ -283,7 +375,7 @@ pub(crate) fn render_forward(
 
         let (_, compact_gid_from_isect) = tracing::trace_span!("Tile sort", sync_burn = true)
             .in_scope(|| {
-                radix_argsort(
+                argsort(
                     tile_id_from_isect,
                     compact_gid_from_isect,
                     &num_intersections,
@@ -315,34 +407,84 @@ pub(crate) fn render_forward(
         DType::F32,
     );
 
-    // Only record the final visible splat per tile if we're not rendering a u32 buffer.
-    // If we're renering a u32 buffer, we can't autodiff anyway, and final index is only needed for
-    // the backward pass.
+    // Record the final visible splat per tile. This is only needed for the backward pass,
+    // forward only renders skip it.
+    let final_index_size = if forward_only {
+        [1, 1]
+    } else {
+        [img_size.y as usize, img_size.x as usize]
+    };
+    let final_index = create_tensor::<2, _>(final_index_size, device, client, DType::I32);
+
+    let mut bindings = vec![
+        uniforms_buffer.clone().handle.binding(),
+        compact_gid_from_isect.handle.clone().binding(),
+        tile_offsets.handle.clone().binding(),
+        projected_splats.handle.clone().binding(),
+        out_img.handle.clone().binding(),
+    ];
+    if let Some(depths) = &depths {
+        bindings.push(depths.handle.clone().binding());
+    }
+    if let Some(depth_buffer) = &depth_buffer {
+        bindings.push(depth_buffer.handle.clone().binding());
+    }
+    if let Some(foveation) = options.foveation {
+        let fovea = create_uniform_buffer(
+            [
+                foveation.center.x as i32,
+                foveation.center.y as i32,
+                foveation.radius as i32,
+                0,
+            ],
+            device,
+            client,
+        );
+        bindings.push(fovea.handle.binding());
+    }
+    if !forward_only {
+        bindings.push(final_index.handle.clone().binding());
+    }
 
-    // Record the final visible splat per tile.
-    let final_index = create_tensor::<2, _>(
-        [img_size.y as usize, img_size.x as usize],
-        device,
-        client,
-        DType::I32,
-    );
+    // Blend weights of each splat, accumulated as fixed point integers by the rasterizer.
+    let contributions = if options.contributions {
+        let contributions = InnerWgpu::int_zeros([num_points].into(), device);
+        bindings.push(global_from_compact_gid.handle.clone().binding());
+        bindings.push(contributions.handle.clone().binding());
+        Some(contributions)
+    } else {
+        None
+    };
 
     // SAFETY: Kernel has to contain no OOB indexing.
     unsafe {
         client.execute_unchecked(
-            Rasterize::task(raster_u32),
+            Rasterize::task(
+                raster_u32,
+                options.order_independent,
+                forward_only,
+                options.mode == RenderMode::Points,
+                options.mode == RenderMode::Ellipsoids,
+                contributions.is_some(),
+                depth_buffer.is_some(),
+                options.depth_key != DepthKey::Depth,
+                options.foveation.is_some(),
+                options.color_space == ColorSpace::DisplayP3,
+                options.color_space == ColorSpace::Hdr10,
+            ),
             calc_cube_count([img_size.x, img_size.y], Rasterize::WORKGROUP_SIZE),
-            vec![
-                uniforms_buffer.clone().handle.binding(),
-                compact_gid_from_isect.handle.clone().binding(),
-                tile_offsets.handle.clone().binding(),
-                projected_splats.handle.clone().binding(),
-                out_img.handle.clone().binding(),
-                final_index.handle.clone().binding(),
-            ],
+            bindings,
         );
     }
 
+    let contributions = match contributions {
+        Some(contributions) => InnerWgpu::float_div_scalar(
+            InnerWgpu::int_into_float(contributions),
+            shaders::rasterize::CONTRIBUTION_SCALE as f32,
+        ),
+        None => create_tensor([1], device, client, DType::F32),
+    };
+
     (
         out_img,
         RenderAuxPrimitive {
@@ -355,6 +497,7 @@ pub(crate) fn render_forward(
             compact_gid_from_isect,
             global_from_compact_gid,
             radii,
+            contributions,
             sender: None,
         },
     )
@@ -375,6 +518,71 @@ pub fn has_hard_floats() -> bool {
     HARD_FLOATS_AVAILABLE.load(Ordering::SeqCst)
 }
 
+// Optional adapter capabilities of the kernels. These are assumed to be available until
+// `set_adapter_capabilities` says otherwise, kernels then switch to slower variants that
+// older (integrated) GPUs can still compile.
+static SUBGROUPS_AVAILABLE: AtomicBool = AtomicBool::new(true);
+static LARGE_WG_MEM_AVAILABLE: AtomicBool = AtomicBool::new(true);
+static GPU_SORT_AVAILABLE: AtomicBool = AtomicBool::new(true);
+
+/// Workgroup memory needed by the default variant of the backwards rasterizer, in bytes:
+/// batches of splats and queued gradients, with their ids and the queue counter.
+pub const RASTERIZE_BACKWARDS_WG_MEM: u32 =
+    shaders::helpers::TILE_SIZE * 2 * (size_of::<shaders::helpers::ProjectedSplat>() as u32 + 4)
+        + 4;
+
+/// Select the kernel variants to use for an adapter. Call this once at startup, before
+/// rendering anything.
+///
+/// - `subgroups`: whether subgroup operations are supported. Without them gradients are
+///   queued per thread, which is correct but slower.
+/// - `max_wg_memory`: the maximum workgroup storage size. Below
+///   [`RASTERIZE_BACKWARDS_WG_MEM`] the backwards pass gathers smaller batches.
+/// - `gpu_sort`: whether to sort on the GPU. Otherwise splats are sorted on the CPU, which
+///   stalls every render but works where the sorting kernels don't, eg. on the GL backend.
+pub fn set_adapter_capabilities(subgroups: bool, max_wg_memory: u32, gpu_sort: bool) {
+    let large_wg_mem = max_wg_memory >= RASTERIZE_BACKWARDS_WG_MEM;
+    if !subgroups {
+        log::warn!("Adapter doesn't support subgroups, using slower fallback kernels.");
+    }
+    if !large_wg_mem {
+        log::warn!(
+            "Adapter only has {max_wg_memory} bytes of workgroup memory, using smaller batches."
+        );
+    }
+    if !gpu_sort {
+        log::warn!("Sorting splats on the CPU, rendering will be slow.");
+    }
+    SUBGROUPS_AVAILABLE.store(subgroups, Ordering::SeqCst);
+    GPU_SORT_AVAILABLE.store(gpu_sort, Ordering::SeqCst);
+    LARGE_WG_MEM_AVAILABLE.store(large_wg_mem, Ordering::SeqCst);
+}
+
+pub fn has_subgroups() -> bool {
+    SUBGROUPS_AVAILABLE.load(Ordering::SeqCst)
+}
+
+pub fn has_large_wg_mem() -> bool {
+    LARGE_WG_MEM_AVAILABLE.load(Ordering::SeqCst)
+}
+
+pub fn has_gpu_sort() -> bool {
+    GPU_SORT_AVAILABLE.load(Ordering::SeqCst)
+}
+
+fn argsort(
+    keys: JitTensor<WgpuRuntime>,
+    values: JitTensor<WgpuRuntime>,
+    n_sort: &JitTensor<WgpuRuntime>,
+    sorting_bits: u32,
+) -> (JitTensor<WgpuRuntime>, JitTensor<WgpuRuntime>) {
+    if has_gpu_sort() {
+        radix_argsort(keys, values, n_sort, sorting_bits)
+    } else {
+        cpu_argsort(keys, values, n_sort, sorting_bits)
+    }
+}
+
 pub(crate) fn render_backward(
     v_output: JitTensor<WgpuRuntime>,
 
@@ -393,6 +601,7 @@ pub(crate) fn render_backward(
 
     num_visible: u32,
     sh_degree: u32,
+    camera_grad: bool,
 ) -> SplatGrads<InnerWgpu> {
     let device = &out_img.device;
     let img_dimgs = out_img.shape.dims;
@@ -416,12 +625,14 @@ pub(crate) fn render_backward(
         let v_colors = InnerWgpu::float_zeros([num_visible as usize, 4].into(), device);
 
         let hard_floats = has_hard_floats();
+        let subgroups = has_subgroups();
+        let small_wg_mem = !has_large_wg_mem();
 
         tracing::trace_span!("RasterizeBackwards", sync_burn = true).in_scope(|| 
         // SAFETY: Kernel has to contain no OOB indexing.
         unsafe {
             client.execute_unchecked(
-                RasterizeBackwards::task(hard_floats),
+                RasterizeBackwards::task(hard_floats, subgroups, small_wg_mem),
                 CubeCount::Static(invocations, 1, 1),
                 vec![
                     uniforms_buffer.clone().handle.binding(),
@@ -474,27 +685,48 @@ pub(crate) fn render_backward(
     let v_scales = InnerWgpu::float_zeros([num_points, 3].into(), device);
     let v_quats = InnerWgpu::float_zeros([num_points, 4].into(), device);
 
+    // The per splat camera gradients are only needed when the camera is refined.
+    let v_camera = camera_grad
+        .then(|| InnerWgpu::float_zeros([num_visible as usize, CAMERA_GRAD_SIZE].into(), device));
+
+    let mut bindings = vec![
+        uniforms_buffer.handle.binding(),
+        means.handle.binding(),
+        log_scales.handle.binding(),
+        quats.handle.binding(),
+        global_from_compact_gid.handle.binding(),
+        v_xys_local.handle.clone().binding(),
+        v_conics.handle.binding(),
+        v_means.handle.clone().binding(),
+        v_scales.handle.clone().binding(),
+        v_quats.handle.clone().binding(),
+    ];
+
+    if let Some(v_camera) = &v_camera {
+        bindings.push(v_camera.handle.clone().binding());
+    }
+
     tracing::trace_span!("ProjectBackwards", sync_burn = true).in_scope(|| 
         // SAFETY: Kernel has to contain no OOB indexing.
         unsafe {
         client.execute_unchecked(
-            ProjectBackwards::task(),
+            ProjectBackwards::task(RELATIVE_COV_EPS, camera_grad),
             calc_cube_count([num_points as u32], ProjectBackwards::WORKGROUP_SIZE),
-            vec![
-                uniforms_buffer.handle.binding(),
-                means.handle.binding(),
-                log_scales.handle.binding(),
-                quats.handle.binding(),
-                global_from_compact_gid.handle.binding(),
-                v_xys_local.handle.clone().binding(),
-                v_conics.handle.binding(),
-                v_means.handle.clone().binding(),
-                v_scales.handle.clone().binding(),
-                v_quats.handle.clone().binding(),
-            ],
+            bindings,
         );
     });
 
+    // Reduce the per splat camera gradients to a single gradient. When the camera isn't
+    // refined nothing reads the gradient, so it's left uninitialized.
+    let v_camera = if let Some(v_camera) = v_camera {
+        InnerWgpu::float_reshape(
+            InnerWgpu::float_sum_dim(v_camera, 0),
+            [CAMERA_GRAD_SIZE].into(),
+        )
+    } else {
+        create_tensor([CAMERA_GRAD_SIZE], device, client, DType::F32)
+    };
+
     SplatGrads {
         v_means,
         v_quats,
@@ -502,5 +734,6 @@ pub(crate) fn render_backward(
         v_coeffs,
         v_raw_opac,
         v_xy: v_xys_local,
+        v_camera,
     }
 }
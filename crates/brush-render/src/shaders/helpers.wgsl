@@ -7,6 +7,9 @@ const MAIN_WG: u32 = 256u;
 struct RenderUniforms {
     // View matrix transform world to view position.
     viewmat: mat4x4f,
+    // View matrix when the last image row is exposed. Equal to viewmat
+    // for global shutter cameras.
+    viewmat_end: mat4x4f,
     // Position of camera (xyz + pad)
     camera_position: vec4f,
     // Focal of camera (fx, fy)
@@ -118,6 +121,33 @@ fn scale_to_mat(scale: vec3f) -> mat3x3f {
     );
 }
 
+// Unsigned normal of a gaussian, taken as its shortest axis.
+fn normal_from_scale_rot(scale: vec3f, quat: vec4f) -> vec3f {
+    let R = quat_to_mat(quat);
+    if scale.x <= scale.y && scale.x <= scale.z {
+        return R[0];
+    } else if scale.y <= scale.z {
+        return R[1];
+    }
+    return R[2];
+}
+
+// Rolling shutter cameras expose rows one after another. Get the fraction of the
+// exposure at which a point is captured, approximated by the row it projects to at the start pose.
+fn shutter_time(mean: vec3f, viewmat: mat4x4f, focal: vec2f, pixel_center: vec2f, img_size: vec2i) -> f32 {
+    let R = mat3x3f(viewmat[0].xyz, viewmat[1].xyz, viewmat[2].xyz);
+    let mean_c = R * mean + viewmat[3].xyz;
+    // Points behind or on the camera plane are culled, but keep their time finite.
+    let row = focal.y * mean_c.y / max(mean_c.z, 0.01) + pixel_center.y;
+    return clamp(row / f32(img_size.y), 0.0, 1.0);
+}
+
+// Interpolate between the start and end view matrix. Linear interpolation is fine
+// for the small motions during one exposure.
+fn shutter_viewmat(viewmat: mat4x4f, viewmat_end: mat4x4f, t: f32) -> mat4x4f {
+    return viewmat + (viewmat_end - viewmat) * t;
+}
+
 fn calc_cov3d(scale: vec3f, quat: vec4f) -> mat3x3f {
     let M = quat_to_mat(quat) * scale_to_mat(scale);
     return M * transpose(M);
@@ -152,21 +182,46 @@ fn calc_cov2d(cov3d: mat3x3f, mean_c: vec3f, focal: vec2f, img_size: vec2i, pixe
 
     let cov2d = J * covar_cam * transpose(J);
 
-    // add a little blur along axes and save upper triangular elements
-    let c00 = cov2d[0][0] + COV_BLUR;
-    let c11 = cov2d[1][1] + COV_BLUR;
-    let c01 = cov2d[0][1];
+    // add a little blur along axes and save upper triangular elements.
+    // The variances are clamped so products of them can't overflow for huge splats.
+    let c00 = clamp(cov2d[0][0], 0.0, MAX_COV2D) + COV_BLUR;
+    let c11 = clamp(cov2d[1][1], 0.0, MAX_COV2D) + COV_BLUR;
+    let c01 = clamp_covariance(c00, cov2d[0][1], c11);
     return vec3f(c00, c01, c11);
 }
 
 fn inverse_symmetric(mat: vec3f) -> vec3f {
-    let det = mat.x * mat.z - mat.y * mat.y;
+    // The determinant is kept positive by calc_cov2d, but guard against dividing by zero.
+    let det = max(mat.x * mat.z - mat.y * mat.y, 1e-30);
     let inv_det = 1.0 / det;
     return vec3f(mat.z, -mat.y, mat.x) * inv_det;
 }
 
 const COV_BLUR: f32 = 0.3;
 
+// Largest 2D variance, in pixels squared. Splats this big cover the whole image anyway.
+const MAX_COV2D: f32 = 1e12;
+
+#ifdef RELATIVE_EPS
+// Smallest determinant relative to the product of the variances. This bounds the
+// condition number of the covariance, and is accurate no matter the size of the splat.
+const COV_DET_EPS: f32 = 1e-4;
+
+fn clamp_covariance(c00: f32, c01: f32, c11: f32) -> f32 {
+    let max_c01 = sqrt((1.0 - COV_DET_EPS) * c00 * c11);
+    return clamp(c01, -max_c01, max_c01);
+}
+#else
+// Smallest absolute determinant, in pixels^4. Cheap, but for large splats this is below
+// the rounding error of the determinant and it can still end up negative.
+const COV_DET_EPS: f32 = 1e-6;
+
+fn clamp_covariance(c00: f32, c01: f32, c11: f32) -> f32 {
+    let max_c01 = sqrt(max(c00 * c11 - COV_DET_EPS, 0.0));
+    return clamp(c01, -max_c01, max_c01);
+}
+#endif
+
 fn cov_compensation(cov2d: vec3f) -> f32 {
     let cov_orig = cov2d - vec3f(COV_BLUR, 0.0, COV_BLUR);
     let det_orig = cov_orig.x * cov_orig.z - cov_orig.y * cov_orig.y;
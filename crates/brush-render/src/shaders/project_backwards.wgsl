@@ -15,7 +15,50 @@
 @group(0) @binding(7) var<storage, read_write> v_means: array<helpers::PackedVec3>;
 @group(0) @binding(8) var<storage, read_write> v_scales: array<helpers::PackedVec3>;
 @group(0) @binding(9) var<storage, read_write> v_quats: array<vec4f>;
+const CAMERA_GRAD_SIZE: i32 = 16;
+
+#ifdef CAMERA_GRAD
+    // Per visible splat gradient of the camera, see `write_camera_grad`. Only computed when
+    // the camera is refined.
+    @group(0) @binding(10) var<storage, read_write> v_camera: array<f32>;
+
+    // Layout: [focal.x, focal.y, pixel_center.x, pixel_center.y, start translation (3), end translation (3),
+    // start rotation (3), end rotation (3)].
+    fn write_camera_grad(
+        compact_gid: i32,
+        v_focal: vec2f,
+        v_pixel_center: vec2f,
+        v_trans_start: vec3f,
+        v_trans_end: vec3f,
+        v_rot_start: vec3f,
+        v_rot_end: vec3f,
+    ) {
+        let base = compact_gid * CAMERA_GRAD_SIZE;
+        v_camera[base + 0] = v_focal.x;
+        v_camera[base + 1] = v_focal.y;
+        v_camera[base + 2] = v_pixel_center.x;
+        v_camera[base + 3] = v_pixel_center.y;
+        v_camera[base + 4] = v_trans_start.x;
+        v_camera[base + 5] = v_trans_start.y;
+        v_camera[base + 6] = v_trans_start.z;
+        v_camera[base + 7] = v_trans_end.x;
+        v_camera[base + 8] = v_trans_end.y;
+        v_camera[base + 9] = v_trans_end.z;
+        v_camera[base + 10] = v_rot_start.x;
+        v_camera[base + 11] = v_rot_start.y;
+        v_camera[base + 12] = v_rot_start.z;
+        v_camera[base + 13] = v_rot_end.x;
+        v_camera[base + 14] = v_rot_end.y;
+        v_camera[base + 15] = v_rot_end.z;
+    }
 
+    // Gradient of a small rotation w applied to a view rotation R, as R' = exp([w]x) * R,
+    // given the gradient v_R of R.
+    fn rotation_vjp(R: mat3x3f, v_R: mat3x3f) -> vec3f {
+        let A = v_R * transpose(R);
+        return vec3f(A[1][2] - A[2][1], A[2][0] - A[0][2], A[0][1] - A[1][0]);
+    }
+#endif
 
 fn normalize_vjp(quat: vec4f) -> mat4x4f {
     let quat_sqr = quat * quat;
@@ -156,13 +199,16 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
         return;
     }
 
-    let viewmat = uniforms.viewmat;
     let focal = uniforms.focal;
     let img_size = uniforms.img_size;
     let pixel_center = uniforms.pixel_center;
 
     let global_gid = global_from_compact_gid[compact_gid];
     let mean = helpers::as_vec(means[global_gid]);
+
+    // The shutter time is treated as a constant for the gradients.
+    let shutter_t = helpers::shutter_time(mean, uniforms.viewmat, focal, pixel_center, img_size);
+    let viewmat = helpers::shutter_viewmat(uniforms.viewmat, uniforms.viewmat_end, shutter_t);
     let scale = exp(helpers::as_vec(log_scales[global_gid]));
     let quat_unorm = quats[global_gid];
     let quat = normalize(quat_unorm);
@@ -194,11 +240,39 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     // persp_proj_vjp
     let J = helpers::calc_cam_J(mean_c, focal, img_size, pixel_center);
     let v_mean_c = persp_proj_vjp(J, mean_c, covar_c, focal, pixel_center, img_size, v_covar2d, v_mean2d);
+
     // cov = J * V * Jt; G = df/dcov = v_cov
     // -> df/dV = Jt * G * J
     // -> df/dJ = G * J * Vt + Gt * J * V
     let v_covar_c = transpose(J) * v_covar2d * J;
 
+#ifdef CAMERA_GRAD
+        // Intrinsics gradient. mean2d = focal * mean_c.xy / z + pixel_center, and the rows
+        // of J are linear in focal. The dependence of the frustum clamping on the intrinsics is ignored.
+        let v_J = v_covar2d * J * transpose(covar_c) + transpose(v_covar2d) * J * covar_c;
+        let v_focal = v_mean2d * mean_c.xy * rz + vec2f(
+            v_J[0][0] * J[0][0] + v_J[2][0] * J[2][0],
+            v_J[1][1] * J[1][1] + v_J[2][1] * J[2][1],
+        ) / focal;
+
+        // mean_c = R * mean + t, and covar_c = R * covar * Rt. The view matrix is interpolated
+        // between the start and end pose over the exposure.
+        let v_R = mat3x3f(v_mean_c * mean.x, v_mean_c * mean.y, v_mean_c * mean.z) +
+                  v_covar_c * R * transpose(covar) + transpose(v_covar_c) * R * covar;
+        let R_start = mat3x3f(uniforms.viewmat[0].xyz, uniforms.viewmat[1].xyz, uniforms.viewmat[2].xyz);
+        let R_end = mat3x3f(uniforms.viewmat_end[0].xyz, uniforms.viewmat_end[1].xyz, uniforms.viewmat_end[2].xyz);
+
+        write_camera_grad(
+            compact_gid,
+            v_focal,
+            v_mean2d,
+            v_mean_c * (1.0 - shutter_t),
+            v_mean_c * shutter_t,
+            rotation_vjp(R_start, v_R * (1.0 - shutter_t)),
+            rotation_vjp(R_end, v_R * shutter_t),
+        );
+#endif
+
     // df/dx = -fx * rz2 * df/dJ_02
     // df/dy = -fy * rz2 * df/dJ_12
     // df/dz = -fx * rz2 * df/dJ_00 - fy * rz2 * df/dJ_11
@@ -206,15 +280,9 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     // for D = W * X, G = df/dD
     // df/dW = G * XT, df/dX = WT * G
 
-    // TODO: Camera gradient is not done yet.
-    // var v_R = outer_product(v_mean_c, mean);
     let v_mean = transpose(R) * v_mean_c;
 
     // covar_world_to_cam_vjp
-    // TODO: Camera gradient is not done yet.
-    // v_R += v_covar_c * R * transpose(covar) +
-    //        transpose(v_covar_c) * R * covar;
-
     let v_covar = transpose(R) * v_covar_c * R;
 
     // quat_scale_to_covar_vjp
@@ -2,6 +2,12 @@
 
 #import helpers;
 
+// Number of bits of the log depth key.
+const LOG_DEPTH_BITS: u32 = 24u;
+// Splats are only rendered between these view space depths. The log depth key spans them.
+const NEAR_DEPTH: f32 = 0.01;
+const FAR_DEPTH: f32 = 1e10;
+
 // Unfiroms contains the splat count which we're writing to.
 @group(0) @binding(0) var<storage, read_write> uniforms: helpers::RenderUniforms;
 
@@ -11,9 +17,13 @@
 @group(0) @binding(4) var<storage, read> raw_opacities: array<f32>;
 
 @group(0) @binding(5) var<storage, read_write> global_from_compact_gid: array<u32>;
-@group(0) @binding(6) var<storage, read_write> depths: array<f32>;
+// Depth sorting keys.
+@group(0) @binding(6) var<storage, read_write> depths: array<u32>;
 
-@group(0) @binding(7) var<storage, read_write> radii: array<f32>;
+#ifndef FORWARD_ONLY
+    // Screenspace radius of each splat, gathered as a training statistic.
+    @group(0) @binding(7) var<storage, read_write> radii: array<f32>;
+#endif
 
 @compute
 @workgroup_size(helpers::MAIN_WG, 1, 1)
@@ -28,11 +38,12 @@ fn main(@builtin(global_invocation_id) global_id: vec3u) {
     let mean = helpers::as_vec(means[global_gid]);
 
     let img_size = uniforms.img_size;
-    let viewmat = uniforms.viewmat;
+    let shutter_t = helpers::shutter_time(mean, uniforms.viewmat, uniforms.focal, uniforms.pixel_center, img_size);
+    let viewmat = helpers::shutter_viewmat(uniforms.viewmat, uniforms.viewmat_end, shutter_t);
     let R = mat3x3f(viewmat[0].xyz, viewmat[1].xyz, viewmat[2].xyz);
     let mean_c = R * mean + viewmat[3].xyz;
 
-    if mean_c.z < 0.01 || mean_c.z > 1e10 {
+    if mean_c.z < NEAR_DEPTH || mean_c.z > FAR_DEPTH {
         return;
     }
 
@@ -72,8 +83,25 @@ fn main(@builtin(global_invocation_id) global_id: vec3u) {
     // Now write all the data to the buffers.
     let write_id = atomicAdd(&uniforms.num_visible, 1);
     global_from_compact_gid[write_id] = global_gid;
-    depths[write_id] = mean_c.z;
 
+#ifdef SORT_DISTANCE
+    let depth = length(mean_c);
+#else
+    let depth = mean_c.z;
+#endif
+
+#ifdef LOG_DEPTH
+    // Quantize the log depth over the range of depths that aren't culled.
+    let log_depth = (log(depth) - log(NEAR_DEPTH)) / (log(FAR_DEPTH) - log(NEAR_DEPTH));
+    depths[write_id] = u32(clamp(log_depth, 0.0, 1.0) * f32((1u << LOG_DEPTH_BITS) - 1u));
+#else
+    // Interpret the depth as a u32. This is fine for a radix sort, as long as the depth > 0.0,
+    // which we know to be the case given how we cull splats.
+    depths[write_id] = bitcast<u32>(depth);
+#endif
+
+#ifndef FORWARD_ONLY
     // Write metadata to global array.
     radii[global_gid] = radius;
+#endif
 }
@@ -2,6 +2,9 @@
 
 #import helpers;
 
+// Radius in pixels of the discs drawn for the points render mode.
+const ISO_POINT_RADIUS: f32 = 2.0;
+
 struct IsectInfo {
     compact_gid: i32,
     tile_id: i32,
@@ -179,22 +182,28 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     let mean = helpers::as_vec(means[global_gid]);
     let scale = exp(helpers::as_vec(log_scales[global_gid]));
     let quat = normalize(quats[global_gid]);
-    let opac = helpers::sigmoid(raw_opacities[global_gid]);
+    var opac = helpers::sigmoid(raw_opacities[global_gid]);
 
-    let viewmat = uniforms.viewmat;
+    let shutter_t = helpers::shutter_time(mean, uniforms.viewmat, uniforms.focal, uniforms.pixel_center, uniforms.img_size);
+    let viewmat = helpers::shutter_viewmat(uniforms.viewmat, uniforms.viewmat_end, shutter_t);
     let R = mat3x3f(viewmat[0].xyz, viewmat[1].xyz, viewmat[2].xyz);
     let mean_c = R * mean + viewmat[3].xyz;
 
     let covar = helpers::calc_cov3d(scale, quat);
     let cov2d = helpers::calc_cov2d(covar, mean_c, uniforms.focal, uniforms.img_size, uniforms.pixel_center, viewmat);
-    let conic = helpers::inverse_symmetric(cov2d);
+    var conic = helpers::inverse_symmetric(cov2d);
 
     // compute the projected mean
     let rz = 1.0 / mean_c.z;
     let mean2d = uniforms.focal * mean_c.xy * rz + uniforms.pixel_center;
 
+    let num_coeffs = num_sh_coeffs(uniforms.sh_degree);
+#ifdef DC_ONLY
+    // Only read the base color, the higher bands are skipped entirely.
+    let sh_degree = 0u;
+#else
     let sh_degree = uniforms.sh_degree;
-    let num_coeffs = num_sh_coeffs(sh_degree);
+#endif
     var base_id = u32(global_gid) * num_coeffs;
 
     var sh = ShCoeffs();
@@ -242,13 +251,32 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     // TODO: This would be good but need to update backwards gradient as well.
     // color = max(color, vec3f(0.0));
 
+#ifdef SHADED
+    // Use the shortest axis of the gaussian as its normal, and shade with a simple headlight.
+    let normal = helpers::normal_from_scale_rot(scale, quat);
+    let n_dot_v = abs(dot(normal, viewdir));
+    color = color * (0.25 + 0.75 * n_dot_v);
+#endif
+
+    var radius = helpers::radius_from_cov(cov2d, opac);
+
+#ifdef ISO_POINTS
+    // A disc of a fixed size, the rasterizer draws it solid.
+    opac = 1.0;
+    conic = vec3f(1.0 / (ISO_POINT_RADIUS * ISO_POINT_RADIUS), 0.0, 1.0 / (ISO_POINT_RADIUS * ISO_POINT_RADIUS));
+    radius = ceil(3.0 * ISO_POINT_RADIUS);
+#endif
+#ifdef ISO_ELLIPSOIDS
+    // The rasterizer only draws the outline, which shouldn't depend on the opacity.
+    opac = 1.0;
+#endif
+
     projected[compact_gid] = helpers::create_projected_splat(
         mean2d,
         conic,
         vec4f(color, opac)
     );
 
-    let radius = helpers::radius_from_cov(cov2d, opac);
     let tile_minmax = helpers::get_tile_bbox(mean2d, radius, uniforms.tile_bounds);
     let tile_min = tile_minmax.xy;
     let tile_max = tile_minmax.zw;
@@ -11,7 +11,117 @@
     @group(0) @binding(4) var<storage, read_write> out_img: array<vec4f>;
 #endif
 
-@group(0) @binding(5) var<storage, read_write> final_index : array<i32>;
+#ifdef OIT
+    // Depth of each splat, as the float bits of the depth keys.
+    @group(0) @binding(5) var<storage, read> depths: array<u32>;
+
+    var<workgroup> local_depths: array<f32, helpers::TILE_SIZE>;
+
+    // Depth weight for weighted blended order independent transparency,
+    // see McGuire and Bavoil 2013, equation 9.
+    fn oit_weight(depth: f32) -> f32 {
+        return clamp(10.0 / (1e-5 + pow(depth / 5.0, 2.0) + pow(depth / 200.0, 6.0)), 1e-2, 3e3);
+    }
+#else
+#ifdef DEPTH_TEST
+    // The sorted depth keys, as for order independent blending.
+    @group(0) @binding(5) var<storage, read> depths: array<u32>;
+
+    var<workgroup> local_depths: array<f32, helpers::TILE_SIZE>;
+#endif
+#endif
+
+#ifdef DEPTH_TEST
+    // View space depth of the scene the splats are composited into. Only inference renders are
+    // depth tested, so this doesn't clash with the final index.
+    @group(0) @binding(6) var<storage, read> depth_buffer: array<f32>;
+#endif
+
+#ifdef FOVEATED
+    // Center of the fovea in pixels, and its radius. Only inference renders are foveated, so
+    // this comes right after the other optional inputs.
+#ifdef DEPTH_TEST
+    @group(0) @binding(7) var<storage, read> fovea: vec4i;
+#else
+#ifdef OIT
+    @group(0) @binding(6) var<storage, read> fovea: vec4i;
+#else
+    @group(0) @binding(5) var<storage, read> fovea: vec4i;
+#endif
+#endif
+#endif
+
+// The last splat contributing to each pixel, only needed for the backward pass.
+#ifndef FORWARD_ONLY
+#ifdef OIT
+    @group(0) @binding(6) var<storage, read_write> final_index : array<i32>;
+#else
+    @group(0) @binding(5) var<storage, read_write> final_index : array<i32>;
+#endif
+#endif
+
+#ifdef CONTRIBUTIONS
+    // Only supported with depth sorting, and with the final index.
+    @group(0) @binding(6) var<storage, read> global_from_compact_gid: array<i32>;
+    // The blend weights of each splat summed over all pixels, in fixed point.
+    @group(0) @binding(7) var<storage, read_write> contributions: array<atomic<u32>>;
+
+    var<workgroup> local_gids: array<i32, helpers::TILE_SIZE>;
+#endif
+
+// Scale of the fixed point contributions, a 256th of a pixel.
+const CONTRIBUTION_SCALE: u32 = 256u;
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    let c = max(color, vec3f(0.0));
+    return select(pow((c + 0.055) / 1.055, vec3f(2.4)), c / 12.92, c <= vec3f(0.04045));
+}
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let c = max(color, vec3f(1e-10));
+    return select(1.055 * pow(c, vec3f(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3f(0.0031308));
+}
+
+// Linear sRGB to linear Display P3, columns of the matrix.
+const SRGB_TO_P3 = mat3x3f(
+    vec3f(0.8225, 0.0332, 0.0171),
+    vec3f(0.1774, 0.9669, 0.0724),
+    vec3f(0.0, 0.0, 0.9108),
+);
+
+// Linear sRGB (Rec. 709) to linear Rec. 2020, columns of the matrix.
+const SRGB_TO_REC2020 = mat3x3f(
+    vec3f(0.6274, 0.0691, 0.0164),
+    vec3f(0.3293, 0.9195, 0.0880),
+    vec3f(0.0433, 0.0114, 0.8956),
+);
+
+// The PQ transfer function of ITU-R BT.2100, from nits.
+fn pq_encode(nits: vec3f) -> vec3f {
+    let m1 = 0.1593017578125;
+    let m2 = 78.84375;
+    let c1 = 0.8359375;
+    let c2 = 18.8515625;
+    let c3 = 18.6875;
+    let y = pow(clamp(nits / 10000.0, vec3f(1e-10), vec3f(1.0)), vec3f(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3f(m2));
+}
+
+// Convert a premultiplied sRGB color to the output color space, as a premultiplied color.
+fn output_color(color: vec3f, alpha: f32) -> vec3f {
+#ifdef DISPLAY_P3
+    let straight = color / max(alpha, 1e-5);
+    return linear_to_srgb(SRGB_TO_P3 * srgb_to_linear(straight)) * alpha;
+#else
+#ifdef HDR10
+    // SDR white is placed at 203 nits, see ITU-R BT.2408.
+    let straight = color / max(alpha, 1e-5);
+    return pq_encode(SRGB_TO_REC2020 * srgb_to_linear(straight) * 203.0) * alpha;
+#else
+    return color;
+#endif
+#endif
+}
 
 var<workgroup> local_batch: array<helpers::ProjectedSplat, helpers::TILE_SIZE>;
 
@@ -27,14 +137,32 @@ fn main(
 ) {
     let img_size = uniforms.img_size;
 
+    // Each thread shades a block of pixels, normally a single one.
+    var pixel = global_id.xy;
+    var block = 1u;
+    var has_block = true;
+
+#ifdef FOVEATED
+    // Away from the fovea, tiles are shaded in blocks of 2x2 pixels, and further out in blocks
+    // of 4x4 pixels. The first threads of the tile shade a block each, the others only help
+    // loading the splats.
+    let tile_center = (vec2f(workgroup_id.xy) + 0.5) * f32(helpers::TILE_WIDTH);
+    let fovea_dist = distance(tile_center, vec2f(fovea.xy)) / max(f32(fovea.z), 1.0);
+    block = select(select(4u, 2u, fovea_dist <= 2.0), 1u, fovea_dist <= 1.0);
+    let blocks_per_row = helpers::TILE_WIDTH / block;
+    let block_id = vec2u(local_idx % blocks_per_row, local_idx / blocks_per_row);
+    pixel = workgroup_id.xy * helpers::TILE_WIDTH + block_id * block;
+    has_block = local_idx < blocks_per_row * blocks_per_row;
+#endif
+
     // Get index of tile being drawn.
-    let pix_id = i32(global_id.x) + i32(global_id.y) * img_size.x;
+    let pix_id = i32(pixel.x) + i32(pixel.y) * img_size.x;
     let tile_id = i32(workgroup_id.x) + i32(workgroup_id.y) * uniforms.tile_bounds.x;
-    let pixel_coord = vec2f(global_id.xy) + 0.5;
+    let pixel_coord = vec2f(pixel) + 0.5 * f32(block);
 
     // return if out of bounds
     // keep not rasterizing threads around for reading data
-    let inside = i32(global_id.x) < img_size.x && i32(global_id.y) < img_size.y;
+    let inside = has_block && i32(pixel.x) < img_size.x && i32(pixel.y) < img_size.y;
     var done = !inside;
 
     // have all threads in tile process the same gaussians in batches
@@ -47,6 +175,25 @@ fn main(
 
     var pix_out = vec3f(0.0);
 
+#ifdef OIT
+    // Sum of the weighted colors, and the sum of weights.
+    var oit_accum = vec4f(0.0);
+#endif
+
+#ifdef DEPTH_TEST
+    // Splats behind the depth buffer are hidden by the scene.
+    var max_depth = 0.0;
+    if inside {
+        max_depth = depth_buffer[pix_id];
+#ifdef DISTANCE_KEYS
+        // Splats are keyed on their distance to the camera, convert the depth to a distance
+        // along the ray through the pixel.
+        let ray = (pixel_coord - uniforms.pixel_center) / uniforms.focal;
+        max_depth *= length(vec3f(ray, 1.0));
+#endif
+    }
+#endif
+
     // collect and process batches of gaussians
     // each thread loads one gaussian at a time before rasterizing its
     // designated pixel
@@ -66,12 +213,34 @@ fn main(
 
         if i32(local_idx) < remaining {
             let load_isect_id = batch_start + i32(local_idx);
-            local_batch[local_idx] = projected_splats[compact_gid_from_isect[load_isect_id]];
+            let load_compact_gid = compact_gid_from_isect[load_isect_id];
+            local_batch[local_idx] = projected_splats[load_compact_gid];
+#ifdef OIT
+            local_depths[local_idx] = bitcast<f32>(depths[load_compact_gid]);
+#else
+#ifdef DEPTH_TEST
+            local_depths[local_idx] = bitcast<f32>(depths[load_compact_gid]);
+#endif
+#endif
+#ifdef CONTRIBUTIONS
+            local_gids[local_idx] = global_from_compact_gid[load_compact_gid];
+#endif
         }
         // Wait for all writes to complete.
         workgroupBarrier();
 
         for (var t = 0; t < remaining && !done; t++) {
+#ifdef DEPTH_TEST
+            if local_depths[t] > max_depth {
+#ifdef OIT
+                continue;
+#else
+                // Splats are sorted front to back, so the next ones are hidden as well.
+                done = true;
+                break;
+#endif
+            }
+#endif
             let projected = local_batch[t];
 
             let xy = vec2f(projected.xy_x, projected.xy_y);
@@ -81,9 +250,31 @@ fn main(
             let delta = xy - pixel_coord;
             let sigma = 0.5f * (conic.x * delta.x * delta.x + conic.z * delta.y * delta.y) + conic.y * delta.x * delta.y;
             let vis = exp(-sigma);
-            let alpha = min(0.999f, color.a * vis);
+            var alpha = min(0.999f, color.a * vis);
+
+#ifdef ISO_POINTS
+            // Solid discs, the nearest one covers the pixel.
+            alpha = select(0.0, 0.999, sigma <= 0.5);
+#endif
+#ifdef ISO_ELLIPSOIDS
+            // The outline at two standard deviations, about a pixel wide. The gradient of the
+            // mahalanobis distance converts the pixel width to its units.
+            let dist = sqrt(2.0 * sigma);
+            let dist_grad = length(vec2f(
+                conic.x * delta.x + conic.y * delta.y,
+                conic.y * delta.x + conic.z * delta.y,
+            )) / max(dist, 1e-6);
+            alpha = select(0.0, 0.999, abs(dist - 2.0) < 0.75 * dist_grad);
+#endif
 
             if sigma >= 0.0 && alpha >= 1.0 / 255.0 {
+#ifdef OIT
+                // Splats aren't in depth order, so there's no early termination. Instead
+                // blend colors weighted by their depth.
+                let weight = alpha * oit_weight(local_depths[t]);
+                oit_accum += vec4f(color.rgb * weight, weight);
+                T *= 1.0 - alpha;
+#else
                 let next_T = T * (1.0 - alpha);
 
                 if next_T <= 1e-4f {
@@ -93,24 +284,44 @@ fn main(
 
                 let fac = alpha * T;
                 pix_out += vec3f(color.r, color.g, color.b) * fac;
+#ifdef CONTRIBUTIONS
+                atomicAdd(&contributions[local_gids[t]], u32(fac * f32(CONTRIBUTION_SCALE) + 0.5));
+#endif
                 T = next_T;
 
                 let isect_id = batch_start + t;
                 final_idx = isect_id + 1;
+#endif
             }
         }
     }
 
     if inside {
         let img_alpha = (1.0 - T);
-        let final_color = vec4f(pix_out, img_alpha);
+#ifdef OIT
+        pix_out = oit_accum.rgb / max(oit_accum.w, 1e-5) * img_alpha;
+#endif
+        let final_color = vec4f(output_color(pix_out, img_alpha), img_alpha);
         #ifdef RASTER_U32
             let colors_u = vec4u(clamp(final_color * 255.0, vec4f(0.0), vec4f(255.0)));
             let packed: u32 = colors_u.x | (colors_u.y << 8u) | (colors_u.z << 16u) | (colors_u.w << 24u);
-            out_img[pix_id] = packed;
-            final_index[pix_id] = final_idx;
+            let out_value = packed;
+        #else
+            let out_value = final_color;
+        #endif
+        #ifdef FOVEATED
+            for (var y = 0u; y < block; y++) {
+                for (var x = 0u; x < block; x++) {
+                    let p = vec2i(pixel + vec2u(x, y));
+                    if p.x < img_size.x && p.y < img_size.y {
+                        out_img[p.x + p.y * img_size.x] = out_value;
+                    }
+                }
+            }
         #else
-            out_img[pix_id] = final_color;
+            out_img[pix_id] = out_value;
+        #endif
+        #ifndef FORWARD_ONLY
             final_index[pix_id] = final_idx;
         #endif
     }
@@ -22,7 +22,12 @@
 #endif
 
 
-const BATCH_SIZE = helpers::TILE_SIZE;
+#ifdef SMALL_WG_MEM
+    // Gather splats in half the batches to stay under 16KB of workgroup memory.
+    const BATCH_SIZE = helpers::TILE_SIZE / 2u;
+#else
+    const BATCH_SIZE = helpers::TILE_SIZE;
+#endif
 
 // Gaussians gathered in batch.
 var<workgroup> local_batch: array<helpers::ProjectedSplat, BATCH_SIZE>;
@@ -36,8 +41,8 @@ var<workgroup> local_id: array<i32, BATCH_SIZE>;
 
 // Current queue of gradients to be flushed.
 var<workgroup> grad_count: atomic<i32>;
-var<workgroup> gather_grads: array<helpers::ProjectedSplat, BATCH_SIZE>;
-var<workgroup> gather_grad_id: array<i32, BATCH_SIZE>;
+var<workgroup> gather_grads: array<helpers::ProjectedSplat, helpers::TILE_SIZE>;
+var<workgroup> gather_grad_id: array<i32, helpers::TILE_SIZE>;
 
 fn add_bitcast(cur: u32, add: f32) -> u32 {
     return bitcast<u32>(bitcast<f32>(cur) + add);
@@ -142,8 +147,10 @@ fn main(
     @builtin(global_invocation_id) global_id: vec3u,
     @builtin(workgroup_id) workgroup_id: vec3u,
     @builtin(local_invocation_index) local_idx: u32,
+#ifdef SUBGROUPS
     @builtin(subgroup_size) subgroup_size: u32,
     @builtin(subgroup_invocation_id) subgroup_invocation_id: u32
+#endif
 ) {
     let img_size = uniforms.img_size;
     let tile_bounds = uniforms.tile_bounds;
@@ -188,7 +195,12 @@ fn main(
     // Make sure all groups start with empty gradient queue.
     atomicStore(&grad_count, 0);
 
+#ifdef SUBGROUPS
     let sg_per_tile = helpers::ceil_div(i32(helpers::TILE_SIZE), i32(subgroup_size));
+#else
+    // Without subgroups every thread queues its own gradient.
+    let sg_per_tile = i32(helpers::TILE_SIZE);
+#endif
     let microbatch_size = i32(helpers::TILE_SIZE) / sg_per_tile;
 
     for (var b = 0; b < num_batches; b++) {
@@ -273,6 +285,7 @@ fn main(
                     }
                 }
 
+#ifdef SUBGROUPS
                 // Queue a new gradient if this subgroup has any.
                 // The gradient is sum of all gradients in the subgroup.
                 if subgroupAny(splat_active) {
@@ -292,6 +305,13 @@ fn main(
                         gather_grad_id[grad_idx] = local_id[t];
                     }
                 }
+#else
+                if splat_active {
+                    let grad_idx = atomicAdd(&grad_count, 1);
+                    gather_grads[grad_idx] = helpers::create_projected_splat(v_xy, v_conic, v_colors);
+                    gather_grad_id[grad_idx] = local_id[t];
+                }
+#endif
             }
 
             // Make sure all threads are done, and flush a batch of gradients.
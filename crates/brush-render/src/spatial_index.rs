@@ -0,0 +1,85 @@
+//! A spatial index over splats, shared by everything that looks up splats by position:
+//! raycasts, crop boxes, nearest neighbours and spatial chunking.
+//!
+//! The index is a snapshot, splats that move afterwards aren't tracked. While training, it's
+//! rebuilt after the splats are refined, see `SplatTrainer::spatial_index`, as the means only
+//! move a little between refinements.
+use glam::{Quat, Vec3};
+use kiddo::{KdTree, SquaredEuclidean};
+
+use crate::{
+    gaussian_splats::Splats,
+    raycast::{RayHit, SplatBvh},
+    Backend,
+};
+
+pub struct SpatialIndex {
+    bvh: SplatBvh,
+    points: KdTree<f32, 3>,
+}
+
+impl SpatialIndex {
+    /// Index splats by their means, normalized rotations, scales and opacities.
+    pub fn new(means: &[Vec3], rotations: &[Quat], scales: &[Vec3], opacities: &[f32]) -> Self {
+        let points: Vec<[f32; 3]> = means.iter().map(|m| m.to_array()).collect();
+        Self {
+            bvh: SplatBvh::new(means, rotations, scales, opacities),
+            points: (&points).into(),
+        }
+    }
+
+    /// Index points without a size, eg. to find the neighbours of initial points. Rays don't
+    /// hit these.
+    pub fn from_means(means: &[Vec3]) -> Self {
+        let n = means.len();
+        Self::new(
+            means,
+            &vec![Quat::IDENTITY; n],
+            &vec![Vec3::ZERO; n],
+            &vec![1.0; n],
+        )
+    }
+
+    /// Read back the splats and index them.
+    pub async fn from_splats<B: Backend>(splats: &Splats<B>) -> Self {
+        let bvh = SplatBvh::from_splats(splats).await;
+        let points: Vec<[f32; 3]> = bvh.means().map(|m| m.to_array()).collect();
+        Self {
+            bvh,
+            points: (&points).into(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bvh.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bvh.is_empty()
+    }
+
+    /// All splats along a ray, sorted front to back, see [`SplatBvh::raycast`].
+    pub fn raycast(&self, origin: Vec3, dir: Vec3) -> Vec<RayHit> {
+        self.bvh.raycast(origin, dir)
+    }
+
+    /// Indices of the splats with their mean inside the box, eg. to crop a scene.
+    pub fn query_box(&self, min: Vec3, max: Vec3) -> Vec<usize> {
+        self.bvh.query_box(min, max)
+    }
+
+    /// The `k` splats with their mean nearest to `point`, as indices and squared distances,
+    /// nearest first.
+    pub fn nearest(&self, point: Vec3, k: usize) -> Vec<(usize, f32)> {
+        self.points
+            .nearest_n::<SquaredEuclidean>(&point.to_array(), k)
+            .into_iter()
+            .map(|n| (n.item as usize, n.distance))
+            .collect()
+    }
+
+    /// Spatially compact groups of at most `max_count` splats, eg. to stream chunks.
+    pub fn clusters(&self, max_count: usize) -> Vec<Vec<usize>> {
+        self.bvh.clusters(max_count)
+    }
+}
@@ -0,0 +1,148 @@
+use burn::tensor::{activation::sigmoid, Tensor, Transaction};
+
+use crate::{gaussian_splats::Splats, Backend};
+
+const NUM_BINS: usize = 32;
+
+/// A histogram of values, with evenly spaced bins between the smallest and largest value.
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub counts: Vec<u32>,
+}
+
+impl Histogram {
+    pub fn new(values: &[f32], bins: usize) -> Self {
+        let values: Vec<_> = values.iter().copied().filter(|v| v.is_finite()).collect();
+
+        if values.is_empty() || bins == 0 {
+            return Self::default();
+        }
+
+        let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+
+        let mut counts = vec![0; bins];
+        let range = (max - min).max(f32::EPSILON);
+        for v in values {
+            let bin = (((v - min) / range) * bins as f32) as usize;
+            counts[bin.min(bins - 1)] += 1;
+        }
+
+        Self {
+            min,
+            max,
+            mean,
+            counts,
+        }
+    }
+
+    /// The value at the center of a bin.
+    pub fn bin_center(&self, bin: usize) -> f32 {
+        let width = (self.max - self.min) / self.counts.len().max(1) as f32;
+        self.min + (bin as f32 + 0.5) * width
+    }
+}
+
+/// Distributions of the properties of a set of splats, see [`Splats::statistics`].
+#[derive(Debug, Clone, Default)]
+pub struct SplatStatistics {
+    pub num_splats: usize,
+    pub opacity: Histogram,
+    /// Log10 of the ratio between the largest and smallest scale of each splat.
+    pub anisotropy: Histogram,
+    /// RMS of the view dependent SH coefficients of each splat. Zero for splats
+    /// without higher SH bands.
+    pub sh_energy: Histogram,
+    /// Log10 of the screenspace radius in pixels of the splats visible in a render,
+    /// see [`SplatStatistics::with_screen_radii`].
+    pub screen_radius: Option<Histogram>,
+}
+
+impl SplatStatistics {
+    /// Add the distribution of screen radii from a render, eg. from [`crate::RenderAux::radii`].
+    pub async fn with_screen_radii<B: Backend>(mut self, radii: Tensor<B, 1>) -> Self {
+        let radii = radii
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+            .unwrap_or_default();
+        // Splats that aren't visible have a radius of zero.
+        let visible: Vec<_> = radii
+            .into_iter()
+            .filter(|r| *r > 0.0)
+            .map(f32::log10)
+            .collect();
+        self.screen_radius = Some(Histogram::new(&visible, NUM_BINS));
+        self
+    }
+}
+
+impl<B: Backend> Splats<B> {
+    /// Calculate the distributions of opacity, anisotropy and SH energy of the splats.
+    ///
+    /// This reads back a few values per splat, so it's best not to call this every frame.
+    pub async fn statistics(&self) -> SplatStatistics {
+        let num_splats = self.num_splats();
+        if num_splats == 0 {
+            return SplatStatistics::default();
+        }
+
+        let opacity = sigmoid(self.raw_opacity.val());
+
+        let log_scales = self.log_scales.val();
+        let anisotropy = (log_scales.clone().max_dim(1) - log_scales.min_dim(1))
+            .reshape([num_splats])
+            / std::f32::consts::LN_10;
+
+        let [_, num_coeffs, _] = self.sh_coeffs.dims();
+        let sh_energy = if num_coeffs > 1 {
+            let rest = self
+                .sh_coeffs
+                .val()
+                .slice([0..num_splats, 1..num_coeffs, 0..3]);
+            (rest.powf_scalar(2.0).sum_dim(1).sum_dim(2) / ((num_coeffs - 1) * 3) as f32)
+                .sqrt()
+                .reshape([num_splats])
+        } else {
+            Tensor::zeros([num_splats], &self.means.device())
+        };
+
+        let data = Transaction::default()
+            .register(opacity)
+            .register(anisotropy)
+            .register(sh_energy)
+            .execute_async()
+            .await;
+
+        let histogram = |i: usize| {
+            let values = data[i].to_vec::<f32>().unwrap_or_default();
+            Histogram::new(&values, NUM_BINS)
+        };
+
+        SplatStatistics {
+            num_splats,
+            opacity: histogram(0),
+            anisotropy: histogram(1),
+            sh_energy: histogram(2),
+            screen_radius: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Histogram;
+
+    #[test]
+    fn histogram_bins() {
+        let hist = Histogram::new(&[0.0, 0.1, 0.5, 1.0, f32::NAN], 2);
+        assert_eq!(hist.counts, vec![2, 2]);
+        assert_eq!(hist.min, 0.0);
+        assert_eq!(hist.max, 1.0);
+        assert!((hist.bin_center(0) - 0.25).abs() < 1e-6);
+    }
+}
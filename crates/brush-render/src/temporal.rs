@@ -0,0 +1,328 @@
+//! Temporal anti-aliasing, to accumulate the renders of a moving view.
+//!
+//! Every frame is rendered with the camera shifted by a different subpixel offset, and
+//! blended with the previous frames. The previous frames are reprojected with the depth of
+//! the current frame so they line up while the camera moves, and clamped to the colors
+//! around each pixel of the current frame, which hides history that doesn't match anymore,
+//! eg. where something comes into view. Frames can be rendered at a lower resolution than
+//! the output, the offsets then recover detail over a few frames.
+//!
+//! Instead of the depth, the previous frames can be reprojected with motion vectors, see
+//! [`crate::motion`], which also follow splats that move themselves in animated scenes.
+use burn::tensor::{module::max_pool2d, Int, Tensor};
+use glam::{UVec2, Vec2};
+
+use crate::{camera::Camera, Backend};
+
+/// Weight of the newest frame when not specified otherwise.
+pub const DEFAULT_BLEND: f32 = 0.1;
+
+// Number of different offsets before the sequence repeats.
+const JITTER_LENGTH: u32 = 16;
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Subpixel offset of a frame in pixels, between -0.5 and 0.5. The offsets follow a Halton
+/// sequence, which covers a pixel evenly in a few frames.
+pub fn jitter(frame: u32) -> Vec2 {
+    let i = frame % JITTER_LENGTH + 1;
+    Vec2::new(halton(i, 2), halton(i, 3)) - 0.5
+}
+
+/// The camera with its image shifted by `offset` pixels of an image of `img_size`.
+pub fn jittered_camera(camera: &Camera, img_size: UVec2, offset: Vec2) -> Camera {
+    Camera {
+        center_uv: camera.center_uv + offset / img_size.as_vec2(),
+        ..camera.clone()
+    }
+}
+
+fn lerp<B: Backend>(a: Tensor<B, 2>, b: Tensor<B, 2>, t: Tensor<B, 2>) -> Tensor<B, 2> {
+    a.clone() + (b - a) * t
+}
+
+// Sample an `[h, w, c]` image at pixel coordinates, as `[n, c]`. Coordinates are clamped to
+// the image, and the center of pixel `i` is at `i`.
+fn sample<B: Backend>(
+    img: Tensor<B, 3>,
+    x: Tensor<B, 1>,
+    y: Tensor<B, 1>,
+    bilinear: bool,
+) -> Tensor<B, 2> {
+    let [h, w, c] = img.dims();
+    let flat = img.reshape([h * w, c]);
+    let x = x.clamp(0.0, (w - 1) as f32);
+    let y = y.clamp(0.0, (h - 1) as f32);
+    let at =
+        |xi: Tensor<B, 1, Int>, yi: Tensor<B, 1, Int>| flat.clone().select(0, yi * (w as i32) + xi);
+
+    if !bilinear {
+        return at((x + 0.5).int(), (y + 0.5).int());
+    }
+
+    // The coordinates aren't negative, so truncating them rounds down.
+    let (x0, y0) = (x.clone().int(), y.clone().int());
+    let fx = (x - x0.clone().float()).unsqueeze_dim(1);
+    let fy = (y - y0.clone().float()).unsqueeze_dim(1);
+    let x1 = (x0.clone() + 1).clamp_max(w as i32 - 1);
+    let y1 = (y0.clone() + 1).clamp_max(h as i32 - 1);
+
+    let top = lerp(at(x0.clone(), y0.clone()), at(x1.clone(), y0), fx.clone());
+    let bottom = lerp(at(x0, y1.clone()), at(x1, y1), fx);
+    lerp(top, bottom, fy)
+}
+
+// Minimum and maximum of the 3x3 neighbourhood of every pixel of an `[h, w, c]` image, as
+// `[h * w, c]`.
+fn neighbourhood_bounds<B: Backend>(img: Tensor<B, 3>) -> (Tensor<B, 2>, Tensor<B, 2>) {
+    let [h, w, c] = img.dims();
+    let nchw = img.permute([2, 0, 1]).unsqueeze_dim::<4>(0);
+    let pool = |x: Tensor<B, 4>| max_pool2d(x, [3, 3], [1, 1], [1, 1], [1, 1]);
+    let flat = |x: Tensor<B, 4>| x.squeeze::<3>(0).permute([1, 2, 0]).reshape([h * w, c]);
+    let max = pool(nchw.clone());
+    let min = pool(nchw.neg()).neg();
+    (flat(min), flat(max))
+}
+
+// How the previous frames are lined up with the current one.
+enum Reprojection<B: Backend> {
+    Depth(Tensor<B, 2>),
+    Motion(Tensor<B, 3>),
+}
+
+/// Blends the frames of a moving view, see the [module docs](self).
+pub struct TemporalAccumulator<B: Backend> {
+    /// Weight of the newest frame, lower is smoother but slower to respond to changes.
+    pub blend: f32,
+    frame: u32,
+    // The last output and the camera it was seen from.
+    history: Option<(Tensor<B, 3>, Camera)>,
+}
+
+impl<B: Backend> Default for TemporalAccumulator<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Backend> TemporalAccumulator<B> {
+    pub fn new() -> Self {
+        Self {
+            blend: DEFAULT_BLEND,
+            frame: 0,
+            history: None,
+        }
+    }
+
+    /// Forget the previous frames, eg. when the view stops moving.
+    pub fn reset(&mut self) {
+        self.history = None;
+    }
+
+    /// The camera of the last frame, to render the motion vectors of the next frame from.
+    pub fn previous_camera(&self) -> Option<&Camera> {
+        self.history.as_ref().map(|(_, camera)| camera)
+    }
+
+    /// The offset to render the next frame with, see [`jittered_camera`].
+    pub fn next_offset(&mut self) -> Vec2 {
+        self.frame = self.frame.wrapping_add(1);
+        jitter(self.frame)
+    }
+
+    /// Blend a new frame with the previous ones, and return the result at `out_size`.
+    ///
+    /// `frame` is an `[h, w, 4]` render from `camera` jittered by `offset`, and `depth` the
+    /// view space depth of the unjittered camera, at any resolution. Pixels with a depth of 0
+    /// aren't reprojected.
+    pub fn accumulate(
+        &mut self,
+        frame: Tensor<B, 3>,
+        depth: Tensor<B, 2>,
+        camera: &Camera,
+        offset: Vec2,
+        out_size: UVec2,
+    ) -> Tensor<B, 3> {
+        self.accumulate_inner(frame, Reprojection::Depth(depth), camera, offset, out_size)
+    }
+
+    fn accumulate_inner(
+        &mut self,
+        frame: Tensor<B, 3>,
+        reprojection: Reprojection<B>,
+        camera: &Camera,
+        offset: Vec2,
+        out_size: UVec2,
+    ) -> Tensor<B, 3> {
+        let device = frame.device();
+        let [frame_h, frame_w, channels] = frame.dims();
+        let (w, h) = (out_size.x as usize, out_size.y as usize);
+        let n = w * h;
+
+        // Centers of the output pixels.
+        let px = Tensor::<B, 1, Int>::arange(0..w as i64, &device)
+            .float()
+            .add_scalar(0.5)
+            .reshape([1, w])
+            .repeat_dim(0, h)
+            .reshape([n]);
+        let py = Tensor::<B, 1, Int>::arange(0..h as i64, &device)
+            .float()
+            .add_scalar(0.5)
+            .reshape([h, 1])
+            .repeat_dim(1, w)
+            .reshape([n]);
+
+        // Undo the jitter while upsampling, pixel `i` of the frame shows the unjittered
+        // image at `i + 0.5 - offset`.
+        let scale = Vec2::new(frame_w as f32 / w as f32, frame_h as f32 / h as f32);
+        let current = sample(
+            frame,
+            px.clone() * scale.x + (offset.x - 0.5),
+            py.clone() * scale.y + (offset.y - 0.5),
+            true,
+        );
+        let current_img = current.clone().reshape([h, w, channels]);
+
+        let history = self
+            .history
+            .take()
+            .filter(|(history, _)| history.dims() == [h, w, channels]);
+        let Some((history, prev_camera)) = history else {
+            self.history = Some((current_img.clone(), camera.clone()));
+            return current_img;
+        };
+
+        let (hx, hy, valid) = match reprojection {
+            Reprojection::Depth(depth) => {
+                let [depth_h, depth_w] = depth.dims();
+
+                // Depth edges shouldn't be blended, so the depth isn't interpolated.
+                let depth = sample(
+                    depth.unsqueeze_dim(2),
+                    px.clone() * (depth_w as f32 / w as f32) - 0.5,
+                    py.clone() * (depth_h as f32 / h as f32) - 0.5,
+                    false,
+                )
+                .reshape([n]);
+
+                // Where the points seen by the output pixels were in the previous frame.
+                let focal = camera.focal(out_size);
+                let center = camera.center(out_size);
+                let lx = (px - center.x) / focal.x * depth.clone();
+                let ly = (py - center.y) / focal.y * depth.clone();
+                let to_prev = prev_camera.world_to_local() * camera.local_to_world();
+                let prev_local = |row: usize| {
+                    let r = to_prev.row(row);
+                    lx.clone() * r.x + ly.clone() * r.y + depth.clone() * r.z + r.w
+                };
+                let (qx, qy, qz) = (prev_local(0), prev_local(1), prev_local(2));
+
+                let prev_focal = prev_camera.focal(out_size);
+                let prev_center = prev_camera.center(out_size);
+                let hx = qx / qz.clone().clamp_min(1e-4) * prev_focal.x + prev_center.x;
+                let hy = qy / qz.clone().clamp_min(1e-4) * prev_focal.y + prev_center.y;
+
+                // History is only valid where there's a depth.
+                let valid = depth.greater_elem(0.0).float() * qz.greater_elem(1e-4).float();
+                (hx, hy, valid)
+            }
+            Reprojection::Motion(motion) => {
+                // Motion vectors are in pixels of their own resolution, and like the depth
+                // aren't interpolated across edges.
+                let [motion_h, motion_w, _] = motion.dims();
+                let (sx, sy) = (motion_w as f32 / w as f32, motion_h as f32 / h as f32);
+                let motion = sample(motion, px.clone() * sx - 0.5, py.clone() * sy - 0.5, false);
+                let hx = px + motion.clone().slice([0..n, 0..1]).reshape([n]) / sx;
+                let hy = py + motion.slice([0..n, 1..2]).reshape([n]) / sy;
+                (hx, hy, Tensor::ones([n], &device))
+            }
+        };
+        self.blend_history(current, current_img, history, hx, hy, valid, camera)
+    }
+
+    /// Like [`Self::accumulate`], reprojecting the previous frames with `motion`, the
+    /// `[h, w, 2]` motion vectors of the unjittered camera since the previous frame at any
+    /// resolution, see [`crate::motion::render_motion_vectors`].
+    pub fn accumulate_with_motion(
+        &mut self,
+        frame: Tensor<B, 3>,
+        motion: Tensor<B, 3>,
+        camera: &Camera,
+        offset: Vec2,
+        out_size: UVec2,
+    ) -> Tensor<B, 3> {
+        self.accumulate_inner(
+            frame,
+            Reprojection::Motion(motion),
+            camera,
+            offset,
+            out_size,
+        )
+    }
+
+    // Blend the history at pixel coordinates `hx`, `hy` of the previous output with the
+    // current frame, where `valid`.
+    fn blend_history(
+        &mut self,
+        current: Tensor<B, 2>,
+        current_img: Tensor<B, 3>,
+        history: Tensor<B, 3>,
+        hx: Tensor<B, 1>,
+        hy: Tensor<B, 1>,
+        valid: Tensor<B, 1>,
+        camera: &Camera,
+    ) -> Tensor<B, 3> {
+        let [h, w, channels] = current_img.dims();
+
+        // History is only valid where it was in view.
+        let valid = valid
+            * hx.clone().greater_equal_elem(0.0).float()
+            * hx.clone().lower_elem(w as f32).float()
+            * hy.clone().greater_equal_elem(0.0).float()
+            * hy.clone().lower_elem(h as f32).float();
+
+        let history = sample(history, hx - 0.5, hy - 0.5, true);
+        let (min, max) = neighbourhood_bounds(current_img);
+        let history = history
+            .clone()
+            .mask_where(history.clone().lower(min.clone()), min);
+        let history = history
+            .clone()
+            .mask_where(history.clone().greater(max.clone()), max);
+
+        // Without valid history, only the current frame is shown.
+        let weight = (valid * (self.blend - 1.0) + 1.0).unsqueeze_dim(1);
+        let out = lerp(history, current, weight).reshape([h, w, channels]);
+        self.history = Some((out.clone(), camera.clone()));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::jitter;
+
+    #[test]
+    fn jitter_covers_pixel() {
+        let offsets: Vec<_> = (0..16).map(jitter).collect();
+        assert!(offsets
+            .iter()
+            .all(|o| o.abs().max_element() <= 0.5 && o.is_finite()));
+
+        // Offsets are spread over all quadrants of the pixel.
+        for (sx, sy) in [(1.0, 1.0), (1.0, -1.0), (-1.0, 1.0), (-1.0, -1.0)] {
+            assert!(offsets.iter().any(|o| o.x * sx > 0.0 && o.y * sy > 0.0));
+        }
+        assert_eq!(jitter(3), jitter(3 + 16));
+    }
+}
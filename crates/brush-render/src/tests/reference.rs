@@ -2,7 +2,7 @@ use crate::{
     camera::{focal_to_fov, fov_to_focal, Camera},
     gaussian_splats::Splats,
     safetensor_utils::safetensor_to_burn,
-    Backend,
+    Backend, RenderOptions, CAMERA_GRAD_SIZE,
 };
 
 use anyhow::{Context, Result};
@@ -87,11 +87,15 @@ async fn test_reference() -> Result<()> {
             glam::uvec2(w as u32, h as u32),
             splats.means.val().into_primitive().tensor(),
             splats.xys_dummy.clone().into_primitive().tensor(),
+            Tensor::<DiffBack, 1>::zeros([CAMERA_GRAD_SIZE], &device)
+                .into_primitive()
+                .tensor(),
             splats.log_scales.val().into_primitive().tensor(),
             splats.rotation.val().into_primitive().tensor(),
             splats.sh_coeffs.val().into_primitive().tensor(),
             splats.raw_opacity.val().into_primitive().tensor(),
             false,
+            RenderOptions::default(),
         );
 
         let (out, aux) = (Tensor::from_primitive(TensorPrimitive::Float(img)), aux);
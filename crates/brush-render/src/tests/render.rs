@@ -1,4 +1,4 @@
-use crate::{camera::Camera, Backend};
+use crate::{camera::Camera, Backend, ColorSpace, Foveation, RenderOptions, CAMERA_GRAD_SIZE};
 use assert_approx_eq::assert_approx_eq;
 use burn::{
     backend::Autodiff,
@@ -8,6 +8,65 @@ use burn_wgpu::{Wgpu, WgpuDevice};
 
 type DiffBack = Autodiff<Wgpu>;
 
+// Camera looking at the test splats from a distance of 4.
+fn test_camera() -> Camera {
+    Camera::new(
+        glam::vec3(0.0, 0.0, -4.0),
+        glam::Quat::IDENTITY,
+        0.5,
+        0.5,
+        glam::vec2(0.5, 0.5),
+    )
+}
+
+// A few small, half transparent splats at a depth of 4 to 5.5 from the test camera.
+struct TestSplats<B: burn::tensor::backend::Backend> {
+    means: Tensor<B, 2>,
+    log_scales: Tensor<B, 2>,
+    quats: Tensor<B, 2>,
+    sh_coeffs: Tensor<B, 3>,
+    raw_opacity: Tensor<B, 1>,
+}
+
+const NUM_TEST_SPLATS: usize = 4;
+
+fn test_splats<B: burn::tensor::backend::Backend>(device: &B::Device) -> TestSplats<B> {
+    TestSplats {
+        means: Tensor::<B, 1>::from_floats(
+            [0.0, 0.0, 0.0, 0.2, 0.1, 0.5, -0.3, 0.2, 1.0, 0.1, -0.2, 1.5],
+            device,
+        )
+        .reshape([NUM_TEST_SPLATS, 3]),
+        log_scales: Tensor::<B, 2>::ones([NUM_TEST_SPLATS, 3], device) * -1.5,
+        quats: Tensor::<B, 1>::from_floats(glam::Quat::IDENTITY.to_array(), device)
+            .unsqueeze_dim(0)
+            .repeat_dim(0, NUM_TEST_SPLATS),
+        sh_coeffs: Tensor::ones([NUM_TEST_SPLATS, 1, 3], device),
+        raw_opacity: Tensor::zeros([NUM_TEST_SPLATS], device),
+    }
+}
+
+// Render the test splats from the test camera to a 32x32 image, without gradients.
+fn render_test_splats(
+    depth_buffer: Option<Tensor<Wgpu, 2>>,
+    options: RenderOptions,
+) -> Tensor<Wgpu, 3> {
+    let splats = test_splats::<Wgpu>(&WgpuDevice::DefaultDevice);
+    let output = Wgpu::render_splats_inference(
+        &test_camera(),
+        glam::uvec2(32, 32),
+        splats.means.into_primitive().tensor(),
+        splats.log_scales.into_primitive().tensor(),
+        splats.quats.into_primitive().tensor(),
+        splats.sh_coeffs.into_primitive().tensor(),
+        splats.raw_opacity.into_primitive().tensor(),
+        depth_buffer.map(|d| d.into_primitive().tensor()),
+        false,
+        options,
+    );
+    Tensor::from_primitive(TensorPrimitive::Float(output))
+}
+
 #[tokio::test]
 async fn renders_at_all() {
     // Check if rendering doesn't hard crash or anything.
@@ -25,6 +84,7 @@ async fn renders_at_all() {
     let num_points = 8;
     let means = Tensor::<DiffBack, 2>::zeros([num_points, 3], &device);
     let xy_dummy = Tensor::<DiffBack, 2>::zeros([num_points, 2], &device);
+    let camera_dummy = Tensor::<DiffBack, 1>::zeros([CAMERA_GRAD_SIZE], &device);
     let log_scales = Tensor::<DiffBack, 2>::ones([num_points, 3], &device) * 2.0;
     let quats: Tensor<DiffBack, 2> =
         Tensor::<DiffBack, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
@@ -37,11 +97,13 @@ async fn renders_at_all() {
         img_size,
         means.into_primitive().tensor(),
         xy_dummy.into_primitive().tensor(),
+        camera_dummy.into_primitive().tensor(),
         log_scales.into_primitive().tensor(),
         quats.into_primitive().tensor(),
         sh_coeffs.into_primitive().tensor(),
         raw_opacity.into_primitive().tensor(),
         false,
+        RenderOptions::default(),
     );
     aux.into_wrapped().debug_assert_valid();
 
@@ -57,3 +119,165 @@ async fn renders_at_all() {
     assert_approx_eq!(rgb_mean, 0.0, 1e-5);
     assert_approx_eq!(alpha_mean, 0.0);
 }
+
+#[tokio::test]
+async fn contributions_sum_to_alpha() {
+    // Blend weights of all splats in a pixel add up to its alpha, so the contributions
+    // over the whole image add up to the summed alpha.
+    let device = WgpuDevice::DefaultDevice;
+    let splats = test_splats::<DiffBack>(&device);
+    let xy_dummy = Tensor::<DiffBack, 2>::zeros([NUM_TEST_SPLATS, 2], &device);
+    let camera_dummy = Tensor::<DiffBack, 1>::zeros([CAMERA_GRAD_SIZE], &device);
+    let options = RenderOptions {
+        contributions: true,
+        ..Default::default()
+    };
+    let (output, aux) = DiffBack::render_splats(
+        &test_camera(),
+        glam::uvec2(32, 32),
+        splats.means.into_primitive().tensor(),
+        xy_dummy.into_primitive().tensor(),
+        camera_dummy.into_primitive().tensor(),
+        splats.log_scales.into_primitive().tensor(),
+        splats.quats.into_primitive().tensor(),
+        splats.sh_coeffs.into_primitive().tensor(),
+        splats.raw_opacity.into_primitive().tensor(),
+        false,
+        options,
+    );
+    let aux = aux.into_wrapped();
+    let contributions = aux.contributions.clone();
+    aux.resolve_bwd_data().await;
+
+    let output: Tensor<DiffBack, 3> = Tensor::from_primitive(TensorPrimitive::Float(output));
+    let alpha_sum = output
+        .slice([0..32, 0..32, 3..4])
+        .sum()
+        .into_scalar_async()
+        .await;
+    let contribution_sum = contributions.sum().into_scalar_async().await;
+    assert!(alpha_sum > 1.0, "Splats should be visible");
+    assert_approx_eq!(contribution_sum, alpha_sum, alpha_sum * 0.02);
+}
+
+#[tokio::test]
+async fn depth_buffer_hides_splats_behind() {
+    // Alpha of the test splats, tested against a depth buffer of the given depth.
+    let alpha_sum = |depth: f32| async move {
+        let depth_buffer = Tensor::<Wgpu, 2>::ones([32, 32], &WgpuDevice::DefaultDevice) * depth;
+        render_test_splats(Some(depth_buffer), RenderOptions::default())
+            .slice([0..32, 0..32, 3..4])
+            .sum()
+            .into_scalar_async()
+            .await
+    };
+
+    let all = alpha_sum(f32::INFINITY).await;
+    let front = alpha_sum(4.7).await;
+    let none = alpha_sum(1.0).await;
+    assert!(all > 1.0, "Splats should be visible");
+    assert!(
+        front > 0.0 && front < all,
+        "Only the front splats should be visible"
+    );
+    assert_approx_eq!(none, 0.0);
+}
+
+#[tokio::test]
+async fn foveation_coarsens_periphery() {
+    let render = |foveation: Option<Foveation>| async move {
+        let options = RenderOptions {
+            foveation,
+            ..Default::default()
+        };
+        render_test_splats(None, options)
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+            .expect("Wrong type")
+    };
+    let pixel =
+        |img: &[f32], x: usize, y: usize| img[(y * 32 + x) * 4..(y * 32 + x + 1) * 4].to_vec();
+
+    // A fovea covering the whole image changes nothing.
+    let full = render(None).await;
+    let covered = render(Some(Foveation {
+        center: glam::uvec2(16, 16),
+        radius: 64,
+    }))
+    .await;
+    for (a, b) in full.iter().zip(&covered) {
+        assert_approx_eq!(a, b, 1e-5);
+    }
+
+    // With the fovea in a corner, the opposite tile is shaded in blocks of 4x4 pixels.
+    let cornered = render(Some(Foveation {
+        center: glam::uvec2(0, 0),
+        radius: 12,
+    }))
+    .await;
+    for y in 16..20 {
+        for x in 16..20 {
+            assert_eq!(pixel(&cornered, x, y), pixel(&cornered, 16, 16));
+        }
+    }
+    assert_eq!(pixel(&cornered, 0, 0), pixel(&full, 0, 0));
+}
+
+#[tokio::test]
+async fn display_p3_keeps_white_and_desaturates_red() {
+    let img_size = glam::uvec2(16, 16);
+    let device = WgpuDevice::DefaultDevice;
+
+    // The center pixel of a large opaque splat of a color.
+    let render = |rgb: [f32; 3], color_space: ColorSpace| {
+        let device = device.clone();
+        async move {
+            let means = Tensor::<Wgpu, 2>::zeros([1, 3], &device);
+            let log_scales = Tensor::<Wgpu, 2>::ones([1, 3], &device);
+            let quats = Tensor::<Wgpu, 1>::from_floats(glam::Quat::IDENTITY.to_array(), &device)
+                .unsqueeze_dim(0);
+            let sh_coeffs =
+                Tensor::<Wgpu, 1>::from_floats(rgb.map(|c| (c - 0.5) / 0.282_094_8), &device)
+                    .reshape([1, 1, 3]);
+            let raw_opacity = Tensor::<Wgpu, 1>::ones([1], &device) * 10.0;
+            let options = RenderOptions {
+                color_space,
+                ..Default::default()
+            };
+            let output = Wgpu::render_splats_inference(
+                &test_camera(),
+                img_size,
+                means.into_primitive().tensor(),
+                log_scales.into_primitive().tensor(),
+                quats.into_primitive().tensor(),
+                sh_coeffs.into_primitive().tensor(),
+                raw_opacity.into_primitive().tensor(),
+                None,
+                false,
+                options,
+            );
+            let output: Tensor<Wgpu, 3> = Tensor::from_primitive(TensorPrimitive::Float(output));
+            let img = output
+                .into_data_async()
+                .await
+                .to_vec::<f32>()
+                .expect("Wrong type");
+            let i = (8 * 16 + 8) * 4;
+            [img[i], img[i + 1], img[i + 2], img[i + 3]]
+        }
+    };
+
+    let white = render([1.0; 3], ColorSpace::DisplayP3).await;
+    for c in &white[..3] {
+        assert_approx_eq!(c, white[3], 1e-2);
+    }
+
+    let srgb = render([1.0, 0.0, 0.0], ColorSpace::Srgb).await;
+    let p3 = render([1.0, 0.0, 0.0], ColorSpace::DisplayP3).await;
+    assert!(p3[0] < srgb[0], "Red should be less saturated in P3");
+    assert!(
+        p3[1] > 0.1 && p3[2] > 0.05,
+        "Red in P3 mixes in green and blue"
+    );
+}
@@ -0,0 +1,218 @@
+//! Visualize which parts of a model are under-constrained by its training views.
+//!
+//! A splat seen by few training views can take almost any shape that explains those views,
+//! and a pixel that no splats cover has nothing constraining it at all. Rendering
+//! [`uncertainty_splats`] over [`UNCERTAIN_COLOR`] shows both: every splat is colored by how
+//! many training views it was in, and the accumulated alpha shortfall of a pixel lets the
+//! background show through. Regions that render red need more photos, and
+//! [`suggest_views`] proposes where to take them from.
+use burn::tensor::{Tensor, TensorData};
+use glam::{Mat3, Quat, Vec3};
+
+use crate::{camera::Camera, gaussian_splats::Splats, render::SH_C0, Backend, RenderOptions};
+
+/// Color of a fully unconstrained pixel, shown where the splats don't add up to full alpha.
+pub const UNCERTAIN_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+
+// Number of views after which a splat is considered half constrained.
+const HALF_CONFIDENT_VIEWS: f32 = 4.0;
+
+/// Count in how many of the given views each splat is visible, as a `[num_splats]` tensor.
+///
+/// Views are `(camera, image size)` pairs, eg. the training views of a dataset. A splat
+/// counts as visible when its center is in the view frustum. This only projects the centers
+/// instead of rendering the views, so it's cheap enough to redo while training.
+pub fn view_counts<B: Backend>(
+    splats: &Splats<B>,
+    views: &[(Camera, glam::UVec2)],
+) -> Tensor<B, 1> {
+    let device = splats.means.device();
+    let n = splats.num_splats();
+    let means = splats.means.val();
+
+    views
+        .iter()
+        .fold(Tensor::zeros([n], &device), |counts, (camera, _)| {
+            let view = camera.world_to_local();
+            // Row major, the data of the transpose, to multiply the row vectors of the means.
+            let rotation = Tensor::<B, 2>::from_data(
+                TensorData::new(Mat3::from_mat4(view).to_cols_array().to_vec(), [3, 3]),
+                &device,
+            );
+            let translation = Tensor::<B, 2>::from_data(
+                TensorData::new(view.w_axis.truncate().to_array().to_vec(), [1, 3]),
+                &device,
+            );
+            let local = means.clone().matmul(rotation) + translation;
+            let x = local.clone().slice([0..n, 0..1]);
+            let y = local.clone().slice([0..n, 1..2]);
+            let z = local.slice([0..n, 2..3]);
+
+            // The image spans these slopes of x / z and y / z, see `Camera::ray`.
+            let tan_half = glam::vec2(
+                (camera.fov_x * 0.5).tan() as f32,
+                (camera.fov_y * 0.5).tan() as f32,
+            );
+            let lo = -camera.center_uv * 2.0 * tan_half;
+            let hi = (1.0 - camera.center_uv) * 2.0 * tan_half;
+
+            let visible = z.clone().greater_elem(0.01).float()
+                * (x.clone() - z.clone() * lo.x)
+                    .greater_equal_elem(0.0)
+                    .float()
+                * (z.clone() * hi.x - x).greater_equal_elem(0.0).float()
+                * (y.clone() - z.clone() * lo.y)
+                    .greater_equal_elem(0.0)
+                    .float()
+                * (z * hi.y - y).greater_equal_elem(0.0).float();
+            counts + visible.reshape([n])
+        })
+}
+
+/// Uncertainty of each splat from its view count, from 1 for a splat no view saw, towards 0
+/// for a splat seen by many views.
+pub fn splat_uncertainty<B: Backend>(view_counts: Tensor<B, 1>) -> Tensor<B, 1> {
+    (view_counts + HALF_CONFIDENT_VIEWS).recip() * HALF_CONFIDENT_VIEWS
+}
+
+/// The splats colored by their uncertainty, green for splats seen by many views to red for
+/// splats seen by none. Render these with [`crate::RenderMode::Color`] over
+/// [`UNCERTAIN_COLOR`].
+pub fn uncertainty_splats<B: Backend>(splats: &Splats<B>, view_counts: Tensor<B, 1>) -> Splats<B> {
+    let n = splats.num_splats();
+    let uncertainty = splat_uncertainty(view_counts).reshape([n, 1]);
+    let device = uncertainty.device();
+
+    let rgb = Tensor::cat(
+        vec![
+            uncertainty.clone(),
+            uncertainty.ones_like() - uncertainty,
+            Tensor::zeros([n, 1], &device),
+        ],
+        1,
+    );
+    // Only a base color, so the color doesn't change with the view direction.
+    let sh_coeffs = ((rgb - 0.5) / SH_C0).reshape([n, 1, 3]);
+
+    Splats::from_tensor_data(
+        splats.means.val(),
+        splats.rotation.val(),
+        splats.log_scales.val(),
+        sh_coeffs,
+        splats.raw_opacity.val(),
+    )
+}
+
+// Resolution the candidate views of `suggest_views` are scored at.
+const CANDIDATE_SIZE: u32 = 128;
+
+// Mean uncertainty of a render of uncertainty colored splats over `UNCERTAIN_COLOR`: the red
+// of the splats plus the alpha shortfall.
+fn mean_uncertainty<B: Backend>(
+    splats: &Splats<B>,
+    camera: &Camera,
+    img_size: glam::UVec2,
+) -> Tensor<B, 1> {
+    let img = splats.render_inference(camera, img_size, false, RenderOptions::default());
+    let [h, w, _] = img.dims();
+    let red = img.clone().slice([0..h, 0..w, 0..1]);
+    let alpha = img.slice([0..h, 0..w, 3..4]);
+    (red - alpha + 1.0).mean()
+}
+
+// Candidate views near the given views: each view orbited around the center of the scene,
+// and tilted up and down. This keeps candidates at a similar distance and orientation as the
+// views taken so far, which are poses that can actually be reached.
+fn candidate_views(views: &[(Camera, glam::UVec2)], max_base_views: usize) -> Vec<Camera> {
+    let positions: Vec<_> = views.iter().map(|(cam, _)| cam.position).collect();
+    let center = positions.iter().sum::<Vec3>() / positions.len().max(1) as f32;
+    // Cameras look along +Z with +Y down, so their up is -Y.
+    let up = views
+        .iter()
+        .map(|(cam, _)| cam.rotation * Vec3::NEG_Y)
+        .sum::<Vec3>()
+        .normalize_or(Vec3::NEG_Y);
+
+    let step = views.len().div_ceil(max_base_views).max(1);
+    let yaws = [-90.0f32, -45.0, -20.0, 20.0, 45.0, 90.0, 180.0];
+    let pitches = [-20.0f32, 20.0];
+
+    views
+        .iter()
+        .step_by(step)
+        .flat_map(|(cam, _)| {
+            let right = cam.rotation * Vec3::X;
+            let yawed = yaws.map(|deg| Quat::from_axis_angle(up, deg.to_radians()));
+            let pitched = pitches.map(|deg| Quat::from_axis_angle(right, deg.to_radians()));
+            yawed.into_iter().chain(pitched).map(move |orbit| {
+                Camera::new(
+                    center + orbit * (cam.position - center),
+                    orbit * cam.rotation,
+                    cam.fov_x,
+                    cam.fov_y,
+                    cam.center_uv,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Propose up to `count` camera poses that would best constrain the splats, to guide where
+/// to take more photos. `views` are the views the splats were trained on, see [`view_counts`].
+///
+/// Candidates are generated around the existing views, and picked greedily by how much
+/// uncertainty they see. After each pick, the splats it sees count as one view more
+/// constrained, so the next pick favours a different region. Returns the cameras with the
+/// mean uncertainty each one sees, from 0 to 1.
+pub async fn suggest_views<B: Backend>(
+    splats: &Splats<B>,
+    views: &[(Camera, glam::UVec2)],
+    count: usize,
+) -> Vec<(Camera, f32)> {
+    // Limits the number of candidates scored for every pick.
+    const MAX_BASE_VIEWS: usize = 16;
+
+    if views.is_empty() || splats.num_splats() == 0 {
+        return vec![];
+    }
+
+    let mut candidates = candidate_views(views, MAX_BASE_VIEWS);
+    let mut counts = view_counts(splats, views);
+    let mut suggestions = vec![];
+
+    // Score candidates at a low resolution with the aspect ratio of the first view.
+    let aspect = views[0].1.x as f32 / views[0].1.y.max(1) as f32;
+    let img_size = glam::uvec2(
+        CANDIDATE_SIZE,
+        ((CANDIDATE_SIZE as f32 / aspect).round() as u32).max(1),
+    );
+
+    while suggestions.len() < count && !candidates.is_empty() {
+        let colored = uncertainty_splats(splats, counts.clone());
+        let scores: Vec<_> = candidates
+            .iter()
+            .map(|cam| mean_uncertainty(&colored, cam, img_size))
+            .collect();
+        let Ok(scores) = Tensor::cat(scores, 0)
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+        else {
+            break;
+        };
+
+        let Some((best, &score)) = scores
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        else {
+            break;
+        };
+
+        let camera = candidates.swap_remove(best);
+        counts = counts + view_counts(splats, &[(camera.clone(), img_size)]);
+        suggestions.push((camera, score));
+    }
+
+    suggestions
+}
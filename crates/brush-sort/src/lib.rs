@@ -1,6 +1,6 @@
 use brush_kernel::create_dispatch_buffer;
-use brush_kernel::create_tensor;
 use brush_kernel::create_uniform_buffer;
+use brush_kernel::pool::create_pooled_tensor;
 use brush_kernel::CubeCount;
 use burn::tensor::DType;
 use burn::tensor::Int;
@@ -24,6 +24,10 @@ const ELEMENTS_PER_THREAD: u32 = shaders::sorting::ELEMENTS_PER_THREAD;
 const BLOCK_SIZE: u32 = WG * ELEMENTS_PER_THREAD;
 const BIN_COUNT: u32 = shaders::sorting::BIN_COUNT;
 
+/// The most kernels a [`radix_argsort`] dispatches: two to compute the dispatch sizes and
+/// one to scale them, then four for each pass over 4 bits of a 32 bit key.
+pub const MAX_DISPATCHES: usize = 3 + 4 * 32usize.div_ceil(4);
+
 kernel_source_gen!(SortCount {}, sort_count);
 kernel_source_gen!(SortReduce {}, sort_reduce);
 kernel_source_gen!(SortScanAdd {}, sort_scan_add);
@@ -69,7 +73,7 @@ pub fn radix_argsort(
             client,
         );
 
-        let count_buf = create_tensor::<1, WgpuRuntime>(
+        let count_buf = create_pooled_tensor::<1, WgpuRuntime>(
             [(max_needed_wgs as usize) * 16],
             device,
             client,
@@ -91,8 +95,12 @@ pub fn radix_argsort(
         }
 
         {
-            let reduced_buf =
-                create_tensor::<1, WgpuRuntime>([BLOCK_SIZE as usize], device, client, DType::I32);
+            let reduced_buf = create_pooled_tensor::<1, WgpuRuntime>(
+                [BLOCK_SIZE as usize],
+                device,
+                client,
+                DType::I32,
+            );
 
             // SAFETY: Kernel has to contain no OOB indexing.
             unsafe {
@@ -133,9 +141,10 @@ pub fn radix_argsort(
             }
         }
 
-        let output_keys = create_tensor::<1, _>([max_n as usize], device, client, cur_keys.dtype());
+        let output_keys =
+            create_pooled_tensor::<1, _>([max_n as usize], device, client, cur_keys.dtype());
         let output_values =
-            create_tensor::<1, _>([max_n as usize], device, client, cur_vals.dtype());
+            create_pooled_tensor::<1, _>([max_n as usize], device, client, cur_vals.dtype());
 
         // SAFETY: Kernel has to contain no OOB indexing.
         unsafe {
@@ -160,9 +169,66 @@ pub fn radix_argsort(
     (cur_keys, cur_vals)
 }
 
+/// Same as [`radix_argsort`], but sorts on the CPU.
+///
+/// This reads back the keys and values, so it stalls the GPU. Only meant for adapters where
+/// the sorting kernels are unsupported or impractically slow, like the GL backend.
+pub fn cpu_argsort(
+    input_keys: JitTensor<WgpuRuntime>,
+    input_values: JitTensor<WgpuRuntime>,
+    n_sort: &JitTensor<WgpuRuntime>,
+    sorting_bits: u32,
+) -> (JitTensor<WgpuRuntime>, JitTensor<WgpuRuntime>) {
+    assert_eq!(
+        input_keys.shape.dims[0], input_values.shape.dims[0],
+        "Input keys and values must have the same number of elements"
+    );
+    assert_eq!(n_sort.shape.dims[0], 1, "Sort count must have one element");
+    assert!(sorting_bits <= 32, "Can only sort up to 32 bits");
+
+    let _span = tracing::trace_span!("CPU sort").entered();
+
+    let client = &input_keys.client.clone();
+    let read = |tensor: &JitTensor<WgpuRuntime>| -> Vec<u32> {
+        bytemuck::cast_slice(&client.read_one(tensor.handle.clone().binding())).to_vec()
+    };
+
+    let mut keys = read(&input_keys);
+    let mut values = read(&input_values);
+    let n = (read(n_sort)[0] as usize).min(keys.len());
+
+    let mask = if sorting_bits == 32 {
+        u32::MAX
+    } else {
+        (1 << sorting_bits) - 1
+    };
+    let mut order: Vec<(u32, u32)> = keys[..n]
+        .iter()
+        .zip(&values[..n])
+        .map(|(&k, &v)| (k, v))
+        .collect();
+    // Stable, like the radix sort.
+    order.sort_by_key(|&(k, _)| k & mask);
+    for (i, (k, v)) in order.into_iter().enumerate() {
+        keys[i] = k;
+        values[i] = v;
+    }
+
+    let upload = |data: &[u32], like: &JitTensor<WgpuRuntime>| {
+        JitTensor::new_contiguous(
+            client.clone(),
+            like.device.clone(),
+            like.shape.clone(),
+            client.create(bytemuck::cast_slice(data)),
+            like.dtype(),
+        )
+    };
+    (upload(&keys, &input_keys), upload(&values, &input_values))
+}
+
 #[cfg(all(test, not(target_family = "wasm")))]
 mod tests {
-    use crate::radix_argsort;
+    use crate::{cpu_argsort, radix_argsort};
     use burn::tensor::{Int, Tensor};
     use burn_wgpu::{JitBackend, WgpuRuntime};
     use rand::Rng;
@@ -277,4 +343,32 @@ mod tests {
             assert_eq!(*val, ref_val as i32);
         }
     }
+
+    #[test]
+    fn test_cpu_sorting() {
+        let keys_inp: Vec<i32> = (0..5000).map(|i| (i * 7919) % 1021).collect();
+        let values_inp: Vec<i32> = (0..5000).collect();
+        // Only sort part of the keys, the rest should be left alone.
+        let n_sort = 4000;
+
+        let device = Default::default();
+        let keys =
+            Tensor::<Backend, 1, Int>::from_ints(keys_inp.as_slice(), &device).into_primitive();
+        let values =
+            Tensor::<Backend, 1, Int>::from_ints(values_inp.as_slice(), &device).into_primitive();
+        let num_points = Tensor::<Backend, 1, Int>::from_ints([n_sort], &device).into_primitive();
+
+        let (gpu_keys, gpu_values) = radix_argsort(keys.clone(), values.clone(), &num_points, 10);
+        let (cpu_keys, cpu_values) = cpu_argsort(keys, values, &num_points, 10);
+
+        let read = |t| {
+            Tensor::<Backend, 1, Int>::from_primitive(t)
+                .to_data()
+                .to_vec::<i32>()
+                .expect("Wrong type")
+        };
+        let n = n_sort as usize;
+        assert_eq!(read(cpu_keys)[..n], read(gpu_keys)[..n]);
+        assert_eq!(read(cpu_values)[..n], read(gpu_values)[..n]);
+    }
 }
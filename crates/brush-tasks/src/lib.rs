@@ -0,0 +1,92 @@
+//! Background work that runs the same way on native and on the web.
+//!
+//! On native, futures run on the tokio runtime, and blocking work runs on tokio's blocking
+//! threads. On the web there is only the main thread, which also runs the UI. Futures run on
+//! the browser's event loop there, and blocking work runs on web workers, so decoding images
+//! or writing a big export doesn't freeze the page.
+//!
+//! Loaders, exporters and eval should spawn work through here instead of picking a runtime
+//! themselves.
+use std::future::Future;
+
+use async_fn_stream::fn_stream;
+use tokio_stream::Stream;
+use tokio_with_wasm::alias as tokio_wasm;
+
+mod progress;
+
+pub use progress::{progress_bar, Progress, ProgressSender};
+pub use tokio_util::sync::CancellationToken;
+pub use tokio_wasm::task::JoinHandle;
+
+/// Run a future in the background.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio_wasm::task::spawn(future)
+}
+
+/// Run a future until it completes or the token is cancelled, whichever happens first.
+///
+/// When cancelled, the future is dropped at its current await point, which drops everything it
+/// owns, like GPU tensors, streams and channels. Returns `None` when cancelled.
+pub async fn until_cancelled<T>(
+    token: &CancellationToken,
+    future: impl Future<Output = T>,
+) -> Option<T> {
+    tokio::select! {
+        biased;
+        () = token.cancelled() => None,
+        value = future => Some(value),
+    }
+}
+
+/// Run CPU heavy work off the async runtime and the UI thread, and wait for the result.
+pub async fn run_blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio_wasm::task::spawn_blocking(work)
+        .await
+        .expect("Background task panicked")
+}
+
+/// Let other tasks run, eg. to keep the UI responsive during a long loop on the web.
+pub async fn yield_now() {
+    tokio_wasm::task::yield_now().await;
+}
+
+/// Number of futures to run at once when loading many things in parallel.
+pub fn parallelism() -> usize {
+    if cfg!(target_family = "wasm") {
+        1
+    } else {
+        std::thread::available_parallelism()
+            .map(|x| x.get())
+            .unwrap_or(8)
+    }
+}
+
+/// Run futures in parallel batches, and stream their results in order.
+pub fn stream_parallel<T: Send + 'static>(
+    futures: Vec<impl Future<Output = T> + Send + 'static>,
+) -> impl Stream<Item = T> {
+    let parallel = parallelism();
+    log::info!("Loading stream with {parallel} threads");
+
+    let mut futures = futures;
+    fn_stream(|emitter| async move {
+        while !futures.is_empty() {
+            // Spawn a batch of tasks.
+            let handles: Vec<_> = futures
+                .drain(..futures.len().min(parallel))
+                .map(spawn)
+                .collect();
+            // Stream each of them.
+            for handle in handles {
+                emitter
+                    .emit(handle.await.expect("Underlying stream panicked"))
+                    .await;
+            }
+        }
+    })
+}
@@ -0,0 +1,79 @@
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// How far along a long operation is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    /// What is being done, eg. "Loading views".
+    pub stage: String,
+    /// Fraction of the stage that is done, from 0 to 1.
+    pub fraction: f32,
+}
+
+/// Reports [`Progress`] to whoever listens, if anyone. Cheap to clone, so every part of an
+/// operation can report its own stage.
+#[derive(Clone, Debug, Default)]
+pub struct ProgressSender {
+    sender: Option<UnboundedSender<Progress>>,
+}
+
+impl ProgressSender {
+    /// A sender, and the receiver its progress arrives on.
+    pub fn channel() -> (Self, UnboundedReceiver<Progress>) {
+        let (sender, receiver) = unbounded_channel();
+        (
+            Self {
+                sender: Some(sender),
+            },
+            receiver,
+        )
+    }
+
+    /// Report that `done` out of `total` items of a stage are done.
+    pub fn report(&self, stage: &str, done: usize, total: usize) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let fraction = if total == 0 {
+            1.0
+        } else {
+            (done as f32 / total as f32).clamp(0.0, 1.0)
+        };
+        // Nobody listening anymore is fine, the work itself carries on.
+        let _ = sender.send(Progress {
+            stage: stage.to_owned(),
+            fraction,
+        });
+    }
+}
+
+/// Draw progress as a text bar, eg. `Loading views [#####     ] 50%`.
+pub fn progress_bar(progress: &Progress, width: usize) -> String {
+    let filled = ((progress.fraction * width as f32).round() as usize).min(width);
+    format!(
+        "{} [{}{}] {:>3.0}%",
+        progress.stage,
+        "#".repeat(filled),
+        " ".repeat(width - filled),
+        progress.fraction * 100.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{progress_bar, Progress, ProgressSender};
+
+    #[test]
+    fn reports_fractions() {
+        let (sender, mut receiver) = ProgressSender::channel();
+        sender.report("Loading", 1, 4);
+        sender.report("Empty", 0, 0);
+        let loading = receiver.try_recv().expect("Missing progress");
+        assert_eq!(loading.fraction, 0.25);
+        assert_eq!(receiver.try_recv().expect("Missing progress").fraction, 1.0);
+
+        // Reporting without a listener does nothing.
+        ProgressSender::default().report("Loading", 1, 2);
+
+        assert_eq!(progress_bar(&loading, 8), "Loading [##      ]  25%");
+    }
+}
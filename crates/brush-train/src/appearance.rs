@@ -0,0 +1,86 @@
+use burn::{
+    module::{Module, Param, ParamId},
+    prelude::Backend,
+    tensor::Tensor,
+};
+
+/// A learned color correction per capture, for datasets that combine several captures of the
+/// same scene.
+///
+/// Captures taken on different days or with different devices differ in exposure, white
+/// balance and lighting. Each capture gets an affine color transform that is applied to the
+/// render before comparing it to the images of that capture, so the shared splats don't have
+/// to average out these differences.
+#[derive(Module, Debug)]
+pub struct CaptureAppearance<B: Backend> {
+    // Affine color transform per capture, [num_captures, 3, 4]. The last column is the offset.
+    pub transforms: Param<Tensor<B, 3>>,
+}
+
+impl<B: Backend> CaptureAppearance<B> {
+    pub fn new(num_captures: usize, device: &B::Device) -> Self {
+        // Starts out as the identity transform.
+        #[rustfmt::skip]
+        let identity = [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+        ];
+        let identity = Tensor::<B, 1>::from_floats(identity, device)
+            .reshape([1, 3, 4])
+            .repeat_dim(0, num_captures)
+            .require_grad();
+        Self {
+            transforms: Param::initialized(ParamId::new(), identity),
+        }
+    }
+
+    /// Add identity transforms for new captures, keeping the learned transforms.
+    pub fn with_captures(self, num_captures: usize) -> Self {
+        let current = self.num_captures();
+        if num_captures <= current {
+            return self;
+        }
+
+        let device = self.transforms.device();
+        let added = Self::new(num_captures - current, &device).transforms.val();
+        let transforms = Tensor::cat(vec![self.transforms.val().detach(), added.detach()], 0);
+        Self {
+            transforms: Param::initialized(ParamId::new(), transforms.require_grad()),
+        }
+    }
+
+    pub fn num_captures(&self) -> usize {
+        self.transforms.dims()[0]
+    }
+
+    /// Apply the color transform of each capture to a batch of RGB images of `[batch, h, w, 3]`.
+    pub fn apply(&self, images: Tensor<B, 4>, capture_ids: &[u32]) -> Tensor<B, 4> {
+        let [batch, h, w, _] = images.dims();
+
+        let transformed = capture_ids
+            .iter()
+            .enumerate()
+            .map(|(i, &capture)| {
+                let capture = (capture as usize).min(self.num_captures() - 1);
+                let transform = self
+                    .transforms
+                    .val()
+                    .slice([capture..capture + 1, 0..3, 0..4])
+                    .reshape([3, 4]);
+                let matrix = transform.clone().slice([0..3, 0..3]);
+                let offset = transform.slice([0..3, 3..4]).reshape([1, 3]);
+
+                let pixels = images
+                    .clone()
+                    .slice([i..i + 1, 0..h, 0..w, 0..3])
+                    .reshape([h * w, 3]);
+                (pixels.matmul(matrix.transpose()) + offset).reshape([1, h, w, 3])
+            })
+            .collect();
+
+        let transformed: Tensor<B, 4> = Tensor::cat(transformed, 0);
+        debug_assert_eq!(transformed.dims()[0], batch);
+        transformed
+    }
+}
@@ -0,0 +1,105 @@
+use brush_render::camera::Camera;
+use glam::Vec3;
+use hashbrown::{HashMap, HashSet};
+
+use crate::intrinsics::ScalarAdam;
+use crate::scene::{Scene, SceneView};
+
+/// Models motion blur and defocus for blurry views.
+///
+/// Flagged views are rendered from several jittered sub-poses which are averaged, so they
+/// contribute useful gradients instead of blurring the scene. The sub-poses are spread
+/// along a linear camera motion over the exposure, and over a circular aperture. Both the
+/// motion and aperture size are learned per view. The aperture is modeled as focused at infinity.
+//
+// The parameters are [motion (3), aperture radius], in world units.
+#[derive(Default, Clone, Debug)]
+pub struct BlurRefiner {
+    flagged: HashSet<String>,
+    params: HashMap<String, ScalarAdam<4>>,
+}
+
+impl BlurRefiner {
+    /// Flag views which are much blurrier than the typical view in the scene.
+    ///
+    /// A view is flagged when its sharpness is below `threshold` times the median sharpness.
+    pub fn flag_blurry_views(&mut self, scene: &Scene, threshold: f32) {
+        let Some(median) = scene.median_sharpness() else {
+            return;
+        };
+
+        self.flagged = scene
+            .views
+            .iter()
+            .filter(|view| view.sharpness < median * threshold)
+            .map(|view| view.name.clone())
+            .collect();
+
+        log::info!(
+            "Modelling blur for {} out of {} views",
+            self.flagged.len(),
+            scene.views.len()
+        );
+    }
+
+    pub fn is_flagged(&self, view: &SceneView) -> bool {
+        self.flagged.contains(&view.name)
+    }
+
+    fn sample_offsets(samples: u32) -> impl Iterator<Item = (f32, glam::Vec2)> {
+        (0..samples).map(move |k| {
+            // Position along the motion, in [-0.5, 0.5].
+            let t = k as f32 / (samples - 1).max(1) as f32 - 0.5;
+            let angle = std::f32::consts::TAU * k as f32 / samples as f32;
+            (t, glam::vec2(angle.cos(), angle.sin()))
+        })
+    }
+
+    /// Get the cameras to render this view from, given the unblurred camera.
+    pub fn sub_cameras(
+        &self,
+        view: &SceneView,
+        camera: &Camera,
+        samples: u32,
+        scene_extent: f32,
+    ) -> Vec<Camera> {
+        let [mx, my, mz, radius] = self.params.get(&view.name).map_or([0.0; 4], |p| p.params);
+        // Keep a small minimum aperture, otherwise all sub-poses are equal and the gradients
+        // of the motion and aperture cancel out.
+        let radius = radius + 1e-3 * scene_extent;
+        let motion = Vec3::new(mx, my, mz);
+
+        let right = camera.rotation * Vec3::X;
+        let up = camera.rotation * Vec3::Y;
+
+        Self::sample_offsets(samples)
+            .map(|(t, disk)| {
+                let mut sub = camera.clone();
+                let offset = motion * t + (right * disk.x + up * disk.y) * radius;
+                sub.position += offset;
+                if let Some(rs) = sub.rolling_shutter.as_mut() {
+                    rs.end_position += offset;
+                }
+                sub
+            })
+            .collect()
+    }
+
+    /// Apply a gradient step, given the gradient of the loss wrt. the position of each sub camera.
+    pub fn step(&mut self, view: &SceneView, camera: &Camera, v_positions: &[Vec3], lr: f64) {
+        let right = camera.rotation * Vec3::X;
+        let up = camera.rotation * Vec3::Y;
+
+        let mut v_motion = Vec3::ZERO;
+        let mut v_radius = 0.0;
+        for ((t, disk), v_pos) in Self::sample_offsets(v_positions.len() as u32).zip(v_positions) {
+            v_motion += *v_pos * t;
+            v_radius += v_pos.dot(right * disk.x + up * disk.y);
+        }
+
+        let params = self.params.entry(view.name.clone()).or_default();
+        params.step([v_motion.x, v_motion.y, v_motion.z, v_radius], lr);
+        // The aperture can't be negative.
+        params.params[3] = params.params[3].max(0.0);
+    }
+}
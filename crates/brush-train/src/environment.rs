@@ -0,0 +1,132 @@
+use brush_render::camera::Camera;
+use burn::{
+    module::{Module, Param, ParamId},
+    prelude::Backend,
+    tensor::{activation::sigmoid, Int, Tensor, TensorData},
+};
+use hashbrown::HashMap;
+
+// Bits of everything the rays of a camera depend on: its rotation, intrinsics and image size,
+// and the size of the map. Not its position, the background is infinitely far away.
+type RayKey = ([u32; 4], [u64; 2], [u32; 2], [u32; 2], [usize; 2]);
+
+// Drop the cached rays once they take this many indices, 256MB.
+const MAX_CACHED_RAYS: usize = 1 << 26;
+
+/// The pixels of cameras looked up in an [`EnvironmentMap`], cached as the same views are
+/// rendered over and over.
+pub struct EnvironmentRays<B: Backend> {
+    indices: HashMap<RayKey, Tensor<B, 1, Int>>,
+    cached: usize,
+}
+
+impl<B: Backend> Default for EnvironmentRays<B> {
+    fn default() -> Self {
+        Self {
+            indices: HashMap::new(),
+            cached: 0,
+        }
+    }
+}
+
+/// A learned background for unbounded scenes, as a low resolution equirectangular map.
+///
+/// The background is infinitely far away, so it only depends on the ray direction. This captures
+/// the sky and distant background, instead of the optimizer smearing huge gaussians at infinity.
+#[derive(Module, Debug)]
+pub struct EnvironmentMap<B: Backend> {
+    // Colors before a sigmoid, [height, width, 3].
+    pub raw_colors: Param<Tensor<B, 3>>,
+}
+
+impl<B: Backend> EnvironmentMap<B> {
+    pub fn new(height: usize, device: &B::Device) -> Self {
+        // Starts out as a gray background.
+        let raw_colors = Tensor::zeros([height, height * 2, 3], device).require_grad();
+        Self {
+            raw_colors: Param::initialized(ParamId::new(), raw_colors),
+        }
+    }
+
+    // Index into the flattened map for the ray through each pixel.
+    fn pixel_indices(
+        camera: &Camera,
+        img_size: glam::UVec2,
+        [height, width]: [usize; 2],
+    ) -> Vec<i32> {
+        let focal = camera.focal(img_size);
+        let center = camera.center(img_size);
+
+        let mut indices = Vec::with_capacity((img_size.x * img_size.y) as usize);
+        for y in 0..img_size.y {
+            for x in 0..img_size.x {
+                let pixel = glam::vec2(x as f32, y as f32) + 0.5;
+                let local = ((pixel - center) / focal).extend(1.0);
+                let dir = (camera.rotation * local).normalize();
+
+                let u = dir.x.atan2(dir.z) / std::f32::consts::TAU + 0.5;
+                let v = dir.y.clamp(-1.0, 1.0).acos() / std::f32::consts::PI;
+                let u = ((u * width as f32) as usize).min(width - 1);
+                let v = ((v * height as f32) as usize).min(height - 1);
+                indices.push((v * width + u) as i32);
+            }
+        }
+        indices
+    }
+
+    /// Render the background as seen by `camera`, as an RGB image of `[h, w, 3]`.
+    pub fn render(
+        &self,
+        rays: &mut EnvironmentRays<B>,
+        camera: &Camera,
+        img_size: glam::UVec2,
+    ) -> Tensor<B, 3> {
+        let [height, width, _] = self.raw_colors.dims();
+        let num_pixels = (img_size.x * img_size.y) as usize;
+
+        let key = (
+            camera.rotation.to_array().map(f32::to_bits),
+            [camera.fov_x.to_bits(), camera.fov_y.to_bits()],
+            camera.center_uv.to_array().map(f32::to_bits),
+            img_size.to_array(),
+            [height, width],
+        );
+        if rays.cached + num_pixels > MAX_CACHED_RAYS {
+            rays.indices.clear();
+            rays.cached = 0;
+        }
+        let indices = rays
+            .indices
+            .entry(key)
+            .or_insert_with(|| {
+                rays.cached += num_pixels;
+                let indices = Self::pixel_indices(camera, img_size, [height, width]);
+                Tensor::from_data(
+                    TensorData::new(indices, [num_pixels]),
+                    &self.raw_colors.device(),
+                )
+            })
+            .clone();
+
+        let colors = sigmoid(self.raw_colors.val().reshape([height * width, 3]));
+        colors
+            .select(0, indices)
+            .reshape([img_size.y as usize, img_size.x as usize, 3])
+    }
+
+    /// Composite a render of `[h, w, 4]` with premultiplied colors over the background, which
+    /// makes it opaque.
+    pub fn composite(
+        &self,
+        rays: &mut EnvironmentRays<B>,
+        image: Tensor<B, 3>,
+        camera: &Camera,
+    ) -> Tensor<B, 3> {
+        let [h, w, _] = image.dims();
+        let background = self.render(rays, camera, glam::uvec2(w as u32, h as u32));
+        let rgb = image.clone().slice([0..h, 0..w, 0..3]);
+        let alpha = image.slice([0..h, 0..w, 3..4]);
+        let rgb = rgb + background * (-alpha.clone() + 1.0);
+        Tensor::cat(vec![rgb, alpha.ones_like()], 2)
+    }
+}
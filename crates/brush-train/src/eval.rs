@@ -1,10 +1,12 @@
 use brush_render::RenderAux;
 use brush_render::{gaussian_splats::Splats, Backend};
+use brush_tasks::ProgressSender;
 use burn::tensor::{ElementConversion, Tensor};
 use image::DynamicImage;
 use rand::seq::IteratorRandom;
 
-use crate::image::image_to_tensor;
+use crate::environment::{EnvironmentMap, EnvironmentRays};
+use crate::image::{composite_on_white, image_to_tensor};
 use crate::scene::{Scene, SceneView};
 use crate::ssim::Ssim;
 
@@ -24,12 +26,19 @@ pub struct EvalStats<B: Backend> {
     pub samples: Vec<EvalView<B>>,
 }
 
+/// Render eval views and compare them against their images. With `white_background`,
+/// transparent images and the renders are composited onto white first, see
+/// [`crate::train::TrainConfig::white_background`]. Renders of opaque images are composited
+/// over the learned background, if any, as in training.
 pub async fn eval_stats<B: Backend>(
     splats: Splats<B>,
     eval_scene: &Scene,
     num_frames: Option<usize>,
+    white_background: bool,
+    environment: Option<(&EnvironmentMap<B>, &mut EnvironmentRays<B>)>,
     rng: &mut impl rand::Rng,
     device: &B::Device,
+    progress: &ProgressSender,
 ) -> EvalStats<B> {
     let indices = if let Some(num) = num_frames {
         (0..eval_scene.views.len()).choose_multiple(rng, num)
@@ -42,16 +51,39 @@ pub async fn eval_stats<B: Backend>(
         .map(|i| eval_scene.views[i].clone())
         .collect();
 
+    let mut environment = environment;
     let mut ret = vec![];
+    let total = eval_views.len();
 
-    for view in eval_views {
+    for (i, view) in eval_views.into_iter().enumerate() {
         // Compare MSE in RGB only, not sure if this should include alpha.
-        let ground_truth: DynamicImage = view.image.clone().to_rgb8().into();
+        let white = white_background && view.image.has_alpha();
+        let image = view.image.clone();
+        let ground_truth: DynamicImage = brush_tasks::run_blocking(move || {
+            if white {
+                image.load().to_rgba8().into()
+            } else {
+                image.load().to_rgb8().into()
+            }
+        })
+        .await;
         let res = glam::uvec2(ground_truth.width(), ground_truth.height());
 
         let gt_tensor = image_to_tensor::<B>(&ground_truth, device);
         let (rendered, aux) = splats.render(&view.camera, res, false);
 
+        let (gt_tensor, rendered) = match &mut environment {
+            _ if white => {
+                let alpha = rendered
+                    .clone()
+                    .slice([0..res.y as usize, 0..res.x as usize, 3..4]);
+                (composite_on_white(gt_tensor), rendered - alpha + 1.0)
+            }
+            Some((env, rays)) if !view.image.has_alpha() => {
+                (gt_tensor, env.composite(rays, rendered, &view.camera))
+            }
+            _ => (gt_tensor, rendered),
+        };
         let render_rgb = rendered
             .slice([0..res.y as usize, 0..res.x as usize, 0..3])
             .clamp_min(0.0);
@@ -73,6 +105,11 @@ pub async fn eval_stats<B: Backend>(
             rendered: render_rgb,
             aux,
         });
+
+        progress.report("Evaluating", i + 1, total);
+
+        // Eval can take a while on big scenes, let the UI update in between views.
+        brush_tasks::yield_now().await;
     }
 
     EvalStats { samples: ret }
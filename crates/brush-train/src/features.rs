@@ -0,0 +1,236 @@
+use brush_render::{camera::Camera, gaussian_splats::Splats, render::SH_C0};
+use burn::{
+    module::{Module, Param, ParamId},
+    prelude::Backend,
+    tensor::{Bool, Tensor},
+};
+
+/// A dense feature map of a view, eg. the CLIP or DINO features of the image, at its own
+/// resolution. Stored row major as `[height, width, dim]`.
+#[derive(Debug, Clone)]
+pub struct FeatureMap {
+    pub width: u32,
+    pub height: u32,
+    pub dim: usize,
+    pub data: Vec<f32>,
+}
+
+impl FeatureMap {
+    pub fn to_tensor<B: Backend>(&self, device: &B::Device) -> Tensor<B, 3> {
+        Tensor::<B, 1>::from_floats(self.data.as_slice(), device).reshape([
+            self.height as usize,
+            self.width as usize,
+            self.dim,
+        ])
+    }
+}
+
+/// A learned feature vector per splat, rendered with the same alpha blending as the colors.
+///
+/// Trained on the feature maps of the views, the rendered features of a new view can be
+/// compared against eg. a CLIP text embedding to find objects in the scene. Feature maps are
+/// best reduced to a handful of dimensions (eg. with PCA) first, every three dimensions cost
+/// an extra render.
+#[derive(Module, Debug)]
+pub struct FeatureField<B: Backend> {
+    // Features of each splat, [n, dim].
+    pub features: Param<Tensor<B, 2>>,
+}
+
+impl<B: Backend> FeatureField<B> {
+    pub fn new(num_splats: usize, dim: usize, device: &B::Device) -> Self {
+        let features = Tensor::<B, 2>::zeros([num_splats, dim], device).require_grad();
+        Self {
+            features: Param::initialized(ParamId::new(), features),
+        }
+    }
+
+    pub fn num_splats(&self) -> usize {
+        self.features.dims()[0]
+    }
+
+    pub fn dim(&self) -> usize {
+        self.features.dims()[1]
+    }
+
+    /// The features of all splats, row major `[n, dim]`.
+    pub async fn read(&self) -> Vec<f32> {
+        self.features
+            .val()
+            .into_data_async()
+            .await
+            .to_vec()
+            .expect("Failed to read features")
+    }
+}
+
+impl<B: brush_render::Backend> FeatureField<B> {
+    /// Render the features as seen by `camera`, as `[h, w, dim]`.
+    ///
+    /// Features are rendered as splat colors, three dimensions at a time. The geometry of the
+    /// splats is detached, so only the features are trained on the result.
+    pub async fn render(
+        &self,
+        splats: &Splats<B>,
+        camera: &Camera,
+        img_size: glam::UVec2,
+    ) -> Tensor<B, 3> {
+        let n = self.num_splats();
+        let dim = self.dim();
+        let features = self.features.val();
+        let device = features.device();
+
+        // Pad up to a multiple of three channels.
+        let padded = dim.div_ceil(3) * 3;
+        let features = if padded > dim {
+            Tensor::cat(vec![features, Tensor::zeros([n, padded - dim], &device)], 1)
+        } else {
+            features
+        };
+
+        let geometry = Splats {
+            means: Param::initialized(ParamId::new(), splats.means.val().detach()),
+            rotation: Param::initialized(ParamId::new(), splats.rotation.val().detach()),
+            log_scales: Param::initialized(ParamId::new(), splats.log_scales.val().detach()),
+            raw_opacity: Param::initialized(ParamId::new(), splats.raw_opacity.val().detach()),
+            ..splats.clone()
+        };
+
+        let [w, h] = [img_size.x as usize, img_size.y as usize];
+        let mut channels = vec![];
+        for start in (0..padded).step_by(3) {
+            let group = features.clone().slice([0..n, start..start + 3]);
+            let sh_coeffs = ((group - 0.5) / SH_C0).reshape([n, 1, 3]);
+            let group_splats = Splats {
+                sh_coeffs: Param::initialized(ParamId::new(), sh_coeffs),
+                xys_dummy: geometry.xys_dummy.clone().detach(),
+                ..geometry.clone()
+            };
+            let (img, aux) = group_splats.render(camera, img_size, false);
+            aux.resolve_bwd_data().await;
+            channels.push(img.slice([0..h, 0..w, 0..3]));
+        }
+
+        Tensor::cat(channels, 2).slice([0..h, 0..w, 0..dim])
+    }
+}
+
+/// Cosine similarity of each row of `features`, `[n, dim]`, with the query feature, as `[n]`.
+///
+/// The query is eg. a CLIP text embedding, reduced to the same dimensions as the feature maps,
+/// or the feature of a picked pixel.
+pub fn feature_similarity<B: Backend>(features: Tensor<B, 2>, query: &[f32]) -> Tensor<B, 1> {
+    let [n, dim] = features.dims();
+    assert_eq!(
+        query.len(),
+        dim,
+        "Query must have the dimensions of the features"
+    );
+
+    let query = Tensor::<B, 1>::from_floats(query, &features.device()).reshape([dim, 1]);
+    let query = query.clone() / (query.powf_scalar(2.0).sum().sqrt() + 1e-12).reshape([1, 1]);
+    let norms = features.clone().powf_scalar(2.0).sum_dim(1).sqrt() + 1e-12;
+    (features.matmul(query) / norms).reshape([n])
+}
+
+impl<B: Backend> FeatureField<B> {
+    /// Select the splats with a feature similar to the query, as a mask of `[n]` in the order
+    /// of the splats. See [`select_splats`] to keep the selection, or
+    /// [`crate::train::prune_points`] to remove it.
+    pub fn select(&self, query: &[f32], threshold: f32) -> Tensor<B, 1, Bool> {
+        feature_similarity(self.features.val(), query).greater_equal_elem(threshold)
+    }
+}
+
+impl<B: brush_render::Backend> FeatureField<B> {
+    /// Select the pixels of a view with a feature similar to the query, as a mask of `[h, w]`.
+    pub async fn query_mask(
+        &self,
+        splats: &Splats<B>,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        query: &[f32],
+        threshold: f32,
+    ) -> Tensor<B, 2, Bool> {
+        let [h, w] = [img_size.y as usize, img_size.x as usize];
+        let features = self.render(splats, camera, img_size).await;
+        feature_similarity(features.reshape([h * w, self.dim()]), query)
+            .greater_equal_elem(threshold)
+            .reshape([h, w])
+    }
+
+    /// The rendered feature of a pixel, eg. to query for everything similar to it.
+    pub async fn pick(
+        &self,
+        splats: &Splats<B>,
+        camera: &Camera,
+        img_size: glam::UVec2,
+        pixel: glam::UVec2,
+    ) -> Vec<f32> {
+        let dim = self.dim();
+        let pixel = pixel.min(img_size - 1);
+        let (x, y) = (pixel.x as usize, pixel.y as usize);
+        self.render(splats, camera, img_size)
+            .await
+            .slice([y..y + 1, x..x + 1, 0..dim])
+            .into_data_async()
+            .await
+            .to_vec()
+            .expect("Failed to read feature")
+    }
+}
+
+/// The splats where the mask is set.
+pub async fn select_splats<B: brush_render::Backend>(
+    splats: &Splats<B>,
+    mask: Tensor<B, 1, Bool>,
+) -> Splats<B> {
+    let indices = mask.argwhere_async().await.squeeze(1);
+    Splats::from_tensor_data(
+        splats.means.val().select(0, indices.clone()),
+        splats.rotation.val().select(0, indices.clone()),
+        splats.log_scales.val().select(0, indices.clone()),
+        splats.sh_coeffs.val().select(0, indices.clone()),
+        splats.raw_opacity.val().select(0, indices),
+    )
+}
+
+/// The features of the splats where the mask is set, matching [`select_splats`].
+pub async fn select_features<B: Backend>(
+    features: &FeatureField<B>,
+    mask: Tensor<B, 1, Bool>,
+) -> FeatureField<B> {
+    let indices = mask.argwhere_async().await.squeeze(1);
+    FeatureField {
+        features: Param::initialized(ParamId::new(), features.features.val().select(0, indices)),
+    }
+}
+
+/// Color of selected splats in [`highlight_selection`].
+pub const SELECTION_COLOR: [f32; 3] = [1.0, 0.35, 0.0];
+
+/// The splats with the selected ones tinted, to show a selection.
+pub fn highlight_selection<B: brush_render::Backend>(
+    splats: &Splats<B>,
+    mask: Tensor<B, 1, Bool>,
+) -> Splats<B> {
+    let [n, coeffs, _] = splats.sh_coeffs.dims();
+    let sh = splats.sh_coeffs.val();
+    let color =
+        Tensor::<B, 1>::from_floats(SELECTION_COLOR.map(|c| (c - 0.5) / SH_C0), &sh.device())
+            .reshape([1, 1, 3]);
+
+    let base = sh.clone().slice([0..n, 0..1, 0..3]);
+    let tinted = base.clone() * 0.3 + color * 0.7;
+    let base = base.mask_where(mask.reshape([n, 1, 1]).expand([n, 1, 3]), tinted);
+    let sh_coeffs = if coeffs > 1 {
+        Tensor::cat(vec![base, sh.slice([0..n, 1..coeffs, 0..3])], 1)
+    } else {
+        base
+    };
+
+    Splats {
+        sh_coeffs: Param::initialized(ParamId::new(), sh_coeffs),
+        ..splats.clone()
+    }
+}
@@ -0,0 +1,151 @@
+//! Freezing groups of splat parameters while training, eg. to lock the positions after a
+//! number of steps, or to only train the colors of an imported model.
+use std::{fmt, str::FromStr};
+
+use burn::config::Config;
+
+/// A group of splat parameters that is optimized separately.
+#[derive(Config, Debug, Copy, PartialEq, Eq)]
+pub enum ParamGroup {
+    Means,
+    Rotations,
+    Scales,
+    Opacity,
+    /// The spherical harmonics coefficients, ie. the colors.
+    ShCoeffs,
+}
+
+impl ParamGroup {
+    pub const ALL: [Self; 5] = [
+        Self::Means,
+        Self::Rotations,
+        Self::Scales,
+        Self::Opacity,
+        Self::ShCoeffs,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Means => "means",
+            Self::Rotations => "rotations",
+            Self::Scales => "scales",
+            Self::Opacity => "opacity",
+            Self::ShCoeffs => "sh",
+        }
+    }
+
+    /// Whether the group is part of the shape of the splats, which refinement changes.
+    pub fn is_geometry(&self) -> bool {
+        matches!(self, Self::Means | Self::Rotations | Self::Scales)
+    }
+}
+
+impl fmt::Display for ParamGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ParamGroup {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        match s.as_str() {
+            "positions" => return Ok(Self::Means),
+            "colors" => return Ok(Self::ShCoeffs),
+            _ => {}
+        }
+        Self::ALL
+            .into_iter()
+            .find(|g| g.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|g| g.name()).collect();
+                anyhow::anyhow!(
+                    "Unknown parameters {s}, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Keep a group of parameters fixed from a step on, and optionally until a later step.
+#[derive(Config, Debug, PartialEq)]
+pub struct FreezeRule {
+    pub group: ParamGroup,
+    #[config(default = 0)]
+    pub from_iter: u32,
+    /// The first step where the group is trained again, `None` to keep it frozen.
+    pub until_iter: Option<u32>,
+}
+
+impl FreezeRule {
+    pub fn is_active(&self, iter: u32) -> bool {
+        iter >= self.from_iter && !self.until_iter.is_some_and(|until| iter >= until)
+    }
+}
+
+/// Parses `group`, `group:from` or `group:from:until`, eg. `means:7000` to lock the means
+/// after 7000 steps.
+impl FromStr for FreezeRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let group = parts.next().unwrap_or_default().parse()?;
+        let mut step = |name: &str| -> anyhow::Result<Option<u32>> {
+            parts
+                .next()
+                .map(|v| {
+                    v.trim()
+                        .parse()
+                        .map_err(|_e| anyhow::anyhow!("Invalid {name} step {v}"))
+                })
+                .transpose()
+        };
+        let from_iter = step("start")?.unwrap_or(0);
+        let until_iter = step("end")?;
+        anyhow::ensure!(parts.next().is_none(), "Expected group:from:until, got {s}");
+        Ok(Self {
+            group,
+            from_iter,
+            until_iter,
+        })
+    }
+}
+
+/// Whether `group` is frozen at `iter` by any of the rules.
+pub fn is_frozen(rules: &[FreezeRule], group: ParamGroup, iter: u32) -> bool {
+    rules.iter().any(|r| r.group == group && r.is_active(iter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_frozen, FreezeRule, ParamGroup};
+
+    #[test]
+    fn freeze_schedule() {
+        let rules = vec![
+            FreezeRule::new(ParamGroup::Means).with_from_iter(7000),
+            FreezeRule::new(ParamGroup::Opacity).with_until_iter(Some(100)),
+        ];
+        assert!(!is_frozen(&rules, ParamGroup::Means, 6999));
+        assert!(is_frozen(&rules, ParamGroup::Means, 30000));
+        assert!(is_frozen(&rules, ParamGroup::Opacity, 0));
+        assert!(!is_frozen(&rules, ParamGroup::Opacity, 100));
+        assert!(!is_frozen(&rules, ParamGroup::ShCoeffs, 0));
+        assert_eq!(
+            "colors".parse::<ParamGroup>().ok(),
+            Some(ParamGroup::ShCoeffs)
+        );
+
+        let rule: FreezeRule = "opacity:10:20".parse().expect("Valid rule");
+        assert_eq!(
+            rule,
+            FreezeRule::new(ParamGroup::Opacity)
+                .with_from_iter(10)
+                .with_until_iter(Some(20))
+        );
+        assert!("means:soon".parse::<FreezeRule>().is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! Estimate the ground a scene rests on, and the direction of gravity.
+//!
+//! Gravity is taken from the cameras, which are held upright on average. The ground is the
+//! dominant plane of the SfM points that is roughly perpendicular to it, and has most of the
+//! scene above it. The viewer aligns its up axis to the ground, exports can be leveled to it
+//! and the shadow catcher is placed on it.
+use brush_render::camera::Camera;
+use glam::{Mat3, Vec3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::simplify::symmetric_eigen;
+
+/// A plane the scene rests on, the points `p` where `normal.dot(p) == height`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GroundPlane {
+    /// Up direction, pointing away from the ground.
+    pub normal: Vec3,
+    pub height: f32,
+}
+
+// Points used to find the plane at most, plenty for a robust fit.
+const MAX_POINTS: usize = 4096;
+const RANSAC_ITERATIONS: usize = 256;
+// How far the ground can tilt from the camera up direction, in degrees.
+const MAX_TILT: f32 = 25.0;
+// Fraction of the points that can be below the ground, from noise and holes in it.
+const MAX_BELOW: f32 = 0.2;
+// Fraction of points the ground needs to support, or it's just noise.
+const MIN_INLIERS: f32 = 0.03;
+// Fraction of points below the ground when there's no clear plane.
+const GROUND_PERCENTILE: f32 = 0.05;
+
+/// The average up direction of the cameras, `None` if they don't agree on it.
+pub fn camera_up(cameras: &[Camera]) -> Option<Vec3> {
+    // Cameras look down +z with y pointing down the image.
+    let up: Vec3 = cameras.iter().map(|c| c.rotation * Vec3::NEG_Y).sum();
+    if up.length() < 0.5 * cameras.len() as f32 {
+        return None;
+    }
+    up.try_normalize()
+}
+
+impl GroundPlane {
+    /// Estimate the ground below some points, eg. the SfM points of a scene, seen by the
+    /// given cameras. See the [module docs](self).
+    ///
+    /// Returns `None` if there are no points, or the cameras don't agree on up.
+    pub fn estimate(points: &[Vec3], cameras: &[Camera]) -> Option<Self> {
+        let up = camera_up(cameras)?;
+        if points.is_empty() {
+            return None;
+        }
+
+        let step = points.len().div_ceil(MAX_POINTS).max(1);
+        let points: Vec<_> = points
+            .iter()
+            .step_by(step)
+            .copied()
+            .filter(|p| p.is_finite())
+            .collect();
+
+        let mut heights: Vec<f32> = points.iter().map(|p| up.dot(*p)).collect();
+        heights.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |f: f32| heights[((heights.len() - 1) as f32 * f) as usize];
+        let fallback = Self {
+            normal: up,
+            height: percentile(GROUND_PERCENTILE),
+        };
+
+        if points.len() < 3 {
+            return Some(fallback);
+        }
+
+        // Points this close to the plane support it.
+        let threshold = (percentile(0.95) - percentile(0.05)) * 0.01;
+        let min_dot = MAX_TILT.to_radians().cos();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut best: Option<(usize, Self)> = None;
+
+        for _ in 0..RANSAC_ITERATIONS {
+            let [a, b, c] = [(); 3].map(|_| points[rng.gen_range(0..points.len())]);
+            let Some(normal) = (b - a).cross(c - a).try_normalize() else {
+                continue;
+            };
+            let normal = if normal.dot(up) < 0.0 {
+                -normal
+            } else {
+                normal
+            };
+            if normal.dot(up) < min_dot {
+                continue;
+            }
+
+            let plane = Self {
+                normal,
+                height: normal.dot(a),
+            };
+            let (mut inliers, mut below) = (0, 0);
+            for p in &points {
+                let dist = plane.distance(*p);
+                if dist.abs() < threshold {
+                    inliers += 1;
+                } else if dist < 0.0 {
+                    below += 1;
+                }
+            }
+
+            if below as f32 > MAX_BELOW * points.len() as f32 {
+                continue;
+            }
+            if best.as_ref().is_some_and(|(count, _)| inliers <= *count) {
+                continue;
+            }
+            best = Some((inliers, plane));
+        }
+
+        let Some((count, plane)) = best else {
+            return Some(fallback);
+        };
+        if (count as f32) < MIN_INLIERS * points.len() as f32 {
+            return Some(fallback);
+        }
+
+        // Refit the plane to all its inliers, the normal is the direction they vary least in.
+        let inliers: Vec<_> = points
+            .iter()
+            .filter(|p| plane.distance(**p).abs() < threshold)
+            .copied()
+            .collect();
+        let center = inliers.iter().sum::<Vec3>() / inliers.len() as f32;
+        let covariance = inliers.iter().fold(Mat3::ZERO, |acc, p| {
+            let d = *p - center;
+            acc + Mat3::from_cols(d * d.x, d * d.y, d * d.z)
+        });
+        let (values, vectors) = symmetric_eigen(covariance);
+        let smallest = if values.x <= values.y && values.x <= values.z {
+            0
+        } else if values.y <= values.z {
+            1
+        } else {
+            2
+        };
+        let normal = vectors.col(smallest).normalize();
+        let normal = if normal.dot(up) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+
+        Some(Self {
+            normal,
+            height: normal.dot(center),
+        })
+    }
+
+    /// Signed distance of a point above the plane.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) - self.height
+    }
+
+    /// Project a point onto the plane.
+    pub fn project(&self, point: Vec3) -> Vec3 {
+        point - self.normal * self.distance(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_render::camera::Camera;
+    use glam::{Quat, Vec3};
+
+    use super::GroundPlane;
+
+    fn upright_cameras() -> Vec<Camera> {
+        // Cameras looking at the origin from the side, held upright with y up.
+        (0..4)
+            .map(|i| {
+                let rotation = Quat::from_rotation_y(i as f32 * 1.5)
+                    * Quat::from_rotation_x(std::f32::consts::PI);
+                Camera::new(
+                    rotation * Vec3::NEG_Z * 5.0,
+                    rotation,
+                    0.8,
+                    0.8,
+                    glam::vec2(0.5, 0.5),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_tilted_ground_below_object() {
+        let tilt = Quat::from_rotation_z(0.1);
+        // A tilted floor, with a column of points standing on it and some noise below.
+        let mut points = vec![];
+        for i in 0..40 {
+            for j in 0..40 {
+                let p = Vec3::new(i as f32 * 0.1 - 2.0, -1.0, j as f32 * 0.1 - 2.0);
+                points.push(tilt * p);
+            }
+        }
+        for i in 0..400 {
+            points.push(Vec3::new(
+                (i % 7) as f32 * 0.05,
+                -0.9 + (i / 7) as f32 * 0.03,
+                0.0,
+            ));
+        }
+        points.extend((0..20).map(|i| Vec3::new(i as f32 * 0.1, -3.0, 0.0)));
+
+        let plane = GroundPlane::estimate(&points, &upright_cameras()).expect("Cameras agree");
+        assert!((plane.normal - tilt * Vec3::Y).length() < 1e-3);
+        assert!(plane.distance(tilt * Vec3::new(0.5, -1.0, 0.5)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn projects_onto_plane() {
+        let plane = GroundPlane {
+            normal: Vec3::Y,
+            height: -1.0,
+        };
+        let projected = plane.project(Vec3::new(1.0, 3.0, 2.0));
+        assert!((projected - Vec3::new(1.0, -1.0, 2.0)).length() < 1e-5);
+    }
+}
@@ -17,6 +17,20 @@ pub fn image_to_tensor<B: Backend>(image: &DynamicImage, device: &B::Device) ->
     Tensor::from_data(tensor_data, device)
 }
 
+/// Composite images with a straight alpha channel onto white, from `[.., 4]` to `[.., 3]`.
+pub fn composite_on_white<B: Backend, const D: usize>(images: Tensor<B, D>) -> Tensor<B, D> {
+    let rgb = images.clone().narrow(D - 1, 0, 3);
+    let alpha = images.narrow(D - 1, 3, 1);
+    rgb * alpha.clone() - alpha + 1.0
+}
+
+/// Convert a mask or confidence map to a single channel tensor of `[h, w, 1]`.
+pub fn mask_to_tensor<B: Backend>(mask: &DynamicImage, device: &B::Device) -> Tensor<B, 3> {
+    let (w, h) = (mask.width(), mask.height());
+    let tensor_data = TensorData::new(mask.to_luma32f().into_vec(), [h as usize, w as usize, 1]);
+    Tensor::from_data(tensor_data, device)
+}
+
 pub trait TensorDataToImage {
     fn into_image(self) -> DynamicImage;
 }
@@ -44,3 +58,32 @@ pub fn tensor_into_image(data: TensorData) -> DynamicImage {
 
     img
 }
+
+/// Variance of the laplacian of the luminance, a simple measure of how sharp an image is.
+///
+/// The image is downscaled first so scores are comparable between resolutions.
+pub fn image_sharpness(image: &DynamicImage) -> f32 {
+    let gray = image
+        .resize(512, 512, image::imageops::FilterType::Triangle)
+        .to_luma32f();
+    let (w, h) = gray.dimensions();
+
+    if w < 3 || h < 3 {
+        return 0.0;
+    }
+
+    let px = |x: u32, y: u32| gray.get_pixel(x, y).0[0];
+
+    let mut sum = 0.0;
+    let mut sum_sq = 0.0;
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let lap = px(x - 1, y) + px(x + 1, y) + px(x, y - 1) + px(x, y + 1) - 4.0 * px(x, y);
+            sum += lap;
+            sum_sq += lap * lap;
+        }
+    }
+    let count = ((w - 2) * (h - 2)) as f32;
+    let mean = sum / count;
+    sum_sq / count - mean * mean
+}
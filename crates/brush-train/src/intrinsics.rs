@@ -0,0 +1,92 @@
+use brush_render::camera::Camera;
+use hashbrown::HashMap;
+
+use crate::scene::SceneView;
+
+const BETA_1: f32 = 0.9;
+const BETA_2: f32 = 0.999;
+const EPSILON: f32 = 1e-8;
+
+// A handful of parameters optimized with Adam on the CPU. Camera parameters are tiny,
+// and need to be on the CPU anyway to build the camera uniforms.
+#[derive(Clone, Debug)]
+pub(crate) struct ScalarAdam<const N: usize> {
+    pub(crate) params: [f32; N],
+    moment_1: [f32; N],
+    moment_2: [f32; N],
+    steps: i32,
+}
+
+impl<const N: usize> Default for ScalarAdam<N> {
+    fn default() -> Self {
+        Self {
+            params: [0.0; N],
+            moment_1: [0.0; N],
+            moment_2: [0.0; N],
+            steps: 0,
+        }
+    }
+}
+
+impl<const N: usize> ScalarAdam<N> {
+    pub(crate) fn step(&mut self, grads: [f32; N], lr: f64) {
+        self.steps += 1;
+
+        let bias_1 = 1.0 - BETA_1.powi(self.steps);
+        let bias_2 = 1.0 - BETA_2.powi(self.steps);
+
+        for (i, grad) in grads.into_iter().enumerate() {
+            if !grad.is_finite() {
+                continue;
+            }
+            self.moment_1[i] = BETA_1 * self.moment_1[i] + (1.0 - BETA_1) * grad;
+            self.moment_2[i] = BETA_2 * self.moment_2[i] + (1.0 - BETA_2) * grad * grad;
+
+            let m = self.moment_1[i] / bias_1;
+            let v = self.moment_2[i] / bias_2;
+            self.params[i] -= lr as f32 * m / (v.sqrt() + EPSILON);
+        }
+    }
+}
+
+/// Learnable focal length and principal point corrections, shared per camera id.
+//
+// The parameters are [relative focal x, relative focal y, center u, center v], such that
+// focal' = focal * (1 + p[0..2]) and center_uv' = center_uv + p[2..4]. This keeps all
+// parameters in a similar range so a single learning rate works.
+#[derive(Default, Clone, Debug)]
+pub struct IntrinsicsRefiner {
+    offsets: HashMap<u32, ScalarAdam<4>>,
+}
+
+impl IntrinsicsRefiner {
+    /// Apply the current refined intrinsics of this view to `camera`.
+    pub fn refine_camera(&self, view: &SceneView, camera: &mut Camera) {
+        if let Some(offset) = self.offsets.get(&view.camera_id) {
+            let [fx, fy, cu, cv] = offset.params;
+            // Scaling the focal length scales tan(fov / 2) inversely.
+            camera.fov_x = 2.0 * ((camera.fov_x * 0.5).tan() / (1.0 + fx as f64)).atan();
+            camera.fov_y = 2.0 * ((camera.fov_y * 0.5).tan() / (1.0 + fy as f64)).atan();
+            camera.center_uv += glam::vec2(cu, cv);
+        }
+    }
+
+    /// Apply a gradient step, given the gradient of the loss wrt. `[fx, fy, cx, cy]` in pixels
+    /// as rendered for `view`.
+    pub fn step(
+        &mut self,
+        view: &SceneView,
+        img_size: glam::UVec2,
+        v_intrinsics: [f32; 4],
+        lr: f64,
+    ) {
+        // Chain rule from pixel units to the parametrization of the offsets.
+        let focal = view.camera.focal(img_size);
+        let scale = [focal.x, focal.y, img_size.x as f32, img_size.y as f32];
+        let grads = [0, 1, 2, 3].map(|i| v_intrinsics[i] * scale[i]);
+        self.offsets
+            .entry(view.camera_id)
+            .or_default()
+            .step(grads, lr);
+    }
+}
@@ -1,9 +1,26 @@
+pub mod appearance;
 pub mod eval;
 pub mod ssim;
 pub mod train;
 
+pub mod blur;
+pub mod environment;
+pub mod features;
+pub mod freeze;
+pub mod ground;
 pub mod image;
+pub mod intrinsics;
+pub mod lighting;
+pub mod loss;
+pub mod perceptual;
+pub mod pyramid;
+pub mod residency;
+pub mod rolling_shutter;
+pub mod sampler;
 pub mod scene;
+pub mod shadow_catcher;
+pub mod simplify;
+pub mod tracking;
 
 mod adam_scaled;
 mod stats;
@@ -0,0 +1,204 @@
+use brush_render::{gaussian_splats::Splats, render::SH_C0};
+use burn::{
+    module::{Module, Param, ParamId},
+    prelude::Backend,
+    tensor::Tensor,
+};
+
+// Number of spherical harmonics up to degree 2.
+const NUM_COEFFS: usize = 9;
+
+// Normalization of the degree 0 spherical harmonic.
+const SH_Y0: f32 = 0.282_094_8;
+
+/// A decomposition of the splat colors into an albedo and a shared low order lighting, so
+/// captures can be relit a little.
+///
+/// Splats are treated as diffuse surfels: the base color of a splat is its albedo, times the
+/// shading of its normal. The shading is a degree 2 spherical harmonic of the normal, which
+/// represents any distant lighting on diffuse surfaces well. Higher SH bands of the splats
+/// keep view dependent effects, which aren't relit.
+#[derive(Module, Debug)]
+pub struct LightingModel<B: Backend> {
+    // Shading coefficients per color channel, [9, 3].
+    pub coeffs: Param<Tensor<B, 2>>,
+}
+
+// Spherical harmonics basis up to degree 2 for a batch of directions, [n, 3] -> [n, 9].
+fn sh_basis<B: Backend>(dirs: Tensor<B, 2>) -> Tensor<B, 2> {
+    let n = dirs.dims()[0];
+    let x = dirs.clone().slice([0..n, 0..1]);
+    let y = dirs.clone().slice([0..n, 1..2]);
+    let z = dirs.slice([0..n, 2..3]);
+
+    Tensor::cat(
+        vec![
+            x.ones_like() * SH_Y0,
+            y.clone() * 0.488_602_5,
+            z.clone() * 0.488_602_5,
+            x.clone() * 0.488_602_5,
+            x.clone() * y.clone() * 1.092_548,
+            y.clone() * z.clone() * 1.092_548,
+            (z.clone() * z.clone() * 3.0 - 1.0) * 0.315_391_57,
+            x.clone() * z * 1.092_548,
+            (x.clone() * x - y.clone() * y) * 0.546_274,
+        ],
+        1,
+    )
+}
+
+// Axis of a batch of quaternions (w, x, y, z) for the given column of their rotation matrix.
+fn rotation_axis<B: Backend>(quats: Tensor<B, 2>, axis: usize) -> Tensor<B, 2> {
+    let n = quats.dims()[0];
+    let w = quats.clone().slice([0..n, 0..1]);
+    let x = quats.clone().slice([0..n, 1..2]);
+    let y = quats.clone().slice([0..n, 2..3]);
+    let z = quats.slice([0..n, 3..4]);
+
+    let column = match axis {
+        0 => vec![
+            -(y.clone() * y + z.clone() * z) * 2.0 + 1.0,
+            (x.clone() * y + w.clone() * z.clone()) * 2.0,
+            (x * z - w * y) * 2.0,
+        ],
+        1 => vec![
+            (x.clone() * y.clone() - w.clone() * z.clone()) * 2.0,
+            -(x.clone() * x.clone() + z.clone() * z.clone()) * 2.0 + 1.0,
+            (y * z + w * x) * 2.0,
+        ],
+        _ => vec![
+            (x.clone() * z.clone() + w.clone() * y.clone()) * 2.0,
+            (y.clone() * z - w * x.clone()) * 2.0,
+            -(x.clone() * x + y.clone() * y) * 2.0 + 1.0,
+        ],
+    };
+    Tensor::cat(column, 1)
+}
+
+/// Normals of the splats as their shortest axis, `[n, 3]`, see
+/// [`brush_render::gaussian_splats::estimate_normal`].
+///
+/// Splats are two sided, so normals are oriented away from the center of the splats. That is
+/// right for object captures, and consistently flipped for captures from inside a room,
+/// which the learned shading absorbs either way.
+pub fn splat_normals<B: brush_render::Backend>(splats: &Splats<B>) -> Tensor<B, 2> {
+    let n = splats.num_splats();
+    let quats = splats.rotation.val().detach();
+    let quats = quats.clone() / (quats.powf_scalar(2.0).sum_dim(1).sqrt() + 1e-12);
+
+    let log_scales = splats.log_scales.val().detach();
+    let shortest = log_scales
+        .clone()
+        .equal(log_scales.min_dim(1).repeat_dim(1, 3))
+        .float();
+
+    let normals = (0..3)
+        .map(|axis| {
+            rotation_axis(quats.clone(), axis) * shortest.clone().slice([0..n, axis..axis + 1])
+        })
+        .reduce(|a, b| a + b)
+        .expect("Splats have three axes");
+    // Ties between axes are rare, renormalize for them.
+    let normals = normals.clone() / (normals.powf_scalar(2.0).sum_dim(1).sqrt() + 1e-12);
+
+    let means = splats.means.val().detach();
+    let outward = means.clone() - means.mean_dim(0);
+    let facing = (normals.clone() * outward)
+        .sum_dim(1)
+        .greater_equal_elem(0.0)
+        .float();
+    normals * (facing * 2.0 - 1.0)
+}
+
+impl<B: Backend> LightingModel<B> {
+    pub fn new(device: &B::Device) -> Self {
+        // Starts out as a uniform shading of 1.
+        let mut coeffs = [[0.0; 3]; NUM_COEFFS];
+        coeffs[0] = [1.0 / SH_Y0; 3];
+        let coeffs = Tensor::<B, 2>::from_floats(coeffs, device).require_grad();
+        Self {
+            coeffs: Param::initialized(ParamId::new(), coeffs),
+        }
+    }
+
+    /// Shading of surfaces with the given normals, as `[n, 3]` RGB multipliers.
+    pub fn shading(&self, normals: Tensor<B, 2>) -> Tensor<B, 2> {
+        sh_basis(normals).matmul(self.coeffs.val()).clamp_min(0.0)
+    }
+
+    /// The shading coefficients, per degree 2 spherical harmonic and color channel.
+    pub async fn coefficients(&self) -> Vec<[f32; 3]> {
+        self.coeffs
+            .val()
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+            .expect("Failed to read lighting")
+            .chunks_exact(3)
+            .map(|c| [c[0], c[1], c[2]])
+            .collect()
+    }
+}
+
+impl<B: brush_render::Backend> LightingModel<B> {
+    /// The splats lit by this lighting, taking the splat colors as albedo.
+    ///
+    /// Gradients flow back to the albedo and the lighting, and all other parameters are
+    /// shared, so the lit splats can be trained on directly.
+    pub fn shade(&self, splats: &Splats<B>) -> Splats<B> {
+        let [n, coeffs, _] = splats.sh_coeffs.dims();
+        let sh = splats.sh_coeffs.val();
+        let albedo = sh.clone().slice([0..n, 0..1, 0..3]).reshape([n, 3]) * SH_C0 + 0.5;
+        let lit = albedo * self.shading(splat_normals(splats));
+        let base = ((lit - 0.5) / SH_C0).reshape([n, 1, 3]);
+
+        let sh_coeffs = if coeffs > 1 {
+            Tensor::cat(vec![base, sh.slice([0..n, 1..coeffs, 0..3])], 1)
+        } else {
+            base
+        };
+
+        Splats {
+            sh_coeffs: Param::initialized(ParamId::new(), sh_coeffs),
+            ..splats.clone()
+        }
+    }
+}
+
+/// The albedo splats and lighting of a model trained with [`LightingModel`], to relight it.
+#[derive(Clone, Debug)]
+pub struct Decomposition<B: brush_render::Backend> {
+    pub albedo: Splats<B>,
+    pub lighting: LightingModel<B>,
+}
+
+#[cfg(test)]
+mod tests {
+    use burn::{
+        backend::{wgpu::WgpuDevice, Wgpu},
+        tensor::Tensor,
+    };
+    use glam::Quat;
+
+    use super::rotation_axis;
+
+    #[test]
+    fn rotation_axes_match_glam() {
+        let quat = Quat::from_euler(glam::EulerRot::XYZ, 0.2, -0.4, 0.3);
+
+        let device = WgpuDevice::DefaultDevice;
+        let quats = Tensor::<Wgpu, 1>::from_floats([quat.w, quat.x, quat.y, quat.z], &device)
+            .reshape([1, 4]);
+        for (axis, reference) in [glam::Vec3::X, glam::Vec3::Y, glam::Vec3::Z]
+            .iter()
+            .enumerate()
+        {
+            let result: Vec<f32> = rotation_axis(quats.clone(), axis)
+                .into_data()
+                .to_vec()
+                .expect("Wrong type");
+            let result = glam::vec3(result[0], result[1], result[2]);
+            assert!((quat * *reference - result).length() < 1e-6);
+        }
+    }
+}
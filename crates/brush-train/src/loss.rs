@@ -0,0 +1,90 @@
+use brush_render::{gaussian_splats::Splats, RenderAux};
+use burn::{config::Config, prelude::Backend, tensor::Tensor};
+
+use crate::scene::SceneView;
+
+/// Per pixel photometric loss between the rendered and ground truth images.
+#[derive(Config, Debug, PartialEq)]
+pub enum PhotometricLoss {
+    L1,
+    L2,
+    /// Quadratic below `huber_delta`, linear above. Less sensitive to outliers than L2,
+    /// but smooth around zero unlike L1.
+    Huber,
+    /// A smooth approximation of L1, `sqrt(x^2 + eps^2)`.
+    Charbonnier,
+}
+
+impl PhotometricLoss {
+    /// The loss for each element of `pred` and `gt`.
+    pub fn elementwise<B: Backend, const D: usize>(
+        &self,
+        pred: Tensor<B, D>,
+        gt: Tensor<B, D>,
+        huber_delta: f32,
+        charbonnier_eps: f32,
+    ) -> Tensor<B, D> {
+        let diff = pred - gt;
+
+        match self {
+            Self::L1 => diff.abs(),
+            Self::L2 => diff.powf_scalar(2.0),
+            Self::Huber => {
+                // Scaled so the slope matches L1 above delta.
+                let abs = diff.abs();
+                let quadratic = abs.clone().powf_scalar(2.0) * (0.5 / huber_delta);
+                let linear = abs.clone() - 0.5 * huber_delta;
+                quadratic.mask_where(abs.greater_equal_elem(huber_delta), linear)
+            }
+            Self::Charbonnier => (diff.powf_scalar(2.0) + charbonnier_eps * charbonnier_eps).sqrt(),
+        }
+    }
+}
+
+/// Mean of `loss` ([batch, h, w, c]) weighted by per pixel `weights` ([batch, h, w, 1]).
+pub fn weighted_mean<B: Backend>(
+    loss: Tensor<B, 4>,
+    weights: Option<Tensor<B, 4>>,
+) -> Tensor<B, 1> {
+    match weights {
+        None => loss.mean(),
+        Some(weights) => {
+            // Normalize by the total weight, so masking out pixels doesn't
+            // lower the loss of the remaining ones.
+            let weight_mean = weights.clone().mean().clamp_min(1e-6);
+            (loss * weights).mean() / weight_mean
+        }
+    }
+}
+
+/// What a training step rendered, and what it should look like, for a [`LossTerm`].
+pub struct RenderPackage<'a, B: brush_render::Backend> {
+    /// Training step, counting from 0.
+    pub iter: u32,
+    /// The splats that were rendered.
+    pub splats: &'a Splats<B>,
+    /// The rendered views, `[batch, h, w, 4]` with alpha.
+    pub pred_images: &'a Tensor<B, 4>,
+    /// The rendered colors with any background, ground and appearance model applied, as they
+    /// are compared to the ground truth, `[batch, h, w, 3]`.
+    pub pred_rgb: &'a Tensor<B, 4>,
+    /// The render aux of each view, eg. to regularize visible splats.
+    pub auxes: &'a [RenderAux<B>],
+    /// The ground truth images, `[batch, h, w, c]` with c 3 or 4.
+    pub gt_images: &'a Tensor<B, 4>,
+    pub gt_views: &'a [SceneView],
+    /// Weight of each pixel, `[batch, h, w, 1]`, when views have masks.
+    pub loss_weights: Option<&'a Tensor<B, 4>>,
+}
+
+/// A custom term of the training loss, eg. a regularizer from a paper. Terms are added with
+/// [`crate::train::SplatTrainer::add_loss_term`], and their losses are added to the loss of
+/// every step, so they're weighted by the term itself.
+pub trait LossTerm<B: brush_render::Backend>: Send + Sync {
+    /// Name of the term, for profiling.
+    fn name(&self) -> &str;
+
+    /// The loss of this step, a tensor of one element. Gradients flow back to the splats
+    /// through the tensors of `render`.
+    fn loss(&self, render: &RenderPackage<'_, B>) -> Tensor<B, 1>;
+}
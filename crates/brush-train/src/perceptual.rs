@@ -0,0 +1,81 @@
+use brush_render::safetensor_utils::safetensor_to_burn;
+use burn::tensor::{
+    activation::relu,
+    backend::Backend,
+    module::{conv2d, max_pool2d},
+    ops::ConvOptions,
+    Tensor,
+};
+use safetensors::SafeTensors;
+
+// The conv layers of the first three blocks of VGG16, as indices into `features`.
+// Deeper layers add a lot of cost for little gain in sharpness.
+const VGG_BLOCKS: [&[usize]; 3] = [&[0, 2], &[5, 7], &[10, 12, 14]];
+
+/// A perceptual loss comparing VGG16 features of the rendered and ground truth images.
+///
+/// The network weights aren't trained, they're loaded from a safetensors file of a VGG16
+/// classifier in the torchvision layout (`features.0.weight`, ...).
+pub struct PerceptualLoss<B: Backend> {
+    blocks: Vec<Vec<(Tensor<B, 4>, Tensor<B, 1>)>>,
+}
+
+impl<B: Backend> PerceptualLoss<B> {
+    pub fn from_safetensors(data: &[u8], device: &B::Device) -> anyhow::Result<Self> {
+        let tensors = SafeTensors::deserialize(data)?;
+
+        let blocks = VGG_BLOCKS
+            .iter()
+            .map(|layers| {
+                layers
+                    .iter()
+                    .map(|i| {
+                        let weight = tensors.tensor(&format!("features.{i}.weight"))?;
+                        let bias = tensors.tensor(&format!("features.{i}.bias"))?;
+                        anyhow::Ok((
+                            safetensor_to_burn::<B, 4>(&weight, device),
+                            safetensor_to_burn::<B, 1>(&bias, device),
+                        ))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self { blocks })
+    }
+
+    /// Perceptual distance between two rgb images of `[batch, h, w, 3]`.
+    pub fn loss(&self, pred: Tensor<B, 4>, gt: Tensor<B, 4>) -> Tensor<B, 1> {
+        let device = pred.device();
+        let batch = pred.dims()[0];
+
+        // Run both images through the network at once, in NCHW.
+        let images = Tensor::cat(vec![pred, gt], 0).permute([0, 3, 1, 2]);
+
+        // Normalize with the ImageNet statistics VGG was trained with.
+        let mean =
+            Tensor::<B, 1>::from_floats([0.485, 0.456, 0.406], &device).reshape([1, 3, 1, 1]);
+        let std = Tensor::<B, 1>::from_floats([0.229, 0.224, 0.225], &device).reshape([1, 3, 1, 1]);
+        let mut x = (images - mean) / std;
+
+        let mut loss = Tensor::zeros([1], &device);
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            if i > 0 {
+                x = max_pool2d(x, [2, 2], [2, 2], [0, 0], [1, 1]);
+            }
+
+            for (weight, bias) in block {
+                let options = ConvOptions::new([1, 1], [1, 1], [1, 1], 1);
+                x = relu(conv2d(x, weight.clone(), Some(bias.clone()), options));
+            }
+
+            let [_, c, h, w] = x.dims();
+            let pred_features = x.clone().slice([0..batch, 0..c, 0..h, 0..w]);
+            let gt_features = x.clone().slice([batch..batch * 2, 0..c, 0..h, 0..w]);
+            loss = loss + (pred_features - gt_features).abs().mean();
+        }
+
+        loss / self.blocks.len() as f32
+    }
+}
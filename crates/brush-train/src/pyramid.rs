@@ -0,0 +1,98 @@
+//! Training images kept on the GPU as a pyramid of downscaled copies, like a mip-mapped
+//! texture. Each image is uploaded once, and lower resolutions are read from the pyramid
+//! instead of resizing and uploading the image again.
+use burn::{prelude::Backend, tensor::Tensor};
+use image::DynamicImage;
+
+use crate::image::{image_to_tensor, mask_to_tensor};
+
+// Levels are made until the image is this small.
+const MIN_LEVEL_SIZE: usize = 32;
+
+#[derive(Clone, Debug)]
+pub struct ImagePyramid<B: Backend> {
+    // Level 0 is the full image of `[h, w, c]`, every next level is half the size.
+    levels: Vec<Tensor<B, 3>>,
+}
+
+// Average each 2x2 block of pixels. An odd last row or column is dropped.
+fn downsample<B: Backend>(image: Tensor<B, 3>) -> Tensor<B, 3> {
+    let [h, w, c] = image.dims();
+    let (h, w) = (h / 2, w / 2);
+    image
+        .slice([0..h * 2, 0..w * 2])
+        .reshape([h, 2, w, 2, c])
+        .mean_dim(3)
+        .mean_dim(1)
+        .reshape([h, w, c])
+}
+
+impl<B: Backend> ImagePyramid<B> {
+    /// Build the pyramid of an image of `[h, w, c]`, on the device of the image.
+    pub fn new(image: Tensor<B, 3>) -> Self {
+        let mut levels = vec![image];
+        loop {
+            let [h, w, _] = levels[levels.len() - 1].dims();
+            if h.min(w) < MIN_LEVEL_SIZE * 2 {
+                break;
+            }
+            levels.push(downsample(levels[levels.len() - 1].clone()));
+        }
+        Self { levels }
+    }
+
+    /// Upload an image and build its pyramid, see [`image_to_tensor`].
+    pub fn from_image(image: &DynamicImage, device: &B::Device) -> Self {
+        Self::new(image_to_tensor(image, device))
+    }
+
+    /// Upload a mask and build its pyramid, see [`mask_to_tensor`].
+    pub fn from_mask(mask: &DynamicImage, device: &B::Device) -> Self {
+        Self::new(mask_to_tensor(mask, device))
+    }
+
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The image at `1 / 2^level` of the full resolution, or the smallest level there is.
+    pub fn level(&self, level: usize) -> Tensor<B, 3> {
+        self.levels[level.min(self.levels.len() - 1)].clone()
+    }
+
+    /// GPU memory used by all levels, in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.levels
+            .iter()
+            .map(|l| l.shape().num_elements() as u64 * 4)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use burn::{
+        backend::{wgpu::WgpuDevice, Wgpu},
+        tensor::{Tensor, TensorData},
+    };
+
+    use super::ImagePyramid;
+
+    #[test]
+    fn levels_average_blocks() {
+        let device = WgpuDevice::DefaultDevice;
+        let (h, w) = (129, 256);
+        let values: Vec<f32> = (0..h * w).map(|i| ((i % w) / 2) as f32).collect();
+        let image = Tensor::<Wgpu, 3>::from_data(TensorData::new(values, [h, w, 1]), &device);
+        let pyramid = ImagePyramid::new(image);
+
+        // 129x256, 64x128 and 32x64.
+        assert_eq!(pyramid.num_levels(), 3);
+        assert_eq!(pyramid.level(2).dims(), [32, 64, 1]);
+        assert_eq!(pyramid.level(10).dims(), [32, 64, 1]);
+
+        // Each 2x2 block of the first level has one value.
+        let level: Vec<f32> = pyramid.level(1).into_data().to_vec().expect("Wrong type");
+        assert!((0..64 * 128).all(|i| level[i] == (i % 128) as f32));
+    }
+}
@@ -0,0 +1,249 @@
+//! Keeps the images of huge datasets within a memory budget.
+//!
+//! A dataset of thousands of 4K images doesn't fit in memory decoded, let alone on the GPU.
+//! [`ViewImage`] keeps the encoded file of an image and decodes it when it's needed, keeping
+//! the most recently used images decoded within the budget of a [`HostImageCache`]. The GPU
+//! copies of the images are kept within a budget the same way, by the scene loader.
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use image::DynamicImage;
+
+/// A least recently used cache, holding values up to a budget of bytes.
+pub struct LruBudget<K, V> {
+    entries: HashMap<K, (V, u64, u64)>,
+    budget: u64,
+    bytes: u64,
+    clock: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruBudget<K, V> {
+    pub fn new(budget: u64) -> Self {
+        Self {
+            entries: HashMap::new(),
+            budget,
+            bytes: 0,
+            clock: 0,
+        }
+    }
+
+    /// The value of `key` if it's cached, marking it as used.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.clock += 1;
+        let (value, _, last_used) = self.entries.get_mut(key)?;
+        *last_used = self.clock;
+        Some(value.clone())
+    }
+
+    /// Cache a value that takes up `bytes`, evicting the least recently used values until it
+    /// fits. A value bigger than the whole budget is still kept, until the next insert.
+    pub fn insert(&mut self, key: K, value: V, bytes: u64) {
+        self.clock += 1;
+        if let Some((_, old_bytes, _)) = self.entries.remove(&key) {
+            self.bytes -= old_bytes;
+        }
+        while self.bytes + bytes > self.budget {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, _, last_used))| *last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some((_, evicted, _)) = self.entries.remove(&oldest) {
+                self.bytes -= evicted;
+            }
+        }
+        self.bytes += bytes;
+        self.entries.insert(key, (value, bytes, self.clock));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes taken up by the cached values.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+/// Decoded images of [`ViewImage`]s, shared by the views of a dataset.
+pub struct HostImageCache {
+    images: Mutex<LruBudget<u64, Arc<DynamicImage>>>,
+    next_id: AtomicU64,
+}
+
+impl HostImageCache {
+    /// A cache keeping decoded images up to `budget` bytes.
+    pub fn new(budget: u64) -> Arc<Self> {
+        Arc::new(Self {
+            images: Mutex::new(LruBudget::new(budget)),
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    fn get(&self, id: u64) -> Option<Arc<DynamicImage>> {
+        self.images.lock().expect("Image cache poisoned").get(&id)
+    }
+
+    fn insert(&self, id: u64, image: Arc<DynamicImage>) {
+        let bytes = image.as_bytes().len() as u64;
+        self.images
+            .lock()
+            .expect("Image cache poisoned")
+            .insert(id, image, bytes);
+    }
+}
+
+/// Decodes an encoded image again, with the same processing as when the dataset was loaded.
+pub type DecodeFn = dyn Fn(&[u8]) -> DynamicImage + Send + Sync;
+
+#[derive(Clone)]
+enum ImageData {
+    Decoded(Arc<DynamicImage>),
+    Encoded {
+        id: u64,
+        bytes: Arc<[u8]>,
+        decode: Arc<DecodeFn>,
+        cache: Arc<HostImageCache>,
+    },
+}
+
+/// The image of a view, either kept decoded or decoded when it's needed.
+#[derive(Clone)]
+pub struct ViewImage {
+    width: u32,
+    height: u32,
+    has_alpha: bool,
+    data: ImageData,
+}
+
+impl ViewImage {
+    /// Keep the encoded file of `image`, and decode it with `decode` when it's needed. The
+    /// decoded image is kept in `cache` until it's evicted.
+    pub fn encoded(
+        image: DynamicImage,
+        bytes: Vec<u8>,
+        decode: Arc<DecodeFn>,
+        cache: &Arc<HostImageCache>,
+    ) -> Self {
+        let id = cache.next_id.fetch_add(1, Ordering::Relaxed);
+        let view_image = Self {
+            width: image.width(),
+            height: image.height(),
+            has_alpha: image.color().has_alpha(),
+            data: ImageData::Encoded {
+                id,
+                bytes: bytes.into(),
+                decode,
+                cache: cache.clone(),
+            },
+        };
+        // The image was just decoded, it's likely used again soon.
+        cache.insert(id, Arc::new(image));
+        view_image
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.has_alpha
+    }
+
+    /// Whether the image is kept decoded, instead of decoded when it's needed.
+    pub fn is_resident(&self) -> bool {
+        matches!(self.data, ImageData::Decoded(_))
+    }
+
+    /// The decoded image. This decodes the image again if it was evicted, so it can take a
+    /// while.
+    pub fn load(&self) -> Arc<DynamicImage> {
+        match &self.data {
+            ImageData::Decoded(image) => image.clone(),
+            ImageData::Encoded {
+                id,
+                bytes,
+                decode,
+                cache,
+            } => {
+                if let Some(image) = cache.get(*id) {
+                    return image;
+                }
+                let _span = tracing::trace_span!("Decode evicted image").entered();
+                let image = Arc::new(decode(bytes));
+                cache.insert(*id, image.clone());
+                image
+            }
+        }
+    }
+}
+
+impl From<DynamicImage> for ViewImage {
+    fn from(image: DynamicImage) -> Self {
+        Arc::new(image).into()
+    }
+}
+
+impl From<Arc<DynamicImage>> for ViewImage {
+    fn from(image: Arc<DynamicImage>) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            has_alpha: image.color().has_alpha(),
+            data: ImageData::Decoded(image),
+        }
+    }
+}
+
+impl std::fmt::Debug for ViewImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ViewImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("has_alpha", &self.has_alpha)
+            .field("resident", &self.is_resident())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruBudget;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut lru = LruBudget::new(10);
+        lru.insert(1, "a", 4);
+        lru.insert(2, "b", 4);
+        assert_eq!(lru.get(&1), Some("a"));
+
+        // Doesn't fit, 2 was used longest ago.
+        lru.insert(3, "c", 4);
+        assert_eq!(lru.get(&2), None);
+        assert_eq!(lru.get(&1), Some("a"));
+        assert_eq!(lru.bytes(), 8);
+
+        // Bigger than the budget, evicts everything else.
+        lru.insert(4, "d", 20);
+        assert_eq!(lru.len(), 1);
+        assert_eq!(lru.get(&4), Some("d"));
+    }
+}
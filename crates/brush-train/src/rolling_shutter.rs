@@ -0,0 +1,157 @@
+use brush_render::camera::{Camera, RollingShutter};
+use glam::{Mat4, Quat, Vec3};
+use hashbrown::HashMap;
+
+use crate::intrinsics::ScalarAdam;
+use crate::scene::SceneView;
+
+// The parameters are [start (3), end (3)], as position offsets in world units and
+// rotations as scaled axes in the local camera frame.
+#[derive(Default, Clone, Debug)]
+struct ShutterPoses {
+    positions: ScalarAdam<6>,
+    rotations: ScalarAdam<6>,
+}
+
+/// Learnable start and end poses for rolling shutter views, stored per view.
+///
+/// Views without a rolling shutter pose start out with equal start and end poses.
+#[derive(Default, Clone, Debug)]
+pub struct RollingShutterRefiner {
+    poses: HashMap<String, ShutterPoses>,
+}
+
+/// Gradient of the loss wrt. the start and end pose of a rolling shutter camera.
+pub struct ShutterGrads {
+    /// Gradient of the view matrix translation.
+    pub v_trans: [Vec3; 2],
+    /// Gradient of a small rotation `w` applied to the view rotation, as `exp([w]x) * R`.
+    pub v_rot: [Vec3; 2],
+}
+
+// Gradient of the camera position, and of a rotation of the camera in its local frame, given
+// the gradients of the view matrix of a camera with `rotation` and `world_to_local` transform.
+fn camera_pose_grad(
+    rotation: Quat,
+    world_to_local: Mat4,
+    v_trans: Vec3,
+    v_rot: Vec3,
+) -> (Vec3, Vec3) {
+    // The view translation is -R^T * position, so the position gradient is -R * v_trans.
+    let v_position = -(rotation * v_trans);
+
+    // Rotating the camera by r in its local frame rotates the view matrix by exp(-[r]x),
+    // both its rotation and translation, as the position stays fixed.
+    let trans = world_to_local.w_axis.truncate();
+    let v_local = -(v_rot + trans.cross(v_trans));
+    (v_position, v_local)
+}
+
+fn scaled_axis(p: &[f32; 6], i: usize) -> Vec3 {
+    Vec3::new(p[3 * i], p[3 * i + 1], p[3 * i + 2])
+}
+
+impl RollingShutterRefiner {
+    /// Apply the current refined rolling shutter poses of this view to `camera`.
+    pub fn refine_camera(&self, view: &SceneView, camera: &mut Camera) {
+        let mut rolling_shutter = camera.rolling_shutter.unwrap_or(RollingShutter {
+            end_position: camera.position,
+            end_rotation: camera.rotation,
+        });
+
+        if let Some(poses) = self.poses.get(&view.name) {
+            let (pos, rot) = (&poses.positions.params, &poses.rotations.params);
+            camera.position += scaled_axis(pos, 0);
+            camera.rotation =
+                (camera.rotation * Quat::from_scaled_axis(scaled_axis(rot, 0))).normalize();
+            rolling_shutter.end_position += scaled_axis(pos, 1);
+            rolling_shutter.end_rotation = (rolling_shutter.end_rotation
+                * Quat::from_scaled_axis(scaled_axis(rot, 1)))
+            .normalize();
+        }
+        camera.rolling_shutter = Some(rolling_shutter);
+    }
+
+    /// Apply a gradient step, given the gradients of the start and end view matrix, as
+    /// rendered with `camera`.
+    ///
+    /// The rotations are refined in the local frame of the camera. The gradient is taken at
+    /// the current refined pose, which is a close approximation for the small corrections
+    /// rolling shutter needs.
+    pub fn step(
+        &mut self,
+        view: &SceneView,
+        camera: &Camera,
+        grads: &ShutterGrads,
+        lr_position: f64,
+        lr_rotation: f64,
+    ) {
+        let end_rotation = camera
+            .rolling_shutter
+            .map_or(camera.rotation, |rs| rs.end_rotation);
+        let views = [camera.world_to_local(), camera.world_to_local_end()];
+
+        let mut v_pos = [0.0; 6];
+        let mut v_rot = [0.0; 6];
+        for (i, rotation) in [camera.rotation, end_rotation].into_iter().enumerate() {
+            let (v_position, v_local) =
+                camera_pose_grad(rotation, views[i], grads.v_trans[i], grads.v_rot[i]);
+            v_pos[3 * i..3 * i + 3].copy_from_slice(&v_position.to_array());
+            v_rot[3 * i..3 * i + 3].copy_from_slice(&v_local.to_array());
+        }
+
+        let poses = self.poses.entry(view.name.clone()).or_default();
+        poses.positions.step(v_pos, lr_position);
+        poses.rotations.step(v_rot, lr_rotation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_render::camera::Camera;
+    use glam::{vec3, Mat3, Quat, Vec3};
+
+    use super::camera_pose_grad;
+
+    #[test]
+    fn pose_grad_matches_finite_differences() {
+        // Loss of a single point in camera space, L = dot(a, R * x + t).
+        let x = vec3(0.3, -0.2, 2.0);
+        let a = vec3(0.5, -1.0, 0.25);
+        let loss = |cam: &Camera| a.dot(cam.world_to_local().transform_point3(x));
+
+        let cam = Camera::new(
+            vec3(0.1, 0.2, -1.0),
+            Quat::from_euler(glam::EulerRot::XYZ, 0.1, -0.3, 0.2),
+            0.5,
+            0.5,
+            glam::vec2(0.5, 0.5),
+        );
+
+        // Gradients of the view matrix, as the projection kernel computes them.
+        let view = cam.world_to_local();
+        let v_trans = a;
+        let v_r = Mat3::from_cols(a * x.x, a * x.y, a * x.z);
+        let m = v_r * Mat3::from_mat4(view).transpose();
+        let v_rot = vec3(
+            m.y_axis.z - m.z_axis.y,
+            m.z_axis.x - m.x_axis.z,
+            m.x_axis.y - m.y_axis.x,
+        );
+
+        let (v_position, v_local) = camera_pose_grad(cam.rotation, view, v_trans, v_rot);
+
+        let eps = 1e-3;
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            let mut moved = cam.clone();
+            moved.position += axis * eps;
+            let numeric = (loss(&moved) - loss(&cam)) / eps;
+            assert!((numeric - v_position.dot(axis)).abs() < 1e-2);
+
+            let mut rotated = cam.clone();
+            rotated.rotation = cam.rotation * Quat::from_scaled_axis(axis * eps);
+            let numeric = (loss(&rotated) - loss(&cam)) / eps;
+            assert!((numeric - v_local.dot(axis)).abs() < 1e-2);
+        }
+    }
+}
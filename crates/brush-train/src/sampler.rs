@@ -0,0 +1,114 @@
+use burn::config::Config;
+use rand::{distributions::WeightedIndex, prelude::Distribution, seq::SliceRandom, Rng};
+
+/// How the training views are picked each step.
+#[derive(Config, Debug, PartialEq)]
+pub enum ViewSampling {
+    /// Visit all views once in a random order, then reshuffle.
+    Shuffle,
+    /// Prefer views with a high recent loss, or that haven't been visited much.
+    Priority,
+}
+
+// How much of a new loss is blended into the running loss of a view.
+const LOSS_DECAY: f32 = 0.3;
+
+/// Picks the next training view, see [`ViewSampling`].
+pub struct ViewSampler {
+    mode: ViewSampling,
+    shuffled: Vec<usize>,
+    losses: Vec<Option<f32>>,
+    visits: Vec<u32>,
+}
+
+impl ViewSampler {
+    pub fn new(num_views: usize, mode: ViewSampling) -> Self {
+        Self {
+            mode,
+            shuffled: vec![],
+            losses: vec![None; num_views],
+            visits: vec![0; num_views],
+        }
+    }
+
+    /// Whether the sampler uses the losses passed to [`Self::report_loss`].
+    pub fn needs_loss(&self) -> bool {
+        self.mode == ViewSampling::Priority
+    }
+
+    pub fn report_loss(&mut self, index: usize, loss: f32) {
+        if let Some(running) = self.losses.get_mut(index) {
+            *running = Some(match *running {
+                Some(prev) => prev * (1.0 - LOSS_DECAY) + loss * LOSS_DECAY,
+                None => loss,
+            });
+        }
+    }
+
+    pub fn next(&mut self, rng: &mut impl Rng) -> usize {
+        let num_views = self.visits.len();
+        assert!(num_views > 0, "Need at least one view in dataset");
+
+        let index = match self.mode {
+            ViewSampling::Shuffle => self.shuffled.pop().unwrap_or_else(|| {
+                self.shuffled = (0..num_views).collect();
+                self.shuffled.shuffle(rng);
+                self.shuffled.pop().expect("Shuffled views can't be empty")
+            }),
+            ViewSampling::Priority => {
+                // Views without a loss yet are treated as the worst view seen so far,
+                // so everything gets visited early on.
+                let max_loss = self.losses.iter().flatten().fold(0.0f32, |a, &b| a.max(b));
+                let mean_loss = self.losses.iter().flatten().sum::<f32>()
+                    / self.losses.iter().flatten().count().max(1) as f32;
+
+                let weights = self.losses.iter().zip(&self.visits).map(|(loss, visits)| {
+                    let loss = loss.unwrap_or(max_loss);
+                    // Exploration bonus, so low loss views are still revisited.
+                    let bonus = mean_loss / (1.0 + *visits as f32).sqrt();
+                    (loss + bonus).max(1e-6)
+                });
+
+                match WeightedIndex::new(weights) {
+                    Ok(dist) => dist.sample(rng),
+                    Err(_) => rng.gen_range(0..num_views),
+                }
+            }
+        };
+
+        self.visits[index] += 1;
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn priority_prefers_high_loss() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut sampler = ViewSampler::new(4, ViewSampling::Priority);
+
+        for i in 0..4 {
+            sampler.report_loss(i, if i == 2 { 1.0 } else { 0.01 });
+        }
+
+        let mut counts = [0; 4];
+        for _ in 0..1000 {
+            counts[sampler.next(&mut rng)] += 1;
+        }
+        assert!(counts[2] > counts[0] + counts[1] + counts[3]);
+    }
+
+    #[test]
+    fn shuffle_visits_all() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut sampler = ViewSampler::new(5, ViewSampling::Shuffle);
+        let mut seen: Vec<_> = (0..5).map(|_| sampler.next(&mut rng)).collect();
+        seen.sort_unstable();
+        assert_eq!(seen, vec![0, 1, 2, 3, 4]);
+    }
+}
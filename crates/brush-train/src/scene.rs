@@ -1,4 +1,7 @@
+use crate::features::FeatureMap;
+use crate::residency::ViewImage;
 use brush_render::{bounding_box::BoundingBox, camera::Camera};
+use burn::{prelude::Backend, tensor::Tensor};
 use glam::Vec3;
 use std::sync::Arc;
 
@@ -13,7 +16,46 @@ pub enum ViewType {
 pub struct SceneView {
     pub name: String,
     pub camera: Camera,
-    pub image: Arc<image::DynamicImage>,
+    pub image: ViewImage,
+    /// Optional per pixel loss weight, eg. a mask or confidence map. Black pixels are
+    /// ignored, white pixels are fully weighted.
+    pub mask: Option<Arc<image::DynamicImage>>,
+    /// Identifier of the physical camera that captured this view. Views with the same
+    /// id share their intrinsics.
+    pub camera_id: u32,
+    /// Index of the capture this view belongs to, when a dataset combines several captures
+    /// of the same scene, eg. taken on different days or with different devices.
+    pub capture_id: u32,
+    /// Sharpness of the image, see [`crate::image::image_sharpness`]. Computed once at load
+    /// time, and used to find blurry views.
+    pub sharpness: f32,
+    /// Optional feature map of the view, eg. CLIP or DINO features, to train the features of
+    /// the splats on, see [`crate::features::FeatureField`].
+    pub features: Option<Arc<FeatureMap>>,
+    /// Optional measured depth of the view, eg. from an RGB-D sensor, to supervise the depth
+    /// of the splats with, see [`crate::train::TrainConfig::depth_weight`].
+    pub depth: Option<Arc<DepthMap>>,
+}
+
+/// A depth map in meters, eg. from an RGB-D sensor, at its own resolution. Pixels without a
+/// measured depth are 0. Stored row major as `[height, width]`.
+#[derive(Debug, Clone)]
+pub struct DepthMap {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f32>,
+}
+
+impl DepthMap {
+    pub fn to_tensor<B: Backend>(&self, device: &B::Device) -> Tensor<B, 2> {
+        Tensor::<B, 1>::from_floats(self.data.as_slice(), device)
+            .reshape([self.height as usize, self.width as usize])
+    }
+
+    /// The depth at a pixel, 0 if it wasn't measured.
+    pub fn get(&self, x: u32, y: u32) -> f32 {
+        self.data[(y * self.width + x) as usize]
+    }
 }
 
 // Encapsulates a multi-view scene including cameras and the splats.
@@ -30,6 +72,29 @@ fn camera_similarity_score(cam: &Camera, reference: &Camera) -> f32 {
     distance * (1.0 - forward_ref.dot(forward_cam))
 }
 
+// Whether a camera sees part of a box: it's inside of it, or the center or a corner of the
+// box is in front of the camera and inside its image.
+fn camera_sees(cam: &Camera, bounds: &BoundingBox) -> bool {
+    if bounds.contains(cam.position) {
+        return true;
+    }
+    let world_to_local = cam.world_to_local();
+    let tan_half = glam::vec2(
+        (cam.fov_x * 0.5).tan() as f32,
+        (cam.fov_y * 0.5).tan() as f32,
+    );
+    std::iter::once(bounds.center)
+        .chain(bounds.corners())
+        .any(|point| {
+            let local = world_to_local.transform_point3(point);
+            if local.z <= 0.0 {
+                return false;
+            }
+            let uv = local.truncate() / local.z / (2.0 * tan_half) + cam.center_uv;
+            uv.cmpge(glam::Vec2::ZERO).all() && uv.cmple(glam::Vec2::ONE).all()
+        })
+}
+
 impl Scene {
     pub fn new(views: Vec<SceneView>) -> Self {
         Self {
@@ -57,6 +122,106 @@ impl Scene {
         BoundingBox::from_min_max(min, max)
     }
 
+    /// Size of the scene in world units, which scales the learning rate of the positions and
+    /// the size thresholds of refinement, like `spatial_lr_scale` in the reference
+    /// implementation.
+    ///
+    /// This is the radius of the cameras around their center. When the cameras are close
+    /// together compared to what they look at, eg. a lidar scan from a single station or a
+    /// room captured from its center, the median distance of `points` to the center is
+    /// used instead.
+    pub fn extent(&self, points: &[Vec3]) -> f32 {
+        // Enough points for a good estimate of the median.
+        const MAX_POINTS: usize = 10000;
+
+        let center = self.bounds().center;
+        let camera_radius = self
+            .views
+            .iter()
+            .map(|v| (v.camera.position - center).length())
+            .fold(0.0, f32::max);
+        // Idk why exactly, but gsplat multiplies this by 1.1
+        let camera_extent = camera_radius * 1.1;
+
+        let step = points.len().div_ceil(MAX_POINTS).max(1);
+        let mut dists: Vec<_> = points
+            .iter()
+            .step_by(step)
+            .map(|p| (*p - center).length())
+            .filter(|d| d.is_finite())
+            .collect();
+        dists.sort_by(|a, b| a.total_cmp(b));
+        let point_extent = dists.get(dists.len() / 2).copied().unwrap_or(0.0);
+
+        let extent = camera_extent.max(point_extent);
+        if extent.is_finite() && extent > 0.0 {
+            extent
+        } else {
+            1.0
+        }
+    }
+
+    /// Median sharpness of the views, `None` for an empty scene.
+    pub fn median_sharpness(&self) -> Option<f32> {
+        let mut sorted: Vec<_> = self.views.iter().map(|v| v.sharpness).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        sorted.get(sorted.len() / 2).copied()
+    }
+
+    /// A scene without the given fraction of blurriest views.
+    pub fn without_blurriest(&self, fraction: f32) -> Self {
+        let num_drop = (self.views.len() as f32 * fraction.clamp(0.0, 1.0)) as usize;
+        if num_drop == 0 {
+            return self.clone();
+        }
+
+        let mut sorted: Vec<_> = self.views.iter().map(|v| v.sharpness).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let cutoff = sorted[num_drop - 1];
+
+        // Keep the original view order.
+        let mut dropped = 0;
+        let views = self
+            .views
+            .iter()
+            .filter(|v| {
+                if dropped < num_drop && v.sharpness <= cutoff {
+                    dropped += 1;
+                    false
+                } else {
+                    true
+                }
+            })
+            .cloned()
+            .collect();
+        Self::new(views)
+    }
+
+    /// The views that see part of `bounds`, to train a region of the scene, see
+    /// [`crate::train::TrainConfig::refine_region`]. All views are kept when none see it.
+    pub fn seeing(&self, bounds: &BoundingBox) -> Self {
+        let views: Vec<_> = self
+            .views
+            .iter()
+            .filter(|v| camera_sees(&v.camera, bounds))
+            .cloned()
+            .collect();
+        if views.is_empty() {
+            log::warn!("No views see the region, training on all views");
+            return self.clone();
+        }
+        Self::new(views)
+    }
+
+    /// Number of captures the views come from, see [`SceneView::capture_id`].
+    pub fn num_captures(&self) -> usize {
+        self.views
+            .iter()
+            .map(|v| v.capture_id as usize + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
     pub fn get_nearest_view(&self, reference: &Camera) -> Option<usize> {
         self.views
             .iter()
@@ -71,3 +236,59 @@ impl Scene {
             .map(|(index, _)| index) // We return the index instead of the camera
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use brush_render::{bounding_box::BoundingBox, camera::Camera};
+    use glam::{Quat, Vec3};
+
+    use super::{Scene, SceneView};
+
+    fn view_at(position: Vec3) -> SceneView {
+        SceneView {
+            name: String::new(),
+            camera: Camera::new(position, Quat::IDENTITY, 1.0, 1.0, glam::vec2(0.5, 0.5)),
+            image: image::DynamicImage::new_rgb8(1, 1).into(),
+            mask: None,
+            camera_id: 0,
+            capture_id: 0,
+            features: None,
+            depth: None,
+            sharpness: 0.0,
+        }
+    }
+
+    #[test]
+    fn extent_from_cameras_or_points() {
+        let scene = Scene::new(vec![
+            view_at(Vec3::new(-0.1, 0.0, 0.0)),
+            view_at(Vec3::new(0.1, 0.0, 0.0)),
+        ]);
+
+        // Without points, the extent is the radius of the cameras.
+        assert!((scene.extent(&[]) - 0.11).abs() < 1e-4);
+
+        // Cameras close together in the middle of a room.
+        let walls: Vec<_> = (0..100)
+            .map(|i| Quat::from_rotation_y(i as f32 * 0.1) * Vec3::Z * 10.0)
+            .collect();
+        assert!((scene.extent(&walls) - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn views_seeing_region() {
+        // Both cameras look down +Z, the second one is past the region.
+        let scene = Scene::new(vec![
+            view_at(Vec3::ZERO),
+            view_at(Vec3::new(0.0, 0.0, 10.0)),
+        ]);
+        let region =
+            BoundingBox::from_min_max(Vec3::new(-1.0, -1.0, 4.0), Vec3::new(1.0, 1.0, 6.0));
+        assert_eq!(scene.seeing(&region).views.len(), 1);
+
+        // Nothing sees a region behind all cameras, so all views are kept.
+        let behind =
+            BoundingBox::from_min_max(Vec3::new(-1.0, -1.0, -21.0), Vec3::new(1.0, 1.0, -19.0));
+        assert_eq!(scene.seeing(&behind).views.len(), 2);
+    }
+}
@@ -0,0 +1,107 @@
+use brush_render::{
+    camera::Camera,
+    gaussian_splats::{inverse_sigmoid, Splats},
+    Backend, RenderAux,
+};
+use burn::{module::Module, tensor::Tensor};
+use glam::{Quat, Vec3};
+
+use crate::ground::GroundPlane;
+
+/// A layer of flat splats on the ground plane, trained behind the scene to catch the ground
+/// and the shadows on it.
+///
+/// Without it, the ground of an object capture ends up in the scene splats, and is baked into
+/// the object when compositing it into another scene. The layer can be left out of exports,
+/// or exported on its own to composite the shadows.
+///
+/// Only the colors and opacities are trained, the splats stay on the ground.
+#[derive(Module, Debug)]
+pub struct ShadowCatcher<B: Backend> {
+    pub splats: Splats<B>,
+}
+
+impl<B: Backend> ShadowCatcher<B> {
+    /// A square grid of `resolution` by `resolution` splats on the ground, with the given
+    /// half size around `center`.
+    pub fn new(
+        plane: GroundPlane,
+        center: Vec3,
+        half_size: f32,
+        resolution: usize,
+        device: &B::Device,
+    ) -> Self {
+        let center = plane.project(center);
+        let (tangent, bitangent) = plane.normal.any_orthonormal_pair();
+        let cell = 2.0 * half_size / resolution as f32;
+        let rotation = Quat::from_rotation_arc(Vec3::Z, plane.normal);
+
+        let mut means = vec![];
+        for i in 0..resolution {
+            for j in 0..resolution {
+                let u = (i as f32 + 0.5) * cell - half_size;
+                let v = (j as f32 + 0.5) * cell - half_size;
+                let pos = center + tangent * u + bitangent * v;
+                means.extend([pos.x, pos.y, pos.z]);
+            }
+        }
+        let n = resolution * resolution;
+
+        let means = Tensor::<B, 1>::from_floats(means.as_slice(), device).reshape([n, 3]);
+        let rotation =
+            Tensor::<B, 1>::from_floats([rotation.w, rotation.x, rotation.y, rotation.z], device)
+                .reshape([1, 4])
+                .repeat_dim(0, n);
+        // Flat splats that overlap their neighbours a little.
+        let log_scales = Tensor::<B, 1>::from_floats(
+            [(cell * 0.5).ln(), (cell * 0.5).ln(), (cell * 0.01).ln()],
+            device,
+        )
+        .reshape([1, 3])
+        .repeat_dim(0, n);
+        // Starts out as a half transparent gray.
+        let sh_coeffs = Tensor::zeros([n, 1, 3], device);
+        let raw_opacity = Tensor::zeros([n], device) + inverse_sigmoid(0.5);
+
+        Self {
+            splats: Splats::from_tensor_data(means, rotation, log_scales, sh_coeffs, raw_opacity),
+        }
+    }
+
+    /// Render the layer as seen by `camera`, as an RGBA image of `[h, w, 4]`.
+    pub fn render(&self, camera: &Camera, img_size: glam::UVec2) -> (Tensor<B, 3>, RenderAux<B>) {
+        self.splats.render(camera, img_size, false)
+    }
+}
+
+/// The splats of a scene together with a shadow catcher, eg. to export them as one model.
+pub fn with_shadow_catcher<B: Backend>(
+    splats: &Splats<B>,
+    catcher: &ShadowCatcher<B>,
+) -> Splats<B> {
+    let layer = &catcher.splats;
+    let [_, coeffs, _] = splats.sh_coeffs.dims();
+    let m = layer.num_splats();
+
+    // The layer only has a base color, pad it to the degree of the scene.
+    let layer_coeffs = if coeffs > 1 {
+        let device = layer.means.device();
+        Tensor::cat(
+            vec![
+                layer.sh_coeffs.val(),
+                Tensor::zeros([m, coeffs - 1, 3], &device),
+            ],
+            1,
+        )
+    } else {
+        layer.sh_coeffs.val()
+    };
+
+    Splats::from_tensor_data(
+        Tensor::cat(vec![splats.means.val(), layer.means.val()], 0),
+        Tensor::cat(vec![splats.rotation.val(), layer.rotation.val()], 0),
+        Tensor::cat(vec![splats.log_scales.val(), layer.log_scales.val()], 0),
+        Tensor::cat(vec![splats.sh_coeffs.val(), layer_coeffs], 0),
+        Tensor::cat(vec![splats.raw_opacity.val(), layer.raw_opacity.val()], 0),
+    )
+}
@@ -0,0 +1,237 @@
+use brush_render::{
+    gaussian_splats::{inverse_sigmoid, Splats},
+    Backend,
+};
+use burn::tensor::DataError;
+use glam::{IVec3, Mat3, Quat, Vec3};
+use hashbrown::{HashMap, HashSet};
+
+fn voxel_key(pos: Vec3, voxel_size: f32) -> IVec3 {
+    (pos / voxel_size).floor().as_ivec3()
+}
+
+fn count_voxels(means: &[Vec3], voxel_size: f32) -> usize {
+    means
+        .iter()
+        .map(|&m| voxel_key(m, voxel_size))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+// Find the smallest voxel size that leaves at most `target_count` occupied voxels.
+fn find_voxel_size(means: &[Vec3], target_count: usize) -> f32 {
+    let (min, max) = means.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), &m| (min.min(m), max.max(m)),
+    );
+    let extent = (max - min).max_element().max(1e-6);
+
+    // Bisect in log space, the voxel count changes with the cube of the size.
+    let mut lo = extent * 1e-6;
+    let mut hi = extent;
+    for _ in 0..32 {
+        let mid = (lo * hi).sqrt();
+        if count_voxels(means, mid) > target_count {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    hi
+}
+
+// Eigen decomposition of a symmetric matrix with Jacobi rotations. Returns the eigenvalues,
+// and the eigenvectors as the columns of a matrix.
+pub(crate) fn symmetric_eigen(mat: Mat3) -> (Vec3, Mat3) {
+    let mut a = mat.to_cols_array_2d();
+    let mut v = Mat3::IDENTITY.to_cols_array_2d();
+
+    for _ in 0..16 {
+        // Find the largest off diagonal element.
+        let (p, q) = [(0, 1), (0, 2), (1, 2)]
+            .into_iter()
+            .max_by(|&(i, j), &(k, l)| a[i][j].abs().total_cmp(&a[k][l].abs()))
+            .expect("Non empty");
+
+        if a[p][q].abs() < 1e-12 * (a[p][p].abs() + a[q][q].abs()).max(f32::MIN_POSITIVE) {
+            break;
+        }
+
+        let theta = 0.5 * (a[q][q] - a[p][p]) / a[p][q];
+        let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+        let c = 1.0 / (t * t + 1.0).sqrt();
+        let s = t * c;
+
+        for k in 0..3 {
+            let (akp, akq) = (a[k][p], a[k][q]);
+            a[k][p] = c * akp - s * akq;
+            a[k][q] = s * akp + c * akq;
+        }
+        for k in 0..3 {
+            let (apk, aqk) = (a[p][k], a[q][k]);
+            a[p][k] = c * apk - s * aqk;
+            a[q][k] = s * apk + c * aqk;
+        }
+        for row in &mut v {
+            let (vp, vq) = (row[p], row[q]);
+            row[p] = c * vp - s * vq;
+            row[q] = s * vp + c * vq;
+        }
+    }
+
+    // Nb: v is stored as columns, but rotated as rows above, so transpose back.
+    let vectors = Mat3::from_cols_array_2d(&v).transpose();
+    (Vec3::new(a[0][0], a[1][1], a[2][2]), vectors)
+}
+
+/// Merge nearby splats until at most `target_count` are left, to make lighter versions of
+/// large scenes.
+///
+/// Splats are binned in a voxel grid, with the voxel size picked to hit the target count.
+/// All splats in a voxel are merged into one, weighted by their opacity. The merged splat
+/// matches the mean and covariance of the splats it replaces, and averages their color.
+pub async fn simplify_splats<B: Backend>(
+    splats: Splats<B>,
+    target_count: usize,
+) -> Result<Splats<B>, DataError> {
+    let num_splats = splats.num_splats();
+    if num_splats <= target_count || target_count == 0 {
+        return Ok(splats);
+    }
+
+    let device = splats.means.device();
+    let sh_len = splats.sh_coeffs.dims()[1] * 3;
+
+    let means: Vec<f32> = splats.means.val().into_data_async().await.to_vec()?;
+    let rotations: Vec<f32> = splats.rotation.val().into_data_async().await.to_vec()?;
+    let log_scales: Vec<f32> = splats.log_scales.val().into_data_async().await.to_vec()?;
+    let raw_opacities: Vec<f32> = splats.raw_opacity.val().into_data_async().await.to_vec()?;
+    let sh_coeffs: Vec<f32> = splats.sh_coeffs.val().into_data_async().await.to_vec()?;
+
+    let means: Vec<_> = means.chunks_exact(3).map(Vec3::from_slice).collect();
+    let voxel_size = find_voxel_size(&means, target_count);
+
+    let mut voxels: HashMap<IVec3, Vec<usize>> = HashMap::new();
+    for (i, &mean) in means.iter().enumerate() {
+        voxels
+            .entry(voxel_key(mean, voxel_size))
+            .or_default()
+            .push(i);
+    }
+
+    let num_merged = voxels.len();
+    let mut new_means = Vec::with_capacity(num_merged);
+    let mut new_rotations = Vec::with_capacity(num_merged);
+    let mut new_log_scales = Vec::with_capacity(num_merged);
+    let mut new_opacities = Vec::with_capacity(num_merged);
+    let mut new_sh = Vec::with_capacity(num_merged * sh_len);
+
+    for members in voxels.values() {
+        let opacity = |i: usize| 1.0 / (1.0 + (-raw_opacities[i]).exp());
+        let covariance = |i: usize| {
+            let r = &rotations[i * 4..i * 4 + 4];
+            let rot = Mat3::from_quat(Quat::from_xyzw(r[1], r[2], r[3], r[0]).normalize());
+            let scale = Vec3::from_slice(&log_scales[i * 3..i * 3 + 3])
+                .to_array()
+                .map(f32::exp);
+            let scale = Vec3::from_array(scale);
+            let m = rot * Mat3::from_diagonal(scale);
+            m * m.transpose()
+        };
+
+        if let [i] = members[..] {
+            new_means.push(means[i]);
+            new_rotations.push({
+                let r = &rotations[i * 4..i * 4 + 4];
+                Quat::from_xyzw(r[1], r[2], r[3], r[0])
+            });
+            new_log_scales.push(Vec3::from_slice(&log_scales[i * 3..i * 3 + 3]));
+            new_opacities.push(raw_opacities[i]);
+            new_sh.extend_from_slice(&sh_coeffs[i * sh_len..(i + 1) * sh_len]);
+            continue;
+        }
+
+        let weights: Vec<f32> = members.iter().map(|&i| opacity(i).max(1e-6)).collect();
+        let total_weight: f32 = weights.iter().sum();
+
+        let mean = members
+            .iter()
+            .zip(&weights)
+            .fold(Vec3::ZERO, |acc, (&i, &w)| acc + means[i] * w)
+            / total_weight;
+
+        // Moment match the covariance, including the spread of the means.
+        let cov = members
+            .iter()
+            .zip(&weights)
+            .fold(Mat3::ZERO, |acc, (&i, &w)| {
+                let d = means[i] - mean;
+                let spread = Mat3::from_cols(d * d.x, d * d.y, d * d.z);
+                acc + (covariance(i) + spread) * w
+            })
+            * (1.0 / total_weight);
+
+        let (eigenvalues, mut axes) = symmetric_eigen(cov);
+        if axes.determinant() < 0.0 {
+            axes.z_axis = -axes.z_axis;
+        }
+        let rotation = Quat::from_mat3(&axes).normalize();
+        let log_scale = Vec3::from_array(
+            eigenvalues
+                .max(Vec3::splat(1e-24))
+                .to_array()
+                .map(|e| 0.5 * e.ln()),
+        );
+
+        // Opacity weighted opacity, so faint splats don't wash out the merged splat.
+        let merged_opacity = members
+            .iter()
+            .zip(&weights)
+            .map(|(&i, &w)| opacity(i) * w)
+            .sum::<f32>()
+            / total_weight;
+
+        let mut sh = vec![0.0; sh_len];
+        for (&i, &w) in members.iter().zip(&weights) {
+            for (acc, &c) in sh.iter_mut().zip(&sh_coeffs[i * sh_len..(i + 1) * sh_len]) {
+                *acc += c * w / total_weight;
+            }
+        }
+
+        new_means.push(mean);
+        new_rotations.push(rotation);
+        new_log_scales.push(log_scale);
+        new_opacities.push(inverse_sigmoid(merged_opacity.clamp(1e-4, 1.0 - 1e-4)));
+        new_sh.extend(sh);
+    }
+
+    log::info!("Simplified {num_splats} splats to {num_merged} with voxel size {voxel_size}");
+
+    Ok(Splats::from_raw(
+        &new_means,
+        Some(new_rotations.as_slice()),
+        Some(new_log_scales.as_slice()),
+        Some(new_sh.as_slice()),
+        Some(new_opacities.as_slice()),
+        &device,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat3, Vec3};
+
+    use super::symmetric_eigen;
+
+    #[test]
+    fn eigen_reconstructs() {
+        let m = Mat3::from_cols(
+            Vec3::new(4.0, 1.0, 0.5),
+            Vec3::new(1.0, 3.0, 0.2),
+            Vec3::new(0.5, 0.2, 1.0),
+        );
+        let (values, vectors) = symmetric_eigen(m);
+        let rebuilt = vectors * Mat3::from_diagonal(values) * vectors.transpose();
+        assert!(rebuilt.abs_diff_eq(m, 1e-4));
+    }
+}
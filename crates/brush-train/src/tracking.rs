@@ -0,0 +1,130 @@
+use brush_render::{camera::Camera, gaussian_splats::Splats, Backend, RenderOptions};
+use burn::tensor::Tensor;
+use glam::{Quat, Vec3};
+use image::DynamicImage;
+
+use crate::image::image_to_tensor;
+
+/// Settings to track the pose of a new frame against a model, see [`track_frame`].
+#[derive(Debug, Clone)]
+pub struct TrackingConfig {
+    /// Width the frame is tracked at. Small sizes are faster and less sensitive to noise.
+    pub resolution: u32,
+    /// Initial translation step, as a fraction of the scene extent.
+    pub translation_step: f32,
+    /// Initial rotation step in degrees.
+    pub rotation_step: f32,
+    /// Number of times the steps are halved before the search stops.
+    pub refinements: u32,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            resolution: 160,
+            translation_step: 0.02,
+            rotation_step: 2.0,
+            refinements: 4,
+        }
+    }
+}
+
+// Mean absolute difference between the render from each camera and the frame.
+async fn render_errors<B: Backend>(
+    splats: &Splats<B>,
+    cameras: &[Camera],
+    frame: &Tensor<B, 3>,
+) -> Vec<f32> {
+    let [h, w, _] = frame.dims();
+    let img_size = glam::uvec2(w as u32, h as u32);
+
+    let errors = cameras
+        .iter()
+        .map(|camera| {
+            let render = splats.render_inference(camera, img_size, false, RenderOptions::default());
+            (render.slice([0..h, 0..w, 0..3]) - frame.clone())
+                .abs()
+                .mean()
+        })
+        .collect();
+
+    Tensor::cat(errors, 0)
+        .into_data_async()
+        .await
+        .to_vec()
+        .unwrap_or_default()
+}
+
+/// Find the pose of a frame by aligning it to a render of the splats, starting from the pose of
+/// the previous frame.
+///
+/// This is a simple pattern search: every round tries small steps along and around each camera
+/// axis and moves to the best pose, halving the steps when no step improves the alignment.
+/// It only works for small motions between frames, like a video from a handheld camera.
+/// Returns the refined camera and the mean absolute color error at that pose.
+pub async fn track_frame<B: Backend>(
+    splats: &Splats<B>,
+    frame: &DynamicImage,
+    previous: &Camera,
+    scene_extent: f32,
+    config: &TrackingConfig,
+) -> (Camera, f32) {
+    let device = splats.means.device();
+    let aspect = frame.height() as f32 / frame.width().max(1) as f32;
+    let height = ((config.resolution as f32 * aspect).round() as u32).max(1);
+    let frame = frame.resize_exact(
+        config.resolution,
+        height,
+        image::imageops::FilterType::Triangle,
+    );
+    let frame = image_to_tensor::<B>(&DynamicImage::ImageRgb32F(frame.to_rgb32f()), &device);
+
+    let mut camera = previous.clone();
+    let mut error = render_errors(splats, &[camera.clone()], &frame)
+        .await
+        .first()
+        .copied()
+        .unwrap_or(f32::INFINITY);
+
+    let mut translation_step = config.translation_step * scene_extent;
+    let mut rotation_step = config.rotation_step.to_radians();
+    let mut refinements = 0;
+
+    while refinements <= config.refinements {
+        let candidates: Vec<_> = [Vec3::X, Vec3::Y, Vec3::Z]
+            .into_iter()
+            .flat_map(|axis| [axis, -axis])
+            .flat_map(|axis| {
+                let moved = Camera {
+                    position: camera.position + camera.rotation * (axis * translation_step),
+                    ..camera.clone()
+                };
+                let turned = Camera {
+                    rotation: camera.rotation * Quat::from_axis_angle(axis, rotation_step),
+                    ..camera.clone()
+                };
+                [moved, turned]
+            })
+            .collect();
+
+        let errors = render_errors(splats, &candidates, &frame).await;
+        let best = errors
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        match best {
+            Some((i, &best_error)) if best_error < error => {
+                camera = candidates[i].clone();
+                error = best_error;
+            }
+            _ => {
+                translation_step *= 0.5;
+                rotation_step *= 0.5;
+                refinements += 1;
+            }
+        }
+    }
+
+    (camera, error)
+}
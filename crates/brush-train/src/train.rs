@@ -1,7 +1,11 @@
 use anyhow::Result;
-use brush_render::gaussian_splats::{inverse_sigmoid, Splats};
+use brush_render::bounding_box::BoundingBox;
+use brush_render::camera::Camera;
+use brush_render::depth::render_depth_with_aux;
+use brush_render::gaussian_splats::{inverse_sigmoid, RandomSplatsConfig, Splats};
 use brush_render::render::sh_coeffs_for_degree;
-use brush_render::{AutodiffBackend, Backend, RenderAux};
+use brush_render::spatial_index::SpatialIndex;
+use brush_render::{AutodiffBackend, Backend, RenderAux, CAMERA_GRAD_SIZE};
 use burn::backend::wgpu::WgpuDevice;
 use burn::backend::{Autodiff, Wgpu};
 use burn::lr_scheduler::exponential::{ExponentialLrScheduler, ExponentialLrSchedulerConfig};
@@ -13,10 +17,25 @@ use burn::optim::Optimizer;
 use burn::tensor::{Bool, Distribution};
 use burn::{config::Config, optim::GradientsParams, tensor::Tensor};
 use hashbrown::HashMap;
+use std::{path::PathBuf, sync::Arc};
 use tracing::trace_span;
 
 use crate::adam_scaled::{AdamScaled, AdamScaledConfig, AdamState};
-use crate::scene::SceneView;
+use crate::appearance::CaptureAppearance;
+use crate::blur::BlurRefiner;
+use crate::environment::{EnvironmentMap, EnvironmentRays};
+use crate::features::{FeatureField, FeatureMap};
+use crate::freeze::{is_frozen, FreezeRule, ParamGroup};
+use crate::ground::GroundPlane;
+use crate::image::composite_on_white;
+use crate::intrinsics::IntrinsicsRefiner;
+use crate::lighting::LightingModel;
+use crate::loss::{weighted_mean, LossTerm, PhotometricLoss, RenderPackage};
+use crate::perceptual::PerceptualLoss;
+use crate::rolling_shutter::{RollingShutterRefiner, ShutterGrads};
+use crate::sampler::ViewSampling;
+use crate::scene::{Scene, SceneView};
+use crate::shadow_catcher::ShadowCatcher;
 use crate::ssim::Ssim;
 use crate::stats::RefineRecord;
 
@@ -47,12 +66,31 @@ pub struct TrainConfig {
     #[config(default = 0.5)]
     cull_scale3d_percentage_threshold: f32,
 
+    // Gaussians smaller than this fraction of the scene extent are culled.
+    #[config(default = 1e-5)]
+    cull_scale3d_min_fraction: f32,
+
     // period of steps where refinement is turned off
     #[config(default = 500)]
     refine_start_iter: u32,
 
     #[config(default = 15000)]
-    refine_stop_iter: u32,
+    pub refine_stop_iter: u32,
+
+    // Groups of splat parameters to keep fixed, from and until the given steps, eg. to lock
+    // the means after some steps, or only train the colors of an imported model. Splats are
+    // not refined while their means, rotations or scales are frozen.
+    #[config(default = "Vec::new()")]
+    pub freeze: Vec<FreezeRule>,
+
+    // Only train and refine the splats in this box, on the views that see it, to fix one
+    // area of a trained scene. The splats outside it keep their values.
+    pub refine_region: Option<BoundingBox>,
+
+    // Seed this many new splats at random in the refine region before training, to heal a
+    // hole or artifact of a trained scene with a short run, see [`seed_region`].
+    #[config(default = 0)]
+    pub heal_seeds: usize,
 
     // Every this many refinement steps, reset the alpha
     #[config(default = 30)]
@@ -64,6 +102,36 @@ pub struct TrainConfig {
     #[config(default = 11)]
     ssim_window_size: usize,
 
+    // Per pixel loss between the render and ground truth image.
+    #[config(default = "PhotometricLoss::L1")]
+    pub photometric_loss: PhotometricLoss,
+
+    // Threshold between the quadratic and linear part of the Huber loss.
+    #[config(default = 0.05)]
+    huber_delta: f32,
+
+    // Smoothing of the Charbonnier loss around zero.
+    #[config(default = 1e-3)]
+    charbonnier_eps: f32,
+
+    // Weight of the perceptual loss. Only used when a perceptual network is set
+    // with `SplatTrainer::set_perceptual_loss`.
+    #[config(default = 0.0)]
+    pub perceptual_weight: f32,
+
+    // Apply the perceptual loss every this many steps, to bound its cost.
+    #[config(default = 10)]
+    pub perceptual_every: u32,
+
+    // Safetensors file of the VGG16 weights of the perceptual loss. When not set, the app
+    // downloads them once and keeps them in its config folder.
+    pub perceptual_weights: Option<PathBuf>,
+
+    // Weight of the L1 loss on the depth of views with a measured depth, eg. from an RGB-D
+    // sensor. Other views aren't affected.
+    #[config(default = 0.1)]
+    pub depth_weight: f32,
+
     // Learning rates.
     lr_mean: ExponentialLrSchedulerConfig,
 
@@ -84,6 +152,122 @@ pub struct TrainConfig {
     #[config(default = 1e-3)]
     lr_rotation: f64,
 
+    // Whether to refine the focal length and principal point of each camera
+    // to correct slightly-off calibrations.
+    #[config(default = false)]
+    pub optimize_intrinsics: bool,
+
+    // Learning rate for the intrinsics. Relative to the focal length & image size.
+    #[config(default = 1e-5)]
+    lr_intrinsics: f64,
+
+    // Whether to model the cameras as rolling shutter cameras, refining a start
+    // and end pose per view. Useful for drone and phone video captures.
+    #[config(default = false)]
+    pub rolling_shutter: bool,
+
+    // Learning rate for the rolling shutter positions. Scaled by the scene extent.
+    #[config(default = 1e-5)]
+    lr_rolling_shutter: f64,
+
+    // Learning rate for the rolling shutter rotations, in radians.
+    #[config(default = 1e-5)]
+    lr_rolling_shutter_rotation: f64,
+
+    // Number of jittered sub-poses to render blurry views with, to model motion blur and
+    // defocus. Set to 0 or 1 to disable.
+    #[config(default = 0)]
+    pub blur_samples: u32,
+
+    // Views with a sharpness below this fraction of the median sharpness are modeled as blurry.
+    #[config(default = 0.5)]
+    blur_sharpness_threshold: f32,
+
+    // Learning rate for the blur motion & aperture. Scaled by the scene extent.
+    #[config(default = 1e-5)]
+    lr_blur: f64,
+
+    // Whether to weight the loss of each view by its sharpness relative to the median
+    // sharpness, so blurry views contribute less.
+    #[config(default = false)]
+    pub sharpness_weighting: bool,
+
+    // Fraction of the blurriest training views to leave out entirely.
+    #[config(default = 0.0)]
+    pub drop_blurry_fraction: f32,
+
+    // Whether to learn a background environment map for unbounded scenes. Only used
+    // for images without an alpha channel.
+    #[config(default = false)]
+    pub background_model: bool,
+
+    // Height of the background environment map, the width is twice this.
+    #[config(default = 64)]
+    background_resolution: usize,
+
+    #[config(default = 1e-2)]
+    lr_background: f64,
+
+    // Whether to composite transparent images and the renders onto white and compare only
+    // the colors, as the NeRF synthetic benchmark does, instead of training the alpha.
+    #[config(default = false)]
+    pub white_background: bool,
+
+    // Whether to learn a color correction per capture, when the dataset combines several
+    // captures of the same scene.
+    #[config(default = true)]
+    pub capture_appearance: bool,
+
+    #[config(default = 1e-3)]
+    lr_appearance: f64,
+
+    // Whether to decompose the splat colors into an albedo and a lighting shared by the
+    // scene, so the model can be relit a little. Experimental, works best with SH degree 0.
+    #[config(default = false)]
+    pub relighting: bool,
+
+    #[config(default = 1e-3)]
+    lr_lighting: f64,
+
+    // Whether to train a layer of splats on the detected ground plane behind the scene, so
+    // the ground and its shadows aren't baked into the splats of an object capture.
+    #[config(default = false)]
+    pub shadow_catcher: bool,
+
+    // Number of splats along each side of the shadow catcher.
+    #[config(default = 64)]
+    shadow_catcher_resolution: usize,
+
+    #[config(default = 1e-2)]
+    lr_shadow_catcher: f64,
+
+    // Whether to learn a feature vector per splat from the feature maps of the views, eg.
+    // CLIP or DINO features. Features are learned once refinement stops, when the number of
+    // splats is fixed.
+    #[config(default = false)]
+    pub feature_splatting: bool,
+
+    #[config(default = 1e-2)]
+    lr_features: f64,
+
+    // How training views are picked, uniformly or prioritizing views with a high loss.
+    #[config(default = "ViewSampling::Shuffle")]
+    pub view_sampling: ViewSampling,
+
+    // Train on downscaled images first, starting at this many halvings of the resolution.
+    // Set to 0 to train at the full resolution from the start.
+    #[config(default = 0)]
+    pub coarse_to_fine_levels: u32,
+
+    // Double the training resolution every this many steps, until the full resolution.
+    #[config(default = 1000)]
+    pub coarse_to_fine_every: u32,
+
+    // GPU memory to keep the training images in, in megabytes. The views that were sampled
+    // longest ago are uploaded again when the images don't fit.
+    #[config(default = 2048)]
+    pub gpu_image_budget_mb: u32,
+
     #[config(default = 42)]
     pub seed: u64,
 
@@ -93,6 +277,15 @@ pub struct TrainConfig {
 
 type B = Autodiff<Wgpu>;
 
+impl TrainConfig {
+    /// The level of the [`crate::pyramid::ImagePyramid`] of the views to train on at `iter`,
+    /// 0 being the full resolution.
+    pub fn resolution_level(&self, iter: u32) -> usize {
+        let doublings = iter / self.coarse_to_fine_every.max(1);
+        self.coarse_to_fine_levels.saturating_sub(doublings) as usize
+    }
+}
+
 impl Default for TrainConfig {
     fn default() -> Self {
         let decay_steps = 30000;
@@ -105,6 +298,8 @@ impl Default for TrainConfig {
 #[derive(Clone, Debug)]
 pub struct SceneBatch<B: Backend> {
     pub gt_images: Tensor<B, 4>,
+    /// Per pixel loss weights of `[batch, h, w, 1]`, from the view masks.
+    pub loss_weights: Option<Tensor<B, 4>>,
     pub gt_views: Vec<SceneView>,
     pub scene_extent: f32,
 }
@@ -132,6 +327,11 @@ pub struct TrainStepStats<B: AutodiffBackend> {
 }
 
 type OptimizerType = OptimizerAdaptor<AdamScaled, Splats<B>, B>;
+type EnvironmentOptimizerType = OptimizerAdaptor<AdamScaled, EnvironmentMap<B>, B>;
+type AppearanceOptimizerType = OptimizerAdaptor<AdamScaled, CaptureAppearance<B>, B>;
+type LightingOptimizerType = OptimizerAdaptor<AdamScaled, LightingModel<B>, B>;
+type ShadowCatcherOptimizerType = OptimizerAdaptor<AdamScaled, ShadowCatcher<B>, B>;
+type FeatureOptimizerType = OptimizerAdaptor<AdamScaled, FeatureField<B>, B>;
 
 pub struct SplatTrainer {
     config: TrainConfig,
@@ -139,6 +339,20 @@ pub struct SplatTrainer {
     optim: OptimizerType,
     ssim: Ssim<B>,
     refine_record: RefineRecord,
+    intrinsics: IntrinsicsRefiner,
+    rolling_shutter: RollingShutterRefiner,
+    blur: BlurRefiner,
+    environment: Option<(EnvironmentMap<B>, EnvironmentOptimizerType)>,
+    environment_rays: EnvironmentRays<B>,
+    appearance: Option<(CaptureAppearance<B>, AppearanceOptimizerType)>,
+    lighting: Option<(LightingModel<B>, LightingOptimizerType)>,
+    shadow_catcher: Option<(ShadowCatcher<B>, ShadowCatcherOptimizerType)>,
+    features: Option<(FeatureField<B>, FeatureOptimizerType)>,
+    spatial_index: Option<Arc<SpatialIndex>>,
+    perceptual: Option<PerceptualLoss<B>>,
+    loss_terms: Vec<Box<dyn LossTerm<B>>>,
+    median_sharpness: f32,
+    device: WgpuDevice,
 }
 
 fn quaternion_vec_multiply<B: Backend>(
@@ -198,22 +412,213 @@ impl SplatTrainer {
             optim,
             refine_record: RefineRecord::new(splats.num_splats(), device),
             ssim,
+            intrinsics: IntrinsicsRefiner::default(),
+            rolling_shutter: RollingShutterRefiner::default(),
+            blur: BlurRefiner::default(),
+            environment: config.background_model.then(|| {
+                (
+                    EnvironmentMap::new(config.background_resolution, device),
+                    AdamScaledConfig::new().init(),
+                )
+            }),
+            environment_rays: EnvironmentRays::default(),
+            appearance: None,
+            lighting: config
+                .relighting
+                .then(|| (LightingModel::new(device), AdamScaledConfig::new().init())),
+            shadow_catcher: None,
+            features: None,
+            spatial_index: None,
+            perceptual: None,
+            loss_terms: vec![],
+            median_sharpness: 0.0,
+            device: device.clone(),
+        }
+    }
+
+    /// Continue the learning rate schedule from the given step, when resuming training from
+    /// a checkpoint. The optimizer state isn't saved in checkpoints, so it starts over.
+    pub fn resume_at(&mut self, iter: u32) {
+        for _ in 0..iter {
+            self.sched_mean.step();
         }
     }
 
+    /// Change the settings while training, eg. from a training script. Settings that are used
+    /// when training starts, like the schedule of the mean learning rate and which models are
+    /// trained, keep their old values.
+    pub fn set_config(&mut self, config: TrainConfig) {
+        let old = &self.config;
+        self.config = TrainConfig {
+            lr_mean: old.lr_mean.clone(),
+            ssim_window_size: old.ssim_window_size,
+            background_model: old.background_model,
+            background_resolution: old.background_resolution,
+            relighting: old.relighting,
+            shadow_catcher: old.shadow_catcher,
+            shadow_catcher_resolution: old.shadow_catcher_resolution,
+            capture_appearance: old.capture_appearance,
+            feature_splatting: old.feature_splatting,
+            rolling_shutter: old.rolling_shutter,
+            optimize_intrinsics: old.optimize_intrinsics,
+            blur_samples: old.blur_samples,
+            ..config
+        };
+    }
+
+    /// Add a custom term to the loss of every step.
+    pub fn add_loss_term(&mut self, term: impl LossTerm<B> + 'static) {
+        self.loss_terms.push(Box::new(term));
+    }
+
+    /// Set the network for the perceptual loss, see [`TrainConfig::perceptual_weight`].
+    pub fn set_perceptual_loss(&mut self, perceptual: PerceptualLoss<B>) {
+        self.perceptual = Some(perceptual);
+    }
+
+    /// The learned background, if [`TrainConfig::background_model`] is enabled.
+    pub fn environment(&self) -> Option<&EnvironmentMap<B>> {
+        self.environment.as_ref().map(|(env, _)| env)
+    }
+
+    /// The learned color correction per capture, if the scene has several captures and
+    /// [`TrainConfig::capture_appearance`] is enabled.
+    pub fn appearance(&self) -> Option<&CaptureAppearance<B>> {
+        self.appearance.as_ref().map(|(appearance, _)| appearance)
+    }
+
+    /// The learned lighting, if [`TrainConfig::relighting`] is enabled. The trained splats
+    /// are then the albedo, see [`LightingModel::shade`] for the lit splats.
+    pub fn lighting(&self) -> Option<&LightingModel<B>> {
+        self.lighting.as_ref().map(|(lighting, _)| lighting)
+    }
+
+    /// The layer of splats on the ground, if [`TrainConfig::shadow_catcher`] is enabled and
+    /// a ground plane was found.
+    pub fn shadow_catcher(&self) -> Option<&ShadowCatcher<B>> {
+        self.shadow_catcher.as_ref().map(|(catcher, _)| catcher)
+    }
+
+    /// The learned features of the splats, if [`TrainConfig::feature_splatting`] is enabled
+    /// and training got past refinement.
+    pub fn features(&self) -> Option<&FeatureField<B>> {
+        self.features.as_ref().map(|(features, _)| features)
+    }
+
+    /// A spatial index of the splats, built once after every refinement and shared until the
+    /// next. Splats move a little in between, which the index doesn't track.
+    pub async fn spatial_index(&mut self, splats: &Splats<B>) -> Arc<SpatialIndex> {
+        match &self.spatial_index {
+            Some(index) if index.len() == splats.num_splats() => index.clone(),
+            _ => {
+                let index = Arc::new(SpatialIndex::from_splats(splats).await);
+                self.spatial_index = Some(index.clone());
+                index
+            }
+        }
+    }
+
+    /// Place the shadow catcher on the ground of the scene, or the ground estimated below
+    /// `points`, eg. the initial points of the scene. Does nothing if
+    /// [`TrainConfig::shadow_catcher`] is disabled or it's already placed.
+    pub fn fit_shadow_catcher(
+        &mut self,
+        scene: &Scene,
+        points: &[glam::Vec3],
+        ground: Option<GroundPlane>,
+    ) {
+        if !self.config.shadow_catcher || self.shadow_catcher.is_some() {
+            return;
+        }
+
+        let cameras: Vec<_> = scene.views.iter().map(|v| v.camera.clone()).collect();
+        let Some(plane) = ground.or_else(|| GroundPlane::estimate(points, &cameras)) else {
+            log::warn!("No ground plane found, training without a shadow catcher");
+            return;
+        };
+
+        let catcher = ShadowCatcher::new(
+            plane,
+            scene.bounds().center,
+            scene.extent(points),
+            self.config.shadow_catcher_resolution,
+            &self.device,
+        );
+        self.shadow_catcher = Some((catcher, AdamScaledConfig::new().init()));
+    }
+
+    /// Prepare training on a scene. This finds which views need their blur modeled, the
+    /// reference sharpness to weight views by, and sets up the appearance of each capture.
+    pub fn prepare_scene(&mut self, scene: &Scene) {
+        self.median_sharpness = scene.median_sharpness().unwrap_or(0.0);
+
+        // Captures can keep streaming in, grow the appearance to include new ones.
+        let num_captures = scene.num_captures();
+        let current = self.appearance().map_or(1, |a| a.num_captures());
+        if self.config.capture_appearance && num_captures > current {
+            let appearance = match self.appearance.take() {
+                Some((appearance, _)) => appearance.with_captures(num_captures),
+                None => CaptureAppearance::new(num_captures, &self.device),
+            };
+            self.appearance = Some((appearance, AdamScaledConfig::new().init()));
+        }
+
+        if self.config.blur_samples > 1 {
+            self.blur
+                .flag_blurry_views(scene, self.config.blur_sharpness_threshold);
+        }
+    }
+
+    /// The camera of a view, with any refined intrinsics & rolling shutter poses applied.
+    pub fn refined_camera(&self, view: &SceneView) -> Camera {
+        let mut camera = view.camera.clone();
+        self.intrinsics.refine_camera(view, &mut camera);
+        if self.config.rolling_shutter {
+            self.rolling_shutter.refine_camera(view, &mut camera);
+        }
+        camera
+    }
+
     pub(crate) fn reset_opacity(
         &self,
         splats: &mut Splats<B>,
         record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, B>>,
     ) {
+        let Some(region) = &self.config.refine_region else {
+            map_param(
+                &mut splats.raw_opacity,
+                record,
+                |op| Tensor::zeros_like(&op) + inverse_sigmoid(self.config.cull_opacity * 2.0),
+                |state| Tensor::zeros_like(&state),
+            );
+            return;
+        };
+
+        // Only reset the splats in the refined region.
+        let inside = splats_in_region(splats, region).float();
+        let inside_inner = inside.clone().inner();
         map_param(
             &mut splats.raw_opacity,
             record,
-            |op| Tensor::zeros_like(&op) + inverse_sigmoid(self.config.cull_opacity * 2.0),
-            |state| Tensor::zeros_like(&state),
+            |op| {
+                let reset =
+                    Tensor::zeros_like(&op) + inverse_sigmoid(self.config.cull_opacity * 2.0);
+                lerp(op, reset, inside)
+            },
+            |state| state.clone() - state * inside_inner.clone(),
         );
     }
 
+    // Restrict a mask of the splats to the splats in the refined region, if any.
+    fn in_refine_region(&self, splats: &Splats<B>, mask: Tensor<B, 1, Bool>) -> Tensor<B, 1, Bool> {
+        match &self.config.refine_region {
+            Some(region) => Tensor::stack::<2>(vec![mask, splats_in_region(splats, region)], 1)
+                .all_dim(1)
+                .squeeze(1),
+            None => mask,
+        }
+    }
+
     pub async fn step(
         &mut self,
         iter: u32,
@@ -227,23 +632,106 @@ impl SplatTrainer {
 
         let mut splats = splats;
 
+        // Render the albedo lit by the learned lighting, if any.
+        let render_splats = match &self.lighting {
+            Some((lighting, _)) => lighting.shade(&splats),
+            None => splats.clone(),
+        };
+
         let [batch_size, img_h, img_w, _] = batch.gt_images.dims();
 
+        let img_size = glam::uvec2(img_w as u32, img_h as u32);
+        let device = splats.means.device();
+
+        let model_blur = self.config.blur_samples > 1;
+        let optimize_camera =
+            self.config.optimize_intrinsics || self.config.rolling_shutter || model_blur;
+
+        let cameras: Vec<_> = batch
+            .gt_views
+            .iter()
+            .map(|view| self.refined_camera(view))
+            .collect();
+
+        // Each view is rendered from one or more sub cameras, which are averaged.
+        let sub_cameras: Vec<Vec<Camera>> = batch
+            .gt_views
+            .iter()
+            .zip(&cameras)
+            .map(|(view, camera)| {
+                if model_blur && self.blur.is_flagged(view) {
+                    self.blur.sub_cameras(
+                        view,
+                        camera,
+                        self.config.blur_samples,
+                        batch.scene_extent,
+                    )
+                } else {
+                    vec![camera.clone()]
+                }
+            })
+            .collect();
+
+        // Dummy tensors to track the camera gradients.
+        let camera_dummies: Vec<Vec<Tensor<B, 1>>> = sub_cameras
+            .iter()
+            .map(|subs| {
+                subs.iter()
+                    .map(|_| {
+                        let dummy = Tensor::zeros([CAMERA_GRAD_SIZE], &device);
+                        if optimize_camera {
+                            dummy.require_grad()
+                        } else {
+                            dummy
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
         let (pred_images, auxes, loss) = {
             let mut renders = vec![];
             let mut auxes = vec![];
+            let mut sub_auxes = vec![];
+
+            for (subs, dummies) in sub_cameras.iter().zip(&camera_dummies) {
+                let mut sub_renders = vec![];
+
+                for (i, (camera, camera_dummy)) in subs.iter().zip(dummies).enumerate() {
+                    // Only track screenspace gradients for the main render, the refine stats
+                    // are gathered for that.
+                    let (pred_image, aux) = if i == 0 {
+                        render_splats.render_with_camera_grad(
+                            camera,
+                            img_size,
+                            camera_dummy.clone(),
+                        )
+                    } else {
+                        let sub_splats = Splats {
+                            xys_dummy: render_splats.xys_dummy.clone().detach(),
+                            ..render_splats.clone()
+                        };
+                        sub_splats.render_with_camera_grad(camera, img_size, camera_dummy.clone())
+                    };
+
+                    if i == 0 {
+                        auxes.push(aux);
+                    } else {
+                        sub_auxes.push(aux);
+                    }
+                    sub_renders.push(pred_image);
+                }
 
-            for i in 0..batch.gt_views.len() {
-                let camera = &batch.gt_views[i].camera;
-
-                let (pred_image, aux) =
-                    splats.render(camera, glam::uvec2(img_w as u32, img_h as u32), false);
-
+                let num_subs = sub_renders.len();
+                let pred_image = if num_subs == 1 {
+                    sub_renders.remove(0)
+                } else {
+                    Tensor::stack::<4>(sub_renders, 0).sum_dim(0).squeeze(0) / num_subs as f32
+                };
                 renders.push(pred_image);
-                auxes.push(aux);
             }
 
-            for aux in &auxes {
+            for aux in auxes.iter().chain(&sub_auxes) {
                 aux.resolve_bwd_data().await;
             }
 
@@ -257,22 +745,106 @@ impl SplatTrainer {
                 .slice([0..batch_size, 0..img_h, 0..img_w, 0..3])
                 .clamp_min(0.0);
 
+            let has_alpha = batch.gt_views[0].image.has_alpha();
+            let alpha = pred_images
+                .clone()
+                .slice([0..batch_size, 0..img_h, 0..img_w, 3..4]);
+
+            let white_background = self.config.white_background && has_alpha;
+            let gt_images = if white_background {
+                composite_on_white(batch.gt_images.clone())
+            } else {
+                batch.gt_images.clone()
+            };
+
+            // Composite the ground behind the splats. The ground is below the scene, so it's
+            // behind the splats wherever they overlap.
+            let (pred_rgb, alpha) = match &self.shadow_catcher {
+                Some((catcher, _)) if !has_alpha => {
+                    let mut grounds = vec![];
+                    for camera in &cameras {
+                        let (ground, aux) = catcher.render(camera, img_size);
+                        aux.resolve_bwd_data().await;
+                        grounds.push(ground);
+                    }
+                    let grounds = Tensor::stack::<4>(grounds, 0);
+                    let ground_rgb =
+                        grounds
+                            .clone()
+                            .slice([0..batch_size, 0..img_h, 0..img_w, 0..3]);
+                    let ground_alpha = grounds.slice([0..batch_size, 0..img_h, 0..img_w, 3..4]);
+
+                    let transmission = -alpha.clone() + 1.0;
+                    (
+                        pred_rgb + ground_rgb * transmission.clone(),
+                        alpha + ground_alpha * transmission,
+                    )
+                }
+                _ => (pred_rgb, alpha),
+            };
+
+            // Composite the learned background behind the splats.
+            let pred_rgb = match &self.environment {
+                Some((env, _)) if !has_alpha => {
+                    let backgrounds = cameras
+                        .iter()
+                        .map(|camera| env.render(&mut self.environment_rays, camera, img_size))
+                        .collect();
+                    let backgrounds = Tensor::stack::<4>(backgrounds, 0);
+                    pred_rgb + backgrounds * (-alpha + 1.0)
+                }
+                _ if white_background => pred_rgb + (-alpha + 1.0),
+                _ => pred_rgb,
+            };
+
+            // Match the colors of the capture each view comes from.
+            let pred_rgb = match &self.appearance {
+                Some((appearance, _)) => {
+                    let capture_ids: Vec<_> = batch.gt_views.iter().map(|v| v.capture_id).collect();
+                    appearance.apply(pred_rgb, &capture_ids)
+                }
+                None => pred_rgb,
+            };
+
             // This is wrong if the batch has mixed transparent and non-transparent images,
             // but that's ok for now.
-            let pred_compare = if batch.gt_views[0].image.color().has_alpha() {
+            let pred_compare = if has_alpha && !white_background {
                 pred_images.clone()
             } else {
                 pred_rgb.clone()
             };
 
-            let loss = (pred_compare - batch.gt_images.clone()).abs().mean();
-
-            let loss = if self.config.ssim_weight > 0.0 {
-                let gt_rgb =
-                    batch
-                        .gt_images
+            let loss = self.config.photometric_loss.elementwise(
+                pred_compare,
+                gt_images.clone(),
+                self.config.huber_delta,
+                self.config.charbonnier_eps,
+            );
+            let loss = weighted_mean(loss, batch.loss_weights.clone());
+
+            let loss = match &self.perceptual {
+                Some(perceptual)
+                    if self.config.perceptual_weight > 0.0
+                        && iter % self.config.perceptual_every.max(1) == 0 =>
+                {
+                    let gt_rgb = gt_images
                         .clone()
                         .slice([0..batch_size, 0..img_h, 0..img_w, 0..3]);
+                    loss + perceptual.loss(pred_rgb.clone(), gt_rgb) * self.config.perceptual_weight
+                }
+                _ => loss,
+            };
+
+            let loss = if self.config.ssim_weight > 0.0 {
+                let gt_rgb = gt_images
+                    .clone()
+                    .slice([0..batch_size, 0..img_h, 0..img_w, 0..3]);
+
+                // Mask out both images, so ignored pixels match exactly.
+                let (pred_rgb, gt_rgb) = match batch.loss_weights.clone() {
+                    Some(weights) => (pred_rgb.clone() * weights.clone(), gt_rgb * weights),
+                    None => (pred_rgb.clone(), gt_rgb),
+                };
 
                 let ssim_loss = -self.ssim.ssim(pred_rgb, gt_rgb) + 1.0;
                 loss * (1.0 - self.config.ssim_weight) + ssim_loss * self.config.ssim_weight
@@ -280,6 +852,47 @@ impl SplatTrainer {
                 loss
             };
 
+            // Supervise the depth where it was measured, rendered at the resolution of the
+            // depth map.
+            let loss = match &batch.gt_views[0].depth {
+                Some(depth) if self.config.depth_weight > 0.0 => {
+                    let size = glam::uvec2(depth.width, depth.height);
+                    let (pred_depth, aux) =
+                        render_depth_with_aux(&render_splats, &cameras[0], size);
+                    aux.resolve_bwd_data().await;
+                    let gt_depth = depth.to_tensor::<B>(&device);
+                    let measured = gt_depth.clone().greater_elem(0.0).float();
+                    let depth_loss = ((pred_depth - gt_depth) * measured.clone()).abs().sum()
+                        / measured.sum().clamp_min(1.0);
+                    loss + depth_loss * self.config.depth_weight
+                }
+                _ => loss,
+            };
+
+            // Down weight views blurrier than the median, sharper views aren't boosted.
+            let loss = if self.config.sharpness_weighting && self.median_sharpness > 0.0 {
+                let sharpness = batch.gt_views.iter().map(|v| v.sharpness).sum::<f32>()
+                    / batch.gt_views.len() as f32;
+                loss * (sharpness / self.median_sharpness).min(1.0)
+            } else {
+                loss
+            };
+
+            let render = RenderPackage {
+                iter,
+                splats: &render_splats,
+                pred_images: &pred_images,
+                pred_rgb: &pred_rgb,
+                auxes: &auxes,
+                gt_images: &gt_images,
+                gt_views: &batch.gt_views,
+                loss_weights: batch.loss_weights.as_ref(),
+            };
+            let loss = self.loss_terms.iter().fold(loss, |loss, term| {
+                let _span = trace_span!("Loss term", name = term.name()).entered();
+                loss + term.loss(&render)
+            });
+
             (pred_images, auxes, loss)
         };
 
@@ -308,61 +921,195 @@ impl SplatTrainer {
             }
         });
 
-        splats = trace_span!("Optimizer step", sync_burn = true).in_scope(|| {
-            splats = trace_span!("Mean step", sync_burn = true).in_scope(|| {
-                let grad_means =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.means.id]);
-                self.optim.step(lr_mean, splats, grad_means)
-            });
+        if optimize_camera {
+            for ((view, camera), dummies) in batch.gt_views.iter().zip(&cameras).zip(camera_dummies)
+            {
+                let mut sub_grads = vec![];
+                for dummy in dummies {
+                    let Some(v_camera) = dummy.grad_remove(&mut grads) else {
+                        continue;
+                    };
+                    let v: Vec<f32> = v_camera
+                        .into_data_async()
+                        .await
+                        .to_vec()
+                        .expect("Failed to read camera gradient");
+                    sub_grads.push(v);
+                }
 
-            splats = trace_span!("Opacity step", sync_burn = true).in_scope(|| {
-                let grad_opac =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.raw_opacity.id]);
-                self.optim.step(lr_opac, splats, grad_opac)
-            });
+                if sub_grads.is_empty() {
+                    continue;
+                }
 
-            splats = trace_span!("SH Coeffs step", sync_burn = true).in_scope(|| {
-                let grad_coeff =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.sh_coeffs.id]);
+                if sub_grads.len() > 1 {
+                    // Position gradient of each sub camera, from both the start and end translation.
+                    let v_positions: Vec<_> = sub_grads
+                        .iter()
+                        .map(|v| {
+                            -(camera.rotation * glam::vec3(v[4] + v[7], v[5] + v[8], v[6] + v[9]))
+                        })
+                        .collect();
+                    self.blur.step(
+                        view,
+                        camera,
+                        &v_positions,
+                        self.config.lr_blur * batch.scene_extent as f64,
+                    );
+                }
 
-                let coeff_count = sh_coeffs_for_degree(splats.sh_degree()) as i32;
-                let sh_size = coeff_count;
-                let mut sh_lr_scales = vec![1.0];
-                for _ in 1..sh_size {
-                    sh_lr_scales.push(1.0 / self.config.lr_coeffs_sh_scale);
+                // The other camera parameters are shared by all sub cameras.
+                let v: Vec<f32> = (0..CAMERA_GRAD_SIZE)
+                    .map(|i| sub_grads.iter().map(|v| v[i]).sum())
+                    .collect();
+
+                if self.config.optimize_intrinsics {
+                    self.intrinsics.step(
+                        view,
+                        img_size,
+                        [v[0], v[1], v[2], v[3]],
+                        self.config.lr_intrinsics,
+                    );
                 }
-                let sh_lr_scales =
-                    Tensor::<_, 1>::from_floats(sh_lr_scales.as_slice(), &splats.means.device())
-                        .reshape([1, coeff_count, 1]);
-
-                let mut record = self.optim.to_record();
-                let mut param_record = record.get_mut(&splats.sh_coeffs.id);
-                if let Some(param) = param_record.as_mut() {
-                    let mut state = param.clone().into_state();
-                    state.scaling = Some(sh_lr_scales);
-                    record.insert(splats.sh_coeffs.id, AdaptorRecord::from_state(state));
-                    self.optim = self.optim.clone().load_record(record);
+
+                if self.config.rolling_shutter {
+                    let grads = ShutterGrads {
+                        v_trans: [glam::vec3(v[4], v[5], v[6]), glam::vec3(v[7], v[8], v[9])],
+                        v_rot: [
+                            glam::vec3(v[10], v[11], v[12]),
+                            glam::vec3(v[13], v[14], v[15]),
+                        ],
+                    };
+                    self.rolling_shutter.step(
+                        view,
+                        camera,
+                        &grads,
+                        self.config.lr_rolling_shutter * batch.scene_extent as f64,
+                        self.config.lr_rolling_shutter_rotation,
+                    );
                 }
+            }
+        }
 
-                self.optim.step(lr_coeffs, splats, grad_coeff)
-            });
+        let trained = |group| !is_frozen(&self.config.freeze, group, iter);
+        let train_means = trained(ParamGroup::Means);
+        let train_rotations = trained(ParamGroup::Rotations);
+        let train_scales = trained(ParamGroup::Scales);
+        let train_opacity = trained(ParamGroup::Opacity);
+        let train_coeffs = trained(ParamGroup::ShCoeffs);
 
-            splats = trace_span!("Rotation step", sync_burn = true).in_scope(|| {
-                let grad_rot =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.rotation.id]);
-                self.optim.step(lr_rotation, splats, grad_rot)
-            });
+        // The splats outside of the refined region are restored after the step, as the
+        // momentum of the optimizer would still move them.
+        let outside_region = self
+            .config
+            .refine_region
+            .map(|region| (splats_in_region(&splats, &region).float(), splats.clone()));
 
-            splats = trace_span!("Scale step", sync_burn = true).in_scope(|| {
-                let grad_scale =
-                    GradientsParams::from_params(&mut grads, &splats, &[splats.log_scales.id]);
-                self.optim.step(lr_scale, splats, grad_scale)
-            });
+        splats = trace_span!("Optimizer step", sync_burn = true).in_scope(|| {
+            if train_means {
+                splats = trace_span!("Mean step", sync_burn = true).in_scope(|| {
+                    let grad_means =
+                        GradientsParams::from_params(&mut grads, &splats, &[splats.means.id]);
+                    self.optim.step(lr_mean, splats, grad_means)
+                });
+            }
+
+            if train_opacity {
+                splats = trace_span!("Opacity step", sync_burn = true).in_scope(|| {
+                    let grad_opac =
+                        GradientsParams::from_params(&mut grads, &splats, &[splats.raw_opacity.id]);
+                    self.optim.step(lr_opac, splats, grad_opac)
+                });
+            }
+
+            if train_coeffs {
+                splats = trace_span!("SH Coeffs step", sync_burn = true).in_scope(|| {
+                    let grad_coeff =
+                        GradientsParams::from_params(&mut grads, &splats, &[splats.sh_coeffs.id]);
+
+                    let coeff_count = sh_coeffs_for_degree(splats.sh_degree()) as i32;
+                    let sh_size = coeff_count;
+                    let mut sh_lr_scales = vec![1.0];
+                    for _ in 1..sh_size {
+                        sh_lr_scales.push(1.0 / self.config.lr_coeffs_sh_scale);
+                    }
+                    let sh_lr_scales = Tensor::<_, 1>::from_floats(
+                        sh_lr_scales.as_slice(),
+                        &splats.means.device(),
+                    )
+                    .reshape([1, coeff_count, 1]);
+
+                    let mut record = self.optim.to_record();
+                    let mut param_record = record.get_mut(&splats.sh_coeffs.id);
+                    if let Some(param) = param_record.as_mut() {
+                        let mut state = param.clone().into_state();
+                        state.scaling = Some(sh_lr_scales);
+                        record.insert(splats.sh_coeffs.id, AdaptorRecord::from_state(state));
+                        self.optim = self.optim.clone().load_record(record);
+                    }
+
+                    self.optim.step(lr_coeffs, splats, grad_coeff)
+                });
+            }
+
+            if train_rotations {
+                splats = trace_span!("Rotation step", sync_burn = true).in_scope(|| {
+                    let grad_rot =
+                        GradientsParams::from_params(&mut grads, &splats, &[splats.rotation.id]);
+                    self.optim.step(lr_rotation, splats, grad_rot)
+                });
+            }
+
+            if train_scales {
+                splats = trace_span!("Scale step", sync_burn = true).in_scope(|| {
+                    let grad_scale =
+                        GradientsParams::from_params(&mut grads, &splats, &[splats.log_scales.id]);
+                    self.optim.step(lr_scale, splats, grad_scale)
+                });
+            }
 
             // Make sure rotations are still valid after optimization step.
             splats
         });
 
+        if let Some((inside, old)) = outside_region {
+            splats = keep_outside_region(splats, old, inside);
+        }
+
+        if let Some((env, mut optim)) = self.environment.take() {
+            let grad_env = GradientsParams::from_params(&mut grads, &env, &[env.raw_colors.id]);
+            let env = optim.step(self.config.lr_background, env, grad_env);
+            self.environment = Some((env, optim));
+        }
+
+        if let Some((appearance, mut optim)) = self.appearance.take() {
+            let grad_appearance =
+                GradientsParams::from_params(&mut grads, &appearance, &[appearance.transforms.id]);
+            let appearance = optim.step(self.config.lr_appearance, appearance, grad_appearance);
+            self.appearance = Some((appearance, optim));
+        }
+
+        if let Some((lighting, mut optim)) = self.lighting.take() {
+            let grad_lighting =
+                GradientsParams::from_params(&mut grads, &lighting, &[lighting.coeffs.id]);
+            let lighting = optim.step(self.config.lr_lighting, lighting, grad_lighting);
+            self.lighting = Some((lighting, optim));
+        }
+
+        // Only the colors of the shadow catcher are trained, it stays on the ground.
+        if let Some((catcher, mut optim)) = self.shadow_catcher.take() {
+            let grad_catcher = GradientsParams::from_params(
+                &mut grads,
+                &catcher,
+                &[catcher.splats.sh_coeffs.id, catcher.splats.raw_opacity.id],
+            );
+            let catcher = optim.step(self.config.lr_shadow_catcher, catcher, grad_catcher);
+            self.shadow_catcher = Some((catcher, optim));
+        }
+
+        if let Some(target) = &batch.gt_views[0].features {
+            self.step_features(iter, &splats, &cameras[0], target).await;
+        }
+
         let stats = TrainStepStats {
             pred_images,
             gt_images: batch.gt_images,
@@ -379,15 +1126,58 @@ impl SplatTrainer {
         (splats, stats)
     }
 
+    // Train the features of the splats on the feature map of a view. This is a separate
+    // pass, the geometry of the splats is fixed by then and not trained on the features.
+    async fn step_features(
+        &mut self,
+        iter: u32,
+        splats: &Splats<B>,
+        camera: &Camera,
+        target: &FeatureMap,
+    ) {
+        if !self.config.feature_splatting || iter < self.config.refine_stop_iter {
+            return;
+        }
+
+        // Start over if the splats changed after all, or the views have other features.
+        let num_splats = splats.num_splats();
+        let (features, mut optim) = match self.features.take() {
+            Some((features, optim))
+                if features.num_splats() == num_splats && features.dim() == target.dim =>
+            {
+                (features, optim)
+            }
+            _ => (
+                FeatureField::new(num_splats, target.dim, &self.device),
+                AdamScaledConfig::new().init(),
+            ),
+        };
+
+        let size = glam::uvec2(target.width, target.height);
+        let pred = features.render(splats, camera, size).await;
+        let loss = (pred - target.to_tensor(&self.device)).abs().mean();
+        let mut grads = loss.backward();
+
+        let grad_features =
+            GradientsParams::from_params(&mut grads, &features, &[features.features.id]);
+        let features = optim.step(self.config.lr_features, features, grad_features);
+        self.features = Some((features, optim));
+    }
+
     pub async fn refine_if_needed(
         &mut self,
         iter: u32,
         splats: Splats<B>,
         scene_extent: f32,
     ) -> (Splats<B>, Option<RefineStats>) {
+        // Refinement moves, adds and removes splats, so it waits while their shape is frozen.
+        let geometry_frozen = ParamGroup::ALL
+            .into_iter()
+            .any(|group| group.is_geometry() && is_frozen(&self.config.freeze, group, iter));
         let do_refine = iter < self.config.refine_stop_iter
             && iter >= self.config.refine_start_iter
-            && iter % self.config.refine_every == 1;
+            && iter % self.config.refine_every == 1
+            && !geometry_frozen;
 
         if do_refine {
             // If not refining, update splat to step with gradients applied.
@@ -410,6 +1200,9 @@ impl SplatTrainer {
 
         let device = splats.means.device();
 
+        // Splats are added and removed, so the index has to be rebuilt.
+        self.spatial_index = None;
+
         // Otherwise, do refinement, but do the split/clone on gaussians with no grads applied.
         let avg_grad = self.refine_record.average_grad_2d();
 
@@ -431,6 +1224,7 @@ impl SplatTrainer {
                 .all_dim(1)
                 .squeeze::<1>(1);
 
+        let clone_mask = self.in_refine_region(&splats, clone_mask);
         let clone_inds = clone_mask.clone().argwhere_async().await;
 
         // Clone splats
@@ -475,6 +1269,7 @@ impl SplatTrainer {
             .any_dim(1)
             .squeeze::<1>(1);
 
+        let split_mask = self.in_refine_region(&splats, split_mask);
         let split_inds = split_mask.clone().argwhere_async().await;
 
         let split_count = split_inds.dims()[0];
@@ -513,6 +1308,7 @@ impl SplatTrainer {
         let start_count = splats.num_splats();
         // Remove barely visible gaussians.
         let alpha_mask = splats.opacity().lower_elem(self.config.cull_opacity);
+        let alpha_mask = self.in_refine_region(&splats, alpha_mask);
         prune_points(&mut splats, &mut record, alpha_mask).await;
         let alpha_pruned = start_count - splats.num_splats();
 
@@ -522,11 +1318,15 @@ impl SplatTrainer {
             .val()
             .greater_elem((self.config.cull_scale3d_percentage_threshold * scene_extent).ln());
 
-        // less than e^-10, too small to care about.
-        let scale_small = splats.log_scales.val().lower_elem(-10.0);
+        // Too small to care about.
+        let scale_small = splats
+            .log_scales
+            .val()
+            .lower_elem((self.config.cull_scale3d_min_fraction * scene_extent).ln());
 
         let scale_mask =
             Tensor::any_dim(Tensor::cat(vec![scale_small, scale_big], 1), 1).squeeze(1);
+        let scale_mask = self.in_refine_region(&splats, scale_mask);
         prune_points(&mut splats, &mut record, scale_mask).await;
         let scale_pruned = start_count - splats.num_splats();
 
@@ -549,7 +1349,9 @@ impl SplatTrainer {
         }
 
         let refine_step = iter / self.config.refine_every;
-        if refine_step % self.config.reset_alpha_every_refine == 0 {
+        if refine_step % self.config.reset_alpha_every_refine == 0
+            && !is_frozen(&self.config.freeze, ParamGroup::Opacity, iter)
+        {
             self.reset_opacity(&mut splats, &mut record);
         }
 
@@ -581,6 +1383,94 @@ fn map_param<B: AutodiffBackend, const D: usize>(
     record.insert(param.id, AdaptorRecord::from_state(state));
 }
 
+/// Which splats have their mean inside of `region`.
+pub fn splats_in_region<B: Backend>(
+    splats: &Splats<B>,
+    region: &BoundingBox,
+) -> Tensor<B, 1, Bool> {
+    let means = splats.means.val();
+    let num_splats = splats.num_splats();
+    let (min, max) = (region.min(), region.max());
+    let bounds = (0..3)
+        .flat_map(|axis| {
+            let coord = means.clone().slice([0..num_splats, axis..axis + 1]);
+            [
+                coord.clone().greater_equal_elem(min[axis]),
+                coord.lower_equal_elem(max[axis]),
+            ]
+        })
+        .collect();
+    Tensor::cat(bounds, 1).all_dim(1).squeeze(1)
+}
+
+/// Adds `count` splats at random positions in `region`, with random colors and the same SH
+/// degree as `splats`. Training the region afterwards fits them to the views, see
+/// [`TrainConfig::heal_seeds`].
+pub fn seed_region<B: Backend>(
+    splats: Splats<B>,
+    region: BoundingBox,
+    count: usize,
+    rng: &mut impl rand::Rng,
+) -> Splats<B> {
+    if count == 0 {
+        return splats;
+    }
+    let device = splats.means.val().device();
+    let seeds = Splats::<B>::from_random_config(
+        &RandomSplatsConfig::new().with_init_count(count),
+        region,
+        rng,
+        &device,
+    )
+    .with_sh_degree(splats.sh_degree());
+    Splats::from_tensor_data(
+        Tensor::cat(vec![splats.means.val(), seeds.means.val()], 0),
+        Tensor::cat(vec![splats.rotation.val(), seeds.rotation.val()], 0),
+        Tensor::cat(vec![splats.log_scales.val(), seeds.log_scales.val()], 0),
+        Tensor::cat(vec![splats.sh_coeffs.val(), seeds.sh_coeffs.val()], 0),
+        Tensor::cat(vec![splats.raw_opacity.val(), seeds.raw_opacity.val()], 0),
+    )
+}
+
+fn lerp<B: Backend, const D: usize>(
+    from: Tensor<B, D>,
+    to: Tensor<B, D>,
+    weight: Tensor<B, D>,
+) -> Tensor<B, D> {
+    from.clone() + (to - from) * weight
+}
+
+// Restore the splats outside of the refined region to their values before an optimizer step.
+// `inside` is 1 for the splats in the region, and 0 for the others.
+fn keep_outside_region<B: AutodiffBackend>(
+    mut splats: Splats<B>,
+    old: Splats<B>,
+    inside: Tensor<B, 1>,
+) -> Splats<B> {
+    let num_splats = splats.num_splats();
+    let inside_2d = inside.clone().reshape([num_splats, 1]);
+    Splats::map_param(&mut splats.means, |new| {
+        lerp(old.means.val(), new, inside_2d.clone())
+    });
+    Splats::map_param(&mut splats.rotation, |new| {
+        lerp(old.rotation.val(), new, inside_2d.clone())
+    });
+    Splats::map_param(&mut splats.log_scales, |new| {
+        lerp(old.log_scales.val(), new, inside_2d.clone())
+    });
+    Splats::map_param(&mut splats.sh_coeffs, |new| {
+        lerp(
+            old.sh_coeffs.val(),
+            new,
+            inside.clone().reshape([num_splats, 1, 1]),
+        )
+    });
+    Splats::map_param(&mut splats.raw_opacity, |new| {
+        lerp(old.raw_opacity.val(), new, inside)
+    });
+    splats
+}
+
 // Prunes points based on the given mask.
 //
 // Args:
@@ -1,69 +1,33 @@
 use std::sync::Arc;
 
+use brush_kernel::texture::{aligned_width, copy_to_texture};
+use brush_render::{
+    camera::{focal_to_fov, Camera},
+    gaussian_splats::Splats,
+    RenderOptions,
+};
 use burn::{
     backend::{
         wgpu::{JitBackend, WgpuRuntime},
         Wgpu,
     },
-    tensor::{Tensor, TensorPrimitive},
+    tensor::{Int, Tensor, TensorPrimitive},
 };
 use burn_fusion::client::FusionClient;
+use burn_wgpu::JitTensor;
 use eframe::egui_wgpu::Renderer;
 use egui::epaint::mutex::RwLock as EguiRwLock;
 use egui::TextureId;
-use wgpu::{CommandEncoderDescriptor, ImageDataLayout, TextureViewDescriptor};
+use wgpu::{CommandEncoderDescriptor, TextureViewDescriptor};
 
 type InnerWgpu = JitBackend<WgpuRuntime, f32, i32, u32>;
 
-fn copy_buffer_to_texture(
-    img: Tensor<InnerWgpu, 3>,
-    texture: &wgpu::Texture,
-    encoder: &mut wgpu::CommandEncoder,
-) {
-    let [height, width, c] = img.dims();
-
-    let padded_shape = vec![height, width.div_ceil(64) * 64, c];
-
-    // Create padded tensor if needed. The bytes_per_row needs to be divisible
-    // by 256 in WebGPU, so 4 bytes per pixel means width needs to be disible by 64.
-    let padded = if width % 64 != 0 {
-        let padded = Tensor::zeros(&padded_shape, &img.device());
-        padded.slice_assign([0..height, 0..width], img)
-    } else {
-        img
-    };
-
-    let prim = padded.into_primitive().tensor();
-
-    let client = &prim.client;
-    client.flush();
-    let img_res = client.get_resource(prim.handle.clone().binding());
-
-    // Put compute passes in encoder before copying the buffer.
-    let bytes_per_row = Some(4 * padded_shape[1] as u32);
-
-    // Now copy the buffer to the texture.
-    encoder.copy_buffer_to_texture(
-        wgpu::ImageCopyBuffer {
-            buffer: img_res.resource().buffer.as_ref(),
-            layout: ImageDataLayout {
-                offset: img_res.resource().offset(),
-                bytes_per_row,
-                rows_per_image: None,
-            },
-        },
-        wgpu::ImageCopyTexture {
-            texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
-            aspect: wgpu::TextureAspect::All,
-        },
-        wgpu::Extent3d {
-            width: width as u32,
-            height: height as u32,
-            depth_or_array_layers: 1,
-        },
-    );
+// Get the image as a tensor of the inner backend, so its buffer can be copied.
+fn resolve_image(img: Tensor<Wgpu, 3>) -> Tensor<InnerWgpu, 3> {
+    let img = img.into_primitive().tensor();
+    let client = img.client.clone();
+    let img = client.resolve_tensor_float::<InnerWgpu>(img);
+    Tensor::from_primitive(TensorPrimitive::Float(img))
 }
 
 struct TextureState {
@@ -103,20 +67,12 @@ impl BurnTexture {
         }
     }
 
-    pub fn update_texture(
+    // Make sure the texture matches the size of the image, and return it.
+    fn texture_for_size(
         &mut self,
-        img: Tensor<Wgpu, 3>,
+        size: glam::UVec2,
         renderer: &EguiRwLock<Renderer>,
-    ) -> TextureId {
-        let mut encoder = self
-            .device
-            .create_command_encoder(&CommandEncoderDescriptor {
-                label: Some("viewer encoder"),
-            });
-
-        let [h, w, _] = img.shape().dims();
-        let size = glam::uvec2(w as u32, h as u32);
-
+    ) -> &TextureState {
         let dirty = if let Some(s) = self.state.as_ref() {
             s.texture.width() != size.x || s.texture.height() != size.y
         } else {
@@ -124,7 +80,7 @@ impl BurnTexture {
         };
 
         if dirty {
-            let texture = create_texture(glam::uvec2(w as u32, h as u32), &self.device);
+            let texture = create_texture(size, &self.device);
 
             if let Some(s) = self.state.as_mut() {
                 s.texture = texture;
@@ -146,19 +102,118 @@ impl BurnTexture {
             }
         }
 
-        let Some(s) = self.state.as_ref() else {
-            unreachable!("Somehow failed to initialize")
+        self.state.as_ref().expect("Texture was just initialized")
+    }
+
+    fn copy_image(
+        &mut self,
+        img: JitTensor<WgpuRuntime>,
+        size: glam::UVec2,
+        renderer: &EguiRwLock<Renderer>,
+    ) -> TextureId {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("viewer encoder"),
+            });
+
+        let state = self.texture_for_size(size, renderer);
+        copy_to_texture(&img, &state.texture, &mut encoder);
+        let id = state.id;
+
+        self.queue.submit([encoder.finish()]);
+        id
+    }
+
+    /// Copy an image of packed rgba8 pixels into the texture.
+    ///
+    /// Images with a width that can't be copied directly are padded first, see
+    /// [`Self::render_splats`] to avoid the extra copy.
+    pub fn update_texture(
+        &mut self,
+        img: Tensor<Wgpu, 3>,
+        renderer: &EguiRwLock<Renderer>,
+    ) -> TextureId {
+        let [h, w, c] = img.shape().dims();
+        let img = resolve_image(img);
+
+        // The bytes per row need to be a multiple of 256 to copy a buffer
+        // to a texture, so pad the image if needed.
+        let padded_width = aligned_width(w as u32) as usize;
+        let img = if padded_width != w {
+            let padded = Tensor::zeros([h, padded_width, c], &img.device());
+            padded.slice_assign([0..h, 0..w], img)
+        } else {
+            img
         };
 
         let img = img.into_primitive().tensor();
-        let client = img.client.clone();
-        let img = client.resolve_tensor_float::<InnerWgpu>(img);
-        let img = Tensor::from_primitive(TensorPrimitive::Float(img));
-        copy_buffer_to_texture(img, &s.texture, &mut encoder);
+        self.copy_image(img, glam::uvec2(w as u32, h as u32), renderer)
+    }
 
-        self.queue.submit([encoder.finish()]);
+    /// Copy an image of `[h, w, 4]` float colors between 0 and 1 into the texture.
+    pub fn update_texture_rgba(
+        &mut self,
+        img: Tensor<Wgpu, 3>,
+        renderer: &EguiRwLock<Renderer>,
+    ) -> TextureId {
+        let [h, w, _] = img.shape().dims();
+        let device = img.device();
+
+        // Pack the channels into the bytes of an integer. The alpha overflows into the sign
+        // bit, which leaves the bits as they should be.
+        let bytes = (img.clamp(0.0, 1.0) * 255.0 + 0.5).int();
+        let shifts = Tensor::<Wgpu, 1, Int>::from_ints([1, 1 << 8, 1 << 16, 1 << 24], &device)
+            .reshape([1, 1, 4]);
+        let packed = (bytes * shifts).sum_dim(2);
+
+        let packed = packed.into_primitive();
+        let client = packed.client.clone();
+        let packed: Tensor<InnerWgpu, 3, Int> =
+            Tensor::from_primitive(client.resolve_tensor_int::<InnerWgpu>(packed));
+
+        let padded_width = aligned_width(w as u32) as usize;
+        let packed = if padded_width != w {
+            let padded = Tensor::zeros([h, padded_width, 1], &packed.device());
+            padded.slice_assign([0..h, 0..w], packed)
+        } else {
+            packed
+        };
+
+        self.copy_image(
+            packed.into_primitive(),
+            glam::uvec2(w as u32, h as u32),
+            renderer,
+        )
+    }
+
+    /// Render splats straight into the texture.
+    ///
+    /// The splats are rendered at a width that can be copied to a texture as is, with
+    /// the camera adjusted so the visible part of the image is unchanged. The padding
+    /// columns are then cropped by the copy, so the image never leaves the GPU and
+    /// isn't copied more than once.
+    pub fn render_splats(
+        &mut self,
+        splats: &Splats<Wgpu>,
+        camera: &Camera,
+        size: glam::UVec2,
+        options: RenderOptions,
+        renderer: &EguiRwLock<Renderer>,
+    ) -> TextureId {
+        let padded_size = glam::uvec2(aligned_width(size.x), size.y);
+
+        // Keep the focal length and principal point in pixels the same.
+        let focal = camera.focal(size);
+        let center = camera.center(size);
+        let padded_camera = Camera {
+            fov_x: focal_to_fov(focal.x as f64, padded_size.x),
+            center_uv: glam::vec2(center.x / padded_size.x as f32, camera.center_uv.y),
+            ..camera.clone()
+        };
 
-        s.id
+        let img = splats.render_inference(&padded_camera, padded_size, true, options);
+        self.copy_image(resolve_image(img).into_primitive().tensor(), size, renderer)
     }
 
     pub fn id(&self) -> Option<TextureId> {
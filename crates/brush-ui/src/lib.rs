@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use burn_wgpu::{RuntimeOptions, WgpuDevice};
+use burn_wgpu::WgpuDevice;
 use eframe::egui_wgpu::WgpuConfiguration;
 use wgpu::{Adapter, Device, Queue};
 
@@ -12,38 +12,51 @@ pub fn create_wgpu_device(
     device: Arc<Device>,
     queue: Arc<Queue>,
 ) -> WgpuDevice {
-    let setup = burn_wgpu::WgpuSetup {
-        instance: Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor::default())), // unused... need to fix this in Burn.
-        adapter,
-        device,
-        queue,
-    };
-
-    burn_wgpu::init_device(
-        setup,
-        RuntimeOptions {
-            tasks_max: 64,
-            memory_config: burn_wgpu::MemoryConfiguration::ExclusivePages,
-        },
-    )
+    brush_render::device::init_device(adapter, device, queue)
 }
 
 pub fn create_egui_options() -> WgpuConfiguration {
     WgpuConfiguration {
         wgpu_setup: eframe::egui_wgpu::WgpuSetup::CreateNew {
-            supported_backends: wgpu::Backends::all(),
+            // WGPU_BACKEND=gl forces eg. the GL fallback.
+            supported_backends: wgpu::util::backend_bits_from_env()
+                .unwrap_or(wgpu::Backends::all()),
             power_preference: wgpu::PowerPreference::HighPerformance,
-            device_descriptor: Arc::new(|adapter: &Adapter| wgpu::DeviceDescriptor {
-                label: Some("egui+burn"),
-                required_features: adapter.features(),
-                required_limits: adapter.limits(),
-                memory_hints: wgpu::MemoryHints::Performance,
-            }),
+            device_descriptor: Arc::new(device_descriptor),
+        },
+        ..Default::default()
+    }
+}
+
+/// Like [`create_egui_options`], but with a device that was already created, eg. on an
+/// adapter picked by the user.
+pub fn create_egui_options_with(
+    instance: Arc<wgpu::Instance>,
+    adapter: Arc<Adapter>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+) -> WgpuConfiguration {
+    WgpuConfiguration {
+        wgpu_setup: eframe::egui_wgpu::WgpuSetup::Existing {
+            instance,
+            adapter,
+            device,
+            queue,
         },
         ..Default::default()
     }
 }
 
+/// The device to create on an adapter, with all the features and limits it supports.
+pub fn device_descriptor(adapter: &Adapter) -> wgpu::DeviceDescriptor<'static> {
+    wgpu::DeviceDescriptor {
+        label: Some("egui+burn"),
+        required_features: adapter.features(),
+        required_limits: adapter.limits(),
+        memory_hints: wgpu::MemoryHints::Performance,
+    }
+}
+
 pub fn draw_checkerboard(ui: &mut egui::Ui, rect: egui::Rect) {
     let id = egui::Id::new("checkerboard");
     let handle = ui
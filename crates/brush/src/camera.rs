@@ -0,0 +1,101 @@
+use glam::{Affine3A, Mat3, Mat4, Quat, UVec2, Vec2, Vec3};
+
+/// A pinhole camera looking down its local +Z axis, with +X to the right and +Y down in the
+/// image, like OpenCV and COLMAP.
+///
+/// The intrinsics are relative to the image size, so the same camera renders any resolution.
+/// Make one with [`Camera::from_focal`] or [`Camera::from_fov`], and place it with one of the
+/// pose builders.
+#[derive(Clone, Debug)]
+pub struct Camera(pub(crate) brush_render::camera::Camera);
+
+impl Camera {
+    /// A camera at the origin with the given fields of view in radians, with the principal
+    /// point in the center.
+    pub fn from_fov(fov_x: f64, fov_y: f64) -> Self {
+        Self(brush_render::camera::Camera::from_fov(fov_x, fov_y))
+    }
+
+    /// A camera at the origin with focal lengths and a principal point in pixels of an image
+    /// of `img_size`.
+    pub fn from_focal(focal: Vec2, center: Vec2, img_size: UVec2) -> Self {
+        Self(brush_render::camera::Camera::from_focal(
+            focal, center, img_size,
+        ))
+    }
+
+    /// A camera at the origin with the intrinsics matrix `K` of an image of `img_size`. Skew
+    /// isn't supported and ignored.
+    pub fn from_intrinsics(k: Mat3, img_size: UVec2) -> Self {
+        Self(brush_render::camera::Camera::from_intrinsics(k, img_size))
+    }
+
+    /// Place the camera at `position`, with `rotation` from camera to world space.
+    pub fn with_pose(self, position: Vec3, rotation: Quat) -> Self {
+        Self(self.0.with_pose(position, rotation))
+    }
+
+    /// Place the camera with a camera to world transform. Any scale is dropped.
+    pub fn with_world_from_camera(self, transform: Affine3A) -> Self {
+        Self(self.0.with_world_from_camera(transform))
+    }
+
+    /// Place the camera with a world to camera transform, a view matrix. Any scale is dropped.
+    pub fn with_camera_from_world(self, transform: Affine3A) -> Self {
+        Self(self.0.with_camera_from_world(transform))
+    }
+
+    /// Place the camera with a COLMAP pose, the rotation and translation of the world to
+    /// camera transform, as in `images.txt`.
+    pub fn with_colmap_pose(self, quat: Quat, tvec: Vec3) -> Self {
+        Self(self.0.with_colmap_pose(quat, tvec))
+    }
+
+    /// Place the camera with an OpenCV pose, the `rvec` and `tvec` of eg. `solvePnP`.
+    pub fn with_opencv_pose(self, rvec: Vec3, tvec: Vec3) -> Self {
+        Self(self.0.with_opencv_pose(rvec, tvec))
+    }
+
+    /// Place the camera with a camera to world transform of a camera looking down -Z with +Y
+    /// up, as used by OpenGL, nerfstudio, Blender and ARKit.
+    pub fn with_opengl_world_from_camera(self, transform: Mat4) -> Self {
+        Self(self.0.with_opengl_world_from_camera(transform))
+    }
+
+    /// Position of the camera in world space.
+    pub fn position(&self) -> Vec3 {
+        self.0.position
+    }
+
+    /// Rotation from camera space to world space.
+    pub fn rotation(&self) -> Quat {
+        self.0.rotation
+    }
+
+    /// The camera to world transform.
+    pub fn world_from_camera(&self) -> Affine3A {
+        self.0.world_from_camera()
+    }
+
+    /// The world to camera transform, a view matrix.
+    pub fn camera_from_world(&self) -> Affine3A {
+        self.0.camera_from_world()
+    }
+
+    /// The camera to world transform of the camera looking down -Z with +Y up, see
+    /// [`Self::with_opengl_world_from_camera`].
+    pub fn opengl_world_from_camera(&self) -> Mat4 {
+        self.0.opengl_world_from_camera()
+    }
+
+    /// The intrinsics matrix `K` for an image of `img_size`.
+    pub fn intrinsics(&self, img_size: UVec2) -> Mat3 {
+        self.0.intrinsics(img_size)
+    }
+
+    /// The ray through a point of the image, given as a fraction of the image size. Returns
+    /// the origin and normalized direction of the ray in world space.
+    pub fn ray(&self, uv: Vec2) -> (Vec3, Vec3) {
+        self.0.ray(uv)
+    }
+}
@@ -0,0 +1,58 @@
+/// Options for training, see [`crate::Trainer`]. Start from [`TrainConfig::new`], which has
+/// the defaults of the Brush app, and change options with the `with_` setters.
+#[derive(Clone, Debug)]
+pub struct TrainConfig(pub(crate) brush_train::train::TrainConfig);
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrainConfig {
+    pub fn new() -> Self {
+        Self(brush_train::train::TrainConfig::new())
+    }
+
+    /// Seed of the random initialization and of the order views are trained in.
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self(self.0.with_seed(seed))
+    }
+
+    /// Stop adding and pruning splats after this many steps.
+    pub fn with_refine_stop_iter(self, iter: u32) -> Self {
+        Self(self.0.with_refine_stop_iter(iter))
+    }
+
+    /// Weight of the loss on the depth of views with a measured depth, eg. from an RGB-D
+    /// sensor.
+    pub fn with_depth_weight(self, weight: f32) -> Self {
+        Self(self.0.with_depth_weight(weight))
+    }
+
+    /// Leave out this fraction of the training views, the blurriest ones.
+    pub fn with_drop_blurry_fraction(self, fraction: f32) -> Self {
+        Self(self.0.with_drop_blurry_fraction(fraction))
+    }
+
+    /// Composite transparent images and the renders onto white and compare only the colors,
+    /// as the NeRF synthetic benchmark does, instead of training the alpha.
+    pub fn with_white_background(self, white_background: bool) -> Self {
+        Self(self.0.with_white_background(white_background))
+    }
+
+    /// Train on images downscaled `levels` times by half first, doubling the resolution every
+    /// `every` steps. 0 levels trains at the full resolution from the start.
+    pub fn with_coarse_to_fine(self, levels: u32, every: u32) -> Self {
+        Self(
+            self.0
+                .with_coarse_to_fine_levels(levels)
+                .with_coarse_to_fine_every(every),
+        )
+    }
+
+    /// GPU memory to keep the training images in, in megabytes.
+    pub fn with_gpu_image_budget_mb(self, budget: u32) -> Self {
+        Self(self.0.with_gpu_image_budget_mb(budget))
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use brush_dataset::{brush_vfs::BrushVfs, LoadDatasetArgs};
+use brush_render::gaussian_splats::Splats as InnerSplats;
+use burn::module::AutodiffModule;
+use tokio_stream::StreamExt;
+
+use crate::{Camera, DiffBackend, Splats};
+
+/// How to load a dataset.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct LoadOptions {
+    /// Load at most this many views.
+    pub max_frames: Option<usize>,
+    /// Downscale images so their longest side is at most this many pixels.
+    pub max_resolution: Option<u32>,
+    /// Hold out every nth view for evaluation.
+    pub eval_split_every: Option<usize>,
+}
+
+impl LoadOptions {
+    fn load_args(&self) -> LoadDatasetArgs {
+        LoadDatasetArgs {
+            max_frames: self.max_frames,
+            max_resolution: self.max_resolution,
+            eval_split_every: self.eval_split_every,
+            ..Default::default()
+        }
+    }
+}
+
+/// Posed images to train on, in any format Brush reads: COLMAP, nerfstudio or a zip of
+/// either.
+#[derive(Clone)]
+pub struct Dataset {
+    pub(crate) inner: brush_dataset::Dataset,
+    pub(crate) init_splats: Option<InnerSplats<DiffBackend>>,
+}
+
+impl Dataset {
+    /// Load a dataset from a directory or a zip file, waiting until all views are loaded.
+    pub async fn load(path: &Path, options: &LoadOptions) -> anyhow::Result<Self> {
+        let vfs = if path.is_dir() {
+            BrushVfs::from_directory(path).await?
+        } else {
+            Self::zip_vfs(std::fs::read(path)?).await?
+        };
+        Self::from_vfs(vfs, options).await
+    }
+
+    /// Load a dataset from the bytes of a zip file.
+    pub async fn from_zip(data: Vec<u8>, options: &LoadOptions) -> anyhow::Result<Self> {
+        Self::from_vfs(Self::zip_vfs(data).await?, options).await
+    }
+
+    async fn zip_vfs(data: Vec<u8>) -> anyhow::Result<BrushVfs> {
+        BrushVfs::from_zip_reader(std::io::Cursor::new(data))
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    async fn from_vfs(vfs: BrushVfs, options: &LoadOptions) -> anyhow::Result<Self> {
+        let (mut splat_stream, mut data_stream) =
+            brush_dataset::load_dataset::<DiffBackend>(vfs, &options.load_args(), &crate::device())
+                .await?;
+
+        let mut init_splats = None;
+        while let Some(message) = splat_stream.next().await {
+            init_splats = Some(message?.splats);
+        }
+        let mut inner = brush_dataset::Dataset::empty();
+        while let Some(dataset) = data_stream.next().await {
+            inner = dataset?;
+        }
+        Ok(Self { inner, init_splats })
+    }
+
+    /// The camera and image size of every training view.
+    pub fn train_views(&self) -> Vec<(Camera, glam::UVec2)> {
+        self.inner
+            .train
+            .views
+            .iter()
+            .map(|v| {
+                (
+                    Camera(v.camera.clone()),
+                    glam::uvec2(v.image.width(), v.image.height()),
+                )
+            })
+            .collect()
+    }
+
+    /// The camera and image size of every view held out for evaluation.
+    pub fn eval_views(&self) -> Vec<(Camera, glam::UVec2)> {
+        self.inner
+            .eval
+            .iter()
+            .flat_map(|s| &s.views)
+            .map(|v| {
+                (
+                    Camera(v.camera.clone()),
+                    glam::uvec2(v.image.width(), v.image.height()),
+                )
+            })
+            .collect()
+    }
+
+    /// The splats training starts from, eg. the points of a COLMAP reconstruction.
+    pub fn initial_splats(&self) -> Option<Splats> {
+        self.init_splats.as_ref().map(|s| Splats(s.valid()))
+    }
+}
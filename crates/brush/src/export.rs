@@ -0,0 +1,15 @@
+//! Write splats and datasets to files other tools read.
+use crate::Splats;
+
+/// The bytes of a ply file with the splats, in the format of the original Gaussian splatting
+/// code, which most splat viewers read.
+pub async fn ply(splats: &Splats) -> anyhow::Result<Vec<u8>> {
+    brush_dataset::splat_export::splat_to_ply(splats.0.clone()).await
+}
+
+/// The files of the dataset in the nerfstudio format, as paths relative to the output
+/// directory and their contents.
+#[cfg(feature = "train")]
+pub fn nerfstudio(dataset: &crate::Dataset) -> anyhow::Result<Vec<(std::path::PathBuf, Vec<u8>)>> {
+    brush_dataset::dataset_export::dataset_to_nerfstudio(&dataset.inner)
+}
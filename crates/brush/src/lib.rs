@@ -0,0 +1,66 @@
+//! Train and render Gaussian splats from Rust, without the Brush viewer.
+//!
+//! This crate is the stable API of Brush: it follows semver, while the `brush-*` crates it
+//! wraps can change in any release. Types here wrap the internal ones instead of exposing
+//! them, and option structs are `#[non_exhaustive]` or have private fields so options can be
+//! added. [`glam`] and [`image`] are re-exported as the versions used in the API, a major
+//! update of either is a major release of this crate.
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! use brush::{Dataset, LoadOptions, Renderer, TrainConfig, Trainer};
+//!
+//! let dataset = Dataset::load("garden".as_ref(), &LoadOptions::default()).await?;
+//! let mut trainer = Trainer::new(&dataset, TrainConfig::new())?;
+//! for _ in 0..1000 {
+//!     trainer.step().await?;
+//! }
+//! let splats = trainer.splats();
+//!
+//! let (camera, size) = dataset.train_views()[0].clone();
+//! let image = Renderer::new().render(&splats, &camera, size).await?;
+//! std::fs::write("garden.ply", brush::export::ply(&splats).await?)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Everything runs on the default wgpu device.
+//!
+//! # Features
+//!
+//! - `train` (default): load datasets and train. Apps that only display splats can turn off
+//!   default features, which leaves out training, dataset loading and autodiff. Splats can
+//!   then still be read from ply files, rendered, and exported.
+mod camera;
+#[cfg(feature = "train")]
+mod config;
+#[cfg(feature = "train")]
+mod dataset;
+pub mod export;
+mod render;
+mod splats;
+#[cfg(feature = "train")]
+mod trainer;
+
+#[cfg(feature = "train")]
+pub use dataset::{Dataset, LoadOptions};
+pub use camera::Camera;
+#[cfg(feature = "train")]
+pub use config::TrainConfig;
+pub use render::Renderer;
+pub use splats::Splats;
+#[cfg(feature = "train")]
+pub use trainer::{StepStats, Trainer};
+
+/// The color space of rendered images, see [`Renderer::with_color_space`].
+pub use brush_render::ColorSpace;
+pub use glam;
+pub use image;
+
+type Backend = burn_wgpu::Wgpu;
+#[cfg(feature = "train")]
+type DiffBackend = burn::backend::Autodiff<Backend>;
+
+fn device() -> burn_wgpu::WgpuDevice {
+    brush_render::device::default_device()
+}
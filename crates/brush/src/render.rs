@@ -0,0 +1,147 @@
+use anyhow::Context;
+use brush_render::{ColorSpace, Foveation, RenderOptions};
+use burn::tensor::{Tensor, TensorData};
+
+use crate::{Camera, Splats};
+
+/// Renders [`Splats`] to images.
+#[derive(Clone, Debug, Default)]
+pub struct Renderer {
+    options: RenderOptions,
+    // Gaze in uv coordinates and the radius of the fovea.
+    fovea: Option<(glam::Vec2, f32)>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Blend splats without sorting them by depth. This scales better to huge scenes, at the
+    /// cost of some blending errors where splats overlap.
+    pub fn with_order_independent(mut self, order_independent: bool) -> Self {
+        self.options.order_independent = order_independent;
+        self
+    }
+
+    /// Only use the base color of each splat, ignoring view dependent effects. Faster to
+    /// render.
+    pub fn with_view_independent(mut self, view_independent: bool) -> Self {
+        self.options.view_independent = view_independent;
+        self
+    }
+
+    /// Only render at full resolution around `gaze`, in uv coordinates of the image, eg. from
+    /// the eye tracking of a headset. `radius` is the size of the fovea as a fraction of the
+    /// image, further away the image is rendered in coarser blocks of pixels, which is much
+    /// cheaper. The gaze usually changes every frame, the renderer is cheap to clone.
+    pub fn with_fovea(mut self, gaze: glam::Vec2, radius: f32) -> Self {
+        self.fovea = Some((gaze, radius));
+        self
+    }
+
+    /// Convert the colors for the display they're shown on, eg. [`ColorSpace::DisplayP3`] for
+    /// a wide gamut display. Defaults to sRGB.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.options.color_space = color_space;
+        self
+    }
+
+    fn options(&self, size: glam::UVec2) -> RenderOptions {
+        RenderOptions {
+            foveation: self
+                .fovea
+                .map(|(gaze, radius)| Foveation::from_gaze(gaze, size, radius)),
+            ..self.options
+        }
+    }
+
+    /// Render the splats as seen by `camera`, in an image of `size` pixels. The alpha channel
+    /// is the coverage of the splats.
+    pub async fn render(
+        &self,
+        splats: &Splats,
+        camera: &Camera,
+        size: glam::UVec2,
+    ) -> anyhow::Result<image::RgbaImage> {
+        let image = splats
+            .0
+            .render_inference(&camera.0, size, false, self.options(size));
+        Self::read_image(image, size).await
+    }
+
+    /// Render the splats like [`Self::render`], to composite them into a scene drawn by
+    /// another renderer, eg. a game engine. `depth` is the view space depth of every pixel of
+    /// that scene, row by row, and splats behind it are hidden. Pixels without anything should
+    /// be `f32::INFINITY`.
+    ///
+    /// The colors are premultiplied by the alpha, so blend the image over the scene with
+    /// premultiplied alpha blending.
+    pub async fn render_over_depth(
+        &self,
+        splats: &Splats,
+        camera: &Camera,
+        size: glam::UVec2,
+        depth: &[f32],
+    ) -> anyhow::Result<image::RgbaImage> {
+        anyhow::ensure!(
+            depth.len() == (size.x * size.y) as usize,
+            "Depth has {} pixels, expected {}",
+            depth.len(),
+            size.x * size.y
+        );
+        let depth = TensorData::new(depth.to_vec(), [size.y as usize, size.x as usize]);
+        let depth = Tensor::<crate::Backend, 2>::from_data(depth, &crate::device());
+        let image =
+            splats
+                .0
+                .render_inference_over_depth(&camera.0, size, depth, false, self.options(size));
+        Self::read_image(image, size).await
+    }
+
+    /// Render the motion vector of every pixel since the previous frame, eg. for an upscaler
+    /// like DLSS or FSR, as `x, y` pairs row by row. A motion vector points to where the
+    /// splats covering the pixel were in the previous frame seen by `prev_camera`, in pixels.
+    ///
+    /// For animated scenes, `prev_splats` are the splats of the previous frame, with the
+    /// same splats in the same order. Without them the splats are static and only the camera
+    /// moves.
+    pub async fn render_motion_vectors(
+        &self,
+        splats: &Splats,
+        prev_splats: Option<&Splats>,
+        camera: &Camera,
+        prev_camera: &Camera,
+        size: glam::UVec2,
+    ) -> anyhow::Result<Vec<f32>> {
+        let motion = brush_render::motion::render_motion_vectors(
+            &splats.0,
+            prev_splats.map(|prev| &prev.0),
+            &camera.0,
+            &prev_camera.0,
+            size,
+        );
+        motion
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+            .map_err(|e| anyhow::anyhow!("{e:?}"))
+    }
+
+    async fn read_image(
+        image: Tensor<crate::Backend, 3>,
+        size: glam::UVec2,
+    ) -> anyhow::Result<image::RgbaImage> {
+        let image = image
+            .slice([0..size.y as usize, 0..size.x as usize, 0..4])
+            .clamp(0.0, 1.0);
+        let pixels = image
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        let image = image::Rgba32FImage::from_raw(size.x, size.y, pixels)
+            .context("Rendered image has the wrong size")?;
+        Ok(image::DynamicImage::ImageRgba32F(image).to_rgba8())
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::Cursor;
+
+use anyhow::Context;
+use brush_dataset::splat_import::load_splat_from_ply;
+use tokio_stream::StreamExt;
+
+use crate::Backend;
+
+/// A set of Gaussian splats on the GPU.
+#[derive(Clone, Debug)]
+pub struct Splats(pub(crate) brush_render::gaussian_splats::Splats<Backend>);
+
+impl Splats {
+    /// Read splats from the bytes of a ply file, eg. as written by [`crate::export::ply`].
+    /// Only the last frame of an animated ply is kept.
+    pub async fn from_ply(data: Vec<u8>) -> anyhow::Result<Self> {
+        let mut stream = std::pin::pin!(load_splat_from_ply(
+            Cursor::new(data),
+            None,
+            crate::device()
+        ));
+        let mut splats = None;
+        while let Some(message) = stream.next().await {
+            splats = Some(message?.splats);
+        }
+        splats.map(Self).context("No splats found in ply")
+    }
+
+    pub fn num_splats(&self) -> usize {
+        self.0.num_splats()
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Context;
+use brush_dataset::scene_loader::SceneLoader;
+use brush_render::gaussian_splats::{RandomSplatsConfig, Splats as InnerSplats};
+use brush_train::train::{seed_region, SplatTrainer, TrainConfig as InnerConfig};
+use burn::{module::AutodiffModule, prelude::Backend};
+use rand::SeedableRng;
+
+use crate::{Dataset, DiffBackend, Splats, TrainConfig};
+
+/// What a training step did.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct StepStats {
+    /// Number of steps trained so far, including this one.
+    pub iter: u32,
+    pub loss: f32,
+    pub num_splats: usize,
+    /// Whether splats were added or pruned in this step.
+    pub refined: bool,
+}
+
+/// Trains splats on the training views of a [`Dataset`], one step at a time.
+pub struct Trainer {
+    trainer: SplatTrainer,
+    loader: SceneLoader<DiffBackend>,
+    splats: InnerSplats<DiffBackend>,
+    config: InnerConfig,
+    iter: u32,
+}
+
+impl Trainer {
+    /// Start training from the initial splats of the dataset, or from random splats within
+    /// the cameras when there are none.
+    pub fn new(dataset: &Dataset, config: TrainConfig) -> anyhow::Result<Self> {
+        let config = config.0;
+        let device = crate::device();
+        let scene = dataset
+            .inner
+            .train
+            .without_blurriest(config.drop_blurry_fraction);
+        anyhow::ensure!(!scene.views.is_empty(), "Dataset has no training views");
+
+        <DiffBackend as Backend>::seed(config.seed);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(config.seed);
+        let splats = match &dataset.init_splats {
+            Some(splats) => splats.clone(),
+            None => {
+                let extent = scene.bounds().extent.length();
+                let bounds = scene.adjusted_bounds(extent * 0.25, extent);
+                InnerSplats::from_random_config(
+                    &RandomSplatsConfig::new(),
+                    bounds,
+                    &mut rng,
+                    &device,
+                )
+            }
+        };
+
+        let splats = match config.refine_region {
+            Some(region) => seed_region(splats, region, config.heal_seeds, &mut rng),
+            None => splats,
+        };
+
+        // The scene extent only needs to be rough, the positions of the cameras are enough.
+        let points: Vec<_> = scene.views.iter().map(|v| v.camera.position).collect();
+        let sampled = match &config.refine_region {
+            Some(region) => scene.seeing(region),
+            None => scene.clone(),
+        };
+        let loader = SceneLoader::new(
+            &sampled,
+            scene.extent(&points),
+            1,
+            config.seed,
+            config.view_sampling.clone(),
+            config.gpu_image_budget_mb,
+            &device,
+        );
+        let mut trainer = SplatTrainer::new(&splats, &config, &device);
+        trainer.prepare_scene(&scene);
+
+        Ok(Self {
+            trainer,
+            loader,
+            splats,
+            config,
+            iter: 0,
+        })
+    }
+
+    /// Train one step. This waits for the GPU to finish the step, to read back the loss.
+    pub async fn step(&mut self) -> anyhow::Result<StepStats> {
+        let batch = self
+            .loader
+            .next_batch(self.config.resolution_level(self.iter))
+            .await;
+        let extent = batch.scene_extent;
+
+        let (splats, stats) = self
+            .trainer
+            .step(self.iter, batch, self.splats.clone())
+            .await;
+        self.loader.report_loss(&stats.gt_views, stats.loss.clone());
+        let (splats, refine) = self
+            .trainer
+            .refine_if_needed(self.iter, splats, extent)
+            .await;
+        self.splats = splats;
+        self.iter += 1;
+
+        let loss = stats
+            .loss
+            .into_data_async()
+            .await
+            .to_vec::<f32>()
+            .ok()
+            .and_then(|l| l.first().copied())
+            .context("Failed to read the loss")?;
+
+        Ok(StepStats {
+            iter: self.iter,
+            loss,
+            num_splats: self.splats.num_splats(),
+            refined: refine.is_some(),
+        })
+    }
+
+    /// Number of steps trained so far.
+    pub fn iter(&self) -> u32 {
+        self.iter
+    }
+
+    /// The splats as trained so far.
+    pub fn splats(&self) -> Splats {
+        Splats(self.splats.valid())
+    }
+}
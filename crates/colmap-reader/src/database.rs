@@ -0,0 +1,539 @@
+// A minimal read-only SQLite reader, just enough to read the tables of a COLMAP database.
+// See https://www.sqlite.org/fileformat.html for the file format.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Seek, SeekFrom};
+
+// Pair ids encode two image ids, see `image_ids_to_pair_id` in COLMAP.
+const MAX_IMAGE_ID: i64 = 2147483647;
+
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseImage {
+    pub name: String,
+    pub camera_id: i64,
+    /// Number of detected keypoints.
+    pub num_keypoints: u64,
+    /// Number of geometrically verified matches with any other image.
+    pub num_matches: u64,
+    /// Number of other images this image has verified matches with.
+    pub num_matched_images: u64,
+}
+
+impl DatabaseImage {
+    /// Fraction of the keypoints that has a verified match, summed over all matched images.
+    pub fn match_ratio(&self) -> f32 {
+        if self.num_keypoints == 0 {
+            0.0
+        } else {
+            self.num_matches as f32 / self.num_keypoints as f32
+        }
+    }
+}
+
+/// Feature & match statistics from a COLMAP `database.db`.
+#[derive(Debug, Clone, Default)]
+pub struct Database {
+    pub images: HashMap<i64, DatabaseImage>,
+    /// Number of image pairs with geometrically verified matches.
+    pub num_verified_pairs: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Null,
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Blob,
+}
+
+impl Value {
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(i) => Some(*i),
+            Self::Float(f) => Some(*f as i64),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid SQLite file: {msg}"),
+    )
+}
+
+fn read_varint(data: &[u8]) -> io::Result<(i64, usize)> {
+    let mut value: u64 = 0;
+    for i in 0..9 {
+        let byte = *data.get(i).ok_or_else(|| invalid("truncated varint"))?;
+        if i == 8 {
+            return Ok((((value << 8) | byte as u64) as i64, 9));
+        }
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            return Ok((value as i64, i + 1));
+        }
+    }
+    unreachable!()
+}
+
+fn be_int(bytes: &[u8]) -> i64 {
+    // Sign extend from the first byte.
+    let mut value = if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+        -1i64
+    } else {
+        0
+    };
+    for &b in bytes {
+        value = (value << 8) | b as i64;
+    }
+    value
+}
+
+// Interior pages deeper than this are treated as corrupt. SQLite itself never builds b-trees
+// deeper than 20 levels.
+const MAX_TREE_DEPTH: usize = 32;
+
+fn be_u16(bytes: &[u8], offset: usize) -> io::Result<usize> {
+    let bytes = bytes
+        .get(offset..offset + 2)
+        .ok_or_else(|| invalid("truncated page"))?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+fn be_u32(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    let bytes = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| invalid("truncated page"))?;
+    Ok(u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+}
+
+// Reads pages on demand, so only the b-tree pages of the tables that are visited are read.
+struct SqliteFile<R> {
+    reader: R,
+    page_size: usize,
+    usable_size: usize,
+    num_pages: u32,
+}
+
+impl<R: Read + Seek> SqliteFile<R> {
+    fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 100];
+        reader.seek(SeekFrom::Start(0))?;
+        reader
+            .read_exact(&mut header)
+            .map_err(|_e| invalid("missing header"))?;
+        if &header[0..16] != b"SQLite format 3\0" {
+            return Err(invalid("missing header"));
+        }
+        let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+            1 => 65536,
+            size => size as usize,
+        };
+        if !page_size.is_power_of_two() || page_size < 512 {
+            return Err(invalid("bad page size"));
+        }
+        // The usable size is at least 480 bytes, which the payload calculations rely on.
+        let usable_size = page_size - header[20] as usize;
+        if usable_size < 480 {
+            return Err(invalid("too many reserved bytes"));
+        }
+        let len = reader.seek(SeekFrom::End(0))?;
+        let num_pages =
+            u32::try_from(len / page_size as u64).map_err(|_e| invalid("too many pages"))?;
+        Ok(Self {
+            reader,
+            page_size,
+            usable_size,
+            num_pages,
+        })
+    }
+
+    fn page(&mut self, page_num: u32) -> io::Result<Vec<u8>> {
+        if page_num == 0 || page_num > self.num_pages {
+            return Err(invalid("page out of bounds"));
+        }
+        let start = (page_num as u64 - 1) * self.page_size as u64;
+        let mut page = vec![0; self.page_size];
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.reader.read_exact(&mut page)?;
+        Ok(page)
+    }
+
+    // Read the payload of a cell, following overflow pages until at least `needed` bytes are read.
+    fn payload(&mut self, cell: &[u8], payload_size: usize, needed: usize) -> io::Result<Vec<u8>> {
+        let u = self.usable_size;
+        if payload_size as u64 > self.num_pages as u64 * u as u64 {
+            return Err(invalid("payload larger than the file"));
+        }
+        let max_local = u - 35;
+        let local = if payload_size <= max_local {
+            payload_size
+        } else {
+            let min_local = (u - 12) * 32 / 255 - 23;
+            let k = min_local + (payload_size - min_local) % (u - 4);
+            if k <= max_local {
+                k
+            } else {
+                min_local
+            }
+        };
+
+        let mut payload = cell
+            .get(..local)
+            .ok_or_else(|| invalid("truncated cell"))?
+            .to_vec();
+
+        if local < payload_size {
+            let mut next = be_u32(cell, local)?;
+            // A chain can't have more pages than the file, otherwise it loops.
+            let mut pages_left = self.num_pages;
+            while next != 0 && payload.len() < needed.min(payload_size) {
+                pages_left = pages_left
+                    .checked_sub(1)
+                    .ok_or_else(|| invalid("overflow page loop"))?;
+                let page = self.page(next)?;
+                next = be_u32(&page, 0)?;
+                let remaining = payload_size - payload.len();
+                payload.extend_from_slice(
+                    page.get(4..4 + remaining.min(u - 4))
+                        .ok_or_else(|| invalid("truncated overflow page"))?,
+                );
+            }
+        }
+        Ok(payload)
+    }
+
+    // Parse the first `num_columns` columns of a record.
+    fn record(
+        &mut self,
+        cell: &[u8],
+        payload_size: usize,
+        num_columns: usize,
+    ) -> io::Result<Vec<Value>> {
+        // First read just the local part to find out how much of the record is needed.
+        let local = self.payload(cell, payload_size, 0)?;
+        let (header_size, mut offset) = read_varint(&local)?;
+        let header_size =
+            usize::try_from(header_size).map_err(|_e| invalid("bad record header"))?;
+
+        let mut serial_types = vec![];
+        while offset < header_size && serial_types.len() < num_columns {
+            let rest = local
+                .get(offset..)
+                .ok_or_else(|| invalid("truncated record header"))?;
+            let (serial, len) = read_varint(rest)?;
+            serial_types.push(serial);
+            offset += len;
+        }
+
+        let serial_size = |serial: i64| -> usize {
+            match serial {
+                0 | 8 | 9 => 0,
+                1 => 1,
+                2 => 2,
+                3 => 3,
+                4 => 4,
+                5 => 6,
+                6 | 7 => 8,
+                s if s >= 12 => ((s - 12) / 2) as usize,
+                _ => 0,
+            }
+        };
+
+        // Blobs aren't read, so only need the data up to the last non blob column.
+        let mut needed = header_size;
+        let mut end = header_size;
+        for &serial in &serial_types {
+            end = end.saturating_add(serial_size(serial));
+            if !(serial >= 12 && serial % 2 == 0) {
+                needed = end;
+            }
+        }
+        if needed > payload_size {
+            return Err(invalid("record larger than its payload"));
+        }
+        let payload = if needed > local.len() {
+            self.payload(cell, payload_size, needed)?
+        } else {
+            local
+        };
+
+        let mut values = vec![];
+        let mut pos = header_size;
+        for serial in serial_types {
+            let size = serial_size(serial);
+            let is_blob = serial >= 12 && serial % 2 == 0;
+            let bytes = if is_blob {
+                &[][..]
+            } else {
+                payload
+                    .get(pos..pos + size)
+                    .ok_or_else(|| invalid("truncated record"))?
+            };
+            values.push(match serial {
+                0 => Value::Null,
+                1..=6 => Value::Int(be_int(bytes)),
+                7 => Value::Float(f64::from_bits(be_int(bytes) as u64)),
+                8 => Value::Int(0),
+                9 => Value::Int(1),
+                s if s >= 12 && s % 2 == 0 => Value::Blob,
+                s if s >= 13 => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+                _ => return Err(invalid("reserved serial type")),
+            });
+            pos = pos.saturating_add(size);
+        }
+        Ok(values)
+    }
+
+    // Visit all rows of the table b-tree at `root`, with their rowid and first `num_columns` columns.
+    fn visit_table(
+        &mut self,
+        root: u32,
+        num_columns: usize,
+        visit: &mut impl FnMut(i64, Vec<Value>),
+    ) -> io::Result<()> {
+        self.visit_page(root, num_columns, visit, &mut HashSet::new(), 0)
+    }
+
+    // Visit the rows below a page of a table b-tree. Pages are only visited once, so
+    // corrupt child pointers can't loop.
+    fn visit_page(
+        &mut self,
+        page_num: u32,
+        num_columns: usize,
+        visit: &mut impl FnMut(i64, Vec<Value>),
+        visited: &mut HashSet<u32>,
+        depth: usize,
+    ) -> io::Result<()> {
+        if depth > MAX_TREE_DEPTH {
+            return Err(invalid("b-tree too deep"));
+        }
+        if !visited.insert(page_num) {
+            return Err(invalid("b-tree page visited twice"));
+        }
+
+        let page = self.page(page_num)?;
+        // The first page starts with the file header.
+        let header_start = if page_num == 1 { 100 } else { 0 };
+
+        let page_type = *page
+            .get(header_start)
+            .ok_or_else(|| invalid("truncated page"))?;
+        let num_cells = be_u16(&page, header_start + 3)?;
+        let header_size = if page_type == 5 { 12 } else { 8 };
+
+        for i in 0..num_cells {
+            let cell_offset = be_u16(&page, header_start + header_size + i * 2)?;
+            let cell = page
+                .get(cell_offset..)
+                .ok_or_else(|| invalid("cell out of bounds"))?;
+
+            match page_type {
+                // Interior table page.
+                5 => {
+                    let child = be_u32(cell, 0)?;
+                    self.visit_page(child, num_columns, visit, visited, depth + 1)?;
+                }
+                // Leaf table page.
+                13 => {
+                    let (payload_size, len_a) = read_varint(cell)?;
+                    let (rowid, len_b) =
+                        read_varint(cell.get(len_a..).ok_or_else(|| invalid("truncated cell"))?)?;
+                    let payload_size =
+                        usize::try_from(payload_size).map_err(|_e| invalid("bad payload size"))?;
+                    let record = cell
+                        .get(len_a + len_b..)
+                        .ok_or_else(|| invalid("truncated cell"))?;
+                    let values = self.record(record, payload_size, num_columns)?;
+                    visit(rowid, values);
+                }
+                _ => return Err(invalid("unexpected page type")),
+            }
+        }
+
+        if page_type == 5 {
+            let right = be_u32(&page, header_start + 8)?;
+            self.visit_page(right, num_columns, visit, visited, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn table_root(&mut self, name: &str) -> io::Result<Option<u32>> {
+        let mut root = None;
+        // sqlite_master has columns (type, name, tbl_name, rootpage, sql).
+        self.visit_table(1, 4, &mut |_, values| {
+            if let (Some(Value::Text(ty)), Some(Value::Text(table))) =
+                (values.first(), values.get(1))
+            {
+                if ty == "table" && table == name {
+                    root = values
+                        .get(3)
+                        .and_then(Value::as_int)
+                        .and_then(|r| u32::try_from(r).ok());
+                }
+            }
+        })?;
+        Ok(root)
+    }
+}
+
+// A row count column, negative counts of corrupt rows count as 0.
+fn count(value: Option<&Value>) -> u64 {
+    value
+        .and_then(Value::as_int)
+        .and_then(|count| u64::try_from(count).ok())
+        .unwrap_or(0)
+}
+
+/// Read the feature and match statistics from a COLMAP `database.db`.
+///
+/// Only the pages of the tables that are needed are read. Descriptors and the keypoint and
+/// match data itself aren't read, so this is fast even for databases of several gigabytes.
+pub fn read_database(reader: impl Read + Seek) -> io::Result<Database> {
+    let mut file = SqliteFile::new(reader)?;
+    let root = |file: &mut SqliteFile<_>, name: &str| -> io::Result<u32> {
+        file.table_root(name)?
+            .ok_or_else(|| invalid(&format!("no {name} table, not a COLMAP database")))
+    };
+
+    let mut db = Database::default();
+
+    // images (image_id, name, camera_id, ...). The id is an alias of the rowid.
+    let images = root(&mut file, "images")?;
+    file.visit_table(images, 3, &mut |rowid, values| {
+        let name = match values.get(1) {
+            Some(Value::Text(name)) => name.clone(),
+            _ => String::new(),
+        };
+        let camera_id = values.get(2).and_then(Value::as_int).unwrap_or(0);
+        db.images.insert(
+            rowid,
+            DatabaseImage {
+                name,
+                camera_id,
+                ..Default::default()
+            },
+        );
+    })?;
+
+    // keypoints (image_id, rows, cols, data).
+    let keypoints = root(&mut file, "keypoints")?;
+    file.visit_table(keypoints, 2, &mut |rowid, values| {
+        if let Some(image) = db.images.get_mut(&rowid) {
+            image.num_keypoints = count(values.get(1));
+        }
+    })?;
+
+    // two_view_geometries (pair_id, rows, cols, data, config, ...), rows is the number of
+    // inlier matches.
+    let mut pairs = vec![];
+    let geometries = root(&mut file, "two_view_geometries")?;
+    file.visit_table(geometries, 2, &mut |pair_id, values| {
+        let num_matches = count(values.get(1));
+        if num_matches > 0 {
+            pairs.push((pair_id, num_matches));
+        }
+    })?;
+
+    for (pair_id, num_matches) in pairs {
+        let image_id2 = pair_id % MAX_IMAGE_ID;
+        let image_id1 = (pair_id - image_id2) / MAX_IMAGE_ID;
+        for id in [image_id1, image_id2] {
+            if let Some(image) = db.images.get_mut(&id) {
+                image.num_matches = image.num_matches.saturating_add(num_matches);
+                image.num_matched_images += 1;
+            }
+        }
+        db.num_verified_pairs += 1;
+    }
+
+    Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // A database with 40 images made by test_data/make_database.py. Its small pages give the
+    // tables interior pages, and some keypoints are on overflow pages.
+    const DATABASE: &[u8] = include_bytes!("../test_data/database.db");
+    const PAGE_SIZE: usize = 1024;
+
+    #[test]
+    fn read_statistics() {
+        let db = read_database(Cursor::new(DATABASE)).expect("Failed to read database");
+        assert_eq!(db.images.len(), 40);
+        // Image i matches image i + 1 with 5i inliers, except for 1-2, and 1 also matches 3.
+        assert_eq!(db.num_verified_pairs, 39);
+
+        let image = &db.images[&3];
+        assert!(image.name.starts_with("frames/capture_0003_"));
+        assert_eq!(image.camera_id, 1);
+        assert_eq!(image.num_keypoints, 3);
+        assert_eq!(image.num_matches, 10 + 15 + 7);
+        assert_eq!(image.num_matched_images, 3);
+
+        assert_eq!(db.images[&1].num_matches, 7);
+        assert_eq!(db.images[&1].num_matched_images, 1);
+        // Stored on overflow pages.
+        assert_eq!(db.images[&10].num_keypoints, 200);
+        assert_eq!(db.images[&40].num_matches, 5 * 39);
+    }
+
+    #[test]
+    fn truncated_database_does_not_panic() {
+        for len in (0..DATABASE.len()).step_by(97) {
+            let _ = read_database(Cursor::new(&DATABASE[..len]));
+        }
+        assert!(read_database(Cursor::new(&DATABASE[..50])).is_err());
+        assert!(read_database(Cursor::new(&DATABASE[..PAGE_SIZE * 5])).is_err());
+    }
+
+    #[test]
+    fn corrupt_database_does_not_panic() {
+        for pos in (0..DATABASE.len()).step_by(7) {
+            for value in [0x00, 0x7f, 0xff] {
+                let mut data = DATABASE.to_vec();
+                data[pos] = value;
+                let _ = read_database(Cursor::new(data));
+            }
+        }
+    }
+
+    #[test]
+    fn bad_page_size() {
+        let mut data = DATABASE.to_vec();
+        // 512 byte pages with 255 reserved bytes leave too little usable space.
+        data[16..18].copy_from_slice(&512u16.to_be_bytes());
+        data[20] = 255;
+        assert!(read_database(Cursor::new(data)).is_err());
+
+        let mut data = DATABASE.to_vec();
+        data[16..18].copy_from_slice(&1000u16.to_be_bytes());
+        assert!(read_database(Cursor::new(data)).is_err());
+    }
+
+    #[test]
+    fn cyclic_child_pointer() {
+        let mut file = SqliteFile::new(Cursor::new(DATABASE)).expect("Valid database");
+        let root = file
+            .table_root("images")
+            .expect("Valid database")
+            .expect("Has an images table");
+        let start = (root as usize - 1) * PAGE_SIZE;
+        assert_eq!(
+            DATABASE[start], 5,
+            "images should have an interior root page"
+        );
+
+        // Point the right child of the root page back to itself.
+        let mut data = DATABASE.to_vec();
+        data[start + 8..start + 12].copy_from_slice(&root.to_be_bytes());
+        assert!(read_database(Cursor::new(data)).is_err());
+    }
+}
@@ -1,12 +1,15 @@
 #![allow(unused)]
 
 use byteorder::{LittleEndian, ReadBytesExt};
+use rayon::prelude::*;
 use std::collections::HashMap;
 use std::io::{self, BufRead, Read};
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncReadExt;
 use tokio::io::{AsyncBufRead, AsyncRead};
 
+pub mod database;
+
 // TODO: Really these should each hold their respective params but bit of an annoying refactor. We just need
 // basic params.
 #[derive(Debug, Clone)]
@@ -181,8 +184,12 @@ async fn read_cameras_text<R: AsyncRead + Unpin + Send>(
         }
 
         let id = parse(parts[0])?;
-        let model = CameraModel::from_name(parts[1])
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid camera model"))?;
+        let model = CameraModel::from_name(parts[1]).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Unsupported camera model {}", parts[1]),
+            )
+        })?;
 
         let width = parse(parts[2])?;
         let height = parse(parts[3])?;
@@ -226,8 +233,12 @@ async fn read_cameras_binary<R: AsyncRead + Unpin + Send>(
         let width = reader.read_u64_le().await?;
         let height = reader.read_u64_le().await?;
 
-        let model = CameraModel::from_id(model_id)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid camera model"))?;
+        let model = CameraModel::from_id(model_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Unsupported camera model id {model_id}"),
+            )
+        })?;
 
         let num_params = model.num_params();
         let mut params = Vec::with_capacity(num_params);
@@ -347,7 +358,7 @@ async fn read_images_binary<R: AsyncBufRead + Unpin + Send>(
                 reader.read_f64_le().await? as f32,
                 reader.read_f64_le().await? as f32,
             ));
-            point3d_ids.push(reader.read_i64().await?);
+            point3d_ids.push(reader.read_i64_le().await?);
         }
 
         images.insert(
@@ -366,108 +377,142 @@ async fn read_images_binary<R: AsyncBufRead + Unpin + Send>(
     Ok(images)
 }
 
-async fn read_points3d_text<R: AsyncRead + Unpin + Send>(
-    mut reader: R,
-) -> io::Result<HashMap<i64, Point3D>> {
-    let mut points3d = HashMap::new();
-    let mut buf_reader = tokio::io::BufReader::new(reader);
-    let mut line = String::new();
+// Points are parsed in parallel in chunks of this many points.
+const POINTS_CHUNK: usize = 16384;
 
-    while buf_reader.read_line(&mut line).await? > 0 {
-        if line.starts_with('#') {
-            line.clear();
-            continue;
-        }
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
 
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid point3D data",
-            ));
-        }
+fn parse_point3d_line(line: &str) -> io::Result<(i64, Point3D)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 8 {
+        return Err(invalid_data("Invalid point3D data"));
+    }
 
-        let id: i64 = parse(parts[0])?;
-        let xyz = glam::Vec3::new(parse(parts[1])?, parse(parts[2])?, parse(parts[3])?);
-        let rgb = [
-            parse::<u8>(parts[4])?,
-            parse::<u8>(parts[5])?,
-            parse::<u8>(parts[6])?,
-        ];
-        let error: f64 = parse(parts[7])?;
+    let id: i64 = parse(parts[0])?;
+    let xyz = glam::Vec3::new(parse(parts[1])?, parse(parts[2])?, parse(parts[3])?);
+    let rgb = [
+        parse::<u8>(parts[4])?,
+        parse::<u8>(parts[5])?,
+        parse::<u8>(parts[6])?,
+    ];
+    let error: f64 = parse(parts[7])?;
+
+    let mut image_ids = Vec::new();
+    let mut point2d_idxs = Vec::new();
+
+    for chunk in parts[8..].chunks(2) {
+        if chunk.len() < 2 {
+            return Err(invalid_data("Invalid point3D track data"));
+        }
+        image_ids.push(parse(chunk[0])?);
+        point2d_idxs.push(parse(chunk[1])?);
+    }
 
-        let mut image_ids = Vec::new();
-        let mut point2d_idxs = Vec::new();
+    Ok((
+        id,
+        Point3D {
+            xyz,
+            rgb,
+            error,
+            image_ids,
+            point2d_idxs,
+        },
+    ))
+}
 
-        for chunk in parts[8..].chunks(2) {
-            if chunk.len() < 2 {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Invalid point3D track data",
-                ));
-            }
-            image_ids.push(parse(chunk[0])?);
-            point2d_idxs.push(parse(chunk[1])?);
-        }
+async fn read_points3d_text<R: AsyncRead + Unpin + Send>(
+    mut reader: R,
+) -> io::Result<HashMap<i64, Point3D>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text).await?;
+
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .collect();
+
+    lines
+        .par_chunks(POINTS_CHUNK)
+        .map(|chunk| chunk.iter().map(|line| parse_point3d_line(line)).collect())
+        .collect::<io::Result<Vec<Vec<_>>>>()
+        .map(|chunks| chunks.into_iter().flatten().collect())
+}
 
-        points3d.insert(
-            id,
-            Point3D {
-                xyz,
-                rgb,
-                error,
-                image_ids,
-                point2d_idxs,
-            },
-        );
-        line.clear();
+// Size of a binary point without its track: id, xyz, rgb and error.
+const POINT3D_SIZE: usize = 8 + 3 * 8 + 3 + 8;
+
+fn parse_point3d_binary(mut data: &[u8]) -> io::Result<(i64, Point3D)> {
+    let point3d_id = data.read_i64::<LittleEndian>()?;
+    let xyz = glam::Vec3::new(
+        data.read_f64::<LittleEndian>()? as f32,
+        data.read_f64::<LittleEndian>()? as f32,
+        data.read_f64::<LittleEndian>()? as f32,
+    );
+    let rgb = [data.read_u8()?, data.read_u8()?, data.read_u8()?];
+    let error = data.read_f64::<LittleEndian>()?;
+
+    let track_length = data.read_u64::<LittleEndian>()? as usize;
+    let mut image_ids = Vec::with_capacity(track_length);
+    let mut point2d_idxs = Vec::with_capacity(track_length);
+
+    for _ in 0..track_length {
+        image_ids.push(data.read_i32::<LittleEndian>()?);
+        point2d_idxs.push(data.read_i32::<LittleEndian>()?);
     }
 
-    Ok(points3d)
+    Ok((
+        point3d_id,
+        Point3D {
+            xyz,
+            rgb,
+            error,
+            image_ids,
+            point2d_idxs,
+        },
+    ))
 }
 
 async fn read_points3d_binary<R: AsyncRead + Unpin + Send>(
     mut reader: R,
 ) -> io::Result<HashMap<i64, Point3D>> {
-    let mut points3d = HashMap::new();
-    let num_points = reader.read_u64_le().await?;
-
+    let mut data = vec![];
+    reader.read_to_end(&mut data).await?;
+
+    let num_points = data
+        .get(..8)
+        .ok_or_else(|| invalid_data("Invalid points3D file"))?
+        .read_u64::<LittleEndian>()? as usize;
+
+    // Points have a variable size, so first find where each point starts. This only reads
+    // the track lengths, the points are parsed in parallel after.
+    let mut records = Vec::with_capacity(num_points);
+    let mut offset = 8;
     for _ in 0..num_points {
-        let point3d_id = reader.read_i64().await?;
-        let xyz = glam::Vec3::new(
-            reader.read_f64_le().await? as f32,
-            reader.read_f64_le().await? as f32,
-            reader.read_f64_le().await? as f32,
-        );
-        let rgb = [
-            reader.read_u8().await?,
-            reader.read_u8().await?,
-            reader.read_u8().await?,
-        ];
-        let error = reader.read_f64_le().await?;
-
-        let track_length = reader.read_u64_le().await?;
-        let mut image_ids = Vec::with_capacity(track_length as usize);
-        let mut point2d_idxs = Vec::with_capacity(track_length as usize);
-
-        for _ in 0..track_length {
-            image_ids.push(reader.read_i32_le().await?);
-            point2d_idxs.push(reader.read_i32_le().await?);
+        let track_at = offset + POINT3D_SIZE;
+        let track_length = data
+            .get(track_at..track_at + 8)
+            .ok_or_else(|| invalid_data("Truncated points3D file"))?
+            .read_u64::<LittleEndian>()? as usize;
+        let end = track_at + 8 + track_length * 8;
+        if end > data.len() {
+            return Err(invalid_data("Truncated points3D file"));
         }
-
-        points3d.insert(
-            point3d_id,
-            Point3D {
-                xyz,
-                rgb,
-                error,
-                image_ids,
-                point2d_idxs,
-            },
-        );
+        records.push(offset..end);
+        offset = end;
     }
 
-    Ok(points3d)
+    records
+        .par_chunks(POINTS_CHUNK)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|range| parse_point3d_binary(&data[range.clone()]))
+                .collect()
+        })
+        .collect::<io::Result<Vec<Vec<_>>>>()
+        .map(|chunks| chunks.into_iter().flatten().collect())
 }
 
 pub async fn read_cameras<R: AsyncRead + Unpin + Send>(
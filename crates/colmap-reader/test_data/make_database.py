@@ -0,0 +1,35 @@
+# Generates database.db, a small COLMAP database for the tests of database.rs.
+# Run from this folder with `python make_database.py`.
+import sqlite3, struct, os
+if os.path.exists("database.db"): os.remove("database.db")
+con = sqlite3.connect("database.db")
+# Small pages so the fixture has interior table pages and overflow pages.
+con.execute("PRAGMA page_size = 1024")
+con.executescript("""
+CREATE TABLE IF NOT EXISTS cameras (camera_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, model INTEGER NOT NULL, width INTEGER NOT NULL, height INTEGER NOT NULL, params BLOB, prior_focal_length INTEGER NOT NULL);
+CREATE TABLE IF NOT EXISTS descriptors (image_id INTEGER PRIMARY KEY NOT NULL, rows INTEGER NOT NULL, cols INTEGER NOT NULL, data BLOB, FOREIGN KEY(image_id) REFERENCES images(image_id) ON DELETE CASCADE);
+CREATE TABLE IF NOT EXISTS images (image_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, name TEXT NOT NULL UNIQUE, camera_id INTEGER NOT NULL, CONSTRAINT image_id_check CHECK(image_id >= 0 and image_id < 2147483647), FOREIGN KEY(camera_id) REFERENCES cameras(camera_id));
+CREATE TABLE IF NOT EXISTS keypoints (image_id INTEGER PRIMARY KEY NOT NULL, rows INTEGER NOT NULL, cols INTEGER NOT NULL, data BLOB, FOREIGN KEY(image_id) REFERENCES images(image_id) ON DELETE CASCADE);
+CREATE TABLE IF NOT EXISTS matches (pair_id INTEGER PRIMARY KEY NOT NULL, rows INTEGER NOT NULL, cols INTEGER NOT NULL, data BLOB);
+CREATE TABLE IF NOT EXISTS two_view_geometries (pair_id INTEGER PRIMARY KEY NOT NULL, rows INTEGER NOT NULL, cols INTEGER NOT NULL, data BLOB, config INTEGER NOT NULL, F BLOB, E BLOB, H BLOB, qvec BLOB, tvec BLOB);
+""")
+con.execute("INSERT INTO cameras VALUES (1, 1, 640, 480, ?, 0)", (struct.pack("4d", 500, 500, 320, 240),))
+N = 40
+for i in range(1, N + 1):
+    # Long names spread the images over several leaf pages.
+    con.execute("INSERT INTO images VALUES (?, ?, 1)", (i, f"frames/capture_{i:04d}_" + "x" * 40 + ".jpg"))
+    # Only every tenth image has keypoints larger than a page, stored on overflow pages.
+    rows = 200 if i % 10 == 0 else i
+    con.execute("INSERT INTO keypoints VALUES (?, ?, 2, ?)", (i, rows, bytes(rows * 2 * 4)))
+    con.execute("INSERT INTO descriptors VALUES (?, ?, 128, ?)", (i, rows, b""))
+def pair_id(a, b):
+    if a > b: a, b = b, a
+    return a * 2147483647 + b
+# Each image matches the next one except 1-2 without inliers, and image 1 also matches image 3.
+for i in range(1, N):
+    n = 0 if i == 1 else 5 * i
+    con.execute("INSERT INTO two_view_geometries VALUES (?, ?, 2, NULL, 2, NULL, NULL, NULL, NULL, NULL)", (pair_id(i, i + 1), n))
+con.execute("INSERT INTO two_view_geometries VALUES (?, 7, 2, NULL, 2, NULL, NULL, NULL, NULL, NULL)", (pair_id(3, 1),))
+con.commit()
+con.execute("VACUUM")
+con.close()
@@ -0,0 +1,67 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+// The data is first written next to the target, so the rename stays on the same file system.
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or_else(|| "file".into(), |n| n.to_string_lossy());
+    path.with_file_name(format!(".{name}.partial"))
+}
+
+fn write_partial(partial: &Path, path: &Path, data: &[u8], sync: bool) -> std::io::Result<()> {
+    let mut file = File::create(partial)?;
+    file.write_all(data)?;
+    if sync {
+        file.sync_all()?;
+    }
+    drop(file);
+    std::fs::rename(partial, path)?;
+
+    // The rename itself only survives a power loss once the folder is synced. Folders can't
+    // be opened as files on Windows, where renames are already durable.
+    #[cfg(unix)]
+    if sync {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            File::open(parent)?.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a file so it's either fully written or not changed at all.
+///
+/// The data is written to a temporary file next to `path`, which is then renamed over it. If
+/// the app is killed halfway through, the old file is left as it was, instead of a truncated
+/// file that fails to load. With `sync` the data is also flushed to the disk before returning,
+/// which is slower, but survives a power loss too.
+pub fn write_atomic(path: &Path, data: &[u8], sync: bool) -> std::io::Result<()> {
+    let partial = partial_path(path);
+    let result = write_partial(&partial, path, data, sync);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{partial_path, write_atomic};
+
+    #[test]
+    fn replaces_file() {
+        let dir = std::env::temp_dir().join(format!("rrfd_atomic_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Failed to create test folder");
+        let path = dir.join("model.ply");
+
+        write_atomic(&path, b"old", false).expect("Failed to write");
+        write_atomic(&path, b"new", true).expect("Failed to write");
+        assert_eq!(std::fs::read(&path).expect("Failed to read"), b"new");
+        assert!(!partial_path(&path).exists());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,11 +1,14 @@
 #[cfg(target_os = "android")]
 pub mod android;
+mod atomic;
 
 #[allow(unused)]
 use anyhow::Context;
 use anyhow::Result;
 use std::path::PathBuf;
 
+pub use atomic::write_atomic;
+
 pub enum FileHandle {
     #[cfg(not(target_os = "android"))]
     Rfd(rfd::FileHandle),
@@ -14,9 +17,12 @@ pub enum FileHandle {
 }
 
 impl FileHandle {
+    /// Write the file. On desktop the file is replaced atomically, see [`write_atomic`].
     pub async fn write(&self, data: &[u8]) -> std::io::Result<()> {
         match self {
-            #[cfg(not(target_os = "android"))]
+            #[cfg(all(not(target_os = "android"), not(target_family = "wasm")))]
+            Self::Rfd(file_handle) => write_atomic(file_handle.path(), data, false),
+            #[cfg(target_family = "wasm")]
             Self::Rfd(file_handle) => file_handle.write(data).await,
             #[cfg(target_os = "android")]
             Self::Android(_) => {
@@ -26,6 +32,21 @@ impl FileHandle {
         }
     }
 
+    /// Path of the picked file. Only known on desktop platforms.
+    pub fn path(&self) -> Option<PathBuf> {
+        #[cfg(all(not(target_os = "android"), not(target_family = "wasm")))]
+        {
+            let Self::Rfd(file_handle) = self;
+            Some(file_handle.path().to_path_buf())
+        }
+
+        #[cfg(any(target_os = "android", target_family = "wasm"))]
+        {
+            let _ = self;
+            None
+        }
+    }
+
     pub async fn read(mut self) -> Vec<u8> {
         match &mut self {
             #[cfg(not(target_os = "android"))]
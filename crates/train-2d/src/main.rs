@@ -1,14 +1,13 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use std::sync::Arc;
-
 use brush_render::{
     bounding_box::BoundingBox,
     camera::{focal_to_fov, fov_to_focal, Camera},
     gaussian_splats::{RandomSplatsConfig, Splats},
+    RenderOptions,
 };
 use brush_train::{
-    image::image_to_tensor,
+    image::{image_sharpness, image_to_tensor},
     scene::SceneView,
     train::{SceneBatch, SplatTrainer, TrainConfig},
 };
@@ -57,7 +56,8 @@ fn spawn_train_loop(
 
         // One batch of training data, it's the same every step so can just cosntruct it once.
         let batch = SceneBatch {
-            gt_images: image_to_tensor(&view.image, &device).unsqueeze(),
+            gt_images: image_to_tensor(&view.image.load(), &device).unsqueeze(),
+            loss_weights: None,
             gt_views: vec![view],
             scene_extent: 1.0,
         };
@@ -129,13 +129,19 @@ impl App {
         let view = SceneView {
             name: "crabby".to_owned(),
             camera,
-            image: Arc::new(image),
+            sharpness: image_sharpness(&image),
+            image: image.into(),
+            mask: None,
+            camera_id: 0,
+            capture_id: 0,
+            features: None,
+            depth: None,
         };
         let (sender, receiver) = tokio::sync::mpsc::channel(32);
 
         let color_img = egui::ColorImage::from_rgb(
             [view.image.width() as usize, view.image.height() as usize],
-            &view.image.to_rgb8().into_vec(),
+            &view.image.load().to_rgb8().into_vec(),
         );
         let handle =
             cc.egui_ctx
@@ -171,10 +177,11 @@ impl eframe::App for App {
 
             let image = &self.view.image;
 
-            let (img, _) = msg.splats.render(
+            let img = msg.splats.render_inference(
                 &self.view.camera,
                 glam::uvec2(image.width(), image.height()),
                 true,
+                RenderOptions::default(),
             );
 
             let renderer = &frame